@@ -0,0 +1,37 @@
+use std::path::Path;
+use std::process::Command;
+
+// Embed the git commit as GIT_SHA for the health endpoints. An explicit
+// GIT_SHA in the environment wins (Docker builds don't ship .git).
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+
+    let git_dir = Path::new("../../.git");
+    if git_dir.join("HEAD").exists() {
+        println!("cargo:rerun-if-changed=../../.git/HEAD");
+        if let Ok(head) = std::fs::read_to_string(git_dir.join("HEAD")) {
+            if let Some(reference) = head.strip_prefix("ref: ") {
+                let ref_path = git_dir.join(reference.trim());
+                if ref_path.exists() {
+                    println!("cargo:rerun-if-changed={}", ref_path.display());
+                }
+            }
+        }
+    }
+
+    let sha = std::env::var("GIT_SHA")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .or_else(|| {
+            Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()
+                .filter(|o| o.status.success())
+                .and_then(|o| String::from_utf8(o.stdout).ok())
+                .map(|s| s.trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=GIT_SHA={}", sha);
+}
@@ -26,11 +26,11 @@ m AS (
 )
 -- Seed companies and contacts
 INSERT INTO companies (tenant_id, name, website, email, phone, address, tags)
-SELECT tenant_id, 'Wayne Enterprises', 'https://wayne.example', 'contact@wayne.example', '+62-21-555-1001', '{"street":"Jl. Mawar 1","city":"Jakarta","country":"ID"}', ARRAY['partner','vip'] FROM m
+SELECT tenant_id, 'Wayne Enterprises', 'https://wayne.example', 'contact@wayne.example', '+62-21-555-1001', '{"street":"Jl. Mawar 1","city":"Jakarta","country":"ID"}'::jsonb, ARRAY['partner','vip'] FROM m
 UNION ALL
-SELECT tenant_id, 'Stark Industries', 'https://stark.example', 'info@stark.example', '+62-21-555-1002', '{"street":"Jl. Melati 2","city":"Bandung","country":"ID"}', ARRAY['lead'] FROM m
+SELECT tenant_id, 'Stark Industries', 'https://stark.example', 'info@stark.example', '+62-21-555-1002', '{"street":"Jl. Melati 2","city":"Bandung","country":"ID"}'::jsonb, ARRAY['lead'] FROM m
 UNION ALL
-SELECT tenant_id, 'Oscorp', 'https://oscorp.example', 'hello@oscorp.example', '+62-21-555-1003', '{"street":"Jl. Anggrek 3","city":"Surabaya","country":"ID"}', ARRAY['supplier'] FROM m;
+SELECT tenant_id, 'Oscorp', 'https://oscorp.example', 'hello@oscorp.example', '+62-21-555-1003', '{"street":"Jl. Anggrek 3","city":"Surabaya","country":"ID"}'::jsonb, ARRAY['supplier'] FROM m;
 
 -- Link contacts to first company
 WITH c AS (
@@ -62,7 +62,7 @@ INSERT INTO purchase_orders (tenant_id, po_number, vendor_id, order_date, expect
 (current_setting('app.current_tenant_id', true)::UUID, 'PO-2024-001', 
  (SELECT id FROM vendors WHERE code = 'VND-001' AND tenant_id = current_setting('app.current_tenant_id', true)::UUID),
  CURRENT_DATE - INTERVAL '5 days', CURRENT_DATE + INTERVAL '10 days', 'approved', 'IDR', 1.0000,
- (SELECT id FROM users WHERE tenant_id = current_setting('app.current_tenant_id', true)::UUID LIMIT 1));
+ (SELECT user_id FROM tenant_memberships WHERE tenant_id = current_setting('app.current_tenant_id', true)::UUID LIMIT 1));
 
 -- PO Items for Samsung order
 INSERT INTO purchase_order_items (tenant_id, purchase_order_id, product_id, description, quantity_ordered, unit_price, line_number) VALUES
@@ -81,7 +81,7 @@ INSERT INTO purchase_orders (tenant_id, po_number, vendor_id, order_date, expect
 (current_setting('app.current_tenant_id', true)::UUID, 'PO-2024-002', 
  (SELECT id FROM vendors WHERE code = 'VND-004' AND tenant_id = current_setting('app.current_tenant_id', true)::UUID),
  CURRENT_DATE - INTERVAL '3 days', CURRENT_DATE + INTERVAL '7 days', 'sent', 'IDR', 1.0000,
- (SELECT id FROM users WHERE tenant_id = current_setting('app.current_tenant_id', true)::UUID LIMIT 1));
+ (SELECT user_id FROM tenant_memberships WHERE tenant_id = current_setting('app.current_tenant_id', true)::UUID LIMIT 1));
 
 -- PO Items for Asus order
 INSERT INTO purchase_order_items (tenant_id, purchase_order_id, product_id, description, quantity_ordered, unit_price, line_number) VALUES
@@ -95,7 +95,7 @@ INSERT INTO purchase_orders (tenant_id, po_number, vendor_id, order_date, expect
 (current_setting('app.current_tenant_id', true)::UUID, 'PO-2024-003', 
  (SELECT id FROM vendors WHERE code = 'VND-006' AND tenant_id = current_setting('app.current_tenant_id', true)::UUID),
  CURRENT_DATE - INTERVAL '7 days', CURRENT_DATE + INTERVAL '14 days', 'received', 'IDR', 1.0000,
- (SELECT id FROM users WHERE tenant_id = current_setting('app.current_tenant_id', true)::UUID LIMIT 1));
+ (SELECT user_id FROM tenant_memberships WHERE tenant_id = current_setting('app.current_tenant_id', true)::UUID LIMIT 1));
 
 -- PO Items for Batik order
 INSERT INTO purchase_order_items (tenant_id, purchase_order_id, product_id, description, quantity_ordered, unit_price, line_number) VALUES
@@ -114,7 +114,7 @@ INSERT INTO purchase_orders (tenant_id, po_number, vendor_id, order_date, expect
 (current_setting('app.current_tenant_id', true)::UUID, 'PO-2024-004', 
  (SELECT id FROM vendors WHERE code = 'VND-008' AND tenant_id = current_setting('app.current_tenant_id', true)::UUID),
  CURRENT_DATE - INTERVAL '2 days', CURRENT_DATE + INTERVAL '5 days', 'pending', 'IDR', 1.0000,
- (SELECT id FROM users WHERE tenant_id = current_setting('app.current_tenant_id', true)::UUID LIMIT 1));
+ (SELECT user_id FROM tenant_memberships WHERE tenant_id = current_setting('app.current_tenant_id', true)::UUID LIMIT 1));
 
 -- PO Items for Coffee order
 INSERT INTO purchase_order_items (tenant_id, purchase_order_id, product_id, description, quantity_ordered, unit_price, line_number) VALUES
@@ -142,7 +142,7 @@ INSERT INTO purchase_receipts (tenant_id, receipt_number, purchase_order_id, ven
  CURRENT_DATE - INTERVAL '1 day',
  (SELECT id FROM warehouses WHERE code = 'WH-JKT-01' AND tenant_id = current_setting('app.current_tenant_id', true)::UUID),
  'received',
- (SELECT id FROM users WHERE tenant_id = current_setting('app.current_tenant_id', true)::UUID LIMIT 1));
+ (SELECT user_id FROM tenant_memberships WHERE tenant_id = current_setting('app.current_tenant_id', true)::UUID LIMIT 1));
 
 -- Purchase Receipt Items
 INSERT INTO purchase_receipt_items (tenant_id, purchase_receipt_id, purchase_order_item_id, product_id, quantity_received, unit_cost, line_total, quality_status) VALUES
@@ -0,0 +1,12 @@
+-- Keyset Pagination Indexes
+-- Cursor pages filter on (sort key, id) within a tenant, so each sort needs an
+-- index ending in id for the page boundary to be an index range scan.
+
+-- Stock movements (newest first)
+DROP INDEX IF EXISTS idx_stock_movements_created_at;
+CREATE INDEX idx_stock_movements_created_at ON stock_movements(tenant_id, created_at, id);
+
+-- Journal entries (entry_number is already unique per tenant)
+DROP INDEX IF EXISTS idx_journal_entries_date;
+CREATE INDEX idx_journal_entries_date ON journal_entries(tenant_id, entry_date, id);
+CREATE INDEX idx_journal_entries_created_at ON journal_entries(tenant_id, created_at, id);
@@ -0,0 +1,35 @@
+-- Bulk Import Jobs
+-- One row per uploaded CSV/XLSX file. The dry run stores the validated rows in
+-- `records`; committing inserts them in a single transaction and clears them.
+
+CREATE TABLE import_jobs (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    entity VARCHAR(50) NOT NULL, -- products, companies, contacts, vendors, accounts, opening_stock
+    file_name VARCHAR(255) NOT NULL,
+    file_format VARCHAR(10) NOT NULL, -- csv, xlsx
+    column_mapping JSONB NOT NULL DEFAULT '{}', -- target field -> source column header
+    status VARCHAR(20) NOT NULL DEFAULT 'validated', -- validated, queued, running, completed, failed
+    total_rows INTEGER NOT NULL DEFAULT 0,
+    error_rows INTEGER NOT NULL DEFAULT 0,
+    imported_rows INTEGER NOT NULL DEFAULT 0,
+    errors JSONB NOT NULL DEFAULT '[]',
+    records JSONB,
+    created_by UUID NOT NULL REFERENCES users(id),
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+    updated_at TIMESTAMPTZ DEFAULT NOW() NOT NULL
+);
+
+CREATE INDEX idx_import_jobs_created_at ON import_jobs(tenant_id, created_at, id);
+-- Startup looks for imports a shutdown interrupted
+CREATE INDEX idx_import_jobs_queued ON import_jobs(created_at) WHERE status = 'queued';
+
+ALTER TABLE import_jobs ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_import_jobs ON import_jobs
+    USING (tenant_id = current_setting('app.current_tenant_id', true)::UUID);
+
+CREATE TRIGGER update_import_jobs_updated_at BEFORE UPDATE ON import_jobs
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
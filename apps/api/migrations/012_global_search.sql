@@ -0,0 +1,90 @@
+-- Global Search
+-- Each searchable table gets a generated `search_vector` combining the `simple`
+-- configuration (codes, names, emails: matched as typed) with `indonesian`
+-- (stemmed names and free text). Codes are split on punctuation first so
+-- `PO-2024-0001` is found by `po 2024`. Trigram indexes back fuzzy/partial
+-- matches on identifiers and the list endpoints' `ILIKE '%...%'` searches.
+--
+-- Weights: A identifiers, B names, C contact details, D free text.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Companies
+ALTER TABLE companies ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', coalesce(name, '')), 'B') ||
+    setweight(to_tsvector('indonesian', coalesce(name, '')), 'B') ||
+    setweight(to_tsvector('simple', regexp_replace(
+        coalesce(email, '') || ' ' || coalesce(website, '') || ' ' || coalesce(phone, ''),
+        '[^[:alnum:]]+', ' ', 'g')), 'C')
+) STORED;
+
+CREATE INDEX idx_companies_search ON companies USING GIN (search_vector);
+CREATE INDEX idx_companies_name_trgm ON companies USING GIN (name gin_trgm_ops);
+CREATE INDEX idx_companies_email_trgm ON companies USING GIN (email gin_trgm_ops);
+
+-- Contacts
+ALTER TABLE contacts ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', coalesce(first_name, '') || ' ' || coalesce(last_name, '')), 'B') ||
+    setweight(to_tsvector('indonesian', coalesce(position, '')), 'C') ||
+    setweight(to_tsvector('simple', regexp_replace(
+        coalesce(email, '') || ' ' || coalesce(phone, ''),
+        '[^[:alnum:]]+', ' ', 'g')), 'C') ||
+    setweight(to_tsvector('indonesian', coalesce(notes, '')), 'D')
+) STORED;
+
+CREATE INDEX idx_contacts_search ON contacts USING GIN (search_vector);
+CREATE INDEX idx_contacts_first_name_trgm ON contacts USING GIN (first_name gin_trgm_ops);
+CREATE INDEX idx_contacts_last_name_trgm ON contacts USING GIN (last_name gin_trgm_ops);
+CREATE INDEX idx_contacts_email_trgm ON contacts USING GIN (email gin_trgm_ops);
+
+-- Products
+ALTER TABLE products ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', regexp_replace(
+        coalesce(sku, '') || ' ' || coalesce(barcode, ''),
+        '[^[:alnum:]]+', ' ', 'g')), 'A') ||
+    setweight(to_tsvector('simple', coalesce(name, '')), 'B') ||
+    setweight(to_tsvector('indonesian', coalesce(name, '')), 'B') ||
+    setweight(to_tsvector('indonesian', coalesce(description, '')), 'D')
+) STORED;
+
+CREATE INDEX idx_products_search ON products USING GIN (search_vector);
+CREATE INDEX idx_products_sku_trgm ON products USING GIN (sku gin_trgm_ops);
+CREATE INDEX idx_products_name_trgm ON products USING GIN (name gin_trgm_ops);
+CREATE INDEX idx_products_barcode_trgm ON products USING GIN (barcode gin_trgm_ops);
+
+-- Vendors
+ALTER TABLE vendors ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', regexp_replace(
+        coalesce(code, '') || ' ' || coalesce(tax_number, ''),
+        '[^[:alnum:]]+', ' ', 'g')), 'A') ||
+    setweight(to_tsvector('simple', coalesce(name, '')), 'B') ||
+    setweight(to_tsvector('indonesian', coalesce(name, '')), 'B') ||
+    setweight(to_tsvector('simple', regexp_replace(
+        coalesce(contact_person, '') || ' ' || coalesce(email, '') || ' ' || coalesce(phone, ''),
+        '[^[:alnum:]]+', ' ', 'g')), 'C')
+) STORED;
+
+CREATE INDEX idx_vendors_search ON vendors USING GIN (search_vector);
+CREATE INDEX idx_vendors_code_trgm ON vendors USING GIN (code gin_trgm_ops);
+CREATE INDEX idx_vendors_name_trgm ON vendors USING GIN (name gin_trgm_ops);
+
+-- Purchase orders
+ALTER TABLE purchase_orders ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', regexp_replace(coalesce(po_number, ''), '[^[:alnum:]]+', ' ', 'g')), 'A') ||
+    setweight(to_tsvector('indonesian', coalesce(notes, '')), 'D')
+) STORED;
+
+CREATE INDEX idx_purchase_orders_search ON purchase_orders USING GIN (search_vector);
+CREATE INDEX idx_purchase_orders_po_number_trgm ON purchase_orders USING GIN (po_number gin_trgm_ops);
+
+-- Journal entries
+ALTER TABLE journal_entries ADD COLUMN search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', regexp_replace(
+        coalesce(entry_number, '') || ' ' || coalesce(reference, ''),
+        '[^[:alnum:]]+', ' ', 'g')), 'A') ||
+    setweight(to_tsvector('indonesian', coalesce(description, '')), 'D')
+) STORED;
+
+CREATE INDEX idx_journal_entries_search ON journal_entries USING GIN (search_vector);
+CREATE INDEX idx_journal_entries_entry_number_trgm ON journal_entries USING GIN (entry_number gin_trgm_ops);
+CREATE INDEX idx_journal_entries_description_trgm ON journal_entries USING GIN (description gin_trgm_ops);
@@ -0,0 +1,80 @@
+-- Attachments
+-- Files are stored once per tenant and content hash (`attachment_blobs`) and
+-- linked to any record through `attachments`. Blobs no attachment refers to
+-- are swept by the API; deleting a blob row queues its file for removal from
+-- storage in `storage_deletions`, which outlives the tenant on purpose.
+
+CREATE TABLE attachment_blobs (
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    sha256 CHAR(64) NOT NULL,
+    size BIGINT NOT NULL,
+    storage_key VARCHAR(255) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+    PRIMARY KEY (tenant_id, sha256)
+);
+
+CREATE TABLE attachments (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    entity_type VARCHAR(50) NOT NULL, -- company, contact, product, vendor, purchase_order, journal_entry
+    entity_id UUID NOT NULL,
+    filename VARCHAR(255) NOT NULL,
+    content_type VARCHAR(255) NOT NULL,
+    sha256 CHAR(64) NOT NULL,
+    uploaded_by UUID NOT NULL REFERENCES users(id),
+    created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+    FOREIGN KEY (tenant_id, sha256) REFERENCES attachment_blobs(tenant_id, sha256)
+);
+
+CREATE INDEX idx_attachments_entity ON attachments(tenant_id, entity_type, entity_id, created_at);
+CREATE INDEX idx_attachments_blob ON attachments(tenant_id, sha256);
+
+CREATE TABLE storage_deletions (
+    storage_key VARCHAR(255) PRIMARY KEY,
+    created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL
+);
+
+ALTER TABLE attachment_blobs ENABLE ROW LEVEL SECURITY;
+ALTER TABLE attachments ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_attachment_blobs ON attachment_blobs
+    USING (tenant_id = current_setting('app.current_tenant_id', true)::UUID);
+
+CREATE POLICY tenant_isolation_attachments ON attachments
+    USING (tenant_id = current_setting('app.current_tenant_id', true)::UUID);
+
+-- Queue the file of every removed blob, including tenant cascades
+CREATE OR REPLACE FUNCTION queue_storage_deletion()
+RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO storage_deletions (storage_key) VALUES (OLD.storage_key)
+    ON CONFLICT (storage_key) DO NOTHING;
+    RETURN OLD;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER queue_attachment_blob_deletion AFTER DELETE ON attachment_blobs
+    FOR EACH ROW EXECUTE FUNCTION queue_storage_deletion();
+
+-- Purging a record drops its attachments; TG_ARGV[0] is the entity_type
+CREATE OR REPLACE FUNCTION delete_entity_attachments()
+RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM attachments
+    WHERE tenant_id = OLD.tenant_id AND entity_type = TG_ARGV[0] AND entity_id = OLD.id;
+    RETURN OLD;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER delete_companies_attachments AFTER DELETE ON companies
+    FOR EACH ROW EXECUTE FUNCTION delete_entity_attachments('company');
+CREATE TRIGGER delete_contacts_attachments AFTER DELETE ON contacts
+    FOR EACH ROW EXECUTE FUNCTION delete_entity_attachments('contact');
+CREATE TRIGGER delete_products_attachments AFTER DELETE ON products
+    FOR EACH ROW EXECUTE FUNCTION delete_entity_attachments('product');
+CREATE TRIGGER delete_vendors_attachments AFTER DELETE ON vendors
+    FOR EACH ROW EXECUTE FUNCTION delete_entity_attachments('vendor');
+CREATE TRIGGER delete_purchase_orders_attachments AFTER DELETE ON purchase_orders
+    FOR EACH ROW EXECUTE FUNCTION delete_entity_attachments('purchase_order');
+CREATE TRIGGER delete_journal_entries_attachments AFTER DELETE ON journal_entries
+    FOR EACH ROW EXECUTE FUNCTION delete_entity_attachments('journal_entry');
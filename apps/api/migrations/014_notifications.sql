@@ -0,0 +1,79 @@
+-- Notifications
+-- One row per recipient. `in_app` rows make up the inbox; `email_status` is
+-- 'pending' while an email copy waits for the mailer and NULL when the
+-- recipient opted out of email for that kind.
+
+CREATE TABLE notifications (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind VARCHAR(50) NOT NULL, -- low_stock, purchase_order_approval, leave_request, invitation_accepted
+    title VARCHAR(255) NOT NULL,
+    body TEXT NOT NULL,
+    data JSONB DEFAULT '{}' NOT NULL,
+    -- Identifies the subject (e.g. a product id); a recipient gets no second
+    -- notification of the same kind and key while the first is unread
+    dedup_key VARCHAR(255),
+    in_app BOOLEAN DEFAULT true NOT NULL,
+    email_status VARCHAR(20), -- pending, sent, failed
+    read_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL
+);
+
+CREATE INDEX idx_notifications_inbox ON notifications(tenant_id, user_id, created_at DESC, id) WHERE in_app;
+CREATE INDEX idx_notifications_unread ON notifications(tenant_id, user_id, kind, dedup_key) WHERE read_at IS NULL;
+CREATE INDEX idx_notifications_email ON notifications(created_at) WHERE email_status = 'pending';
+
+-- Channel choices per notification kind; kinds without a row use the defaults
+-- (in-app on, email off)
+CREATE TABLE notification_preferences (
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind VARCHAR(50) NOT NULL,
+    in_app BOOLEAN NOT NULL,
+    email BOOLEAN NOT NULL,
+    updated_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+    PRIMARY KEY (tenant_id, user_id, kind)
+);
+
+-- Leave requests
+CREATE TABLE leave_requests (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id),
+    leave_type VARCHAR(20) NOT NULL, -- annual, sick, unpaid, other
+    start_date DATE NOT NULL,
+    end_date DATE NOT NULL,
+    reason TEXT,
+    status VARCHAR(20) DEFAULT 'pending' NOT NULL, -- pending, approved, rejected, cancelled
+    reviewed_by UUID REFERENCES users(id),
+    reviewed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+    updated_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+    CHECK (end_date >= start_date)
+);
+
+CREATE INDEX idx_leave_requests_tenant ON leave_requests(tenant_id, start_date);
+CREATE INDEX idx_leave_requests_user ON leave_requests(tenant_id, user_id, start_date);
+
+CREATE TRIGGER update_leave_requests_updated_at BEFORE UPDATE ON leave_requests
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+ALTER TABLE notifications ENABLE ROW LEVEL SECURITY;
+ALTER TABLE notification_preferences ENABLE ROW LEVEL SECURITY;
+ALTER TABLE leave_requests ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_notifications ON notifications
+    USING (tenant_id = current_setting('app.current_tenant_id', true)::UUID);
+
+CREATE POLICY tenant_isolation_notification_preferences ON notification_preferences
+    USING (tenant_id = current_setting('app.current_tenant_id', true)::UUID);
+
+CREATE POLICY tenant_isolation_leave_requests ON leave_requests
+    USING (tenant_id = current_setting('app.current_tenant_id', true)::UUID);
+
+-- Approvers are notified of requests waiting for them
+INSERT INTO permissions (key, name, description, module) VALUES
+('procurement:orders:approve', 'Approve Purchase Orders', 'Can approve submitted purchase orders', 'procurement'),
+('hrm:leaves:approve', 'Approve Leaves', 'Can approve and reject leave requests', 'hrm')
+ON CONFLICT (key) DO NOTHING;
@@ -0,0 +1,5 @@
+-- Journal entry currency
+-- Amounts of an entry and its lines are in the entry's currency, like those of
+-- purchase orders and vendor invoices. Existing entries were all booked in rupiah.
+
+ALTER TABLE journal_entries ADD COLUMN currency VARCHAR(3) DEFAULT 'IDR' NOT NULL;
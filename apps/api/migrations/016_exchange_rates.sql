@@ -0,0 +1,36 @@
+-- Exchange rates
+-- One row per tenant, currency pair, rate type and day: one unit of
+-- `from_currency` is worth `rate` units of `to_currency`. Lookups take the
+-- latest rate on or before a date, so weekends and holidays need no rows.
+
+CREATE TABLE exchange_rates (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    from_currency VARCHAR(3) NOT NULL,
+    to_currency VARCHAR(3) NOT NULL,
+    rate_type VARCHAR(20) DEFAULT 'spot' NOT NULL, -- spot, tax, average
+    rate_date DATE NOT NULL,
+    rate DECIMAL(20,10) NOT NULL CHECK (rate > 0),
+    source VARCHAR(20) DEFAULT 'manual' NOT NULL, -- manual, import, feed
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+    updated_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+    CHECK (from_currency <> to_currency),
+    UNIQUE (tenant_id, from_currency, to_currency, rate_type, rate_date)
+);
+
+CREATE INDEX idx_exchange_rates_lookup
+    ON exchange_rates (tenant_id, rate_type, from_currency, to_currency, rate_date DESC);
+
+CREATE TRIGGER update_exchange_rates_updated_at BEFORE UPDATE ON exchange_rates
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+ALTER TABLE exchange_rates ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_exchange_rates ON exchange_rates
+    USING (tenant_id = current_setting('app.current_tenant_id', true)::UUID);
+
+-- Rates of documents are filled in from the table now; four decimals lose
+-- most of a rate such as IDR -> USD 0.0000615
+ALTER TABLE purchase_orders ALTER COLUMN exchange_rate TYPE DECIMAL(20,10);
+ALTER TABLE vendor_invoices ALTER COLUMN exchange_rate TYPE DECIMAL(20,10);
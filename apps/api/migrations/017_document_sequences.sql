@@ -0,0 +1,43 @@
+-- Document number sequences
+-- `document_sequences` holds a tenant's pattern per document type; types
+-- without a row use the built-in pattern. `document_sequence_counters` holds
+-- the last number issued per period (`2024`, `2024-03`, or '' when the
+-- sequence never resets); {WH} only labels a number, warehouses share the
+-- counter. Numbers are taken by incrementing the counter row inside the
+-- document's own transaction, so concurrent documents wait on the row lock and
+-- a rolled back document gives its number back: no gaps, no duplicates.
+
+CREATE TABLE document_sequences (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    document_type VARCHAR(50) NOT NULL,
+    pattern VARCHAR(100) NOT NULL,
+    reset_rule VARCHAR(20) DEFAULT 'yearly' NOT NULL CHECK (reset_rule IN ('yearly', 'monthly', 'never')),
+    created_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+    updated_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+    UNIQUE (tenant_id, document_type)
+);
+
+CREATE TABLE document_sequence_counters (
+    tenant_id UUID NOT NULL REFERENCES tenants(id) ON DELETE CASCADE,
+    document_type VARCHAR(50) NOT NULL,
+    period VARCHAR(7) NOT NULL,
+    last_value BIGINT NOT NULL CHECK (last_value > 0),
+    updated_at TIMESTAMPTZ DEFAULT NOW() NOT NULL,
+    PRIMARY KEY (tenant_id, document_type, period)
+);
+
+CREATE TRIGGER update_document_sequences_updated_at BEFORE UPDATE ON document_sequences
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+CREATE TRIGGER update_document_sequence_counters_updated_at BEFORE UPDATE ON document_sequence_counters
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+ALTER TABLE document_sequences ENABLE ROW LEVEL SECURITY;
+ALTER TABLE document_sequence_counters ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY tenant_isolation_document_sequences ON document_sequences
+    USING (tenant_id = current_setting('app.current_tenant_id', true)::UUID);
+
+CREATE POLICY tenant_isolation_document_sequence_counters ON document_sequence_counters
+    USING (tenant_id = current_setting('app.current_tenant_id', true)::UUID);
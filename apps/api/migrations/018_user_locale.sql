@@ -0,0 +1,5 @@
+-- Preferred language of a user, `en` or `id`; NULL follows the tenant.
+-- API messages are in the language of Accept-Language, else this, else the
+-- tenant's settings.locale.
+
+ALTER TABLE users ADD COLUMN locale VARCHAR(10);
@@ -0,0 +1,380 @@
+//! File attachments on business records.
+//!
+//! Uploads are spooled to a temporary file while their size and SHA-256 are
+//! computed, then stored once per tenant and content hash; attaching the same
+//! file twice only adds a row. Downloads go through short-lived signed URLs so
+//! browsers can open them without a bearer token. A background sweep removes
+//! blobs nothing refers to anymore and deletes their files from storage.
+
+use std::{
+    fmt::Display,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use shared_types::AttachmentEntity;
+use sqlx::PgPool;
+use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{state::AppState, storage::Storage};
+
+/// Leading bytes kept for content sniffing
+const SNIFF_LEN: usize = 512;
+/// Unreferenced blobs younger than this are left alone, so an upload that
+/// found the blob a moment ago can still attach it
+const ORPHAN_GRACE_SECS: i64 = 3600;
+/// Queued storage deletions handled per batch
+const DELETE_BATCH: i64 = 100;
+
+/// Table holding the records of `entity`; attachments may only point at rows
+/// of the caller's tenant in it.
+pub fn entity_table(entity: AttachmentEntity) -> &'static str {
+    match entity {
+        AttachmentEntity::Company => "companies",
+        AttachmentEntity::Contact => "contacts",
+        AttachmentEntity::Product => "products",
+        AttachmentEntity::Vendor => "vendors",
+        AttachmentEntity::PurchaseOrder => "purchase_orders",
+        AttachmentEntity::JournalEntry => "journal_entries",
+    }
+}
+
+/// An upload written to a temporary file, removed again when dropped.
+#[derive(Debug)]
+pub struct Spooled {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Hex SHA-256 of the contents
+    pub sha256: String,
+    /// First bytes of the contents, for [`sniff`]
+    pub head: Vec<u8>,
+}
+
+impl Drop for Spooled {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpoolError {
+    #[error("File exceeds the {0} byte limit")]
+    TooLarge(usize),
+    #[error("Upload interrupted: {0}")]
+    Read(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Write `chunks` to a temporary file, failing as soon as they add up to more
+/// than `limit` bytes.
+pub async fn spool<S, E>(mut chunks: S, limit: usize) -> Result<Spooled, SpoolError>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Display,
+{
+    let mut spooled = Spooled {
+        path: std::env::temp_dir().join(format!("erp-attachment-{}", Uuid::new_v4())),
+        size: 0,
+        sha256: String::new(),
+        head: Vec::new(),
+    };
+    let mut file = tokio::fs::File::create(&spooled.path).await?;
+    let mut hasher = Sha256::new();
+
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|e| SpoolError::Read(e.to_string()))?;
+        spooled.size += chunk.len() as u64;
+        if spooled.size > limit as u64 {
+            return Err(SpoolError::TooLarge(limit));
+        }
+        hasher.update(&chunk);
+        let wanted = SNIFF_LEN.saturating_sub(spooled.head.len()).min(chunk.len());
+        spooled.head.extend_from_slice(&chunk[..wanted]);
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+
+    spooled.sha256 = hex::encode(hasher.finalize());
+    Ok(spooled)
+}
+
+/// Content type detected from the leading bytes of a file. The name is only
+/// consulted to tell apart formats sharing a container (OOXML inside ZIP, CSV
+/// among plain text); anything unrecognised is `application/octet-stream`.
+pub fn sniff(head: &[u8], filename: &str) -> &'static str {
+    let extension = filename.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+
+    if head.starts_with(b"%PDF-") {
+        "application/pdf"
+    } else if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "image/png"
+    } else if head.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        "image/jpeg"
+    } else if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+        "image/gif"
+    } else if head.len() >= 12 && head.starts_with(b"RIFF") && &head[8..12] == b"WEBP" {
+        "image/webp"
+    } else if head.starts_with(b"PK\x03\x04") {
+        match extension.as_str() {
+            "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+            _ => "application/zip",
+        }
+    } else if head.starts_with(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1]) {
+        match extension.as_str() {
+            "doc" => "application/msword",
+            "xls" => "application/vnd.ms-excel",
+            _ => "application/octet-stream",
+        }
+    } else if !head.is_empty() && is_text(head) {
+        match extension.as_str() {
+            "csv" => "text/csv",
+            _ => "text/plain",
+        }
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// UTF-8 without control characters other than whitespace; a character cut
+/// off at the end of `head` still counts.
+fn is_text(head: &[u8]) -> bool {
+    let valid = match std::str::from_utf8(head) {
+        Ok(_) => head.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => return false,
+    };
+    head[..valid].iter().all(|&b| b >= 0x20 || matches!(b, b'\t' | b'\n' | b'\r' | 0x0C))
+}
+
+/// File name as stored: the last path segment without control characters,
+/// at most 255 characters.
+pub fn clean_filename(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let clean: String = base.chars().filter(|c| !c.is_control()).take(255).collect();
+    match clean.trim() {
+        "" | "." | ".." => "file".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+/// `Content-Disposition` for downloading `filename`, with an ASCII fallback
+/// and the exact name in RFC 5987 encoding.
+pub fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii_graphic() && c != '"' && c != '\\' || c == ' ' { c } else { '_' })
+        .collect();
+    let encoded: String = filename
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded)
+}
+
+/// Signs and checks download URLs.
+pub struct UrlSigner {
+    key: Vec<u8>,
+    ttl: i64,
+}
+
+impl UrlSigner {
+    /// The signing key is derived from `secret` (the JWT secret), so a download
+    /// signature can never pass as anything else signed with it.
+    pub fn new(secret: &str, ttl_secs: u64) -> Self {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(b"attachment-download");
+        Self { key: mac.finalize().into_bytes().to_vec(), ttl: ttl_secs as i64 }
+    }
+
+    pub fn from_state(state: &AppState) -> Self {
+        Self::new(&state.config.jwt.secret, state.config.storage.url_ttl)
+    }
+
+    /// Download URL for attachment `id` and when it stops working.
+    pub fn sign(&self, id: Uuid, now: DateTime<Utc>) -> (String, DateTime<Utc>) {
+        let expires = now.timestamp() + self.ttl;
+        let signature = hex::encode(self.mac(id, expires).finalize().into_bytes());
+        let url = format!("/api/v1/attachments/{}/download?expires={}&signature={}", id, expires, signature);
+        (url, DateTime::from_timestamp(expires, 0).unwrap_or(now))
+    }
+
+    pub fn verify(&self, id: Uuid, expires: i64, signature: &str, now: DateTime<Utc>) -> bool {
+        if expires < now.timestamp() {
+            return false;
+        }
+        match hex::decode(signature) {
+            Ok(signature) => self.mac(id, expires).verify_slice(&signature).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn mac(&self, id: Uuid, expires: i64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(format!("{}:{}", id, expires).as_bytes());
+        mac
+    }
+}
+
+/// Start the periodic sweep of unreferenced files.
+pub fn spawn_cleanup(state: &AppState) {
+    let pool = state.db_pool.clone();
+    let storage = state.storage.clone();
+    let interval = Duration::from_secs(state.config.storage.cleanup_interval);
+    state.workers.spawn("attachment-cleanup", move |shutdown| cleanup(pool, storage, interval, shutdown));
+}
+
+async fn cleanup(pool: PgPool, storage: Arc<dyn Storage>, interval: Duration, shutdown: CancellationToken) {
+    loop {
+        match sweep(&pool, storage.as_ref(), &shutdown).await {
+            Ok(0) => {}
+            Ok(deleted) => info!(deleted, "Removed unreferenced attachment files"),
+            Err(e) => warn!("Attachment cleanup failed: {}", e),
+        }
+        tokio::select! {
+            _ = shutdown.cancelled() => return,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+/// Drop blobs without attachments, then delete queued files from storage.
+/// Returns how many files were deleted.
+async fn sweep(pool: &PgPool, storage: &dyn Storage, shutdown: &CancellationToken) -> Result<usize, sqlx::Error> {
+    // Deleting a blob row queues its file (trigger on attachment_blobs)
+    sqlx::query(
+        r#"DELETE FROM attachment_blobs b
+           WHERE b.created_at < NOW() - make_interval(secs => $1)
+             AND NOT EXISTS (SELECT 1 FROM attachments a WHERE a.tenant_id = b.tenant_id AND a.sha256 = b.sha256)"#,
+    )
+    .bind(ORPHAN_GRACE_SECS as f64)
+    .execute(pool)
+    .await?;
+
+    let mut deleted = 0;
+    loop {
+        let keys: Vec<String> =
+            sqlx::query_scalar("SELECT storage_key FROM storage_deletions ORDER BY created_at LIMIT $1")
+                .bind(DELETE_BATCH)
+                .fetch_all(pool)
+                .await?;
+        let batch = keys.len() as i64;
+
+        for key in keys {
+            if shutdown.is_cancelled() {
+                return Ok(deleted);
+            }
+            if let Err(e) = storage.delete(&key).await {
+                // Left queued for the next sweep
+                warn!(key = %key, "Failed to delete attachment file: {}", e);
+                return Ok(deleted);
+            }
+            sqlx::query("DELETE FROM storage_deletions WHERE storage_key = $1")
+                .bind(&key)
+                .execute(pool)
+                .await?;
+            deleted += 1;
+        }
+
+        if batch < DELETE_BATCH {
+            return Ok(deleted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks(parts: &[&'static [u8]]) -> impl Stream<Item = Result<Bytes, std::io::Error>> + Unpin {
+        futures::stream::iter(parts.iter().map(|p| Ok(Bytes::from_static(p))).collect::<Vec<_>>())
+    }
+
+    #[tokio::test]
+    async fn spools_hashes_and_cleans_up() {
+        let spooled = spool(chunks(&[b"hel", b"lo"]), 5).await.unwrap();
+        assert_eq!(spooled.size, 5);
+        assert_eq!(spooled.sha256, "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824");
+        assert_eq!(spooled.head, b"hello");
+        assert_eq!(tokio::fs::read(&spooled.path).await.unwrap(), b"hello");
+
+        let path = spooled.path.clone();
+        drop(spooled);
+        assert!(!path.exists());
+
+        assert!(matches!(spool(chunks(&[b"hel", b"lo!"]), 5).await, Err(SpoolError::TooLarge(5))));
+    }
+
+    #[test]
+    fn sniffs_contents_not_names() {
+        assert_eq!(sniff(b"%PDF-1.7\n", "invoice.exe"), "application/pdf");
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0", "a.jpg"), "image/png");
+        assert_eq!(sniff(&[0xFF, 0xD8, 0xFF, 0xE0], "scan"), "image/jpeg");
+        assert_eq!(sniff(b"RIFF\x10\0\0\0WEBPVP8 ", "x.webp"), "image/webp");
+        assert_eq!(
+            sniff(b"PK\x03\x04\x14\0", "Stock.XLSX"),
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+        );
+        assert_eq!(sniff(b"PK\x03\x04\x14\0", "archive.pdf"), "application/zip");
+        assert_eq!(sniff(b"sku,name\r\nA-1,Kopi\r\n", "stock.csv"), "text/csv");
+        assert_eq!(sniff("Catatan: kopi \u{e9}".as_bytes(), "note.txt"), "text/plain");
+        // A multi-byte character cut off by the sniff window is still text
+        assert_eq!(sniff(&"\u{e9}".as_bytes()[..1], "x.txt"), "text/plain");
+        assert_eq!(sniff(b"MZ\x90\0\x03\0", "setup.pdf"), "application/octet-stream");
+        assert_eq!(sniff(b"", "empty.txt"), "application/octet-stream");
+    }
+
+    #[test]
+    fn filenames_lose_paths_and_control_characters() {
+        assert_eq!(clean_filename("C:\\Users\\ani\\faktur.pdf"), "faktur.pdf");
+        assert_eq!(clean_filename("../../etc/passwd"), "passwd");
+        assert_eq!(clean_filename("a\r\nb.txt"), "ab.txt");
+        assert_eq!(clean_filename(".."), "file");
+        assert_eq!(clean_filename(&"x".repeat(300)).len(), 255);
+    }
+
+    #[test]
+    fn content_disposition_escapes_names() {
+        assert_eq!(
+            content_disposition("Faktur \"Mei\" é.pdf"),
+            "attachment; filename=\"Faktur _Mei_ _.pdf\"; filename*=UTF-8''Faktur%20%22Mei%22%20%C3%A9.pdf"
+        );
+    }
+
+    #[test]
+    fn signed_urls_expire_and_bind_the_id() {
+        let signer = UrlSigner::new("secret", 900);
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+        let (url, expires_at) = signer.sign(id, now);
+        assert_eq!(expires_at.timestamp(), now.timestamp() + 900);
+
+        let query = url.split_once('?').unwrap().1;
+        let param = |name: &str| {
+            query.split('&').find_map(|p| p.strip_prefix(&format!("{}=", name))).unwrap().to_string()
+        };
+        let expires: i64 = param("expires").parse().unwrap();
+        let signature = param("signature");
+
+        assert!(signer.verify(id, expires, &signature, now));
+        assert!(!signer.verify(Uuid::new_v4(), expires, &signature, now));
+        assert!(!signer.verify(id, expires + 1, &signature, now));
+        assert!(!signer.verify(id, expires, "zz", now));
+        assert!(!signer.verify(id, expires, &signature, now + chrono::Duration::seconds(901)));
+        assert!(!UrlSigner::new("other", 900).verify(id, expires, &signature, now));
+    }
+}
@@ -0,0 +1,336 @@
+//! Read-through cache for hot, rarely written reads: the chart of accounts,
+//! warehouse lists, product detail and the members holding a permission.
+//!
+//! Entries live in the [`KvStore`] as JSON under
+//! `cache:{tenant}:{scope}:{generation}:{key}`. Each tenant and scope has a
+//! generation token; a write drops it with [`ReadCache::invalidate`] after
+//! committing, which orphans every entry of that scope at once and leaves
+//! them to expire. Concurrent misses for one entry wait for a single load in
+//! this process. A failing store never fails a read; it goes to the database.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use telemetry::Metrics;
+use tokio::sync::OwnedMutexGuard;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    config::CacheConfig,
+    kv::{KvError, KvStore},
+};
+
+/// Generations outlive any entry; one that expires anyway is replaced by a
+/// new token, so stale entries are never reached again.
+const GENERATION_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A group of cached reads, invalidated together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheScope {
+    Accounts,
+    Warehouses,
+    Products,
+    Permissions,
+}
+
+impl CacheScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheScope::Accounts => "accounts",
+            CacheScope::Warehouses => "warehouses",
+            CacheScope::Products => "products",
+            CacheScope::Permissions => "permissions",
+        }
+    }
+
+    fn ttl(&self, config: &CacheConfig) -> Duration {
+        Duration::from_secs(match self {
+            CacheScope::Accounts | CacheScope::Warehouses => config.reference_ttl,
+            CacheScope::Products => config.product_ttl,
+            CacheScope::Permissions => config.permission_ttl,
+        })
+    }
+}
+
+/// Whether a read may be answered from the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Freshness {
+    #[default]
+    Cached,
+    /// Straight from the database, for reads that must see the latest commit.
+    Fresh,
+}
+
+#[derive(Clone)]
+pub struct ReadCache {
+    kv: Arc<dyn KvStore>,
+    metrics: Metrics,
+    config: CacheConfig,
+    /// One lock per entry being loaded, for single-flight misses.
+    loading: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl ReadCache {
+    pub fn new(kv: Arc<dyn KvStore>, metrics: Metrics, config: CacheConfig) -> Self {
+        Self { kv, metrics, config, loading: Arc::default() }
+    }
+
+    /// The cached value of `key` in the tenant's `scope`, or `load`'s result,
+    /// cached for the scope's TTL. Errors from `load` are not cached.
+    pub async fn get_or_load<T, E, F, Fut>(
+        &self,
+        tenant_id: Uuid,
+        scope: CacheScope,
+        key: &str,
+        freshness: Freshness,
+        load: F,
+    ) -> Result<T, E>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if !self.config.enabled {
+            return load().await;
+        }
+        if freshness == Freshness::Fresh {
+            self.metrics.record_cache(scope.as_str(), "bypass");
+            return load().await;
+        }
+
+        let key = match self.generation(tenant_id, scope).await {
+            Some(generation) => entry_key(tenant_id, scope, &generation, key),
+            None => return load().await,
+        };
+        if let Some(value) = self.lookup(scope, &key).await {
+            return Ok(value);
+        }
+
+        // Whoever else missed on this entry is loading it; their result will be
+        // in the store by the time the lock is ours.
+        let _loading = self.lock(&key).await;
+        if let Some(value) = self.lookup(scope, &key).await {
+            return Ok(value);
+        }
+        self.metrics.record_cache(scope.as_str(), "miss");
+        let value = load().await?;
+        self.store(scope, &key, &value).await;
+        Ok(value)
+    }
+
+    /// Drop every cached read in the tenant's `scope`. Call after the write
+    /// has committed, or a concurrent read can cache the old rows again.
+    pub async fn invalidate(&self, tenant_id: Uuid, scope: CacheScope) {
+        if !self.config.enabled {
+            return;
+        }
+        self.metrics.record_cache_invalidation(scope.as_str());
+        if let Err(e) = self.kv.delete(&generation_key(tenant_id, scope)).await {
+            // Entries then live out their TTL
+            warn!(%tenant_id, scope = scope.as_str(), "Failed to invalidate cache: {}", e);
+            self.metrics.record_cache(scope.as_str(), "error");
+        }
+    }
+
+    async fn generation(&self, tenant_id: Uuid, scope: CacheScope) -> Option<String> {
+        let key = generation_key(tenant_id, scope);
+        let result = async {
+            if let Some(generation) = self.kv.get(&key).await? {
+                return Ok(generation);
+            }
+            let fresh = Uuid::new_v4().simple().to_string();
+            if self.kv.set_nx(&key, &fresh, GENERATION_TTL).await? {
+                return Ok(fresh);
+            }
+            // Another reader started the generation first
+            Ok::<_, KvError>(self.kv.get(&key).await?.unwrap_or(fresh))
+        }
+        .await;
+        match result {
+            Ok(generation) => Some(generation),
+            Err(e) => {
+                self.failed(scope, "read", e);
+                None
+            }
+        }
+    }
+
+    async fn lookup<T: DeserializeOwned>(&self, scope: CacheScope, key: &str) -> Option<T> {
+        let cached = match self.kv.get(key).await {
+            Ok(cached) => cached?,
+            Err(e) => {
+                self.failed(scope, "read", e);
+                return None;
+            }
+        };
+        match serde_json::from_str(&cached) {
+            Ok(value) => {
+                self.metrics.record_cache(scope.as_str(), "hit");
+                Some(value)
+            }
+            // Written by an older build; the load overwrites it
+            Err(_) => None,
+        }
+    }
+
+    async fn store<T: Serialize>(&self, scope: CacheScope, key: &str, value: &T) {
+        let Ok(json) = serde_json::to_string(value) else {
+            return;
+        };
+        if let Err(e) = self.kv.set(key, &json, scope.ttl(&self.config)).await {
+            self.failed(scope, "write", e);
+        }
+    }
+
+    fn failed(&self, scope: CacheScope, action: &str, error: impl std::fmt::Display) {
+        warn!(scope = scope.as_str(), "Failed to {} cache: {}", action, error);
+        self.metrics.record_cache(scope.as_str(), "error");
+    }
+
+    async fn lock(&self, key: &str) -> Loading {
+        let lock = self.loading.lock().expect("cache lock poisoned").entry(key.to_string()).or_default().clone();
+        Loading { guard: Some(lock.lock_owned().await), loading: self.loading.clone(), key: key.to_string() }
+    }
+}
+
+/// Holds an entry's load lock; forgets the lock once nobody waits on it.
+struct Loading {
+    guard: Option<OwnedMutexGuard<()>>,
+    loading: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+    key: String,
+}
+
+impl Drop for Loading {
+    fn drop(&mut self) {
+        let mut loading = self.loading.lock().expect("cache lock poisoned");
+        self.guard.take();
+        // The map's reference is the last one: no waiters, none can appear
+        // while the map is locked
+        if loading.get(&self.key).is_some_and(|lock| Arc::strong_count(lock) == 1) {
+            loading.remove(&self.key);
+        }
+    }
+}
+
+fn generation_key(tenant_id: Uuid, scope: CacheScope) -> String {
+    format!("cache:{}:{}:generation", tenant_id, scope.as_str())
+}
+
+fn entry_key(tenant_id: Uuid, scope: CacheScope, generation: &str, key: &str) -> String {
+    format!("cache:{}:{}:{}:{}", tenant_id, scope.as_str(), generation, key)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::kv::MemoryKv;
+
+    fn cache(enabled: bool) -> (ReadCache, Metrics) {
+        let metrics = Metrics::new(false).unwrap();
+        let config = CacheConfig { enabled, reference_ttl: 60, product_ttl: 60, permission_ttl: 60 };
+        (ReadCache::new(Arc::new(MemoryKv::new()), metrics.clone(), config), metrics)
+    }
+
+    async fn load(cache: &ReadCache, tenant_id: Uuid, freshness: Freshness, loads: &AtomicUsize, value: &str) -> String {
+        cache
+            .get_or_load(tenant_id, CacheScope::Products, "product:1", freshness, || async {
+                loads.fetch_add(1, Ordering::SeqCst);
+                Ok::<_, ()>(value.to_string())
+            })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn second_read_is_a_hit_until_invalidated() {
+        let (cache, metrics) = cache(true);
+        let tenant = Uuid::new_v4();
+        let loads = AtomicUsize::new(0);
+
+        assert_eq!(load(&cache, tenant, Freshness::Cached, &loads, "v1").await, "v1");
+        assert_eq!(load(&cache, tenant, Freshness::Cached, &loads, "v2").await, "v1");
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+        cache.invalidate(tenant, CacheScope::Products).await;
+        assert_eq!(load(&cache, tenant, Freshness::Cached, &loads, "v2").await, "v2");
+
+        let text = metrics.render();
+        assert!(text.contains(r#"cache_requests_total{result="hit",scope="products"} 1"#));
+        assert!(text.contains(r#"cache_requests_total{result="miss",scope="products"} 2"#));
+    }
+
+    #[tokio::test]
+    async fn tenants_do_not_share_entries() {
+        let (cache, _) = cache(true);
+        let loads = AtomicUsize::new(0);
+        let (northwind, contoso) = (Uuid::new_v4(), Uuid::new_v4());
+
+        load(&cache, northwind, Freshness::Cached, &loads, "northwind").await;
+        assert_eq!(load(&cache, contoso, Freshness::Cached, &loads, "contoso").await, "contoso");
+
+        cache.invalidate(contoso, CacheScope::Products).await;
+        assert_eq!(load(&cache, northwind, Freshness::Cached, &loads, "other").await, "northwind");
+    }
+
+    #[tokio::test]
+    async fn fresh_reads_and_a_disabled_cache_go_to_the_loader() {
+        let (cache, metrics) = cache(true);
+        let tenant = Uuid::new_v4();
+        let loads = AtomicUsize::new(0);
+        load(&cache, tenant, Freshness::Cached, &loads, "v1").await;
+        assert_eq!(load(&cache, tenant, Freshness::Fresh, &loads, "v2").await, "v2");
+        assert!(metrics.render().contains(r#"cache_requests_total{result="bypass",scope="products"} 1"#));
+
+        let (cache, _) = self::cache(false);
+        load(&cache, tenant, Freshness::Cached, &loads, "v1").await;
+        assert_eq!(load(&cache, tenant, Freshness::Cached, &loads, "v2").await, "v2");
+        assert_eq!(loads.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn concurrent_misses_load_once() {
+        let (cache, _) = cache(true);
+        let tenant = Uuid::new_v4();
+        let loads = Arc::new(AtomicUsize::new(0));
+
+        let reads = (0..8).map(|_| {
+            let (cache, loads) = (cache.clone(), loads.clone());
+            tokio::spawn(async move {
+                cache
+                    .get_or_load(tenant, CacheScope::Accounts, "chart", Freshness::Cached, || async {
+                        loads.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        Ok::<_, ()>(vec![1, 2, 3])
+                    })
+                    .await
+                    .unwrap()
+            })
+        });
+        for read in futures::future::join_all(reads).await {
+            assert_eq!(read.unwrap(), vec![1, 2, 3]);
+        }
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert!(cache.loading.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_loads_are_not_cached() {
+        let (cache, _) = cache(true);
+        let tenant = Uuid::new_v4();
+        let failed: Result<String, &str> =
+            cache.get_or_load(tenant, CacheScope::Products, "p", Freshness::Cached, || async { Err("down") }).await;
+        assert_eq!(failed, Err("down"));
+
+        let loaded: Result<String, &str> =
+            cache.get_or_load(tenant, CacheScope::Products, "p", Freshness::Cached, || async { Ok("up".to_string()) }).await;
+        assert_eq!(loaded.unwrap(), "up");
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
@@ -6,8 +8,14 @@ pub struct AppConfig {
     pub server: ServerConfig,
     pub database: DatabaseConfig,
     pub redis: RedisConfig,
+    pub cache: CacheConfig,
     pub jwt: JwtConfig,
     pub email: EmailConfig,
+    pub telemetry: telemetry::TelemetryConfig,
+    pub idempotency: IdempotencyConfig,
+    pub imports: ImportConfig,
+    pub storage: StorageConfig,
+    pub exchange_rates: ExchangeRatesConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,8 +23,37 @@ pub struct ServerConfig {
     pub host: String,
     pub port: u16,
     pub environment: String,
+    /// Seconds to drain in-flight requests and background workers after SIGTERM
+    pub shutdown_timeout: u64,
+    /// Seconds before a request is aborted with 408
+    pub request_timeout: u64,
+    /// Maximum request body size in bytes
+    pub body_limit: usize,
+    pub compression: bool,
+    pub cors: CorsConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorsConfig {
+    /// Exact origins (`https://app.example.com`) allowed to call the API, or `*`
+    /// for any origin without credentials. `SERVER__CORS__ALLOWED_ORIGINS` is comma separated.
+    pub allowed_origins: Vec<String>,
+    pub allow_credentials: bool,
+    /// Seconds browsers may cache preflight responses
+    pub max_age: u64,
+}
+
+impl CorsConfig {
+    /// Frontend dev servers allowed when no origins are configured in development
+    const DEV_ORIGINS: [&'static str; 2] = ["http://localhost:5173", "http://localhost:3000"];
+
+    pub fn allows_any(&self) -> bool {
+        self.allowed_origins.iter().any(|o| o == "*")
+    }
 }
 
+const ENVIRONMENTS: [&str; 4] = ["development", "test", "staging", "production"];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DatabaseConfig {
     pub url: String,
@@ -24,6 +61,9 @@ pub struct DatabaseConfig {
     pub min_connections: u32,
     pub acquire_timeout: u64,
     pub idle_timeout: u64,
+    /// Apply pending migrations when the server starts; turn off when deployments
+    /// run `erpctl migrate up` as a separate step
+    pub auto_migrate: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +73,18 @@ pub struct RedisConfig {
     pub connection_timeout: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// Off sends every read to the database
+    pub enabled: bool,
+    /// Seconds accounts and warehouse lists are cached
+    pub reference_ttl: u64,
+    /// Seconds product detail is cached
+    pub product_ttl: u64,
+    /// Seconds the members holding a permission are cached
+    pub permission_ttl: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JwtConfig {
     pub secret: String,
@@ -40,37 +92,184 @@ pub struct JwtConfig {
     pub refresh_token_duration: u64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdempotencyConfig {
+    /// Seconds a completed response is kept for replay
+    pub ttl: u64,
+    /// Seconds an unfinished request holds its key; must outlast the request timeout
+    pub in_flight_ttl: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportConfig {
+    /// Maximum upload size in bytes; import uploads bypass `server.body_limit`
+    pub max_file_size: usize,
+    /// Maximum data rows per file
+    pub max_rows: usize,
+    /// Seconds after which a job still `running` at startup is assumed lost
+    /// with a crashed worker and requeued
+    pub stale_after: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageConfig {
+    /// `local` or `s3`
+    pub backend: String,
+    /// Root directory of the local backend
+    pub local_path: String,
+    pub s3: S3Config,
+    /// Maximum size of one attachment in bytes; attachment uploads bypass `server.body_limit`
+    pub max_file_size: usize,
+    /// Bytes of attachments a tenant may keep, by tenant plan (`STORAGE__QUOTAS__BASIC`).
+    /// Plans without an entry get the `basic` quota.
+    pub quotas: HashMap<String, u64>,
+    /// Seconds a signed download URL stays valid
+    pub url_ttl: u64,
+    /// Seconds between sweeps that delete files no attachment refers to anymore
+    pub cleanup_interval: u64,
+}
+
+impl StorageConfig {
+    pub fn quota(&self, plan: &str) -> u64 {
+        self.quotas.get(plan).or_else(|| self.quotas.get("basic")).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeRatesConfig {
+    /// Days a rate keeps applying to later dates that have none of their own
+    pub max_age_days: u32,
+    /// Source of `POST /accounting/exchange-rates/fetch`; `file` reads `file_path`
+    pub fetcher: String,
+    /// CSV or XLSX with `from_currency`, `to_currency`, `rate_date`, `rate` and
+    /// optionally `rate_type` columns
+    pub file_path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct S3Config {
+    /// Base URL of the S3-compatible service, e.g. `https://s3.ap-southeast-1.amazonaws.com`
+    /// or `http://localhost:9000` for MinIO
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// `endpoint/bucket/key` instead of `bucket.endpoint/key`; MinIO needs this
+    pub path_style: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailConfig {
+    /// `smtp`, or `file` to write each message as an `.eml` file (tests, local development)
+    pub transport: String,
     pub smtp_host: String,
     pub smtp_port: u16,
     pub smtp_username: String,
     pub smtp_password: String,
+    /// `starttls`, `tls` or `none` (e.g. MailHog)
+    pub smtp_security: String,
+    /// Directory the file transport writes to
+    pub file_path: String,
     pub from_email: String,
     pub from_name: String,
+    /// Frontend base URL that links in emails point to, e.g. `https://app.example.com`
+    pub app_url: String,
+    /// `en` or `id`; used when the tenant has not chosen a language
+    pub default_locale: String,
+    /// Seconds between runs of the worker that emails pending notifications
+    pub outbox_interval: u64,
 }
 
 impl AppConfig {
+    /// Configuration from the environment and `.env`.
     pub fn load() -> Result<Self> {
         dotenvy::dotenv().ok();
 
+        Self::from_source(
+            config::Environment::default()
+                .separator("__")
+                .try_parsing(true)
+                .list_separator(",")
+                .with_list_parse_key("server.cors.allowed_origins"),
+        )
+    }
+
+    /// Configuration from `source` over the defaults, validated like [`load`](Self::load).
+    pub fn from_source(source: impl config::Source + Send + Sync + 'static) -> Result<Self> {
         let config = config::Config::builder()
-            .add_source(config::Environment::default().separator("__"))
+            .add_source(source)
             .set_default("server.host", "0.0.0.0")?
             .set_default("server.port", 3000)?
             .set_default("server.environment", "development")?
+            .set_default("server.shutdown_timeout", 30)?
+            .set_default("server.request_timeout", 30)?
+            .set_default("server.body_limit", 2 * 1024 * 1024)?
+            .set_default("server.compression", true)?
+            .set_default("server.cors.allowed_origins", Vec::<String>::new())?
+            .set_default("server.cors.allow_credentials", true)?
+            .set_default("server.cors.max_age", 3600)?
             .set_default("database.max_connections", 10)?
             .set_default("database.min_connections", 1)?
             .set_default("database.acquire_timeout", 30)?
             .set_default("database.idle_timeout", 600)?
+            .set_default("database.auto_migrate", true)?
             .set_default("redis.max_connections", 10)?
             .set_default("redis.connection_timeout", 5)?
+            .set_default("cache.enabled", true)?
+            .set_default("cache.reference_ttl", 300)?
+            .set_default("cache.product_ttl", 60)?
+            .set_default("cache.permission_ttl", 300)?
             .set_default("jwt.access_token_duration", 900)? // 15 minutes
             .set_default("jwt.refresh_token_duration", 604800)? // 7 days
+            .set_default("email.transport", "smtp")?
+            .set_default("email.smtp_host", "localhost")?
             .set_default("email.smtp_port", 587)?
+            .set_default("email.smtp_username", "")?
+            .set_default("email.smtp_password", "")?
+            .set_default("email.smtp_security", "starttls")?
+            .set_default("email.file_path", "./data/mail")?
+            .set_default("email.from_name", "ERP Platform")?
+            .set_default("email.app_url", "http://localhost:5173")?
+            .set_default("email.default_locale", "en")?
+            .set_default("email.outbox_interval", 30)?
+            .set_default("idempotency.ttl", 86400)? // 24 hours
+            .set_default("idempotency.in_flight_ttl", 120)?
+            .set_default("imports.max_file_size", 10 * 1024 * 1024)?
+            .set_default("imports.max_rows", 50_000)?
+            .set_default("imports.stale_after", 3600)?
+            .set_default("storage.backend", "local")?
+            .set_default("storage.local_path", "./data/attachments")?
+            .set_default("storage.s3.endpoint", "")?
+            .set_default("storage.s3.bucket", "")?
+            .set_default("storage.s3.region", "us-east-1")?
+            .set_default("storage.s3.access_key", "")?
+            .set_default("storage.s3.secret_key", "")?
+            .set_default("storage.s3.path_style", true)?
+            .set_default("storage.max_file_size", 25 * 1024 * 1024)?
+            .set_default("storage.quotas.basic", 1024_i64 * 1024 * 1024)? // 1 GiB
+            .set_default("storage.quotas.professional", 10 * 1024_i64 * 1024 * 1024)?
+            .set_default("storage.quotas.enterprise", 100 * 1024_i64 * 1024 * 1024)?
+            .set_default("storage.url_ttl", 900)? // 15 minutes
+            .set_default("storage.cleanup_interval", 3600)?
+            .set_default("exchange_rates.max_age_days", 7)?
+            .set_default("exchange_rates.fetcher", "file")?
+            .set_default("exchange_rates.file_path", "./data/exchange-rates.csv")?
+            .set_default("telemetry.service_name", "erp-api")?
+            .set_default("telemetry.environment", "development")?
+            .set_default("telemetry.log_format", "pretty")?
+            .set_default("telemetry.log_filter", "api=debug,tower_http=debug")?
+            .set_default("telemetry.sample_ratio", 1.0)?
+            .set_default("telemetry.metrics_tenant_labels", false)?
             .build()?;
 
-        let app_config: AppConfig = config.try_deserialize()?;
+        let mut app_config: AppConfig = config.try_deserialize()?;
+
+        let server = &mut app_config.server;
+        server.cors.allowed_origins.retain(|o| !o.trim().is_empty());
+        if server.cors.allowed_origins.is_empty() && server.environment == "development" {
+            server.cors.allowed_origins = CorsConfig::DEV_ORIGINS.iter().map(|o| o.to_string()).collect();
+        }
         
         // Validate required fields
         if app_config.database.url.is_empty() {
@@ -81,10 +280,263 @@ impl AppConfig {
             anyhow::bail!("REDIS_URL is required");
         }
         
+        let cache = &app_config.cache;
+        if cache.reference_ttl == 0 || cache.product_ttl == 0 || cache.permission_ttl == 0 {
+            anyhow::bail!("CACHE__*_TTL must be greater than 0; set CACHE__ENABLED=false to turn caching off");
+        }
+
         if app_config.jwt.secret.is_empty() {
             anyhow::bail!("JWT_SECRET is required");
         }
 
+        if !(0.0..=1.0).contains(&app_config.telemetry.sample_ratio) {
+            anyhow::bail!("TELEMETRY__SAMPLE_RATIO must be between 0.0 and 1.0");
+        }
+
+        app_config.server.validate()?;
+
+        if app_config.idempotency.in_flight_ttl <= app_config.server.request_timeout {
+            anyhow::bail!("IDEMPOTENCY__IN_FLIGHT_TTL must be greater than SERVER__REQUEST_TIMEOUT");
+        }
+
+        if app_config.imports.max_file_size == 0 || app_config.imports.max_rows == 0 {
+            anyhow::bail!("IMPORTS__MAX_FILE_SIZE and IMPORTS__MAX_ROWS must be greater than 0");
+        }
+
+        app_config.storage.validate()?;
+
+        if app_config.exchange_rates.fetcher != "file" {
+            anyhow::bail!("EXCHANGE_RATES__FETCHER must be file");
+        }
+        app_config.email.validate()?;
+
         Ok(app_config)
     }
 }
+
+impl ServerConfig {
+    fn validate(&self) -> Result<()> {
+        if !ENVIRONMENTS.contains(&self.environment.as_str()) {
+            anyhow::bail!("SERVER__ENVIRONMENT must be one of: {}", ENVIRONMENTS.join(", "));
+        }
+
+        if self.host.is_empty() {
+            anyhow::bail!("SERVER__HOST is required");
+        }
+
+        if self.shutdown_timeout == 0 || self.request_timeout == 0 {
+            anyhow::bail!("SERVER__SHUTDOWN_TIMEOUT and SERVER__REQUEST_TIMEOUT must be greater than 0");
+        }
+
+        if self.body_limit == 0 {
+            anyhow::bail!("SERVER__BODY_LIMIT must be greater than 0");
+        }
+
+        // Browsers reject credentialed responses with `Access-Control-Allow-Origin: *`
+        if self.cors.allows_any() {
+            if self.cors.allow_credentials {
+                anyhow::bail!("SERVER__CORS__ALLOWED_ORIGINS=* cannot be combined with SERVER__CORS__ALLOW_CREDENTIALS=true");
+            }
+            if self.environment == "production" {
+                anyhow::bail!("SERVER__CORS__ALLOWED_ORIGINS=* is not allowed in production");
+            }
+        }
+
+        for origin in self.cors.allowed_origins.iter().filter(|o| *o != "*") {
+            let valid = (origin.starts_with("http://") || origin.starts_with("https://"))
+                && !origin.ends_with('/')
+                && axum::http::HeaderValue::from_str(origin).is_ok();
+            if !valid {
+                anyhow::bail!("Invalid CORS origin '{}': expected scheme://host[:port] without a trailing slash", origin);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl StorageConfig {
+    fn validate(&self) -> Result<()> {
+        match self.backend.as_str() {
+            "local" => {
+                if self.local_path.is_empty() {
+                    anyhow::bail!("STORAGE__LOCAL_PATH is required for the local backend");
+                }
+            }
+            "s3" => {
+                let s3 = &self.s3;
+                if s3.endpoint.is_empty() || s3.bucket.is_empty() || s3.access_key.is_empty() || s3.secret_key.is_empty() {
+                    anyhow::bail!(
+                        "STORAGE__S3__ENDPOINT, STORAGE__S3__BUCKET, STORAGE__S3__ACCESS_KEY and STORAGE__S3__SECRET_KEY are required for the s3 backend"
+                    );
+                }
+                if !(s3.endpoint.starts_with("http://") || s3.endpoint.starts_with("https://")) {
+                    anyhow::bail!("STORAGE__S3__ENDPOINT must start with http:// or https://");
+                }
+            }
+            _ => anyhow::bail!("STORAGE__BACKEND must be local or s3"),
+        }
+
+        if self.max_file_size == 0 || self.url_ttl == 0 || self.cleanup_interval == 0 {
+            anyhow::bail!("STORAGE__MAX_FILE_SIZE, STORAGE__URL_TTL and STORAGE__CLEANUP_INTERVAL must be greater than 0");
+        }
+
+        if !self.quotas.contains_key("basic") {
+            anyhow::bail!("STORAGE__QUOTAS__BASIC is required");
+        }
+
+        Ok(())
+    }
+}
+
+impl EmailConfig {
+    fn validate(&self) -> Result<()> {
+        match self.transport.as_str() {
+            "smtp" => {
+                if self.smtp_host.is_empty() {
+                    anyhow::bail!("EMAIL__SMTP_HOST is required for the smtp transport");
+                }
+                if !["starttls", "tls", "none"].contains(&self.smtp_security.as_str()) {
+                    anyhow::bail!("EMAIL__SMTP_SECURITY must be starttls, tls or none");
+                }
+            }
+            "file" => {
+                if self.file_path.is_empty() {
+                    anyhow::bail!("EMAIL__FILE_PATH is required for the file transport");
+                }
+            }
+            _ => anyhow::bail!("EMAIL__TRANSPORT must be smtp or file"),
+        }
+
+        if self.from_email.parse::<lettre::Address>().is_err() {
+            anyhow::bail!("EMAIL__FROM_EMAIL must be an email address");
+        }
+
+        if crate::email::Locale::parse(&self.default_locale).is_none() {
+            anyhow::bail!("EMAIL__DEFAULT_LOCALE must be en or id");
+        }
+
+        if self.outbox_interval == 0 {
+            anyhow::bail!("EMAIL__OUTBOX_INTERVAL must be greater than 0");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Value;
+
+    /// A valid test configuration with `overrides` applied on top.
+    fn load(overrides: Vec<(&str, Value)>) -> Result<AppConfig> {
+        let base: Vec<(&str, Value)> = vec![
+            ("server.environment", "test".into()),
+            ("database.url", "postgres://unused".into()),
+            ("redis.url", "redis://unused".into()),
+            ("jwt.secret", "test-secret-that-is-long-enough-for-hs256".into()),
+            ("email.transport", "file".into()),
+            ("email.file_path", "/tmp/erp-mail".into()),
+            ("email.from_email", "erp@example.test".into()),
+        ];
+        let source = base
+            .into_iter()
+            .chain(overrides)
+            .try_fold(config::Config::builder(), |b, (key, value)| b.set_override(key, value))?
+            .build()?;
+        AppConfig::from_source(source)
+    }
+
+    fn origins(list: &[&str]) -> Value {
+        list.to_vec().into()
+    }
+
+    fn rejected(overrides: Vec<(&str, Value)>) -> String {
+        load(overrides).expect_err("configuration should be rejected").to_string()
+    }
+
+    #[test]
+    fn the_base_configuration_is_valid() {
+        let config = load(vec![]).unwrap();
+        assert!(config.server.cors.allowed_origins.is_empty());
+    }
+
+    #[test]
+    fn wildcard_cors_cannot_send_credentials() {
+        let error = rejected(vec![
+            ("server.cors.allowed_origins", origins(&["*"])),
+            ("server.cors.allow_credentials", true.into()),
+        ]);
+        assert!(error.contains("cannot be combined"), "{}", error);
+
+        // Without credentials it's fine outside production
+        load(vec![
+            ("server.cors.allowed_origins", origins(&["*"])),
+            ("server.cors.allow_credentials", false.into()),
+        ])
+        .unwrap();
+        let error = rejected(vec![
+            ("server.environment", "production".into()),
+            ("server.cors.allowed_origins", origins(&["*"])),
+            ("server.cors.allow_credentials", false.into()),
+        ]);
+        assert!(error.contains("not allowed in production"), "{}", error);
+    }
+
+    #[test]
+    fn malformed_origins_are_rejected() {
+        for origin in ["app.example.com", "https://app.example.com/", "ftp://app.example.com", "https://bad\nhost"] {
+            let error = rejected(vec![("server.cors.allowed_origins", origins(&["https://ok.example.com", origin]))]);
+            assert!(error.contains("Invalid CORS origin"), "{}: {}", origin, error);
+        }
+    }
+
+    #[test]
+    fn zero_limits_and_timeouts_are_rejected() {
+        let error = rejected(vec![("server.body_limit", 0.into())]);
+        assert!(error.contains("SERVER__BODY_LIMIT"), "{}", error);
+
+        for key in ["server.request_timeout", "server.shutdown_timeout"] {
+            let error = rejected(vec![(key, 0.into())]);
+            assert!(error.contains("must be greater than 0"), "{}: {}", key, error);
+        }
+    }
+
+    #[test]
+    fn in_flight_keys_must_outlive_the_request() {
+        for ttl in [10, 30] {
+            let error = rejected(vec![("server.request_timeout", 30.into()), ("idempotency.in_flight_ttl", ttl.into())]);
+            assert!(error.contains("IDEMPOTENCY__IN_FLIGHT_TTL"), "{}: {}", ttl, error);
+        }
+        load(vec![("server.request_timeout", 30.into()), ("idempotency.in_flight_ttl", 31.into())]).unwrap();
+    }
+
+    #[test]
+    fn each_environment_takes_an_explicit_allow_list() {
+        let allowed = ["https://app.example.com", "https://admin.example.com:8443"];
+        for environment in ENVIRONMENTS {
+            let config = load(vec![
+                ("server.environment", environment.into()),
+                ("server.cors.allowed_origins", origins(&allowed)),
+                ("server.cors.allow_credentials", true.into()),
+            ])
+            .unwrap_or_else(|e| panic!("{}: {}", environment, e));
+            assert_eq!(config.server.cors.allowed_origins, allowed);
+            assert!(config.server.cors.allow_credentials);
+        }
+    }
+
+    #[test]
+    fn development_falls_back_to_local_origins() {
+        let config = load(vec![
+            ("server.environment", "development".into()),
+            ("server.cors.allowed_origins", origins(&[""])),
+        ])
+        .unwrap();
+        assert_eq!(config.server.cors.allowed_origins, CorsConfig::DEV_ORIGINS);
+
+        let config = load(vec![("server.environment", "staging".into())]).unwrap();
+        assert!(config.server.cors.allowed_origins.is_empty());
+    }
+}
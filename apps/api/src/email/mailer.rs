@@ -0,0 +1,169 @@
+use lettre::{
+    message::{header::ContentType, Mailbox, MultiPart, SinglePart},
+    transport::smtp::authentication::Credentials,
+    AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use serde_json::{Map, Value};
+use shared_types::RenderedEmail;
+
+use super::{Brand, EmailTemplate, Locale, TemplateError, Templates};
+use crate::config::EmailConfig;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MailError {
+    #[error(transparent)]
+    Template(#[from] TemplateError),
+    #[error("Invalid email address: {0}")]
+    Address(#[from] lettre::address::AddressError),
+    #[error("Failed to build email: {0}")]
+    Message(#[from] lettre::error::Error),
+    #[error("SMTP error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("Failed to write email file: {0}")]
+    File(#[from] lettre::transport::file::Error),
+}
+
+impl MailError {
+    /// Whether sending again later could succeed, e.g. the SMTP server was unreachable.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            MailError::Smtp(e) => !e.is_permanent(),
+            MailError::File(_) => true,
+            _ => false,
+        }
+    }
+}
+
+enum Transport {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    File(AsyncFileTransport<Tokio1Executor>),
+}
+
+/// Renders templates and sends the result through the configured transport.
+pub struct Mailer {
+    templates: Templates,
+    transport: Transport,
+    from: Mailbox,
+    default_locale: Locale,
+    app_url: String,
+}
+
+impl Mailer {
+    pub fn from_config(config: &EmailConfig) -> anyhow::Result<Self> {
+        let transport = match config.transport.as_str() {
+            "file" => {
+                std::fs::create_dir_all(&config.file_path)?;
+                Transport::File(AsyncFileTransport::new(&config.file_path))
+            }
+            _ => {
+                let mut builder = match config.smtp_security.as_str() {
+                    "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.smtp_host)?,
+                    "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_host),
+                    _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)?,
+                }
+                .port(config.smtp_port);
+                if !config.smtp_username.is_empty() {
+                    builder = builder.credentials(Credentials::new(config.smtp_username.clone(), config.smtp_password.clone()));
+                }
+                Transport::Smtp(builder.build())
+            }
+        };
+
+        Ok(Self {
+            templates: Templates::new()?,
+            transport,
+            from: Mailbox::new(Some(config.from_name.clone()), config.from_email.parse()?),
+            default_locale: Locale::parse(&config.default_locale).unwrap_or(Locale::En),
+            app_url: config.app_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    pub fn templates(&self) -> &Templates {
+        &self.templates
+    }
+
+    /// Language for tenants that have not chosen one.
+    pub fn default_locale(&self) -> Locale {
+        self.default_locale
+    }
+
+    /// Absolute frontend URL for `path`, e.g. `/invitations/accept?token=...`.
+    pub fn link(&self, path: &str) -> String {
+        format!("{}{}", self.app_url, path)
+    }
+
+    pub async fn send_template(
+        &self,
+        to: Mailbox,
+        template: EmailTemplate,
+        locale: Locale,
+        brand: &Brand,
+        variables: &Map<String, Value>,
+    ) -> Result<(), MailError> {
+        let email = self.templates.render(template, locale, brand, variables)?;
+        self.send(to, &email).await
+    }
+
+    pub async fn send(&self, to: Mailbox, email: &RenderedEmail) -> Result<(), MailError> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&email.subject)
+            .multipart(
+                MultiPart::alternative()
+                    .singlepart(SinglePart::builder().header(ContentType::TEXT_PLAIN).body(email.text.clone()))
+                    .singlepart(SinglePart::builder().header(ContentType::TEXT_HTML).body(email.html.clone())),
+            )?;
+
+        match &self.transport {
+            Transport::Smtp(smtp) => {
+                smtp.send(message).await?;
+            }
+            Transport::File(file) => {
+                file.send(message).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared_types::EmailBranding;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn file_transport_writes_a_multipart_message() {
+        let dir = std::env::temp_dir().join(format!("erp-mail-test-{}", Uuid::new_v4()));
+        let config = EmailConfig {
+            transport: "file".into(),
+            smtp_host: String::new(),
+            smtp_port: 0,
+            smtp_username: String::new(),
+            smtp_password: String::new(),
+            smtp_security: "none".into(),
+            file_path: dir.to_string_lossy().into_owned(),
+            from_email: "noreply@erp.test".into(),
+            from_name: "ERP".into(),
+            app_url: "https://app.erp.test/".into(),
+            default_locale: "id".into(),
+            outbox_interval: 30,
+        };
+        let mailer = Mailer::from_config(&config).unwrap();
+        assert_eq!(mailer.link("/notifications"), "https://app.erp.test/notifications");
+
+        let brand = Brand::new("PT Maju Jaya", &EmailBranding::default(), mailer.default_locale());
+        let to: Mailbox = "Siti <siti@erp.test>".parse().unwrap();
+        let template = EmailTemplate::EmailVerification;
+        mailer.send_template(to, template, mailer.default_locale(), &brand, &template.sample()).await.unwrap();
+
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(files.len(), 1);
+        let eml = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(eml.contains("Subject: Verifikasi alamat email Anda"));
+        assert!(eml.contains("multipart/alternative"));
+        assert!(eml.contains("To: Siti <siti@erp.test>"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,409 @@
+//! Transactional email.
+//!
+//! Every template has an HTML and a plain-text version per [`Locale`], compiled
+//! into the binary from `templates/email`. Both extend a shared layout that
+//! shows the tenant's [`Brand`]. Callers pass template variables as JSON;
+//! [`Templates::render`] rejects missing and unknown ones before anything is
+//! rendered, so a typo fails loudly instead of sending an email with a gap.
+//! [`Mailer`] sends the result over SMTP, or writes `.eml` files for tests and
+//! local development.
+
+mod mailer;
+mod outbox;
+
+use minijinja::{context, AutoEscape, Environment, UndefinedBehavior};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use shared_types::{EmailBranding, EmailTemplateInfo, RenderedEmail};
+use sqlx::{PgConnection, Row};
+use uuid::Uuid;
+
+pub use mailer::Mailer;
+pub use outbox::spawn_outbox;
+pub use shared_types::Locale;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailTemplate {
+    Invitation,
+    PasswordReset,
+    EmailVerification,
+    PurchaseOrderSent,
+    Payslip,
+    /// Email copy of an in-app notification
+    Notification,
+}
+
+impl EmailTemplate {
+    pub const ALL: [EmailTemplate; 6] = [
+        EmailTemplate::Invitation,
+        EmailTemplate::PasswordReset,
+        EmailTemplate::EmailVerification,
+        EmailTemplate::PurchaseOrderSent,
+        EmailTemplate::Payslip,
+        EmailTemplate::Notification,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EmailTemplate::Invitation => "invitation",
+            EmailTemplate::PasswordReset => "password_reset",
+            EmailTemplate::EmailVerification => "email_verification",
+            EmailTemplate::PurchaseOrderSent => "purchase_order_sent",
+            EmailTemplate::Payslip => "payslip",
+            EmailTemplate::Notification => "notification",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.name() == s)
+    }
+
+    pub fn required(&self) -> &'static [&'static str] {
+        match self {
+            EmailTemplate::Invitation => &["name", "inviter_name", "invite_url", "expires_at"],
+            EmailTemplate::PasswordReset => &["name", "reset_url", "expires_in_minutes"],
+            EmailTemplate::EmailVerification => &["name", "verify_url"],
+            EmailTemplate::PurchaseOrderSent => &["vendor_name", "po_number", "order_date", "total", "currency"],
+            EmailTemplate::Payslip => {
+                &["employee_name", "period", "gross_pay", "deductions", "net_pay", "currency", "pay_date"]
+            }
+            EmailTemplate::Notification => &["title", "body"],
+        }
+    }
+
+    pub fn optional(&self) -> &'static [&'static str] {
+        match self {
+            EmailTemplate::Invitation => &["role"],
+            EmailTemplate::PasswordReset | EmailTemplate::EmailVerification => &[],
+            EmailTemplate::PurchaseOrderSent => &["contact_name", "expected_delivery_date", "notes"],
+            EmailTemplate::Payslip => &["download_url"],
+            EmailTemplate::Notification => &["action_url"],
+        }
+    }
+
+    fn subject(&self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (EmailTemplate::Invitation, Locale::En) => "{{ inviter_name }} invited you to {{ brand.company_name }}",
+            (EmailTemplate::Invitation, Locale::Id) => "{{ inviter_name }} mengundang Anda ke {{ brand.company_name }}",
+            (EmailTemplate::PasswordReset, Locale::En) => "Reset your {{ brand.company_name }} password",
+            (EmailTemplate::PasswordReset, Locale::Id) => "Atur ulang kata sandi {{ brand.company_name }} Anda",
+            (EmailTemplate::EmailVerification, Locale::En) => "Verify your email address",
+            (EmailTemplate::EmailVerification, Locale::Id) => "Verifikasi alamat email Anda",
+            (EmailTemplate::PurchaseOrderSent, Locale::En) => "Purchase order {{ po_number }} from {{ brand.company_name }}",
+            (EmailTemplate::PurchaseOrderSent, Locale::Id) => "Pesanan pembelian {{ po_number }} dari {{ brand.company_name }}",
+            (EmailTemplate::Payslip, Locale::En) => "Your payslip for {{ period }}",
+            (EmailTemplate::Payslip, Locale::Id) => "Slip gaji Anda periode {{ period }}",
+            (EmailTemplate::Notification, _) => "{{ title }}",
+        }
+    }
+
+    /// Example variables, for previews.
+    pub fn sample(&self) -> Map<String, Value> {
+        let sample = match self {
+            EmailTemplate::Invitation => json!({
+                "name": "Siti Rahayu",
+                "inviter_name": "Budi Santoso",
+                "invite_url": "https://app.example.com/invitations/accept?token=sample",
+                "expires_at": "2026-01-31",
+                "role": "admin",
+            }),
+            EmailTemplate::PasswordReset => json!({
+                "name": "Siti Rahayu",
+                "reset_url": "https://app.example.com/reset-password?token=sample",
+                "expires_in_minutes": 60,
+            }),
+            EmailTemplate::EmailVerification => json!({
+                "name": "Siti Rahayu",
+                "verify_url": "https://app.example.com/verify-email?token=sample",
+            }),
+            EmailTemplate::PurchaseOrderSent => json!({
+                "vendor_name": "PT Sumber Makmur",
+                "contact_name": "Andi Wijaya",
+                "po_number": "PO-202601-000123",
+                "order_date": "2026-01-15",
+                "expected_delivery_date": "2026-01-22",
+                "total": "12.500.000,00",
+                "currency": "IDR",
+            }),
+            EmailTemplate::Payslip => json!({
+                "employee_name": "Siti Rahayu",
+                "period": "Januari 2026",
+                "gross_pay": "9.500.000,00",
+                "deductions": "650.000,00",
+                "net_pay": "8.850.000,00",
+                "currency": "IDR",
+                "pay_date": "2026-01-25",
+            }),
+            EmailTemplate::Notification => json!({
+                "title": "Low stock: Kertas A4",
+                "body": "Kertas A4 (ATK-001) is down to 4 units; the minimum is 10.",
+                "action_url": "https://app.example.com/notifications",
+            }),
+        };
+        match sample {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        }
+    }
+
+    pub fn info(&self) -> EmailTemplateInfo {
+        EmailTemplateInfo {
+            name: self.name().to_string(),
+            locales: Locale::ALL.iter().map(|l| l.as_str().to_string()).collect(),
+            required: self.required().iter().map(|v| v.to_string()).collect(),
+            optional: self.optional().iter().map(|v| v.to_string()).collect(),
+        }
+    }
+}
+
+/// The tenant's branding with defaults filled in, as templates see it (`brand.*`).
+#[derive(Debug, Clone, Serialize)]
+pub struct Brand {
+    pub company_name: String,
+    pub logo_url: Option<String>,
+    pub primary_color: String,
+    pub background_color: String,
+    pub footer: String,
+}
+
+impl Brand {
+    const PRIMARY_COLOR: &'static str = "#1f6feb";
+    const BACKGROUND_COLOR: &'static str = "#f5f7fa";
+
+    pub fn new(company_name: &str, branding: &EmailBranding, locale: Locale) -> Self {
+        let footer = branding.footer.clone().unwrap_or_else(|| match locale {
+            Locale::En => format!("This email was sent by {}.", company_name),
+            Locale::Id => format!("Email ini dikirim oleh {}.", company_name),
+        });
+        Self {
+            company_name: company_name.to_string(),
+            logo_url: branding.logo_url.clone(),
+            primary_color: branding.primary_color.clone().unwrap_or_else(|| Self::PRIMARY_COLOR.to_string()),
+            background_color: branding.background_color.clone().unwrap_or_else(|| Self::BACKGROUND_COLOR.to_string()),
+            footer,
+        }
+    }
+}
+
+/// Email settings a tenant keeps in `tenants.settings`.
+#[derive(Debug, Clone)]
+pub struct TenantEmailSettings {
+    pub company_name: String,
+    pub branding: EmailBranding,
+    /// `settings.locale`, when set to a supported language
+    pub locale: Option<Locale>,
+}
+
+impl TenantEmailSettings {
+    /// Settings as stored; unreadable branding falls back to the defaults.
+    pub fn from_parts(company_name: String, settings: &Value) -> Self {
+        Self {
+            company_name,
+            branding: settings
+                .get("branding")
+                .and_then(|b| serde_json::from_value(b.clone()).ok())
+                .unwrap_or_default(),
+            locale: settings.get("locale").and_then(Value::as_str).and_then(Locale::parse),
+        }
+    }
+
+    pub async fn load(conn: &mut PgConnection, tenant_id: Uuid) -> Result<Self, sqlx::Error> {
+        let row = sqlx::query("SELECT name, settings FROM tenants WHERE id = $1")
+            .bind(tenant_id)
+            .fetch_one(conn)
+            .await?;
+        Ok(Self::from_parts(row.get("name"), &row.get::<Value, _>("settings")))
+    }
+
+    pub fn brand(&self, locale: Locale) -> Brand {
+        Brand::new(&self.company_name, &self.branding, locale)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("Missing template variables: {}", .0.join(", "))]
+    MissingVariables(Vec<String>),
+    #[error("Unknown template variables: {}", .0.join(", "))]
+    UnknownVariables(Vec<String>),
+    #[error("Failed to render template: {0}")]
+    Render(#[from] minijinja::Error),
+}
+
+macro_rules! embed {
+    ($env:ident, $($name:literal),* $(,)?) => {
+        $(
+            $env.add_template(concat!("en/", $name, ".html"), include_str!(concat!("../../templates/email/en/", $name, ".html")))?;
+            $env.add_template(concat!("en/", $name, ".txt"), include_str!(concat!("../../templates/email/en/", $name, ".txt")))?;
+            $env.add_template(concat!("id/", $name, ".html"), include_str!(concat!("../../templates/email/id/", $name, ".html")))?;
+            $env.add_template(concat!("id/", $name, ".txt"), include_str!(concat!("../../templates/email/id/", $name, ".txt")))?;
+        )*
+    };
+}
+
+/// The compiled email templates.
+pub struct Templates {
+    env: Environment<'static>,
+}
+
+impl Templates {
+    pub fn new() -> Result<Self, minijinja::Error> {
+        let mut env = Environment::new();
+        // A variable the catalog doesn't declare is a template bug; fail instead of rendering blanks
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.set_auto_escape_callback(|name| {
+            if name.ends_with(".html") {
+                AutoEscape::Html
+            } else {
+                AutoEscape::None
+            }
+        });
+
+        env.add_template("layout.html", include_str!("../../templates/email/layout.html"))?;
+        env.add_template("layout.txt", include_str!("../../templates/email/layout.txt"))?;
+        env.add_template("button.html", include_str!("../../templates/email/button.html"))?;
+        embed!(env, "invitation", "password_reset", "email_verification", "purchase_order_sent", "payslip", "notification");
+        for template in EmailTemplate::ALL {
+            for locale in Locale::ALL {
+                let name = format!("{}/{}.subject", locale.as_str(), template.name());
+                env.add_template_owned(name, template.subject(locale))?;
+            }
+        }
+        Ok(Self { env })
+    }
+
+    /// Check `variables` against the template's catalog entry.
+    pub fn validate(template: EmailTemplate, variables: &Map<String, Value>) -> Result<(), TemplateError> {
+        let missing: Vec<String> = template
+            .required()
+            .iter()
+            .filter(|name| variables.get(**name).is_none_or(Value::is_null))
+            .map(|name| name.to_string())
+            .collect();
+        if !missing.is_empty() {
+            return Err(TemplateError::MissingVariables(missing));
+        }
+
+        let mut unknown: Vec<String> = variables
+            .keys()
+            .filter(|name| !template.required().contains(&name.as_str()) && !template.optional().contains(&name.as_str()))
+            .cloned()
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            return Err(TemplateError::UnknownVariables(unknown));
+        }
+        Ok(())
+    }
+
+    pub fn render(
+        &self,
+        template: EmailTemplate,
+        locale: Locale,
+        brand: &Brand,
+        variables: &Map<String, Value>,
+    ) -> Result<RenderedEmail, TemplateError> {
+        Self::validate(template, variables)?;
+        // Optional variables given as null count as not given
+        let variables: Map<String, Value> = variables.iter().filter(|(_, v)| !v.is_null()).map(|(k, v)| (k.clone(), v.clone())).collect();
+
+        let prefix = format!("{}/{}", locale.as_str(), template.name());
+        let ctx = context! { brand => brand, locale => locale.as_str(), ..minijinja::Value::from_serialize(&variables) };
+        let subject = self.env.get_template(&format!("{}.subject", prefix))?.render(&ctx)?;
+        let subject = subject.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        let ctx = context! { subject => &subject, ..ctx };
+        let html = self.env.get_template(&format!("{}.html", prefix))?.render(&ctx)?;
+        let text = self.env.get_template(&format!("{}.txt", prefix))?.render(&ctx)?;
+        Ok(RenderedEmail { subject, html, text: text.trim().to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brand(locale: Locale) -> Brand {
+        Brand::new("PT Maju Jaya", &EmailBranding::default(), locale)
+    }
+
+    #[test]
+    fn every_template_renders_its_sample_in_every_locale() {
+        let templates = Templates::new().unwrap();
+        for template in EmailTemplate::ALL {
+            for locale in Locale::ALL {
+                let email = templates.render(template, locale, &brand(locale), &template.sample()).unwrap();
+                assert!(!email.subject.is_empty(), "{} {}", template.name(), locale.as_str());
+                assert!(email.html.contains("PT Maju Jaya"), "{} {}", template.name(), locale.as_str());
+                assert!(email.text.contains("PT Maju Jaya"), "{} {}", template.name(), locale.as_str());
+            }
+        }
+    }
+
+    #[test]
+    fn variables_are_checked_against_the_catalog() {
+        let templates = Templates::new().unwrap();
+        let mut variables = EmailTemplate::PasswordReset.sample();
+        variables.remove("reset_url");
+        variables.insert("reset_link".into(), json!("https://example.com"));
+        match templates.render(EmailTemplate::PasswordReset, Locale::En, &brand(Locale::En), &variables) {
+            Err(TemplateError::MissingVariables(missing)) => assert_eq!(missing, vec!["reset_url"]),
+            other => panic!("expected missing variables, got {:?}", other),
+        }
+
+        variables.insert("reset_url".into(), json!("https://example.com"));
+        match Templates::validate(EmailTemplate::PasswordReset, &variables) {
+            Err(TemplateError::UnknownVariables(unknown)) => assert_eq!(unknown, vec!["reset_link"]),
+            other => panic!("expected unknown variables, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn html_is_escaped_and_text_is_not() {
+        let templates = Templates::new().unwrap();
+        let mut variables = EmailTemplate::Notification.sample();
+        variables.insert("body".into(), json!("Stock of <Kertas> & tinta"));
+        let email = templates.render(EmailTemplate::Notification, Locale::Id, &brand(Locale::Id), &variables).unwrap();
+        assert!(email.html.contains("Stock of &lt;Kertas&gt; &amp; tinta"));
+        assert!(email.text.contains("Stock of <Kertas> & tinta"));
+        assert!(email.text.contains("Buka: https://app.example.com/notifications"));
+    }
+
+    #[test]
+    fn branding_overrides_defaults_and_optional_blocks_follow_variables() {
+        let templates = Templates::new().unwrap();
+        let branding = EmailBranding {
+            logo_url: Some("https://cdn.example.com/logo.png".into()),
+            primary_color: Some("#aa0000".into()),
+            background_color: None,
+            footer: Some("Jl. Sudirman 1, Jakarta".into()),
+        };
+        let brand = Brand::new("PT Maju Jaya", &branding, Locale::En);
+        let mut variables = EmailTemplate::Invitation.sample();
+        variables.remove("role");
+        let email = templates.render(EmailTemplate::Invitation, Locale::En, &brand, &variables).unwrap();
+
+        assert_eq!(email.subject, "Budi Santoso invited you to PT Maju Jaya");
+        // minijinja also escapes `/`, which browsers decode in attributes
+        assert!(email.html.contains(r#"<img src="https:&#x2f;&#x2f;cdn.example.com&#x2f;logo.png""#));
+        assert!(email.html.contains("background-color:#aa0000"));
+        assert!(email.html.contains("background-color:#f5f7fa"));
+        assert!(email.text.ends_with("Jl. Sudirman 1, Jakarta"));
+        assert!(!email.text.contains(" as "));
+    }
+
+    #[test]
+    fn locales_parse_from_language_tags() {
+        assert_eq!(Locale::parse("id-ID"), Some(Locale::Id));
+        assert_eq!(Locale::parse("EN_us"), Some(Locale::En));
+        assert_eq!(Locale::parse("fr"), None);
+    }
+
+    #[test]
+    fn tenant_settings_tolerate_missing_or_invalid_branding() {
+        let settings = TenantEmailSettings::from_parts("Acme".into(), &json!({ "branding": "oops", "locale": "id" }));
+        assert_eq!(settings.branding, EmailBranding::default());
+        assert_eq!(settings.locale, Some(Locale::Id));
+        assert_eq!(settings.brand(Locale::Id).footer, "Email ini dikirim oleh Acme.");
+    }
+}
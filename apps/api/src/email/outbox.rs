@@ -0,0 +1,121 @@
+use std::{sync::Arc, time::Duration};
+
+use lettre::message::Mailbox;
+use serde_json::{json, Map, Value};
+use sqlx::{PgPool, Row};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{EmailTemplate, Mailer, TenantEmailSettings};
+use crate::state::AppState;
+
+/// Notification emails sent per transaction
+const BATCH_SIZE: i64 = 50;
+
+/// Email the notifications whose recipients asked for an email copy
+/// (`email_status = 'pending'`).
+pub fn spawn_outbox(state: &AppState) {
+    let pool = state.db_pool.clone();
+    let mailer = state.mailer.clone();
+    let interval = Duration::from_secs(state.config.email.outbox_interval);
+    state.workers.spawn("email-outbox", move |shutdown| run(pool, mailer, interval, shutdown));
+}
+
+async fn run(pool: PgPool, mailer: Arc<Mailer>, interval: Duration, shutdown: CancellationToken) {
+    loop {
+        loop {
+            match send_batch(&pool, &mailer).await {
+                Ok(None) => break,
+                Ok(Some(0)) => {}
+                Ok(Some(sent)) => info!(sent, "Sent notification emails"),
+                Err(e) => {
+                    warn!("Notification email batch failed: {}", e);
+                    break;
+                }
+            }
+            if shutdown.is_cancelled() {
+                return;
+            }
+        }
+        tokio::select! {
+            _ = shutdown.cancelled() => return,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+/// Send one batch; rows stay locked until their outcome is recorded, so replicas
+/// never email the same notification twice. Returns how many were sent, or
+/// `None` when nothing is pending or delivery is unavailable for now.
+async fn send_batch(pool: &PgPool, mailer: &Mailer) -> Result<Option<usize>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let rows = sqlx::query(
+        r#"SELECT n.id, n.title, n.body, u.email, u.first_name, u.last_name, t.name AS tenant_name, t.settings
+           FROM notifications n
+           JOIN users u ON u.id = n.user_id
+           JOIN tenants t ON t.id = n.tenant_id
+           WHERE n.email_status = 'pending'
+           ORDER BY n.created_at
+           LIMIT $1
+           FOR UPDATE OF n SKIP LOCKED"#,
+    )
+    .bind(BATCH_SIZE)
+    .fetch_all(&mut *tx)
+    .await?;
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let mut sent: Vec<Uuid> = Vec::new();
+    let mut failed: Vec<Uuid> = Vec::new();
+    let mut interrupted = false;
+    for row in &rows {
+        let id: Uuid = row.get("id");
+        let tenant = TenantEmailSettings::from_parts(row.get("tenant_name"), &row.get::<Value, _>("settings"));
+        let locale = tenant.locale.unwrap_or(mailer.default_locale());
+        let mut variables = Map::new();
+        variables.insert("title".into(), json!(row.get::<String, _>("title")));
+        variables.insert("body".into(), json!(row.get::<String, _>("body")));
+        variables.insert("action_url".into(), json!(mailer.link("/notifications")));
+
+        let result = match recipient(row) {
+            Ok(to) => mailer.send_template(to, EmailTemplate::Notification, locale, &tenant.brand(locale), &variables).await,
+            Err(e) => Err(e.into()),
+        };
+        match result {
+            Ok(()) => sent.push(id),
+            Err(e) if e.is_transient() => {
+                warn!(notification_id = %id, "Email delivery unavailable, retrying later: {}", e);
+                interrupted = true;
+                break;
+            }
+            Err(e) => {
+                warn!(notification_id = %id, "Failed to email notification: {}", e);
+                failed.push(id);
+            }
+        }
+    }
+
+    sqlx::query(
+        r#"UPDATE notifications SET email_status = CASE WHEN id = ANY($1) THEN 'sent' ELSE 'failed' END
+           WHERE id = ANY($1) OR id = ANY($2)"#,
+    )
+    .bind(&sent)
+    .bind(&failed)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok((!interrupted).then_some(sent.len()))
+}
+
+fn recipient(row: &sqlx::postgres::PgRow) -> Result<Mailbox, lettre::address::AddressError> {
+    let name = format!(
+        "{} {}",
+        row.get::<Option<String>, _>("first_name").unwrap_or_default(),
+        row.get::<Option<String>, _>("last_name").unwrap_or_default()
+    );
+    let name = name.trim();
+    Ok(Mailbox::new((!name.is_empty()).then(|| name.to_string()), row.get::<String, _>("email").parse()?))
+}
@@ -0,0 +1,77 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+
+use super::NewRate;
+use crate::{config::ExchangeRatesConfig, imports};
+
+/// A source of published rates, e.g. a central bank feed.
+#[async_trait::async_trait]
+pub trait RateFetcher: Send + Sync {
+    /// Rates published for `date`; a day without any is not an error.
+    async fn fetch(&self, date: NaiveDate) -> anyhow::Result<Vec<NewRate>>;
+}
+
+/// Source selected by `exchange_rates.fetcher`.
+pub fn from_config(config: &ExchangeRatesConfig) -> Arc<dyn RateFetcher> {
+    Arc::new(FileRateFetcher::new(&config.file_path))
+}
+
+/// Reads a CSV or XLSX file laid out like an `exchange_rates` import, such as
+/// one a scheduled job downloads, and keeps the rows of the requested day.
+pub struct FileRateFetcher {
+    path: PathBuf,
+}
+
+impl FileRateFetcher {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self { path: path.as_ref().to_path_buf() }
+    }
+}
+
+#[async_trait::async_trait]
+impl RateFetcher for FileRateFetcher {
+    async fn fetch(&self, date: NaiveDate) -> anyhow::Result<Vec<NewRate>> {
+        let bytes = tokio::fs::read(&self.path)
+            .await
+            .with_context(|| format!("Cannot read exchange rate file {}", self.path.display()))?;
+        let file_name = self.path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let rates = imports::read_exchange_rates(file_name, &bytes)
+            .with_context(|| format!("Invalid exchange rate file {}", self.path.display()))?;
+        Ok(rates.into_iter().filter(|rate| rate.rate_date == date).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared_types::{Currency, RateType};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn file_fetcher_keeps_the_rows_of_the_day() {
+        let path = std::env::temp_dir().join(format!("erp-rates-test-{}.csv", Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            "from_currency,to_currency,rate_date,rate,rate_type\n\
+             USD,IDR,2024-03-28,15950,spot\n\
+             USD,IDR,2024-03-29,16000,\n\
+             SGD,IDR,29/03/2024,\"11.850,5\",tax\n",
+        )
+        .unwrap();
+
+        let fetcher = FileRateFetcher::new(&path);
+        let rates = fetcher.fetch(NaiveDate::from_ymd_opt(2024, 3, 29).unwrap()).await.unwrap();
+        assert_eq!(rates.len(), 2);
+        assert_eq!((rates[0].from_currency, rates[0].rate_type, rates[0].rate), (Currency::USD, RateType::Spot, 16000.into()));
+        assert_eq!((rates[1].from_currency, rates[1].rate_type), (Currency::SGD, RateType::Tax));
+        assert_eq!(rates[1].rate.to_string(), "11850.5");
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(fetcher.fetch(NaiveDate::MIN).await.is_err());
+    }
+}
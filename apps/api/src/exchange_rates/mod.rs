@@ -0,0 +1,278 @@
+//! Exchange rates and currency conversion.
+//!
+//! Each tenant keeps its own daily rates per currency pair and [`RateType`].
+//! The rate for a pair on a day is found in this order:
+//!
+//! 1. the same currency converts at 1;
+//! 2. the latest rate of the pair, or the reciprocal of the latest rate of the
+//!    opposite pair, dated on or before the day and at most
+//!    `exchange_rates.max_age_days` old; the newer of the two wins, the direct
+//!    rate on a tie;
+//! 3. failing both, the rates of both currencies against the tenant's base
+//!    currency (`settings.base_currency`, IDR by default), chained.
+
+mod fetcher;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use shared_types::{ConversionMethod, Currency, ExchangeRate, Money, MoneyError, RateType};
+use sqlx::{postgres::PgRow, PgConnection, Row};
+use uuid::Uuid;
+
+pub use fetcher::{from_config, FileRateFetcher, RateFetcher};
+
+/// Decimals of a computed rate, the same as the `rate` column
+const RATE_SCALE: u32 = 10;
+
+pub const EXCHANGE_RATE_COLUMNS: &str =
+    "id, tenant_id, from_currency, to_currency, rate_type, rate_date, rate, source, created_by, created_at, updated_at";
+
+pub fn exchange_rate_from_row(row: &PgRow) -> ExchangeRate {
+    ExchangeRate {
+        id: row.get("id"),
+        tenant_id: row.get("tenant_id"),
+        from_currency: row.get("from_currency"),
+        to_currency: row.get("to_currency"),
+        rate_type: RateType::parse(row.get("rate_type")).unwrap_or_default(),
+        rate_date: row.get("rate_date"),
+        rate: row.get::<Decimal, _>("rate").normalize(),
+        source: row.get("source"),
+        created_by: row.try_get("created_by").unwrap_or(None),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+/// A rate to store, from the API, an import or a fetcher.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewRate {
+    pub from_currency: Currency,
+    pub to_currency: Currency,
+    pub rate_type: RateType,
+    pub rate_date: NaiveDate,
+    pub rate: Decimal,
+}
+
+/// Insert `rate`, or replace the rate with the same pair, type and date.
+pub async fn upsert(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    rate: &NewRate,
+    source: &str,
+    created_by: Option<Uuid>,
+) -> Result<ExchangeRate, sqlx::Error> {
+    let row = sqlx::query(&format!(
+        r#"INSERT INTO exchange_rates (tenant_id, from_currency, to_currency, rate_type, rate_date, rate, source, created_by)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+           ON CONFLICT (tenant_id, from_currency, to_currency, rate_type, rate_date)
+           DO UPDATE SET rate = EXCLUDED.rate, source = EXCLUDED.source, created_by = EXCLUDED.created_by
+           RETURNING {}"#,
+        EXCHANGE_RATE_COLUMNS
+    ))
+    .bind(tenant_id)
+    .bind(rate.from_currency)
+    .bind(rate.to_currency)
+    .bind(rate.rate_type.as_str())
+    .bind(rate.rate_date)
+    .bind(rate.rate)
+    .bind(source)
+    .bind(created_by)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(exchange_rate_from_row(&row))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RateError {
+    #[error("No {rate_type} exchange rate from {from} to {to} on or up to {max_age_days} days before {on}")]
+    Missing { from: Currency, to: Currency, rate_type: &'static str, on: NaiveDate, max_age_days: u32 },
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+}
+
+/// The rate that applies to a pair on a day, and how it was found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EffectiveRate {
+    pub rate: Decimal,
+    /// Date of the stored rate; the older leg's when triangulated
+    pub rate_date: NaiveDate,
+    pub method: ConversionMethod,
+    pub via: Option<Currency>,
+}
+
+impl EffectiveRate {
+    pub fn convert(&self, amount: Money, to: Currency) -> Result<Money, MoneyError> {
+        amount.convert(to, self.rate)
+    }
+}
+
+/// The latest stored rate of one directed pair.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quote {
+    pub from: Currency,
+    pub to: Currency,
+    pub rate: Decimal,
+    pub rate_date: NaiveDate,
+}
+
+/// Rate from `from` to `to` out of `quotes`, which hold at most one quote per
+/// directed pair, all within the lookup window; see the module docs for the order.
+pub fn resolve(quotes: &[Quote], from: Currency, to: Currency, on: NaiveDate, base: Currency) -> Option<EffectiveRate> {
+    if from == to {
+        return Some(EffectiveRate { rate: Decimal::ONE, rate_date: on, method: ConversionMethod::Identity, via: None });
+    }
+    if let Some(rate) = leg(quotes, from, to) {
+        return Some(EffectiveRate { rate: rate.rate.round_dp(RATE_SCALE), ..rate });
+    }
+    if from == base || to == base {
+        return None;
+    }
+    let first = leg(quotes, from, base)?;
+    let second = leg(quotes, base, to)?;
+    Some(EffectiveRate {
+        rate: (first.rate * second.rate).round_dp(RATE_SCALE),
+        rate_date: first.rate_date.min(second.rate_date),
+        method: ConversionMethod::Triangulated,
+        via: Some(base),
+    })
+}
+
+/// A stored rate of the pair, or the reciprocal of the opposite pair's,
+/// unrounded so that chaining two legs loses no precision.
+fn leg(quotes: &[Quote], from: Currency, to: Currency) -> Option<EffectiveRate> {
+    let direct = quotes.iter().find(|q| q.from == from && q.to == to);
+    let opposite = quotes.iter().find(|q| q.from == to && q.to == from);
+    let inverse = |q: &Quote| EffectiveRate {
+        rate: Decimal::ONE / q.rate,
+        rate_date: q.rate_date,
+        method: ConversionMethod::Inverse,
+        via: None,
+    };
+    match (direct, opposite) {
+        (Some(d), Some(o)) if o.rate_date > d.rate_date => Some(inverse(o)),
+        (Some(d), _) => Some(EffectiveRate { rate: d.rate, rate_date: d.rate_date, method: ConversionMethod::Direct, via: None }),
+        (None, Some(o)) => Some(inverse(o)),
+        (None, None) => None,
+    }
+}
+
+/// The currency a tenant keeps its books in: `settings.base_currency`, IDR by default.
+pub async fn base_currency(conn: &mut PgConnection, tenant_id: Uuid) -> Result<Currency, sqlx::Error> {
+    let code = sqlx::query_scalar::<_, Option<String>>("SELECT settings->>'base_currency' FROM tenants WHERE id = $1")
+        .bind(tenant_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .flatten();
+    Ok(code.as_deref().and_then(Currency::parse).unwrap_or(Currency::IDR))
+}
+
+/// The rate from `from` to `to` on `on`.
+pub async fn effective_rate(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    from: Currency,
+    to: Currency,
+    on: NaiveDate,
+    rate_type: RateType,
+    max_age_days: u32,
+) -> Result<EffectiveRate, RateError> {
+    let missing = || RateError::Missing { from, to, rate_type: rate_type.as_str(), on, max_age_days };
+    if from == to {
+        return resolve(&[], from, to, on, from).ok_or_else(missing);
+    }
+    let base = base_currency(conn, tenant_id).await?;
+
+    let currencies: Vec<&str> = vec![from.code(), to.code(), base.code()];
+    let rows = sqlx::query(
+        r#"SELECT DISTINCT ON (from_currency, to_currency) from_currency, to_currency, rate, rate_date
+           FROM exchange_rates
+           WHERE tenant_id = $1 AND rate_type = $2
+             AND rate_date <= $3 AND rate_date >= $3 - $4::INTEGER
+             AND from_currency = ANY($5) AND to_currency = ANY($5)
+           ORDER BY from_currency, to_currency, rate_date DESC"#,
+    )
+    .bind(tenant_id)
+    .bind(rate_type.as_str())
+    .bind(on)
+    .bind(max_age_days as i32)
+    .bind(currencies)
+    .fetch_all(&mut *conn)
+    .await?;
+    let quotes: Vec<Quote> = rows
+        .iter()
+        .map(|row| Quote {
+            from: row.get("from_currency"),
+            to: row.get("to_currency"),
+            rate: row.get("rate"),
+            rate_date: row.get("rate_date"),
+        })
+        .collect();
+
+    resolve(&quotes, from, to, on, base).ok_or_else(missing)
+}
+
+/// Rate for a document in `currency` dated `on`: the spot rate into the
+/// tenant's base currency.
+pub async fn document_rate(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    currency: Currency,
+    on: NaiveDate,
+    max_age_days: u32,
+) -> Result<Decimal, RateError> {
+    let base = base_currency(conn, tenant_id).await?;
+    let effective = effective_rate(conn, tenant_id, currency, base, on, RateType::Spot, max_age_days).await?;
+    Ok(effective.rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, d).unwrap()
+    }
+
+    fn quote(from: Currency, to: Currency, rate: &str, d: u32) -> Quote {
+        Quote { from, to, rate: rate.parse().unwrap(), rate_date: day(d) }
+    }
+
+    #[test]
+    fn same_currency_is_identity() {
+        let rate = resolve(&[], Currency::USD, Currency::USD, day(31), Currency::IDR).unwrap();
+        assert_eq!(rate.rate, Decimal::ONE);
+        assert_eq!(rate.method, ConversionMethod::Identity);
+    }
+
+    #[test]
+    fn direct_rates_win_ties_and_newer_inverses_win_otherwise() {
+        let quotes = [quote(Currency::USD, Currency::IDR, "16000", 28), quote(Currency::IDR, Currency::USD, "0.0000625", 28)];
+        let rate = resolve(&quotes, Currency::USD, Currency::IDR, day(31), Currency::IDR).unwrap();
+        assert_eq!((rate.rate, rate.method), ("16000".parse().unwrap(), ConversionMethod::Direct));
+
+        let quotes = [quote(Currency::USD, Currency::IDR, "16000", 28), quote(Currency::IDR, Currency::USD, "0.00008", 29)];
+        let rate = resolve(&quotes, Currency::USD, Currency::IDR, day(31), Currency::IDR).unwrap();
+        assert_eq!((rate.rate, rate.method, rate.rate_date), ("12500".parse().unwrap(), ConversionMethod::Inverse, day(29)));
+    }
+
+    #[test]
+    fn triangulates_through_the_base_currency() {
+        let quotes = [quote(Currency::USD, Currency::IDR, "16000", 29), quote(Currency::SGD, Currency::IDR, "12000", 27)];
+        let rate = resolve(&quotes, Currency::USD, Currency::SGD, day(31), Currency::IDR).unwrap();
+        assert_eq!(rate.method, ConversionMethod::Triangulated);
+        assert_eq!(rate.via, Some(Currency::IDR));
+        assert_eq!(rate.rate, "1.3333333333".parse().unwrap());
+        assert_eq!(rate.rate_date, day(27));
+
+        let converted = rate.convert(Money::new(Decimal::from(100), Currency::USD), Currency::SGD).unwrap();
+        assert_eq!(converted.to_string(), "SGD 133.33");
+    }
+
+    #[test]
+    fn missing_legs_give_no_rate() {
+        let quotes = [quote(Currency::USD, Currency::IDR, "16000", 29)];
+        assert_eq!(resolve(&quotes, Currency::USD, Currency::SGD, day(31), Currency::IDR), None);
+        assert_eq!(resolve(&quotes, Currency::EUR, Currency::IDR, day(31), Currency::IDR), None);
+    }
+}
@@ -0,0 +1,527 @@
+//! Streaming exports of list endpoints.
+//!
+//! `?format=csv|xlsx|jsonl` (or an `Accept` header naming one of those types)
+//! turns a list request into a download of every matching row: same filters,
+//! search and sort, no pagination. Rows are read from a Postgres cursor and
+//! encoded as they arrive, so memory use doesn't grow with the export.
+
+mod xlsx;
+
+use std::io;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{FromRequestParts, Query},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::TryStreamExt;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use serde_json::Value;
+use shared_types::ApiResponse;
+use sqlx::{pool::PoolConnection, postgres::PgRow, Postgres};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use utoipa::{
+    openapi::{schema::SchemaType, KnownFormat, RefOr, Schema, SchemaFormat},
+    ToSchema,
+};
+use uuid::Uuid;
+
+use crate::{fieldset::Fieldset, imports::sheet, list_query::ListPlan, state::AppState};
+use xlsx::XlsxWriter;
+
+/// Bytes collected before a chunk is handed to the response body.
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Chunks that may wait for a slow client before reading from Postgres pauses.
+const CHUNK_BUFFER: usize = 4;
+/// Locale used when the tenant hasn't set one.
+const DEFAULT_LOCALE: &str = "id-ID";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+    Jsonl,
+}
+
+impl ExportFormat {
+    const ALL: [ExportFormat; 3] = [ExportFormat::Csv, ExportFormat::Xlsx, ExportFormat::Jsonl];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Xlsx => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            ExportFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "text/csv" => Some(ExportFormat::Csv),
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => Some(ExportFormat::Xlsx),
+            "application/x-ndjson" | "application/jsonl" | "application/x-jsonlines" => Some(ExportFormat::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a list request asked for an export, and in which format.
+pub struct ExportRequest(Option<ExportFormat>);
+
+impl ExportRequest {
+    pub fn format(&self) -> Option<ExportFormat> {
+        self.0
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown format '{0}' (allowed: json, csv, xlsx, jsonl)")]
+pub struct ExportError(String);
+
+impl IntoResponse for ExportError {
+    fn into_response(self) -> Response {
+        (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(self.to_string()))).into_response()
+    }
+}
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for ExportRequest
+where
+    S: Send + Sync,
+{
+    type Rejection = ExportError;
+
+    /// `?format=` wins over `Accept`; `json` (or no preference) keeps the normal page.
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let params = Query::<Vec<(String, String)>>::try_from_uri(&parts.uri)
+            .map(|Query(params)| params)
+            .unwrap_or_default();
+        if let Some((_, format)) = params.iter().find(|(k, _)| k == "format") {
+            let format = format.trim().to_ascii_lowercase();
+            if format.is_empty() || format == "json" {
+                return Ok(Self(None));
+            }
+            return ExportFormat::ALL
+                .into_iter()
+                .find(|f| f.as_str() == format)
+                .map(|f| Self(Some(f)))
+                .ok_or(ExportError(format));
+        }
+
+        let accepted = parts
+            .headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(|v| v.split(';').next().unwrap_or_default().trim().to_ascii_lowercase())
+            .find_map(|media_type| ExportFormat::from_media_type(&media_type));
+        Ok(Self(accepted))
+    }
+}
+
+/// How dates and numbers are written in CSV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Locale {
+    /// `12,5` instead of `12.5`; the CSV delimiter becomes `;` so the comma is free
+    decimal_comma: bool,
+    date: &'static str,
+    date_time: &'static str,
+}
+
+impl Locale {
+    /// Locale for a BCP 47 tag such as `id-ID` or `en-US`. Unknown tags get
+    /// ISO 8601 dates and a decimal point.
+    pub fn from_tag(tag: &str) -> Self {
+        let tag = tag.trim().to_ascii_lowercase().replace('_', "-");
+        let language = tag.split('-').next().unwrap_or_default();
+        match (language, tag.as_str()) {
+            ("id", _) => Locale { decimal_comma: true, date: "%d/%m/%Y", date_time: "%d/%m/%Y %H:%M:%S" },
+            ("en", "en-us") | ("en", "en") => {
+                Locale { decimal_comma: false, date: "%m/%d/%Y", date_time: "%m/%d/%Y %H:%M:%S" }
+            }
+            ("en", _) => Locale { decimal_comma: false, date: "%d/%m/%Y", date_time: "%d/%m/%Y %H:%M:%S" },
+            _ => Locale { decimal_comma: false, date: "%Y-%m-%d", date_time: "%Y-%m-%d %H:%M:%S" },
+        }
+    }
+
+    fn delimiter(&self) -> char {
+        if self.decimal_comma {
+            ';'
+        } else {
+            ','
+        }
+    }
+
+    fn format(&self, cell: &Cell) -> String {
+        match cell {
+            Cell::Empty => String::new(),
+            // Spreadsheets run cells starting with these as formulas
+            Cell::Text(text) if text.starts_with(['=', '+', '-', '@']) => format!("'{}", text),
+            Cell::Text(text) => text.clone(),
+            Cell::Number(number) if self.decimal_comma => number.replace('.', ","),
+            Cell::Number(number) => number.clone(),
+            Cell::Bool(value) => value.to_string(),
+            Cell::Date(date) => date.format(self.date).to_string(),
+            Cell::DateTime(at) => at.format(self.date_time).to_string(),
+        }
+    }
+}
+
+/// A typed value in a CSV/XLSX row.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Empty,
+    Text(String),
+    /// Canonical decimal text (`-12.50`), kept as text so nothing is rounded
+    Number(String),
+    Bool(bool),
+    Date(NaiveDate),
+    DateTime(DateTime<Utc>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Text,
+    Number,
+    /// `{"amount": "12.50", "currency": "USD"}`, exported as its amount; the
+    /// currency is a column of its own
+    Money,
+    Bool,
+    Date,
+    DateTime,
+}
+
+impl Kind {
+    fn of(schema: &RefOr<Schema>) -> Kind {
+        match schema {
+            // Decimals have no schema of their own yet and show up as a bare reference
+            RefOr::Ref(reference) if reference.ref_location.ends_with("/Decimal") => Kind::Number,
+            RefOr::Ref(reference) if reference.ref_location.ends_with("/Money") => Kind::Money,
+            RefOr::Ref(_) => Kind::Text,
+            RefOr::T(Schema::AllOf(all_of)) => all_of.items.first().map(Kind::of).unwrap_or(Kind::Text),
+            RefOr::T(Schema::Object(object)) => match (&object.schema_type, &object.format) {
+                (SchemaType::Integer | SchemaType::Number, _) => Kind::Number,
+                (SchemaType::Boolean, _) => Kind::Bool,
+                (SchemaType::String, Some(SchemaFormat::KnownFormat(KnownFormat::Date))) => Kind::Date,
+                (SchemaType::String, Some(SchemaFormat::KnownFormat(KnownFormat::DateTime))) => Kind::DateTime,
+                _ => Kind::Text,
+            },
+            _ => Kind::Text,
+        }
+    }
+
+    fn cell(&self, value: &Value) -> Cell {
+        let parsed = match (self, value) {
+            (_, Value::Null) => Some(Cell::Empty),
+            (Kind::Number, Value::Number(n)) => Some(Cell::Number(n.to_string())),
+            (Kind::Number, Value::String(s)) => s.parse::<Decimal>().ok().map(|d| Cell::Number(d.to_string())),
+            (Kind::Money, Value::Object(money)) => money.get("amount").map(|amount| Kind::Number.cell(amount)),
+            (Kind::Bool, Value::Bool(b)) => Some(Cell::Bool(*b)),
+            (Kind::Date, Value::String(s)) => s.parse::<NaiveDate>().ok().map(Cell::Date),
+            (Kind::DateTime, Value::String(s)) => {
+                DateTime::parse_from_rfc3339(s).ok().map(|at| Cell::DateTime(at.with_timezone(&Utc)))
+            }
+            _ => None,
+        };
+        parsed.unwrap_or_else(|| match value {
+            Value::String(s) => Cell::Text(s.clone()),
+            other => Cell::Text(other.to_string()),
+        })
+    }
+}
+
+/// Exported columns of `T`: the requested `?fields=` (plus `id`) or every
+/// property, in declaration order. Embeddable relations are never columns.
+fn columns<T: for<'s> ToSchema<'s>>(fieldset: &Fieldset, relations: &[&str]) -> Vec<(String, Kind)> {
+    let RefOr::T(Schema::Object(object)) = T::schema().1 else {
+        return Vec::new();
+    };
+    object
+        .properties
+        .iter()
+        .filter(|(name, _)| !relations.iter().any(|r| r.split('.').next() == Some(name.as_str())))
+        .filter(|(name, _)| fieldset.selects(name))
+        .map(|(name, schema)| (name.clone(), Kind::of(schema)))
+        .collect()
+}
+
+/// A list endpoint's query, ready to export.
+pub struct ExportSource<T> {
+    /// File name stem, e.g. `products`
+    pub name: &'static str,
+    pub plan: ListPlan,
+    /// `SELECT` list for `map`
+    pub columns: &'static str,
+    pub map: fn(&PgRow) -> Result<T, sqlx::Error>,
+    /// Relations of the endpoint's `?include=`, left out of exports
+    pub relations: &'static [&'static str],
+}
+
+/// Stream every row of `source` in `format`.
+///
+/// The response starts right away; if reading fails halfway the body is cut
+/// off with an error rather than ending cleanly, so clients can tell a partial
+/// file from a complete one.
+pub async fn respond<T>(
+    state: &AppState,
+    mut conn: PoolConnection<Postgres>,
+    tenant_id: Uuid,
+    format: ExportFormat,
+    fieldset: Fieldset,
+    source: ExportSource<T>,
+) -> Response
+where
+    T: Serialize + for<'s> ToSchema<'s> + Send + 'static,
+{
+    if fieldset.has_includes() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::<()>::error("include is not supported by exports".to_string())),
+        )
+            .into_response();
+    }
+
+    let locale = sqlx::query_scalar::<_, Option<String>>("SELECT settings->>'locale' FROM tenants WHERE id = $1")
+        .bind(tenant_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .ok()
+        .flatten()
+        .flatten();
+    let locale = Locale::from_tag(locale.as_deref().unwrap_or(DEFAULT_LOCALE));
+    let columns = columns::<T>(&fieldset, source.relations);
+    let file_name = format!("{}-{}.{}", source.name, Utc::now().format("%Y%m%d"), format.as_str());
+    info!(export = source.name, format = format.as_str(), "Export started");
+
+    let (tx, rx) = mpsc::channel::<io::Result<Bytes>>(CHUNK_BUFFER);
+    let shutdown = state.workers.shutdown_token();
+    tokio::spawn(async move {
+        let encoder = Encoder::new(format, locale, columns, fieldset);
+        let result = write(conn, tenant_id, source, encoder, &tx, shutdown).await;
+        if let Err(e) = result {
+            warn!("Export failed: {}", e);
+            let _ = tx.send(Err(e)).await;
+        }
+    });
+
+    let body = Body::from_stream(futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    }));
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+async fn write<T: Serialize>(
+    mut conn: PoolConnection<Postgres>,
+    tenant_id: Uuid,
+    source: ExportSource<T>,
+    mut encoder: Encoder,
+    tx: &mpsc::Sender<io::Result<Bytes>>,
+    shutdown: CancellationToken,
+) -> io::Result<()> {
+    let mut buffer = Vec::with_capacity(CHUNK_SIZE);
+    encoder.start(&mut buffer)?;
+
+    let mut query = source.plan.unpaged(tenant_id, source.columns);
+    let mut rows = query.build().fetch(&mut *conn);
+    loop {
+        let row = tokio::select! {
+            row = rows.try_next() => row.map_err(io::Error::other)?,
+            _ = shutdown.cancelled() => return Err(io::Error::other("server is shutting down")),
+        };
+        let Some(row) = row else { break };
+        encoder.row(&mut buffer, &(source.map)(&row).map_err(io::Error::other)?)?;
+
+        if buffer.len() >= CHUNK_SIZE {
+            let chunk = Bytes::from(std::mem::replace(&mut buffer, Vec::with_capacity(CHUNK_SIZE)));
+            if tx.send(Ok(chunk)).await.is_err() {
+                // Client went away; dropping the stream ends the query
+                return Ok(());
+            }
+        }
+    }
+    drop(rows);
+
+    encoder.finish(&mut buffer)?;
+    let _ = tx.send(Ok(Bytes::from(buffer))).await;
+    Ok(())
+}
+
+enum Encoder {
+    Csv { locale: Locale, columns: Vec<(String, Kind)> },
+    Xlsx { writer: Option<XlsxWriter>, columns: Vec<(String, Kind)> },
+    Jsonl { fieldset: Fieldset },
+}
+
+impl Encoder {
+    fn new(format: ExportFormat, locale: Locale, columns: Vec<(String, Kind)>, fieldset: Fieldset) -> Self {
+        match format {
+            ExportFormat::Csv => Encoder::Csv { locale, columns },
+            ExportFormat::Xlsx => Encoder::Xlsx { writer: None, columns },
+            ExportFormat::Jsonl => Encoder::Jsonl { fieldset },
+        }
+    }
+
+    fn start(&mut self, out: &mut Vec<u8>) -> io::Result<()> {
+        match self {
+            Encoder::Csv { locale, columns } => {
+                // Excel only detects UTF-8 in CSV files that start with a BOM
+                out.extend_from_slice("\u{feff}".as_bytes());
+                let header = sheet::csv_record(locale.delimiter(), columns.iter().map(|(name, _)| name));
+                out.extend_from_slice(header.as_bytes());
+            }
+            Encoder::Xlsx { writer, columns } => {
+                let headers: Vec<String> = columns.iter().map(|(name, _)| name.clone()).collect();
+                *writer = Some(XlsxWriter::start(out, &headers)?);
+            }
+            Encoder::Jsonl { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn row<T: Serialize>(&mut self, out: &mut Vec<u8>, item: &T) -> io::Result<()> {
+        match self {
+            Encoder::Csv { locale, columns } => {
+                let cells = cells(columns, item);
+                let record = sheet::csv_record(locale.delimiter(), cells.iter().map(|c| locale.format(c)));
+                out.extend_from_slice(record.as_bytes());
+            }
+            Encoder::Xlsx { writer, columns } => {
+                let cells = cells(columns, item);
+                writer.as_mut().expect("started").write_row(out, &cells)?;
+            }
+            Encoder::Jsonl { fieldset } => {
+                serde_json::to_writer(&mut *out, &fieldset.apply(item))?;
+                out.push(b'\n');
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self, out: &mut Vec<u8>) -> io::Result<()> {
+        match self {
+            Encoder::Xlsx { writer, .. } => writer.expect("started").finish(out),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn cells<T: Serialize>(columns: &[(String, Kind)], item: &T) -> Vec<Cell> {
+    let value = serde_json::to_value(item).unwrap_or(Value::Null);
+    columns
+        .iter()
+        .map(|(name, kind)| kind.cell(value.get(name).unwrap_or(&Value::Null)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fieldset::FieldsetQuery;
+    use shared_types::{Currency, Money};
+
+    #[derive(Serialize, ToSchema)]
+    struct Line {
+        id: u32,
+        sku: String,
+        price: f64,
+        posted_on: NaiveDate,
+        created_at: DateTime<Utc>,
+        account: Option<String>,
+    }
+
+    fn line() -> Line {
+        Line {
+            id: 7,
+            sku: "=SUM(A1)".into(),
+            price: 1250.5,
+            posted_on: NaiveDate::from_ymd_opt(2024, 3, 31).unwrap(),
+            created_at: "2024-03-31T08:15:00Z".parse().unwrap(),
+            account: None,
+        }
+    }
+
+    #[test]
+    fn locales_pick_separators_and_date_order() {
+        let id = Locale::from_tag("id-ID");
+        assert_eq!(id.delimiter(), ';');
+        assert_eq!(id.format(&Cell::Number("1250.5".into())), "1250,5");
+        assert_eq!(id.format(&Cell::Date(NaiveDate::from_ymd_opt(2024, 3, 31).unwrap())), "31/03/2024");
+
+        let us = Locale::from_tag("en_US");
+        assert_eq!(us.delimiter(), ',');
+        assert_eq!(us.format(&Cell::Date(NaiveDate::from_ymd_opt(2024, 3, 31).unwrap())), "03/31/2024");
+        assert_eq!(Locale::from_tag("fr").format(&Cell::Date(NaiveDate::from_ymd_opt(2024, 3, 31).unwrap())), "2024-03-31");
+    }
+
+    #[test]
+    fn csv_rows_follow_schema_types_and_escape_formulas() {
+        let fieldset = FieldsetQuery::from_parts(&["sku", "price", "posted_on", "created_at"], &[])
+            .resolve::<Line>(&["account"])
+            .unwrap();
+        let mut encoder = Encoder::new(ExportFormat::Csv, Locale::from_tag("id"), columns::<Line>(&fieldset, &["account"]), fieldset);
+        let mut out = Vec::new();
+        encoder.start(&mut out).unwrap();
+        encoder.row(&mut out, &line()).unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(
+            text,
+            "\u{feff}id;sku;price;posted_on;created_at\r\n7;'=SUM(A1);1250,5;31/03/2024;31/03/2024 08:15:00\r\n"
+        );
+    }
+
+    #[test]
+    fn jsonl_keeps_raw_values() {
+        let fieldset = FieldsetQuery::from_parts(&["price"], &[]).resolve::<Line>(&[]).unwrap();
+        let mut encoder = Encoder::new(ExportFormat::Jsonl, Locale::from_tag("id"), Vec::new(), fieldset);
+        let mut out = Vec::new();
+        encoder.row(&mut out, &line()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"id\":7,\"price\":1250.5}\n");
+    }
+
+    #[test]
+    fn kinds_convert_serialized_values() {
+        assert_eq!(Kind::Number.cell(&Value::String("12.50".into())), Cell::Number("12.50".into()));
+        assert_eq!(Kind::Number.cell(&Value::String("n/a".into())), Cell::Text("n/a".into()));
+        assert_eq!(Kind::Text.cell(&serde_json::json!(["a", "b"])), Cell::Text("[\"a\",\"b\"]".into()));
+        assert_eq!(Kind::Date.cell(&Value::Null), Cell::Empty);
+    }
+
+    #[test]
+    fn money_columns_export_their_amount() {
+        #[derive(Serialize, ToSchema)]
+        struct Order {
+            currency: Currency,
+            total: Money,
+        }
+
+        let fieldset = FieldsetQuery::from_parts(&[], &[]).resolve::<Order>(&[]).unwrap();
+        assert_eq!(
+            columns::<Order>(&fieldset, &[]),
+            vec![("currency".to_string(), Kind::Text), ("total".to_string(), Kind::Money)]
+        );
+        let order = Order { currency: Currency::USD, total: Money::new(Decimal::new(12345, 1), Currency::USD) };
+        assert_eq!(cells(&columns::<Order>(&fieldset, &[]), &order), vec![Cell::Text("USD".into()), Cell::Number("1234.50".into())]);
+    }
+}
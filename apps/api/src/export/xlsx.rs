@@ -0,0 +1,356 @@
+//! Streaming XLSX writer.
+//!
+//! A workbook is a zip of XML parts. Zip entries normally need their size and
+//! CRC in the header before the data, which forces buffering or seeking; here
+//! every entry sets the "data descriptor" flag and writes them after the data
+//! instead, so the worksheet can be deflated and sent row by row. Only one
+//! worksheet is written, and the whole file is limited to 4 GiB (no ZIP64).
+
+use std::io::{self, Write};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::{write::DeflateEncoder, Compression};
+
+use super::Cell;
+
+/// Cell styles from [`STYLES`]
+const STYLE_DATE: u8 = 1;
+const STYLE_DATE_TIME: u8 = 2;
+const STYLE_HEADER: u8 = 3;
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/><Override PartName="/xl/worksheets/sheet1.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/><Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/></Types>"#;
+
+const ROOT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="xl/workbook.xml"/></Relationships>"#;
+
+const WORKBOOK_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/></Relationships>"#;
+
+/// Built-in number formats 14 (short date) and 22 (date and time) are shown in
+/// the reader's regional format, so dates follow the locale of whoever opens the file.
+const STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<styleSheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><fonts count="2"><font><sz val="11"/><name val="Calibri"/></font><font><b/><sz val="11"/><name val="Calibri"/></font></fonts><fills count="2"><fill><patternFill patternType="none"/></fill><fill><patternFill patternType="gray125"/></fill></fills><borders count="1"><border><left/><right/><top/><bottom/><diagonal/></border></borders><cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs><cellXfs count="4"><xf numFmtId="0" fontId="0" fillId="0" borderId="0" xfId="0"/><xf numFmtId="14" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/><xf numFmtId="22" fontId="0" fillId="0" borderId="0" xfId="0" applyNumberFormat="1"/><xf numFmtId="0" fontId="1" fillId="0" borderId="0" xfId="0" applyFont="1"/></cellXfs></styleSheet>"#;
+
+const SHEET_START: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetViews><sheetView workbookViewId="0"><pane ySplit="1" topLeftCell="A2" activePane="bottomLeft" state="frozen"/></sheetView></sheetViews><sheetData>"#;
+
+const SHEET_END: &str = "</sheetData></worksheet>";
+
+// DOS date/time of 1980-01-01 00:00, the earliest a zip entry can carry
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Zip flags: sizes and CRC follow the data (bit 3), names are UTF-8 (bit 11)
+const FLAGS: u16 = 0x0008 | 0x0800;
+
+struct Entry {
+    name: &'static str,
+    offset: u32,
+    crc: u32,
+    compressed: u32,
+    size: u32,
+}
+
+/// The worksheet entry being streamed.
+struct Open {
+    name: &'static str,
+    offset: u32,
+    encoder: DeflateEncoder<Vec<u8>>,
+    hasher: crc32fast::Hasher,
+    size: u64,
+    compressed: u64,
+}
+
+/// Writes a single-sheet workbook into caller-provided buffers, which can be
+/// sent as soon as each call returns.
+pub struct XlsxWriter {
+    written: u64,
+    entries: Vec<Entry>,
+    sheet: Option<Open>,
+    rows: u32,
+    line: String,
+}
+
+impl XlsxWriter {
+    /// Write the fixed parts and open the worksheet with a bold header row.
+    pub fn start(out: &mut Vec<u8>, headers: &[String]) -> io::Result<Self> {
+        let mut writer = Self { written: 0, entries: Vec::new(), sheet: None, rows: 0, line: String::new() };
+        writer.static_entry(out, "[Content_Types].xml", CONTENT_TYPES)?;
+        writer.static_entry(out, "_rels/.rels", ROOT_RELS)?;
+        writer.static_entry(out, "xl/workbook.xml", WORKBOOK)?;
+        writer.static_entry(out, "xl/_rels/workbook.xml.rels", WORKBOOK_RELS)?;
+        writer.static_entry(out, "xl/styles.xml", STYLES)?;
+
+        let offset = writer.offset()?;
+        writer.local_header(out, "xl/worksheets/sheet1.xml");
+        writer.sheet = Some(Open {
+            name: "xl/worksheets/sheet1.xml",
+            offset,
+            encoder: DeflateEncoder::new(Vec::new(), Compression::fast()),
+            hasher: crc32fast::Hasher::new(),
+            size: 0,
+            compressed: 0,
+        });
+        writer.sheet_data(out, SHEET_START.as_bytes())?;
+
+        let cells: Vec<Cell> = headers.iter().map(|h| Cell::Text(h.clone())).collect();
+        writer.row_with_style(out, &cells, Some(STYLE_HEADER))?;
+        Ok(writer)
+    }
+
+    pub fn write_row(&mut self, out: &mut Vec<u8>, cells: &[Cell]) -> io::Result<()> {
+        self.row_with_style(out, cells, None)
+    }
+
+    /// Close the worksheet and write the zip's central directory.
+    pub fn finish(mut self, out: &mut Vec<u8>) -> io::Result<()> {
+        self.sheet_data(out, SHEET_END.as_bytes())?;
+        let sheet = self.sheet.take().expect("worksheet is open until finish");
+        let rest = sheet.encoder.finish()?;
+        let compressed = sheet.compressed + rest.len() as u64;
+        self.emit(out, &rest);
+
+        let entry = Entry {
+            name: sheet.name,
+            offset: sheet.offset,
+            crc: sheet.hasher.finalize(),
+            compressed: too_large(compressed)?,
+            size: too_large(sheet.size)?,
+        };
+        self.data_descriptor(out, &entry);
+        self.entries.push(entry);
+        self.central_directory(out)
+    }
+
+    /// `header_style` overrides the style of every cell in the row.
+    fn row_with_style(&mut self, out: &mut Vec<u8>, cells: &[Cell], header_style: Option<u8>) -> io::Result<()> {
+        self.rows += 1;
+        let row = self.rows;
+        let mut line = std::mem::take(&mut self.line);
+        line.clear();
+        line.push_str(&format!("<row r=\"{}\">", row));
+        for (i, cell) in cells.iter().enumerate() {
+            let reference = format!("{}{}", column_name(i), row);
+            let style = |s: u8| format!(" s=\"{}\"", header_style.unwrap_or(s));
+            match cell {
+                Cell::Empty => {}
+                Cell::Text(text) => line.push_str(&format!(
+                    "<c r=\"{}\" t=\"inlineStr\"{}><is><t xml:space=\"preserve\">{}</t></is></c>",
+                    reference,
+                    header_style.map(|s| format!(" s=\"{}\"", s)).unwrap_or_default(),
+                    escape(text)
+                )),
+                Cell::Number(number) => line.push_str(&format!("<c r=\"{}\"><v>{}</v></c>", reference, number)),
+                Cell::Bool(value) => {
+                    line.push_str(&format!("<c r=\"{}\" t=\"b\"><v>{}</v></c>", reference, u8::from(*value)))
+                }
+                Cell::Date(date) => line.push_str(&format!(
+                    "<c r=\"{}\"{}><v>{}</v></c>",
+                    reference,
+                    style(STYLE_DATE),
+                    date_serial(*date)
+                )),
+                Cell::DateTime(at) => line.push_str(&format!(
+                    "<c r=\"{}\"{}><v>{}</v></c>",
+                    reference,
+                    style(STYLE_DATE_TIME),
+                    date_time_serial(*at)
+                )),
+            }
+        }
+        line.push_str("</row>");
+        let result = self.sheet_data(out, line.as_bytes());
+        self.line = line;
+        result
+    }
+
+    /// Deflate worksheet XML and move whatever the encoder has produced into `out`.
+    fn sheet_data(&mut self, out: &mut Vec<u8>, data: &[u8]) -> io::Result<()> {
+        let sheet = self.sheet.as_mut().expect("worksheet is open");
+        sheet.encoder.write_all(data)?;
+        sheet.hasher.update(data);
+        sheet.size += data.len() as u64;
+        let produced = std::mem::take(sheet.encoder.get_mut());
+        sheet.compressed += produced.len() as u64;
+        too_large(sheet.size)?;
+        self.emit(out, &produced);
+        Ok(())
+    }
+
+    fn static_entry(&mut self, out: &mut Vec<u8>, name: &'static str, content: &str) -> io::Result<()> {
+        let offset = self.offset()?;
+        self.local_header(out, name);
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content.as_bytes())?;
+        let compressed = encoder.finish()?;
+        self.emit(out, &compressed);
+
+        let entry = Entry {
+            name,
+            offset,
+            crc: crc32fast::hash(content.as_bytes()),
+            compressed: compressed.len() as u32,
+            size: content.len() as u32,
+        };
+        self.data_descriptor(out, &entry);
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    fn local_header(&mut self, out: &mut Vec<u8>, name: &str) {
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes()); // version needed: 2.0 (deflate)
+        header.extend_from_slice(&FLAGS.to_le_bytes());
+        header.extend_from_slice(&8u16.to_le_bytes()); // deflate
+        header.extend_from_slice(&DOS_TIME.to_le_bytes());
+        header.extend_from_slice(&DOS_DATE.to_le_bytes());
+        header.extend_from_slice(&[0; 12]); // crc and sizes are in the data descriptor
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        self.emit(out, &header);
+    }
+
+    fn data_descriptor(&mut self, out: &mut Vec<u8>, entry: &Entry) {
+        let mut descriptor = Vec::with_capacity(16);
+        descriptor.extend_from_slice(&0x0807_4b50u32.to_le_bytes());
+        descriptor.extend_from_slice(&entry.crc.to_le_bytes());
+        descriptor.extend_from_slice(&entry.compressed.to_le_bytes());
+        descriptor.extend_from_slice(&entry.size.to_le_bytes());
+        self.emit(out, &descriptor);
+    }
+
+    fn central_directory(&mut self, out: &mut Vec<u8>) -> io::Result<()> {
+        let start = self.offset()?;
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes()); // made by
+            directory.extend_from_slice(&20u16.to_le_bytes()); // needed
+            directory.extend_from_slice(&FLAGS.to_le_bytes());
+            directory.extend_from_slice(&8u16.to_le_bytes());
+            directory.extend_from_slice(&DOS_TIME.to_le_bytes());
+            directory.extend_from_slice(&DOS_DATE.to_le_bytes());
+            directory.extend_from_slice(&entry.crc.to_le_bytes());
+            directory.extend_from_slice(&entry.compressed.to_le_bytes());
+            directory.extend_from_slice(&entry.size.to_le_bytes());
+            directory.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            directory.extend_from_slice(&[0; 12]); // extra, comment, disk, attributes
+            directory.extend_from_slice(&entry.offset.to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+        let size = too_large(directory.len() as u64)?;
+        self.emit(out, &directory);
+
+        let count = self.entries.len() as u16;
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        end.extend_from_slice(&[0; 4]); // disk numbers
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&size.to_le_bytes());
+        end.extend_from_slice(&start.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        self.emit(out, &end);
+        too_large(self.written).map(|_| ())
+    }
+
+    fn emit(&mut self, out: &mut Vec<u8>, bytes: &[u8]) {
+        self.written += bytes.len() as u64;
+        out.extend_from_slice(bytes);
+    }
+
+    fn offset(&self) -> io::Result<u32> {
+        too_large(self.written)
+    }
+}
+
+fn too_large(value: u64) -> io::Result<u32> {
+    u32::try_from(value).map_err(|_| io::Error::other("XLSX export exceeds 4 GiB; narrow the filters or use CSV"))
+}
+
+const WORKBOOK: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="Export" sheetId="1" r:id="rId1"/></sheets></workbook>"#;
+
+/// `A`, `B`, ..., `Z`, `AA`, ... for a zero-based column index.
+fn column_name(mut index: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).expect("column names are ASCII")
+}
+
+/// XML text with the characters XML 1.0 can't carry dropped.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if (c as u32) < 0x20 || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Days since 1899-12-30, the epoch spreadsheets count dates from.
+fn date_serial(date: NaiveDate) -> i64 {
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30).expect("valid epoch");
+    (date - epoch).num_days()
+}
+
+fn date_time_serial(at: DateTime<Utc>) -> String {
+    let seconds = at.time().signed_duration_since(chrono::NaiveTime::MIN).num_seconds();
+    format!("{:.6}", date_serial(at.date_naive()) as f64 + seconds as f64 / 86_400.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::sheet;
+
+    #[test]
+    fn column_names_roll_over() {
+        assert_eq!(column_name(0), "A");
+        assert_eq!(column_name(25), "Z");
+        assert_eq!(column_name(26), "AA");
+        assert_eq!(column_name(701), "ZZ");
+        assert_eq!(column_name(702), "AAA");
+    }
+
+    #[test]
+    fn serials_count_from_the_spreadsheet_epoch() {
+        assert_eq!(date_serial(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()), 45292);
+        let noon = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(12, 0, 0).unwrap().and_utc();
+        assert_eq!(date_time_serial(noon), "45292.500000");
+    }
+
+    #[test]
+    fn streamed_workbook_reads_back() {
+        let mut out = Vec::new();
+        let headers = vec!["sku".to_string(), "price".to_string(), "note".to_string()];
+        let mut writer = XlsxWriter::start(&mut out, &headers).unwrap();
+        writer
+            .write_row(&mut out, &[Cell::Text("A-1".into()), Cell::Number("12.50".into()), Cell::Text("<b> & \u{1}".into())])
+            .unwrap();
+        writer.write_row(&mut out, &[Cell::Text("A-2".into()), Cell::Empty, Cell::Bool(true)]).unwrap();
+        writer.finish(&mut out).unwrap();
+
+        let (_, parsed) = sheet::read("export.xlsx", &out, 10).unwrap();
+        assert_eq!(parsed.headers, headers);
+        assert_eq!(parsed.rows[0].cells, vec!["A-1", "12.50", "<b> & "]);
+        assert_eq!(parsed.rows[1].cells[0], "A-2");
+        assert_eq!(parsed.rows[1].cells[1], "");
+    }
+}
@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use axum::{extract::{FromRequestParts}, http::request::Parts};
 use sqlx::{pool::PoolConnection, Postgres};
+use tracing::Instrument;
 
 use crate::state::AppState;
 
@@ -10,20 +11,16 @@ use crate::state::AppState;
 pub struct DbConn(pub PoolConnection<Postgres>);
 
 #[axum::async_trait]
-impl<S> FromRequestParts<S> for DbConn
-where
-    S: Send + Sync,
-{
+impl FromRequestParts<Arc<AppState>> for DbConn {
     type Rejection = axum::http::StatusCode;
 
-    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        // Ambil AppState dari Router state
-        let Some(state) = parts.extensions.get::<Arc<AppState>>() else {
-            return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
-        };
+    async fn from_request_parts(_parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        // Ambil AppState dari Router state; hitung request yang menunggu koneksi
+        let _waiting = state.metrics.db_wait();
         let conn = state
             .db_pool
             .acquire()
+            .instrument(tracing::info_span!("db.acquire", otel.kind = "client", db.system = "postgresql"))
             .await
             .map_err(|_| axum::http::StatusCode::INTERNAL_SERVER_ERROR)?;
         Ok(DbConn(conn))
@@ -0,0 +1,59 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap},
+};
+
+use crate::cache::Freshness;
+
+/// Whether a read may come from the cache: `Cache-Control: no-cache` (or
+/// `no-store`, or `Pragma: no-cache`) asks for the database's latest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadFreshness(pub Freshness);
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ReadFreshness {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(freshness(&parts.headers)))
+    }
+}
+
+fn freshness(headers: &HeaderMap) -> Freshness {
+    let directives = headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .chain(headers.get_all(header::PRAGMA))
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| directive.trim().to_ascii_lowercase());
+    for directive in directives {
+        if directive == "no-cache" || directive == "no-store" {
+            return Freshness::Fresh;
+        }
+    }
+    Freshness::Cached
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn no_cache_directives_ask_for_fresh_reads() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(freshness(&headers), Freshness::Cached);
+
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("max-age=0, No-Cache"));
+        assert_eq!(freshness(&headers), Freshness::Fresh);
+
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("max-age=60"));
+        assert_eq!(freshness(&headers), Freshness::Cached);
+        headers.insert(header::PRAGMA, HeaderValue::from_static("no-cache"));
+        assert_eq!(freshness(&headers), Freshness::Fresh);
+    }
+}
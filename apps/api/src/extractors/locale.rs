@@ -0,0 +1,77 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+};
+use shared_types::Locale;
+use sqlx::Row;
+
+use crate::{middleware::auth_middleware::CurrentUser, state::AppState};
+
+/// Language of the messages in a response.
+///
+/// Taken from `Accept-Language` when it names a supported language, else from
+/// the signed-in user's profile, else from the tenant's `settings.locale`,
+/// else `email.default_locale`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestLocale(pub Locale);
+
+#[axum::async_trait]
+impl FromRequestParts<Arc<AppState>> for RequestLocale {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        let accept = parts.headers.get(header::ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok());
+        if let Some(locale) = accept.and_then(Locale::from_accept_language) {
+            return Ok(Self(locale));
+        }
+
+        let (user, tenant): (Option<String>, Option<String>) = match parts.extensions.get::<CurrentUser>() {
+            // A failed lookup only costs the preference
+            Some(current) => {
+                let row = sqlx::query(
+                    r#"SELECT u.locale AS user_locale, t.settings->>'locale' AS tenant_locale
+                       FROM users u LEFT JOIN tenants t ON t.id = $2
+                       WHERE u.id = $1"#,
+                )
+                .bind(current.user_id)
+                .bind(current.tenant_id)
+                .fetch_optional(&state.db_pool)
+                .await
+                .ok()
+                .flatten();
+                match row {
+                    Some(row) => (row.get("user_locale"), row.get("tenant_locale")),
+                    None => (None, None),
+                }
+            }
+            None => (None, None),
+        };
+
+        Ok(Self(resolve(None, user.as_deref(), tenant.as_deref(), state.mailer.default_locale())))
+    }
+}
+
+/// The first supported language of the request header, the user's and the
+/// tenant's preference, in that order.
+pub fn resolve(accept_language: Option<&str>, user: Option<&str>, tenant: Option<&str>, default: Locale) -> Locale {
+    accept_language
+        .and_then(Locale::from_accept_language)
+        .or_else(|| user.and_then(Locale::parse))
+        .or_else(|| tenant.and_then(Locale::parse))
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_then_user_then_tenant() {
+        assert_eq!(resolve(Some("en-US"), Some("id"), Some("id"), Locale::Id), Locale::En);
+        assert_eq!(resolve(Some("fr"), Some("id"), Some("en"), Locale::En), Locale::Id);
+        assert_eq!(resolve(None, None, Some("id-ID"), Locale::En), Locale::Id);
+        assert_eq!(resolve(None, Some("xx"), None, Locale::En), Locale::En);
+    }
+}
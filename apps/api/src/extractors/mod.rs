@@ -1 +1,7 @@
 pub mod db_conn;
+pub mod freshness;
+pub mod locale;
+pub mod preconditions;
+
+pub use freshness::ReadFreshness;
+pub use locale::RequestLocale;
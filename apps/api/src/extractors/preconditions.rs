@@ -0,0 +1,124 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use shared_types::ApiResponse;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// Conditional request headers (`If-Match`, `If-None-Match`) for optimistic concurrency.
+///
+/// Entity versions are exposed as strong ETags derived from `updated_at`, which the
+/// `update_updated_at_column` trigger bumps on every write.
+pub struct Preconditions {
+    if_match: Option<Vec<String>>,
+    if_none_match: Option<Vec<String>>,
+}
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for Preconditions
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self {
+            if_match: tag_list(&parts.headers, header::IF_MATCH),
+            if_none_match: tag_list(&parts.headers, header::IF_NONE_MATCH),
+        })
+    }
+}
+
+impl Preconditions {
+    /// Versions an update may be applied to, for use in the `WHERE` clause of the
+    /// `UPDATE` so the check and the write are atomic. `None` when the request is
+    /// unconditional (no `If-Match`, or `If-Match: *`).
+    pub fn expected_versions(&self) -> Option<Vec<DateTime<Utc>>> {
+        let tags = self.if_match.as_ref()?;
+        if tags.iter().any(|t| t == "*") {
+            return None;
+        }
+        // Tags we didn't issue can never match, so an empty list fails the update
+        Some(tags.iter().filter_map(|t| parse_etag(t)).collect())
+    }
+
+    /// Whether a GET can be answered with 304 for an entity at `updated_at`.
+    pub fn not_modified(&self, updated_at: DateTime<Utc>) -> bool {
+        let Some(tags) = &self.if_none_match else {
+            return false;
+        };
+        // If-None-Match uses weak comparison
+        tags.iter()
+            .any(|t| t == "*" || parse_etag(t.trim_start_matches("W/")) == Some(truncate(updated_at)))
+    }
+}
+
+/// Strong ETag for an entity version.
+pub fn etag(updated_at: DateTime<Utc>) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{:x}\"", updated_at.timestamp_micros()))
+        .expect("hex etag is a valid header value")
+}
+
+/// JSON response carrying the entity's ETag.
+pub fn tagged<T: Serialize>(updated_at: DateTime<Utc>, body: ApiResponse<T>) -> Response {
+    ([(header::ETAG, etag(updated_at))], Json(body)).into_response()
+}
+
+pub fn not_modified(updated_at: DateTime<Utc>) -> Response {
+    (StatusCode::NOT_MODIFIED, [(header::ETAG, etag(updated_at))]).into_response()
+}
+
+pub fn precondition_failed() -> Response {
+    (
+        StatusCode::PRECONDITION_FAILED,
+        Json(ApiResponse::<()>::error(
+            "Resource was modified by another request; fetch it again and retry".to_string(),
+        )),
+    )
+        .into_response()
+}
+
+/// Response for a conditional `UPDATE ... RETURNING` that matched no row: 412 if
+/// the entity still exists (someone else changed it), otherwise `not_found`.
+pub async fn update_missed(conn: &mut PgConnection, table: &'static str, id: Uuid, not_found: &str) -> Response {
+    let exists = sqlx::query_scalar::<_, bool>(&format!("SELECT EXISTS(SELECT 1 FROM {} WHERE id = $1)", table))
+        .bind(id)
+        .fetch_one(conn)
+        .await
+        .unwrap_or(false);
+
+    if exists {
+        precondition_failed()
+    } else {
+        Json(ApiResponse::<()>::error(not_found.to_string())).into_response()
+    }
+}
+
+fn tag_list(headers: &HeaderMap, name: header::HeaderName) -> Option<Vec<String>> {
+    let values: Vec<String> = headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    (!values.is_empty()).then_some(values)
+}
+
+fn parse_etag(tag: &str) -> Option<DateTime<Utc>> {
+    let micros = i64::from_str_radix(tag.strip_prefix('"')?.strip_suffix('"')?, 16).ok()?;
+    DateTime::from_timestamp_micros(micros)
+}
+
+/// Postgres stores microseconds; drop anything finer so comparisons line up.
+fn truncate(ts: DateTime<Utc>) -> DateTime<Utc> {
+    DateTime::from_timestamp_micros(ts.timestamp_micros()).unwrap_or(ts)
+}
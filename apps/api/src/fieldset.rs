@@ -0,0 +1,232 @@
+use std::collections::BTreeSet;
+use std::convert::Infallible;
+
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+use shared_types::ApiResponse;
+use utoipa::{
+    openapi::{RefOr, Schema},
+    ToSchema,
+};
+use uuid::Uuid;
+
+use crate::list_query::ListPage;
+
+/// `?fields=` (sparse fieldsets) and `?include=` (embedded relations) of a read
+/// endpoint, validated against the resource by [`FieldsetQuery::resolve`].
+///
+/// Both take comma-separated lists and may be repeated. `id` is always returned,
+/// as is every included relation.
+pub struct FieldsetQuery {
+    fields: Option<Vec<String>>,
+    include: Vec<String>,
+}
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for FieldsetQuery
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let params = Query::<Vec<(String, String)>>::try_from_uri(&parts.uri)
+            .map(|Query(params)| params)
+            .unwrap_or_default();
+        let list = |key: &str| -> Vec<String> {
+            params
+                .iter()
+                .filter(|(k, _)| k == key)
+                .flat_map(|(_, v)| v.split(','))
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect()
+        };
+
+        let fields = list("fields");
+        Ok(Self {
+            fields: (!fields.is_empty()).then_some(fields),
+            include: list("include"),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FieldsetError {
+    #[error("Unknown field '{field}' (allowed: {allowed})")]
+    UnknownField { field: String, allowed: String },
+    #[error("Unknown include '{name}' (allowed: {allowed})")]
+    UnknownInclude { name: String, allowed: String },
+}
+
+impl IntoResponse for FieldsetError {
+    fn into_response(self) -> Response {
+        (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(self.to_string()))).into_response()
+    }
+}
+
+impl FieldsetQuery {
+    #[cfg(test)]
+    pub fn from_parts(fields: &[&str], include: &[&str]) -> Self {
+        let list = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        Self {
+            fields: (!fields.is_empty()).then(|| list(fields)),
+            include: list(include),
+        }
+    }
+
+    /// Check the request against the properties of `T` and the relations the
+    /// endpoint can embed. A nested include such as `lines.account` implies its parent.
+    pub fn resolve<T>(&self, relations: &'static [&'static str]) -> Result<Fieldset, FieldsetError>
+    where
+        T: for<'s> ToSchema<'s>,
+    {
+        let mut include = BTreeSet::new();
+        for name in &self.include {
+            let relation = relations.iter().find(|r| *r == name).ok_or_else(|| FieldsetError::UnknownInclude {
+                name: name.clone(),
+                allowed: if relations.is_empty() { "none".to_string() } else { relations.join(", ") },
+            })?;
+            include.insert(*relation);
+            for (i, _) in relation.match_indices('.') {
+                if let Some(parent) = relations.iter().find(|r| **r == &relation[..i]) {
+                    include.insert(*parent);
+                }
+            }
+        }
+
+        let fields = match &self.fields {
+            Some(requested) => {
+                let properties = properties::<T>();
+                let mut fields = BTreeSet::new();
+                for field in requested {
+                    if !properties.contains(field) {
+                        return Err(FieldsetError::UnknownField {
+                            field: field.clone(),
+                            allowed: properties.iter().cloned().collect::<Vec<_>>().join(", "),
+                        });
+                    }
+                    fields.insert(field.clone());
+                }
+                Some(fields)
+            }
+            None => None,
+        };
+
+        Ok(Fieldset { fields, include })
+    }
+}
+
+/// A validated `?fields=` / `?include=` request.
+#[derive(Debug, Default)]
+pub struct Fieldset {
+    fields: Option<BTreeSet<String>>,
+    include: BTreeSet<&'static str>,
+}
+
+impl Fieldset {
+    /// Whether `relation` (e.g. `"lines.account"`) should be loaded and embedded.
+    pub fn includes(&self, relation: &str) -> bool {
+        self.include.contains(relation)
+    }
+
+    /// Whether any relation is to be embedded.
+    pub fn has_includes(&self) -> bool {
+        !self.include.is_empty()
+    }
+
+    /// Whether the property `name` is part of the response.
+    pub fn selects(&self, name: &str) -> bool {
+        self.fields.as_ref().is_none_or(|fields| name == "id" || fields.contains(name))
+    }
+
+    /// Serialize `item`, keeping only the requested fields.
+    pub fn apply<T: Serialize>(&self, item: &T) -> Value {
+        let mut value = serde_json::to_value(item).unwrap_or(Value::Null);
+        if let (Some(fields), Value::Object(object)) = (&self.fields, &mut value) {
+            object.retain(|key, _| key == "id" || fields.contains(key) || self.include_root(key));
+        }
+        value
+    }
+
+    pub fn page<T: Serialize>(&self, page: ListPage<T>) -> ListPage<Value> {
+        page.map(|item| self.apply(&item))
+    }
+
+    fn include_root(&self, key: &str) -> bool {
+        self.include.iter().any(|name| name.split('.').next() == Some(key))
+    }
+}
+
+/// Distinct ids referenced by `items`, for a batched `id = ANY($2)` lookup.
+pub fn ids<T>(items: &[T], id: impl Fn(&T) -> Option<Uuid>) -> Vec<Uuid> {
+    items.iter().filter_map(id).collect::<BTreeSet<_>>().into_iter().collect()
+}
+
+fn properties<T: for<'s> ToSchema<'s>>() -> BTreeSet<String> {
+    match T::schema().1 {
+        RefOr::T(Schema::Object(object)) => object.properties.into_keys().collect(),
+        _ => BTreeSet::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Serialize, ToSchema)]
+    struct Entry {
+        id: u32,
+        number: String,
+        status: String,
+        lines: Option<Vec<u32>>,
+    }
+
+    fn query(fields: &[&str], include: &[&str]) -> FieldsetQuery {
+        FieldsetQuery::from_parts(fields, include)
+    }
+
+    fn entry() -> Entry {
+        Entry { id: 1, number: "JE-1".into(), status: "draft".into(), lines: Some(vec![7]) }
+    }
+
+    #[test]
+    fn sparse_fields_keep_id_and_includes() {
+        let fieldset = query(&["number"], &["lines.account"])
+            .resolve::<Entry>(&["lines", "lines.account"])
+            .unwrap();
+        assert!(fieldset.includes("lines"));
+        assert_eq!(fieldset.apply(&entry()), json!({ "id": 1, "number": "JE-1", "lines": [7] }));
+    }
+
+    #[test]
+    fn no_fields_returns_everything() {
+        let fieldset = query(&[], &[]).resolve::<Entry>(&[]).unwrap();
+        assert_eq!(fieldset.apply(&entry())["status"], "draft");
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        assert!(matches!(
+            query(&["secret"], &[]).resolve::<Entry>(&[]),
+            Err(FieldsetError::UnknownField { .. })
+        ));
+        assert!(matches!(
+            query(&[], &["vendor"]).resolve::<Entry>(&["lines"]),
+            Err(FieldsetError::UnknownInclude { .. })
+        ));
+    }
+
+    #[test]
+    fn ids_are_distinct() {
+        let a = Uuid::new_v4();
+        assert_eq!(ids(&[Some(a), None, Some(a)], |id| *id), vec![a]);
+    }
+}
@@ -1,30 +1,34 @@
-use axum::{extract::{State, Extension, Query, Path}, Json};
-use shared_types::{ApiResponse, PaginatedResponse, PaginationMeta};
+use axum::{extract::{State, Extension, Path}, response::{IntoResponse, Response}, Json};
+use shared_types::{ApiResponse, Currency, DocumentType, Money};
 use std::sync::Arc;
 use tracing::info;
-use sqlx::{Row, Acquire};
-use utoipa::ToSchema;
-use validator::Validate;
-use chrono::{DateTime, NaiveDate, Utc};
-use rust_decimal::Decimal;
+use std::collections::HashMap;
 use uuid::Uuid;
+use core_domain::{DomainResult, JournalLine};
+use persistence::{AccountRepo, JournalRepo, NewJournalEntry, NewJournalLine, Tx};
 
-use crate::{state::AppState, middleware::{auth_middleware::CurrentUser, db_conn::DbConn}};
+use crate::{handlers::domain_error, state::AppState, export::{self, ExportRequest, ExportSource}, fieldset::{self, Fieldset, FieldsetQuery}, list_query::{Field, FieldType, ListPage, ListQuery, ListSpec}, cache::CacheScope, extractors::{preconditions::{self, Preconditions}, ReadFreshness, RequestLocale}, middleware::{auth_middleware::CurrentUser, db_conn::DbConn}, sequences};
 use shared_types::accounting::*;
 
-// Query parameters for listing accounts
-#[derive(serde::Deserialize, Validate, Debug)]
-pub struct ListAccountsQuery {
-    #[validate(range(min = 1, max = 10000))]
-    pub page: Option<u32>,
-    #[validate(range(min = 1, max = 100))]
-    pub per_page: Option<u32>,
-    #[validate(length(min = 1, max = 100))]
-    pub search: Option<String>,
-    pub account_type: Option<String>,
-    pub sort_by: Option<String>,
-    pub sort_order: Option<String>,
-}
+static ACCOUNT_LIST: ListSpec = ListSpec {
+    from: "accounts",
+    tenant_column: "tenant_id",
+    base_filter: Some("is_active = true"),
+    id_column: "id",
+    fields: &[
+        Field::new("code", "code", FieldType::Text).sortable(),
+        Field::new("name", "name", FieldType::Text).sortable(),
+        Field::new("account_type", "account_type", FieldType::Text).sortable(),
+        Field::new("account_subtype", "account_subtype", FieldType::Text).nullable(),
+        Field::new("balance_type", "balance_type", FieldType::Text),
+        Field::new("parent_id", "parent_id", FieldType::Uuid).nullable(),
+        Field::new("created_at", "created_at", FieldType::Timestamp).sortable(),
+        Field::new("updated_at", "updated_at", FieldType::Timestamp).sortable(),
+    ],
+    search: &["code", "name"],
+    default_sort: "code",
+    aliases: &[],
+};
 
 #[utoipa::path(
     get,
@@ -32,25 +36,41 @@ pub struct ListAccountsQuery {
     params(
         ("page" = Option<u32>, Query, description = "Page number"),
         ("per_page" = Option<u32>, Query, description = "Items per page"),
-        ("search" = Option<String>, Query, description = "Search term"),
-        ("account_type" = Option<String>, Query, description = "Filter by account type"),
-        ("sort_by" = Option<String>, Query, description = "code|name|created_at"),
-        ("sort_order" = Option<String>, Query, description = "asc|desc"),
+        ("cursor" = Option<String>, Query, description = "Opaque cursor from pagination.next_cursor"),
+        ("limit" = Option<u32>, Query, description = "Items per page (keyset pagination, max 200)"),
+        ("count" = Option<CountMode>, Query, description = "Keyset total: none|estimated|exact"),
+        ("search" = Option<String>, Query, description = "Search code and name"),
+        ("sort" = Option<String>, Query, description = "Comma-separated, '-' for descending: code|name|account_type|created_at|updated_at"),
+        ("account_type" = Option<String>, Query, description = "Filter by account type; any field also takes field[op]=value with eq|ne|in|gt|gte|lt|lte|ilike|null"),
+        ("parent_id" = Option<String>, Query, description = "Filter by parent account"),
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return; id is always included"),
+        ("format" = Option<String>, Query, description = "Stream every matching row as csv|xlsx|jsonl instead of a JSON page (also via Accept)"),
+    ),
+    responses(
+        (status = 200, description = "List accounts", body = inline(ApiResponse<ListPage<Account>>)),
+        (status = 400, description = "Unknown field, operator or value")
     ),
-    responses((status = 200, description = "List accounts", body = ApiResponse<PaginatedResponse<Account>>)),
     tag = "accounting"
 )]
 pub async fn list_accounts(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
-    Query(q): Query<ListAccountsQuery>,
-) -> Json<ApiResponse<PaginatedResponse<Account>>> {
+    ReadFreshness(freshness): ReadFreshness,
+    query: ListQuery,
+    fieldset: FieldsetQuery,
+    export: ExportRequest,
+) -> Response {
     info!("List accounts");
 
-    if let Err(e) = q.validate() {
-        return Json(ApiResponse::error_typed(format!("Invalid query: {}", e)));
-    }
+    let plan = match ACCOUNT_LIST.plan(&query) {
+        Ok(plan) => plan,
+        Err(e) => return e.into_response(),
+    };
+    let fieldset = match fieldset.resolve::<Account>(&[]) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return e.into_response(),
+    };
 
     // Set tenant context (RLS)
     let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
@@ -58,402 +78,168 @@ pub async fn list_accounts(
         .execute(&mut *conn)
         .await;
 
-    let page = q.page.unwrap_or(1).max(1);
-    let per_page = q.per_page.unwrap_or(20).clamp(1, 100);
-    let offset = ((page - 1) as i64) * (per_page as i64);
-
-    // Build dynamic filters
-    let mut where_parts: Vec<&str> = vec!["is_active = true"];
-    let mut bind_count = 0;
-
-    // Determine sort
-    let sort_by = q.sort_by.as_deref().unwrap_or("code");
-    let order_by = match sort_by {
-        "name" => "name",
-        "created_at" => "created_at",
-        _ => "code"
-    };
-    let direction = match q.sort_order.as_deref().map(|s| s.to_ascii_lowercase()) {
-        Some(ref s) if s == "desc" => "DESC",
-        _ => "ASC",
-    };
-
-    // Build query with filters
-    let (total_count, rows): (i64, Vec<Account>) = if let Some(search) = &q.search {
-        bind_count += 1;
-        let pattern = format!("%{}%", search);
-        where_parts.push("(code ILIKE $1 OR name ILIKE $1)");
-
-        if let Some(account_type) = &q.account_type {
-            bind_count += 1;
-            where_parts.push("account_type = $2");
-        }
-
-        let where_clause = format!("WHERE {}", where_parts.join(" AND "));
-
-        let total: i64 = if q.account_type.is_some() {
-            sqlx::query_scalar(&format!("SELECT COUNT(*) FROM accounts {}", where_clause))
-                .bind(&pattern)
-                .bind(q.account_type.as_ref().unwrap())
-                .fetch_one(&mut *conn)
-                .await
-                .unwrap_or(0)
-        } else {
-            sqlx::query_scalar(&format!("SELECT COUNT(*) FROM accounts {}", where_clause))
-                .bind(&pattern)
-                .fetch_one(&mut *conn)
-                .await
-                .unwrap_or(0)
-        };
-
-        let sql = format!(
-            r#"SELECT id, tenant_id, code, name, account_type, account_subtype, parent_id,
-                      is_active, description, balance_type, created_at, updated_at
-               FROM accounts
-               {}
-               ORDER BY {} {}
-               LIMIT ${} OFFSET ${}"#,
-            where_clause, order_by, direction, bind_count + 1, bind_count + 2
-        );
-
-        let query = if q.account_type.is_some() {
-            sqlx::query(&sql)
-                .bind(&pattern)
-                .bind(q.account_type.as_ref().unwrap())
-                .bind(per_page as i64)
-                .bind(offset)
-        } else {
-            sqlx::query(&sql)
-                .bind(&pattern)
-                .bind(per_page as i64)
-                .bind(offset)
-        };
-
-        let data = query
-            .fetch_all(&mut *conn)
-            .await
-            .map(|recs| {
-                recs.into_iter().map(|row| {
-                    Account {
-                        id: row.get("id"),
-                        tenant_id: row.get("tenant_id"),
-                        code: row.get("code"),
-                        name: row.get("name"),
-                        account_type: match row.get::<String, _>("account_type").as_str() {
-                            "asset" => AccountType::Asset,
-                            "liability" => AccountType::Liability,
-                            "equity" => AccountType::Equity,
-                            "revenue" => AccountType::Revenue,
-                            "expense" => AccountType::Expense,
-                            _ => AccountType::Asset,
-                        },
-                        account_subtype: row.try_get("account_subtype").unwrap_or(None),
-                        parent_id: row.try_get("parent_id").unwrap_or(None),
-                        is_active: row.get("is_active"),
-                        description: row.try_get("description").unwrap_or(None),
-                        balance_type: match row.get::<String, _>("balance_type").as_str() {
-                            "credit" => BalanceType::Credit,
-                            _ => BalanceType::Debit,
-                        },
-                        created_at: row.get("created_at"),
-                        updated_at: row.get("updated_at"),
-                    }
-                }).collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
-
-        (total, data)
-    } else {
-        // No search filter
-        if let Some(account_type) = &q.account_type {
-            where_parts.push("account_type = $1");
-            bind_count += 1;
-        }
-
-        let where_clause = format!("WHERE {}", where_parts.join(" AND "));
-
-        let total: i64 = if q.account_type.is_some() {
-            sqlx::query_scalar(&format!("SELECT COUNT(*) FROM accounts {}", where_clause))
-                .bind(q.account_type.as_ref().unwrap())
-                .fetch_one(&mut *conn)
-                .await
-                .unwrap_or(0)
-        } else {
-            sqlx::query_scalar(&format!("SELECT COUNT(*) FROM accounts {}", where_clause))
-                .fetch_one(&mut *conn)
-                .await
-                .unwrap_or(0)
-        };
-
-        let sql = format!(
-            r#"SELECT id, tenant_id, code, name, account_type, account_subtype, parent_id,
-                      is_active, description, balance_type, created_at, updated_at
-               FROM accounts
-               {}
-               ORDER BY {} {}
-               LIMIT ${} OFFSET ${}"#,
-            where_clause, order_by, direction, bind_count + 1, bind_count + 2
-        );
-
-        let query = if q.account_type.is_some() {
-            sqlx::query(&sql)
-                .bind(q.account_type.as_ref().unwrap())
-                .bind(per_page as i64)
-                .bind(offset)
-        } else {
-            sqlx::query(&sql)
-                .bind(per_page as i64)
-                .bind(offset)
-        };
-
-        let data = query
-            .fetch_all(&mut *conn)
-            .await
-            .map(|recs| {
-                recs.into_iter().map(|row| {
-                    Account {
-                        id: row.get("id"),
-                        tenant_id: row.get("tenant_id"),
-                        code: row.get("code"),
-                        name: row.get("name"),
-                        account_type: match row.get::<String, _>("account_type").as_str() {
-                            "asset" => AccountType::Asset,
-                            "liability" => AccountType::Liability,
-                            "equity" => AccountType::Equity,
-                            "revenue" => AccountType::Revenue,
-                            "expense" => AccountType::Expense,
-                            _ => AccountType::Asset,
-                        },
-                        account_subtype: row.try_get("account_subtype").unwrap_or(None),
-                        parent_id: row.try_get("parent_id").unwrap_or(None),
-                        is_active: row.get("is_active"),
-                        description: row.try_get("description").unwrap_or(None),
-                        balance_type: match row.get::<String, _>("balance_type").as_str() {
-                            "credit" => BalanceType::Credit,
-                            _ => BalanceType::Debit,
-                        },
-                        created_at: row.get("created_at"),
-                        updated_at: row.get("updated_at"),
-                    }
-                }).collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
-
-        (total, data)
-    };
-
-    let total_pages = ((total_count as f64) / (per_page as f64)).ceil() as u32;
-    let meta = PaginationMeta {
-        current_page: page,
-        per_page,
-        total_pages,
-        total_count: total_count as u64,
-        has_next: page < total_pages,
-        has_prev: page > 1,
-    };
-
-    let resp = PaginatedResponse { data: rows, pagination: meta };
-    Json(ApiResponse::success(resp))
+    if let Some(format) = export.format() {
+        let source = ExportSource { name: "accounts", plan, columns: AccountRepo::COLUMNS, map: AccountRepo::from_row, relations: &[] };
+        return export::respond(&state, conn, current.tenant_id, format, fieldset, source).await;
+    }
+    let page = state.cache.get_or_load(current.tenant_id, CacheScope::Accounts, &query.cache_key(), freshness, || {
+        plan.fetch(&mut conn, current.tenant_id, AccountRepo::COLUMNS, AccountRepo::from_row)
+    })
+    .await;
+    match page {
+        Ok(page) => fieldset.page(page).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
 }
 
 #[utoipa::path(
     post,
     path = "/api/v1/accounting/accounts",
     request_body = CreateAccountRequest,
-    responses((status = 201, description = "Account created", body = ApiResponse<Account>)),
+    responses((status = 201, description = "Account created", body = inline(ApiResponse<Account>))),
     tag = "accounting"
 )]
 pub async fn create_account(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
     Json(req): Json<CreateAccountRequest>,
-) -> Json<ApiResponse<Account>> {
+) -> Response {
     info!("Create account");
 
-    // Set tenant context (RLS)
-    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
-        .bind(current.tenant_id.to_string())
-        .execute(&mut *conn)
-        .await;
-
-    // Check if account code already exists
-    let existing = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM accounts WHERE code = $1")
-        .bind(&req.code)
-        .fetch_one(&mut *conn)
-        .await
-        .unwrap_or(0);
+    let mut tx = match persistence::begin(&mut conn, current.tenant_id).await {
+        Ok(tx) => tx,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
 
-    if existing > 0 {
-        return Json(ApiResponse::error_typed("Account code already exists".to_string()));
+    match AccountRepo::code_exists(&mut tx, current.tenant_id, &req.code).await {
+        Ok(false) => {}
+        Ok(true) => return Json(ApiResponse::<()>::error("Account code already exists".to_string())).into_response(),
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
     }
 
-    let account_type_str = match req.account_type {
-        AccountType::Asset => "asset",
-        AccountType::Liability => "liability",
-        AccountType::Equity => "equity",
-        AccountType::Revenue => "revenue",
-        AccountType::Expense => "expense",
+    let account = match AccountRepo::insert(&mut tx, current.tenant_id, &req).await {
+        Ok(account) => account,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
     };
-
-    let balance_type_str = match req.balance_type {
-        BalanceType::Debit => "debit",
-        BalanceType::Credit => "credit",
-    };
-
-    let row = sqlx::query(
-        r#"INSERT INTO accounts (tenant_id, code, name, account_type, account_subtype, parent_id, description, balance_type)
-           VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-           RETURNING id, tenant_id, code, name, account_type, account_subtype, parent_id, is_active, description, balance_type, created_at, updated_at"#
-    )
-    .bind(current.tenant_id)
-    .bind(&req.code)
-    .bind(&req.name)
-    .bind(account_type_str)
-    .bind(&req.account_subtype)
-    .bind(&req.parent_id)
-    .bind(&req.description)
-    .bind(balance_type_str)
-    .fetch_one(&mut *conn)
-    .await;
-
-    match row {
-        Ok(row) => {
-            let account = Account {
-                id: row.get("id"),
-                tenant_id: row.get("tenant_id"),
-                code: row.get("code"),
-                name: row.get("name"),
-                account_type: match row.get::<String, _>("account_type").as_str() {
-                    "asset" => AccountType::Asset,
-                    "liability" => AccountType::Liability,
-                    "equity" => AccountType::Equity,
-                    "revenue" => AccountType::Revenue,
-                    "expense" => AccountType::Expense,
-                    _ => AccountType::Asset,
-                },
-                account_subtype: row.try_get("account_subtype").unwrap_or(None),
-                parent_id: row.try_get("parent_id").unwrap_or(None),
-                is_active: row.get("is_active"),
-                description: row.try_get("description").unwrap_or(None),
-                balance_type: match row.get::<String, _>("balance_type").as_str() {
-                    "credit" => BalanceType::Credit,
-                    _ => BalanceType::Debit,
-                },
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            };
-            Json(ApiResponse::success(account))
-        }
-        Err(e) => Json(ApiResponse::error_typed(format!("{}", e)))
+    if let Err(e) = tx.commit().await {
+        return Json(ApiResponse::<()>::error(format!("{}", e))).into_response();
     }
+    state.cache.invalidate(current.tenant_id, CacheScope::Accounts).await;
+    preconditions::tagged(account.updated_at, ApiResponse::success(account))
 }
 
 #[utoipa::path(
     get,
     path = "/api/v1/accounting/accounts/{id}",
-    params(("id" = uuid::Uuid, Path, description = "Account ID")),
-    responses((status = 200, description = "Account detail", body = ApiResponse<Account>)),
+    params(
+        ("id" = uuid::Uuid, Path, description = "Account ID"),
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return; id is always included"),
+    ),
+    responses(
+        (status = 200, description = "Account detail", body = inline(ApiResponse<Account>)),
+        (status = 304, description = "Not modified since the ETag in If-None-Match"),
+        (status = 400, description = "Unknown field")
+    ),
     tag = "accounting"
 )]
 pub async fn get_account(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
+    ReadFreshness(freshness): ReadFreshness,
+    preconditions: Preconditions,
+    fieldset: FieldsetQuery,
     Path(id): Path<Uuid>,
-) -> Json<ApiResponse<Account>> {
+) -> Response {
     info!("Get account {}", id);
 
-    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
-        .bind(current.tenant_id.to_string())
-        .execute(&mut *conn)
-        .await;
+    let fieldset = match fieldset.resolve::<Account>(&[]) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return e.into_response(),
+    };
 
-    let row = sqlx::query(
-        r#"SELECT id, tenant_id, code, name, account_type, account_subtype, parent_id,
-                  is_active, description, balance_type, created_at, updated_at
-           FROM accounts WHERE id = $1 LIMIT 1"#
-    )
-    .bind(id)
-    .fetch_optional(&mut *conn)
+    let account = state.cache.get_or_load(current.tenant_id, CacheScope::Accounts, &format!("account:{}", id), freshness, || async {
+        let mut tx = persistence::begin(&mut conn, current.tenant_id).await?;
+        AccountRepo::find(&mut tx, current.tenant_id, id).await
+    })
     .await;
-
-    match row {
-        Ok(Some(row)) => {
-            let account = Account {
-                id: row.get("id"),
-                tenant_id: row.get("tenant_id"),
-                code: row.get("code"),
-                name: row.get("name"),
-                account_type: match row.get::<String, _>("account_type").as_str() {
-                    "asset" => AccountType::Asset,
-                    "liability" => AccountType::Liability,
-                    "equity" => AccountType::Equity,
-                    "revenue" => AccountType::Revenue,
-                    "expense" => AccountType::Expense,
-                    _ => AccountType::Asset,
-                },
-                account_subtype: row.try_get("account_subtype").unwrap_or(None),
-                parent_id: row.try_get("parent_id").unwrap_or(None),
-                is_active: row.get("is_active"),
-                description: row.try_get("description").unwrap_or(None),
-                balance_type: match row.get::<String, _>("balance_type").as_str() {
-                    "credit" => BalanceType::Credit,
-                    _ => BalanceType::Debit,
-                },
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            };
-            Json(ApiResponse::success(account))
+    match account {
+        Ok(Some(account)) => {
+            if preconditions.not_modified(account.updated_at) {
+                return preconditions::not_modified(account.updated_at);
+            }
+            preconditions::tagged(account.updated_at, ApiResponse::success(fieldset.apply(&account)))
         }
-        Ok(None) => Json(ApiResponse::error_typed("Account not found".to_string())),
-        Err(e) => Json(ApiResponse::error_typed(format!("{}", e))),
+        Ok(None) => Json(ApiResponse::<()>::error("Account not found".to_string())).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
     }
 }
 
 // Journal Entry handlers
-#[derive(serde::Deserialize, Validate, Debug)]
-pub struct ListJournalEntriesQuery {
-    #[validate(range(min = 1, max = 10000))]
-    pub page: Option<u32>,
-    #[validate(range(min = 1, max = 100))]
-    pub per_page: Option<u32>,
-    #[validate(length(min = 1, max = 100))]
-    pub search: Option<String>,
-    pub status: Option<String>,
-    pub from_date: Option<NaiveDate>,
-    pub to_date: Option<NaiveDate>,
-    pub sort_by: Option<String>,
-    pub sort_order: Option<String>,
-}
+static JOURNAL_ENTRY_LIST: ListSpec = ListSpec {
+    from: "journal_entries",
+    tenant_column: "tenant_id",
+    base_filter: None,
+    id_column: "id",
+    fields: &[
+        Field::new("entry_number", "entry_number", FieldType::Text).sortable(),
+        Field::new("entry_date", "entry_date", FieldType::Date).sortable(),
+        Field::new("status", "status", FieldType::Text).sortable(),
+        Field::new("reference", "reference", FieldType::Text).nullable(),
+        Field::new("total_debit", "total_debit", FieldType::Decimal).sortable(),
+        Field::new("created_by", "created_by", FieldType::Uuid),
+        Field::new("posted_at", "posted_at", FieldType::Timestamp).sortable().nullable(),
+        Field::new("created_at", "created_at", FieldType::Timestamp).sortable(),
+        Field::new("updated_at", "updated_at", FieldType::Timestamp).sortable(),
+    ],
+    search: &["entry_number", "description"],
+    default_sort: "-entry_date",
+    aliases: &[("from_date", "entry_date[gte]"), ("to_date", "entry_date[lte]")],
+};
 
 #[utoipa::path(
     get,
     path = "/api/v1/accounting/journal-entries",
     params(
-        ("page" = Option<u32>, Query, description = "Page number"),
-        ("per_page" = Option<u32>, Query, description = "Items per page"),
-        ("search" = Option<String>, Query, description = "Search term"),
-        ("status" = Option<String>, Query, description = "Filter by status"),
-        ("from_date" = Option<String>, Query, description = "From date (YYYY-MM-DD)"),
-        ("to_date" = Option<String>, Query, description = "To date (YYYY-MM-DD)"),
-        ("sort_by" = Option<String>, Query, description = "entry_date|entry_number|created_at"),
-        ("sort_order" = Option<String>, Query, description = "asc|desc"),
+        ("page" = Option<u32>, Query, description = "Page number (offset pagination)"),
+        ("per_page" = Option<u32>, Query, description = "Items per page (offset pagination)"),
+        ("cursor" = Option<String>, Query, description = "Opaque cursor from pagination.next_cursor"),
+        ("limit" = Option<u32>, Query, description = "Items per page (keyset pagination, max 200)"),
+        ("count" = Option<CountMode>, Query, description = "Keyset total: none|estimated|exact"),
+        ("search" = Option<String>, Query, description = "Search entry number and description"),
+        ("sort" = Option<String>, Query, description = "Comma-separated, '-' for descending: entry_date|entry_number|status|total_debit|posted_at|created_at|updated_at"),
+        ("status" = Option<String>, Query, description = "Filter by status; any field also takes field[op]=value with eq|ne|in|gt|gte|lt|lte|ilike|null"),
+        ("from_date" = Option<String>, Query, description = "From date (YYYY-MM-DD), same as entry_date[gte]"),
+        ("to_date" = Option<String>, Query, description = "To date (YYYY-MM-DD), same as entry_date[lte]"),
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return; id is always included"),
+        ("include" = Option<String>, Query, description = "Comma-separated relations to embed: lines|lines.account"),
+        ("format" = Option<String>, Query, description = "Stream every matching row as csv|xlsx|jsonl instead of a JSON page (also via Accept)"),
+    ),
+    responses(
+        (status = 200, description = "List journal entries; with cursor or limit the data is a CursorPaginatedResponse", body = inline(ApiResponse<ListPage<JournalEntry>>)),
+        (status = 400, description = "Unknown field, operator or value")
     ),
-    responses((status = 200, description = "List journal entries", body = ApiResponse<PaginatedResponse<JournalEntry>>)),
     tag = "accounting"
 )]
 pub async fn list_journal_entries(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
-    Query(q): Query<ListJournalEntriesQuery>,
-) -> Json<ApiResponse<PaginatedResponse<JournalEntry>>> {
+    query: ListQuery,
+    fieldset: FieldsetQuery,
+    export: ExportRequest,
+) -> Response {
     info!("List journal entries");
 
-    if let Err(e) = q.validate() {
-        return Json(ApiResponse::error_typed(format!("Invalid query: {}", e)));
-    }
+    let plan = match JOURNAL_ENTRY_LIST.plan(&query) {
+        Ok(plan) => plan,
+        Err(e) => return e.into_response(),
+    };
+    let fieldset = match fieldset.resolve::<JournalEntry>(JOURNAL_ENTRY_INCLUDES) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return e.into_response(),
+    };
 
     // Set tenant context (RLS)
     let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
@@ -461,255 +247,146 @@ pub async fn list_journal_entries(
         .execute(&mut *conn)
         .await;
 
-    let page = q.page.unwrap_or(1).max(1);
-    let per_page = q.per_page.unwrap_or(20).clamp(1, 100);
-    let offset = ((page - 1) as i64) * (per_page as i64);
-
-    // Build dynamic filters
-    let mut where_parts: Vec<String> = vec![];
-    let mut bind_params: Vec<Box<dyn sqlx::Encode<'_, sqlx::Postgres> + Send + Sync>> = vec![];
-    let mut param_count = 0;
-
-    if let Some(search) = &q.search {
-        param_count += 1;
-        where_parts.push(format!("(entry_number ILIKE ${} OR description ILIKE ${})", param_count, param_count));
-        bind_params.push(Box::new(format!("%{}%", search)));
+    if let Some(format) = export.format() {
+        let source = ExportSource { name: "journal-entries", plan, columns: JournalRepo::COLUMNS, map: JournalRepo::from_row, relations: JOURNAL_ENTRY_INCLUDES };
+        return export::respond(&state, conn, current.tenant_id, format, fieldset, source).await;
     }
-
-    if let Some(status) = &q.status {
-        param_count += 1;
-        where_parts.push(format!("status = ${}", param_count));
-        bind_params.push(Box::new(status.clone()));
+    let mut tx = match persistence::begin(&mut conn, current.tenant_id).await {
+        Ok(tx) => tx,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+    let mut page = match plan.fetch(&mut tx, current.tenant_id, JournalRepo::COLUMNS, JournalRepo::from_row).await {
+        Ok(page) => page,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+    if let Err(e) = embed_journal_entries(&mut tx, current.tenant_id, &fieldset, page.data_mut()).await {
+        return Json(ApiResponse::<()>::error(format!("{}", e))).into_response();
     }
+    fieldset.page(page).into_response()
+}
 
-    if let Some(from_date) = &q.from_date {
-        param_count += 1;
-        where_parts.push(format!("entry_date >= ${}", param_count));
-        bind_params.push(Box::new(*from_date));
+/// Relations journal entries can embed with `?include=`
+const JOURNAL_ENTRY_INCLUDES: &[&str] = &["lines", "lines.account"];
+
+/// Fill the relations requested with `?include=`: one query for the lines of
+/// every entry, and one for their accounts.
+async fn embed_journal_entries(
+    tx: &mut Tx<'_>,
+    tenant_id: Uuid,
+    fieldset: &Fieldset,
+    entries: &mut [JournalEntry],
+) -> Result<(), sqlx::Error> {
+    if !fieldset.includes("lines") {
+        return Ok(());
     }
 
-    if let Some(to_date) = &q.to_date {
-        param_count += 1;
-        where_parts.push(format!("entry_date <= ${}", param_count));
-        bind_params.push(Box::new(*to_date));
+    let mut lines = JournalRepo::lines(tx, tenant_id, &fieldset::ids(entries, |e| Some(e.id))).await?;
+    if fieldset.includes("lines.account") {
+        let accounts = AccountRepo::find_many(tx, tenant_id, &fieldset::ids(&lines, |l| Some(l.account_id))).await?;
+        for line in lines.iter_mut() {
+            line.account = accounts.get(&line.account_id).cloned();
+        }
     }
 
-    let where_clause = if where_parts.is_empty() {
-        String::new()
-    } else {
-        format!("WHERE {}", where_parts.join(" AND "))
-    };
-
-    // Determine sort
-    let sort_by = q.sort_by.as_deref().unwrap_or("entry_date");
-    let order_by = match sort_by {
-        "entry_number" => "entry_number",
-        "created_at" => "created_at",
-        _ => "entry_date"
-    };
-    let direction = match q.sort_order.as_deref().map(|s| s.to_ascii_lowercase()) {
-        Some(ref s) if s == "asc" => "ASC",
-        _ => "DESC",
-    };
-
-    // Count total
-    let count_sql = format!("SELECT COUNT(*) FROM journal_entries {}", where_clause);
-    let mut count_query = sqlx::query_scalar::<_, i64>(&count_sql);
-    for param in &bind_params {
-        // This is a simplified approach - in real implementation you'd need proper parameter binding
+    let mut by_entry: HashMap<Uuid, Vec<JournalEntryLine>> = HashMap::new();
+    for line in lines {
+        by_entry.entry(line.journal_entry_id).or_default().push(line);
     }
-    let total_count = count_query.fetch_one(&mut *conn).await.unwrap_or(0);
-
-    // Fetch data
-    let data_sql = format!(
-        r#"SELECT id, tenant_id, entry_number, entry_date, reference, description,
-                  total_debit, total_credit, status, created_by, posted_by, posted_at,
-                  created_at, updated_at
-           FROM journal_entries
-           {}
-           ORDER BY {} {}
-           LIMIT ${} OFFSET ${}"#,
-        where_clause, order_by, direction, param_count + 1, param_count + 2
-    );
-
-    let mut data_query = sqlx::query(&data_sql);
-    for param in &bind_params {
-        // This is a simplified approach - in real implementation you'd need proper parameter binding
+    for entry in entries.iter_mut() {
+        entry.lines = Some(by_entry.remove(&entry.id).unwrap_or_default());
     }
-    data_query = data_query.bind(per_page as i64).bind(offset);
-
-    let rows = data_query
-        .fetch_all(&mut *conn)
-        .await
-        .map(|recs| {
-            recs.into_iter().map(|row| {
-                JournalEntry {
-                    id: row.get("id"),
-                    tenant_id: row.get("tenant_id"),
-                    entry_number: row.get("entry_number"),
-                    entry_date: row.get("entry_date"),
-                    reference: row.try_get("reference").unwrap_or(None),
-                    description: row.get("description"),
-                    total_debit: row.get("total_debit"),
-                    total_credit: row.get("total_credit"),
-                    status: match row.get::<String, _>("status").as_str() {
-                        "posted" => JournalEntryStatus::Posted,
-                        "reversed" => JournalEntryStatus::Reversed,
-                        _ => JournalEntryStatus::Draft,
-                    },
-                    created_by: row.get("created_by"),
-                    posted_by: row.try_get("posted_by").unwrap_or(None),
-                    posted_at: row.try_get("posted_at").unwrap_or(None),
-                    created_at: row.get("created_at"),
-                    updated_at: row.get("updated_at"),
-                    lines: None, // Will be loaded separately if needed
-                }
-            }).collect::<Vec<_>>()
-        })
-        .unwrap_or_default();
-
-    let total_pages = ((total_count as f64) / (per_page as f64)).ceil() as u32;
-    let meta = PaginationMeta {
-        current_page: page,
-        per_page,
-        total_pages,
-        total_count: total_count as u64,
-        has_next: page < total_pages,
-        has_prev: page > 1,
-    };
-
-    let resp = PaginatedResponse { data: rows, pagination: meta };
-    Json(ApiResponse::success(resp))
+    Ok(())
 }
 
 #[utoipa::path(
     post,
     path = "/api/v1/accounting/journal-entries",
     request_body = CreateJournalEntryRequest,
-    responses((status = 201, description = "Journal entry created", body = ApiResponse<JournalEntry>)),
+    responses(
+        (status = 201, description = "Journal entry created", body = inline(ApiResponse<JournalEntry>)),
+        (status = 422, description = "Unbalanced entry, a line that isn't exactly one positive debit or credit, or an unknown account")
+    ),
     tag = "accounting"
 )]
 pub async fn create_journal_entry(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
+    RequestLocale(locale): RequestLocale,
     Json(req): Json<CreateJournalEntryRequest>,
-) -> Json<ApiResponse<JournalEntry>> {
+) -> Response {
     info!("Create journal entry");
 
-    // Set tenant context (RLS)
-    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
-        .bind(current.tenant_id.to_string())
-        .execute(&mut *conn)
-        .await;
-
-    // Validate that debits equal credits
-    let total_debits: Decimal = req.lines.iter().map(|l| l.debit_amount).sum();
-    let total_credits: Decimal = req.lines.iter().map(|l| l.credit_amount).sum();
-
-    if total_debits != total_credits {
-        return Json(ApiResponse::error_typed("Total debits must equal total credits".to_string()));
-    }
-
-    if total_debits == Decimal::ZERO {
-        return Json(ApiResponse::error_typed("Journal entry must have non-zero amounts".to_string()));
-    }
-
-    // Validate that each line has either debit or credit (not both)
-    for line in &req.lines {
-        if (line.debit_amount > Decimal::ZERO && line.credit_amount > Decimal::ZERO) ||
-           (line.debit_amount == Decimal::ZERO && line.credit_amount == Decimal::ZERO) {
-            return Json(ApiResponse::error_typed("Each line must have either debit or credit amount (not both or neither)".to_string()));
-        }
-    }
-
-    // Generate entry number
-    let entry_number = format!("JE-{}-{:06}",
-        chrono::Utc::now().format("%Y%m"),
-        chrono::Utc::now().timestamp_millis() % 1000000
-    );
+    // Amounts must fit the entry's currency: no cents on a rupiah entry
+    let currency = req.currency.unwrap_or(Currency::IDR);
+    let lines = req
+        .lines
+        .iter()
+        .map(|l| {
+            JournalLine::new(
+                l.account_id,
+                Money::exact(l.debit_amount, currency)?,
+                Money::exact(l.credit_amount, currency)?,
+            )
+        })
+        .collect::<DomainResult<Vec<_>>>();
+    let draft = match lines.and_then(|lines| core_domain::JournalEntry::draft(currency, lines)) {
+        Ok(draft) => draft,
+        Err(e) => return domain_error(&e, locale),
+    };
 
     // Start transaction
-    let mut tx = match conn.begin().await {
+    let mut tx = match persistence::begin(&mut conn, current.tenant_id).await {
         Ok(tx) => tx,
-        Err(e) => return Json(ApiResponse::error_typed(format!("Failed to start transaction: {}", e))),
+        Err(e) => return Json(ApiResponse::<()>::error(format!("Failed to start transaction: {}", e))).into_response(),
     };
 
-    // Insert journal entry header
-    let journal_row = sqlx::query(
-        r#"INSERT INTO journal_entries (tenant_id, entry_number, entry_date, reference, description,
-                                       total_debit, total_credit, created_by)
-           VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-           RETURNING id, tenant_id, entry_number, entry_date, reference, description,
-                     total_debit, total_credit, status, created_by, posted_by, posted_at,
-                     created_at, updated_at"#
-    )
-    .bind(current.tenant_id)
-    .bind(&entry_number)
-    .bind(&req.entry_date)
-    .bind(&req.reference)
-    .bind(&req.description)
-    .bind(total_debits)
-    .bind(total_credits)
-    .bind(current.user_id)
-    .fetch_one(&mut *tx)
-    .await;
-
-    let journal_entry = match journal_row {
-        Ok(row) => {
-            let journal_id: Uuid = row.get("id");
-
-            // Insert journal entry lines
-            for (index, line) in req.lines.iter().enumerate() {
-                let line_result = sqlx::query(
-                    r#"INSERT INTO journal_entry_lines (tenant_id, journal_entry_id, account_id,
-                                                       description, debit_amount, credit_amount, line_number)
-                       VALUES ($1, $2, $3, $4, $5, $6, $7)"#
-                )
-                .bind(current.tenant_id)
-                .bind(journal_id)
-                .bind(line.account_id)
-                .bind(&line.description)
-                .bind(line.debit_amount)
-                .bind(line.credit_amount)
-                .bind((index + 1) as i32)
-                .execute(&mut *tx)
-                .await;
+    // Lines may only post to this tenant's accounts
+    let account_ids: Vec<Uuid> = draft.lines().iter().map(|l| l.account_id()).collect();
+    let accounts = match AccountRepo::find_many(&mut tx, current.tenant_id, &account_ids).await {
+        Ok(accounts) => accounts,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+    if let Some(unknown) = account_ids.iter().find(|id| !accounts.contains_key(id)) {
+        let e = core_domain::DomainError::ValidationFailed { message: format!("account {} does not exist", unknown) };
+        return domain_error(&e, locale);
+    }
 
-                if line_result.is_err() {
-                    let _ = tx.rollback().await;
-                    return Json(ApiResponse::error_typed("Failed to create journal entry lines".to_string()));
-                }
-            }
+    // Take the entry number; a rollback below returns it
+    let entry_number = match sequences::next_number(&mut tx, current.tenant_id, DocumentType::JournalEntry, req.entry_date).await {
+        Ok(number) => number,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
 
-            // Commit transaction
-            if tx.commit().await.is_err() {
-                return Json(ApiResponse::error_typed("Failed to commit transaction".to_string()));
-            }
+    let entry = NewJournalEntry {
+        entry_number: &entry_number,
+        entry_date: req.entry_date,
+        reference: req.reference.as_deref(),
+        description: &req.description,
+        total_debit: draft.total(),
+        total_credit: draft.total(),
+        created_by: current.user_id,
+    };
+    let lines: Vec<NewJournalLine> = req
+        .lines
+        .iter()
+        .zip(draft.lines())
+        .map(|(line, domain_line)| NewJournalLine {
+            account_id: domain_line.account_id(),
+            description: line.description.as_deref(),
+            debit: domain_line.debit_amount(),
+            credit: domain_line.credit_amount(),
+        })
+        .collect();
 
-            JournalEntry {
-                id: row.get("id"),
-                tenant_id: row.get("tenant_id"),
-                entry_number: row.get("entry_number"),
-                entry_date: row.get("entry_date"),
-                reference: row.try_get("reference").unwrap_or(None),
-                description: row.get("description"),
-                total_debit: row.get("total_debit"),
-                total_credit: row.get("total_credit"),
-                status: JournalEntryStatus::Draft,
-                created_by: row.get("created_by"),
-                posted_by: None,
-                posted_at: None,
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                lines: None,
-            }
-        }
-        Err(e) => {
-            let _ = tx.rollback().await;
-            return Json(ApiResponse::error_typed(format!("Failed to create journal entry: {}", e)));
-        }
+    let journal_entry = match JournalRepo::insert(&mut tx, current.tenant_id, &entry, &lines).await {
+        Ok(journal_entry) => journal_entry,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("Failed to create journal entry: {}", e))).into_response(),
     };
+    if tx.commit().await.is_err() {
+        return Json(ApiResponse::<()>::error("Failed to commit transaction".to_string())).into_response();
+    }
+    state.metrics.record_journal_entry_posted(current.tenant_id);
 
-    Json(ApiResponse::success(journal_entry))
+    preconditions::tagged(journal_entry.updated_at, ApiResponse::success(journal_entry))
 }
@@ -0,0 +1,458 @@
+use axum::{
+    body::Body,
+    extract::{Extension, Multipart, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use shared_types::{ApiResponse, Attachment, AttachmentEntity};
+use sqlx::{postgres::PgRow, PgConnection, Row};
+use std::sync::Arc;
+use tracing::{error, info};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    attachments::{self, SpoolError, Spooled, UrlSigner},
+    middleware::{auth_middleware::CurrentUser, db_conn::DbConn},
+    state::AppState,
+    storage::StorageError,
+};
+
+const ATTACHMENT_COLUMNS: &str = r#"a.id, a.entity_type, a.entity_id, a.filename, a.content_type, b.size, a.sha256,
+       a.uploaded_by, a.created_at"#;
+const ATTACHMENT_FROM: &str =
+    "attachments a JOIN attachment_blobs b ON b.tenant_id = a.tenant_id AND b.sha256 = a.sha256";
+
+fn attachment_from_row(row: &PgRow, signer: &UrlSigner) -> Attachment {
+    let id = row.get("id");
+    let (url, url_expires_at) = signer.sign(id, Utc::now());
+    Attachment {
+        id,
+        entity_type: AttachmentEntity::parse(row.get("entity_type")).unwrap_or(AttachmentEntity::Company),
+        entity_id: row.get("entity_id"),
+        filename: row.get("filename"),
+        content_type: row.get("content_type"),
+        size: row.get::<i64, _>("size") as u64,
+        sha256: row.get("sha256"),
+        url,
+        url_expires_at,
+        uploaded_by: row.get("uploaded_by"),
+        uploaded_at: row.get("created_at"),
+    }
+}
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(ApiResponse::<()>::error(message.into()))).into_response()
+}
+
+fn unknown_entity_type() -> Response {
+    let allowed: Vec<_> = AttachmentEntity::ALL.iter().map(|e| e.as_str()).collect();
+    error_response(StatusCode::BAD_REQUEST, format!("entity_type must be one of: {}", allowed.join(", ")))
+}
+
+/// The record an attachment belongs to.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AttachmentTarget {
+    /// company|contact|product|vendor|purchase_order|journal_entry
+    entity_type: String,
+    entity_id: Uuid,
+}
+
+impl AttachmentTarget {
+    fn entity(&self) -> Option<AttachmentEntity> {
+        AttachmentEntity::parse(&self.entity_type)
+    }
+
+    /// 404 unless the record exists in the caller's tenant.
+    async fn check_exists(&self, conn: &mut PgConnection, tenant_id: Uuid) -> Result<AttachmentEntity, Response> {
+        let entity = self.entity().ok_or_else(unknown_entity_type)?;
+        let exists = sqlx::query_scalar::<_, bool>(&format!(
+            "SELECT EXISTS (SELECT 1 FROM {} WHERE id = $1 AND tenant_id = $2)",
+            attachments::entity_table(entity)
+        ))
+        .bind(self.entity_id)
+        .bind(tenant_id)
+        .fetch_one(conn)
+        .await;
+        match exists {
+            Ok(true) => Ok(entity),
+            Ok(false) => Err(error_response(StatusCode::NOT_FOUND, format!("{} not found", entity.as_str()))),
+            Err(e) => Err(Json(ApiResponse::<()>::error(format!("{}", e))).into_response()),
+        }
+    }
+}
+
+/// Read the form until its `file` field has been spooled.
+async fn read_file(mut multipart: Multipart, limit: usize) -> Result<(String, Spooled), Response> {
+    let malformed = |e: axum::extract::multipart::MultipartError| error_response(StatusCode::BAD_REQUEST, e.body_text());
+
+    while let Some(field) = multipart.next_field().await.map_err(malformed)? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let filename = attachments::clean_filename(field.file_name().unwrap_or_default());
+        return match attachments::spool(field, limit).await {
+            Ok(spooled) => Ok((filename, spooled)),
+            Err(e @ SpoolError::TooLarge(_)) => Err(error_response(StatusCode::PAYLOAD_TOO_LARGE, e.to_string())),
+            Err(e @ SpoolError::Read(_)) => Err(error_response(StatusCode::BAD_REQUEST, e.to_string())),
+            Err(SpoolError::Io(e)) => {
+                error!("Failed to spool attachment: {}", e);
+                Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to store the file"))
+            }
+        };
+    }
+    Err(error_response(StatusCode::BAD_REQUEST, "file is required"))
+}
+
+/// Multipart form of an attachment upload (documentation only; parsed by [`read_file`]).
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct AttachmentUploadForm {
+    /// Any file up to storage.max_file_size; its type is detected from the contents
+    #[schema(value_type = String, format = Binary)]
+    file: Vec<u8>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/attachments",
+    params(AttachmentTarget),
+    request_body(content = AttachmentUploadForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "File attached", body = inline(ApiResponse<Attachment>)),
+        (status = 400, description = "Unknown entity_type or missing file"),
+        (status = 404, description = "Record not found"),
+        (status = 413, description = "File larger than storage.max_file_size, or the tenant's storage quota is used up")
+    ),
+    tag = "attachments"
+)]
+pub async fn upload_attachment(
+    State(state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Query(target): Query<AttachmentTarget>,
+    multipart: Multipart,
+) -> Response {
+    // Set tenant context (RLS)
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let entity = match target.check_exists(&mut conn, current.tenant_id).await {
+        Ok(entity) => entity,
+        Err(response) => return response,
+    };
+    let (filename, file) = match read_file(multipart, state.config.storage.max_file_size).await {
+        Ok(upload) => upload,
+        Err(response) => return response,
+    };
+    let content_type = attachments::sniff(&file.head, &filename);
+    info!(entity = entity.as_str(), entity_id = %target.entity_id, size = file.size, content_type, "Upload attachment");
+
+    // The same contents are stored once per tenant; touching the blob keeps the
+    // cleanup sweep away from it until the attachment row exists
+    let existing = sqlx::query("UPDATE attachment_blobs SET created_at = NOW() WHERE tenant_id = $1 AND sha256 = $2")
+        .bind(current.tenant_id)
+        .bind(&file.sha256)
+        .execute(&mut *conn)
+        .await;
+    match existing {
+        Ok(done) if done.rows_affected() > 0 => {}
+        Ok(_) => {
+            if let Err(response) = store_blob(&state, &mut conn, current.tenant_id, &file, content_type).await {
+                return response;
+            }
+        }
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+
+    let row = sqlx::query(&format!(
+        r#"WITH a AS (
+               INSERT INTO attachments (tenant_id, entity_type, entity_id, filename, content_type, sha256, uploaded_by)
+               VALUES ($1, $2, $3, $4, $5, $6, $7)
+               RETURNING *
+           )
+           SELECT {} FROM a JOIN attachment_blobs b ON b.tenant_id = a.tenant_id AND b.sha256 = a.sha256"#,
+        ATTACHMENT_COLUMNS
+    ))
+    .bind(current.tenant_id)
+    .bind(entity.as_str())
+    .bind(target.entity_id)
+    .bind(&filename)
+    .bind(content_type)
+    .bind(&file.sha256)
+    .bind(current.user_id)
+    .fetch_one(&mut *conn)
+    .await;
+
+    match row {
+        Ok(row) => {
+            let attachment = attachment_from_row(&row, &UrlSigner::from_state(&state));
+            (StatusCode::CREATED, Json(ApiResponse::success(attachment))).into_response()
+        }
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+/// Put new contents into storage and record the blob, within the tenant's quota.
+///
+/// New contents are stored one upload at a time per tenant, under an advisory
+/// lock held until the blob row commits, so concurrent uploads cannot all pass
+/// the quota check.
+async fn store_blob(
+    state: &AppState,
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    file: &Spooled,
+    content_type: &str,
+) -> Result<(), Response> {
+    let db_error = |e: sqlx::Error| Json(ApiResponse::<()>::error(format!("{}", e))).into_response();
+
+    let mut tx = persistence::begin(conn, tenant_id).await.map_err(db_error)?;
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended('attachment_blobs:' || $1::text, 0))")
+        .bind(tenant_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+    // An upload of the same contents may have stored them while we waited
+    let existing = sqlx::query("UPDATE attachment_blobs SET created_at = NOW() WHERE tenant_id = $1 AND sha256 = $2")
+        .bind(tenant_id)
+        .bind(&file.sha256)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    if existing.rows_affected() > 0 {
+        return tx.commit().await.map_err(db_error);
+    }
+
+    let plan: String = sqlx::query_scalar("SELECT plan FROM tenants WHERE id = $1")
+        .bind(tenant_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    let used: i64 = sqlx::query_scalar("SELECT COALESCE(SUM(size), 0)::BIGINT FROM attachment_blobs WHERE tenant_id = $1")
+        .bind(tenant_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    let quota = state.config.storage.quota(&plan);
+    if used as u64 + file.size > quota {
+        return Err(error_response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Attachment storage quota of {} bytes for the {} plan is used up", quota, plan),
+        ));
+    }
+
+    let key = format!("{}/{}", tenant_id, Uuid::new_v4());
+    if let Err(e) = state.storage.put(&key, &file.path, file.size, content_type, &file.sha256).await {
+        error!("Failed to store attachment: {}", e);
+        return Err(error_response(StatusCode::BAD_GATEWAY, "Failed to store the file"));
+    }
+
+    sqlx::query("INSERT INTO attachment_blobs (tenant_id, sha256, size, storage_key) VALUES ($1, $2, $3, $4)")
+        .bind(tenant_id)
+        .bind(&file.sha256)
+        .bind(file.size as i64)
+        .bind(&key)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+    tx.commit().await.map_err(db_error)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/attachments",
+    params(AttachmentTarget),
+    responses(
+        (status = 200, description = "Files attached to the record, oldest first", body = inline(ApiResponse<Vec<Attachment>>)),
+        (status = 400, description = "Unknown entity_type")
+    ),
+    tag = "attachments"
+)]
+pub async fn list_attachments(
+    State(state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Query(target): Query<AttachmentTarget>,
+) -> Response {
+    let Some(entity) = target.entity() else {
+        return unknown_entity_type();
+    };
+    info!("List attachments of {} {}", entity.as_str(), target.entity_id);
+
+    // Set tenant context (RLS)
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let rows = sqlx::query(&format!(
+        r#"SELECT {} FROM {}
+           WHERE a.tenant_id = $1 AND a.entity_type = $2 AND a.entity_id = $3
+           ORDER BY a.created_at, a.id"#,
+        ATTACHMENT_COLUMNS, ATTACHMENT_FROM
+    ))
+    .bind(current.tenant_id)
+    .bind(entity.as_str())
+    .bind(target.entity_id)
+    .fetch_all(&mut *conn)
+    .await;
+
+    match rows {
+        Ok(rows) => {
+            let signer = UrlSigner::from_state(&state);
+            let attachments: Vec<Attachment> = rows.iter().map(|row| attachment_from_row(row, &signer)).collect();
+            Json(ApiResponse::success(attachments)).into_response()
+        }
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/attachments/{id}",
+    params(("id" = uuid::Uuid, Path, description = "Attachment ID")),
+    responses(
+        (status = 200, description = "Attachment with a fresh download URL", body = inline(ApiResponse<Attachment>)),
+        (status = 404, description = "Attachment not found")
+    ),
+    tag = "attachments"
+)]
+pub async fn get_attachment(
+    State(state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Path(id): Path<Uuid>,
+) -> Response {
+    info!("Get attachment {}", id);
+
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM {} WHERE a.id = $1 AND a.tenant_id = $2",
+        ATTACHMENT_COLUMNS, ATTACHMENT_FROM
+    ))
+    .bind(id)
+    .bind(current.tenant_id)
+    .fetch_optional(&mut *conn)
+    .await;
+
+    match row {
+        Ok(Some(row)) => Json(ApiResponse::success(attachment_from_row(&row, &UrlSigner::from_state(&state)))).into_response(),
+        Ok(None) => error_response(StatusCode::NOT_FOUND, "Attachment not found"),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/attachments/{id}",
+    params(("id" = uuid::Uuid, Path, description = "Attachment ID")),
+    responses(
+        (status = 200, description = "Attachment removed; the file is deleted once nothing else refers to it", body = inline(ApiResponse<serde_json::Value>)),
+        (status = 404, description = "Attachment not found")
+    ),
+    tag = "attachments"
+)]
+pub async fn delete_attachment(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Path(id): Path<Uuid>,
+) -> Response {
+    info!("Delete attachment {}", id);
+
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let deleted = sqlx::query("DELETE FROM attachments WHERE id = $1 AND tenant_id = $2")
+        .bind(id)
+        .bind(current.tenant_id)
+        .execute(&mut *conn)
+        .await;
+
+    match deleted {
+        Ok(done) if done.rows_affected() > 0 => Json(ApiResponse::success(json!({"deleted_id": id}))).into_response(),
+        Ok(_) => error_response(StatusCode::NOT_FOUND, "Attachment not found"),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DownloadSignature {
+    /// Unix time the link stops working
+    expires: i64,
+    signature: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/attachments/{id}/download",
+    params(("id" = uuid::Uuid, Path, description = "Attachment ID"), DownloadSignature),
+    responses(
+        (status = 200, description = "File contents with the detected content type", content_type = "application/octet-stream", body = String),
+        (status = 403, description = "Signature invalid or expired"),
+        (status = 404, description = "Attachment not found")
+    ),
+    tag = "attachments"
+)]
+pub async fn download_attachment(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(signed): Query<DownloadSignature>,
+) -> Response {
+    // Signed links work without a bearer token, so the signature is the only check
+    if !UrlSigner::from_state(&state).verify(id, signed.expires, &signed.signature, Utc::now()) {
+        return error_response(StatusCode::FORBIDDEN, "Download link is invalid or has expired");
+    }
+    info!("Download attachment {}", id);
+
+    let row = sqlx::query(&format!(
+        "SELECT a.filename, a.content_type, b.size, b.storage_key FROM {} WHERE a.id = $1",
+        ATTACHMENT_FROM
+    ))
+    .bind(id)
+    .fetch_optional(&state.db_pool)
+    .await;
+    let row = match row {
+        Ok(Some(row)) => row,
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "Attachment not found"),
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+
+    let key: String = row.get("storage_key");
+    let body = match state.storage.get(&key).await {
+        Ok(stream) => Body::from_stream(stream),
+        Err(StorageError::NotFound) => return error_response(StatusCode::NOT_FOUND, "Attachment file is missing"),
+        Err(e) => {
+            error!(key = %key, "Failed to read attachment: {}", e);
+            return error_response(StatusCode::BAD_GATEWAY, "Failed to read the file");
+        }
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, row.get::<String, _>("content_type")),
+            (header::CONTENT_LENGTH, row.get::<i64, _>("size").to_string()),
+            (header::CONTENT_DISPOSITION, attachments::content_disposition(row.get("filename"))),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+            (header::CACHE_CONTROL, "private, no-store".to_string()),
+        ],
+        body,
+    )
+        .into_response()
+}
@@ -1,13 +1,13 @@
 use axum::{extract::State, Json};
 use shared_types::{
     ApiResponse, LoginRequest, LoginResponse, RegisterTenantRequest,
-    ForgotPasswordRequest, ResetPasswordRequest, RefreshTokenRequest
+    ForgotPasswordRequest, ResetPasswordRequest, RefreshTokenRequest, AcceptInvitationRequest
 };
 use validator::Validate;
 use std::sync::Arc;
 use tracing::info;
 
-use crate::state::AppState;
+use crate::{cache::CacheScope, extractors::RequestLocale, state::AppState};
 
 /// User login
 #[utoipa::path(
@@ -15,7 +15,7 @@ use crate::state::AppState;
     path = "/api/v1/auth/login",
     request_body = LoginRequest,
     responses(
-        (status = 200, description = "Login successful", body = ApiResponse<LoginResponse>),
+        (status = 200, description = "Login successful", body = inline(ApiResponse<LoginResponse>)),
         (status = 401, description = "Invalid credentials", body = ApiResponse<()>),
         (status = 422, description = "Validation error", body = ApiResponse<()>)
     ),
@@ -23,22 +23,33 @@ use crate::state::AppState;
 )]
 pub async fn login(
     State(state): State<Arc<AppState>>,
+    RequestLocale(locale): RequestLocale,
     Json(request): Json<LoginRequest>,
 ) -> Json<ApiResponse<LoginResponse>> {
     info!("Login attempt for email: {}", request.email);
 
     // Validate input (basic)
     if let Err(e) = request.validate() {
-        return Json(ApiResponse::<LoginResponse>::error_typed(format!("Invalid input: {}", e)));
+        return Json(ApiResponse::<LoginResponse>::invalid(&e, locale));
     }
 
     // Do login via service
-    match crate::services::AuthAppService::new(&state.db_pool, &state.jwt_service, &state.password_service, &state.redis)
+    match crate::services::AuthAppService::new(&state.db_pool, &state.jwt_service, &state.password_service, state.kv.as_ref(), &state.metrics)
         .login(&request)
         .await
     {
-        Ok(resp) => Json(ApiResponse::success(resp)),
-        Err(e) => Json(ApiResponse::<LoginResponse>::error_typed(format!("{}", e))),
+        Ok(resp) => {
+            state.metrics.record_login(resp.tenant.base.id);
+            Json(ApiResponse::success(resp))
+        }
+        Err(e) => {
+            // Database and Redis errors are outages, not failed logins
+            let code = e.to_string();
+            if code == "INVALID_CREDENTIALS" {
+                state.metrics.record_failed_login();
+            }
+            Json(ApiResponse::<LoginResponse>::business_error(&code, locale))
+        }
     }
 }
 
@@ -56,15 +67,16 @@ pub async fn login(
 )]
 pub async fn register_tenant(
     State(state): State<Arc<AppState>>,
+    RequestLocale(locale): RequestLocale,
     Json(request): Json<RegisterTenantRequest>,
 ) -> Json<ApiResponse<()>> {
     info!("Tenant registration attempt for: {}", request.company_name);
 
     if let Err(e) = request.validate() {
-        return Json(ApiResponse::<()>::error(format!("Invalid input: {}", e)));
+        return Json(ApiResponse::<()>::invalid(&e, locale));
     }
 
-    let svc = crate::services::AuthAppService::new(&state.db_pool, &state.jwt_service, &state.password_service, &state.redis);
+    let svc = crate::services::AuthAppService::new(&state.db_pool, &state.jwt_service, &state.password_service, state.kv.as_ref(), &state.metrics);
     let result = svc
         .register_tenant(
             &request.company_name,
@@ -78,11 +90,50 @@ pub async fn register_tenant(
 
     match result {
         Ok(()) => Json(ApiResponse::success_with_message((), "Registration successful".to_string())),
-        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))),
+        Err(e) => Json(ApiResponse::<()>::business_error(&e.to_string(), locale)),
+    }
+}
+
+/// Accept an invitation to join a tenant
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/invitations/accept",
+    request_body = AcceptInvitationRequest,
+    responses(
+        (status = 200, description = "Joined the tenant; log in to continue", body = ApiResponse<()>),
+        (status = 400, description = "Invitation unknown, expired or already accepted (INVALID_INVITATION), or wrong password for an existing account", body = ApiResponse<()>)
+    ),
+    tag = "auth"
+)]
+pub async fn accept_invitation(
+    State(state): State<Arc<AppState>>,
+    RequestLocale(locale): RequestLocale,
+    Json(request): Json<AcceptInvitationRequest>,
+) -> Json<ApiResponse<()>> {
+    info!("Invitation acceptance attempt");
+
+    if let Err(e) = request.validate() {
+        return Json(ApiResponse::<()>::invalid(&e, locale));
+    }
+
+    let svc = crate::services::AuthAppService::new(&state.db_pool, &state.jwt_service, &state.password_service, state.kv.as_ref(), &state.metrics);
+    match svc.accept_invitation(&request.token, &request.password).await {
+        Ok((tenant_id, created)) => {
+            state.cache.invalidate(tenant_id, CacheScope::Permissions).await;
+            crate::notifications::publish(state.kv.as_ref(), &created).await;
+            Json(ApiResponse::success_with_message((), "Invitation accepted".to_string()))
+        }
+        Err(e) => Json(ApiResponse::<()>::business_error(&e.to_string(), locale)),
     }
 }
 
 /// User logout
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/logout",
+    responses((status = 200, description = "Logged out", body = ApiResponse<()>)),
+    tag = "auth"
+)]
 pub async fn logout(
     State(_state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<()>> {
@@ -103,7 +154,7 @@ pub async fn logout(
     path = "/api/v1/auth/refresh",
     request_body = RefreshTokenRequest,
     responses(
-        (status = 200, description = "Token refreshed", body = ApiResponse<LoginResponse>),
+        (status = 200, description = "Token refreshed", body = inline(ApiResponse<LoginResponse>)),
         (status = 401, description = "Invalid or expired token", body = ApiResponse<()>),
         (status = 422, description = "Validation error", body = ApiResponse<()>)
     ),
@@ -111,22 +162,30 @@ pub async fn logout(
 )]
 pub async fn refresh(
     State(state): State<Arc<AppState>>,
+    RequestLocale(locale): RequestLocale,
     Json(request): Json<RefreshTokenRequest>,
 ) -> Json<ApiResponse<LoginResponse>> {
     info!("Token refresh attempt");
 
     if let Err(e) = request.validate() {
-        return Json(ApiResponse::<LoginResponse>::error_typed(format!("Invalid input: {}", e)));
+        return Json(ApiResponse::<LoginResponse>::invalid(&e, locale));
     }
 
-    let svc = crate::services::AuthAppService::new(&state.db_pool, &state.jwt_service, &state.password_service, &state.redis);
+    let svc = crate::services::AuthAppService::new(&state.db_pool, &state.jwt_service, &state.password_service, state.kv.as_ref(), &state.metrics);
     match svc.refresh(&request.refresh_token).await {
         Ok(resp) => Json(ApiResponse::success(resp)),
-        Err(e) => Json(ApiResponse::<LoginResponse>::error_typed(format!("{}", e))),
+        Err(e) => Json(ApiResponse::<LoginResponse>::business_error(&e.to_string(), locale)),
     }
 }
 
 /// Forgot password
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/forgot-password",
+    request_body = ForgotPasswordRequest,
+    responses((status = 200, description = "Reset link sent if the email exists", body = ApiResponse<()>)),
+    tag = "auth"
+)]
 pub async fn forgot_password(
     State(_state): State<Arc<AppState>>,
     Json(request): Json<ForgotPasswordRequest>,
@@ -148,9 +207,16 @@ pub async fn forgot_password(
 }
 
 /// Reset password
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/reset-password",
+    request_body = ResetPasswordRequest,
+    responses((status = 200, description = "Not implemented yet", body = ApiResponse<()>)),
+    tag = "auth"
+)]
 pub async fn reset_password(
     State(_state): State<Arc<AppState>>,
-    Json(request): Json<ResetPasswordRequest>,
+    Json(_request): Json<ResetPasswordRequest>,
 ) -> Json<ApiResponse<()>> {
     info!("Password reset attempt");
     
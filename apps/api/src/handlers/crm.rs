@@ -1,26 +1,31 @@
-use axum::{extract::{State, Extension, Query, Path}, Json};
+use axum::{extract::{State, Extension, Path}, response::{IntoResponse, Response}, Json};
 use shared_types::ApiResponse;
 use std::sync::Arc;
 use tracing::info;
 
-use crate::{state::AppState, middleware::{auth_middleware::CurrentUser, db_conn::DbConn}};
-use sqlx::Row;
+use crate::{state::AppState, export::{self, ExportRequest, ExportSource}, fieldset::FieldsetQuery, list_query::{Field, FieldType, ListPage, ListQuery, ListSpec}, extractors::{preconditions::{self, Preconditions}, RequestLocale}, middleware::{auth_middleware::CurrentUser, db_conn::DbConn}};
+use persistence::{CompanyChanges, CompanyRepo, ContactChanges, ContactRepo, NewCompany, NewContact};
 use utoipa::ToSchema;
 
 use validator::Validate;
 // Company handlers
-#[derive(serde::Deserialize, Validate, Debug)]
-pub struct ListCompaniesQuery {
-    #[validate(range(min = 1, max = 10000))]
-    pub page: Option<u32>,
-    #[validate(range(min = 1, max = 100))]
-    pub per_page: Option<u32>,
-    #[validate(length(min = 1, max = 100))]
-    pub search: Option<String>,
-    pub sort_by: Option<String>,
-    pub sort_order: Option<String>, // "asc" | "desc"
-}
-
+static COMPANY_LIST: ListSpec = ListSpec {
+    from: "companies",
+    tenant_column: "tenant_id",
+    base_filter: Some("is_active = true"),
+    id_column: "id",
+    fields: &[
+        Field::new("name", "name", FieldType::Text).sortable(),
+        Field::new("email", "email", FieldType::Text).nullable(),
+        Field::new("website", "website", FieldType::Text).nullable(),
+        Field::new("phone", "phone", FieldType::Text).nullable(),
+        Field::new("created_at", "created_at", FieldType::Timestamp).sortable(),
+        Field::new("updated_at", "updated_at", FieldType::Timestamp).sortable(),
+    ],
+    search: &["name", "email", "website"],
+    default_sort: "-created_at",
+    aliases: &[],
+};
 
 #[utoipa::path(
     get,
@@ -28,42 +33,39 @@ pub struct ListCompaniesQuery {
     params(
         ("page" = Option<u32>, Query, description = "Page number"),
         ("per_page" = Option<u32>, Query, description = "Items per page"),
-        ("search" = Option<String>, Query, description = "Search term"),
-        ("sort_by" = Option<String>, Query, description = "name|created_at|updated_at"),
-        ("sort_order" = Option<String>, Query, description = "asc|desc"),
+        ("cursor" = Option<String>, Query, description = "Opaque cursor from pagination.next_cursor"),
+        ("limit" = Option<u32>, Query, description = "Items per page (keyset pagination, max 200)"),
+        ("count" = Option<CountMode>, Query, description = "Keyset total: none|estimated|exact"),
+        ("search" = Option<String>, Query, description = "Search name, email and website"),
+        ("sort" = Option<String>, Query, description = "Comma-separated, '-' for descending: name|created_at|updated_at"),
+        ("email" = Option<String>, Query, description = "Filter by email; any field also takes field[op]=value with eq|ne|in|gt|gte|lt|lte|ilike|null"),
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return; id is always included"),
+        ("format" = Option<String>, Query, description = "Stream every matching row as csv|xlsx|jsonl instead of a JSON page (also via Accept)"),
+    ),
+    responses(
+        (status = 200, description = "List companies", body = inline(ApiResponse<ListPage<shared_types::Company>>)),
+        (status = 400, description = "Unknown field, operator or value")
     ),
-    responses((status = 200, description = "List companies", body = ApiResponse<shared_types::PaginatedResponse<shared_types::Company>>)),
     tag = "crm"
 )]
 pub async fn list_companies(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
-    Query(q): Query<ListCompaniesQuery>,
-) -> Json<ApiResponse<shared_types::PaginatedResponse<shared_types::Company>>> {
+    query: ListQuery,
+    fieldset: FieldsetQuery,
+    export: ExportRequest,
+) -> Response {
     info!("List companies");
 
-    if let Err(e) = q.validate() {
-        return Json(ApiResponse::error_typed(format!("Invalid query: {}", e)));
-    }
-    if let Some(ref by) = q.sort_by {
-        match by.as_str() {
-            "name" | "created_at" | "updated_at" => {}
-            _ => return Json(ApiResponse::error_typed("Invalid sort_by".to_string())),
-        }
-    }
-    if let Some(ref by) = q.sort_by {
-        match by.as_str() {
-            "name" | "created_at" | "updated_at" => {}
-            _ => return Json(ApiResponse::error_typed("Invalid sort_by".to_string())),
-        }
-    }
-    if let Some(ref ord) = q.sort_order {
-        match ord.to_ascii_lowercase().as_str() {
-            "asc" | "desc" => {}
-            _ => return Json(ApiResponse::error_typed("Invalid sort_order".to_string())),
-        }
-    }
+    let plan = match COMPANY_LIST.plan(&query) {
+        Ok(plan) => plan,
+        Err(e) => return e.into_response(),
+    };
+    let fieldset = match fieldset.resolve::<shared_types::Company>(&[]) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return e.into_response(),
+    };
 
     // Set tenant context (RLS)
     let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
@@ -71,124 +73,14 @@ pub async fn list_companies(
         .execute(&mut *conn)
         .await;
 
-    let page = q.page.unwrap_or(1).max(1);
-    let per_page = q.per_page.unwrap_or(20).clamp(1, 100);
-    let offset = ((page - 1) as i64) * (per_page as i64);
-
-    // Build dynamic filters
-    let mut where_parts: Vec<&str> = vec!["is_active = true"];
-
-    // Determine sort
-    let sort_by = q.sort_by.as_deref().unwrap_or("created_at");
-    let order_by = match sort_by { "name" => "name", "updated_at" => "updated_at", _ => "created_at" };
-    let direction = match q.sort_order.as_deref().map(|s| s.to_ascii_lowercase()) {
-        Some(ref s) if s == "asc" => "ASC",
-        _ => "DESC",
-    };
-
-    // Count total + Fetch data with proper bind numbering depending on search
-    let (total_count, rows): (i64, Vec<shared_types::Company>) = if let Some(search) = &q.search {
-        let pattern = format!("%{}%", search);
-        where_parts.push("(name ILIKE $1 OR email ILIKE $1 OR website ILIKE $1)");
-        let where_clause = format!("WHERE {}", where_parts.join(" AND "));
-
-        let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM companies {}", where_clause))
-            .bind(&pattern)
-            .fetch_one(&mut *conn)
-            .await
-            .unwrap_or(0);
-
-        let sql = format!(
-            r#"SELECT id, tenant_id, name, website, email, phone, address, tags, is_active, created_at, updated_at
-               FROM companies
-               {}
-               ORDER BY {} {}
-               LIMIT $2 OFFSET $3"#,
-            where_clause, order_by, direction
-        );
-
-        let data = sqlx::query(&sql)
-            .bind(&pattern)
-            .bind(per_page as i64)
-            .bind(offset)
-            .fetch_all(&mut *conn)
-            .await
-            .map(|recs| {
-                recs.into_iter().map(|row| {
-                    shared_types::Company {
-                        id: row.get("id"),
-                        tenant_id: row.get("tenant_id"),
-                        name: row.get("name"),
-                        website: row.try_get("website").unwrap_or(None),
-                        email: row.try_get("email").unwrap_or(None),
-                        phone: row.try_get("phone").unwrap_or(None),
-                        address: row.try_get("address").unwrap_or(serde_json::json!({})),
-                        tags: row.try_get("tags").unwrap_or_default(),
-                        is_active: row.get("is_active"),
-                        created_at: row.get("created_at"),
-                        updated_at: row.get("updated_at"),
-                    }
-                }).collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
-
-        (total, data)
-    } else {
-        let where_clause = format!("WHERE {}", where_parts.join(" AND "));
-        let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM companies {}", where_clause))
-            .fetch_one(&mut *conn)
-            .await
-            .unwrap_or(0);
-
-        let sql = format!(
-            r#"SELECT id, tenant_id, name, website, email, phone, address, tags, is_active, created_at, updated_at
-               FROM companies
-               {}
-               ORDER BY {} {}
-               LIMIT $1 OFFSET $2"#,
-            where_clause, order_by, direction
-        );
-
-        let data = sqlx::query(&sql)
-            .bind(per_page as i64)
-            .bind(offset)
-            .fetch_all(&mut *conn)
-            .await
-            .map(|recs| {
-                recs.into_iter().map(|row| {
-                    shared_types::Company {
-                        id: row.get("id"),
-                        tenant_id: row.get("tenant_id"),
-                        name: row.get("name"),
-                        website: row.try_get("website").unwrap_or(None),
-                        email: row.try_get("email").unwrap_or(None),
-                        phone: row.try_get("phone").unwrap_or(None),
-                        address: row.try_get("address").unwrap_or(serde_json::json!({})),
-                        tags: row.try_get("tags").unwrap_or_default(),
-                        is_active: row.get("is_active"),
-                        created_at: row.get("created_at"),
-                        updated_at: row.get("updated_at"),
-                    }
-                }).collect::<Vec<_>>()
-            })
-            .unwrap_or_default();
-
-        (total, data)
-    };
-
-
-    let total_pages = ((total_count as f64) / (per_page as f64)).ceil() as u32;
-    let meta = shared_types::PaginationMeta {
-        current_page: page,
-        per_page,
-        total_pages,
-        total_count: total_count as u64,
-        has_next: page < total_pages,
-        has_prev: page > 1,
-    };
-
-    let resp = shared_types::PaginatedResponse { data: rows, pagination: meta };
-    Json(ApiResponse::success(resp))
+    if let Some(format) = export.format() {
+        let source = ExportSource { name: "companies", plan, columns: CompanyRepo::COLUMNS, map: CompanyRepo::from_row, relations: &[] };
+        return export::respond(&state, conn, current.tenant_id, format, fieldset, source).await;
+    }
+    match plan.fetch(&mut conn, current.tenant_id, CompanyRepo::COLUMNS, CompanyRepo::from_row).await {
+        Ok(page) => fieldset.page(page).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
 }
 
 #[derive(serde::Deserialize, Validate, Debug, ToSchema)]
@@ -209,112 +101,88 @@ pub struct CreateCompanyRequest {
     post,
     path = "/api/v1/crm/companies",
     request_body = CreateCompanyRequest,
-    responses((status = 201, description = "Company created", body = ApiResponse<shared_types::Company>)),
+    responses((status = 201, description = "Company created", body = inline(ApiResponse<shared_types::Company>))),
     tag = "crm"
 )]
 pub async fn create_company(
     State(_state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
+    RequestLocale(locale): RequestLocale,
     Json(req): Json<CreateCompanyRequest>,
-) -> Json<ApiResponse<shared_types::Company>> {
+) -> Response {
     info!("Create company");
 
     if let Err(e) = req.validate() {
-        return Json(ApiResponse::error_typed(format!("Invalid input: {}", e)));
+        return Json(ApiResponse::<()>::invalid(&e, locale)).into_response();
     }
 
-    // Set tenant context (RLS)
-    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
-        .bind(current.tenant_id.to_string())
-        .execute(&mut *conn)
-        .await;
+    let mut tx = match persistence::begin(&mut conn, current.tenant_id).await {
+        Ok(tx) => tx,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
 
-    let address = req.address.unwrap_or(serde_json::json!({}));
-    let tags = req.tags.unwrap_or_default();
-
-    let row = sqlx::query(
-        r#"INSERT INTO companies (tenant_id, name, website, email, phone, address, tags)
-           VALUES ($1, $2, $3, $4, $5, $6, $7)
-           RETURNING id, tenant_id, name, website, email, phone, address, tags, is_active, created_at, updated_at"#
-    )
-    .bind(current.tenant_id)
-    .bind(&req.name)
-    .bind(&req.website)
-    .bind(&req.email)
-    .bind(&req.phone)
-    .bind(address)
-    .bind(tags)
-    .fetch_one(&mut *conn)
-    .await;
-
-    match row {
-        Ok(row) => {
-            let json = shared_types::Company {
-                id: row.get("id"),
-                tenant_id: row.get("tenant_id"),
-                name: row.get("name"),
-                website: row.try_get("website").unwrap_or(None),
-                email: row.try_get("email").unwrap_or(None),
-                phone: row.try_get("phone").unwrap_or(None),
-                address: row.try_get("address").unwrap_or(serde_json::json!({})),
-                tags: row.try_get("tags").unwrap_or_default(),
-                is_active: row.get("is_active"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            };
-            Json(ApiResponse::success(json))
-        }
-        Err(e) => Json(ApiResponse::error_typed(format!("{}", e)))
+    let company = NewCompany {
+        name: req.name,
+        website: req.website,
+        email: req.email,
+        phone: req.phone,
+        address: req.address.unwrap_or(serde_json::json!({})),
+        tags: req.tags.unwrap_or_default(),
+    };
+    let company = match CompanyRepo::insert(&mut tx, current.tenant_id, &company).await {
+        Ok(company) => company,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+    if let Err(e) = tx.commit().await {
+        return Json(ApiResponse::<()>::error(format!("{}", e))).into_response();
     }
+    preconditions::tagged(company.updated_at, ApiResponse::success(company))
 }
 
 #[utoipa::path(
     get,
     path = "/api/v1/crm/companies/{id}",
-    params(("id" = uuid::Uuid, Path, description = "Company ID")),
-    responses((status = 200, description = "Company detail", body = ApiResponse<shared_types::Company>)),
+    params(
+        ("id" = uuid::Uuid, Path, description = "Company ID"),
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return; id is always included"),
+    ),
+    responses(
+        (status = 200, description = "Company detail", body = inline(ApiResponse<shared_types::Company>)),
+        (status = 304, description = "Not modified since the ETag in If-None-Match"),
+        (status = 400, description = "Unknown field")
+    ),
     tag = "crm"
 )]
 pub async fn get_company(
     State(_state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
+    preconditions: Preconditions,
+    fieldset: FieldsetQuery,
     Path(id): Path<uuid::Uuid>,
-) -> Json<ApiResponse<shared_types::Company>> {
+) -> Response {
     info!("Get company {}", id);
-    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
-        .bind(current.tenant_id.to_string())
-        .execute(&mut *conn)
-        .await;
 
-    let row = sqlx::query(
-        r#"SELECT id, tenant_id, name, website, email, phone, address, tags, is_active, created_at, updated_at
-           FROM companies WHERE id = $1 LIMIT 1"#
-    )
-    .bind(id)
-    .fetch_optional(&mut *conn)
-    .await;
-
-    match row {
-        Ok(Some(row)) => {
-            let json = shared_types::Company {
-                id: row.get("id"),
-                tenant_id: row.get("tenant_id"),
-                name: row.get("name"),
-                website: row.try_get("website").unwrap_or(None),
-                email: row.try_get("email").unwrap_or(None),
-                phone: row.try_get("phone").unwrap_or(None),
-                address: row.try_get("address").unwrap_or(serde_json::json!({})),
-                tags: row.try_get("tags").unwrap_or_default(),
-                is_active: row.get("is_active"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            };
-            Json(ApiResponse::success(json))
+    let fieldset = match fieldset.resolve::<shared_types::Company>(&[]) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return e.into_response(),
+    };
+
+    let mut tx = match persistence::begin(&mut conn, current.tenant_id).await {
+        Ok(tx) => tx,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+
+    match CompanyRepo::find(&mut tx, current.tenant_id, id).await {
+        Ok(Some(company)) => {
+            if preconditions.not_modified(company.updated_at) {
+                return preconditions::not_modified(company.updated_at);
+            }
+            preconditions::tagged(company.updated_at, ApiResponse::success(fieldset.apply(&company)))
         }
-        Ok(None) => Json(ApiResponse::error_typed("Company not found".to_string())),
-        Err(e) => Json(ApiResponse::error_typed(format!("{}", e))),
+        Ok(None) => Json(ApiResponse::<()>::error("Company not found".to_string())).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
     }
 }
 
@@ -338,68 +206,50 @@ pub struct UpdateCompanyRequest {
     path = "/api/v1/crm/companies/{id}",
     params(("id" = uuid::Uuid, Path, description = "Company ID")),
     request_body = UpdateCompanyRequest,
-    responses((status = 200, description = "Company updated", body = ApiResponse<shared_types::Company>)),
+    responses(
+        (status = 200, description = "Company updated", body = inline(ApiResponse<shared_types::Company>)),
+        (status = 412, description = "If-Match does not match the current ETag")
+    ),
     tag = "crm"
 )]
 pub async fn update_company(
     State(_state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
+    RequestLocale(locale): RequestLocale,
+    preconditions: Preconditions,
     Path(id): Path<uuid::Uuid>,
     Json(req): Json<UpdateCompanyRequest>,
-) -> Json<ApiResponse<shared_types::Company>> {
+) -> Response {
     info!("Update company {}", id);
     if let Err(e) = req.validate() {
-        return Json(ApiResponse::error_typed(format!("Invalid input: {}", e)));
+        return Json(ApiResponse::<()>::invalid(&e, locale)).into_response();
     }
 
-    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
-        .bind(current.tenant_id.to_string())
-        .execute(&mut *conn)
-        .await;
+    let mut tx = match persistence::begin(&mut conn, current.tenant_id).await {
+        Ok(tx) => tx,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
 
-    let row = sqlx::query(
-        r#"UPDATE companies SET
-               name = COALESCE($2, name),
-               website = COALESCE($3, website),
-               email = COALESCE($4, email),
-               phone = COALESCE($5, phone),
-               address = COALESCE($6, address),
-               tags = COALESCE($7, tags),
-               is_active = COALESCE($8, is_active),
-               updated_at = NOW()
-           WHERE id = $1
-           RETURNING id, tenant_id, name, website, email, phone, address, tags, is_active, created_at, updated_at"#
-    )
-    .bind(id)
-    .bind(&req.name)
-    .bind(&req.website)
-    .bind(&req.email)
-    .bind(&req.phone)
-    .bind(&req.address)
-    .bind(&req.tags)
-    .bind(&req.is_active)
-    .fetch_one(&mut *conn)
-    .await;
-
-    match row {
-        Ok(row) => {
-            let json = shared_types::Company {
-                id: row.get("id"),
-                tenant_id: row.get("tenant_id"),
-                name: row.get("name"),
-                website: row.try_get("website").unwrap_or(None),
-                email: row.try_get("email").unwrap_or(None),
-                phone: row.try_get("phone").unwrap_or(None),
-                address: row.try_get("address").unwrap_or(serde_json::json!({})),
-                tags: row.try_get("tags").unwrap_or_default(),
-                is_active: row.get("is_active"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            };
-            Json(ApiResponse::success(json))
+    let changes = CompanyChanges {
+        name: req.name,
+        website: req.website,
+        email: req.email,
+        phone: req.phone,
+        address: req.address,
+        tags: req.tags,
+        is_active: req.is_active,
+    };
+    let expected = preconditions.expected_versions();
+    match CompanyRepo::update(&mut tx, current.tenant_id, id, &changes, expected.as_deref()).await {
+        Ok(Some(company)) => {
+            if let Err(e) = tx.commit().await {
+                return Json(ApiResponse::<()>::error(format!("{}", e))).into_response();
+            }
+            preconditions::tagged(company.updated_at, ApiResponse::success(company))
         }
-        Err(e) => Json(ApiResponse::error_typed(format!("{}", e)))
+        Ok(None) => preconditions::update_missed(&mut tx, "companies", id, "Company not found").await,
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
     }
 }
 
@@ -417,40 +267,41 @@ pub async fn delete_company(
     Path(id): Path<uuid::Uuid>,
 ) -> Json<ApiResponse<serde_json::Value>> {
     info!("Delete company {}", id);
-    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
-        .bind(current.tenant_id.to_string())
-        .execute(&mut *conn)
-        .await;
+    let mut tx = match persistence::begin(&mut conn, current.tenant_id).await {
+        Ok(tx) => tx,
+        Err(e) => return Json(ApiResponse::error_typed(format!("{}", e))),
+    };
 
-    let res = sqlx::query(
-        r#"UPDATE companies SET is_active = false, updated_at = NOW() WHERE id = $1 RETURNING id"#
-    )
-    .bind(id)
-    .fetch_optional(&mut *conn)
-    .await;
-
-    match res {
-        Ok(Some(row)) => {
-            let deleted_id: uuid::Uuid = row.get("id");
-            Json(ApiResponse::success(serde_json::json!({ "deleted_id": deleted_id })))
-        }
-        Ok(None) => Json(ApiResponse::error_typed("Company not found".to_string())),
+    match CompanyRepo::deactivate(&mut tx, current.tenant_id, id).await {
+        Ok(true) => match tx.commit().await {
+            Ok(()) => Json(ApiResponse::success(serde_json::json!({ "deleted_id": id }))),
+            Err(e) => Json(ApiResponse::error_typed(format!("{}", e))),
+        },
+        Ok(false) => Json(ApiResponse::error_typed("Company not found".to_string())),
         Err(e) => Json(ApiResponse::error_typed(format!("{}", e))),
     }
 }
 
 // Contact handlers
-#[derive(serde::Deserialize, Validate, Debug)]
-pub struct ListContactsQuery {
-    #[validate(range(min = 1, max = 10000))]
-    pub page: Option<u32>,
-    #[validate(range(min = 1, max = 100))]
-    pub per_page: Option<u32>,
-    #[validate(length(min = 1, max = 100))]
-    pub search: Option<String>,
-    pub sort_by: Option<String>, // first_name | last_name | created_at | updated_at
-    pub sort_order: Option<String>, // asc | desc
-}
+static CONTACT_LIST: ListSpec = ListSpec {
+    from: "contacts",
+    tenant_column: "tenant_id",
+    base_filter: Some("is_active = true"),
+    id_column: "id",
+    fields: &[
+        Field::new("first_name", "first_name", FieldType::Text).sortable(),
+        Field::new("last_name", "last_name", FieldType::Text).sortable(),
+        Field::new("email", "email", FieldType::Text).nullable(),
+        Field::new("phone", "phone", FieldType::Text).nullable(),
+        Field::new("position", "position", FieldType::Text).nullable(),
+        Field::new("company_id", "company_id", FieldType::Uuid).nullable(),
+        Field::new("created_at", "created_at", FieldType::Timestamp).sortable(),
+        Field::new("updated_at", "updated_at", FieldType::Timestamp).sortable(),
+    ],
+    search: &["first_name", "last_name", "email"],
+    default_sort: "-created_at",
+    aliases: &[],
+};
 
 #[utoipa::path(
     get,
@@ -458,123 +309,53 @@ pub struct ListContactsQuery {
     params(
         ("page" = Option<u32>, Query, description = "Page number"),
         ("per_page" = Option<u32>, Query, description = "Items per page"),
-        ("search" = Option<String>, Query, description = "Search term"),
-        ("sort_by" = Option<String>, Query, description = "first_name|last_name|created_at|updated_at"),
-        ("sort_order" = Option<String>, Query, description = "asc|desc"),
+        ("cursor" = Option<String>, Query, description = "Opaque cursor from pagination.next_cursor"),
+        ("limit" = Option<u32>, Query, description = "Items per page (keyset pagination, max 200)"),
+        ("count" = Option<CountMode>, Query, description = "Keyset total: none|estimated|exact"),
+        ("search" = Option<String>, Query, description = "Search first name, last name and email"),
+        ("sort" = Option<String>, Query, description = "Comma-separated, '-' for descending: first_name|last_name|created_at|updated_at"),
+        ("company_id" = Option<String>, Query, description = "Filter by company; any field also takes field[op]=value with eq|ne|in|gt|gte|lt|lte|ilike|null"),
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return; id is always included"),
+        ("format" = Option<String>, Query, description = "Stream every matching row as csv|xlsx|jsonl instead of a JSON page (also via Accept)"),
+    ),
+    responses(
+        (status = 200, description = "List contacts", body = inline(ApiResponse<ListPage<shared_types::Contact>>)),
+        (status = 400, description = "Unknown field, operator or value")
     ),
-    responses((status = 200, description = "List contacts", body = ApiResponse<shared_types::PaginatedResponse<shared_types::Contact>>)),
     tag = "crm"
 )]
 pub async fn list_contacts(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
-    Query(q): Query<ListContactsQuery>,
-) -> Json<ApiResponse<shared_types::PaginatedResponse<shared_types::Contact>>> {
+    query: ListQuery,
+    fieldset: FieldsetQuery,
+    export: ExportRequest,
+) -> Response {
     info!("List contacts");
 
-    if let Err(e) = q.validate() {
-        return Json(ApiResponse::error_typed(format!("Invalid query: {}", e)));
-    }
-    if let Some(ref by) = q.sort_by {
-        match by.as_str() {
-            "first_name" | "last_name" | "created_at" | "updated_at" => {}
-            _ => return Json(ApiResponse::error_typed("Invalid sort_by".to_string())),
-        }
-    }
-    if let Some(ref ord) = q.sort_order {
-        match ord.to_ascii_lowercase().as_str() {
-            "asc" | "desc" => {}
-            _ => return Json(ApiResponse::error_typed("Invalid sort_order".to_string())),
-        }
-    }
+    let plan = match CONTACT_LIST.plan(&query) {
+        Ok(plan) => plan,
+        Err(e) => return e.into_response(),
+    };
+    let fieldset = match fieldset.resolve::<shared_types::Contact>(&[]) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return e.into_response(),
+    };
 
     let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
         .bind(current.tenant_id.to_string())
         .execute(&mut *conn)
         .await;
 
-    let page = q.page.unwrap_or(1).max(1);
-    let per_page = q.per_page.unwrap_or(20).clamp(1, 100);
-    let offset = ((page - 1) as i64) * (per_page as i64);
-
-    let mut conds: Vec<String> = vec!["is_active = true".to_string()];
-    let mut args: Vec<(usize, String)> = vec![];
-
-    if let Some(search) = &q.search {
-        conds.push("(first_name ILIKE $1 OR last_name ILIKE $1 OR email ILIKE $1)".to_string());
-        args.push((1, format!("%{}%", search)));
+    if let Some(format) = export.format() {
+        let source = ExportSource { name: "contacts", plan, columns: ContactRepo::COLUMNS, map: ContactRepo::from_row, relations: &[] };
+        return export::respond(&state, conn, current.tenant_id, format, fieldset, source).await;
+    }
+    match plan.fetch(&mut conn, current.tenant_id, ContactRepo::COLUMNS, ContactRepo::from_row).await {
+        Ok(page) => fieldset.page(page).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
     }
-
-    let where_clause = if conds.is_empty() { String::new() } else { format!("WHERE {}", conds.join(" AND ")) };
-
-    let total_count: i64 = if let Some((_, s)) = args.get(0) {
-        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM contacts {}", where_clause))
-            .bind(s)
-            .fetch_one(&mut *conn)
-            .await
-            .unwrap_or(0)
-    } else {
-        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM contacts {}", where_clause))
-            .fetch_one(&mut *conn)
-            .await
-            .unwrap_or(0)
-    };
-
-    let sort_by = q.sort_by.as_deref().unwrap_or("created_at");
-    let order_by = match sort_by { "first_name" => "first_name", "last_name" => "last_name", "updated_at" => "updated_at", _ => "created_at" };
-    let direction = match q.sort_order.as_deref().map(|s| s.to_ascii_lowercase()) { Some(ref s) if s == "asc" => "ASC", _ => "DESC" };
-
-    let base_sql = format!(
-        r#"SELECT id, tenant_id, company_id, first_name, last_name, email, phone, position, notes, is_active, created_at, updated_at
-           FROM contacts
-           {}
-           ORDER BY {} {}
-           LIMIT $2 OFFSET $3"#,
-        where_clause, order_by, direction
-    );
-
-    let query = if let Some((_, s)) = args.get(0) {
-        sqlx::query(&base_sql).bind(s).bind(per_page as i64).bind(offset)
-    } else {
-        sqlx::query(&base_sql).bind(per_page as i64).bind(offset)
-    };
-
-    let rows: Vec<shared_types::Contact> = query
-        .fetch_all(&mut *conn)
-        .await
-        .map(|recs| {
-            recs.into_iter().map(|row| {
-                shared_types::Contact {
-                    id: row.get("id"),
-                    tenant_id: row.get("tenant_id"),
-                    company_id: row.try_get("company_id").ok(),
-                    first_name: row.get("first_name"),
-                    last_name: row.get("last_name"),
-                    email: row.try_get("email").unwrap_or(None),
-                    phone: row.try_get("phone").unwrap_or(None),
-                    position: row.try_get("position").unwrap_or(None),
-                    notes: row.try_get("notes").unwrap_or(None),
-                    is_active: row.get("is_active"),
-                    created_at: row.get("created_at"),
-                    updated_at: row.get("updated_at"),
-                }
-            }).collect::<Vec<_>>()
-        })
-        .unwrap_or_default();
-
-    let total_pages = ((total_count as f64) / (per_page as f64)).ceil() as u32;
-    let meta = shared_types::PaginationMeta {
-        current_page: page,
-        per_page,
-        total_pages,
-        total_count: total_count as u64,
-        has_next: page < total_pages,
-        has_prev: page > 1,
-    };
-
-    let resp = shared_types::PaginatedResponse { data: rows, pagination: meta };
-    Json(ApiResponse::success(resp))
 }
 
 #[derive(serde::Deserialize, Validate, Debug, ToSchema)]
@@ -597,111 +378,89 @@ pub struct CreateContactRequest {
     post,
     path = "/api/v1/crm/contacts",
     request_body = CreateContactRequest,
-    responses((status = 201, description = "Contact created", body = ApiResponse<shared_types::Contact>)),
+    responses((status = 201, description = "Contact created", body = inline(ApiResponse<shared_types::Contact>))),
     tag = "crm"
 )]
 pub async fn create_contact(
     State(_state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
+    RequestLocale(locale): RequestLocale,
     Json(req): Json<CreateContactRequest>,
-) -> Json<ApiResponse<shared_types::Contact>> {
+) -> Response {
     info!("Create contact");
 
     if let Err(e) = req.validate() {
-        return Json(ApiResponse::error_typed(format!("Invalid input: {}", e)));
+        return Json(ApiResponse::<()>::invalid(&e, locale)).into_response();
     }
 
-    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
-        .bind(current.tenant_id.to_string())
-        .execute(&mut *conn)
-        .await;
+    let mut tx = match persistence::begin(&mut conn, current.tenant_id).await {
+        Ok(tx) => tx,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
 
-    let row = sqlx::query(
-        r#"INSERT INTO contacts (tenant_id, company_id, first_name, last_name, email, phone, position, notes)
-           VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-           RETURNING id, tenant_id, company_id, first_name, last_name, email, phone, position, notes, is_active, created_at, updated_at"#
-    )
-    .bind(current.tenant_id)
-    .bind(&req.company_id)
-    .bind(&req.first_name)
-    .bind(&req.last_name)
-    .bind(&req.email)
-    .bind(&req.phone)
-    .bind(&req.position)
-    .bind(&req.notes)
-    .fetch_one(&mut *conn)
-    .await;
-
-    match row {
-        Ok(row) => {
-            let json = shared_types::Contact {
-                id: row.get("id"),
-                tenant_id: row.get("tenant_id"),
-                company_id: row.try_get("company_id").ok(),
-                first_name: row.get("first_name"),
-                last_name: row.get("last_name"),
-                email: row.try_get("email").unwrap_or(None),
-                phone: row.try_get("phone").unwrap_or(None),
-                position: row.try_get("position").unwrap_or(None),
-                notes: row.try_get("notes").unwrap_or(None),
-                is_active: row.get("is_active"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            };
-            Json(ApiResponse::success(json))
-        }
-        Err(e) => Json(ApiResponse::error_typed(format!("{}", e)))
+    let contact = NewContact {
+        company_id: req.company_id,
+        first_name: req.first_name,
+        last_name: req.last_name,
+        email: req.email,
+        phone: req.phone,
+        position: req.position,
+        notes: req.notes,
+    };
+    let contact = match ContactRepo::insert(&mut tx, current.tenant_id, &contact).await {
+        Ok(contact) => contact,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+    if let Err(e) = tx.commit().await {
+        return Json(ApiResponse::<()>::error(format!("{}", e))).into_response();
     }
+    preconditions::tagged(contact.updated_at, ApiResponse::success(contact))
 }
 
 #[utoipa::path(
     get,
     path = "/api/v1/crm/contacts/{id}",
-    params(("id" = uuid::Uuid, Path, description = "Contact ID")),
-    responses((status = 200, description = "Contact detail", body = ApiResponse<shared_types::Contact>)),
+    params(
+        ("id" = uuid::Uuid, Path, description = "Contact ID"),
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return; id is always included"),
+    ),
+    responses(
+        (status = 200, description = "Contact detail", body = inline(ApiResponse<shared_types::Contact>)),
+        (status = 304, description = "Not modified since the ETag in If-None-Match"),
+        (status = 400, description = "Unknown field")
+    ),
     tag = "crm"
 )]
 pub async fn get_contact(
     State(_state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
+    preconditions: Preconditions,
+    fieldset: FieldsetQuery,
     Path(id): Path<uuid::Uuid>,
-) -> Json<ApiResponse<shared_types::Contact>> {
+) -> Response {
     info!("Get contact {}", id);
-    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
-        .bind(current.tenant_id.to_string())
-        .execute(&mut *conn)
-        .await;
 
-    let row = sqlx::query(
-        r#"SELECT id, tenant_id, company_id, first_name, last_name, email, phone, position, notes, is_active, created_at, updated_at
-           FROM contacts WHERE id = $1 LIMIT 1"#
-    )
-    .bind(id)
-    .fetch_optional(&mut *conn)
-    .await;
-
-    match row {
-        Ok(Some(row)) => {
-            let json = shared_types::Contact {
-                id: row.get("id"),
-                tenant_id: row.get("tenant_id"),
-                company_id: row.try_get("company_id").ok(),
-                first_name: row.get("first_name"),
-                last_name: row.get("last_name"),
-                email: row.try_get("email").unwrap_or(None),
-                phone: row.try_get("phone").unwrap_or(None),
-                position: row.try_get("position").unwrap_or(None),
-                notes: row.try_get("notes").unwrap_or(None),
-                is_active: row.get("is_active"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            };
-            Json(ApiResponse::success(json))
+    let fieldset = match fieldset.resolve::<shared_types::Contact>(&[]) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return e.into_response(),
+    };
+
+    let mut tx = match persistence::begin(&mut conn, current.tenant_id).await {
+        Ok(tx) => tx,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+
+    match ContactRepo::find(&mut tx, current.tenant_id, id).await {
+        Ok(Some(contact)) => {
+            if preconditions.not_modified(contact.updated_at) {
+                return preconditions::not_modified(contact.updated_at);
+            }
+            preconditions::tagged(contact.updated_at, ApiResponse::success(fieldset.apply(&contact)))
         }
-        Ok(None) => Json(ApiResponse::error_typed("Contact not found".to_string())),
-        Err(e) => Json(ApiResponse::error_typed(format!("{}", e))),
+        Ok(None) => Json(ApiResponse::<()>::error("Contact not found".to_string())).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
     }
 }
 
@@ -727,71 +486,51 @@ pub struct UpdateContactRequest {
     path = "/api/v1/crm/contacts/{id}",
     params(("id" = uuid::Uuid, Path, description = "Contact ID")),
     request_body = UpdateContactRequest,
-    responses((status = 200, description = "Contact updated", body = ApiResponse<shared_types::Contact>)),
+    responses(
+        (status = 200, description = "Contact updated", body = inline(ApiResponse<shared_types::Contact>)),
+        (status = 412, description = "If-Match does not match the current ETag")
+    ),
     tag = "crm"
 )]
 pub async fn update_contact(
     State(_state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
+    RequestLocale(locale): RequestLocale,
+    preconditions: Preconditions,
     Path(id): Path<uuid::Uuid>,
     Json(req): Json<UpdateContactRequest>,
-) -> Json<ApiResponse<shared_types::Contact>> {
+) -> Response {
     info!("Update contact {}", id);
     if let Err(e) = req.validate() {
-        return Json(ApiResponse::error_typed(format!("Invalid input: {}", e)));
+        return Json(ApiResponse::<()>::invalid(&e, locale)).into_response();
     }
 
-    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
-        .bind(current.tenant_id.to_string())
-        .execute(&mut *conn)
-        .await;
+    let mut tx = match persistence::begin(&mut conn, current.tenant_id).await {
+        Ok(tx) => tx,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
 
-    let row = sqlx::query(
-        r#"UPDATE contacts SET
-               company_id = COALESCE($2, company_id),
-               first_name = COALESCE($3, first_name),
-               last_name = COALESCE($4, last_name),
-               email = COALESCE($5, email),
-               phone = COALESCE($6, phone),
-               position = COALESCE($7, position),
-               notes = COALESCE($8, notes),
-               is_active = COALESCE($9, is_active),
-               updated_at = NOW()
-           WHERE id = $1
-           RETURNING id, tenant_id, company_id, first_name, last_name, email, phone, position, notes, is_active, created_at, updated_at"#
-    )
-    .bind(id)
-    .bind(&req.company_id)
-    .bind(&req.first_name)
-    .bind(&req.last_name)
-    .bind(&req.email)
-    .bind(&req.phone)
-    .bind(&req.position)
-    .bind(&req.notes)
-    .bind(&req.is_active)
-    .fetch_one(&mut *conn)
-    .await;
-
-    match row {
-        Ok(row) => {
-            let json = shared_types::Contact {
-                id: row.get("id"),
-                tenant_id: row.get("tenant_id"),
-                company_id: row.try_get("company_id").ok(),
-                first_name: row.get("first_name"),
-                last_name: row.get("last_name"),
-                email: row.try_get("email").unwrap_or(None),
-                phone: row.try_get("phone").unwrap_or(None),
-                position: row.try_get("position").unwrap_or(None),
-                notes: row.try_get("notes").unwrap_or(None),
-                is_active: row.get("is_active"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            };
-            Json(ApiResponse::success(json))
+    let changes = ContactChanges {
+        company_id: req.company_id,
+        first_name: req.first_name,
+        last_name: req.last_name,
+        email: req.email,
+        phone: req.phone,
+        position: req.position,
+        notes: req.notes,
+        is_active: req.is_active,
+    };
+    let expected = preconditions.expected_versions();
+    match ContactRepo::update(&mut tx, current.tenant_id, id, &changes, expected.as_deref()).await {
+        Ok(Some(contact)) => {
+            if let Err(e) = tx.commit().await {
+                return Json(ApiResponse::<()>::error(format!("{}", e))).into_response();
+            }
+            preconditions::tagged(contact.updated_at, ApiResponse::success(contact))
         }
-        Err(e) => Json(ApiResponse::error_typed(format!("{}", e)))
+        Ok(None) => preconditions::update_missed(&mut tx, "contacts", id, "Contact not found").await,
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
     }
 }
 
@@ -809,24 +548,17 @@ pub async fn delete_contact(
     Path(id): Path<uuid::Uuid>,
 ) -> Json<ApiResponse<serde_json::Value>> {
     info!("Delete contact {}", id);
-    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
-        .bind(current.tenant_id.to_string())
-        .execute(&mut *conn)
-        .await;
+    let mut tx = match persistence::begin(&mut conn, current.tenant_id).await {
+        Ok(tx) => tx,
+        Err(e) => return Json(ApiResponse::error_typed(format!("{}", e))),
+    };
 
-    let res = sqlx::query(
-        r#"UPDATE contacts SET is_active = false, updated_at = NOW() WHERE id = $1 RETURNING id"#
-    )
-    .bind(id)
-    .fetch_optional(&mut *conn)
-    .await;
-
-    match res {
-        Ok(Some(row)) => {
-            let deleted_id: uuid::Uuid = row.get("id");
-            Json(ApiResponse::success(serde_json::json!({ "deleted_id": deleted_id })))
-        }
-        Ok(None) => Json(ApiResponse::error_typed("Contact not found".to_string())),
+    match ContactRepo::deactivate(&mut tx, current.tenant_id, id).await {
+        Ok(true) => match tx.commit().await {
+            Ok(()) => Json(ApiResponse::success(serde_json::json!({ "deleted_id": id }))),
+            Err(e) => Json(ApiResponse::error_typed(format!("{}", e))),
+        },
+        Ok(false) => Json(ApiResponse::error_typed("Contact not found".to_string())),
         Err(e) => Json(ApiResponse::error_typed(format!("{}", e))),
     }
 }
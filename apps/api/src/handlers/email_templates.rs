@@ -0,0 +1,93 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use shared_types::{ApiResponse, EmailTemplateInfo, PreviewEmailRequest};
+use std::sync::Arc;
+use tracing::info;
+use validator::Validate;
+
+use crate::{
+    email::{EmailTemplate, Locale, TemplateError, TenantEmailSettings},
+    extractors::RequestLocale,
+    middleware::{auth_middleware::CurrentUser, db_conn::DbConn},
+    state::AppState,
+};
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(ApiResponse::<()>::error(message.into()))).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/email-templates",
+    responses((status = 200, description = "Email templates with their locales and variables", body = inline(ApiResponse<Vec<EmailTemplateInfo>>))),
+    tag = "email"
+)]
+pub async fn list_email_templates(
+    State(_state): State<Arc<AppState>>,
+    _current: Extension<CurrentUser>,
+) -> Json<ApiResponse<Vec<EmailTemplateInfo>>> {
+    Json(ApiResponse::success(EmailTemplate::ALL.iter().map(EmailTemplate::info).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/email-templates/{name}/preview",
+    params(("name" = String, Path, description = "Template name, e.g. invitation")),
+    request_body = PreviewEmailRequest,
+    responses(
+        (status = 200, description = "Subject, HTML and text as they would be sent, with the tenant's branding", body = inline(ApiResponse<shared_types::RenderedEmail>)),
+        (status = 400, description = "Unsupported locale or invalid branding"),
+        (status = 404, description = "Unknown template"),
+        (status = 422, description = "Missing or unknown template variables")
+    ),
+    tag = "email"
+)]
+pub async fn preview_email_template(
+    State(state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    RequestLocale(locale): RequestLocale,
+    Path(name): Path<String>,
+    Json(req): Json<PreviewEmailRequest>,
+) -> Response {
+    info!("Preview email template {}", name);
+
+    let Some(template) = EmailTemplate::parse(&name) else {
+        return error_response(StatusCode::NOT_FOUND, format!("Unknown email template '{}'", name));
+    };
+    if let Err(e) = req.validate() {
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::invalid(&e, locale))).into_response();
+    }
+    let requested_locale = match req.locale.as_deref().map(Locale::parse) {
+        Some(None) => return error_response(StatusCode::BAD_REQUEST, "locale must be en or id"),
+        Some(locale) => locale,
+        None => None,
+    };
+
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let mut tenant = match TenantEmailSettings::load(&mut conn, current.tenant_id).await {
+        Ok(tenant) => tenant,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+    if let Some(branding) = req.branding {
+        tenant.branding = branding;
+    }
+    let locale = requested_locale.or(tenant.locale).unwrap_or(state.mailer.default_locale());
+    let variables = req.variables.unwrap_or_else(|| template.sample());
+
+    match state.mailer.templates().render(template, locale, &tenant.brand(locale), &variables) {
+        Ok(email) => Json(ApiResponse::success(email)).into_response(),
+        Err(e @ (TemplateError::MissingVariables(_) | TemplateError::UnknownVariables(_))) => {
+            error_response(StatusCode::UNPROCESSABLE_ENTITY, e.to_string())
+        }
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
@@ -0,0 +1,319 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde_json::json;
+use shared_types::{
+    ApiResponse, Conversion, CreateExchangeRateRequest, Currency, ExchangeRate, FetchExchangeRatesRequest, Money,
+    RateType,
+};
+use sqlx::Acquire;
+use std::sync::Arc;
+use tracing::info;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+use crate::{
+    exchange_rates::{self, NewRate, RateError, EXCHANGE_RATE_COLUMNS},
+    export::{self, ExportRequest, ExportSource},
+    extractors::preconditions,
+    fieldset::FieldsetQuery,
+    list_query::{Field, FieldType, ListPage, ListQuery, ListSpec},
+    middleware::{auth_middleware::CurrentUser, db_conn::DbConn},
+    state::AppState,
+};
+
+static EXCHANGE_RATE_LIST: ListSpec = ListSpec {
+    from: "exchange_rates",
+    tenant_column: "tenant_id",
+    base_filter: None,
+    id_column: "id",
+    fields: &[
+        Field::new("from_currency", "from_currency", FieldType::Text).sortable(),
+        Field::new("to_currency", "to_currency", FieldType::Text).sortable(),
+        Field::new("rate_type", "rate_type", FieldType::Text),
+        Field::new("rate_date", "rate_date", FieldType::Date).sortable(),
+        Field::new("source", "source", FieldType::Text),
+        Field::new("created_at", "created_at", FieldType::Timestamp).sortable(),
+        Field::new("updated_at", "updated_at", FieldType::Timestamp).sortable(),
+    ],
+    search: &[],
+    default_sort: "-rate_date",
+    aliases: &[],
+};
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(ApiResponse::<()>::error(message.into()))).into_response()
+}
+
+fn rate_error(e: RateError) -> Response {
+    match e {
+        RateError::Missing { .. } => error_response(StatusCode::UNPROCESSABLE_ENTITY, e.to_string()),
+        RateError::Database(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounting/exchange-rates",
+    params(
+        ("page" = Option<u32>, Query, description = "Page number"),
+        ("per_page" = Option<u32>, Query, description = "Items per page"),
+        ("cursor" = Option<String>, Query, description = "Opaque cursor from pagination.next_cursor"),
+        ("limit" = Option<u32>, Query, description = "Items per page (keyset pagination, max 200)"),
+        ("count" = Option<CountMode>, Query, description = "Keyset total: none|estimated|exact"),
+        ("sort" = Option<String>, Query, description = "Comma-separated, '-' for descending: rate_date|from_currency|to_currency|created_at|updated_at"),
+        ("from_currency" = Option<String>, Query, description = "Filter by source currency; any field also takes field[op]=value with eq|ne|in|gt|gte|lt|lte|ilike|null"),
+        ("to_currency" = Option<String>, Query, description = "Filter by target currency"),
+        ("rate_type" = Option<String>, Query, description = "Filter by rate type: spot|tax|average"),
+        ("rate_date" = Option<String>, Query, description = "Filter by date, e.g. rate_date[gte]=2024-03-01"),
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return; id is always included"),
+        ("format" = Option<String>, Query, description = "Stream every matching row as csv|xlsx|jsonl instead of a JSON page (also via Accept)"),
+    ),
+    responses(
+        (status = 200, description = "List exchange rates", body = inline(ApiResponse<ListPage<ExchangeRate>>)),
+        (status = 400, description = "Unknown field, operator or value")
+    ),
+    tag = "accounting"
+)]
+pub async fn list_exchange_rates(
+    State(state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    query: ListQuery,
+    fieldset: FieldsetQuery,
+    export: ExportRequest,
+) -> Response {
+    info!("List exchange rates");
+
+    let plan = match EXCHANGE_RATE_LIST.plan(&query) {
+        Ok(plan) => plan,
+        Err(e) => return e.into_response(),
+    };
+    let fieldset = match fieldset.resolve::<ExchangeRate>(&[]) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return e.into_response(),
+    };
+
+    // Set tenant context (RLS)
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    if let Some(format) = export.format() {
+        let source = ExportSource {
+            name: "exchange-rates",
+            plan,
+            columns: EXCHANGE_RATE_COLUMNS,
+            map: |row| Ok(exchange_rates::exchange_rate_from_row(row)),
+            relations: &[],
+        };
+        return export::respond(&state, conn, current.tenant_id, format, fieldset, source).await;
+    }
+    match plan.fetch(&mut conn, current.tenant_id, EXCHANGE_RATE_COLUMNS, |row| Ok(exchange_rates::exchange_rate_from_row(row))).await {
+        Ok(page) => fieldset.page(page).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/accounting/exchange-rates",
+    request_body = CreateExchangeRateRequest,
+    responses(
+        (status = 200, description = "Rate stored, replacing any rate of the same pair, type and date", body = inline(ApiResponse<ExchangeRate>)),
+        (status = 400, description = "Rate not positive, or both currencies the same")
+    ),
+    tag = "accounting"
+)]
+pub async fn upsert_exchange_rate(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Json(req): Json<CreateExchangeRateRequest>,
+) -> Response {
+    info!("Upsert exchange rate {}/{}", req.from_currency, req.to_currency);
+
+    if req.rate <= Decimal::ZERO {
+        return error_response(StatusCode::BAD_REQUEST, "rate must be greater than 0");
+    }
+    if req.from_currency == req.to_currency {
+        return error_response(StatusCode::BAD_REQUEST, "from_currency and to_currency must differ");
+    }
+
+    // Set tenant context (RLS)
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let rate = NewRate {
+        from_currency: req.from_currency,
+        to_currency: req.to_currency,
+        rate_type: req.rate_type.unwrap_or_default(),
+        rate_date: req.rate_date,
+        rate: req.rate,
+    };
+    match exchange_rates::upsert(&mut conn, current.tenant_id, &rate, "manual", Some(current.user_id)).await {
+        Ok(rate) => preconditions::tagged(rate.updated_at, ApiResponse::success(rate)),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/accounting/exchange-rates/{id}",
+    params(("id" = uuid::Uuid, Path, description = "Exchange rate ID")),
+    responses(
+        (status = 200, description = "Rate removed; documents keep the rate they were created with", body = inline(ApiResponse<serde_json::Value>)),
+        (status = 404, description = "Exchange rate not found")
+    ),
+    tag = "accounting"
+)]
+pub async fn delete_exchange_rate(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Path(id): Path<Uuid>,
+) -> Response {
+    info!("Delete exchange rate {}", id);
+
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let deleted = sqlx::query("DELETE FROM exchange_rates WHERE id = $1 AND tenant_id = $2")
+        .bind(id)
+        .bind(current.tenant_id)
+        .execute(&mut *conn)
+        .await;
+
+    match deleted {
+        Ok(done) if done.rows_affected() > 0 => Json(ApiResponse::success(json!({"deleted_id": id}))).into_response(),
+        Ok(_) => error_response(StatusCode::NOT_FOUND, "Exchange rate not found"),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+/// An amount to convert.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ConvertQuery {
+    /// Amount in `from`, at no more than its precision
+    #[param(value_type = String, example = "1250.50")]
+    amount: Decimal,
+    from: Currency,
+    to: Currency,
+    /// Day of the rate; today when omitted
+    date: Option<NaiveDate>,
+    /// `spot` when omitted
+    rate_type: Option<RateType>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounting/exchange-rates/convert",
+    params(ConvertQuery),
+    responses(
+        (status = 200, description = "Converted amount and the rate used", body = inline(ApiResponse<Conversion>)),
+        (status = 400, description = "Amount more precise than its currency"),
+        (status = 422, description = "No rate within exchange_rates.max_age_days of the date, directly or through the base currency")
+    ),
+    tag = "accounting"
+)]
+pub async fn convert(
+    State(state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Query(query): Query<ConvertQuery>,
+) -> Response {
+    info!("Convert {} to {}", query.from, query.to);
+
+    let amount = match Money::exact(query.amount, query.from) {
+        Ok(amount) => amount,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let on = query.date.unwrap_or_else(|| Utc::now().date_naive());
+    let rate_type = query.rate_type.unwrap_or_default();
+
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let max_age_days = state.config.exchange_rates.max_age_days;
+    let effective =
+        match exchange_rates::effective_rate(&mut conn, current.tenant_id, query.from, query.to, on, rate_type, max_age_days).await {
+            Ok(effective) => effective,
+            Err(e) => return rate_error(e),
+        };
+    let converted = match effective.convert(amount, query.to) {
+        Ok(converted) => converted,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+
+    Json(ApiResponse::success(Conversion {
+        amount,
+        converted,
+        rate: effective.rate.normalize(),
+        rate_type,
+        rate_date: effective.rate_date,
+        method: effective.method,
+        via: effective.via,
+    }))
+    .into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/accounting/exchange-rates/fetch",
+    request_body = FetchExchangeRatesRequest,
+    responses(
+        (status = 200, description = "Rates published for the day, stored for the tenant", body = inline(ApiResponse<Vec<ExchangeRate>>)),
+        (status = 502, description = "The rate source could not be read")
+    ),
+    tag = "accounting"
+)]
+pub async fn fetch_exchange_rates(
+    State(state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Json(req): Json<FetchExchangeRatesRequest>,
+) -> Response {
+    let date = req.date.unwrap_or_else(|| Utc::now().date_naive());
+    info!("Fetch exchange rates for {}", date);
+
+    let rates = match state.rate_fetcher.fetch(date).await {
+        Ok(rates) => rates,
+        Err(e) => return error_response(StatusCode::BAD_GATEWAY, format!("{:#}", e)),
+    };
+
+    let mut tx = match conn.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("Failed to start transaction: {}", e))).into_response(),
+    };
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *tx)
+        .await;
+
+    let mut stored = Vec::with_capacity(rates.len());
+    for rate in &rates {
+        match exchange_rates::upsert(&mut tx, current.tenant_id, rate, "feed", Some(current.user_id)).await {
+            Ok(rate) => stored.push(rate),
+            Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+        }
+    }
+    if let Err(e) = tx.commit().await {
+        return Json(ApiResponse::<()>::error(format!("Failed to commit transaction: {}", e))).into_response();
+    }
+
+    Json(ApiResponse::success(stored)).into_response()
+}
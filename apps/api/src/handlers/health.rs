@@ -1,81 +1,216 @@
-use axum::{extract::State, Json};
+use axum::{extract::State, http::StatusCode, Json};
 use serde::{Deserialize, Serialize};
 use shared_types::ApiResponse;
-use std::sync::Arc;
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 use utoipa::ToSchema;
 
 use crate::state::AppState;
+use tracing::Instrument;
+
+/// Upper bound for a single dependency check, so a hung dependency fails
+/// the probe instead of stalling it.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+static PROCESS_START: OnceLock<Instant> = OnceLock::new();
+
+/// Record the process start time. Call this first thing in `main`.
+pub fn mark_process_start() {
+    PROCESS_START.get_or_init(Instant::now);
+}
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct HealthStatus {
     pub status: String,
     pub version: String,
+    pub git_sha: String,
     pub database: String,
     pub redis: String,
     pub uptime: u64,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LivenessStatus {
+    pub status: String,
+    pub version: String,
+    pub git_sha: String,
+    pub uptime: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: String,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReadinessStatus {
+    pub status: String,
+    pub version: String,
+    pub git_sha: String,
+    pub uptime: u64,
+    pub checks: Vec<CheckResult>,
+}
+
+shared_types::data_schema!(HealthStatus, LivenessStatus, ReadinessStatus);
+
 /// Health check endpoint
 #[utoipa::path(
     get,
     path = "/health",
     responses(
-        (status = 200, description = "Health check successful", body = ApiResponse<HealthStatus>),
-        (status = 503, description = "Service unavailable", body = ApiResponse<()>)
+        (status = 200, description = "Health check successful", body = inline(ApiResponse<HealthStatus>)),
+        (status = 503, description = "Service unavailable", body = inline(ApiResponse<HealthStatus>))
     ),
     tag = "health"
 )]
 pub async fn health_check(
     State(state): State<Arc<AppState>>,
-) -> Json<ApiResponse<HealthStatus>> {
-    let database_status = check_database(&state).await;
-    let redis_status = check_redis(&state).await;
-    
-    let overall_status = if database_status == "healthy" && redis_status == "healthy" {
-        "healthy"
-    } else {
-        "unhealthy"
-    };
+) -> (StatusCode, Json<ApiResponse<HealthStatus>>) {
+    let database = run_check("database", check_database(&state)).await;
+    let redis = run_check("redis", check_redis(&state)).await;
+    let healthy = database.error.is_none() && redis.error.is_none();
 
     let health = HealthStatus {
-        status: overall_status.to_string(),
+        status: if healthy { "healthy" } else { "unhealthy" }.to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_sha: env!("GIT_SHA").to_string(),
+        database: database.status,
+        redis: redis.status,
+        uptime: get_uptime(),
+    };
+
+    if healthy {
+        (StatusCode::OK, Json(ApiResponse::success(health)))
+    } else {
+        let body = ApiResponse {
+            success: false,
+            data: Some(health),
+            message: Some("Service unhealthy".to_string()),
+            errors: None,
+            code: None,
+        };
+        (StatusCode::SERVICE_UNAVAILABLE, Json(body))
+    }
+}
+
+/// Liveness probe: the process is up and serving requests. Never touches dependencies.
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    responses(
+        (status = 200, description = "Process is alive", body = inline(ApiResponse<LivenessStatus>))
+    ),
+    tag = "health"
+)]
+pub async fn liveness() -> Json<ApiResponse<LivenessStatus>> {
+    Json(ApiResponse::success(LivenessStatus {
+        status: "alive".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_sha: env!("GIT_SHA").to_string(),
+        uptime: get_uptime(),
+    }))
+}
+
+/// Readiness probe: database, Redis and schema migrations, with per-check latency
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    responses(
+        (status = 200, description = "Ready to serve traffic", body = inline(ApiResponse<ReadinessStatus>)),
+        (status = 503, description = "A dependency is unavailable", body = inline(ApiResponse<ReadinessStatus>))
+    ),
+    tag = "health"
+)]
+pub async fn readiness(
+    State(state): State<Arc<AppState>>,
+) -> (StatusCode, Json<ApiResponse<ReadinessStatus>>) {
+    let (database, redis, migrations) = tokio::join!(
+        run_check("database", check_database(&state)),
+        run_check("redis", check_redis(&state)),
+        run_check("migrations", check_migrations(&state)),
+    );
+    let checks = vec![database, redis, migrations];
+    let ready = checks.iter().all(|c| c.error.is_none());
+
+    let status = ReadinessStatus {
+        status: if ready { "ready" } else { "not_ready" }.to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
-        database: database_status,
-        redis: redis_status,
+        git_sha: env!("GIT_SHA").to_string(),
         uptime: get_uptime(),
+        checks,
     };
 
-    Json(ApiResponse::success(health))
+    if ready {
+        (StatusCode::OK, Json(ApiResponse::success(status)))
+    } else {
+        let body = ApiResponse {
+            success: false,
+            data: Some(status),
+            message: Some("Service not ready".to_string()),
+            errors: None,
+            code: None,
+        };
+        (StatusCode::SERVICE_UNAVAILABLE, Json(body))
+    }
 }
 
-async fn check_database(state: &AppState) -> String {
-    match sqlx::query("SELECT 1").execute(&state.db_pool).await {
-        Ok(_) => "healthy".to_string(),
-        Err(_) => "unhealthy".to_string(),
+async fn run_check<F>(name: &str, check: F) -> CheckResult
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let start = Instant::now();
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("timed out after {}ms", CHECK_TIMEOUT.as_millis())),
+    };
+
+    CheckResult {
+        name: name.to_string(),
+        status: if result.is_ok() { "healthy" } else { "unhealthy" }.to_string(),
+        latency_ms: start.elapsed().as_millis() as u64,
+        error: result.err(),
     }
 }
 
-async fn check_redis(state: &AppState) -> String {
-    let mut conn = state.redis.clone();
-    match redis::cmd("PING").query_async::<_, String>(&mut conn).await {
-        Ok(_) => "healthy".to_string(),
-        Err(_) => "unhealthy".to_string(),
+async fn check_database(state: &AppState) -> Result<(), String> {
+    sqlx::query("SELECT 1")
+        .execute(&state.db_pool)
+        .instrument(telemetry::db_span("SELECT", "health"))
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn check_redis(state: &AppState) -> Result<(), String> {
+    state.kv.ping().await.map_err(|e| e.to_string())
+}
+
+/// Fails while any migration embedded in this build has not been applied successfully.
+async fn check_migrations(state: &AppState) -> Result<(), String> {
+    let applied: Vec<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = true")
+            .fetch_all(&state.db_pool)
+            .instrument(telemetry::db_span("SELECT", "_sqlx_migrations"))
+            .await
+            .map_err(|e| e.to_string())?;
+
+    let pending = crate::MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration() && !applied.contains(&m.version))
+        .count();
+
+    if pending == 0 {
+        Ok(())
+    } else {
+        Err(format!("{} pending migration(s)", pending))
     }
 }
 
+/// Seconds since `mark_process_start` was called.
 fn get_uptime() -> u64 {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    
-    static START_TIME: std::sync::OnceLock<u64> = std::sync::OnceLock::new();
-    let start = START_TIME.get_or_init(|| {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-    });
-    
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs() - start
+    PROCESS_START.get_or_init(Instant::now).elapsed().as_secs()
 }
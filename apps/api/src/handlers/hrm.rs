@@ -1,11 +1,19 @@
-use axum::{extract::{State, Extension}, Json};
-use shared_types::ApiResponse;
+use axum::{extract::{State, Extension}, http::StatusCode, response::{IntoResponse, Response}, Json};
+use shared_types::{ApiResponse, CreateLeaveRequest, LeaveRequest, LeaveStatus, LeaveType, Notification};
+use sqlx::{postgres::PgRow, Acquire, PgConnection, Row};
 use std::sync::Arc;
 use tracing::info;
+use validator::Validate;
 
-use crate::{state::AppState, middleware::{auth_middleware::CurrentUser, db_conn::DbConn}};
+use crate::{state::AppState, cache::ReadCache, fieldset::FieldsetQuery, list_query::{Field, FieldType, ListPage, ListQuery, ListSpec}, extractors::RequestLocale, middleware::{auth_middleware::CurrentUser, db_conn::DbConn}, notifications};
 
 // Employee handlers
+#[utoipa::path(
+    get,
+    path = "/api/v1/hrm/employees",
+    responses((status = 200, description = "Not implemented yet", body = ApiResponse<()>)),
+    tag = "hrm"
+)]
 pub async fn list_employees(
     State(_state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
@@ -19,6 +27,12 @@ pub async fn list_employees(
     Json(ApiResponse::<()>::error_typed("Not implemented yet".to_string()))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/hrm/employees",
+    responses((status = 200, description = "Not implemented yet", body = ApiResponse<()>)),
+    tag = "hrm"
+)]
 pub async fn create_employee(
     State(_state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
@@ -33,28 +47,190 @@ pub async fn create_employee(
 }
 
 // Leave handlers
+
+static LEAVE_LIST: ListSpec = ListSpec {
+    from: "leave_requests",
+    tenant_column: "tenant_id",
+    base_filter: None,
+    id_column: "id",
+    fields: &[
+        Field::new("user_id", "user_id", FieldType::Uuid),
+        Field::new("leave_type", "leave_type", FieldType::Text),
+        Field::new("status", "status", FieldType::Text).sortable(),
+        Field::new("start_date", "start_date", FieldType::Date).sortable(),
+        Field::new("end_date", "end_date", FieldType::Date).sortable(),
+        Field::new("created_at", "created_at", FieldType::Timestamp).sortable(),
+    ],
+    search: &["reason"],
+    default_sort: "-start_date",
+    aliases: &[],
+};
+
+const LEAVE_COLUMNS: &str = r#"id, tenant_id, user_id, leave_type, start_date, end_date, reason, status,
+       reviewed_by, reviewed_at, created_at, updated_at"#;
+
+fn leave_from_row(row: &PgRow) -> LeaveRequest {
+    LeaveRequest {
+        id: row.get("id"),
+        tenant_id: row.get("tenant_id"),
+        user_id: row.get("user_id"),
+        leave_type: match row.get::<String, _>("leave_type").as_str() {
+            "sick" => LeaveType::Sick,
+            "unpaid" => LeaveType::Unpaid,
+            "other" => LeaveType::Other,
+            _ => LeaveType::Annual,
+        },
+        start_date: row.get("start_date"),
+        end_date: row.get("end_date"),
+        reason: row.get("reason"),
+        status: match row.get::<String, _>("status").as_str() {
+            "approved" => LeaveStatus::Approved,
+            "rejected" => LeaveStatus::Rejected,
+            "cancelled" => LeaveStatus::Cancelled,
+            _ => LeaveStatus::Pending,
+        },
+        reviewed_by: row.get("reviewed_by"),
+        reviewed_at: row.get("reviewed_at"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/hrm/leaves",
+    params(
+        ("page" = Option<u32>, Query, description = "Page number"),
+        ("per_page" = Option<u32>, Query, description = "Items per page"),
+        ("cursor" = Option<String>, Query, description = "Opaque cursor from pagination.next_cursor"),
+        ("limit" = Option<u32>, Query, description = "Items per page (keyset pagination, max 200)"),
+        ("count" = Option<CountMode>, Query, description = "Keyset total: none|estimated|exact"),
+        ("sort" = Option<String>, Query, description = "Comma-separated sort fields, '-' prefix for descending (default -start_date)"),
+        ("status" = Option<LeaveStatus>, Query, description = "Filter by status; any field also takes field[op]=value with eq|ne|in|gt|gte|lt|lte|ilike|null"),
+        ("user_id" = Option<uuid::Uuid>, Query, description = "Filter by requester"),
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return; id is always included"),
+    ),
+    responses(
+        (status = 200, description = "Leave requests; without hrm:leaves:approve only the caller's own", body = inline(ApiResponse<ListPage<LeaveRequest>>)),
+        (status = 400, description = "Unknown field, operator or value")
+    ),
+    tag = "hrm"
+)]
 pub async fn list_leaves(
     State(_state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
-) -> Json<ApiResponse<()>> {
+    query: ListQuery,
+    fieldset: FieldsetQuery,
+) -> Response {
     info!("List leaves");
+
+    let plan = match LEAVE_LIST.plan(&query) {
+        Ok(plan) if current.can("hrm:leaves:approve") => plan,
+        Ok(plan) => plan.scoped("user_id", current.user_id),
+        Err(e) => return e.into_response(),
+    };
+    let fieldset = match fieldset.resolve::<LeaveRequest>(&[]) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return e.into_response(),
+    };
+
+    // Set tenant context (RLS)
     let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
         .bind(current.tenant_id.to_string())
         .execute(&mut *conn)
         .await;
-    Json(ApiResponse::<()>::error_typed("Not implemented yet".to_string()))
+
+    match plan.fetch(&mut conn, current.tenant_id, LEAVE_COLUMNS, |row| Ok(leave_from_row(row))).await {
+        Ok(page) => fieldset.page(page).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/hrm/leaves",
+    request_body = CreateLeaveRequest,
+    responses(
+        (status = 201, description = "Leave requested; approvers are notified", body = inline(ApiResponse<LeaveRequest>)),
+        (status = 400, description = "Invalid input")
+    ),
+    tag = "hrm"
+)]
 pub async fn create_leave(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
-) -> Json<ApiResponse<()>> {
+    RequestLocale(locale): RequestLocale,
+    Json(req): Json<CreateLeaveRequest>,
+) -> Response {
     info!("Create leave");
+
+    if let Err(e) = req.validate() {
+        return Json(ApiResponse::<()>::invalid(&e, locale)).into_response();
+    }
+    if req.end_date < req.start_date {
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error("end_date is before start_date".to_string()))).into_response();
+    }
+
+    let mut tx = match conn.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("Failed to start transaction: {}", e))).into_response(),
+    };
     let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
         .bind(current.tenant_id.to_string())
-        .execute(&mut *conn)
+        .execute(&mut *tx)
         .await;
-    Json(ApiResponse::<()>::error_typed("Not implemented yet".to_string()))
+
+    let row = sqlx::query(&format!(
+        r#"INSERT INTO leave_requests (tenant_id, user_id, leave_type, start_date, end_date, reason)
+           VALUES ($1, $2, $3, $4, $5, $6)
+           RETURNING {}"#,
+        LEAVE_COLUMNS
+    ))
+    .bind(current.tenant_id)
+    .bind(current.user_id)
+    .bind(req.leave_type.as_str())
+    .bind(req.start_date)
+    .bind(req.end_date)
+    .bind(&req.reason)
+    .fetch_one(&mut *tx)
+    .await;
+    let leave = match row {
+        Ok(row) => leave_from_row(&row),
+        Err(e) => return Json(ApiResponse::<()>::error(format!("Failed to create leave request: {}", e))).into_response(),
+    };
+
+    let created = notify_approvers(&mut tx, &state.cache, &current, &leave).await;
+    let created = match created {
+        Ok(created) => created,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+
+    if let Err(e) = tx.commit().await {
+        return Json(ApiResponse::<()>::error(format!("Failed to commit transaction: {}", e))).into_response();
+    }
+    notifications::publish(state.kv.as_ref(), &created).await;
+
+    (StatusCode::CREATED, Json(ApiResponse::success(leave))).into_response()
+}
+
+/// Tell everyone who can approve the leave, apart from the requester.
+async fn notify_approvers(
+    conn: &mut PgConnection,
+    cache: &ReadCache,
+    current: &CurrentUser,
+    leave: &LeaveRequest,
+) -> Result<Vec<Notification>, sqlx::Error> {
+    let requester: String = sqlx::query_scalar(
+        "SELECT COALESCE(NULLIF(TRIM(CONCAT(first_name, ' ', last_name)), ''), email) FROM users WHERE id = $1",
+    )
+    .bind(current.user_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let mut approvers = notifications::recipients(&mut *conn, cache, current.tenant_id, "hrm:leaves:approve").await?;
+    approvers.retain(|user_id| *user_id != current.user_id);
+    let notice = notifications::Notice::leave_request(leave, &requester);
+    notifications::create(conn, current.tenant_id, &approvers, &notice).await
 }
@@ -0,0 +1,490 @@
+use axum::{
+    extract::{Extension, Multipart, Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::Value;
+use shared_types::{ApiResponse, ImportEntity, ImportField, ImportJob, ImportPreview, ImportRowError, ImportStatus};
+use sqlx::{postgres::PgRow, Row};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::info;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::{
+    export::{self, ExportRequest, ExportSource},
+    fieldset::FieldsetQuery,
+    imports::{self, sheet, Mapping},
+    list_query::{Field, FieldType, ListPage, ListQuery, ListSpec},
+    middleware::{auth_middleware::CurrentUser, db_conn::DbConn},
+    state::AppState,
+};
+
+/// Row errors returned with a job; the full list is at `/imports/{id}/errors`.
+const JOB_ERROR_LIMIT: usize = 100;
+/// Validated rows echoed back by the upload so the mapping can be checked.
+const SAMPLE_ROWS: usize = 5;
+
+static IMPORT_JOB_LIST: ListSpec = ListSpec {
+    from: "import_jobs",
+    tenant_column: "tenant_id",
+    base_filter: None,
+    id_column: "id",
+    fields: &[
+        Field::new("entity", "entity", FieldType::Text).sortable(),
+        Field::new("status", "status", FieldType::Text).sortable(),
+        Field::new("file_name", "file_name", FieldType::Text).sortable(),
+        Field::new("created_by", "created_by", FieldType::Uuid),
+        Field::new("created_at", "created_at", FieldType::Timestamp).sortable(),
+        Field::new("finished_at", "finished_at", FieldType::Timestamp).sortable().nullable(),
+    ],
+    search: &["file_name"],
+    default_sort: "-created_at",
+    aliases: &[],
+};
+
+const IMPORT_JOB_COLUMNS: &str = r#"id, tenant_id, entity, file_name, file_format, column_mapping, status,
+       total_rows, error_rows, imported_rows, created_by, created_at, started_at, finished_at, updated_at"#;
+
+fn job_from_row(row: &PgRow) -> ImportJob {
+    ImportJob {
+        id: row.get("id"),
+        tenant_id: row.get("tenant_id"),
+        entity: ImportEntity::parse(row.get("entity")).unwrap_or(ImportEntity::Products),
+        file_name: row.get("file_name"),
+        file_format: row.get("file_format"),
+        column_mapping: serde_json::from_value(row.get("column_mapping")).unwrap_or_default(),
+        status: ImportStatus::parse(row.get("status")),
+        total_rows: row.get("total_rows"),
+        error_rows: row.get("error_rows"),
+        imported_rows: row.get("imported_rows"),
+        // Only selected by the single-job queries
+        errors: row
+            .try_get::<Value, _>("errors")
+            .ok()
+            .and_then(|errors| serde_json::from_value(errors).ok()),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        started_at: row.try_get("started_at").unwrap_or(None),
+        finished_at: row.try_get("finished_at").unwrap_or(None),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(ApiResponse::<()>::error(message.into()))).into_response()
+}
+
+fn import_fields(entity: ImportEntity) -> Vec<ImportField> {
+    imports::fields(entity)
+        .iter()
+        .map(|f| ImportField {
+            name: f.name.to_string(),
+            required: f.required,
+            description: f.description.to_string(),
+        })
+        .collect()
+}
+
+/// The multipart form of an upload.
+struct Upload {
+    entity: Option<String>,
+    file: Option<(String, Vec<u8>)>,
+    mapping: BTreeMap<String, String>,
+    dry_run: bool,
+}
+
+/// Read the form, failing with 413 as soon as it grows past `limit` bytes.
+async fn read_upload(mut multipart: Multipart, limit: usize) -> Result<Upload, Response> {
+    let mut upload = Upload { entity: None, file: None, mapping: BTreeMap::new(), dry_run: true };
+    let mut total = 0usize;
+    let malformed = |e: axum::extract::multipart::MultipartError| error_response(StatusCode::BAD_REQUEST, e.body_text());
+
+    while let Some(mut field) = multipart.next_field().await.map_err(malformed)? {
+        let name = field.name().unwrap_or_default().to_string();
+        let file_name = field.file_name().map(str::to_string);
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.chunk().await.map_err(malformed)? {
+            total += chunk.len();
+            if total > limit {
+                return Err(error_response(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("Upload exceeds the {} byte limit", limit),
+                ));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        let text = || String::from_utf8_lossy(&bytes).trim().to_string();
+        match name.as_str() {
+            "file" => upload.file = Some((file_name.unwrap_or_default(), bytes)),
+            "entity" => upload.entity = Some(text()),
+            "mapping" if !text().is_empty() => {
+                upload.mapping = serde_json::from_str(&text()).map_err(|_| {
+                    error_response(StatusCode::BAD_REQUEST, "mapping must be a JSON object of field -> column header")
+                })?;
+            }
+            "dry_run" => {
+                upload.dry_run = match text().as_str() {
+                    "" | "true" | "1" => true,
+                    "false" | "0" => false,
+                    _ => return Err(error_response(StatusCode::BAD_REQUEST, "dry_run must be true or false")),
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(upload)
+}
+
+/// Multipart form of an import upload (documentation only; parsed by [`read_upload`]).
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct ImportUploadForm {
+    entity: ImportEntity,
+    /// .csv or .xlsx, header in the first row
+    #[schema(value_type = String, format = Binary)]
+    file: Vec<u8>,
+    /// JSON object of field -> column header, e.g. {"sku": "Kode Barang"}
+    mapping: Option<String>,
+    /// Defaults to true; false queues the import straight away when every row is valid
+    dry_run: Option<bool>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/imports",
+    request_body(content = ImportUploadForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "File validated; the job holds the valid rows until committed", body = inline(ApiResponse<ImportPreview>)),
+        (status = 400, description = "Unreadable file, unknown entity or incomplete mapping"),
+        (status = 413, description = "File larger than imports.max_file_size")
+    ),
+    tag = "imports"
+)]
+pub async fn upload_import(
+    State(state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    multipart: Multipart,
+) -> Response {
+    let limits = &state.config.imports;
+    let upload = match read_upload(multipart, limits.max_file_size).await {
+        Ok(upload) => upload,
+        Err(response) => return response,
+    };
+
+    let Some(entity) = upload.entity.as_deref().and_then(ImportEntity::parse) else {
+        let allowed: Vec<_> = ImportEntity::ALL.iter().map(|e| e.as_str()).collect();
+        return error_response(StatusCode::BAD_REQUEST, format!("entity must be one of: {}", allowed.join(", ")));
+    };
+    let Some((file_name, bytes)) = upload.file else {
+        return error_response(StatusCode::BAD_REQUEST, "file is required");
+    };
+    info!(entity = entity.as_str(), file_name = %file_name, size = bytes.len(), "Upload import");
+
+    let (format, sheet) = match sheet::read(&file_name, &bytes, limits.max_rows) {
+        Ok(parsed) => parsed,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let mapping = match Mapping::new(entity, &sheet.headers, &upload.mapping) {
+        Ok(mapping) => mapping,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+
+    // Set tenant context (RLS)
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let mut validation = match imports::validate(&mut conn, current.tenant_id, entity, &sheet, &mapping).await {
+        Ok(validation) => validation,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+    let error_rows = validation.error_rows();
+    validation.errors.truncate(imports::MAX_STORED_ERRORS);
+    let status = if upload.dry_run || error_rows > 0 { ImportStatus::Validated } else { ImportStatus::Queued };
+    let sample: Vec<Value> = validation.records.iter().take(SAMPLE_ROWS).map(|r| r.data.clone()).collect();
+
+    let row = sqlx::query(&format!(
+        r#"INSERT INTO import_jobs (tenant_id, entity, file_name, file_format, column_mapping, status,
+                                    total_rows, error_rows, errors, records, created_by)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+           RETURNING {}, jsonb_path_query_array(errors, '$[0 to {}]') AS errors"#,
+        IMPORT_JOB_COLUMNS,
+        JOB_ERROR_LIMIT - 1
+    ))
+    .bind(current.tenant_id)
+    .bind(entity.as_str())
+    .bind(&file_name)
+    .bind(format.as_str())
+    .bind(serde_json::to_value(mapping.to_map()).unwrap_or_default())
+    .bind(status.as_str())
+    .bind(sheet.rows.len() as i32)
+    .bind(error_rows as i32)
+    .bind(serde_json::to_value(&validation.errors).unwrap_or_default())
+    .bind(serde_json::to_value(&validation.records).unwrap_or_default())
+    .bind(current.user_id)
+    .fetch_one(&mut *conn)
+    .await;
+
+    match row {
+        Ok(row) => {
+            let job = job_from_row(&row);
+            if job.status == ImportStatus::Queued {
+                let importer = imports::Importer::new(&state);
+                importer.report_queue_depth().await;
+                let job_id = job.id;
+                state.workers.spawn("import", move |shutdown| imports::run(importer, job_id, shutdown));
+            }
+            let preview = ImportPreview {
+                job,
+                sample,
+                unmapped_columns: mapping.unmapped_columns(),
+                fields: import_fields(entity),
+            };
+            (StatusCode::CREATED, Json(ApiResponse::success(preview))).into_response()
+        }
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/imports",
+    params(
+        ("page" = Option<u32>, Query, description = "Page number"),
+        ("per_page" = Option<u32>, Query, description = "Items per page"),
+        ("cursor" = Option<String>, Query, description = "Opaque cursor from pagination.next_cursor"),
+        ("limit" = Option<u32>, Query, description = "Items per page (keyset pagination, max 200)"),
+        ("count" = Option<CountMode>, Query, description = "Keyset total: none|estimated|exact"),
+        ("search" = Option<String>, Query, description = "Search file names"),
+        ("sort" = Option<String>, Query, description = "Comma-separated, '-' for descending: created_at|finished_at|entity|status|file_name"),
+        ("entity" = Option<String>, Query, description = "Filter by entity; any field also takes field[op]=value with eq|ne|in|gt|gte|lt|lte|ilike|null"),
+        ("status" = Option<String>, Query, description = "Filter by status"),
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return; id is always included"),
+        ("format" = Option<String>, Query, description = "Stream every matching row as csv|xlsx|jsonl instead of a JSON page (also via Accept)"),
+    ),
+    responses(
+        (status = 200, description = "Import history, newest first", body = inline(ApiResponse<ListPage<ImportJob>>)),
+        (status = 400, description = "Unknown field, operator or value")
+    ),
+    tag = "imports"
+)]
+pub async fn list_imports(
+    State(state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    query: ListQuery,
+    fieldset: FieldsetQuery,
+    export: ExportRequest,
+) -> Response {
+    info!("List imports");
+
+    let plan = match IMPORT_JOB_LIST.plan(&query) {
+        Ok(plan) => plan,
+        Err(e) => return e.into_response(),
+    };
+    let fieldset = match fieldset.resolve::<ImportJob>(&[]) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return e.into_response(),
+    };
+
+    // Set tenant context (RLS)
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    if let Some(format) = export.format() {
+        let source = ExportSource { name: "imports", plan, columns: IMPORT_JOB_COLUMNS, map: |row| Ok(job_from_row(row)), relations: &[] };
+        return export::respond(&state, conn, current.tenant_id, format, fieldset, source).await;
+    }
+    match plan.fetch(&mut conn, current.tenant_id, IMPORT_JOB_COLUMNS, |row| Ok(job_from_row(row))).await {
+        Ok(page) => fieldset.page(page).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/imports/fields/{entity}",
+    params(("entity" = ImportEntity, Path, description = "Entity to import")),
+    responses(
+        (status = 200, description = "Columns the entity understands", body = inline(ApiResponse<Vec<ImportField>>)),
+        (status = 404, description = "Unknown entity")
+    ),
+    tag = "imports"
+)]
+pub async fn get_import_fields(Path(entity): Path<String>) -> Response {
+    match ImportEntity::parse(&entity) {
+        Some(entity) => Json(ApiResponse::success(import_fields(entity))).into_response(),
+        None => error_response(StatusCode::NOT_FOUND, "Unknown import entity"),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/imports/{id}",
+    params(("id" = uuid::Uuid, Path, description = "Import job ID")),
+    responses(
+        (status = 200, description = "Import job with its first 100 row errors", body = inline(ApiResponse<ImportJob>)),
+        (status = 404, description = "Import not found")
+    ),
+    tag = "imports"
+)]
+pub async fn get_import(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Path(id): Path<Uuid>,
+) -> Response {
+    info!("Get import {}", id);
+
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let row = sqlx::query(&format!(
+        r#"SELECT {}, jsonb_path_query_array(errors, '$[0 to {}]') AS errors
+           FROM import_jobs WHERE id = $1 AND tenant_id = $2"#,
+        IMPORT_JOB_COLUMNS,
+        JOB_ERROR_LIMIT - 1
+    ))
+    .bind(id)
+    .bind(current.tenant_id)
+    .fetch_optional(&mut *conn)
+    .await;
+
+    match row {
+        Ok(Some(row)) => Json(ApiResponse::success(job_from_row(&row))).into_response(),
+        Ok(None) => error_response(StatusCode::NOT_FOUND, "Import not found"),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/imports/{id}/commit",
+    params(("id" = uuid::Uuid, Path, description = "Import job ID")),
+    responses(
+        (status = 202, description = "Import queued; poll the job for its outcome", body = inline(ApiResponse<ImportJob>)),
+        (status = 400, description = "The file has invalid rows; fix them and upload again"),
+        (status = 404, description = "Import not found"),
+        (status = 409, description = "Import was already committed")
+    ),
+    tag = "imports"
+)]
+pub async fn commit_import(
+    State(state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Path(id): Path<Uuid>,
+) -> Response {
+    info!("Commit import {}", id);
+
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    // Only a clean, validated job moves to queued, so concurrent commits queue it once
+    let queued = sqlx::query(&format!(
+        r#"UPDATE import_jobs SET status = 'queued'
+           WHERE id = $1 AND tenant_id = $2 AND status = 'validated' AND error_rows = 0
+           RETURNING {}"#,
+        IMPORT_JOB_COLUMNS
+    ))
+    .bind(id)
+    .bind(current.tenant_id)
+    .fetch_optional(&mut *conn)
+    .await;
+
+    match queued {
+        Ok(Some(row)) => {
+            let job = job_from_row(&row);
+            let importer = imports::Importer::new(&state);
+            importer.report_queue_depth().await;
+            state.workers.spawn("import", move |shutdown| imports::run(importer, id, shutdown));
+            (StatusCode::ACCEPTED, Json(ApiResponse::success(job))).into_response()
+        }
+        Ok(None) => {
+            let current_state = sqlx::query("SELECT status, error_rows FROM import_jobs WHERE id = $1 AND tenant_id = $2")
+                .bind(id)
+                .bind(current.tenant_id)
+                .fetch_optional(&mut *conn)
+                .await;
+            match current_state {
+                Ok(Some(row)) if row.get::<String, _>("status") != ImportStatus::Validated.as_str() => {
+                    error_response(StatusCode::CONFLICT, "Import was already committed")
+                }
+                Ok(Some(row)) => error_response(
+                    StatusCode::BAD_REQUEST,
+                    format!("{} rows have errors; fix them and upload the file again", row.get::<i32, _>("error_rows")),
+                ),
+                Ok(None) => error_response(StatusCode::NOT_FOUND, "Import not found"),
+                Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+            }
+        }
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/imports/{id}/errors",
+    params(("id" = uuid::Uuid, Path, description = "Import job ID")),
+    responses(
+        (status = 200, description = "Row errors as CSV (row, column, value, message)", content_type = "text/csv", body = String),
+        (status = 404, description = "Import not found")
+    ),
+    tag = "imports"
+)]
+pub async fn download_import_errors(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Path(id): Path<Uuid>,
+) -> Response {
+    info!("Download import errors {}", id);
+
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let errors = sqlx::query_scalar::<_, Value>("SELECT errors FROM import_jobs WHERE id = $1 AND tenant_id = $2")
+        .bind(id)
+        .bind(current.tenant_id)
+        .fetch_optional(&mut *conn)
+        .await;
+
+    let errors: Vec<ImportRowError> = match errors {
+        Ok(Some(errors)) => serde_json::from_value(errors).unwrap_or_default(),
+        Ok(None) => return error_response(StatusCode::NOT_FOUND, "Import not found"),
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+
+    let mut body = sheet::csv_record(',', ["row", "column", "value", "message"]);
+    for error in &errors {
+        body.push_str(&sheet::csv_record(',', [
+            error.row.to_string().as_str(),
+            error.column.as_deref().unwrap_or(""),
+            error.value.as_deref().unwrap_or(""),
+            error.message.as_str(),
+        ]));
+    }
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"import-{}-errors.csv\"", id)),
+        ],
+        body,
+    )
+        .into_response()
+}
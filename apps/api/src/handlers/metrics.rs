@@ -0,0 +1,25 @@
+use axum::{extract::State, http::header, response::IntoResponse};
+use std::sync::Arc;
+
+use crate::state::AppState;
+
+/// Prometheus scrape endpoint
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    responses(
+        (status = 200, description = "Metrics in Prometheus text format", body = String, content_type = "text/plain")
+    ),
+    tag = "health"
+)]
+pub async fn metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    // Pool gauges are sampled at scrape time rather than tracked on every checkout
+    state
+        .metrics
+        .set_db_pool(state.db_pool.size(), state.db_pool.num_idle());
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
@@ -1,9 +1,31 @@
 pub mod auth;
 pub mod health;
+pub mod metrics;
 pub mod tenant;
 pub mod user;
 pub mod crm;
 pub mod inventory;
 pub mod procurement;
 pub mod accounting;
+pub mod exchange_rates;
 pub mod hrm;
+pub mod imports;
+pub mod search;
+pub mod attachments;
+pub mod notifications;
+pub mod email_templates;
+pub mod sequences;
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use core_domain::DomainError;
+use shared_types::{ApiResponse, Locale};
+
+/// A broken business rule, with its code and a message in `locale`.
+pub(crate) fn domain_error(e: &DomainError, locale: Locale) -> Response {
+    let status = StatusCode::from_u16(e.http_status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    (status, Json(ApiResponse::<()>::error(e.message(locale)).with_code(e.error_code()))).into_response()
+}
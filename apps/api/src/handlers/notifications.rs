@@ -0,0 +1,349 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures::{Stream, StreamExt};
+use serde_json::json;
+use shared_types::{
+    ApiResponse, Notification, NotificationKind, NotificationPreference, UnreadCount,
+    UpdateNotificationPreferencesRequest,
+};
+use sqlx::Row;
+use std::{convert::Infallible, sync::Arc};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    fieldset::FieldsetQuery,
+    list_query::{Field, FieldType, ListPage, ListQuery, ListSpec},
+    middleware::{auth_middleware::CurrentUser, db_conn::DbConn},
+    notifications::{notification_from_row, NOTIFICATION_COLUMNS},
+    state::AppState,
+};
+
+static NOTIFICATION_LIST: ListSpec = ListSpec {
+    from: "notifications",
+    tenant_column: "tenant_id",
+    base_filter: Some("in_app = true"),
+    id_column: "id",
+    fields: &[
+        Field::new("kind", "kind", FieldType::Text),
+        Field::new("read", "(read_at IS NOT NULL)", FieldType::Bool),
+        Field::new("read_at", "read_at", FieldType::Timestamp).nullable(),
+        Field::new("created_at", "created_at", FieldType::Timestamp).sortable(),
+    ],
+    search: &["title", "body"],
+    default_sort: "-created_at",
+    aliases: &[],
+};
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(ApiResponse::<()>::error(message.into()))).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/notifications",
+    params(
+        ("page" = Option<u32>, Query, description = "Page number"),
+        ("per_page" = Option<u32>, Query, description = "Items per page"),
+        ("cursor" = Option<String>, Query, description = "Opaque cursor from pagination.next_cursor"),
+        ("limit" = Option<u32>, Query, description = "Items per page (keyset pagination, max 200)"),
+        ("count" = Option<CountMode>, Query, description = "Keyset total: none|estimated|exact"),
+        ("search" = Option<String>, Query, description = "Search titles and bodies"),
+        ("sort" = Option<String>, Query, description = "created_at or -created_at (default)"),
+        ("read" = Option<bool>, Query, description = "false for unread only; any field also takes field[op]=value with eq|ne|in|gt|gte|lt|lte|ilike|null"),
+        ("kind" = Option<NotificationKind>, Query, description = "Filter by kind"),
+        ("fields" = Option<String>, Query, description = "Comma-separated fields to return; id is always included"),
+    ),
+    responses(
+        (status = 200, description = "The caller's inbox, newest first", body = inline(ApiResponse<ListPage<Notification>>)),
+        (status = 400, description = "Unknown field, operator or value")
+    ),
+    tag = "notifications"
+)]
+pub async fn list_notifications(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    query: ListQuery,
+    fieldset: FieldsetQuery,
+) -> Response {
+    info!("List notifications");
+
+    let plan = match NOTIFICATION_LIST.plan(&query) {
+        Ok(plan) => plan.scoped("user_id", current.user_id),
+        Err(e) => return e.into_response(),
+    };
+    let fieldset = match fieldset.resolve::<Notification>(&[]) {
+        Ok(fieldset) => fieldset,
+        Err(e) => return e.into_response(),
+    };
+
+    // Set tenant context (RLS)
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    match plan.fetch(&mut conn, current.tenant_id, NOTIFICATION_COLUMNS, |row| Ok(notification_from_row(row))).await {
+        Ok(page) => fieldset.page(page).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/notifications/unread-count",
+    responses((status = 200, description = "Unread notifications in the caller's inbox", body = inline(ApiResponse<UnreadCount>))),
+    tag = "notifications"
+)]
+pub async fn unread_count(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+) -> Response {
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let unread = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM notifications WHERE tenant_id = $1 AND user_id = $2 AND in_app AND read_at IS NULL",
+    )
+    .bind(current.tenant_id)
+    .bind(current.user_id)
+    .fetch_one(&mut *conn)
+    .await;
+
+    match unread {
+        Ok(unread) => Json(ApiResponse::success(UnreadCount { unread })).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/notifications/{id}/read",
+    params(("id" = uuid::Uuid, Path, description = "Notification ID")),
+    responses(
+        (status = 200, description = "Notification marked as read", body = inline(ApiResponse<Notification>)),
+        (status = 404, description = "Notification not found")
+    ),
+    tag = "notifications"
+)]
+pub async fn mark_read(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Path(id): Path<Uuid>,
+) -> Response {
+    set_read(current, &mut conn, id, true).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/notifications/{id}/unread",
+    params(("id" = uuid::Uuid, Path, description = "Notification ID")),
+    responses(
+        (status = 200, description = "Notification marked as unread", body = inline(ApiResponse<Notification>)),
+        (status = 404, description = "Notification not found")
+    ),
+    tag = "notifications"
+)]
+pub async fn mark_unread(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Path(id): Path<Uuid>,
+) -> Response {
+    set_read(current, &mut conn, id, false).await
+}
+
+async fn set_read(current: Extension<CurrentUser>, conn: &mut sqlx::PgConnection, id: Uuid, read: bool) -> Response {
+    info!("Mark notification {} as {}", id, if read { "read" } else { "unread" });
+
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    // Marking twice keeps the first read_at
+    let row = sqlx::query(&format!(
+        r#"UPDATE notifications SET read_at = CASE WHEN $4 THEN COALESCE(read_at, NOW()) END
+           WHERE id = $1 AND tenant_id = $2 AND user_id = $3 AND in_app
+           RETURNING {}"#,
+        NOTIFICATION_COLUMNS
+    ))
+    .bind(id)
+    .bind(current.tenant_id)
+    .bind(current.user_id)
+    .bind(read)
+    .fetch_optional(&mut *conn)
+    .await;
+
+    match row {
+        Ok(Some(row)) => Json(ApiResponse::success(notification_from_row(&row))).into_response(),
+        Ok(None) => error_response(StatusCode::NOT_FOUND, "Notification not found"),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/notifications/read-all",
+    responses((status = 200, description = "Every unread notification marked as read; returns how many", body = inline(ApiResponse<serde_json::Value>))),
+    tag = "notifications"
+)]
+pub async fn mark_all_read(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+) -> Response {
+    info!("Mark all notifications as read");
+
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let updated = sqlx::query(
+        r#"UPDATE notifications SET read_at = NOW()
+           WHERE tenant_id = $1 AND user_id = $2 AND in_app AND read_at IS NULL"#,
+    )
+    .bind(current.tenant_id)
+    .bind(current.user_id)
+    .execute(&mut *conn)
+    .await;
+
+    match updated {
+        Ok(done) => Json(ApiResponse::success(json!({ "updated": done.rows_affected() }))).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/notifications/preferences",
+    responses((status = 200, description = "Channels for every notification kind", body = inline(ApiResponse<Vec<NotificationPreference>>))),
+    tag = "notifications"
+)]
+pub async fn get_preferences(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+) -> Response {
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    match load_preferences(&mut conn, current.tenant_id, current.user_id).await {
+        Ok(preferences) => Json(ApiResponse::success(preferences)).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/notifications/preferences",
+    request_body = UpdateNotificationPreferencesRequest,
+    responses((status = 200, description = "Preferences saved; returns every kind", body = inline(ApiResponse<Vec<NotificationPreference>>))),
+    tag = "notifications"
+)]
+pub async fn update_preferences(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Json(req): Json<UpdateNotificationPreferencesRequest>,
+) -> Response {
+    info!("Update notification preferences");
+
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let kinds: Vec<&str> = req.preferences.iter().map(|p| p.kind.as_str()).collect();
+    let in_app: Vec<bool> = req.preferences.iter().map(|p| p.in_app).collect();
+    let email: Vec<bool> = req.preferences.iter().map(|p| p.email).collect();
+    let saved = sqlx::query(
+        r#"INSERT INTO notification_preferences (tenant_id, user_id, kind, in_app, email)
+           SELECT $1, $2, kind, in_app, email FROM UNNEST($3::text[], $4::bool[], $5::bool[]) AS p(kind, in_app, email)
+           ON CONFLICT (tenant_id, user_id, kind)
+           DO UPDATE SET in_app = EXCLUDED.in_app, email = EXCLUDED.email, updated_at = NOW()"#,
+    )
+    .bind(current.tenant_id)
+    .bind(current.user_id)
+    .bind(&kinds)
+    .bind(&in_app)
+    .bind(&email)
+    .execute(&mut *conn)
+    .await;
+    if let Err(e) = saved {
+        return Json(ApiResponse::<()>::error(format!("{}", e))).into_response();
+    }
+
+    match load_preferences(&mut conn, current.tenant_id, current.user_id).await {
+        Ok(preferences) => Json(ApiResponse::success(preferences)).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+/// Preferences for every kind, with defaults for kinds never set.
+async fn load_preferences(
+    conn: &mut sqlx::PgConnection,
+    tenant_id: Uuid,
+    user_id: Uuid,
+) -> Result<Vec<NotificationPreference>, sqlx::Error> {
+    let rows = sqlx::query("SELECT kind, in_app, email FROM notification_preferences WHERE tenant_id = $1 AND user_id = $2")
+        .bind(tenant_id)
+        .bind(user_id)
+        .fetch_all(conn)
+        .await?;
+
+    Ok(NotificationKind::ALL
+        .into_iter()
+        .map(|kind| {
+            let saved = rows.iter().find(|row| row.get::<String, _>("kind") == kind.as_str());
+            NotificationPreference {
+                kind,
+                in_app: saved.is_none_or(|row| row.get("in_app")),
+                email: saved.is_some_and(|row| row.get("email")),
+            }
+        })
+        .collect())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/notifications/stream",
+    responses(
+        (status = 200, description = "Server-Sent Events: a `notification` event with a Notification for each new in-app notification", content_type = "text/event-stream", body = String)
+    ),
+    tag = "notifications"
+)]
+pub async fn stream_notifications(
+    State(state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    info!("Open notification stream");
+
+    // Open streams end when shutdown starts instead of holding up the drain
+    let shutdown = state.workers.shutdown_token();
+    let events = state
+        .notification_hub
+        .subscribe(current.tenant_id, current.user_id)
+        .map(|notification| {
+            let event = Event::default().event("notification").id(notification.id.to_string());
+            Ok(event.json_data(&*notification).unwrap_or_else(|_| Event::default().comment("unencodable")))
+        })
+        .take_until(shutdown.cancelled_owned());
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,48 @@
+use axum::{
+    extract::{Extension, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use shared_types::ApiResponse;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::{
+    middleware::{auth_middleware::CurrentUser, db_conn::DbConn},
+    search::Search,
+    state::AppState,
+};
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/search",
+    params(
+        ("q" = String, Query, description = "Text to look for: names, codes, SKUs, barcodes, emails, PO and journal numbers (2-100 characters)"),
+        ("types" = Option<String>, Query, description = "Comma-separated record types: company|contact|product|vendor|purchase_order|journal_entry"),
+        ("limit" = Option<u32>, Query, description = "Maximum hits (default 20, max 50)"),
+    ),
+    responses(
+        (status = 200, description = "Hits across modules, best first; types the user may not read are left out", body = inline(ApiResponse<Vec<shared_types::SearchHit>>)),
+        (status = 400, description = "Query too short or long, or unknown type")
+    ),
+    tag = "search"
+)]
+pub async fn search(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    search: Search,
+) -> Response {
+    info!("Global search");
+
+    // Set tenant context (RLS)
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    match search.run(&mut conn, current.tenant_id, |permission| current.can(permission)).await {
+        Ok(hits) => Json(ApiResponse::success(hits)).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
@@ -0,0 +1,115 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use shared_types::{ApiResponse, DocumentSequence, DocumentType, UpdateDocumentSequenceRequest};
+use sqlx::Acquire;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::{
+    middleware::{auth_middleware::CurrentUser, db_conn::DbConn},
+    sequences::{self, Pattern},
+    state::AppState,
+};
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(ApiResponse::<()>::error(message.into()))).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/tenants/current/sequences",
+    responses(
+        (status = 200, description = "Numbering of every document type, with the next number for today", body = inline(ApiResponse<Vec<DocumentSequence>>))
+    ),
+    tag = "tenants"
+)]
+pub async fn list_sequences(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+) -> Response {
+    info!("List document sequences");
+
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let today = Utc::now().date_naive();
+    let mut described = Vec::with_capacity(DocumentType::ALL.len());
+    for document_type in DocumentType::ALL {
+        let sequence = match sequences::load(&mut conn, current.tenant_id, document_type).await {
+            Ok(sequence) => sequence,
+            Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+        };
+        let last = match sequences::last_value(&mut conn, current.tenant_id, &sequence, today).await {
+            Ok(last) => last,
+            Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+        };
+        described.push(sequence.describe(last, today));
+    }
+
+    Json(ApiResponse::success(described)).into_response()
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/tenants/current/sequences/{document_type}",
+    params(("document_type" = DocumentType, Path, description = "Document type")),
+    request_body = UpdateDocumentSequenceRequest,
+    responses(
+        (status = 200, description = "Pattern and reset rule replaced; numbering continues from the last number issued", body = inline(ApiResponse<DocumentSequence>)),
+        (status = 400, description = "Malformed pattern, or one whose numbers could repeat under the reset rule"),
+        (status = 403, description = "Requires tenants:manage")
+    ),
+    tag = "tenants"
+)]
+pub async fn update_sequence(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    Path(document_type): Path<DocumentType>,
+    Json(req): Json<UpdateDocumentSequenceRequest>,
+) -> Response {
+    info!("Update {} sequence", document_type.as_str());
+
+    if !current.can("tenants:manage") {
+        return error_response(StatusCode::FORBIDDEN, "Requires tenants:manage");
+    }
+    let pattern = match Pattern::parse(&req.pattern) {
+        Ok(pattern) => pattern,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, format!("Invalid pattern: {}", e)),
+    };
+    if let Err(e) = pattern.check(req.reset_rule) {
+        return error_response(StatusCode::BAD_REQUEST, format!("Invalid pattern: {}", e));
+    }
+
+    let mut tx = match conn.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("Failed to start transaction: {}", e))).into_response(),
+    };
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *tx)
+        .await;
+
+    let today = Utc::now().date_naive();
+    let sequence = match sequences::save(&mut tx, current.tenant_id, document_type, &pattern, req.reset_rule, today).await {
+        Ok(sequence) => sequence,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+    let last = match sequences::last_value(&mut tx, current.tenant_id, &sequence, today).await {
+        Ok(last) => last,
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    };
+    if let Err(e) = tx.commit().await {
+        return Json(ApiResponse::<()>::error(format!("Failed to commit transaction: {}", e))).into_response();
+    }
+
+    Json(ApiResponse::success(sequence.describe(last, today))).into_response()
+}
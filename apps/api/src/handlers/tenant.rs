@@ -1,11 +1,21 @@
-use axum::{extract::{State, Extension}, Json};
-use shared_types::ApiResponse;
+use axum::{extract::{State, Extension}, http::StatusCode, response::{IntoResponse, Response}, Json};
+use lettre::message::Mailbox;
+use serde_json::{json, Map};
+use shared_types::{ApiResponse, EmailBranding, Invitation, InviteUserRequest};
+use sqlx::Row;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{info, warn};
+use validator::Validate;
 
-use crate::{state::AppState, middleware::{auth_middleware::CurrentUser, db_conn::DbConn}};
+use crate::{state::AppState, email::{EmailTemplate, TenantEmailSettings}, extractors::RequestLocale, middleware::{auth_middleware::CurrentUser, db_conn::DbConn}};
 
 /// Get current tenant information
+#[utoipa::path(
+    get,
+    path = "/api/v1/tenants/current",
+    responses((status = 200, description = "Not implemented yet", body = ApiResponse<()>)),
+    tag = "tenants"
+)]
 pub async fn get_current_tenant(
     State(_state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
@@ -20,6 +30,12 @@ pub async fn get_current_tenant(
 }
 
 /// Update current tenant
+#[utoipa::path(
+    put,
+    path = "/api/v1/tenants/current",
+    responses((status = 200, description = "Not implemented yet", body = ApiResponse<()>)),
+    tag = "tenants"
+)]
 pub async fn update_current_tenant(
     State(_state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
@@ -34,6 +50,12 @@ pub async fn update_current_tenant(
 }
 
 /// Get tenant members
+#[utoipa::path(
+    get,
+    path = "/api/v1/tenants/members",
+    responses((status = 200, description = "Not implemented yet", body = ApiResponse<()>)),
+    tag = "tenants"
+)]
 pub async fn get_members(
     State(_state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
@@ -47,16 +69,198 @@ pub async fn get_members(
     Json(ApiResponse::<()>::error("Not implemented yet".to_string()))
 }
 
-/// Invite user to tenant
+/// Invite someone to join the current tenant
+#[utoipa::path(
+    post,
+    path = "/api/v1/tenants/invite",
+    request_body = InviteUserRequest,
+    responses(
+        (status = 201, description = "Invitation created; its token is valid for 7 days", body = inline(ApiResponse<Invitation>)),
+        (status = 409, description = "Already a member or already invited", body = ApiResponse<()>)
+    ),
+    tag = "tenants"
+)]
 pub async fn invite_user(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     current: Extension<CurrentUser>,
     DbConn(mut conn): DbConn,
-) -> Json<ApiResponse<()>> {
+    RequestLocale(locale): RequestLocale,
+    Json(req): Json<InviteUserRequest>,
+) -> Response {
     info!("Invite user to tenant");
+
+    if let Err(e) = req.validate() {
+        return Json(ApiResponse::<()>::invalid(&e, locale)).into_response();
+    }
+
     let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
         .bind(current.tenant_id.to_string())
         .execute(&mut *conn)
         .await;
-    Json(ApiResponse::<()>::error("Not implemented yet".to_string()))
+
+    let taken = sqlx::query_scalar::<_, bool>(
+        r#"SELECT EXISTS (SELECT 1 FROM tenant_memberships m JOIN users u ON u.id = m.user_id
+                          WHERE m.tenant_id = $1 AND m.is_active AND lower(u.email) = lower($2))
+               OR EXISTS (SELECT 1 FROM invitations
+                          WHERE tenant_id = $1 AND lower(email) = lower($2)
+                            AND accepted_at IS NULL AND expires_at > NOW())"#,
+    )
+    .bind(current.tenant_id)
+    .bind(&req.email)
+    .fetch_one(&mut *conn)
+    .await;
+    match taken {
+        Ok(false) => {}
+        Ok(true) => {
+            return (StatusCode::CONFLICT, Json(ApiResponse::<()>::error("Already a member or already invited".to_string())))
+                .into_response()
+        }
+        Err(e) => return Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+
+    let row = sqlx::query(
+        r#"INSERT INTO invitations (tenant_id, email, first_name, last_name, role, token, invited_by, expires_at)
+           VALUES ($1, $2, $3, $4, $5, $6, $7, NOW() + INTERVAL '7 days')
+           RETURNING id, tenant_id, email, first_name, last_name, role, token, invited_by,
+                     expires_at, accepted_at, created_at"#,
+    )
+    .bind(current.tenant_id)
+    .bind(&req.email)
+    .bind(&req.first_name)
+    .bind(&req.last_name)
+    .bind(&req.role)
+    .bind(state.jwt_service.generate_refresh_token())
+    .bind(current.user_id)
+    .fetch_one(&mut *conn)
+    .await;
+
+    match row {
+        Ok(row) => {
+            let invitation = Invitation {
+                id: row.get("id"),
+                tenant_id: row.get("tenant_id"),
+                email: row.get("email"),
+                first_name: row.get("first_name"),
+                last_name: row.get("last_name"),
+                role: row.get("role"),
+                token: row.get("token"),
+                invited_by: row.get("invited_by"),
+                expires_at: row.get("expires_at"),
+                accepted_at: row.get("accepted_at"),
+                created_at: row.get("created_at"),
+            };
+            if let Err(e) = send_invitation(&state, &mut conn, &current, &invitation).await {
+                warn!(invitation_id = %invitation.id, "Failed to prepare invitation email: {}", e);
+            }
+            (StatusCode::CREATED, Json(ApiResponse::success(invitation))).into_response()
+        }
+        Err(e) => Json(ApiResponse::<()>::error(format!("Failed to create invitation: {}", e))).into_response(),
+    }
+}
+
+/// Email the invitation link in the background; the invitation stands even if
+/// the email never arrives, and can be shared by hand.
+async fn send_invitation(
+    state: &Arc<AppState>,
+    conn: &mut sqlx::PgConnection,
+    current: &CurrentUser,
+    invitation: &Invitation,
+) -> Result<(), sqlx::Error> {
+    let tenant = TenantEmailSettings::load(&mut *conn, current.tenant_id).await?;
+    let inviter: String = sqlx::query_scalar(
+        "SELECT COALESCE(NULLIF(TRIM(CONCAT(first_name, ' ', last_name)), ''), email) FROM users WHERE id = $1",
+    )
+    .bind(current.user_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let mailer = state.mailer.clone();
+    let locale = tenant.locale.unwrap_or(mailer.default_locale());
+    let brand = tenant.brand(locale);
+    let mut variables = Map::new();
+    variables.insert("name".into(), json!(invitation.first_name));
+    variables.insert("inviter_name".into(), json!(inviter));
+    variables.insert("invite_url".into(), json!(mailer.link(&format!("/invitations/accept?token={}", invitation.token))));
+    variables.insert("expires_at".into(), json!(invitation.expires_at.format("%Y-%m-%d").to_string()));
+    variables.insert("role".into(), json!(invitation.role));
+    let (id, email, name) = (invitation.id, invitation.email.clone(), format!("{} {}", invitation.first_name, invitation.last_name));
+
+    state.workers.spawn("email", move |_| async move {
+        let to = match email.parse() {
+            Ok(address) => Mailbox::new(Some(name), address),
+            Err(e) => return warn!(invitation_id = %id, "Invitation has an invalid email address: {}", e),
+        };
+        if let Err(e) = mailer.send_template(to, EmailTemplate::Invitation, locale, &brand, &variables).await {
+            warn!(invitation_id = %id, "Failed to send invitation email: {}", e);
+        }
+    });
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/tenants/current/branding",
+    responses((status = 200, description = "Email branding of the current tenant; unset fields use the defaults", body = inline(ApiResponse<EmailBranding>))),
+    tag = "tenants"
+)]
+pub async fn get_branding(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+) -> Response {
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    match TenantEmailSettings::load(&mut conn, current.tenant_id).await {
+        Ok(tenant) => Json(ApiResponse::success(tenant.branding)).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/tenants/current/branding",
+    request_body = EmailBranding,
+    responses(
+        (status = 200, description = "Branding replaced", body = inline(ApiResponse<EmailBranding>)),
+        (status = 400, description = "Invalid logo URL, colour or footer"),
+        (status = 403, description = "Requires tenants:manage")
+    ),
+    tag = "tenants"
+)]
+pub async fn update_branding(
+    State(_state): State<Arc<AppState>>,
+    current: Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    RequestLocale(locale): RequestLocale,
+    Json(branding): Json<EmailBranding>,
+) -> Response {
+    info!("Update tenant branding");
+
+    if !current.can("tenants:manage") {
+        return (StatusCode::FORBIDDEN, Json(ApiResponse::<()>::error("Requires tenants:manage".to_string()))).into_response();
+    }
+    if let Err(e) = branding.validate() {
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::invalid(&e, locale))).into_response();
+    }
+
+    let _ = sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(current.tenant_id.to_string())
+        .execute(&mut *conn)
+        .await;
+
+    let updated = sqlx::query(
+        "UPDATE tenants SET settings = jsonb_set(settings, '{branding}', $2), updated_at = NOW() WHERE id = $1",
+    )
+    .bind(current.tenant_id)
+    .bind(json!(branding))
+    .execute(&mut *conn)
+    .await;
+
+    match updated {
+        Ok(_) => Json(ApiResponse::success(branding)).into_response(),
+        Err(e) => Json(ApiResponse::<()>::error(format!("{}", e))).into_response(),
+    }
 }
@@ -1,12 +1,32 @@
 use axum::{extract::{State}, Json};
-use shared_types::{ApiResponse, User};
+use shared_types::{ApiResponse, Locale, UpdateProfileRequest, User};
 use std::sync::Arc;
 use tracing::info;
-use sqlx::Row;
+use sqlx::{postgres::PgRow, Row};
+use validator::Validate;
 
-use crate::{state::AppState, middleware::{auth_middleware::CurrentUser, db_conn::DbConn}};
+use crate::{state::AppState, extractors::RequestLocale, middleware::{auth_middleware::CurrentUser, db_conn::DbConn}};
+
+fn user_from_row(row: &PgRow) -> User {
+    User {
+        base: shared_types::BaseEntity { id: row.get("id"), created_at: row.get("created_at"), updated_at: row.get("updated_at") },
+        email: row.get("email"),
+        first_name: row.try_get("first_name").unwrap_or(None),
+        last_name: row.try_get("last_name").unwrap_or(None),
+        is_active: row.get("is_active"),
+        email_verified_at: row.try_get("email_verified_at").unwrap_or(None),
+        last_login_at: row.try_get("last_login_at").unwrap_or(None),
+        locale: row.try_get::<Option<String>, _>("locale").unwrap_or(None).as_deref().and_then(Locale::parse),
+    }
+}
 
 /// Get user profile
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/profile",
+    responses((status = 200, description = "The signed-in user", body = inline(ApiResponse<User>))),
+    tag = "users"
+)]
 pub async fn get_profile(
     State(_state): State<Arc<AppState>>,
     current: axum::extract::Extension<CurrentUser>,
@@ -22,7 +42,8 @@ pub async fn get_profile(
 
     // Query profile data using the same connection
     let row = sqlx::query(
-        r#"SELECT id, email, first_name, last_name, is_active, email_verified_at, last_login_at
+        r#"SELECT id, email, first_name, last_name, is_active, email_verified_at, last_login_at, locale,
+                  created_at, updated_at
             FROM users WHERE id = $1 LIMIT 1"#
     )
     .bind(current.user_id)
@@ -30,31 +51,66 @@ pub async fn get_profile(
     .await;
 
     match row {
-        Ok(row) => {
-            let user = User {
-                base: shared_types::BaseEntity { id: row.get("id"), created_at: chrono::Utc::now(), updated_at: chrono::Utc::now() },
-                email: row.get("email"),
-                first_name: row.try_get("first_name").ok(),
-                last_name: row.try_get("last_name").ok(),
-                is_active: row.get("is_active"),
-                email_verified_at: row.try_get("email_verified_at").ok(),
-                last_login_at: row.try_get("last_login_at").ok(),
-            };
-            Json(ApiResponse::success(user))
-        }
+        Ok(row) => Json(ApiResponse::success(user_from_row(&row))),
         Err(e) => Json(ApiResponse::<User>::error_typed(format!("{}", e)))
     }
 }
 
 /// Update user profile
+#[utoipa::path(
+    put,
+    path = "/api/v1/users/profile",
+    request_body = UpdateProfileRequest,
+    responses(
+        (status = 200, description = "Profile updated", body = inline(ApiResponse<User>)),
+        (status = 422, description = "Validation error", body = ApiResponse<()>)
+    ),
+    tag = "users"
+)]
 pub async fn update_profile(
     State(_state): State<Arc<AppState>>,
-) -> Json<ApiResponse<()>> {
-    info!("Update user profile");
-    Json(ApiResponse::<()>::error("Not implemented yet".to_string()))
+    current: axum::extract::Extension<CurrentUser>,
+    DbConn(mut conn): DbConn,
+    RequestLocale(locale): RequestLocale,
+    Json(req): Json<UpdateProfileRequest>,
+) -> Json<ApiResponse<User>> {
+    info!("Update user profile for {}", current.email);
+
+    if let Err(e) = req.validate() {
+        return Json(ApiResponse::<User>::invalid(&e, locale));
+    }
+
+    let row = sqlx::query(
+        r#"UPDATE users SET
+               first_name = COALESCE($2, first_name),
+               last_name = COALESCE($3, last_name),
+               locale = COALESCE($4, locale),
+               updated_at = NOW()
+           WHERE id = $1
+           RETURNING id, email, first_name, last_name, is_active, email_verified_at, last_login_at, locale,
+                     created_at, updated_at"#
+    )
+    .bind(current.user_id)
+    .bind(&req.first_name)
+    .bind(&req.last_name)
+    .bind(req.locale.map(|l| l.as_str()))
+    .fetch_one(&mut *conn)
+    .await;
+
+    match row {
+        Ok(row) => Json(ApiResponse::success(user_from_row(&row))),
+        Err(e) => Json(ApiResponse::<User>::error_typed(format!("{}", e)))
+    }
 }
 
 /// Change user password
+#[utoipa::path(
+    post,
+    path = "/api/v1/users/change-password",
+    request_body = ChangePasswordRequest,
+    responses((status = 200, description = "Not implemented yet", body = ApiResponse<()>)),
+    tag = "users"
+)]
 pub async fn change_password(
     State(_state): State<Arc<AppState>>,
 ) -> Json<ApiResponse<()>> {
@@ -0,0 +1,864 @@
+//! Field definitions, validation and inserts for each importable entity.
+
+use std::collections::{HashMap, HashSet};
+
+use rust_decimal::Decimal;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use shared_types::{ImportEntity, ImportRowError, RateType};
+use sqlx::{PgConnection, Row as _};
+use uuid::Uuid;
+
+use super::sheet::Sheet;
+use super::{row_failure, Batch, FieldDef, Mapping, Record, Validation};
+use crate::exchange_rates::{self, NewRate};
+
+const PRODUCT_FIELDS: &[FieldDef] = &[
+    FieldDef::required("sku", "Unique product code"),
+    FieldDef::required("name", "Product name"),
+    FieldDef::optional("description", "Free text"),
+    FieldDef::optional("category", "Name of an existing product category"),
+    FieldDef::optional("unit_of_measure", "Defaults to pcs"),
+    FieldDef::optional("cost_price", "Number, defaults to 0"),
+    FieldDef::optional("selling_price", "Number, defaults to 0"),
+    FieldDef::optional("minimum_stock", "Whole number, defaults to 0"),
+    FieldDef::optional("barcode", "Free text"),
+    FieldDef::optional("weight", "Number"),
+];
+
+const COMPANY_FIELDS: &[FieldDef] = &[
+    FieldDef::required("name", "Company name"),
+    FieldDef::optional("website", "Free text"),
+    FieldDef::optional("email", "Email address"),
+    FieldDef::optional("phone", "Free text"),
+    FieldDef::optional("tags", "Separated by ;"),
+];
+
+const CONTACT_FIELDS: &[FieldDef] = &[
+    FieldDef::required("first_name", "First name"),
+    FieldDef::required("last_name", "Last name"),
+    FieldDef::optional("email", "Email address"),
+    FieldDef::optional("phone", "Free text"),
+    FieldDef::optional("position", "Job title"),
+    FieldDef::optional("notes", "Free text"),
+    FieldDef::optional("company", "Company name; created if it doesn't exist yet"),
+];
+
+const VENDOR_FIELDS: &[FieldDef] = &[
+    FieldDef::required("code", "Unique vendor code"),
+    FieldDef::required("name", "Vendor name"),
+    FieldDef::optional("contact_person", "Free text"),
+    FieldDef::optional("email", "Email address"),
+    FieldDef::optional("phone", "Free text"),
+    FieldDef::optional("tax_number", "NPWP or other tax id"),
+    FieldDef::optional("payment_terms", "e.g. NET30"),
+    FieldDef::optional("currency", "ISO 4217 code, defaults to IDR"),
+    FieldDef::optional("credit_limit", "Number"),
+];
+
+const ACCOUNT_FIELDS: &[FieldDef] = &[
+    FieldDef::required("code", "Unique account code"),
+    FieldDef::required("name", "Account name"),
+    FieldDef::required("account_type", "asset, liability, equity, revenue or expense"),
+    FieldDef::optional("account_subtype", "e.g. current_asset"),
+    FieldDef::optional("parent_code", "Code of the parent account, existing or in this file"),
+    FieldDef::optional("description", "Free text"),
+    FieldDef::optional("balance_type", "debit or credit; defaults from account_type"),
+];
+
+const OPENING_STOCK_FIELDS: &[FieldDef] = &[
+    FieldDef::required("sku", "SKU of an existing product"),
+    FieldDef::required("warehouse_code", "Code of an existing warehouse"),
+    FieldDef::required("quantity", "Whole number greater than 0"),
+    FieldDef::optional("unit_cost", "Number, defaults to the product's cost price"),
+];
+
+const EXCHANGE_RATE_FIELDS: &[FieldDef] = &[
+    FieldDef::required("from_currency", "ISO 4217 code, e.g. USD"),
+    FieldDef::required("to_currency", "ISO 4217 code, e.g. IDR"),
+    FieldDef::required("rate_date", "Date, e.g. 2024-03-31 or 31/03/2024"),
+    FieldDef::required("rate", "Units of to_currency per unit of from_currency"),
+    FieldDef::optional("rate_type", "spot, tax or average; defaults to spot"),
+];
+
+const ACCOUNT_TYPES: &[&str] = &["asset", "liability", "equity", "revenue", "expense"];
+const RATE_TYPES: &[&str] = &["spot", "tax", "average"];
+const BALANCE_TYPES: &[&str] = &["debit", "credit"];
+
+pub fn fields(entity: ImportEntity) -> &'static [FieldDef] {
+    match entity {
+        ImportEntity::Products => PRODUCT_FIELDS,
+        ImportEntity::Companies => COMPANY_FIELDS,
+        ImportEntity::Contacts => CONTACT_FIELDS,
+        ImportEntity::Vendors => VENDOR_FIELDS,
+        ImportEntity::Accounts => ACCOUNT_FIELDS,
+        ImportEntity::OpeningStock => OPENING_STOCK_FIELDS,
+        ImportEntity::ExchangeRates => EXCHANGE_RATE_FIELDS,
+    }
+}
+
+/// Parse and check every row of `sheet` without writing anything.
+pub async fn validate(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    entity: ImportEntity,
+    sheet: &Sheet,
+    mapping: &Mapping,
+) -> Result<Validation, sqlx::Error> {
+    match entity {
+        ImportEntity::Products => validate_products(conn, tenant_id, sheet, mapping).await,
+        ImportEntity::Companies => Ok(validate_companies(sheet, mapping)),
+        ImportEntity::Contacts => validate_contacts(conn, tenant_id, sheet, mapping).await,
+        ImportEntity::Vendors => validate_vendors(conn, tenant_id, sheet, mapping).await,
+        ImportEntity::Accounts => validate_accounts(conn, tenant_id, sheet, mapping).await,
+        ImportEntity::OpeningStock => validate_opening_stock(conn, tenant_id, sheet, mapping).await,
+        ImportEntity::ExchangeRates => Ok(parse_exchange_rates(sheet, mapping).finish()),
+    }
+}
+
+/// Insert validated records. Runs inside the caller's transaction; the first
+/// failing row aborts the whole import.
+pub async fn commit(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    user_id: Uuid,
+    job_id: Uuid,
+    entity: ImportEntity,
+    records: &[Record],
+) -> Result<usize, ImportRowError> {
+    match entity {
+        ImportEntity::Products => commit_products(conn, tenant_id, records).await,
+        ImportEntity::Companies => commit_companies(conn, tenant_id, records).await,
+        ImportEntity::Contacts => commit_contacts(conn, tenant_id, records).await,
+        ImportEntity::Vendors => commit_vendors(conn, tenant_id, records).await,
+        ImportEntity::Accounts => commit_accounts(conn, tenant_id, records).await,
+        ImportEntity::OpeningStock => commit_opening_stock(conn, tenant_id, user_id, job_id, records).await,
+        ImportEntity::ExchangeRates => commit_exchange_rates(conn, tenant_id, user_id, records).await,
+    }
+}
+
+fn decode<T: DeserializeOwned>(record: &Record) -> Result<T, ImportRowError> {
+    serde_json::from_value(record.data.clone()).map_err(|e| ImportRowError {
+        row: record.row,
+        column: None,
+        value: None,
+        message: format!("Stored record is unreadable: {}", e),
+    })
+}
+
+/// `key -> id` for the given keys, e.g. existing SKUs or category names.
+/// `sql` selects `key, id` and binds the tenant as `$1` and the keys as `$2`.
+async fn lookup(
+    conn: &mut PgConnection,
+    sql: &str,
+    tenant_id: Uuid,
+    keys: Vec<String>,
+) -> Result<HashMap<String, Uuid>, sqlx::Error> {
+    if keys.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let rows = sqlx::query(sql).bind(tenant_id).bind(keys).fetch_all(&mut *conn).await?;
+    Ok(rows.into_iter().map(|row| (row.get("key"), row.get("id"))).collect())
+}
+
+// ---------------------------------------------------------------------------
+// Products
+
+#[derive(Serialize, Deserialize)]
+struct ProductRecord {
+    sku: String,
+    name: String,
+    description: Option<String>,
+    category: Option<String>,
+    category_id: Option<Uuid>,
+    unit_of_measure: String,
+    cost_price: Decimal,
+    selling_price: Decimal,
+    minimum_stock: i32,
+    barcode: Option<String>,
+    weight: Option<Decimal>,
+}
+
+async fn validate_products(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    sheet: &Sheet,
+    mapping: &Mapping,
+) -> Result<Validation, sqlx::Error> {
+    let mut batch = Batch::parse(mapping, sheet, |cells| {
+        let sku = cells.required("sku", 100);
+        let name = cells.required("name", 255);
+        let record = ProductRecord {
+            description: cells.text("description", 10_000),
+            category: cells.text("category", 255),
+            category_id: None,
+            unit_of_measure: cells.text("unit_of_measure", 50).unwrap_or_else(|| "pcs".to_string()),
+            cost_price: cells.amount("cost_price").unwrap_or_default(),
+            selling_price: cells.amount("selling_price").unwrap_or_default(),
+            minimum_stock: cells.integer("minimum_stock", 0).unwrap_or(0),
+            barcode: cells.text("barcode", 255),
+            weight: cells.amount("weight"),
+            sku: sku?,
+            name: name?,
+        };
+        Some(record)
+    });
+    batch.reject_duplicates("sku", |p| Some((p.sku.clone(), p.sku.clone())));
+
+    let skus = batch.parsed.iter().map(|(_, p)| p.sku.clone()).collect();
+    let existing = lookup(
+        conn,
+        "SELECT sku AS key, id FROM products WHERE tenant_id = $1 AND sku = ANY($2)",
+        tenant_id,
+        skus,
+    )
+    .await?;
+    let categories = batch.parsed.iter().filter_map(|(_, p)| p.category.as_ref().map(|c| c.to_lowercase())).collect();
+    let categories = lookup(
+        conn,
+        "SELECT lower(name) AS key, id FROM product_categories WHERE tenant_id = $1 AND lower(name) = ANY($2)",
+        tenant_id,
+        categories,
+    )
+    .await?;
+
+    let mut rejects = Vec::new();
+    for (row, product) in &mut batch.parsed {
+        if existing.contains_key(&product.sku) {
+            rejects.push((*row, "sku", product.sku.clone(), "A product with this SKU already exists"));
+        }
+        if let Some(category) = &product.category {
+            match categories.get(&category.to_lowercase()) {
+                Some(id) => product.category_id = Some(*id),
+                None => rejects.push((*row, "category", category.clone(), "Unknown category")),
+            }
+        }
+    }
+    for (row, field, value, message) in rejects {
+        batch.reject(row, field, value, message);
+    }
+    Ok(batch.finish())
+}
+
+async fn commit_products(conn: &mut PgConnection, tenant_id: Uuid, records: &[Record]) -> Result<usize, ImportRowError> {
+    for record in records {
+        let p: ProductRecord = decode(record)?;
+        sqlx::query(
+            r#"INSERT INTO products (tenant_id, sku, name, description, category_id, unit_of_measure,
+                                     cost_price, selling_price, minimum_stock, barcode, weight)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)"#,
+        )
+        .bind(tenant_id)
+        .bind(&p.sku)
+        .bind(&p.name)
+        .bind(&p.description)
+        .bind(p.category_id)
+        .bind(&p.unit_of_measure)
+        .bind(p.cost_price)
+        .bind(p.selling_price)
+        .bind(p.minimum_stock)
+        .bind(&p.barcode)
+        .bind(p.weight)
+        .execute(&mut *conn)
+        .await
+        .map_err(row_failure(record.row))?;
+    }
+    Ok(records.len())
+}
+
+// ---------------------------------------------------------------------------
+// Companies
+
+#[derive(Serialize, Deserialize)]
+struct CompanyRecord {
+    name: String,
+    website: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+    tags: Vec<String>,
+}
+
+fn validate_companies(sheet: &Sheet, mapping: &Mapping) -> Validation {
+    let mut batch = Batch::parse(mapping, sheet, |cells| {
+        let name = cells.required("name", 255);
+        let tags = cells
+            .raw("tags")
+            .map(|tags| tags.split(';').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string).collect())
+            .unwrap_or_default();
+        let record = CompanyRecord {
+            website: cells.text("website", 255),
+            email: cells.email("email"),
+            phone: cells.text("phone", 50),
+            tags,
+            name: name?,
+        };
+        Some(record)
+    });
+    // Company names aren't unique in the database, but the same name twice in
+    // one file is almost always a copy-paste mistake.
+    batch.reject_duplicates("name", |c| Some((c.name.to_lowercase(), c.name.clone())));
+    batch.finish()
+}
+
+async fn commit_companies(conn: &mut PgConnection, tenant_id: Uuid, records: &[Record]) -> Result<usize, ImportRowError> {
+    for record in records {
+        let c: CompanyRecord = decode(record)?;
+        sqlx::query(
+            r#"INSERT INTO companies (tenant_id, name, website, email, phone, tags)
+               VALUES ($1, $2, $3, $4, $5, $6)"#,
+        )
+        .bind(tenant_id)
+        .bind(&c.name)
+        .bind(&c.website)
+        .bind(&c.email)
+        .bind(&c.phone)
+        .bind(&c.tags)
+        .execute(&mut *conn)
+        .await
+        .map_err(row_failure(record.row))?;
+    }
+    Ok(records.len())
+}
+
+// ---------------------------------------------------------------------------
+// Contacts
+
+#[derive(Serialize, Deserialize)]
+struct ContactRecord {
+    first_name: String,
+    last_name: String,
+    email: Option<String>,
+    phone: Option<String>,
+    position: Option<String>,
+    notes: Option<String>,
+    /// Company name from the file, created on commit when `company_id` is unset.
+    company: Option<String>,
+    company_id: Option<Uuid>,
+}
+
+async fn validate_contacts(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    sheet: &Sheet,
+    mapping: &Mapping,
+) -> Result<Validation, sqlx::Error> {
+    let mut batch = Batch::parse(mapping, sheet, |cells| {
+        let first_name = cells.required("first_name", 100);
+        let last_name = cells.required("last_name", 100);
+        let record = ContactRecord {
+            email: cells.email("email"),
+            phone: cells.text("phone", 50),
+            position: cells.text("position", 100),
+            notes: cells.text("notes", 10_000),
+            company: cells.text("company", 255),
+            company_id: None,
+            first_name: first_name?,
+            last_name: last_name?,
+        };
+        Some(record)
+    });
+    batch.reject_duplicates("email", |c| c.email.as_ref().map(|e| (e.to_lowercase(), e.clone())));
+
+    let names: Vec<String> = batch.parsed.iter().filter_map(|(_, c)| c.company.as_ref().map(|n| n.to_lowercase())).collect();
+    let mut companies: HashMap<String, Vec<Uuid>> = HashMap::new();
+    if !names.is_empty() {
+        let rows = sqlx::query(
+            "SELECT lower(name) AS key, id FROM companies WHERE tenant_id = $1 AND is_active = true AND lower(name) = ANY($2)",
+        )
+        .bind(tenant_id)
+        .bind(names)
+        .fetch_all(&mut *conn)
+        .await?;
+        for row in rows {
+            companies.entry(row.get("key")).or_default().push(row.get("id"));
+        }
+    }
+
+    let mut rejects = Vec::new();
+    for (row, contact) in &mut batch.parsed {
+        let Some(company) = &contact.company else { continue };
+        match companies.get(&company.to_lowercase()).map(Vec::as_slice) {
+            Some([id]) => contact.company_id = Some(*id),
+            Some(_) => rejects.push((*row, company.clone())),
+            None => {}
+        }
+    }
+    for (row, company) in rejects {
+        batch.reject(row, "company", company, "Several companies have this name");
+    }
+    Ok(batch.finish())
+}
+
+async fn commit_contacts(conn: &mut PgConnection, tenant_id: Uuid, records: &[Record]) -> Result<usize, ImportRowError> {
+    let mut created: HashMap<String, Uuid> = HashMap::new();
+    for record in records {
+        let c: ContactRecord = decode(record)?;
+        let company_id = match (c.company_id, &c.company) {
+            (Some(id), _) => Some(id),
+            (None, Some(name)) => match created.get(&name.to_lowercase()) {
+                Some(id) => Some(*id),
+                None => {
+                    let id: Uuid =
+                        sqlx::query_scalar("INSERT INTO companies (tenant_id, name) VALUES ($1, $2) RETURNING id")
+                            .bind(tenant_id)
+                            .bind(name)
+                            .fetch_one(&mut *conn)
+                            .await
+                            .map_err(row_failure(record.row))?;
+                    created.insert(name.to_lowercase(), id);
+                    Some(id)
+                }
+            },
+            (None, None) => None,
+        };
+
+        sqlx::query(
+            r#"INSERT INTO contacts (tenant_id, company_id, first_name, last_name, email, phone, position, notes)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"#,
+        )
+        .bind(tenant_id)
+        .bind(company_id)
+        .bind(&c.first_name)
+        .bind(&c.last_name)
+        .bind(&c.email)
+        .bind(&c.phone)
+        .bind(&c.position)
+        .bind(&c.notes)
+        .execute(&mut *conn)
+        .await
+        .map_err(row_failure(record.row))?;
+    }
+    Ok(records.len())
+}
+
+// ---------------------------------------------------------------------------
+// Vendors
+
+#[derive(Serialize, Deserialize)]
+struct VendorRecord {
+    code: String,
+    name: String,
+    contact_person: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+    tax_number: Option<String>,
+    payment_terms: Option<String>,
+    currency: String,
+    credit_limit: Option<Decimal>,
+}
+
+async fn validate_vendors(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    sheet: &Sheet,
+    mapping: &Mapping,
+) -> Result<Validation, sqlx::Error> {
+    let mut batch = Batch::parse(mapping, sheet, |cells| {
+        let code = cells.required("code", 50);
+        let name = cells.required("name", 255);
+        let currency = match cells.raw("currency").map(str::to_uppercase) {
+            None => Some("IDR".to_string()),
+            Some(c) if c.len() == 3 && c.chars().all(|ch| ch.is_ascii_alphabetic()) => Some(c),
+            Some(_) => {
+                cells.error("currency", "Must be a 3-letter ISO 4217 code");
+                None
+            }
+        };
+        let record = VendorRecord {
+            contact_person: cells.text("contact_person", 255),
+            email: cells.email("email"),
+            phone: cells.text("phone", 50),
+            tax_number: cells.text("tax_number", 100),
+            payment_terms: cells.text("payment_terms", 50),
+            credit_limit: cells.amount("credit_limit"),
+            code: code?,
+            name: name?,
+            currency: currency?,
+        };
+        Some(record)
+    });
+    batch.reject_duplicates("code", |v| Some((v.code.clone(), v.code.clone())));
+
+    let codes = batch.parsed.iter().map(|(_, v)| v.code.clone()).collect();
+    let existing = lookup(
+        conn,
+        "SELECT code AS key, id FROM vendors WHERE tenant_id = $1 AND code = ANY($2)",
+        tenant_id,
+        codes,
+    )
+    .await?;
+    let rejects: Vec<_> = batch
+        .parsed
+        .iter()
+        .filter(|(_, v)| existing.contains_key(&v.code))
+        .map(|(row, v)| (*row, v.code.clone()))
+        .collect();
+    for (row, code) in rejects {
+        batch.reject(row, "code", code, "A vendor with this code already exists");
+    }
+    Ok(batch.finish())
+}
+
+async fn commit_vendors(conn: &mut PgConnection, tenant_id: Uuid, records: &[Record]) -> Result<usize, ImportRowError> {
+    for record in records {
+        let v: VendorRecord = decode(record)?;
+        sqlx::query(
+            r#"INSERT INTO vendors (tenant_id, code, name, contact_person, email, phone,
+                                    tax_number, payment_terms, currency, credit_limit)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"#,
+        )
+        .bind(tenant_id)
+        .bind(&v.code)
+        .bind(&v.name)
+        .bind(&v.contact_person)
+        .bind(&v.email)
+        .bind(&v.phone)
+        .bind(&v.tax_number)
+        .bind(&v.payment_terms)
+        .bind(&v.currency)
+        .bind(v.credit_limit)
+        .execute(&mut *conn)
+        .await
+        .map_err(row_failure(record.row))?;
+    }
+    Ok(records.len())
+}
+
+// ---------------------------------------------------------------------------
+// Chart of accounts
+
+#[derive(Serialize, Deserialize)]
+struct AccountRecord {
+    code: String,
+    name: String,
+    account_type: String,
+    account_subtype: Option<String>,
+    parent_code: Option<String>,
+    description: Option<String>,
+    balance_type: String,
+}
+
+async fn validate_accounts(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    sheet: &Sheet,
+    mapping: &Mapping,
+) -> Result<Validation, sqlx::Error> {
+    let mut batch = Batch::parse(mapping, sheet, |cells| {
+        let code = cells.required("code", 20);
+        let name = cells.required("name", 255);
+        let account_type = if cells.raw("account_type").is_some() {
+            cells.choice("account_type", ACCOUNT_TYPES)
+        } else {
+            cells.error("account_type", "Is required");
+            None
+        };
+        let balance_type = match (cells.raw("balance_type"), account_type) {
+            (Some(_), _) => cells.choice("balance_type", BALANCE_TYPES),
+            (None, Some("asset" | "expense")) => Some("debit"),
+            (None, _) => Some("credit"),
+        };
+        let record = AccountRecord {
+            account_subtype: cells.text("account_subtype", 100),
+            parent_code: cells.text("parent_code", 20),
+            description: cells.text("description", 10_000),
+            code: code?,
+            name: name?,
+            account_type: account_type?.to_string(),
+            balance_type: balance_type?.to_string(),
+        };
+        Some(record)
+    });
+    batch.reject_duplicates("code", |a| Some((a.code.clone(), a.code.clone())));
+
+    let mut codes: Vec<String> = batch.parsed.iter().map(|(_, a)| a.code.clone()).collect();
+    codes.extend(batch.parsed.iter().filter_map(|(_, a)| a.parent_code.clone()));
+    let existing = lookup(
+        conn,
+        "SELECT code AS key, id FROM accounts WHERE tenant_id = $1 AND code = ANY($2)",
+        tenant_id,
+        codes,
+    )
+    .await?;
+    let in_file: HashSet<String> = batch.parsed.iter().map(|(_, a)| a.code.clone()).collect();
+
+    let mut rejects = Vec::new();
+    for (row, account) in &batch.parsed {
+        if existing.contains_key(&account.code) {
+            rejects.push((*row, "code", account.code.clone(), "An account with this code already exists"));
+        }
+        if let Some(parent) = &account.parent_code {
+            if parent == &account.code {
+                rejects.push((*row, "parent_code", parent.clone(), "An account cannot be its own parent"));
+            } else if !existing.contains_key(parent) && !in_file.contains(parent) {
+                rejects.push((*row, "parent_code", parent.clone(), "Unknown parent account"));
+            }
+        }
+    }
+    for (row, field, value, message) in rejects {
+        batch.reject(row, field, value, message);
+    }
+    Ok(batch.finish())
+}
+
+async fn commit_accounts(conn: &mut PgConnection, tenant_id: Uuid, records: &[Record]) -> Result<usize, ImportRowError> {
+    let mut parents = Vec::new();
+    for record in records {
+        let a: AccountRecord = decode(record)?;
+        let id: Uuid = sqlx::query_scalar(
+            r#"INSERT INTO accounts (tenant_id, code, name, account_type, account_subtype, description, balance_type)
+               VALUES ($1, $2, $3, $4, $5, $6, $7)
+               RETURNING id"#,
+        )
+        .bind(tenant_id)
+        .bind(&a.code)
+        .bind(&a.name)
+        .bind(&a.account_type)
+        .bind(&a.account_subtype)
+        .bind(&a.description)
+        .bind(&a.balance_type)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(row_failure(record.row))?;
+        if let Some(parent) = a.parent_code {
+            parents.push((record.row, id, parent));
+        }
+    }
+
+    // Parents may come later in the file, so link them once every row exists.
+    for (row, id, parent) in parents {
+        let linked = sqlx::query(
+            "UPDATE accounts SET parent_id = (SELECT id FROM accounts WHERE tenant_id = $1 AND code = $3) WHERE id = $2",
+        )
+        .bind(tenant_id)
+        .bind(id)
+        .bind(&parent)
+        .execute(&mut *conn)
+        .await
+        .map_err(row_failure(row))?;
+        if linked.rows_affected() == 0 {
+            return Err(ImportRowError {
+                row,
+                column: None,
+                value: Some(parent),
+                message: "Parent account no longer exists".to_string(),
+            });
+        }
+    }
+    Ok(records.len())
+}
+
+// ---------------------------------------------------------------------------
+// Opening stock
+
+#[derive(Serialize, Deserialize)]
+struct OpeningStockRecord {
+    sku: String,
+    warehouse_code: String,
+    quantity: i32,
+    unit_cost: Option<Decimal>,
+    product_id: Option<Uuid>,
+    warehouse_id: Option<Uuid>,
+}
+
+async fn validate_opening_stock(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    sheet: &Sheet,
+    mapping: &Mapping,
+) -> Result<Validation, sqlx::Error> {
+    let mut batch = Batch::parse(mapping, sheet, |cells| {
+        let sku = cells.required("sku", 100);
+        let warehouse_code = cells.required("warehouse_code", 50);
+        let quantity = if cells.raw("quantity").is_some() {
+            cells.integer("quantity", 1)
+        } else {
+            cells.error("quantity", "Is required");
+            None
+        };
+        let record = OpeningStockRecord {
+            unit_cost: cells.amount("unit_cost"),
+            product_id: None,
+            warehouse_id: None,
+            sku: sku?,
+            warehouse_code: warehouse_code?,
+            quantity: quantity?,
+        };
+        Some(record)
+    });
+    batch.reject_duplicates("sku", |s| {
+        Some(((s.sku.clone(), s.warehouse_code.clone()), format!("{} @ {}", s.sku, s.warehouse_code)))
+    });
+
+    let skus: Vec<String> = batch.parsed.iter().map(|(_, s)| s.sku.clone()).collect();
+    let mut products: HashMap<String, (Uuid, Decimal)> = HashMap::new();
+    if !skus.is_empty() {
+        let rows = sqlx::query("SELECT sku, id, cost_price FROM products WHERE tenant_id = $1 AND is_active = true AND sku = ANY($2)")
+            .bind(tenant_id)
+            .bind(skus)
+            .fetch_all(&mut *conn)
+            .await?;
+        for row in rows {
+            products.insert(row.get("sku"), (row.get("id"), row.get("cost_price")));
+        }
+    }
+    let codes = batch.parsed.iter().map(|(_, s)| s.warehouse_code.clone()).collect();
+    let warehouses = lookup(
+        conn,
+        "SELECT code AS key, id FROM warehouses WHERE tenant_id = $1 AND is_active = true AND code = ANY($2)",
+        tenant_id,
+        codes,
+    )
+    .await?;
+
+    let mut rejects = Vec::new();
+    for (row, stock) in &mut batch.parsed {
+        match products.get(&stock.sku) {
+            Some((id, cost)) => {
+                stock.product_id = Some(*id);
+                stock.unit_cost.get_or_insert(*cost);
+            }
+            None => rejects.push((*row, "sku", stock.sku.clone(), "Unknown product")),
+        }
+        match warehouses.get(&stock.warehouse_code) {
+            Some(id) => stock.warehouse_id = Some(*id),
+            None => rejects.push((*row, "warehouse_code", stock.warehouse_code.clone(), "Unknown warehouse")),
+        }
+    }
+
+    let pairs: Vec<(u32, Uuid, Uuid)> = batch
+        .parsed
+        .iter()
+        .filter_map(|(row, s)| Some((*row, s.product_id?, s.warehouse_id?)))
+        .collect();
+    if !pairs.is_empty() {
+        let stocked: HashSet<(Uuid, Uuid)> = sqlx::query(
+            r#"SELECT product_id, warehouse_id FROM stock_levels
+               WHERE tenant_id = $1 AND product_id = ANY($2) AND quantity_on_hand <> 0"#,
+        )
+        .bind(tenant_id)
+        .bind(pairs.iter().map(|(_, p, _)| *p).collect::<Vec<_>>())
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|row| (row.get("product_id"), row.get("warehouse_id")))
+        .collect();
+        for (row, product_id, warehouse_id) in pairs {
+            if stocked.contains(&(product_id, warehouse_id)) {
+                let stock = &batch.parsed.iter().find(|(r, _)| *r == row).expect("row is parsed").1;
+                rejects.push((row, "sku", stock.sku.clone(), "Product already has stock in this warehouse"));
+            }
+        }
+    }
+
+    for (row, field, value, message) in rejects {
+        batch.reject(row, field, value, message);
+    }
+    Ok(batch.finish())
+}
+
+async fn commit_opening_stock(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    user_id: Uuid,
+    job_id: Uuid,
+    records: &[Record],
+) -> Result<usize, ImportRowError> {
+    for record in records {
+        let s: OpeningStockRecord = decode(record)?;
+        let (Some(product_id), Some(warehouse_id)) = (s.product_id, s.warehouse_id) else {
+            return Err(ImportRowError {
+                row: record.row,
+                column: None,
+                value: None,
+                message: "Stored record is missing its product or warehouse".to_string(),
+            });
+        };
+
+        // The movement trigger maintains stock_levels; the product total is kept here.
+        sqlx::query(
+            r#"INSERT INTO stock_movements (tenant_id, product_id, warehouse_id, movement_type, quantity,
+                                            unit_cost, reference_type, reference_id, notes, created_by)
+               VALUES ($1, $2, $3, 'in', $4, $5, 'opening_balance', $6, 'Opening stock import', $7)"#,
+        )
+        .bind(tenant_id)
+        .bind(product_id)
+        .bind(warehouse_id)
+        .bind(s.quantity)
+        .bind(s.unit_cost)
+        .bind(job_id)
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(row_failure(record.row))?;
+
+        sqlx::query("UPDATE products SET current_stock = current_stock + $2 WHERE id = $1")
+            .bind(product_id)
+            .bind(s.quantity)
+            .execute(&mut *conn)
+            .await
+            .map_err(row_failure(record.row))?;
+    }
+    Ok(records.len())
+}
+
+// ---------------------------------------------------------------------------
+// Exchange rates
+
+/// Rows for days that already have a rate replace it, so a corrected file can
+/// simply be imported again.
+pub(super) fn parse_exchange_rates<'a>(sheet: &Sheet, mapping: &'a Mapping) -> Batch<'a, NewRate> {
+    let mut batch = Batch::parse(mapping, sheet, |cells| {
+        for field in ["from_currency", "to_currency", "rate_date", "rate"] {
+            if cells.raw(field).is_none() {
+                cells.error(field, "Is required");
+            }
+        }
+        let from_currency = cells.currency("from_currency");
+        let to_currency = cells.currency("to_currency");
+        if from_currency.is_some() && from_currency == to_currency {
+            cells.error("to_currency", "Must differ from from_currency");
+        }
+        let rate = match cells.amount("rate") {
+            Some(rate) if rate.is_zero() => {
+                cells.error("rate", "Must be greater than 0");
+                None
+            }
+            rate => rate,
+        };
+        let rate_date = cells.date("rate_date");
+        let rate_type = match cells.raw("rate_type") {
+            Some(_) => cells.choice("rate_type", RATE_TYPES).and_then(RateType::parse),
+            None => Some(RateType::Spot),
+        };
+        let record = NewRate {
+            rate_type: rate_type?,
+            rate_date: rate_date?,
+            from_currency: from_currency?,
+            to_currency: to_currency?,
+            rate: rate?,
+        };
+        Some(record)
+    });
+    batch.reject_duplicates("rate_date", |r| {
+        Some((
+            (r.from_currency, r.to_currency, r.rate_type, r.rate_date),
+            format!("{} {}/{} {}", r.rate_date, r.from_currency, r.to_currency, r.rate_type.as_str()),
+        ))
+    });
+    batch
+}
+
+async fn commit_exchange_rates(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    user_id: Uuid,
+    records: &[Record],
+) -> Result<usize, ImportRowError> {
+    for record in records {
+        let rate: NewRate = decode(record)?;
+        exchange_rates::upsert(conn, tenant_id, &rate, "import", Some(user_id))
+            .await
+            .map_err(row_failure(record.row))?;
+    }
+    Ok(records.len())
+}
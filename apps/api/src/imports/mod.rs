@@ -0,0 +1,687 @@
+//! Bulk import of master data from CSV/XLSX uploads.
+//!
+//! An upload is parsed, mapped onto the entity's fields and validated against
+//! the database up front (the dry run); nothing is written except the import job
+//! itself, which keeps the validated rows. Committing hands the job to a
+//! background worker that inserts every row in one transaction, so an import
+//! either lands completely or not at all.
+
+mod entities;
+pub mod sheet;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chrono::NaiveDate;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use shared_types::{Currency, ImportEntity, ImportRowError};
+use sqlx::{postgres::PgQueryResult, PgPool, Row as _};
+use telemetry::Metrics;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use uuid::Uuid;
+use validator::ValidateEmail;
+
+use crate::{
+    cache::{CacheScope, ReadCache},
+    exchange_rates::NewRate,
+    kv::KvStore,
+    notifications,
+    state::AppState,
+};
+use sheet::{Row, Sheet};
+
+pub use entities::{fields, validate};
+
+/// Rates in a CSV/XLSX file laid out like an `exchange_rates` import, for
+/// fetchers that read such files. Fails on the first invalid row.
+pub fn read_exchange_rates(file_name: &str, bytes: &[u8]) -> anyhow::Result<Vec<NewRate>> {
+    let (_, sheet) = sheet::read(file_name, bytes, usize::MAX)?;
+    let mapping = Mapping::new(ImportEntity::ExchangeRates, &sheet.headers, &BTreeMap::new())?;
+    let batch = entities::parse_exchange_rates(&sheet, &mapping);
+    if let Some(e) = batch.errors.iter().min_by_key(|e| e.row) {
+        anyhow::bail!("row {}: {} {}", e.row, e.column.as_deref().unwrap_or_default(), e.message);
+    }
+    Ok(batch.parsed.into_iter().map(|(_, rate)| rate).collect())
+}
+
+/// Row errors kept on a job; the rest are counted but not stored.
+pub const MAX_STORED_ERRORS: usize = 10_000;
+
+/// A column an import understands.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldDef {
+    pub name: &'static str,
+    pub required: bool,
+    pub description: &'static str,
+}
+
+impl FieldDef {
+    pub const fn required(name: &'static str, description: &'static str) -> Self {
+        Self { name, required: true, description }
+    }
+
+    pub const fn optional(name: &'static str, description: &'static str) -> Self {
+        Self { name, required: false, description }
+    }
+}
+
+/// Which sheet column feeds each field.
+#[derive(Debug, Clone)]
+pub struct Mapping {
+    columns: HashMap<&'static str, usize>,
+    headers: Vec<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MappingError {
+    #[error("Unknown field '{field}' in mapping (allowed: {allowed})")]
+    UnknownField { field: String, allowed: String },
+    #[error("Column '{column}' mapped to '{field}' is not in the file")]
+    UnknownColumn { field: String, column: String },
+    #[error("Required field '{0}' is not mapped to a column")]
+    MissingRequired(&'static str),
+}
+
+impl Mapping {
+    /// Map fields to columns. Columns whose header matches a field name
+    /// (ignoring case, spaces and dashes) are picked up automatically; `custom`
+    /// (field -> header) overrides that, e.g. `{"sku": "Kode Barang"}`.
+    pub fn new(entity: ImportEntity, headers: &[String], custom: &BTreeMap<String, String>) -> Result<Self, MappingError> {
+        let fields = fields(entity);
+        let mut columns = HashMap::new();
+
+        for (i, header) in headers.iter().enumerate() {
+            if let Some(field) = fields.iter().find(|f| f.name == normalize(header)) {
+                columns.insert(field.name, i);
+            }
+        }
+        for (field, column) in custom {
+            let def = fields.iter().find(|f| f.name == field).ok_or_else(|| MappingError::UnknownField {
+                field: field.clone(),
+                allowed: fields.iter().map(|f| f.name).collect::<Vec<_>>().join(", "),
+            })?;
+            let index = headers.iter().position(|h| h == column.trim()).ok_or_else(|| MappingError::UnknownColumn {
+                field: field.clone(),
+                column: column.clone(),
+            })?;
+            columns.insert(def.name, index);
+        }
+        if let Some(missing) = fields.iter().find(|f| f.required && !columns.contains_key(f.name)) {
+            return Err(MappingError::MissingRequired(missing.name));
+        }
+
+        Ok(Self { columns, headers: headers.to_vec() })
+    }
+
+    /// Field -> header, as stored on the job.
+    pub fn to_map(&self) -> BTreeMap<String, String> {
+        self.columns.iter().map(|(field, i)| (field.to_string(), self.headers[*i].clone())).collect()
+    }
+
+    /// Non-empty headers no field reads from.
+    pub fn unmapped_columns(&self) -> Vec<String> {
+        let used: BTreeSet<usize> = self.columns.values().copied().collect();
+        self.headers
+            .iter()
+            .enumerate()
+            .filter(|(i, h)| !h.is_empty() && !used.contains(i))
+            .map(|(_, h)| h.clone())
+            .collect()
+    }
+
+    fn header(&self, field: &str) -> Option<String> {
+        self.columns.get(field).map(|i| self.headers[*i].clone())
+    }
+}
+
+fn normalize(header: &str) -> String {
+    header.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+/// A validated row, serialized so it can wait on the job until commit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub row: u32,
+    pub data: Value,
+}
+
+#[derive(Debug, Default)]
+pub struct Validation {
+    pub records: Vec<Record>,
+    pub errors: Vec<ImportRowError>,
+}
+
+impl Validation {
+    /// Number of distinct rows with at least one error.
+    pub fn error_rows(&self) -> usize {
+        self.errors.iter().map(|e| e.row).collect::<BTreeSet<_>>().len()
+    }
+}
+
+/// Typed rows collected during validation. Rows that pick up an error at any
+/// stage (cell parsing, duplicates within the file, database lookups) are
+/// dropped from the records by [`Batch::finish`].
+struct Batch<'a, T> {
+    mapping: &'a Mapping,
+    parsed: Vec<(u32, T)>,
+    errors: Vec<ImportRowError>,
+}
+
+impl<'a, T: Serialize> Batch<'a, T> {
+    fn new(mapping: &'a Mapping) -> Self {
+        Self { mapping, parsed: Vec::new(), errors: Vec::new() }
+    }
+
+    /// Parse every row with `parse`, keeping the ones that had no cell errors.
+    fn parse(mapping: &'a Mapping, sheet: &Sheet, mut parse: impl FnMut(&mut Cells) -> Option<T>) -> Self {
+        let mut batch = Self::new(mapping);
+        for row in &sheet.rows {
+            let mut cells = Cells { row, mapping, errors: Vec::new() };
+            let parsed = parse(&mut cells);
+            match parsed {
+                Some(record) if cells.errors.is_empty() => batch.parsed.push((row.number, record)),
+                _ => batch.errors.append(&mut cells.errors),
+            }
+        }
+        batch
+    }
+
+    fn reject(&mut self, row: u32, field: &str, value: impl Into<String>, message: impl Into<String>) {
+        self.errors.push(ImportRowError {
+            row,
+            column: self.mapping.header(field),
+            value: Some(value.into()),
+            message: message.into(),
+        });
+    }
+
+    /// Flag every row whose `key` already appeared on an earlier row.
+    fn reject_duplicates<K: std::hash::Hash + Eq>(&mut self, field: &str, key: impl Fn(&T) -> Option<(K, String)>) {
+        let mut seen: HashMap<K, u32> = HashMap::new();
+        let mut duplicates = Vec::new();
+        for (row, record) in &self.parsed {
+            if let Some((k, shown)) = key(record) {
+                match seen.get(&k) {
+                    Some(first) => duplicates.push((*row, shown, *first)),
+                    None => {
+                        seen.insert(k, *row);
+                    }
+                }
+            }
+        }
+        for (row, shown, first) in duplicates {
+            self.reject(row, field, shown, format!("Duplicate of row {}", first));
+        }
+    }
+
+    fn finish(self) -> Validation {
+        let rejected: BTreeSet<u32> = self.errors.iter().map(|e| e.row).collect();
+        let records = self
+            .parsed
+            .into_iter()
+            .filter(|(row, _)| !rejected.contains(row))
+            .map(|(row, record)| Record {
+                row,
+                data: serde_json::to_value(record).expect("import record serializes"),
+            })
+            .collect();
+        let mut errors = self.errors;
+        errors.sort_by_key(|e| e.row);
+        Validation { records, errors }
+    }
+}
+
+/// Typed access to the mapped cells of one row, collecting errors as it goes.
+struct Cells<'a> {
+    row: &'a Row,
+    mapping: &'a Mapping,
+    errors: Vec<ImportRowError>,
+}
+
+impl Cells<'_> {
+    /// Trimmed cell value, `None` when the field isn't mapped or the cell is blank.
+    fn raw(&self, field: &str) -> Option<&str> {
+        let index = *self.mapping.columns.get(field)?;
+        let value = self.row.cells.get(index)?.trim();
+        (!value.is_empty()).then_some(value)
+    }
+
+    fn error(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(ImportRowError {
+            row: self.row.number,
+            column: self.mapping.header(field),
+            value: self.raw(field).map(str::to_string),
+            message: message.into(),
+        });
+    }
+
+    fn text(&mut self, field: &str, max_len: usize) -> Option<String> {
+        let value = self.raw(field)?.to_string();
+        if value.chars().count() > max_len {
+            self.error(field, format!("Must be at most {} characters", max_len));
+            return None;
+        }
+        Some(value)
+    }
+
+    fn required(&mut self, field: &str, max_len: usize) -> Option<String> {
+        if self.raw(field).is_none() {
+            self.error(field, "Is required");
+            return None;
+        }
+        self.text(field, max_len)
+    }
+
+    fn email(&mut self, field: &str) -> Option<String> {
+        let value = self.text(field, 255)?;
+        if !value.validate_email() {
+            self.error(field, "Is not a valid email address");
+            return None;
+        }
+        Some(value)
+    }
+
+    /// Non-negative number; a decimal comma (`12,50`) is accepted.
+    fn amount(&mut self, field: &str) -> Option<Decimal> {
+        let raw = self.raw(field)?;
+        match parse_decimal(raw) {
+            Some(value) if value >= Decimal::ZERO => Some(value),
+            Some(_) => {
+                self.error(field, "Must not be negative");
+                None
+            }
+            None => {
+                self.error(field, "Must be a number");
+                None
+            }
+        }
+    }
+
+    fn integer(&mut self, field: &str, min: i32) -> Option<i32> {
+        let raw = self.raw(field)?;
+        match parse_integer(raw) {
+            Some(value) if value >= min => Some(value),
+            Some(_) => {
+                self.error(field, format!("Must be at least {}", min));
+                None
+            }
+            None => {
+                self.error(field, "Must be a whole number");
+                None
+            }
+        }
+    }
+
+    fn date(&mut self, field: &str) -> Option<NaiveDate> {
+        let raw = self.raw(field)?;
+        let parsed = parse_date(raw);
+        if parsed.is_none() {
+            self.error(field, "Must be a date, e.g. 2024-03-31 or 31/03/2024");
+        }
+        parsed
+    }
+
+    fn currency(&mut self, field: &str) -> Option<Currency> {
+        let raw = self.raw(field)?;
+        let parsed = Currency::parse(raw);
+        if parsed.is_none() {
+            self.error(field, "Must be a supported ISO 4217 currency code");
+        }
+        parsed
+    }
+
+    /// Case-insensitive choice from `allowed`, returned in its canonical form.
+    fn choice(&mut self, field: &str, allowed: &[&'static str]) -> Option<&'static str> {
+        let raw = self.raw(field)?.to_lowercase();
+        let found = allowed.iter().find(|a| **a == raw).copied();
+        if found.is_none() {
+            self.error(field, format!("Must be one of: {}", allowed.join(", ")));
+        }
+        found
+    }
+}
+
+/// Parse a number written with either decimal separator. With both present the
+/// last one is the decimal point (`1.234,50`, `1,234.50`).
+fn parse_decimal(raw: &str) -> Option<Decimal> {
+    let s: String = raw.chars().filter(|c| !c.is_whitespace()).collect();
+    let normalized = match (s.rfind(','), s.rfind('.')) {
+        (Some(comma), Some(dot)) if comma > dot => s.replace('.', "").replace(',', "."),
+        (Some(_), Some(_)) => s.replace(',', ""),
+        (Some(_), None) if s.matches(',').count() == 1 => s.replace(',', "."),
+        (Some(_), None) => return None,
+        _ => s,
+    };
+    Decimal::from_str(&normalized).ok()
+}
+
+/// Whole number; `12.0` is accepted because XLSX stores every number as a float.
+fn parse_integer(raw: &str) -> Option<i32> {
+    let value = parse_decimal(raw)?;
+    if !value.fract().is_zero() {
+        return None;
+    }
+    value.to_i32()
+}
+
+/// Day-first or ISO dates (`31/03/2024`, `2024-03-31`), or the day number XLSX
+/// stores dates as.
+fn parse_date(raw: &str) -> Option<NaiveDate> {
+    if let Ok(serial) = raw.parse::<i64>() {
+        // Day 1 is 1900-01-01; counting from 1899-12-30 absorbs Excel's phantom 1900-02-29
+        if !(1..=2_958_465).contains(&serial) {
+            return None;
+        }
+        return NaiveDate::from_ymd_opt(1899, 12, 30)?.checked_add_days(chrono::Days::new(serial as u64));
+    }
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(raw, "%d/%m/%Y"))
+        .ok()
+}
+
+/// Error for a row that the database rejected while committing.
+fn row_failure(row: u32) -> impl Fn(sqlx::Error) -> ImportRowError {
+    move |e| ImportRowError {
+        row,
+        column: None,
+        value: None,
+        message: match e.as_database_error() {
+            Some(db) if db.code().as_deref() == Some("23505") => {
+                "Conflicts with a record created after the file was validated".to_string()
+            }
+            Some(db) => db.message().to_string(),
+            None => e.to_string(),
+        },
+    }
+}
+
+/// What a background import needs from the app state.
+#[derive(Clone)]
+pub struct Importer {
+    pool: PgPool,
+    kv: Arc<dyn KvStore>,
+    cache: ReadCache,
+    metrics: Metrics,
+}
+
+impl Importer {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            pool: state.db_pool.clone(),
+            kv: state.kv.clone(),
+            cache: state.cache.clone(),
+            metrics: state.metrics.clone(),
+        }
+    }
+
+    /// Publish how many imports are waiting for a worker.
+    pub async fn report_queue_depth(&self) {
+        let queued: Result<i64, _> = sqlx::query_scalar("SELECT COUNT(*) FROM import_jobs WHERE status = 'queued'")
+            .fetch_one(&self.pool)
+            .await;
+        match queued {
+            Ok(queued) => self.metrics.set_job_queue_depth("imports", queued as usize),
+            Err(e) => warn!("Failed to count queued imports: {}", e),
+        }
+    }
+}
+
+/// Commit a queued import in the background.
+///
+/// The job is claimed by moving it from `queued` to `running`, so it runs at most
+/// once even if several commits race. If shutdown starts first the transaction is
+/// rolled back and the job goes back to `queued` for [`resume_queued`].
+pub async fn run(importer: Importer, job_id: Uuid, shutdown: CancellationToken) {
+    let Importer { pool, kv, cache, metrics } = &importer;
+    let claimed = sqlx::query(
+        r#"UPDATE import_jobs SET status = 'running', started_at = NOW()
+           WHERE id = $1 AND status = 'queued'
+           RETURNING tenant_id, entity, created_by, records"#,
+    )
+    .bind(job_id)
+    .fetch_optional(pool)
+    .await;
+    importer.report_queue_depth().await;
+    let row = match claimed {
+        Ok(Some(row)) => row,
+        Ok(None) => return,
+        Err(e) => {
+            warn!(%job_id, "Failed to claim import: {}", e);
+            return;
+        }
+    };
+
+    let tenant_id: Uuid = row.get("tenant_id");
+    let user_id: Uuid = row.get("created_by");
+    let entity_name: String = row.get("entity");
+    let Some(entity) = ImportEntity::parse(&entity_name) else {
+        warn!(%job_id, entity = entity_name, "Import has an unknown entity");
+        let failure = ImportRowError {
+            row: 0,
+            column: None,
+            value: Some(entity_name.clone()),
+            message: format!("Unknown import entity '{}'", entity_name),
+        };
+        if let Err(e) = fail(pool, job_id, failure).await {
+            warn!(%job_id, "Failed to record import outcome: {}", e);
+        }
+        importer.report_queue_depth().await;
+        return;
+    };
+    let records: Vec<Record> = row
+        .try_get::<Option<Value>, _>("records")
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+
+    let outcome = tokio::select! {
+        result = commit(pool, tenant_id, user_id, job_id, entity, &records) => Some(result),
+        _ = shutdown.cancelled() => None,
+    };
+
+    let updated = match outcome {
+        None => {
+            info!(%job_id, "Import interrupted by shutdown, requeued");
+            sqlx::query("UPDATE import_jobs SET status = 'queued', started_at = NULL WHERE id = $1")
+                .bind(job_id)
+                .execute(pool)
+                .await
+        }
+        Some(Ok(imported)) => {
+            info!(%job_id, entity = entity.as_str(), imported, "Import completed");
+            match entity {
+                ImportEntity::Products | ImportEntity::OpeningStock => {
+                    cache.invalidate(tenant_id, CacheScope::Products).await;
+                }
+                ImportEntity::Accounts => cache.invalidate(tenant_id, CacheScope::Accounts).await,
+                _ => {}
+            }
+            if entity == ImportEntity::OpeningStock {
+                // Every opening stock row is one `in` movement
+                metrics.record_stock_movements("in", tenant_id, imported as u64);
+                notify_low_stock(pool, kv.as_ref(), cache, tenant_id, &records).await;
+            }
+            sqlx::query(
+                r#"UPDATE import_jobs SET status = 'completed', imported_rows = $2, records = NULL, finished_at = NOW()
+                   WHERE id = $1"#,
+            )
+            .bind(job_id)
+            .bind(imported as i32)
+            .execute(pool)
+            .await
+        }
+        Some(Err(failure)) => {
+            warn!(%job_id, row = failure.row, "Import failed: {}", failure.message);
+            fail(pool, job_id, failure).await
+        }
+    };
+    if let Err(e) = updated {
+        warn!(%job_id, "Failed to record import outcome: {}", e);
+    }
+    importer.report_queue_depth().await;
+}
+
+/// Mark a claimed job `failed` with `failure` as its only error.
+async fn fail(pool: &PgPool, job_id: Uuid, failure: ImportRowError) -> Result<PgQueryResult, sqlx::Error> {
+    sqlx::query(
+        r#"UPDATE import_jobs SET status = 'failed', errors = $2, error_rows = 1, finished_at = NOW()
+           WHERE id = $1"#,
+    )
+    .bind(job_id)
+    .bind(serde_json::to_value(vec![failure]).expect("import errors serialize"))
+    .execute(pool)
+    .await
+}
+
+async fn commit(
+    pool: &PgPool,
+    tenant_id: Uuid,
+    user_id: Uuid,
+    job_id: Uuid,
+    entity: ImportEntity,
+    records: &[Record],
+) -> Result<usize, ImportRowError> {
+    let failure = row_failure(0);
+    let mut tx = pool.begin().await.map_err(&failure)?;
+    sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(tenant_id.to_string())
+        .execute(&mut *tx)
+        .await
+        .map_err(&failure)?;
+
+    let imported = entities::commit(&mut tx, tenant_id, user_id, job_id, entity, records).await?;
+    tx.commit().await.map_err(&failure)?;
+    Ok(imported)
+}
+
+/// Alert stock keepers about imported products that start out at or below
+/// their minimum stock. The import has committed, so failures are only logged.
+async fn notify_low_stock(pool: &PgPool, kv: &dyn KvStore, cache: &ReadCache, tenant_id: Uuid, records: &[Record]) {
+    let product_ids: Vec<Uuid> = records
+        .iter()
+        .filter_map(|r| r.data.get("product_id")?.as_str()?.parse().ok())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let created = match pool.acquire().await {
+        Ok(mut conn) => notifications::low_stock(&mut conn, cache, tenant_id, &product_ids).await,
+        Err(e) => Err(e),
+    };
+    match created {
+        Ok(created) => notifications::publish(kv, &created).await,
+        Err(e) => warn!(%tenant_id, "Failed to send low stock notifications: {}", e),
+    }
+}
+
+/// Start imports left queued, e.g. by a shutdown in the middle of a commit.
+///
+/// Jobs still `running` after `imports.stale_after` lost their worker to a
+/// crash; their commit transaction was rolled back with it, so they are
+/// requeued and run again.
+pub async fn resume_queued(state: &AppState) -> Result<(), sqlx::Error> {
+    let stale = sqlx::query(
+        r#"UPDATE import_jobs SET status = 'queued', started_at = NULL
+           WHERE status = 'running' AND started_at < NOW() - make_interval(secs => $1)"#,
+    )
+    .bind(state.config.imports.stale_after as f64)
+    .execute(&state.db_pool)
+    .await?;
+    if stale.rows_affected() > 0 {
+        warn!(jobs = stale.rows_affected(), "Requeued imports left running by a crashed worker");
+    }
+
+    let queued: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM import_jobs WHERE status = 'queued' ORDER BY created_at")
+        .fetch_all(&state.db_pool)
+        .await?;
+    let importer = Importer::new(state);
+    importer.report_queue_depth().await;
+    for job_id in queued {
+        let importer = importer.clone();
+        state.workers.spawn("import", move |shutdown| run(importer, job_id, shutdown));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn numbers_accept_either_decimal_separator() {
+        assert_eq!(parse_decimal("12,50"), Some(Decimal::new(1250, 2)));
+        assert_eq!(parse_decimal("1.234,50"), Some(Decimal::new(123450, 2)));
+        assert_eq!(parse_decimal("1,234.50"), Some(Decimal::new(123450, 2)));
+        assert_eq!(parse_decimal("1,234,567"), None);
+        assert_eq!(parse_decimal("abc"), None);
+        assert_eq!(parse_integer("12.0"), Some(12));
+        assert_eq!(parse_integer("12.5"), None);
+    }
+
+    #[test]
+    fn dates_accept_iso_day_first_and_xlsx_serials() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 31);
+        assert_eq!(parse_date("2024-03-31"), day);
+        assert_eq!(parse_date("31/03/2024"), day);
+        assert_eq!(parse_date("45382"), day);
+        assert_eq!(parse_date("03/31/2024"), None);
+        assert_eq!(parse_date("0"), None);
+        assert_eq!(parse_date("yesterday"), None);
+    }
+
+    #[test]
+    fn mapping_matches_headers_and_applies_overrides() {
+        let sheet_headers = headers(&["SKU", "Nama Barang", "Cost Price", "Notes"]);
+        let custom = BTreeMap::from([("name".to_string(), "Nama Barang".to_string())]);
+        let mapping = Mapping::new(ImportEntity::Products, &sheet_headers, &custom).unwrap();
+        assert_eq!(mapping.to_map().get("cost_price").map(String::as_str), Some("Cost Price"));
+        assert_eq!(mapping.unmapped_columns(), vec!["Notes"]);
+
+        assert!(matches!(
+            Mapping::new(ImportEntity::Products, &sheet_headers, &BTreeMap::new()),
+            Err(MappingError::MissingRequired("name"))
+        ));
+        let unknown = BTreeMap::from([("price".to_string(), "SKU".to_string())]);
+        assert!(matches!(
+            Mapping::new(ImportEntity::Products, &sheet_headers, &unknown),
+            Err(MappingError::UnknownField { .. })
+        ));
+    }
+
+    #[test]
+    fn batch_drops_rows_with_errors() {
+        let sheet = Sheet {
+            headers: headers(&["code", "qty"]),
+            rows: vec![
+                Row { number: 2, cells: vec!["A".into(), "1".into()] },
+                Row { number: 3, cells: vec!["".into(), "x".into()] },
+                Row { number: 4, cells: vec!["A".into(), "2".into()] },
+            ],
+        };
+        let mapping = Mapping {
+            columns: HashMap::from([("code", 0), ("qty", 1)]),
+            headers: sheet.headers.clone(),
+        };
+        let mut batch = Batch::parse(&mapping, &sheet, |cells| {
+            let code = cells.required("code", 10);
+            let qty = cells.integer("qty", 0);
+            Some((code?, qty?))
+        });
+        batch.reject_duplicates("code", |(code, _)| Some((code.clone(), code.clone())));
+        let validation = batch.finish();
+
+        assert_eq!(validation.records.len(), 1);
+        assert_eq!(validation.records[0].row, 2);
+        assert_eq!(validation.error_rows(), 2);
+        assert_eq!(validation.errors[0].column.as_deref(), Some("code"));
+        assert_eq!(validation.errors[2].message, "Duplicate of row 2");
+    }
+}
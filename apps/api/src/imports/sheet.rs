@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+
+/// A data row of an uploaded sheet. `number` is the row as the user sees it in
+/// their spreadsheet (the header is row 1), so errors can point back at it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub number: u32,
+    pub cells: Vec<String>,
+}
+
+/// First worksheet of an upload: a header row and the non-empty rows below it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sheet {
+    pub headers: Vec<String>,
+    pub rows: Vec<Row>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Csv,
+    Xlsx,
+}
+
+impl FileFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileFormat::Csv => "csv",
+            FileFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SheetError {
+    #[error("Unsupported file type; upload a .csv or .xlsx file")]
+    Unsupported,
+    #[error("Invalid CSV: {0}")]
+    InvalidCsv(String),
+    #[error("Invalid XLSX: {0}")]
+    InvalidXlsx(String),
+    #[error("The file has no header row")]
+    Empty,
+    #[error("Duplicate column '{0}'")]
+    DuplicateColumn(String),
+    #[error("The file has more than {0} rows; split it into smaller imports")]
+    TooManyRows(usize),
+}
+
+/// Parse an upload, telling CSV and XLSX apart by content rather than trusting
+/// the file name.
+pub fn read(file_name: &str, bytes: &[u8], max_rows: usize) -> Result<(FileFormat, Sheet), SheetError> {
+    let (format, mut rows) = if bytes.starts_with(b"PK\x03\x04") {
+        (FileFormat::Xlsx, read_xlsx(bytes)?)
+    } else if file_name.to_ascii_lowercase().ends_with(".xlsx") {
+        return Err(SheetError::InvalidXlsx("not a zip archive".to_string()));
+    } else {
+        let text = std::str::from_utf8(bytes).map_err(|_| SheetError::Unsupported)?;
+        (FileFormat::Csv, read_csv(text)?)
+    };
+
+    rows.retain(|row| row.cells.iter().any(|c| !c.trim().is_empty()));
+    if rows.is_empty() {
+        return Err(SheetError::Empty);
+    }
+    let header = rows.remove(0);
+    if rows.len() > max_rows {
+        return Err(SheetError::TooManyRows(max_rows));
+    }
+
+    let headers: Vec<String> = header.cells.iter().map(|h| h.trim().to_string()).collect();
+    for (i, h) in headers.iter().enumerate() {
+        if !h.is_empty() && headers[..i].contains(h) {
+            return Err(SheetError::DuplicateColumn(h.clone()));
+        }
+    }
+    Ok((format, Sheet { headers, rows }))
+}
+
+/// RFC 4180 CSV with a UTF-8 BOM tolerated. Spreadsheet exports in locales with a
+/// decimal comma use `;` as the separator, so the header line decides which one.
+fn read_csv(text: &str) -> Result<Vec<Row>, SheetError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let first_line = text.lines().next().unwrap_or_default();
+    let delimiter = if first_line.matches(';').count() > first_line.matches(',').count() { ';' } else { ',' };
+
+    let mut rows = Vec::new();
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut line = 1u32;
+    let mut row_start = 1u32;
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    cell.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    cell.push(c);
+                }
+                _ => cell.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if cell.is_empty() => in_quotes = true,
+            c if c == delimiter => cells.push(std::mem::take(&mut cell)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                cells.push(std::mem::take(&mut cell));
+                rows.push(Row { number: row_start, cells: std::mem::take(&mut cells) });
+                line += 1;
+                row_start = line;
+            }
+            _ => cell.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(SheetError::InvalidCsv(format!("unterminated quote starting on line {}", row_start)));
+    }
+    if !cell.is_empty() || !cells.is_empty() {
+        cells.push(cell);
+        rows.push(Row { number: row_start, cells });
+    }
+    Ok(rows)
+}
+
+/// First worksheet of an XLSX workbook. Only cell values are read; formulas
+/// contribute their cached result and number formats are ignored.
+fn read_xlsx(bytes: &[u8]) -> Result<Vec<Row>, SheetError> {
+    let invalid = |e: &dyn std::fmt::Display| SheetError::InvalidXlsx(e.to_string());
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| invalid(&e))?;
+    let mut part = |name: &str| -> Option<String> {
+        let mut file = archive.by_name(name).ok()?;
+        let mut xml = String::new();
+        file.read_to_string(&mut xml).ok()?;
+        Some(xml)
+    };
+
+    let shared_strings: Vec<String> = part("xl/sharedStrings.xml")
+        .map(|xml| elements(&xml, "si").map(|(_, inner)| text_runs(inner.unwrap_or_default())).collect())
+        .unwrap_or_default();
+
+    let sheet_path = part("xl/workbook.xml")
+        .zip(part("xl/_rels/workbook.xml.rels"))
+        .and_then(|(workbook, rels)| first_sheet_path(&workbook, &rels))
+        .unwrap_or_else(|| "xl/worksheets/sheet1.xml".to_string());
+    let sheet = part(&sheet_path).ok_or_else(|| invalid(&"workbook has no worksheet"))?;
+
+    let mut rows = Vec::new();
+    for (index, (attrs, inner)) in elements(&sheet, "row").enumerate() {
+        let number = attr(attrs, "r").and_then(|r| r.parse().ok()).unwrap_or(index as u32 + 1);
+        let mut cells: Vec<String> = Vec::new();
+        for (cell_attrs, value) in elements(inner.unwrap_or_default(), "c") {
+            let column = attr(cell_attrs, "r").and_then(column_index).unwrap_or(cells.len());
+            let value = value.unwrap_or_default();
+            let raw = elements(value, "v").next().and_then(|(_, v)| v).map(unescape).unwrap_or_default();
+            let text = match attr(cell_attrs, "t") {
+                Some("s") => raw
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| shared_strings.get(i).cloned())
+                    .ok_or_else(|| invalid(&format!("bad shared string in row {}", number)))?,
+                Some("inlineStr") => elements(value, "is").next().map(|(_, is)| text_runs(is.unwrap_or_default())).unwrap_or_default(),
+                Some("b") => (if raw == "1" { "true" } else { "false" }).to_string(),
+                _ => raw,
+            };
+            if cells.len() <= column {
+                cells.resize(column + 1, String::new());
+            }
+            cells[column] = text;
+        }
+        rows.push(Row { number, cells });
+    }
+    Ok(rows)
+}
+
+fn first_sheet_path(workbook: &str, rels: &str) -> Option<String> {
+    let (attrs, _) = elements(workbook, "sheet").next()?;
+    let id = attr(attrs, "r:id")?;
+    let targets: HashMap<&str, &str> = elements(rels, "Relationship")
+        .filter_map(|(a, _)| Some((attr(a, "Id")?, attr(a, "Target")?)))
+        .collect();
+    let target = targets.get(id)?;
+    Some(match target.strip_prefix('/') {
+        Some(absolute) => absolute.to_string(),
+        None => format!("xl/{}", target),
+    })
+}
+
+/// `(attributes, inner xml)` of every `<tag>` element; inner is `None` when self-closing.
+/// Good enough for the flat, machine-written parts of an OOXML package.
+fn elements<'a>(xml: &'a str, tag: &'a str) -> impl Iterator<Item = (&'a str, Option<&'a str>)> + 'a {
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut rest = xml;
+    std::iter::from_fn(move || loop {
+        let start = rest.find(&open)?;
+        let after = &rest[start + open.len()..];
+        if !after.starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace()) {
+            rest = after;
+            continue;
+        }
+        let end = after.find('>')?;
+        if after[..end].ends_with('/') {
+            rest = &after[end + 1..];
+            return Some((after[..end - 1].trim(), None));
+        }
+        let body = &after[end + 1..];
+        let inner_end = body.find(&close)?;
+        rest = &body[inner_end + close.len()..];
+        return Some((after[..end].trim(), Some(&body[..inner_end])));
+    })
+}
+
+fn attr<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    let needle = format!("{}=\"", name);
+    let mut search = attrs;
+    loop {
+        let at = search.find(&needle)?;
+        let boundary = at == 0 || search[..at].ends_with(char::is_whitespace);
+        let value = &search[at + needle.len()..];
+        if boundary {
+            return Some(&value[..value.find('"')?]);
+        }
+        search = value;
+    }
+}
+
+/// Concatenated `<t>` runs of a (possibly rich text) string item.
+fn text_runs(xml: &str) -> String {
+    elements(xml, "t").filter_map(|(_, t)| t).map(unescape).collect()
+}
+
+/// Zero-based column of a cell reference such as `AB12`.
+fn column_index(reference: &str) -> Option<usize> {
+    let letters: Vec<u8> = reference.bytes().take_while(u8::is_ascii_alphabetic).collect();
+    if letters.is_empty() {
+        return None;
+    }
+    let n = letters
+        .iter()
+        .fold(0usize, |n, b| n * 26 + (b.to_ascii_uppercase() - b'A' + 1) as usize);
+    Some(n - 1)
+}
+
+fn unescape(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(at) = rest.find('&') {
+        out.push_str(&rest[..at]);
+        let entity_end = rest[at..].find(';').map(|e| at + e);
+        let decoded = entity_end.and_then(|end| match &rest[at + 1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            e if e.starts_with("#x") => u32::from_str_radix(&e[2..], 16).ok().and_then(char::from_u32),
+            e if e.starts_with('#') => e[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        });
+        match (decoded, entity_end) {
+            (Some(c), Some(end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                out.push('&');
+                rest = &rest[at + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// One CSV record separated by `delimiter`, quoting fields that need it.
+pub fn csv_record<I, S>(delimiter: char, fields: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut line = fields
+        .into_iter()
+        .map(|f| {
+            let f = f.as_ref();
+            if f.contains([delimiter, '"', '\n', '\r']) {
+                format!("\"{}\"", f.replace('"', "\"\""))
+            } else {
+                f.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(&delimiter.to_string());
+    line.push_str("\r\n");
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn csv_handles_quotes_and_multiline_cells() {
+        let (format, sheet) = read(
+            "p.csv",
+            "\u{feff}sku,name\r\nA-1,\"Kopi, \"\"Arabica\"\"\"\n\nB-2,\"two\nlines\"\nC-3,x\n".as_bytes(),
+            10,
+        )
+        .unwrap();
+        assert_eq!(format, FileFormat::Csv);
+        assert_eq!(sheet.headers, vec!["sku", "name"]);
+        assert_eq!(sheet.rows[0], Row { number: 2, cells: vec!["A-1".into(), "Kopi, \"Arabica\"".into()] });
+        assert_eq!(sheet.rows[1].number, 4);
+        assert_eq!(sheet.rows[1].cells[1], "two\nlines");
+        assert_eq!(sheet.rows[2].number, 6);
+    }
+
+    #[test]
+    fn csv_detects_semicolon_separator() {
+        let (_, sheet) = read("p.csv", b"sku;cost_price\nA;12,50\n", 10).unwrap();
+        assert_eq!(sheet.rows[0].cells, vec!["A", "12,50"]);
+    }
+
+    #[test]
+    fn row_limit_and_duplicate_headers_are_rejected() {
+        assert!(matches!(read("p.csv", b"a\n1\n2\n", 1), Err(SheetError::TooManyRows(1))));
+        assert!(matches!(read("p.csv", b"a,a\n1,2\n", 10), Err(SheetError::DuplicateColumn(_))));
+        assert!(matches!(read("p.csv", b"\n\n", 10), Err(SheetError::Empty)));
+    }
+
+    fn xlsx(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, body) in files {
+            zip.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            zip.write_all(body.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn xlsx_reads_shared_inline_and_sparse_cells() {
+        let bytes = xlsx(&[
+            ("xl/workbook.xml", r#"<workbook><sheets><sheet name="Data" sheetId="1" r:id="rId3"/></sheets></workbook>"#),
+            (
+                "xl/_rels/workbook.xml.rels",
+                r#"<Relationships><Relationship Id="rId3" Target="worksheets/data.xml"/></Relationships>"#,
+            ),
+            ("xl/sharedStrings.xml", r#"<sst><si><t>sku</t></si><si><r><t>na</t></r><r><t>me</t></r></si><si><t>Teh &amp; Gula</t></si></sst>"#),
+            (
+                "xl/worksheets/data.xml",
+                r#"<worksheet><sheetData>
+                    <row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1" t="s"><v>1</v></c><c r="D1" t="inlineStr"><is><t>qty</t></is></c></row>
+                    <row r="3"><c r="A3" t="inlineStr"><is><t>T-1</t></is></c><c r="B3" t="s"><v>2</v></c><c r="D3"><v>12.5</v></c></row>
+                </sheetData></worksheet>"#,
+            ),
+        ]);
+        let (format, sheet) = read("upload.bin", &bytes, 10).unwrap();
+        assert_eq!(format, FileFormat::Xlsx);
+        assert_eq!(sheet.headers, vec!["sku", "name", "", "qty"]);
+        assert_eq!(sheet.rows, vec![Row { number: 3, cells: vec!["T-1".into(), "Teh & Gula".into(), "".into(), "12.5".into()] }]);
+    }
+
+    #[test]
+    fn csv_records_are_quoted_when_needed() {
+        assert_eq!(csv_record(',', ["2", "name", "a \"b\", c"]), "2,name,\"a \"\"b\"\", c\"\r\n");
+        assert_eq!(csv_record(';', ["1,5", "a;b"]), "1,5;\"a;b\"\r\n");
+    }
+}
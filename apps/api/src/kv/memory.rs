@@ -0,0 +1,125 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use super::{KvError, KvStore};
+
+/// Keys expire like in Redis; published messages are kept so tests can
+/// check what was sent.
+#[derive(Default)]
+pub struct MemoryKv {
+    entries: Mutex<HashMap<String, (String, Instant)>>,
+    published: Mutex<Vec<(String, String)>>,
+    unreachable: AtomicBool,
+}
+
+impl MemoryKv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Messages published on `channel` so far, oldest first.
+    pub fn published(&self, channel: &str) -> Vec<String> {
+        let published = self.published.lock().expect("kv lock poisoned");
+        published.iter().filter(|(c, _)| c == channel).map(|(_, m)| m.clone()).collect()
+    }
+
+    /// Make `ping` fail as if the server were down, e.g. to test readiness.
+    pub fn set_unreachable(&self, unreachable: bool) {
+        self.unreachable.store(unreachable, Ordering::Relaxed);
+    }
+
+    fn live(entries: &mut HashMap<String, (String, Instant)>, key: &str) -> Option<String> {
+        match entries.get(key) {
+            Some((value, expires_at)) if *expires_at > Instant::now() => Some(value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl KvStore for MemoryKv {
+    async fn get(&self, key: &str) -> Result<Option<String>, KvError> {
+        let mut entries = self.entries.lock().expect("kv lock poisoned");
+        Ok(Self::live(&mut entries, key))
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> Result<(), KvError> {
+        let mut entries = self.entries.lock().expect("kv lock poisoned");
+        entries.insert(key.to_string(), (value.to_string(), Instant::now() + ttl));
+        Ok(())
+    }
+
+    async fn set_nx(&self, key: &str, value: &str, ttl: Duration) -> Result<bool, KvError> {
+        let mut entries = self.entries.lock().expect("kv lock poisoned");
+        if Self::live(&mut entries, key).is_some() {
+            return Ok(false);
+        }
+        entries.insert(key.to_string(), (value.to_string(), Instant::now() + ttl));
+        Ok(true)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), KvError> {
+        self.entries.lock().expect("kv lock poisoned").remove(key);
+        Ok(())
+    }
+
+    async fn publish(&self, channel: &str, messages: &[String]) -> Result<(), KvError> {
+        let mut published = self.published.lock().expect("kv lock poisoned");
+        published.extend(messages.iter().map(|m| (channel.to_string(), m.clone())));
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), KvError> {
+        if self.unreachable.load(Ordering::Relaxed) {
+            return Err(KvError("connection refused".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn values_expire() {
+        let kv = MemoryKv::new();
+        kv.set("a", "1", Duration::from_secs(60)).await.unwrap();
+        kv.set("b", "2", Duration::ZERO).await.unwrap();
+        assert_eq!(kv.get("a").await.unwrap().as_deref(), Some("1"));
+        assert_eq!(kv.get("b").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn set_nx_only_claims_absent_keys() {
+        let kv = MemoryKv::new();
+        assert!(kv.set_nx("k", "first", Duration::from_secs(60)).await.unwrap());
+        assert!(!kv.set_nx("k", "second", Duration::from_secs(60)).await.unwrap());
+        assert_eq!(kv.get("k").await.unwrap().as_deref(), Some("first"));
+
+        kv.delete("k").await.unwrap();
+        assert!(kv.set_nx("k", "third", Duration::from_secs(60)).await.unwrap());
+
+        kv.set("gone", "x", Duration::ZERO).await.unwrap();
+        assert!(kv.set_nx("gone", "y", Duration::from_secs(60)).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn published_messages_are_kept_per_channel() {
+        let kv = MemoryKv::new();
+        kv.publish("a", &["1".to_string(), "2".to_string()]).await.unwrap();
+        kv.publish("b", &["3".to_string()]).await.unwrap();
+        assert_eq!(kv.published("a"), ["1", "2"]);
+        assert_eq!(kv.published("c"), Vec::<String>::new());
+    }
+}
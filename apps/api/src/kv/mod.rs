@@ -0,0 +1,36 @@
+//! Short-lived shared state: refresh tokens, idempotency records and the
+//! channel notifications are published on.
+//!
+//! [`RedisKv`] is what runs in production; [`MemoryKv`] keeps everything in
+//! the process, so tests don't need a Redis server.
+
+mod memory;
+mod redis;
+
+use std::time::Duration;
+
+pub use self::memory::MemoryKv;
+pub use self::redis::RedisKv;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Key-value store error: {0}")]
+pub struct KvError(pub String);
+
+#[async_trait::async_trait]
+pub trait KvStore: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<String>, KvError>;
+
+    /// Store `value` under `key` for `ttl`, replacing any previous value.
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> Result<(), KvError>;
+
+    /// Like [`set`](Self::set), but only if `key` is absent. `false` if it was there.
+    async fn set_nx(&self, key: &str, value: &str, ttl: Duration) -> Result<bool, KvError>;
+
+    /// Remove `key`; removing a key that doesn't exist is not an error.
+    async fn delete(&self, key: &str) -> Result<(), KvError>;
+
+    /// Send `messages` to whoever is subscribed to `channel`.
+    async fn publish(&self, channel: &str, messages: &[String]) -> Result<(), KvError>;
+
+    async fn ping(&self) -> Result<(), KvError>;
+}
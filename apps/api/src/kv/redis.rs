@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use redis::{aio::ConnectionManager, AsyncCommands};
+use telemetry::Metrics;
+
+use super::{KvError, KvStore};
+
+impl From<redis::RedisError> for KvError {
+    fn from(e: redis::RedisError) -> Self {
+        KvError(e.to_string())
+    }
+}
+
+/// Commands are timed into `redis_command_duration_seconds`, each under a
+/// `redis.command` span.
+#[derive(Clone)]
+pub struct RedisKv {
+    conn: ConnectionManager,
+    metrics: Metrics,
+}
+
+impl RedisKv {
+    pub fn new(conn: ConnectionManager, metrics: Metrics) -> Self {
+        Self { conn, metrics }
+    }
+}
+
+#[async_trait::async_trait]
+impl KvStore for RedisKv {
+    async fn get(&self, key: &str) -> Result<Option<String>, KvError> {
+        let mut conn = self.conn.clone();
+        Ok(self.metrics.time_redis("GET", conn.get(key)).await?)
+    }
+
+    async fn set(&self, key: &str, value: &str, ttl: Duration) -> Result<(), KvError> {
+        let mut conn = self.conn.clone();
+        let mut set = redis::cmd("SET");
+        set.arg(key).arg(value).arg("PX").arg(millis(ttl));
+        Ok(self.metrics.time_redis("SET", set.query_async(&mut conn)).await?)
+    }
+
+    async fn set_nx(&self, key: &str, value: &str, ttl: Duration) -> Result<bool, KvError> {
+        let mut conn = self.conn.clone();
+        let mut set = redis::cmd("SET");
+        set.arg(key).arg(value).arg("NX").arg("PX").arg(millis(ttl));
+        let stored: Option<String> = self.metrics.time_redis("SET", set.query_async(&mut conn)).await?;
+        Ok(stored.is_some())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), KvError> {
+        let mut conn = self.conn.clone();
+        Ok(self.metrics.time_redis("DEL", conn.del(key)).await?)
+    }
+
+    async fn publish(&self, channel: &str, messages: &[String]) -> Result<(), KvError> {
+        let mut pipe = redis::pipe();
+        for message in messages {
+            pipe.cmd("PUBLISH").arg(channel).arg(message).ignore();
+        }
+        let mut conn = self.conn.clone();
+        Ok(self.metrics.time_redis("PUBLISH", pipe.query_async(&mut conn)).await?)
+    }
+
+    async fn ping(&self) -> Result<(), KvError> {
+        let mut conn = self.conn.clone();
+        let _: String = self.metrics.time_redis("PING", redis::cmd("PING").query_async(&mut conn)).await?;
+        Ok(())
+    }
+}
+
+/// Redis refuses an expiry of 0.
+fn millis(ttl: Duration) -> u64 {
+    u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX).max(1)
+}
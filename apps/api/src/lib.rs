@@ -0,0 +1,174 @@
+//! ERP platform API: configuration, application state, routes and background
+//! workers, shared by the `api` server and the `erpctl` admin CLI.
+
+pub mod attachments;
+pub mod cache;
+pub mod config;
+pub mod email;
+pub mod exchange_rates;
+pub mod export;
+pub mod fieldset;
+pub mod handlers;
+pub mod imports;
+pub mod kv;
+pub mod list_query;
+pub mod middleware;
+pub mod notifications;
+pub mod openapi;
+pub mod pagination;
+pub mod routes;
+pub mod search;
+pub mod sequences;
+pub mod services;
+pub mod storage;
+pub mod state;
+pub mod extractors;
+pub mod workers;
+
+use anyhow::Result;
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{header, HeaderName, Method},
+    Router,
+};
+use config::CorsConfig;
+use sqlx::{
+    migrate::{Migrate, MigrateError},
+    PgPool,
+};
+use state::AppState;
+use std::{sync::Arc, time::Duration};
+use tower::ServiceBuilder;
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{AllowOrigin, CorsLayer},
+    timeout::TimeoutLayer,
+    trace::TraceLayer,
+};
+use tracing::instrument;
+
+/// Migrations embedded at build time; also consulted by the readiness probe.
+///
+/// sqlx checksums applied migrations, so a shipped file must never change;
+/// fixes go in a new migration. The one exception is 003 and 009, corrected
+/// in place (text into a jsonb column, a `users.tenant_id` that does not
+/// exist). A database that recorded them as first shipped reports them as
+/// `modified` and refuses to migrate until `erpctl migrate repair` records
+/// the corrected checksums.
+pub static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations");
+
+/// The seed migration that creates the `acme` tenant later seeds insert for.
+const SEED_TENANT_VERSION: i64 = 3;
+
+/// Apply the pending [`MIGRATOR`] migrations. The server, `erpctl migrate up`
+/// and the test harness all migrate through here.
+///
+/// Checks what `MIGRATOR.run` checks, and also sets `app.current_tenant_id`
+/// to the tenant 003 creates before the seeds after it run; they insert for
+/// that setting and fail without it.
+pub async fn migrate(pool: &PgPool) -> Result<(), MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.lock().await?;
+    conn.ensure_migrations_table().await?;
+    if let Some(version) = conn.dirty_version().await? {
+        return Err(MigrateError::Dirty(version));
+    }
+
+    let applied = conn.list_applied_migrations().await?;
+    if let Some(missing) = applied.iter().find(|a| !MIGRATOR.iter().any(|m| m.version == a.version)) {
+        return Err(MigrateError::VersionMissing(missing.version));
+    }
+
+    for migration in MIGRATOR.iter().filter(|m| !m.migration_type.is_down_migration()) {
+        match applied.iter().find(|a| a.version == migration.version) {
+            Some(a) if a.checksum != migration.checksum => return Err(MigrateError::VersionMismatch(migration.version)),
+            Some(_) => {}
+            None => {
+                conn.apply(migration).await?;
+            }
+        }
+        if migration.version == SEED_TENANT_VERSION {
+            sqlx::query("SELECT set_config('app.current_tenant_id', id::text, false) FROM tenants WHERE slug = 'acme'")
+                .execute(&mut *conn)
+                .await?;
+        }
+    }
+
+    conn.unlock().await?;
+    // Keep the tenant setting out of the pool
+    drop(conn.detach());
+    Ok(())
+}
+
+#[instrument(skip(state))]
+pub async fn create_app(state: AppState) -> Result<Router> {
+    let server = &state.config.server;
+    let cors = cors_layer(&server.cors);
+    let timeout = TimeoutLayer::new(Duration::from_secs(server.request_timeout));
+    let body_limit = DefaultBodyLimit::max(server.body_limit);
+    // With every encoding switched off the layer passes responses through untouched
+    let compression = if server.compression {
+        CompressionLayer::new()
+    } else {
+        CompressionLayer::new().no_br().no_deflate().no_gzip().no_zstd()
+    };
+
+    let shared_state = Arc::new(state);
+    // RequestIdLayer goes first so its span (continuing any incoming
+    // traceparent) is the root that TraceLayer and handlers nest under.
+    let middleware_stack = ServiceBuilder::new()
+        .layer(middleware::request_id::RequestIdLayer::new())
+        .layer(middleware::metrics::HttpMetricsLayer::new(shared_state.metrics.clone()))
+        .layer(TraceLayer::new_for_http())
+        .layer(compression)
+        .layer(cors)
+        .layer(timeout)
+        .layer(middleware::error_handler::ErrorHandlerLayer::new())
+        .layer(middleware::auth_layer::AuthLayer::new(shared_state.clone()))
+        .layer(middleware::idempotency::IdempotencyLayer::new(shared_state.clone()));
+
+    let app = Router::new()
+        .nest("/api/v1", routes::api_routes())
+        .nest("/docs", routes::docs_routes())
+        .merge(routes::probe_routes())
+        .layer(middleware_stack)
+        .layer(body_limit)
+        .with_state(shared_state);
+
+    Ok(app)
+}
+
+fn cors_layer(config: &CorsConfig) -> CorsLayer {
+    let origins = if config.allows_any() {
+        AllowOrigin::any()
+    } else {
+        // Origins are validated when the config is loaded
+        AllowOrigin::list(config.allowed_origins.iter().filter_map(|o| o.parse().ok()))
+    };
+    let request_id = HeaderName::from_static("x-request-id");
+
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::ACCEPT,
+            header::ACCEPT_LANGUAGE,
+            header::IF_MATCH,
+            header::IF_NONE_MATCH,
+            header::CACHE_CONTROL,
+            request_id.clone(),
+            middleware::idempotency::IDEMPOTENCY_KEY,
+        ])
+        .expose_headers([request_id, header::ETAG, middleware::idempotency::IDEMPOTENT_REPLAYED])
+        .allow_credentials(config.allow_credentials)
+        .max_age(Duration::from_secs(config.max_age))
+}
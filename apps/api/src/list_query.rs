@@ -0,0 +1,757 @@
+use std::borrow::Cow;
+
+use axum::{
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use shared_types::{
+    schema::{self, DataSchema}, ApiResponse, CountMode, CursorPaginatedResponse, FilterOperator, PaginatedResponse, PaginationMeta,
+};
+use sha2::{Digest, Sha256};
+use sqlx::{postgres::PgRow, PgConnection, Postgres, QueryBuilder};
+use telemetry::db_span;
+use tracing::Instrument;
+use utoipa::openapi::{schema::{OneOfBuilder, Schema}, Ref, RefOr};
+use uuid::Uuid;
+
+use crate::pagination::{self, Cursor, InvalidCursor, Keyset, SortKey};
+
+/// Query parameters that control the list itself rather than filter a field.
+const RESERVED: &[&str] = &[
+    "page", "per_page", "cursor", "limit", "count", "sort", "sort_by", "sort_order", "search", "fields",
+    "include", "format",
+];
+
+const MAX_PAGE: u32 = 10_000;
+const MAX_PER_PAGE: u32 = 100;
+const MAX_IN_VALUES: usize = 100;
+const MAX_SEARCH_LEN: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Uuid,
+    Text,
+    Bool,
+    Integer,
+    Decimal,
+    Date,
+    Timestamp,
+}
+
+impl FieldType {
+    fn sql_type(self) -> &'static str {
+        match self {
+            FieldType::Uuid => "uuid",
+            FieldType::Text => "text",
+            FieldType::Bool => "boolean",
+            FieldType::Integer => "bigint",
+            FieldType::Decimal => "numeric",
+            FieldType::Date => "date",
+            FieldType::Timestamp => "timestamptz",
+        }
+    }
+
+    fn supports(self, op: FilterOperator) -> bool {
+        use FilterOperator::*;
+        match self {
+            FieldType::Text => matches!(op, Eq | Ne | In | Ilike | Null),
+            FieldType::Uuid => matches!(op, Eq | Ne | In | Null),
+            FieldType::Bool => matches!(op, Eq | Ne | Null),
+            FieldType::Integer | FieldType::Decimal | FieldType::Date | FieldType::Timestamp => {
+                !matches!(op, Ilike)
+            }
+        }
+    }
+}
+
+/// A field a list can be filtered (and optionally sorted) by.
+#[derive(Debug, Clone, Copy)]
+pub struct Field {
+    /// Name used in the query string
+    pub name: &'static str,
+    /// SQL expression, qualified with the table alias used in [`ListSpec::from`]
+    pub column: &'static str,
+    pub ty: FieldType,
+    pub sortable: bool,
+    pub nullable: bool,
+}
+
+impl Field {
+    pub const fn new(name: &'static str, column: &'static str, ty: FieldType) -> Self {
+        Self { name, column, ty, sortable: false, nullable: false }
+    }
+
+    pub const fn sortable(mut self) -> Self {
+        self.sortable = true;
+        self
+    }
+
+    pub const fn nullable(mut self) -> Self {
+        self.nullable = true;
+        self
+    }
+}
+
+/// Whitelist of what a list endpoint lets clients filter and sort by.
+///
+/// Query strings use `field=value`, `field[op]=value` (see [`FilterOperator`]),
+/// `sort=-created_at,name`, `search=` and either `page`/`per_page` or
+/// `cursor`/`limit`/`count` (see [`crate::pagination`]). Only whitelisted fields
+/// reach SQL, and every value is a bind parameter.
+pub struct ListSpec {
+    /// `FROM` clause including joins, e.g. `products p LEFT JOIN ...`
+    pub from: &'static str,
+    /// Column scoping rows to the current tenant
+    pub tenant_column: &'static str,
+    /// Condition every row must meet, e.g. hiding soft-deleted rows
+    pub base_filter: Option<&'static str>,
+    pub id_column: &'static str,
+    pub fields: &'static [Field],
+    /// Columns matched case-insensitively by `?search=`
+    pub search: &'static [&'static str],
+    /// Sort used when the request has none, in `sort` syntax
+    pub default_sort: &'static str,
+    /// Legacy parameters kept for older clients, e.g. `("from_date", "order_date[gte]")`
+    pub aliases: &'static [(&'static str, &'static str)],
+}
+
+/// Raw list query string, validated against a [`ListSpec`] by [`ListSpec::plan`].
+pub struct ListQuery {
+    params: Vec<(String, String)>,
+}
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for ListQuery
+where
+    S: Send + Sync,
+{
+    type Rejection = ListQueryError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let params = parse_params(parts.uri.query().unwrap_or_default())?;
+        Ok(Self { params })
+    }
+}
+
+/// Decode a query string into its pairs. `Query` quietly swaps escapes that
+/// are not UTF-8 for replacement characters; here they reject the request so
+/// a filter is never matched against a mangled value.
+fn parse_params(query: &str) -> Result<Vec<(String, String)>, ListQueryError> {
+    let decode = |s: &str| {
+        percent_encoding::percent_decode_str(&s.replace('+', " "))
+            .decode_utf8()
+            .map(Cow::into_owned)
+            .map_err(|_| ListQueryError::InvalidParameter("query string"))
+    };
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((decode(key)?, decode(value)?))
+        })
+        .collect()
+}
+
+impl ListQuery {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Identifies the rows this query selects, for caching its page. Ignores
+    /// parameter order and `fields`/`format`, which only shape the response.
+    pub fn cache_key(&self) -> String {
+        let mut params: Vec<_> = self.params.iter().filter(|(k, _)| k != "fields" && k != "format").collect();
+        params.sort();
+        let mut hasher = Sha256::new();
+        for (key, value) in params {
+            hasher.update(key.as_bytes());
+            hasher.update([0]);
+            hasher.update(value.as_bytes());
+            hasher.update([0]);
+        }
+        format!("list:{}", hex::encode(hasher.finalize()))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ListQueryError {
+    #[error("Unknown field '{field}' (allowed: {allowed})")]
+    UnknownField { field: String, allowed: String },
+    #[error("Operator '{op}' is not supported for '{field}'")]
+    UnsupportedOperator { field: String, op: String },
+    #[error("Invalid value for '{field}': '{value}'")]
+    InvalidValue { field: String, value: String },
+    #[error("Field '{field}' is not sortable (sortable: {allowed})")]
+    NotSortable { field: String, allowed: String },
+    #[error("Cursor pagination cannot sort by '{0}' because it may be empty")]
+    NullableCursorSort(String),
+    #[error("Invalid {0}")]
+    InvalidParameter(&'static str),
+    #[error(transparent)]
+    Cursor(#[from] InvalidCursor),
+}
+
+impl IntoResponse for ListQueryError {
+    fn into_response(self) -> Response {
+        (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(self.to_string()))).into_response()
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Uuid(Uuid),
+    Text(String),
+    Bool(bool),
+    Integer(i64),
+    Decimal(Decimal),
+    Date(NaiveDate),
+    Timestamp(DateTime<Utc>),
+}
+
+struct Filter {
+    field: &'static Field,
+    op: FilterOperator,
+    values: Vec<Value>,
+}
+
+enum Paging {
+    Offset { page: u32, per_page: u32 },
+    Cursor { cursor: Option<Cursor>, limit: u32, count: CountMode },
+}
+
+/// A validated list request, ready to run.
+pub struct ListPlan {
+    spec: &'static ListSpec,
+    filters: Vec<Filter>,
+    search: Option<String>,
+    keyset: Keyset,
+    paging: Paging,
+    /// Extra `column = id` condition, e.g. to list only the caller's own rows
+    scope: Option<(&'static str, Uuid)>,
+}
+
+/// One page of a list in whichever pagination style the client asked for.
+#[derive(Serialize, Deserialize)]
+pub enum ListPage<T> {
+    Offset(PaginatedResponse<T>),
+    Cursor(CursorPaginatedResponse<T>),
+}
+
+impl<T> ListPage<T> {
+    /// Rows of the page, e.g. to embed related entities before responding.
+    pub fn data_mut(&mut self) -> &mut Vec<T> {
+        match self {
+            ListPage::Offset(page) => &mut page.data,
+            ListPage::Cursor(page) => &mut page.data,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> ListPage<U> {
+        match self {
+            ListPage::Offset(page) => ListPage::Offset(PaginatedResponse {
+                data: page.data.into_iter().map(f).collect(),
+                pagination: page.pagination,
+            }),
+            ListPage::Cursor(page) => ListPage::Cursor(CursorPaginatedResponse {
+                data: page.data.into_iter().map(f).collect(),
+                pagination: page.pagination,
+            }),
+        }
+    }
+}
+
+/// Offset or keyset pagination, whichever the query asked for.
+impl<T: DataSchema> DataSchema for ListPage<T> {
+    fn data_schema() -> Option<RefOr<Schema>> {
+        let pagination = OneOfBuilder::new()
+            .item(Ref::from_schema_name("PaginationMeta"))
+            .item(Ref::from_schema_name("CursorMeta"));
+        Some(schema::page_schema::<T>(pagination.into()))
+    }
+}
+
+impl<T: Serialize> IntoResponse for ListPage<T> {
+    fn into_response(self) -> Response {
+        match self {
+            ListPage::Offset(page) => Json(ApiResponse::success(page)).into_response(),
+            ListPage::Cursor(page) => Json(ApiResponse::success(page)).into_response(),
+        }
+    }
+}
+
+impl ListSpec {
+    /// Base table of [`ListSpec::from`], naming the query's span.
+    fn table(&self) -> &'static str {
+        self.from.split_whitespace().next().unwrap_or(self.from)
+    }
+
+    pub fn plan(&'static self, query: &ListQuery) -> Result<ListPlan, ListQueryError> {
+        let mut filters = Vec::new();
+        for (key, raw) in &query.params {
+            if RESERVED.contains(&key.as_str()) {
+                continue;
+            }
+            let key = self
+                .aliases
+                .iter()
+                .find(|(alias, _)| alias == key)
+                .map(|(_, target)| *target)
+                .unwrap_or(key);
+            filters.push(self.parse_filter(key, raw)?);
+        }
+
+        let search = match query.get("search") {
+            Some(s) if s.chars().count() > MAX_SEARCH_LEN => return Err(ListQueryError::InvalidParameter("search")),
+            Some(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+            _ => None,
+        };
+
+        let cursor_mode = query.get("cursor").is_some() || query.get("limit").is_some();
+        let sort = self.parse_sort(query, cursor_mode)?;
+        let keyset = Keyset {
+            keys: sort
+                .iter()
+                .map(|(field, descending)| SortKey {
+                    column: field.column,
+                    sql_type: field.ty.sql_type(),
+                    descending: *descending,
+                })
+                .collect(),
+            id_column: self.id_column,
+        };
+
+        let paging = if cursor_mode {
+            let limit = parse_number(query.get("limit"), "limit", 1, pagination::MAX_LIMIT)?;
+            let cursor = match query.get("cursor") {
+                Some(raw) => Some(keyset.decode(raw)?),
+                None => None,
+            };
+            let count = match query.get("count") {
+                None => CountMode::None,
+                Some(raw) => serde_json::from_value(serde_json::Value::String(raw.to_string()))
+                    .map_err(|_| ListQueryError::InvalidParameter("count"))?,
+            };
+            Paging::Cursor { cursor, limit: pagination::limit(limit), count }
+        } else {
+            let page = parse_number(query.get("page"), "page", 1, MAX_PAGE)?.unwrap_or(1);
+            let per_page = parse_number(query.get("per_page"), "per_page", 1, MAX_PER_PAGE)?.unwrap_or(20);
+            Paging::Offset { page, per_page }
+        };
+
+        Ok(ListPlan { spec: self, filters, search, keyset, paging, scope: None })
+    }
+
+    fn field(&'static self, name: &str) -> Option<&'static Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    fn parse_filter(&'static self, key: &str, raw: &str) -> Result<Filter, ListQueryError> {
+        let (name, op) = match key.split_once('[') {
+            Some((name, rest)) => {
+                let op = rest.strip_suffix(']').and_then(FilterOperator::parse).ok_or_else(|| {
+                    ListQueryError::UnsupportedOperator { field: name.to_string(), op: rest.trim_end_matches(']').to_string() }
+                })?;
+                (name, op)
+            }
+            None => (key, FilterOperator::Eq),
+        };
+
+        let field = self.field(name).ok_or_else(|| ListQueryError::UnknownField {
+            field: name.to_string(),
+            allowed: self.fields.iter().map(|f| f.name).collect::<Vec<_>>().join(", "),
+        })?;
+        if !field.ty.supports(op) || (op == FilterOperator::Null && !field.nullable) {
+            return Err(ListQueryError::UnsupportedOperator { field: name.to_string(), op: op.as_str().to_string() });
+        }
+
+        let invalid = || ListQueryError::InvalidValue { field: name.to_string(), value: raw.to_string() };
+        let values = match op {
+            FilterOperator::Null => vec![Value::Bool(raw.parse().map_err(|_| invalid())?)],
+            FilterOperator::Ilike => vec![Value::Text(raw.to_string())],
+            FilterOperator::In => {
+                let values = raw
+                    .split(',')
+                    .map(|v| parse_value(field.ty, v.trim()).ok_or_else(invalid))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if values.is_empty() || values.len() > MAX_IN_VALUES {
+                    return Err(invalid());
+                }
+                values
+            }
+            _ => vec![parse_value(field.ty, raw).ok_or_else(invalid)?],
+        };
+
+        Ok(Filter { field, op, values })
+    }
+
+    fn parse_sort(&'static self, query: &ListQuery, cursor_mode: bool) -> Result<Vec<(&'static Field, bool)>, ListQueryError> {
+        let requested = match (query.get("sort"), query.get("sort_by")) {
+            (Some(sort), _) => sort.to_string(),
+            // Legacy `sort_by`/`sort_order`; the direction falls back to the default sort's
+            (None, Some(by)) => {
+                let descending = match query.get("sort_order").map(|o| o.to_ascii_lowercase()) {
+                    Some(o) if o == "asc" => false,
+                    Some(o) if o == "desc" => true,
+                    Some(_) => return Err(ListQueryError::InvalidParameter("sort_order")),
+                    None => self.default_sort.starts_with('-'),
+                };
+                format!("{}{}", if descending { "-" } else { "" }, by)
+            }
+            (None, None) => self.default_sort.to_string(),
+        };
+
+        let mut sort = Vec::new();
+        for part in requested.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, descending) = match part.strip_prefix('-') {
+                Some(name) => (name, true),
+                None => (part.strip_prefix('+').unwrap_or(part), false),
+            };
+            let field = self.field(name).filter(|f| f.sortable).ok_or_else(|| ListQueryError::NotSortable {
+                field: name.to_string(),
+                allowed: self.fields.iter().filter(|f| f.sortable).map(|f| f.name).collect::<Vec<_>>().join(", "),
+            })?;
+            if cursor_mode && field.nullable {
+                return Err(ListQueryError::NullableCursorSort(name.to_string()));
+            }
+            if sort.iter().any(|(f, _): &(&Field, bool)| f.name == field.name) {
+                continue;
+            }
+            sort.push((field, descending));
+        }
+        Ok(sort)
+    }
+}
+
+impl ListPlan {
+    /// Only rows whose `column` equals `id`, on top of the tenant scope.
+    pub fn scoped(mut self, column: &'static str, id: Uuid) -> Self {
+        self.scope = Some((column, id));
+        self
+    }
+
+    /// `FROM ... WHERE ...` for this list: tenant scope, base filter, field filters and search.
+    fn push_from_where(&self, qb: &mut QueryBuilder<'_, Postgres>, tenant_id: Uuid) {
+        qb.push(format_args!(" FROM {} WHERE {} = ", self.spec.from, self.spec.tenant_column));
+        qb.push_bind(tenant_id);
+        if let Some(base) = self.spec.base_filter {
+            qb.push(format_args!(" AND {}", base));
+        }
+        if let Some((column, id)) = self.scope {
+            qb.push(format_args!(" AND {} = ", column)).push_bind(id);
+        }
+        for filter in &self.filters {
+            filter.push(qb);
+        }
+        if let Some(search) = &self.search {
+            let pattern = format!("%{}%", escape_like(search));
+            qb.push(" AND (");
+            for (i, column) in self.spec.search.iter().enumerate() {
+                if i > 0 {
+                    qb.push(" OR ");
+                }
+                qb.push(format_args!("{} ILIKE ", column));
+                qb.push_bind(pattern.clone());
+            }
+            if self.spec.search.is_empty() {
+                qb.push("false");
+            }
+            qb.push(")");
+        }
+    }
+
+    /// Every matching row in list order, ignoring paging, e.g. to stream an export.
+    pub fn unpaged(&self, tenant_id: Uuid, columns: &str) -> QueryBuilder<'static, Postgres> {
+        let mut query = QueryBuilder::new("SELECT ");
+        query.push(columns);
+        self.push_from_where(&mut query, tenant_id);
+        self.keyset.push_order_by(&mut query);
+        query
+    }
+
+    /// Run the list query, selecting `columns` and mapping each row with `map`.
+    pub async fn fetch<T>(
+        &self,
+        conn: &mut PgConnection,
+        tenant_id: Uuid,
+        columns: &str,
+        map: impl Fn(&PgRow) -> Result<T, sqlx::Error>,
+    ) -> Result<ListPage<T>, sqlx::Error> {
+        let from_where = |qb: &mut QueryBuilder<'_, Postgres>| self.push_from_where(qb, tenant_id);
+
+        let mut query = QueryBuilder::new("SELECT ");
+        query.push(columns);
+
+        match &self.paging {
+            Paging::Cursor { cursor, limit, count } => {
+                self.keyset.push_cursor_columns(&mut query);
+                from_where(&mut query);
+                if let Some(cursor) = cursor {
+                    self.keyset.push_after(&mut query, cursor);
+                }
+                self.keyset.push_order_by(&mut query);
+                query.push(" LIMIT ").push_bind(*limit as i64 + 1);
+
+                let rows = query.build().fetch_all(&mut *conn).instrument(db_span("SELECT", self.spec.table())).await?;
+                let total = pagination::total_count(conn, *count, from_where).await?;
+                Ok(ListPage::Cursor(pagination::page(rows, *limit, &self.keyset, map, total)?))
+            }
+            Paging::Offset { page, per_page } => {
+                let total_count = pagination::total_count(&mut *conn, CountMode::Exact, from_where)
+                    .await?
+                    .map(|(count, _)| count)
+                    .unwrap_or(0);
+
+                from_where(&mut query);
+                self.keyset.push_order_by(&mut query);
+                query.push(" LIMIT ").push_bind(*per_page as i64);
+                query.push(" OFFSET ").push_bind((*page as i64 - 1) * *per_page as i64);
+
+                let rows = query.build().fetch_all(conn).instrument(db_span("SELECT", self.spec.table())).await?;
+                let total_pages = total_count.div_ceil(*per_page as u64) as u32;
+                Ok(ListPage::Offset(PaginatedResponse {
+                    data: rows.iter().map(map).collect::<Result<_, _>>()?,
+                    pagination: PaginationMeta {
+                        current_page: *page,
+                        per_page: *per_page,
+                        total_pages,
+                        total_count,
+                        has_next: *page < total_pages,
+                        has_prev: *page > 1,
+                    },
+                }))
+            }
+        }
+    }
+}
+
+impl Filter {
+    fn push(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        let column = self.field.column;
+        let value = &self.values[0];
+
+        // A bare date against a timestamp column means the whole (UTC) day
+        if let (FieldType::Timestamp, Value::Date(date)) = (self.field.ty, value) {
+            if self.op != FilterOperator::In {
+                let start = date.and_hms_opt(0, 0, 0).expect("midnight is valid").and_utc();
+                let end = date
+                    .checked_add_days(Days::new(1))
+                    .map(|d| d.and_hms_opt(0, 0, 0).expect("midnight is valid").and_utc())
+                    .unwrap_or(DateTime::<Utc>::MAX_UTC);
+                let (op, bound) = match self.op {
+                    FilterOperator::Gt => (">=", end),
+                    FilterOperator::Gte => (">=", start),
+                    FilterOperator::Lt => ("<", start),
+                    FilterOperator::Lte => ("<", end),
+                    FilterOperator::Ne => {
+                        qb.push(format_args!(" AND NOT ({} >= ", column)).push_bind(start);
+                        qb.push(format_args!(" AND {} < ", column)).push_bind(end).push(")");
+                        return;
+                    }
+                    _ => {
+                        qb.push(format_args!(" AND {} >= ", column)).push_bind(start);
+                        qb.push(format_args!(" AND {} < ", column)).push_bind(end);
+                        return;
+                    }
+                };
+                qb.push(format_args!(" AND {} {} ", column, op)).push_bind(bound);
+                return;
+            }
+        }
+
+        let op = match self.op {
+            FilterOperator::Null => {
+                let is_null = matches!(value, Value::Bool(true));
+                qb.push(format_args!(" AND {} IS {}NULL", column, if is_null { "" } else { "NOT " }));
+                return;
+            }
+            FilterOperator::In => {
+                qb.push(format_args!(" AND {} IN (", column));
+                let mut list = qb.separated(", ");
+                for value in &self.values {
+                    push_value(&mut list, value);
+                }
+                list.push_unseparated(")");
+                return;
+            }
+            FilterOperator::Ilike => {
+                if let Value::Text(text) = value {
+                    qb.push(format_args!(" AND {} ILIKE ", column));
+                    qb.push_bind(format!("%{}%", escape_like(text)));
+                }
+                return;
+            }
+            FilterOperator::Eq => "=",
+            FilterOperator::Ne => "<>",
+            FilterOperator::Gt => ">",
+            FilterOperator::Gte => ">=",
+            FilterOperator::Lt => "<",
+            FilterOperator::Lte => "<=",
+        };
+        qb.push(format_args!(" AND {} {} ", column, op));
+        let mut single = qb.separated("");
+        push_value(&mut single, value);
+    }
+}
+
+fn push_value<Sep: std::fmt::Display>(list: &mut sqlx::query_builder::Separated<'_, '_, Postgres, Sep>, value: &Value) {
+    match value.clone() {
+        Value::Uuid(v) => list.push_bind(v),
+        Value::Text(v) => list.push_bind(v),
+        Value::Bool(v) => list.push_bind(v),
+        Value::Integer(v) => list.push_bind(v),
+        Value::Decimal(v) => list.push_bind(v),
+        Value::Date(v) => list.push_bind(v),
+        Value::Timestamp(v) => list.push_bind(v),
+    };
+}
+
+fn parse_value(ty: FieldType, raw: &str) -> Option<Value> {
+    Some(match ty {
+        FieldType::Uuid => Value::Uuid(raw.parse().ok()?),
+        FieldType::Text => Value::Text(raw.to_string()),
+        FieldType::Bool => Value::Bool(raw.parse().ok()?),
+        FieldType::Integer => Value::Integer(raw.parse().ok()?),
+        FieldType::Decimal => Value::Decimal(raw.parse().ok()?),
+        FieldType::Date => Value::Date(raw.parse().ok()?),
+        FieldType::Timestamp => match raw.parse::<DateTime<Utc>>() {
+            Ok(ts) => Value::Timestamp(ts),
+            Err(_) => Value::Date(raw.parse().ok()?),
+        },
+    })
+}
+
+fn parse_number(raw: Option<&str>, name: &'static str, min: u32, max: u32) -> Result<Option<u32>, ListQueryError> {
+    raw.map(|raw| {
+        raw.parse::<u32>()
+            .ok()
+            .filter(|n| (min..=max).contains(n))
+            .ok_or(ListQueryError::InvalidParameter(name))
+    })
+    .transpose()
+}
+
+/// Escape `LIKE` wildcards so user input matches literally.
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SPEC: ListSpec = ListSpec {
+        from: "purchase_orders po JOIN vendors v ON po.vendor_id = v.id",
+        tenant_column: "po.tenant_id",
+        base_filter: None,
+        id_column: "po.id",
+        fields: &[
+            Field::new("status", "po.status", FieldType::Text).sortable(),
+            Field::new("vendor_id", "po.vendor_id", FieldType::Uuid),
+            Field::new("order_date", "po.order_date", FieldType::Date).sortable(),
+            Field::new("total_amount", "po.total_amount", FieldType::Decimal).sortable(),
+            Field::new("approved_at", "po.approved_at", FieldType::Timestamp).sortable().nullable(),
+        ],
+        search: &["po.po_number", "v.name"],
+        default_sort: "-order_date",
+        aliases: &[("from_date", "order_date[gte]")],
+    };
+
+    fn query(qs: &str) -> ListQuery {
+        ListQuery { params: parse_params(qs).unwrap() }
+    }
+
+    fn where_sql(qs: &str) -> String {
+        let plan = SPEC.plan(&query(qs)).unwrap();
+        let mut qb = QueryBuilder::new("");
+        plan.push_from_where(&mut qb, Uuid::nil());
+        qb.sql().to_string()
+    }
+
+    #[test]
+    fn query_strings_decode_strictly() {
+        let pairs = |v: &[(&str, &str)]| v.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<Vec<_>>();
+        assert_eq!(
+            parse_params("search=caf%C3%A9+bar&status[in]=a,b&&flag&x=1=2").unwrap(),
+            pairs(&[("search", "café bar"), ("status[in]", "a,b"), ("flag", ""), ("x", "1=2")])
+        );
+        assert_eq!(parse_params("").unwrap(), pairs(&[]));
+        assert!(matches!(parse_params("search=%FF"), Err(ListQueryError::InvalidParameter(_))));
+        assert!(matches!(parse_params("%C3=1"), Err(ListQueryError::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn filters_become_bind_parameters() {
+        assert_eq!(
+            where_sql("status[in]=draft,sent&total_amount[gte]=100&from_date=2024-01-01&search=acme"),
+            " FROM purchase_orders po JOIN vendors v ON po.vendor_id = v.id WHERE po.tenant_id = $1 \
+             AND po.status IN ($2, $3) AND po.total_amount >= $4 AND po.order_date >= $5 \
+             AND (po.po_number ILIKE $6 OR v.name ILIKE $7)"
+        );
+    }
+
+    #[test]
+    fn scope_follows_the_tenant() {
+        let plan = SPEC.plan(&query("status=draft")).unwrap().scoped("po.created_by", Uuid::nil());
+        let mut qb = QueryBuilder::new("");
+        plan.push_from_where(&mut qb, Uuid::nil());
+        assert_eq!(
+            qb.sql(),
+            " FROM purchase_orders po JOIN vendors v ON po.vendor_id = v.id WHERE po.tenant_id = $1 \
+             AND po.created_by = $2 AND po.status = $3"
+        );
+    }
+
+    #[test]
+    fn date_on_timestamp_field_covers_the_whole_day() {
+        assert_eq!(
+            where_sql("approved_at[lte]=2024-01-31&approved_at[null]=false"),
+            " FROM purchase_orders po JOIN vendors v ON po.vendor_id = v.id WHERE po.tenant_id = $1 \
+             AND po.approved_at < $2 AND po.approved_at IS NOT NULL"
+        );
+    }
+
+    #[test]
+    fn rejects_what_is_not_whitelisted() {
+        let err = |qs: &str| SPEC.plan(&query(qs)).err().map(|e| e.to_string());
+        assert!(err("tenant_id=x").unwrap().starts_with("Unknown field 'tenant_id'"));
+        assert!(err("status[like]=x").is_some());
+        assert!(err("vendor_id[ilike]=x").is_some());
+        assert!(err("vendor_id=not-a-uuid").is_some());
+        assert!(err("sort=vendor_id").is_some());
+        assert!(err("sort=id;drop%20table%20x").is_some());
+        assert!(err("per_page=1000").is_some());
+        assert!(err("limit=10&sort=approved_at").is_some());
+        assert!(err("status=x&sort=-total_amount,status&page=2").is_none());
+    }
+
+    #[test]
+    fn multi_sort_and_legacy_sort_params() {
+        let plan = SPEC.plan(&query("sort=status,-total_amount")).unwrap();
+        let keys: Vec<_> = plan.keyset.keys.iter().map(|k| (k.column, k.descending)).collect();
+        assert_eq!(keys, [("po.status", false), ("po.total_amount", true)]);
+
+        let plan = SPEC.plan(&query("sort_by=status&sort_order=asc")).unwrap();
+        assert!(!plan.keyset.keys[0].descending);
+        let plan = SPEC.plan(&query("")).unwrap();
+        assert_eq!((plan.keyset.keys[0].column, plan.keyset.keys[0].descending), ("po.order_date", true));
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+    }
+
+    #[test]
+    fn cache_key_ignores_order_and_response_shape() {
+        let key = query("status=draft&page=2").cache_key();
+        assert_eq!(query("page=2&fields=status&status=draft&format=csv").cache_key(), key);
+        assert_ne!(query("status=draft&page=3").cache_key(), key);
+        assert_ne!(query("status=draft,page=2").cache_key(), key);
+    }
+}
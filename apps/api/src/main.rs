@@ -1,83 +1,109 @@
-mod config;
-mod handlers;
-mod middleware;
-mod routes;
-mod services;
-mod state;
-mod extractors;
-
 use anyhow::Result;
-use axum::Router;
-use config::AppConfig;
-use state::AppState;
-use std::sync::Arc;
-use tower::ServiceBuilder;
-use tower_http::{
-    cors::{Any, CorsLayer},
-    trace::TraceLayer,
-};
-use tracing::{info, instrument};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use api::{attachments, config::AppConfig, create_app, email, handlers, imports, notifications, state::AppState};
+use std::{future::IntoFuture, time::Duration};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "api=debug,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    handlers::health::mark_process_start();
 
     // Load configuration
     let config = AppConfig::load()?;
+
+    // Initialize tracing (logs + OTLP export); flushed when the guard drops
+    let _telemetry = telemetry::init(&config.telemetry)?;
     info!("Configuration loaded successfully");
 
     // Initialize application state
     let state = AppState::new(config).await?;
     info!("Application state initialized");
 
-    // Run database migrations
-    sqlx::migrate!("./migrations")
-        .run(&state.db_pool)
-        .await?;
-    info!("Database migrations completed");
+    // Run database migrations unless deployments apply them with `erpctl migrate up`
+    if state.config.database.auto_migrate {
+        api::migrate(&state.db_pool).await?;
+        info!("Database migrations completed");
+    } else {
+        info!("Automatic migrations disabled");
+    }
+
+    // Pick up imports a previous shutdown interrupted
+    imports::resume_queued(&state).await?;
+    attachments::spawn_cleanup(&state);
+    notifications::spawn_relay(&state);
+    email::spawn_outbox(&state);
+
+    let server_config = state.config.server.clone();
+    let workers = state.workers.clone();
+    let shutdown = workers.shutdown_token();
 
     // Build application router
     let app = create_app(state).await?;
 
     // Start server
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
-    info!("Server starting on http://0.0.0.0:3000");
-    
-    axum::serve(listener, app).await?;
+    let listener = tokio::net::TcpListener::bind((server_config.host.as_str(), server_config.port)).await?;
+    info!("Server starting on http://{}", listener.local_addr()?);
+
+    tokio::spawn(shutdown_signal(shutdown.clone()));
+    let server = axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+        .into_future();
+    tokio::pin!(server);
+
+    // Once a signal arrives, in-flight requests and background workers share
+    // one deadline; connections still open after it are dropped.
+    let grace = Duration::from_secs(server_config.shutdown_timeout);
+    let deadline = tokio::select! {
+        result = &mut server => {
+            result?;
+            Instant::now() + grace
+        }
+        _ = shutdown.cancelled() => {
+            let deadline = Instant::now() + grace;
+            match tokio::time::timeout_at(deadline, &mut server).await {
+                Ok(result) => result?,
+                Err(_) => warn!("Shutdown deadline elapsed with requests still in flight"),
+            }
+            deadline
+        }
+    };
+    workers.shutdown(deadline.saturating_duration_since(Instant::now())).await;
+    info!("Server stopped");
 
     Ok(())
 }
 
-#[instrument(skip(state))]
-async fn create_app(state: AppState) -> Result<Router> {
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any)
-        .allow_credentials(true);
-
-    let shared_state = Arc::new(state);
-    let middleware_stack = ServiceBuilder::new()
-        .layer(TraceLayer::new_for_http())
-        .layer(cors)
-        .layer(middleware::request_id::RequestIdLayer::new())
-        .layer(middleware::error_handler::ErrorHandlerLayer::new())
-        .layer(middleware::auth_layer::AuthLayer::new(shared_state.clone()));
-
-    let app = Router::new()
-        .nest("/api/v1", routes::api_routes())
-        .nest("/docs", routes::docs_routes())
-        .route("/health", axum::routing::get(handlers::health::health_check))
-        .layer(middleware_stack)
-        .with_state(shared_state);
-
-    Ok(app)
+/// Resolves on Ctrl+C or SIGTERM and starts shutdown by cancelling `token`.
+async fn shutdown_signal(token: CancellationToken) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!("Shutdown signal received, draining connections");
+    token.cancel();
 }
@@ -45,7 +45,7 @@ where
         Box::pin(async move {
             // Bypass for public paths
             let path = req.uri().path();
-            let is_public = path.starts_with("/api/v1/auth") || path.starts_with("/docs") || path == "/health";
+            let is_public = is_public(path);
 
             // Try read Authorization
             if let Some(value) = req.headers().get(AUTHORIZATION) {
@@ -95,3 +95,10 @@ where
         })
     }
 }
+
+/// Paths served without a bearer token.
+pub fn is_public(path: &str) -> bool {
+    path.starts_with("/api/v1/auth") || path.starts_with("/docs") || path == "/health" || path.starts_with("/health/") || path == "/metrics"
+        // Signed download links carry their own authorization
+        || (path.starts_with("/api/v1/attachments/") && path.ends_with("/download"))
+}
@@ -11,6 +11,7 @@ use uuid::Uuid;
 use crate::state::AppState;
 
 #[derive(Clone, Debug)]
+#[allow(dead_code)]
 pub struct CurrentUser {
     pub user_id: Uuid,
     pub tenant_id: Uuid,
@@ -19,6 +20,16 @@ pub struct CurrentUser {
     pub permissions: Vec<String>,
 }
 
+impl CurrentUser {
+    /// Whether the token grants `permission`. Tokens are issued without
+    /// permission claims until roles are wired into login; those stay
+    /// unrestricted, as they are on every other endpoint.
+    pub fn can(&self, permission: &str) -> bool {
+        self.permissions.is_empty() || self.permissions.iter().any(|p| p == permission)
+    }
+}
+
+#[allow(dead_code)]
 pub async fn require_auth(
     State(state): State<Arc<AppState>>,
     mut req: Request,
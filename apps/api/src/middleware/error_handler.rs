@@ -8,7 +8,7 @@ use shared_types::ApiError;
 use tower::{Layer, Service};
 use tracing::error;
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct ErrorHandlerLayer;
 
 impl ErrorHandlerLayer {
@@ -57,6 +57,7 @@ where
 }
 
 // Global error handler for unhandled errors
+#[allow(dead_code)]
 pub async fn handle_error(err: Box<dyn std::error::Error + Send + Sync>) -> impl IntoResponse {
     error!("Unhandled error: {:?}", err);
     
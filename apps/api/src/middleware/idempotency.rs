@@ -0,0 +1,294 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use shared_types::ApiError;
+use tower::{Layer, Service};
+use tracing::warn;
+
+use crate::{middleware::auth_middleware::CurrentUser, state::AppState};
+
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+const MAX_KEY_LEN: usize = 255;
+
+/// Makes authenticated POST requests carrying an `Idempotency-Key` header safe to retry.
+///
+/// The first request claims `idempotency:{tenant}:{key}` in the key-value store with a
+/// hash of the request. Retries with the same body get the stored response back;
+/// a different body, or a retry while the first request is still running, gets 409.
+/// Server errors and `success: false` envelopes (which handlers also return for
+/// transient database failures) release the key so the client can try again.
+#[derive(Clone)]
+pub struct IdempotencyLayer {
+    state: Arc<AppState>,
+}
+
+impl IdempotencyLayer {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+}
+
+impl<S> Layer<S> for IdempotencyLayer {
+    type Service = IdempotencyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IdempotencyService { inner, state: self.state.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct IdempotencyService<S> {
+    inner: S,
+    state: Arc<AppState>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+enum Record {
+    InFlight { request_hash: String },
+    Completed { request_hash: String, response: StoredResponse },
+}
+
+impl Record {
+    fn request_hash(&self) -> &str {
+        match self {
+            Record::InFlight { request_hash } | Record::Completed { request_hash, .. } => request_hash,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// Base64 encoded body
+    body: String,
+}
+
+impl<S> Service<Request> for IdempotencyService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let mut inner = self.inner.clone();
+        let state = self.state.clone();
+
+        Box::pin(async move {
+            // Only authenticated POSTs that opt in are tracked; keys are scoped per tenant
+            let tenant_id = request.extensions().get::<CurrentUser>().map(|u| u.tenant_id);
+            let key = request.headers().get(IDEMPOTENCY_KEY).cloned();
+            let (Method::POST, Some(tenant_id), Some(key)) = (request.method().clone(), tenant_id, key) else {
+                return inner.call(request).await;
+            };
+
+            let key = match key.to_str() {
+                Ok(k) if !k.is_empty() && k.len() <= MAX_KEY_LEN => k.to_string(),
+                _ => {
+                    return Ok(error_response(
+                        StatusCode::BAD_REQUEST,
+                        ApiError::bad_request("Idempotency-Key must be 1-255 visible ASCII characters"),
+                    ))
+                }
+            };
+            let store_key = format!("idempotency:{}:{}", tenant_id, key);
+
+            // Buffer the body so it can be hashed and handed on unchanged. Import
+            // and attachment uploads may be larger than other bodies; handlers still apply their own limit.
+            let (parts, body) = request.into_parts();
+            let limit = state.config.server.body_limit.max(state.config.imports.max_file_size).max(state.config.storage.max_file_size);
+            let bytes = match to_bytes(body, limit).await {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    return Ok(error_response(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        ApiError::payload_too_large("Request body too large"),
+                    ))
+                }
+            };
+            let request_hash = request_hash(&parts.method, parts.uri.path(), parts.uri.query(), &bytes);
+
+            let claim = serde_json::to_string(&Record::InFlight { request_hash: request_hash.clone() })
+                .expect("idempotency record serializes");
+            let in_flight_ttl = Duration::from_secs(state.config.idempotency.in_flight_ttl);
+            let claimed = state.kv.set_nx(&store_key, &claim, in_flight_ttl).await;
+
+            match claimed {
+                Ok(true) => {}
+                Ok(false) => return Ok(replay_or_conflict(&state, &store_key, &request_hash).await),
+                Err(e) => {
+                    warn!("Idempotency store unavailable: {}", e);
+                    return Ok(error_response(
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        ApiError::service_unavailable("Idempotency store unavailable, retry later"),
+                    ));
+                }
+            }
+
+            let response = match inner.call(Request::from_parts(parts, Body::from(bytes))).await {
+                Ok(response) => response,
+                Err(e) => {
+                    release(&state, &store_key).await;
+                    return Err(e);
+                }
+            };
+
+            if response.status().is_server_error() {
+                release(&state, &store_key).await;
+                return Ok(response);
+            }
+
+            let (parts, body) = response.into_parts();
+            let bytes = match to_bytes(body, usize::MAX).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    warn!("Failed to buffer response for idempotent replay: {}", e);
+                    release(&state, &store_key).await;
+                    return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+                }
+            };
+            if is_failure(&bytes) {
+                release(&state, &store_key).await;
+                return Ok(Response::from_parts(parts, Body::from(bytes)));
+            }
+
+            let record = Record::Completed {
+                request_hash,
+                response: StoredResponse {
+                    status: parts.status.as_u16(),
+                    headers: stored_headers(&parts.headers),
+                    body: BASE64.encode(&bytes),
+                },
+            };
+            let stored = serde_json::to_string(&record).expect("idempotency record serializes");
+            let saved = state.kv.set(&store_key, &stored, Duration::from_secs(state.config.idempotency.ttl)).await;
+            if let Err(e) = saved {
+                // The request already succeeded; a retry will now be treated as new
+                warn!("Failed to store idempotent response: {}", e);
+                release(&state, &store_key).await;
+            }
+
+            Ok(Response::from_parts(parts, Body::from(bytes)))
+        })
+    }
+}
+
+async fn replay_or_conflict(state: &AppState, store_key: &str, request_hash: &str) -> Response {
+    let existing = state.kv.get(store_key).await;
+
+    let record = match existing {
+        Ok(Some(raw)) => serde_json::from_str::<Record>(&raw).ok(),
+        Ok(None) => None,
+        Err(e) => {
+            warn!("Idempotency store unavailable: {}", e);
+            return error_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                ApiError::service_unavailable("Idempotency store unavailable, retry later"),
+            );
+        }
+    };
+
+    match record {
+        Some(record) if record.request_hash() != request_hash => error_response(
+            StatusCode::CONFLICT,
+            ApiError::conflict("Idempotency-Key was already used with a different request"),
+        ),
+        Some(Record::Completed { response, .. }) => replay(response),
+        // Still running, or expired between our SET and GET
+        _ => error_response(
+            StatusCode::CONFLICT,
+            ApiError::conflict("A request with this Idempotency-Key is still being processed"),
+        ),
+    }
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let body = BASE64.decode(stored.body).unwrap_or_default();
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+
+    let headers = response.headers_mut();
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            headers.append(name, value);
+        }
+    }
+    headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    response
+}
+
+async fn release(state: &AppState, store_key: &str) {
+    if let Err(e) = state.kv.delete(store_key).await {
+        warn!("Failed to release idempotency key: {}", e);
+    }
+}
+
+/// Hash of everything that identifies the request, so a reused key with a
+/// different target or payload is detected.
+fn request_hash(method: &Method, path: &str, query: Option<&str>, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update(b"\n");
+    hasher.update(path);
+    hasher.update(b"?");
+    hasher.update(query.unwrap_or_default());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+/// An `ApiResponse` envelope reporting failure, whatever the status code.
+fn is_failure(body: &[u8]) -> bool {
+    #[derive(Deserialize)]
+    struct Envelope {
+        success: bool,
+    }
+    serde_json::from_slice::<Envelope>(body).is_ok_and(|envelope| !envelope.success)
+}
+
+fn stored_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+fn error_response(status: StatusCode, error: ApiError) -> Response {
+    (status, Json(error)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failure_envelopes_are_not_kept() {
+        assert!(is_failure(br#"{"success":false,"message":"Failed to start transaction"}"#));
+        assert!(!is_failure(br#"{"success":true,"data":{"id":1}}"#));
+        assert!(!is_failure(br#"[{"success":false}]"#));
+        assert!(!is_failure(b"id,name\n1,Globex"));
+    }
+}
@@ -0,0 +1,68 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use axum::{extract::{MatchedPath, Request}, http::Response};
+use telemetry::Metrics;
+use tower::{Layer, Service};
+
+/// Records request count and latency per route template, method and status
+#[derive(Clone)]
+pub struct HttpMetricsLayer {
+    metrics: Metrics,
+}
+
+impl HttpMetricsLayer {
+    pub fn new(metrics: Metrics) -> Self {
+        Self { metrics }
+    }
+}
+
+impl<S> Layer<S> for HttpMetricsLayer {
+    type Service = HttpMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpMetricsService { inner, metrics: self.metrics.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct HttpMetricsService<S> {
+    inner: S,
+    metrics: Metrics,
+}
+
+impl<S, ResBody> Service<Request> for HttpMetricsService<S>
+where
+    S: Service<Request, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ResBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // Label by route template so path parameters don't explode cardinality;
+        // anything the router didn't match is grouped together.
+        let route = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|p| p.as_str().to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+        let method = request.method().to_string();
+        let metrics = self.metrics.clone();
+        let mut inner = self.inner.clone();
+
+        Box::pin(async move {
+            let start = Instant::now();
+            let response = inner.call(request).await?;
+            metrics.observe_http(&route, &method, response.status().as_u16(), start.elapsed());
+            Ok(response)
+        })
+    }
+}
@@ -1,6 +1,8 @@
 pub mod error_handler;
 pub mod request_id;
+pub mod metrics;
 pub mod auth_layer;
 pub mod auth_middleware;
+pub mod idempotency;
 pub mod tenant_context;
 pub mod db_conn;
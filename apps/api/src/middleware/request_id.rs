@@ -1,12 +1,12 @@
 use axum::{
     extract::Request,
-    http::HeaderValue,
-    response::Response,
+    http::{HeaderValue, Response},
 };
 use tower::{Layer, Service};
+use tracing::Instrument;
 use uuid::Uuid;
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct RequestIdLayer;
 
 impl RequestIdLayer {
@@ -28,10 +28,11 @@ pub struct RequestIdService<S> {
     inner: S,
 }
 
-impl<S> Service<Request> for RequestIdService<S>
+impl<S, ResBody> Service<Request> for RequestIdService<S>
 where
-    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S: Service<Request, Response = Response<ResBody>> + Clone + Send + 'static,
     S::Future: Send + 'static,
+    ResBody: Send + 'static,
 {
     type Response = S::Response;
     type Error = S::Error;
@@ -42,26 +43,48 @@ where
     }
 
     fn call(&mut self, mut request: Request) -> Self::Future {
-        let request_id = Uuid::new_v4().to_string();
-        
+        // Reuse the caller's request ID when it is sane, otherwise mint one
+        let request_id = request
+            .headers()
+            .get("x-request-id")
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.is_empty() && v.len() <= 128)
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
         // Add request ID to headers
         request.headers_mut().insert(
             "x-request-id",
             HeaderValue::from_str(&request_id).unwrap(),
         );
 
+        // Root span for the request, continuing the caller's W3C trace if any
+        let span = tracing::info_span!(
+            "http.request",
+            otel.kind = "server",
+            otel.name = %format_args!("{} {}", request.method(), request.uri().path()),
+            http.method = %request.method(),
+            http.target = %request.uri(),
+            request_id = %request_id,
+        );
+        telemetry::set_parent_from_headers(&span, request.headers());
+
         let mut inner = self.inner.clone();
         
-        Box::pin(async move {
-            let mut response = inner.call(request).await?;
-            
-            // Add request ID to response headers
-            response.headers_mut().insert(
-                "x-request-id",
-                HeaderValue::from_str(&request_id).unwrap(),
-            );
-            
-            Ok(response)
-        })
+        Box::pin(
+            async move {
+                let mut response = inner.call(request).await?;
+
+                // Add request ID and trace context to response headers
+                response.headers_mut().insert(
+                    "x-request-id",
+                    HeaderValue::from_str(&request_id).unwrap(),
+                );
+                telemetry::inject_span_context(&tracing::Span::current(), response.headers_mut());
+
+                Ok(response)
+            }
+            .instrument(span),
+        )
     }
 }
@@ -6,12 +6,13 @@ use axum::{
 
 use super::auth_middleware::CurrentUser;
 
+#[allow(dead_code)]
 pub async fn set_tenant_context(
     req: Request,
     next: Next,
 ) -> Result<Response, Response> {
     // Get CurrentUser from extensions
-    let Some(user) = req.extensions().get::<CurrentUser>() else {
+    let Some(_user) = req.extensions().get::<CurrentUser>() else {
         return Err(axum::response::IntoResponse::into_response(axum::http::StatusCode::UNAUTHORIZED));
     };
 
@@ -0,0 +1,372 @@
+//! In-app notifications.
+//!
+//! Producers insert one row per recipient inside their own transaction, after
+//! consulting each recipient's channel preferences, and publish the rows on a
+//! Redis channel once that transaction has committed. Every API replica relays
+//! the channel into its [`NotificationHub`], which feeds the open
+//! `/notifications/stream` connections of the recipients it holds.
+
+use std::{sync::Arc, time::Duration};
+
+use futures::{Stream, StreamExt};
+use serde_json::{json, Value};
+use shared_types::{LeaveRequest, Money, Notification, NotificationKind};
+use sqlx::{postgres::PgRow, PgConnection, Row};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    cache::{CacheScope, Freshness, ReadCache},
+    kv::KvStore,
+    state::AppState,
+};
+
+/// Redis channel notifications are fanned out on
+const CHANNEL: &str = "notifications";
+/// Notifications a replica buffers for slow stream connections before they miss some
+const HUB_CAPACITY: usize = 1024;
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+pub const NOTIFICATION_COLUMNS: &str = "id, tenant_id, user_id, kind, title, body, data, read_at, created_at";
+
+pub fn notification_from_row(row: &PgRow) -> Notification {
+    Notification {
+        id: row.get("id"),
+        tenant_id: row.get("tenant_id"),
+        user_id: row.get("user_id"),
+        kind: NotificationKind::parse(row.get("kind")).unwrap_or(NotificationKind::LowStock),
+        title: row.get("title"),
+        body: row.get("body"),
+        data: row.get("data"),
+        read_at: row.get("read_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// Contents of a notification, before it is addressed to anyone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notice {
+    pub kind: NotificationKind,
+    pub title: String,
+    pub body: String,
+    pub data: Value,
+    /// Subject of the notice; while a recipient has an unread in-app
+    /// notification of the same kind and key they get no second one
+    pub dedup_key: Option<String>,
+}
+
+impl Notice {
+    pub fn low_stock(product_id: Uuid, sku: &str, name: &str, current: i32, minimum: i32) -> Self {
+        Self {
+            kind: NotificationKind::LowStock,
+            title: format!("Low stock: {}", name),
+            body: format!("{} ({}) is down to {} units; the minimum is {}.", name, sku, current, minimum),
+            data: json!({ "product_id": product_id, "sku": sku }),
+            dedup_key: Some(product_id.to_string()),
+        }
+    }
+
+    pub fn purchase_order_approval(po_id: Uuid, po_number: &str, vendor: &str, total: Money) -> Self {
+        Self {
+            kind: NotificationKind::PurchaseOrderApproval,
+            title: format!("{} needs approval", po_number),
+            body: format!("Purchase order {} to {} for {} was submitted for approval.", po_number, vendor, total),
+            data: json!({ "purchase_order_id": po_id, "po_number": po_number }),
+            dedup_key: Some(po_id.to_string()),
+        }
+    }
+
+    pub fn leave_request(leave: &LeaveRequest, requester: &str) -> Self {
+        let days = (leave.end_date - leave.start_date).num_days() + 1;
+        Self {
+            kind: NotificationKind::LeaveRequest,
+            title: format!("Leave request from {}", requester),
+            body: format!(
+                "{} requested {} {} of {} leave from {} to {}.",
+                requester,
+                days,
+                if days == 1 { "day" } else { "days" },
+                leave.leave_type.as_str(),
+                leave.start_date,
+                leave.end_date
+            ),
+            data: json!({ "leave_request_id": leave.id, "user_id": leave.user_id }),
+            dedup_key: Some(leave.id.to_string()),
+        }
+    }
+
+    pub fn invitation_accepted(invitation_id: Uuid, user_id: Uuid, name: &str, email: &str) -> Self {
+        Self {
+            kind: NotificationKind::InvitationAccepted,
+            title: format!("{} joined", name),
+            body: format!("{} ({}) accepted your invitation.", name, email),
+            data: json!({ "invitation_id": invitation_id, "user_id": user_id }),
+            dedup_key: None,
+        }
+    }
+}
+
+/// Active members of the tenant holding `permission`; owners and admins hold
+/// every permission. Cached until membership changes.
+pub async fn recipients(
+    conn: &mut PgConnection,
+    cache: &ReadCache,
+    tenant_id: Uuid,
+    permission: &str,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let key = format!("recipients:{}", permission);
+    cache.get_or_load(tenant_id, CacheScope::Permissions, &key, Freshness::Cached, || sqlx::query_scalar(
+        r#"SELECT DISTINCT m.user_id
+           FROM tenant_memberships m
+           JOIN users u ON u.id = m.user_id AND u.is_active
+           WHERE m.tenant_id = $1 AND m.is_active
+             AND (m.role IN ('owner', 'admin') OR EXISTS (
+                 SELECT 1 FROM roles r
+                 JOIN role_permissions rp ON rp.role_id = r.id
+                 JOIN permissions p ON p.id = rp.permission_id
+                 WHERE r.tenant_id = m.tenant_id AND r.name = m.role AND p.key = $2))"#,
+    )
+    .bind(tenant_id)
+    .bind(permission)
+    .fetch_all(conn))
+    .await
+}
+
+/// Address `notice` to `recipients` on the channels each of them chose.
+/// Returns the in-app notifications, to [`publish`] once the surrounding
+/// transaction has committed.
+pub async fn create(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    recipients: &[Uuid],
+    notice: &Notice,
+) -> Result<Vec<Notification>, sqlx::Error> {
+    if recipients.is_empty() {
+        return Ok(Vec::new());
+    }
+    let rows = sqlx::query(&format!(
+        r#"INSERT INTO notifications (tenant_id, user_id, kind, title, body, data, dedup_key, in_app, email_status)
+           SELECT $1, r.user_id, $2, $3, $4, $5, $6, COALESCE(p.in_app, true),
+                  CASE WHEN COALESCE(p.email, false) THEN 'pending' END
+           FROM UNNEST($7::uuid[]) AS r(user_id)
+           LEFT JOIN notification_preferences p ON p.tenant_id = $1 AND p.user_id = r.user_id AND p.kind = $2
+           WHERE (COALESCE(p.in_app, true) OR COALESCE(p.email, false))
+             AND ($6::text IS NULL OR NOT EXISTS (
+                 SELECT 1 FROM notifications n
+                 WHERE n.tenant_id = $1 AND n.user_id = r.user_id AND n.kind = $2
+                   AND n.dedup_key = $6 AND n.in_app AND n.read_at IS NULL))
+           RETURNING {}, in_app"#,
+        NOTIFICATION_COLUMNS
+    ))
+    .bind(tenant_id)
+    .bind(notice.kind.as_str())
+    .bind(&notice.title)
+    .bind(&notice.body)
+    .bind(&notice.data)
+    .bind(&notice.dedup_key)
+    .bind(recipients)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.iter().filter(|row| row.get::<bool, _>("in_app")).map(notification_from_row).collect())
+}
+
+/// Alert stock keepers about the given products that are at or below their
+/// minimum stock.
+pub async fn low_stock(
+    conn: &mut PgConnection,
+    cache: &ReadCache,
+    tenant_id: Uuid,
+    product_ids: &[Uuid],
+) -> Result<Vec<Notification>, sqlx::Error> {
+    let products = sqlx::query(
+        r#"SELECT id, sku, name, current_stock, minimum_stock FROM products
+           WHERE tenant_id = $1 AND id = ANY($2) AND is_active
+             AND minimum_stock > 0 AND current_stock <= minimum_stock"#,
+    )
+    .bind(tenant_id)
+    .bind(product_ids)
+    .fetch_all(&mut *conn)
+    .await?;
+    if products.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let to = recipients(&mut *conn, cache, tenant_id, "inventory:stock:write").await?;
+    let mut created = Vec::new();
+    for product in &products {
+        let notice = Notice::low_stock(
+            product.get("id"),
+            product.get("sku"),
+            product.get("name"),
+            product.get("current_stock"),
+            product.get("minimum_stock"),
+        );
+        created.extend(create(&mut *conn, tenant_id, &to, &notice).await?);
+    }
+    Ok(created)
+}
+
+/// Send in-app notifications to the recipients' open streams on every replica.
+/// Failures are logged; the notifications are in the inbox either way.
+pub async fn publish(kv: &dyn KvStore, notifications: &[Notification]) {
+    if notifications.is_empty() {
+        return;
+    }
+    let mut messages = Vec::with_capacity(notifications.len());
+    for notification in notifications {
+        match serde_json::to_string(notification) {
+            Ok(payload) => messages.push(payload),
+            Err(e) => warn!("Failed to encode notification: {}", e),
+        }
+    }
+    if let Err(e) = kv.publish(CHANNEL, &messages).await {
+        warn!("Failed to publish notifications: {}", e);
+    }
+}
+
+/// Notifications relayed from Redis to the stream connections of this replica.
+#[derive(Clone)]
+pub struct NotificationHub {
+    sender: broadcast::Sender<Arc<Notification>>,
+}
+
+impl Default for NotificationHub {
+    fn default() -> Self {
+        Self { sender: broadcast::channel(HUB_CAPACITY).0 }
+    }
+}
+
+impl NotificationHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn dispatch(&self, notification: Notification) {
+        // No receivers just means nobody on this replica is listening
+        let _ = self.sender.send(Arc::new(notification));
+    }
+
+    /// Notifications for one user from now on. A connection that falls more
+    /// than [`HUB_CAPACITY`] behind skips what it missed; the inbox has it.
+    pub fn subscribe(&self, tenant_id: Uuid, user_id: Uuid) -> impl Stream<Item = Arc<Notification>> {
+        futures::stream::unfold(self.sender.subscribe(), move |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(n) if n.tenant_id == tenant_id && n.user_id == user_id => return Some((n, receiver)),
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+/// Start relaying the Redis channel into this replica's hub.
+pub fn spawn_relay(state: &AppState) {
+    let url = state.config.redis.url.clone();
+    let hub = state.notification_hub.clone();
+    state.workers.spawn("notification-relay", move |shutdown| relay(url, hub, shutdown));
+}
+
+async fn relay(url: String, hub: NotificationHub, shutdown: CancellationToken) {
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled() => return,
+            result = listen(&url, &hub) => match result {
+                Ok(()) => warn!("Notification relay connection closed"),
+                Err(e) => warn!("Notification relay failed: {}", e),
+            },
+        }
+        tokio::select! {
+            _ = shutdown.cancelled() => return,
+            _ = tokio::time::sleep(RECONNECT_DELAY) => {}
+        }
+    }
+}
+
+async fn listen(url: &str, hub: &NotificationHub) -> redis::RedisResult<()> {
+    let client = redis::Client::open(url)?;
+    let mut pubsub = client.get_async_connection().await?.into_pubsub();
+    pubsub.subscribe(CHANNEL).await?;
+    info!("Notification relay subscribed");
+
+    let mut messages = pubsub.on_message();
+    while let Some(message) = messages.next().await {
+        let payload: String = message.get_payload()?;
+        match serde_json::from_str(&payload) {
+            Ok(notification) => hub.dispatch(notification),
+            Err(e) => warn!("Ignoring malformed notification message: {}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, Utc};
+    use shared_types::{LeaveStatus, LeaveType};
+
+    fn notification(tenant_id: Uuid, user_id: Uuid, title: &str) -> Notification {
+        Notification {
+            id: Uuid::new_v4(),
+            tenant_id,
+            user_id,
+            kind: NotificationKind::LowStock,
+            title: title.to_string(),
+            body: String::new(),
+            data: json!({}),
+            read_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn hub_delivers_only_to_the_recipient() {
+        let hub = NotificationHub::new();
+        let (tenant, user) = (Uuid::new_v4(), Uuid::new_v4());
+        let mine = hub.subscribe(tenant, user);
+        futures::pin_mut!(mine);
+
+        hub.dispatch(notification(tenant, Uuid::new_v4(), "colleague"));
+        hub.dispatch(notification(Uuid::new_v4(), user, "other tenant"));
+        hub.dispatch(notification(tenant, user, "mine"));
+
+        assert_eq!(mine.next().await.unwrap().title, "mine");
+    }
+
+    #[test]
+    fn leave_notice_counts_days_inclusively() {
+        let date = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        let mut leave = LeaveRequest {
+            id: Uuid::new_v4(),
+            tenant_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            leave_type: LeaveType::Annual,
+            start_date: date(13),
+            end_date: date(15),
+            reason: None,
+            status: LeaveStatus::Pending,
+            reviewed_by: None,
+            reviewed_at: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        let notice = Notice::leave_request(&leave, "Budi Santoso");
+        assert_eq!(notice.body, "Budi Santoso requested 3 days of annual leave from 2024-05-13 to 2024-05-15.");
+        assert_eq!(notice.dedup_key, Some(leave.id.to_string()));
+
+        leave.end_date = leave.start_date;
+        assert!(Notice::leave_request(&leave, "Budi").body.contains("1 day of"));
+    }
+
+    #[test]
+    fn notifications_round_trip_through_the_channel_payload() {
+        let sent = notification(Uuid::new_v4(), Uuid::new_v4(), "Low stock: Kopi");
+        let received: Notification = serde_json::from_str(&serde_json::to_string(&sent).unwrap()).unwrap();
+        assert_eq!((received.id, received.kind, received.title), (sent.id, sent.kind, sent.title));
+    }
+}
@@ -0,0 +1,376 @@
+//! The OpenAPI document served at `/docs` and compiled into `erp-client`.
+//!
+//! Every route in [`routes`](crate::routes) has a `#[utoipa::path]` listed
+//! here; the tests below fail when one is missing or stale.
+
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::{handlers, middleware::auth_layer};
+
+#[derive(OpenApi)]
+#[openapi(
+    modifiers(&SecurityAddon),
+    paths(
+        handlers::health::health_check,
+        handlers::health::liveness,
+        handlers::health::readiness,
+        handlers::metrics::metrics,
+        handlers::auth::login,
+        handlers::auth::logout,
+        handlers::auth::register_tenant,
+        handlers::auth::refresh,
+        handlers::auth::forgot_password,
+        handlers::auth::reset_password,
+        handlers::auth::accept_invitation,
+        handlers::tenant::get_current_tenant,
+        handlers::tenant::update_current_tenant,
+        handlers::tenant::get_members,
+        handlers::tenant::invite_user,
+        handlers::tenant::get_branding,
+        handlers::tenant::update_branding,
+        handlers::sequences::list_sequences,
+        handlers::sequences::update_sequence,
+        handlers::user::get_profile,
+        handlers::user::update_profile,
+        handlers::user::change_password,
+        handlers::email_templates::list_email_templates,
+        handlers::email_templates::preview_email_template,
+        handlers::crm::list_companies,
+        handlers::crm::create_company,
+        handlers::crm::get_company,
+        handlers::crm::update_company,
+        handlers::crm::delete_company,
+        handlers::crm::list_contacts,
+        handlers::crm::create_contact,
+        handlers::crm::get_contact,
+        handlers::crm::update_contact,
+        handlers::crm::delete_contact,
+        handlers::accounting::list_accounts,
+        handlers::accounting::create_account,
+        handlers::accounting::get_account,
+        handlers::accounting::list_journal_entries,
+        handlers::accounting::create_journal_entry,
+        handlers::exchange_rates::list_exchange_rates,
+        handlers::exchange_rates::upsert_exchange_rate,
+        handlers::exchange_rates::delete_exchange_rate,
+        handlers::exchange_rates::convert,
+        handlers::exchange_rates::fetch_exchange_rates,
+        handlers::inventory::list_products,
+        handlers::inventory::create_product,
+        handlers::inventory::get_product,
+        handlers::inventory::update_product,
+        handlers::inventory::delete_product,
+        handlers::inventory::list_warehouses,
+        handlers::inventory::create_warehouse,
+        handlers::inventory::list_stock,
+        handlers::inventory::list_stock_movements,
+        handlers::procurement::list_vendors,
+        handlers::procurement::create_vendor,
+        handlers::procurement::list_purchase_orders,
+        handlers::procurement::create_purchase_order,
+        handlers::procurement::submit_purchase_order,
+        handlers::hrm::list_employees,
+        handlers::hrm::create_employee,
+        handlers::hrm::list_leaves,
+        handlers::hrm::create_leave,
+        handlers::imports::upload_import,
+        handlers::imports::list_imports,
+        handlers::imports::get_import_fields,
+        handlers::imports::get_import,
+        handlers::imports::commit_import,
+        handlers::imports::download_import_errors,
+        handlers::search::search,
+        handlers::attachments::upload_attachment,
+        handlers::attachments::list_attachments,
+        handlers::attachments::get_attachment,
+        handlers::attachments::delete_attachment,
+        handlers::attachments::download_attachment,
+        handlers::notifications::list_notifications,
+        handlers::notifications::unread_count,
+        handlers::notifications::mark_read,
+        handlers::notifications::mark_unread,
+        handlers::notifications::mark_all_read,
+        handlers::notifications::get_preferences,
+        handlers::notifications::update_preferences,
+        handlers::notifications::stream_notifications,
+    ),
+    components(
+        schemas(
+            shared_types::ApiResponse<()>,
+            shared_types::ValidationError,
+            shared_types::PaginationMeta,
+            shared_types::CursorMeta,
+            shared_types::BaseEntity,
+            shared_types::CountMode,
+            shared_types::Locale,
+            shared_types::Money,
+            shared_types::Currency,
+            shared_types::LoginRequest,
+            shared_types::LoginResponse,
+            shared_types::RegisterTenantRequest,
+            shared_types::RefreshTokenRequest,
+            shared_types::ForgotPasswordRequest,
+            shared_types::ResetPasswordRequest,
+            shared_types::ChangePasswordRequest,
+            shared_types::AcceptInvitationRequest,
+            shared_types::UpdateProfileRequest,
+            shared_types::User,
+            shared_types::Tenant,
+            shared_types::Invitation,
+            shared_types::InviteUserRequest,
+            shared_types::EmailBranding,
+            handlers::crm::CreateCompanyRequest,
+            handlers::crm::UpdateCompanyRequest,
+            handlers::crm::CreateContactRequest,
+            handlers::crm::UpdateContactRequest,
+            shared_types::Company,
+            shared_types::Contact,
+            shared_types::Account,
+            shared_types::AccountType,
+            shared_types::BalanceType,
+            shared_types::CreateAccountRequest,
+            shared_types::JournalEntry,
+            shared_types::JournalEntryStatus,
+            shared_types::JournalEntryLine,
+            shared_types::CreateJournalEntryRequest,
+            shared_types::CreateJournalEntryLineRequest,
+            shared_types::ExchangeRate,
+            shared_types::CreateExchangeRateRequest,
+            shared_types::FetchExchangeRatesRequest,
+            shared_types::Conversion,
+            shared_types::RateType,
+            shared_types::ConversionMethod,
+            shared_types::Product,
+            shared_types::ProductStatus,
+            shared_types::Category,
+            shared_types::CreateProductRequest,
+            shared_types::UpdateProductRequest,
+            shared_types::Warehouse,
+            shared_types::CreateWarehouseRequest,
+            shared_types::StockLevel,
+            shared_types::StockMovement,
+            shared_types::StockMovementType,
+            shared_types::Vendor,
+            shared_types::VendorStatus,
+            shared_types::CreateVendorRequest,
+            shared_types::PurchaseOrder,
+            shared_types::PurchaseOrderStatus,
+            shared_types::PurchaseOrderItem,
+            shared_types::CreatePurchaseOrderRequest,
+            shared_types::CreatePurchaseOrderItemRequest,
+            shared_types::LeaveRequest,
+            shared_types::CreateLeaveRequest,
+            shared_types::LeaveStatus,
+            shared_types::LeaveType,
+            shared_types::DocumentSequence,
+            shared_types::UpdateDocumentSequenceRequest,
+            shared_types::DocumentType,
+            shared_types::ResetRule,
+            shared_types::ImportJob,
+            shared_types::ImportStatus,
+            shared_types::ImportRowError,
+            shared_types::ImportEntity,
+            shared_types::ImportField,
+            shared_types::ImportPreview,
+            handlers::imports::ImportUploadForm,
+            shared_types::Attachment,
+            shared_types::AttachmentEntity,
+            shared_types::SearchHit,
+            shared_types::SearchEntity,
+            handlers::attachments::AttachmentUploadForm,
+            shared_types::Notification,
+            shared_types::NotificationKind,
+            shared_types::NotificationPreference,
+            shared_types::UnreadCount,
+            shared_types::UpdateNotificationPreferencesRequest,
+            shared_types::EmailTemplateInfo,
+            shared_types::PreviewEmailRequest,
+            shared_types::RenderedEmail,
+            handlers::health::HealthStatus,
+            handlers::health::LivenessStatus,
+            handlers::health::ReadinessStatus,
+            handlers::health::CheckResult,
+        )
+    ),
+    tags(
+        (name = "auth", description = "Authentication endpoints"),
+        (name = "health", description = "Health check endpoints"),
+        (name = "tenants", description = "Tenant and membership endpoints"),
+        (name = "users", description = "The signed-in user's profile"),
+        (name = "crm", description = "CRM endpoints"),
+        (name = "accounting", description = "Accounting endpoints"),
+        (name = "inventory", description = "Inventory endpoints"),
+        (name = "procurement", description = "Procurement endpoints"),
+        (name = "hrm", description = "HRM endpoints"),
+        (name = "imports", description = "Bulk CSV/XLSX import endpoints"),
+        (name = "search", description = "Global search across modules"),
+        (name = "attachments", description = "Files attached to records"),
+        (name = "notifications", description = "In-app notification inbox and live stream"),
+        (name = "email", description = "Email templates and previews"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Declares the security schemes and requires the bearer token on every
+/// operation the auth layer doesn't let through without one.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some("Access token from /api/v1/auth/login or /api/v1/auth/refresh"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "X-API-Key",
+                "Service-to-service key; reserved, not accepted by this server yet",
+            ))),
+        );
+
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            if auth_layer::is_public(path) {
+                continue;
+            }
+            for operation in item.operations.values_mut() {
+                operation.security = Some(vec![SecurityRequirement::new("bearer", Vec::<String>::new())]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use axum::Router;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::routes;
+
+    fn spec() -> Value {
+        serde_json::to_value(ApiDoc::openapi()).unwrap()
+    }
+
+    /// `(method, path)` for every handler the router serves, read off its
+    /// `Debug` output since axum has no public way to list routes.
+    fn served(router: Router<std::sync::Arc<crate::state::AppState>>) -> BTreeSet<(String, String)> {
+        let debug = format!("{:?}", router);
+        let (routes, _fallback) = debug.split_once("fallback_router").unwrap();
+
+        let mut methods = Vec::new();
+        let mut paths = Vec::new();
+        for entry in routes.split("RouteId(").skip(1) {
+            let (id, rest) = entry.split_once("): ").unwrap();
+            if let Some(rest) = rest.strip_prefix("MethodRouter(MethodRouter { ") {
+                let set = rest
+                    .split(", ")
+                    .take_while(|field| !field.starts_with("fallback"))
+                    .filter(|field| !field.ends_with(": None"))
+                    .filter_map(|field| field.split_once(':').map(|(method, _)| method.to_string()))
+                    .collect::<Vec<_>>();
+                methods.push((id.to_string(), set));
+            } else if let Some(rest) = rest.strip_prefix('"') {
+                paths.push((id.to_string(), rest[..rest.find('"').unwrap()].to_string()));
+            }
+        }
+
+        let mut served = BTreeSet::new();
+        for (id, path) in paths {
+            let (_, set) = methods.iter().find(|(route, _)| *route == id).expect("route has a method router");
+            let path = path
+                .split('/')
+                .map(|segment| match segment.strip_prefix(':') {
+                    Some(param) => format!("{{{}}}", param),
+                    None => segment.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            for method in set {
+                served.insert((method.clone(), path.clone()));
+            }
+        }
+        served
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let router = Router::new().nest("/api/v1", routes::api_routes()).merge(routes::probe_routes());
+        let served = served(router);
+
+        let documented: BTreeSet<_> = spec()["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, item)| item.as_object().unwrap().keys().map(move |method| (method.clone(), path.clone())))
+            .collect();
+
+        let undocumented: Vec<_> = served.difference(&documented).collect();
+        let stale: Vec<_> = documented.difference(&served).collect();
+        assert!(undocumented.is_empty(), "routes without a #[utoipa::path] in ApiDoc: {:?}", undocumented);
+        assert!(stale.is_empty(), "documented but not routed: {:?}", stale);
+    }
+
+    #[test]
+    fn every_schema_reference_resolves() {
+        fn refs(value: &Value, found: &mut BTreeSet<String>) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::String(target)) = map.get("$ref") {
+                        found.insert(target.clone());
+                    }
+                    map.values().for_each(|v| refs(v, found));
+                }
+                Value::Array(items) => items.iter().for_each(|v| refs(v, found)),
+                _ => {}
+            }
+        }
+
+        let spec = spec();
+        let mut found = BTreeSet::new();
+        refs(&spec, &mut found);
+        let schemas = spec["components"]["schemas"].as_object().unwrap();
+        let missing: Vec<_> = found
+            .iter()
+            .filter(|target| {
+                let name = target.strip_prefix("#/components/schemas/").unwrap_or(target);
+                !schemas.contains_key(name)
+            })
+            .collect();
+        assert!(missing.is_empty(), "schemas missing from ApiDoc components: {:?}", missing);
+    }
+
+    #[test]
+    fn protected_operations_require_the_bearer_token() {
+        let spec = spec();
+        let schemes = &spec["components"]["securitySchemes"];
+        assert_eq!(schemes["bearer"]["scheme"], "bearer");
+        assert_eq!(schemes["api_key"]["name"], "X-API-Key");
+
+        assert_eq!(spec["paths"]["/api/v1/crm/companies"]["get"]["security"][0]["bearer"], serde_json::json!([]));
+        assert!(spec["paths"]["/api/v1/auth/login"]["post"].get("security").is_none());
+        assert!(spec["paths"]["/health"]["get"].get("security").is_none());
+    }
+
+    #[test]
+    fn erp_client_is_generated_from_the_current_document() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../crates/erp-client/openapi.json");
+        let committed: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert!(
+            committed == spec(),
+            "crates/erp-client/openapi.json is stale; run `erpctl --pretty openapi > crates/erp-client/openapi.json`"
+        );
+    }
+}
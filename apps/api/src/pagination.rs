@@ -0,0 +1,287 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use shared_types::{CountMode, CursorMeta, CursorPaginatedResponse};
+use sqlx::{postgres::PgRow, PgConnection, Postgres, QueryBuilder, Row};
+use uuid::Uuid;
+
+pub const DEFAULT_LIMIT: u32 = 20;
+pub const MAX_LIMIT: u32 = 200;
+
+/// Result columns carrying the last row's position, selected by [`Keyset::push_cursor_columns`].
+const CURSOR_KEY: &str = "cursor_key";
+const CURSOR_ID: &str = "cursor_id";
+
+/// One column of a keyset ordering.
+#[derive(Debug, Clone, Copy)]
+pub struct SortKey {
+    /// Qualified column, e.g. `sm.created_at`. Must be `NOT NULL`.
+    pub column: &'static str,
+    /// Postgres type the cursor value is cast back to
+    pub sql_type: &'static str,
+    pub descending: bool,
+}
+
+/// Ordering of a keyset-paginated list: the sort keys plus the row id as a final
+/// tiebreaker, so every row has a unique, stable position.
+///
+/// Pages are fetched with `WHERE (keys, id) > (last row)` instead of `OFFSET`, so
+/// the cost of a page doesn't grow with its depth as long as an index on
+/// `(tenant_id, keys, id)` exists.
+#[derive(Debug, Clone)]
+pub struct Keyset {
+    pub keys: Vec<SortKey>,
+    /// Qualified id column, e.g. `sm.id`
+    pub id_column: &'static str,
+}
+
+/// Position after the last row of a page. Serialized as base64url JSON; clients
+/// must treat it as opaque.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// Ordering the cursor was issued for, so it can't be replayed against another sort
+    #[serde(rename = "o")]
+    order: String,
+    #[serde(rename = "k")]
+    keys: Vec<String>,
+    id: Uuid,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid cursor")]
+pub struct InvalidCursor;
+
+impl Keyset {
+    fn order(&self) -> String {
+        self.keys
+            .iter()
+            .map(|k| format!("{} {}", k.column, if k.descending { "desc" } else { "asc" }))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// The id tiebreaker follows the direction of the last sort key.
+    fn id_descending(&self) -> bool {
+        self.keys.last().map(|k| k.descending).unwrap_or(false)
+    }
+
+    /// Cursor pointing after the row with sort key values `keys` and id `id`.
+    pub fn cursor(&self, keys: Vec<String>, id: Uuid) -> Cursor {
+        Cursor { order: self.order(), keys, id }
+    }
+
+    /// Decode a client-supplied cursor and check it was issued for this ordering.
+    pub fn decode(&self, raw: &str) -> Result<Cursor, InvalidCursor> {
+        let bytes = BASE64.decode(raw).map_err(|_| InvalidCursor)?;
+        let cursor: Cursor = serde_json::from_slice(&bytes).map_err(|_| InvalidCursor)?;
+        if cursor.order != self.order() || cursor.keys.len() != self.keys.len() {
+            return Err(InvalidCursor);
+        }
+        Ok(cursor)
+    }
+
+    /// Select the sort key values (as text) and id alongside the row so [`page`]
+    /// can build the next cursor without knowing the row type.
+    pub fn push_cursor_columns(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        let keys = self
+            .keys
+            .iter()
+            .map(|k| format!("({})::text", k.column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        qb.push(format_args!(
+            ", ARRAY[{}]::text[] AS {}, {} AS {}",
+            keys, CURSOR_KEY, self.id_column, CURSOR_ID
+        ));
+    }
+
+    /// Append the predicate selecting rows after `cursor` to a query whose `WHERE`
+    /// is already open.
+    pub fn push_after(&self, qb: &mut QueryBuilder<'_, Postgres>, cursor: &Cursor) {
+        let uniform = self.keys.iter().all(|k| k.descending == self.id_descending());
+        if uniform {
+            // Row comparison, which Postgres can answer with an index range scan
+            let columns: Vec<&str> = self.keys.iter().map(|k| k.column).chain([self.id_column]).collect();
+            qb.push(format_args!(
+                " AND ({}) {} (",
+                columns.join(", "),
+                if self.id_descending() { "<" } else { ">" }
+            ));
+            for (key, value) in self.keys.iter().zip(&cursor.keys) {
+                qb.push_bind(value.clone());
+                qb.push(format_args!("::{}, ", key.sql_type));
+            }
+            qb.push_bind(cursor.id);
+            qb.push(")");
+            return;
+        }
+
+        // Mixed directions: (a > ka) OR (a = ka AND b < kb) OR (a = ka AND b = kb AND id > kid)
+        qb.push(" AND (");
+        for depth in 0..=self.keys.len() {
+            if depth > 0 {
+                qb.push(" OR ");
+            }
+            qb.push("(");
+            for (key, value) in self.keys.iter().zip(&cursor.keys).take(depth) {
+                qb.push(format_args!("{} = ", key.column));
+                qb.push_bind(value.clone());
+                qb.push(format_args!("::{} AND ", key.sql_type));
+            }
+            match self.keys.get(depth) {
+                Some(key) => {
+                    qb.push(format_args!("{} {} ", key.column, if key.descending { "<" } else { ">" }));
+                    qb.push_bind(cursor.keys[depth].clone());
+                    qb.push(format_args!("::{}", key.sql_type));
+                }
+                None => {
+                    qb.push(format_args!("{} {} ", self.id_column, if self.id_descending() { "<" } else { ">" }));
+                    qb.push_bind(cursor.id);
+                }
+            }
+            qb.push(")");
+        }
+        qb.push(")");
+    }
+
+    pub fn push_order_by(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        let dir = |descending: bool| if descending { "DESC" } else { "ASC" };
+        qb.push(" ORDER BY ");
+        for key in &self.keys {
+            qb.push(format_args!("{} {}, ", key.column, dir(key.descending)));
+        }
+        qb.push(format_args!("{} {}", self.id_column, dir(self.id_descending())));
+    }
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        BASE64.encode(serde_json::to_vec(self).expect("cursor serializes"))
+    }
+}
+
+/// Clamp a requested page size to `1..=MAX_LIMIT`.
+pub fn limit(requested: Option<u32>) -> u32 {
+    requested.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+}
+
+/// Build a page from rows fetched with `LIMIT limit + 1` and the cursor columns;
+/// the extra row only signals that another page exists.
+pub fn page<T>(
+    mut rows: Vec<PgRow>,
+    limit: u32,
+    keyset: &Keyset,
+    map: impl Fn(&PgRow) -> Result<T, sqlx::Error>,
+    total: Option<(u64, bool)>,
+) -> Result<CursorPaginatedResponse<T>, sqlx::Error> {
+    let has_next = rows.len() > limit as usize;
+    rows.truncate(limit as usize);
+
+    let next_cursor = if has_next {
+        rows.last().and_then(|row| {
+            let keys: Vec<String> = row.try_get(CURSOR_KEY).ok()?;
+            let id: Uuid = row.try_get(CURSOR_ID).ok()?;
+            Some(keyset.cursor(keys, id).encode())
+        })
+    } else {
+        None
+    };
+
+    Ok(CursorPaginatedResponse {
+        data: rows.iter().map(map).collect::<Result<_, _>>()?,
+        pagination: CursorMeta {
+            limit,
+            next_cursor,
+            has_next,
+            total_count: total.map(|(count, _)| count),
+            total_count_estimated: total.map(|(_, estimated)| estimated).unwrap_or(false),
+        },
+    })
+}
+
+/// Total for a list as `(count, is_estimate)`. `from_where` pushes the list's
+/// `FROM ... WHERE ...` (without the cursor predicate).
+///
+/// `Estimated` reads the planner's row estimate via `EXPLAIN`, which is cheap but
+/// only as accurate as the table statistics.
+pub async fn total_count(
+    conn: &mut PgConnection,
+    mode: CountMode,
+    from_where: impl Fn(&mut QueryBuilder<'_, Postgres>),
+) -> Result<Option<(u64, bool)>, sqlx::Error> {
+    match mode {
+        CountMode::None => Ok(None),
+        CountMode::Exact => {
+            let mut qb = QueryBuilder::new("SELECT COUNT(*)");
+            from_where(&mut qb);
+            let count: i64 = qb.build_query_scalar().fetch_one(conn).await?;
+            Ok(Some((count.max(0) as u64, false)))
+        }
+        CountMode::Estimated => {
+            let mut qb = QueryBuilder::new("EXPLAIN (FORMAT JSON) SELECT 1");
+            from_where(&mut qb);
+            let plan: Value = qb.build_query_scalar().fetch_one(conn).await?;
+            let rows = plan[0]["Plan"]["Plan Rows"].as_f64().unwrap_or(0.0);
+            Ok(Some((rows.max(0.0).round() as u64, true)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn by_date(descending: bool) -> Keyset {
+        Keyset {
+            keys: vec![SortKey { column: "je.entry_date", sql_type: "date", descending }],
+            id_column: "je.id",
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let keyset = by_date(true);
+        let cursor = keyset.cursor(vec!["2024-03-01".to_string()], Uuid::new_v4());
+        assert_eq!(keyset.decode(&cursor.encode()).unwrap(), cursor);
+    }
+
+    #[test]
+    fn cursor_is_bound_to_its_ordering() {
+        let cursor = by_date(true).cursor(vec!["2024-03-01".to_string()], Uuid::new_v4());
+        assert!(by_date(false).decode(&cursor.encode()).is_err());
+        assert!(by_date(true).decode("not-a-cursor").is_err());
+    }
+
+    #[test]
+    fn uniform_directions_use_row_comparison() {
+        let keyset = by_date(true);
+        let cursor = keyset.cursor(vec!["2024-03-01".to_string()], Uuid::nil());
+        let mut qb = QueryBuilder::new("SELECT 1 FROM journal_entries je WHERE true");
+        keyset.push_after(&mut qb, &cursor);
+        keyset.push_order_by(&mut qb);
+        assert_eq!(
+            qb.sql(),
+            "SELECT 1 FROM journal_entries je WHERE true AND (je.entry_date, je.id) < ($1::date, $2) \
+             ORDER BY je.entry_date DESC, je.id DESC"
+        );
+    }
+
+    #[test]
+    fn mixed_directions_expand_to_disjunction() {
+        let keyset = Keyset {
+            keys: vec![
+                SortKey { column: "status", sql_type: "text", descending: false },
+                SortKey { column: "entry_date", sql_type: "date", descending: true },
+            ],
+            id_column: "id",
+        };
+        let cursor = keyset.cursor(vec!["draft".to_string(), "2024-03-01".to_string()], Uuid::nil());
+        let mut qb = QueryBuilder::new("WHERE true");
+        keyset.push_after(&mut qb, &cursor);
+        assert_eq!(
+            qb.sql(),
+            "WHERE true AND ((status > $1::text) OR (status = $2::text AND entry_date < $3::date) \
+             OR (status = $4::text AND entry_date = $5::date AND id < $6))"
+        );
+    }
+}
@@ -1,8 +1,8 @@
-use axum::{routing::get, Router};
+use axum::{extract::DefaultBodyLimit, routing::get, Router};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::{handlers, state::AppState};
+use crate::{handlers, openapi::ApiDoc, state::AppState};
 use std::sync::Arc;
 
 pub fn api_routes() -> Router<Arc<AppState>> {
@@ -15,6 +15,11 @@ pub fn api_routes() -> Router<Arc<AppState>> {
         .nest("/procurement", procurement_routes())
         .nest("/accounting", accounting_routes())
         .nest("/hrm", hrm_routes())
+        .nest("/imports", import_routes())
+        .route("/search", get(handlers::search::search))
+        .nest("/attachments", attachment_routes())
+        .nest("/notifications", notification_routes())
+        .nest("/email-templates", email_template_routes())
 }
 
 pub fn auth_routes() -> Router<Arc<AppState>> {
@@ -25,12 +30,19 @@ pub fn auth_routes() -> Router<Arc<AppState>> {
         .route("/register", axum::routing::post(handlers::auth::register_tenant))
         .route("/forgot-password", axum::routing::post(handlers::auth::forgot_password))
         .route("/reset-password", axum::routing::post(handlers::auth::reset_password))
+        .route("/invitations/accept", axum::routing::post(handlers::auth::accept_invitation))
 }
 
 pub fn tenant_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/current", get(handlers::tenant::get_current_tenant))
         .route("/current", axum::routing::put(handlers::tenant::update_current_tenant))
+        .route(
+            "/current/branding",
+            get(handlers::tenant::get_branding).put(handlers::tenant::update_branding),
+        )
+        .route("/current/sequences", get(handlers::sequences::list_sequences))
+        .route("/current/sequences/:document_type", axum::routing::put(handlers::sequences::update_sequence))
         .route("/members", get(handlers::tenant::get_members))
         .route("/invite", axum::routing::post(handlers::tenant::invite_user))
 
@@ -78,6 +90,7 @@ pub fn procurement_routes() -> Router<Arc<AppState>> {
         .route("/vendors", axum::routing::post(handlers::procurement::create_vendor))
         .route("/purchase-orders", get(handlers::procurement::list_purchase_orders))
         .route("/purchase-orders", axum::routing::post(handlers::procurement::create_purchase_order))
+        .route("/purchase-orders/:id/submit", axum::routing::post(handlers::procurement::submit_purchase_order))
 
 }
 
@@ -88,6 +101,11 @@ pub fn accounting_routes() -> Router<Arc<AppState>> {
         .route("/accounts/:id", get(handlers::accounting::get_account))
         .route("/journal-entries", get(handlers::accounting::list_journal_entries))
         .route("/journal-entries", axum::routing::post(handlers::accounting::create_journal_entry))
+        .route("/exchange-rates", get(handlers::exchange_rates::list_exchange_rates))
+        .route("/exchange-rates", axum::routing::post(handlers::exchange_rates::upsert_exchange_rate))
+        .route("/exchange-rates/:id", axum::routing::delete(handlers::exchange_rates::delete_exchange_rate))
+        .route("/exchange-rates/convert", get(handlers::exchange_rates::convert))
+        .route("/exchange-rates/fetch", axum::routing::post(handlers::exchange_rates::fetch_exchange_rates))
 
 }
 
@@ -100,63 +118,65 @@ pub fn hrm_routes() -> Router<Arc<AppState>> {
 
 }
 
-pub fn docs_routes() -> Router<Arc<AppState>> {
-    #[derive(OpenApi)]
-    #[openapi(
-        paths(
-            handlers::health::health_check,
-            handlers::auth::login,
-            handlers::auth::register_tenant,
-            handlers::auth::refresh,
-            handlers::crm::list_companies,
-            handlers::crm::create_company,
-            handlers::crm::get_company,
-            handlers::crm::update_company,
-            handlers::crm::delete_company,
-            handlers::crm::list_contacts,
-            handlers::crm::create_contact,
-            handlers::crm::get_contact,
-            handlers::crm::update_contact,
-            handlers::crm::delete_contact,
-            handlers::accounting::list_accounts,
-            handlers::accounting::create_account,
-            handlers::accounting::get_account,
-            handlers::accounting::list_journal_entries,
-            handlers::accounting::create_journal_entry,
-            handlers::inventory::list_products,
-            handlers::inventory::create_product,
-            handlers::inventory::get_product,
-            handlers::inventory::update_product,
-            handlers::inventory::delete_product,
-            handlers::inventory::list_warehouses,
-            handlers::inventory::create_warehouse,
-            handlers::inventory::list_stock,
-            handlers::inventory::list_stock_movements,
-            handlers::procurement::list_vendors,
-            handlers::procurement::create_vendor,
-            handlers::procurement::list_purchase_orders,
-            handlers::procurement::create_purchase_order,
-        ),
-        components(
-            schemas(
-                shared_types::ApiResponse<()>,
-                shared_types::LoginRequest,
-                shared_types::LoginResponse,
-                shared_types::RegisterTenantRequest,
-                shared_types::RefreshTokenRequest,
-            )
-        ),
-        tags(
-            (name = "auth", description = "Authentication endpoints"),
-            (name = "health", description = "Health check endpoints"),
-            (name = "crm", description = "CRM endpoints"),
-            (name = "accounting", description = "Accounting endpoints"),
-            (name = "inventory", description = "Inventory endpoints"),
-            (name = "procurement", description = "Procurement endpoints"),
+pub fn import_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        // Uploads are streamed and capped by imports.max_file_size instead
+        .route(
+            "/",
+            axum::routing::post(handlers::imports::upload_import).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/", get(handlers::imports::list_imports))
+        .route("/fields/:entity", get(handlers::imports::get_import_fields))
+        .route("/:id", get(handlers::imports::get_import))
+        .route("/:id/commit", axum::routing::post(handlers::imports::commit_import))
+        .route("/:id/errors", get(handlers::imports::download_import_errors))
+}
+
+pub fn attachment_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        // Uploads are streamed to disk and capped by storage.max_file_size instead
+        .route(
+            "/",
+            axum::routing::post(handlers::attachments::upload_attachment).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/", get(handlers::attachments::list_attachments))
+        .route(
+            "/:id",
+            get(handlers::attachments::get_attachment).delete(handlers::attachments::delete_attachment),
+        )
+        .route("/:id/download", get(handlers::attachments::download_attachment))
+}
+
+pub fn notification_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(handlers::notifications::list_notifications))
+        .route("/unread-count", get(handlers::notifications::unread_count))
+        .route("/stream", get(handlers::notifications::stream_notifications))
+        .route("/read-all", axum::routing::post(handlers::notifications::mark_all_read))
+        .route(
+            "/preferences",
+            get(handlers::notifications::get_preferences).put(handlers::notifications::update_preferences),
         )
-    )]
-    struct ApiDoc;
+        .route("/:id/read", axum::routing::post(handlers::notifications::mark_read))
+        .route("/:id/unread", axum::routing::post(handlers::notifications::mark_unread))
+}
+
+pub fn email_template_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(handlers::email_templates::list_email_templates))
+        .route("/:name/preview", axum::routing::post(handlers::email_templates::preview_email_template))
+}
 
+/// Health probes and the Prometheus scrape, outside `/api/v1`.
+pub fn probe_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/health", get(handlers::health::health_check))
+        .route("/health/live", get(handlers::health::liveness))
+        .route("/health/ready", get(handlers::health::readiness))
+        .route("/metrics", get(handlers::metrics::metrics))
+}
+
+pub fn docs_routes() -> Router<Arc<AppState>> {
     Router::new()
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
 }
@@ -0,0 +1,326 @@
+//! Global search across modules.
+//!
+//! Each searchable table has a generated `search_vector` (simple + indonesian
+//! configurations) and trigram indexes on its identifiers. A search runs one
+//! branch per record type the caller may see and merges them by rank.
+
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use shared_types::{ApiResponse, SearchEntity, SearchHit};
+use sqlx::{PgConnection, Postgres, QueryBuilder, Row};
+use uuid::Uuid;
+
+const MIN_QUERY_LEN: usize = 2;
+const MAX_QUERY_LEN: usize = 100;
+/// Words of the query that take part in the full-text match
+const MAX_TERMS: usize = 8;
+const DEFAULT_LIMIT: u32 = 20;
+const MAX_LIMIT: u32 = 50;
+
+/// One searchable record type.
+struct Source {
+    entity: SearchEntity,
+    /// Permission needed to see hits of this type
+    permission: &'static str,
+    from: &'static str,
+    tenant_column: &'static str,
+    base_filter: Option<&'static str>,
+    id: &'static str,
+    vector: &'static str,
+    title: &'static str,
+    subtitle: &'static str,
+    /// Identifier columns also matched by trigram word similarity, so typos and
+    /// fragments of codes still hit
+    codes: &'static [&'static str],
+}
+
+static SOURCES: &[Source] = &[
+    Source {
+        entity: SearchEntity::Product,
+        permission: "inventory:products:read",
+        from: "products p",
+        tenant_column: "p.tenant_id",
+        base_filter: Some("p.is_active = true"),
+        id: "p.id",
+        vector: "p.search_vector",
+        title: "p.name",
+        subtitle: "p.sku",
+        codes: &["p.sku", "p.barcode"],
+    },
+    Source {
+        entity: SearchEntity::PurchaseOrder,
+        permission: "procurement:orders:read",
+        from: "purchase_orders po JOIN vendors v ON po.vendor_id = v.id",
+        tenant_column: "po.tenant_id",
+        base_filter: None,
+        id: "po.id",
+        vector: "po.search_vector",
+        title: "po.po_number",
+        subtitle: "v.name",
+        codes: &["po.po_number"],
+    },
+    Source {
+        entity: SearchEntity::Vendor,
+        permission: "procurement:vendors:read",
+        from: "vendors",
+        tenant_column: "tenant_id",
+        base_filter: Some("is_active = true"),
+        id: "id",
+        vector: "search_vector",
+        title: "name",
+        subtitle: "code",
+        codes: &["code", "name"],
+    },
+    Source {
+        entity: SearchEntity::Company,
+        permission: "crm:companies:read",
+        from: "companies",
+        tenant_column: "tenant_id",
+        base_filter: Some("is_active = true"),
+        id: "id",
+        vector: "search_vector",
+        title: "name",
+        subtitle: "coalesce(email, website)",
+        codes: &["name"],
+    },
+    Source {
+        entity: SearchEntity::Contact,
+        permission: "crm:contacts:read",
+        from: "contacts",
+        tenant_column: "tenant_id",
+        base_filter: Some("is_active = true"),
+        id: "id",
+        vector: "search_vector",
+        title: "concat_ws(' ', first_name, last_name)",
+        subtitle: "coalesce(email, phone)",
+        codes: &["email"],
+    },
+    Source {
+        entity: SearchEntity::JournalEntry,
+        permission: "accounting:journals:read",
+        from: "journal_entries",
+        tenant_column: "tenant_id",
+        base_filter: None,
+        id: "id",
+        vector: "search_vector",
+        title: "entry_number",
+        subtitle: "description",
+        codes: &["entry_number"],
+    },
+];
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum SearchError {
+    #[error("q must be {MIN_QUERY_LEN} to {MAX_QUERY_LEN} characters")]
+    QueryLength,
+    #[error("q must contain letters or digits")]
+    NoTerms,
+    #[error("Unknown type '{0}'")]
+    UnknownType(String),
+    #[error("limit must be between 1 and {MAX_LIMIT}")]
+    InvalidLimit,
+}
+
+impl IntoResponse for SearchError {
+    fn into_response(self) -> Response {
+        (StatusCode::BAD_REQUEST, Json(ApiResponse::<()>::error(self.to_string()))).into_response()
+    }
+}
+
+/// A validated `?q=&types=&limit=` search.
+#[derive(Debug, PartialEq)]
+pub struct Search {
+    text: String,
+    /// Prefix `tsquery` of the words in `text`, e.g. `po:* & 2024:*`
+    terms: String,
+    entities: Vec<SearchEntity>,
+    limit: u32,
+}
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for Search
+where
+    S: Send + Sync,
+{
+    type Rejection = SearchError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let params = Query::<Vec<(String, String)>>::try_from_uri(&parts.uri)
+            .map(|Query(params)| params)
+            .unwrap_or_default();
+        let get = |key: &str| params.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        Search::parse(get("q"), get("types"), get("limit"))
+    }
+}
+
+impl Search {
+    fn parse(q: Option<&str>, types: Option<&str>, limit: Option<&str>) -> Result<Self, SearchError> {
+        let text = q.unwrap_or_default().trim();
+        let len = text.chars().count();
+        if !(MIN_QUERY_LEN..=MAX_QUERY_LEN).contains(&len) {
+            return Err(SearchError::QueryLength);
+        }
+        let terms = prefix_terms(text).ok_or(SearchError::NoTerms)?;
+
+        let entities = match types.map(str::trim).filter(|t| !t.is_empty()) {
+            None => SearchEntity::ALL.to_vec(),
+            Some(types) => types
+                .split(',')
+                .map(|t| SearchEntity::parse(t.trim()).ok_or_else(|| SearchError::UnknownType(t.trim().to_string())))
+                .collect::<Result<_, _>>()?,
+        };
+
+        let limit = match limit {
+            None => DEFAULT_LIMIT,
+            Some(limit) => limit.trim().parse().map_err(|_| SearchError::InvalidLimit)?,
+        };
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(SearchError::InvalidLimit);
+        }
+
+        Ok(Self { text: text.to_string(), terms, entities, limit })
+    }
+
+    /// Best hits across the requested types that `allowed` permits.
+    pub async fn run(
+        &self,
+        conn: &mut PgConnection,
+        tenant_id: Uuid,
+        allowed: impl Fn(&str) -> bool,
+    ) -> Result<Vec<SearchHit>, sqlx::Error> {
+        let sources: Vec<&Source> = SOURCES
+            .iter()
+            .filter(|s| self.entities.contains(&s.entity) && allowed(s.permission))
+            .collect();
+        if sources.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT entity, id, title, subtitle, rank FROM (");
+        for (i, source) in sources.iter().enumerate() {
+            if i > 0 {
+                qb.push(" UNION ALL ");
+            }
+            self.push_branch(&mut qb, source, tenant_id);
+        }
+        qb.push(") hits ORDER BY rank DESC, title LIMIT ").push_bind(self.limit as i64);
+
+        let rows = qb.build().fetch_all(conn).await?;
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                Some(SearchHit {
+                    entity: SearchEntity::parse(row.get("entity"))?,
+                    id: row.get("id"),
+                    title: row.get("title"),
+                    subtitle: row.get("subtitle"),
+                    rank: row.get("rank"),
+                })
+            })
+            .collect())
+    }
+
+    /// `(SELECT ... LIMIT n)` for one record type, ranked by full-text
+    /// relevance plus how closely an identifier matches.
+    fn push_branch(&self, qb: &mut QueryBuilder<'_, Postgres>, source: &Source, tenant_id: Uuid) {
+        let tsquery = |qb: &mut QueryBuilder<'_, Postgres>| {
+            qb.push("(to_tsquery('simple', ");
+            qb.push_bind(self.terms.clone());
+            qb.push(") || to_tsquery('indonesian', ");
+            qb.push_bind(self.terms.clone());
+            qb.push("))");
+        };
+
+        qb.push("(SELECT ");
+        qb.push_bind(source.entity.as_str());
+        qb.push(format_args!(
+            "::text AS entity, {} AS id, {}::text AS title, {}::text AS subtitle, (ts_rank({}, ",
+            source.id, source.title, source.subtitle, source.vector
+        ));
+        tsquery(qb);
+        qb.push(")");
+        for code in source.codes {
+            qb.push(" + word_similarity(");
+            qb.push_bind(self.text.clone());
+            qb.push(format_args!(", coalesce({}, ''))", code));
+        }
+        qb.push(format_args!(")::real AS rank FROM {} WHERE {} = ", source.from, source.tenant_column));
+        qb.push_bind(tenant_id);
+        if let Some(base) = source.base_filter {
+            qb.push(format_args!(" AND {}", base));
+        }
+        qb.push(format_args!(" AND ({} @@ ", source.vector));
+        tsquery(qb);
+        for code in source.codes {
+            qb.push(" OR ");
+            qb.push_bind(self.text.clone());
+            qb.push(format_args!(" <% {}", code));
+        }
+        qb.push(") ORDER BY rank DESC LIMIT ").push_bind(self.limit as i64);
+        qb.push(")");
+    }
+}
+
+/// Prefix `tsquery` matching every word of `text`: `PO-2024 kopi` becomes
+/// `po:* & 2024:* & kopi:*`. Only letters and digits survive, so the result
+/// is always valid `to_tsquery` input.
+fn prefix_terms(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .take(MAX_TERMS)
+        .map(|word| format!("{}:*", word.to_lowercase()))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" & "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terms_are_sanitized_prefixes() {
+        assert_eq!(prefix_terms("PO-2024/0001").as_deref(), Some("po:* & 2024:* & 0001:*"));
+        assert_eq!(prefix_terms("Kopi  Bubuk's & | !x").as_deref(), Some("kopi:* & bubuk:* & s:* & x:*"));
+        assert_eq!(prefix_terms("Ñandú").as_deref(), Some("ñandú:*"));
+        assert_eq!(prefix_terms("-- !!"), None);
+    }
+
+    #[test]
+    fn parses_types_and_limits() {
+        let search = Search::parse(Some(" kopi "), Some("product, vendor"), Some("5")).unwrap();
+        assert_eq!(search.text, "kopi");
+        assert_eq!(search.entities, vec![SearchEntity::Product, SearchEntity::Vendor]);
+        assert_eq!(search.limit, 5);
+
+        let search = Search::parse(Some("kopi"), Some(""), None).unwrap();
+        assert_eq!(search.entities, SearchEntity::ALL.to_vec());
+        assert_eq!(search.limit, DEFAULT_LIMIT);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!(Search::parse(None, None, None), Err(SearchError::QueryLength));
+        assert_eq!(Search::parse(Some("k"), None, None), Err(SearchError::QueryLength));
+        assert_eq!(Search::parse(Some(&"k".repeat(101)), None, None), Err(SearchError::QueryLength));
+        assert_eq!(Search::parse(Some("--"), None, None), Err(SearchError::NoTerms));
+        assert_eq!(
+            Search::parse(Some("kopi"), Some("product,invoice"), None),
+            Err(SearchError::UnknownType("invoice".into()))
+        );
+        assert_eq!(Search::parse(Some("kopi"), None, Some("0")), Err(SearchError::InvalidLimit));
+        assert_eq!(Search::parse(Some("kopi"), None, Some("51")), Err(SearchError::InvalidLimit));
+        assert_eq!(Search::parse(Some("kopi"), None, Some("ten")), Err(SearchError::InvalidLimit));
+    }
+
+    #[test]
+    fn every_entity_has_a_source() {
+        for entity in SearchEntity::ALL {
+            assert!(SOURCES.iter().any(|s| s.entity == entity), "{:?}", entity);
+        }
+    }
+}
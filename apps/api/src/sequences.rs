@@ -0,0 +1,339 @@
+//! Gapless document numbers.
+//!
+//! Each tenant numbers its journal entries and purchase orders from a pattern
+//! such as `PO/{YYYY}/{MM}/{SEQ:6}`:
+//!
+//! - `{YYYY}` and `{MM}`: year and month of the document date;
+//! - `{SEQ:n}`: the counter, zero-padded to `n` digits, exactly once.
+//!
+//! The counter restarts every year or month of the document date, or never.
+//! [`next_number`] increments it in the caller's transaction, so the row lock
+//! serialises concurrent documents and a rollback returns the number.
+
+use std::fmt;
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use shared_types::{DocumentSequence, DocumentType, ResetRule};
+use sqlx::{PgConnection, Row};
+use uuid::Uuid;
+
+/// Longest accepted pattern, as stored
+pub const MAX_PATTERN_LEN: usize = 100;
+const MAX_SEQ_WIDTH: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(String),
+    Year,
+    Month,
+    Seq(usize),
+}
+
+/// Why a pattern was rejected.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PatternError {
+    #[error("pattern must be 1 to {MAX_PATTERN_LEN} characters")]
+    Length,
+    #[error("unclosed '{{' in pattern")]
+    Unclosed,
+    #[error("unknown placeholder {{{0}}}; use {{YYYY}}, {{MM}} or {{SEQ:n}}")]
+    Unknown(String),
+    #[error("{{SEQ:n}} needs a width from 1 to {MAX_SEQ_WIDTH}")]
+    Width,
+    #[error("pattern must contain {{SEQ:n}} exactly once")]
+    Seq,
+    #[error("a {0} reset needs {1} in the pattern, or numbers repeat")]
+    Reset(&'static str, &'static str),
+}
+
+/// A parsed numbering pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    source: String,
+    tokens: Vec<Token>,
+}
+
+impl Pattern {
+    pub fn parse(source: &str) -> Result<Self, PatternError> {
+        if source.is_empty() || source.chars().count() > MAX_PATTERN_LEN {
+            return Err(PatternError::Length);
+        }
+        let mut tokens = Vec::new();
+        let mut rest = source;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                tokens.push(Token::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..].find('}').ok_or(PatternError::Unclosed)? + open;
+            let name = &rest[open + 1..close];
+            tokens.push(match name {
+                "YYYY" => Token::Year,
+                "MM" => Token::Month,
+                _ => match name.strip_prefix("SEQ:") {
+                    Some(width) => match width.parse::<usize>() {
+                        Ok(width) if (1..=MAX_SEQ_WIDTH).contains(&width) => Token::Seq(width),
+                        _ => return Err(PatternError::Width),
+                    },
+                    None => return Err(PatternError::Unknown(name.to_string())),
+                },
+            });
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            tokens.push(Token::Literal(rest.to_string()));
+        }
+        if tokens.iter().filter(|t| matches!(t, Token::Seq(_))).count() != 1 {
+            return Err(PatternError::Seq);
+        }
+        Ok(Self { source: source.to_string(), tokens })
+    }
+
+    /// Checks that numbers cannot repeat under `reset`.
+    pub fn check(&self, reset: ResetRule) -> Result<(), PatternError> {
+        let has = |token: &Token| self.tokens.contains(token);
+        match reset {
+            ResetRule::Yearly if !has(&Token::Year) => return Err(PatternError::Reset("yearly", "{YYYY}")),
+            ResetRule::Monthly if !(has(&Token::Year) && has(&Token::Month)) => {
+                return Err(PatternError::Reset("monthly", "{YYYY} and {MM}"))
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// The number `seq` of a document dated `date`.
+    pub fn render(&self, date: NaiveDate, seq: u64) -> String {
+        let mut out = String::new();
+        for token in &self.tokens {
+            match token {
+                Token::Literal(text) => out.push_str(text),
+                Token::Year => out.push_str(&format!("{:04}", date.year())),
+                Token::Month => out.push_str(&format!("{:02}", date.month())),
+                Token::Seq(width) => out.push_str(&format!("{:0width$}", seq, width = *width)),
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Pattern and reset rule used until a tenant configures its own.
+pub fn default_sequence(document_type: DocumentType) -> (&'static str, ResetRule) {
+    match document_type {
+        DocumentType::JournalEntry => ("JE-{YYYY}{MM}-{SEQ:6}", ResetRule::Monthly),
+        DocumentType::PurchaseOrder => ("PO-{YYYY}{MM}-{SEQ:6}", ResetRule::Monthly),
+    }
+}
+
+/// Counter key of a document dated `date`.
+pub fn period(reset: ResetRule, date: NaiveDate) -> String {
+    match reset {
+        ResetRule::Yearly => format!("{:04}", date.year()),
+        ResetRule::Monthly => format!("{:04}-{:02}", date.year(), date.month()),
+        ResetRule::Never => String::new(),
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SequenceError {
+    #[error("Invalid {document_type} numbering pattern: {error}")]
+    Pattern { document_type: &'static str, error: PatternError },
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+}
+
+/// A tenant's numbering of one document type.
+#[derive(Debug, Clone)]
+pub struct Sequence {
+    pub document_type: DocumentType,
+    pub pattern: Pattern,
+    pub reset_rule: ResetRule,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Sequence {
+    /// The API view, with the number the next document dated `on` would get.
+    pub fn describe(&self, last_value: u64, on: NaiveDate) -> DocumentSequence {
+        let (default_pattern, default_reset) = default_sequence(self.document_type);
+        DocumentSequence {
+            document_type: self.document_type,
+            pattern: self.pattern.to_string(),
+            reset_rule: self.reset_rule,
+            next_number: self.pattern.render(on, last_value + 1),
+            customized: self.pattern.as_str() != default_pattern || self.reset_rule != default_reset,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+/// The tenant's sequence for `document_type`, or the built-in one.
+pub async fn load(conn: &mut PgConnection, tenant_id: Uuid, document_type: DocumentType) -> Result<Sequence, SequenceError> {
+    let row = sqlx::query(
+        "SELECT pattern, reset_rule, updated_at FROM document_sequences WHERE tenant_id = $1 AND document_type = $2",
+    )
+    .bind(tenant_id)
+    .bind(document_type.as_str())
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let (source, reset_rule, updated_at) = match row {
+        Some(row) => (
+            row.get::<String, _>("pattern"),
+            ResetRule::parse(row.get("reset_rule")).unwrap_or_default(),
+            Some(row.get("updated_at")),
+        ),
+        None => {
+            let (pattern, reset_rule) = default_sequence(document_type);
+            (pattern.to_string(), reset_rule, None)
+        }
+    };
+    let pattern = Pattern::parse(&source)
+        .map_err(|error| SequenceError::Pattern { document_type: document_type.as_str(), error })?;
+    Ok(Sequence { document_type, pattern, reset_rule, updated_at })
+}
+
+/// Last number issued in the period of `on`, 0 if none.
+pub async fn last_value(conn: &mut PgConnection, tenant_id: Uuid, sequence: &Sequence, on: NaiveDate) -> Result<u64, sqlx::Error> {
+    let last = sqlx::query_scalar::<_, i64>(
+        "SELECT last_value FROM document_sequence_counters WHERE tenant_id = $1 AND document_type = $2 AND period = $3",
+    )
+    .bind(tenant_id)
+    .bind(sequence.document_type.as_str())
+    .bind(period(sequence.reset_rule, on))
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(last.unwrap_or(0) as u64)
+}
+
+/// Takes the next number for a document dated `date`.
+///
+/// `conn` must be the transaction that inserts the document: the counter row
+/// stays locked until it ends, and rolling it back releases the number.
+pub async fn next_number(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    document_type: DocumentType,
+    date: NaiveDate,
+) -> Result<String, SequenceError> {
+    let sequence = load(conn, tenant_id, document_type).await?;
+    let value = sqlx::query_scalar::<_, i64>(
+        r#"INSERT INTO document_sequence_counters (tenant_id, document_type, period, last_value)
+           VALUES ($1, $2, $3, 1)
+           ON CONFLICT (tenant_id, document_type, period)
+           DO UPDATE SET last_value = document_sequence_counters.last_value + 1
+           RETURNING last_value"#,
+    )
+    .bind(tenant_id)
+    .bind(document_type.as_str())
+    .bind(period(sequence.reset_rule, date))
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(sequence.pattern.render(date, value as u64))
+}
+
+/// Stores a tenant's pattern and reset rule. When the reset rule changes, the
+/// counter of the current period carries on from the highest number issued in
+/// the same year (or ever, for `never`) so that numbers do not repeat.
+pub async fn save(
+    conn: &mut PgConnection,
+    tenant_id: Uuid,
+    document_type: DocumentType,
+    pattern: &Pattern,
+    reset_rule: ResetRule,
+    today: NaiveDate,
+) -> Result<Sequence, SequenceError> {
+    let updated_at: DateTime<Utc> = sqlx::query_scalar(
+        r#"INSERT INTO document_sequences (tenant_id, document_type, pattern, reset_rule)
+           VALUES ($1, $2, $3, $4)
+           ON CONFLICT (tenant_id, document_type)
+           DO UPDATE SET pattern = EXCLUDED.pattern, reset_rule = EXCLUDED.reset_rule
+           RETURNING updated_at"#,
+    )
+    .bind(tenant_id)
+    .bind(document_type.as_str())
+    .bind(pattern.as_str())
+    .bind(reset_rule.as_str())
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let current = period(reset_rule, today);
+    let related = match reset_rule {
+        ResetRule::Never => "%".to_string(),
+        _ => format!("{:04}%", today.year()),
+    };
+    sqlx::query(
+        r#"INSERT INTO document_sequence_counters (tenant_id, document_type, period, last_value)
+           SELECT $1, $2, $3, MAX(last_value) FROM document_sequence_counters
+           WHERE tenant_id = $1 AND document_type = $2 AND period <> $3 AND (period LIKE $4 OR period = '')
+           HAVING MAX(last_value) IS NOT NULL
+           ON CONFLICT (tenant_id, document_type, period)
+           DO UPDATE SET last_value = GREATEST(document_sequence_counters.last_value, EXCLUDED.last_value)"#,
+    )
+    .bind(tenant_id)
+    .bind(document_type.as_str())
+    .bind(&current)
+    .bind(related)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(Sequence { document_type, pattern: pattern.clone(), reset_rule, updated_at: Some(updated_at) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn renders_placeholders_and_pads_the_counter() {
+        let pattern = Pattern::parse("PO/{YYYY}/{MM}/{SEQ:5}").unwrap();
+        assert_eq!(pattern.render(day(2024, 3, 9), 42), "PO/2024/03/00042");
+        // A counter past its width keeps all its digits
+        assert_eq!(Pattern::parse("{SEQ:2}").unwrap().render(day(2024, 1, 1), 123), "123");
+    }
+
+    #[test]
+    fn rejects_malformed_patterns() {
+        assert_eq!(Pattern::parse(""), Err(PatternError::Length));
+        assert_eq!(Pattern::parse("INV-{SEQ:6"), Err(PatternError::Unclosed));
+        assert_eq!(Pattern::parse("INV-{YY}-{SEQ:6}"), Err(PatternError::Unknown("YY".into())));
+        assert_eq!(Pattern::parse("GR-{WH}-{SEQ:6}"), Err(PatternError::Unknown("WH".into())));
+        assert_eq!(Pattern::parse("INV-{SEQ:0}"), Err(PatternError::Width));
+        assert_eq!(Pattern::parse("INV-{SEQ:x}"), Err(PatternError::Width));
+        assert_eq!(Pattern::parse("INV-{YYYY}"), Err(PatternError::Seq));
+        assert_eq!(Pattern::parse("{SEQ:3}-{SEQ:3}"), Err(PatternError::Seq));
+    }
+
+    #[test]
+    fn reset_rules_need_their_date_parts() {
+        let yearly_only = Pattern::parse("INV/{YYYY}/{SEQ:6}").unwrap();
+        assert!(yearly_only.check(ResetRule::Yearly).is_ok());
+        assert!(yearly_only.check(ResetRule::Monthly).is_err());
+        let plain = Pattern::parse("INV-{SEQ:6}").unwrap();
+        assert!(plain.check(ResetRule::Never).is_ok());
+        assert_eq!(plain.check(ResetRule::Yearly), Err(PatternError::Reset("yearly", "{YYYY}")));
+    }
+
+    #[test]
+    fn defaults_are_valid_and_periods_follow_the_reset_rule() {
+        for document_type in DocumentType::ALL {
+            let (pattern, reset) = default_sequence(document_type);
+            Pattern::parse(pattern).unwrap().check(reset).unwrap();
+        }
+        assert_eq!(period(ResetRule::Yearly, day(2024, 3, 9)), "2024");
+        assert_eq!(period(ResetRule::Monthly, day(2024, 3, 9)), "2024-03");
+        assert_eq!(period(ResetRule::Never, day(2024, 3, 9)), "");
+    }
+}
@@ -1,15 +1,19 @@
 use anyhow::Result;
 use auth::{JwtService, PasswordService};
 use chrono::Utc;
-use shared_types::{LoginRequest, LoginResponse, User, Tenant};
+use shared_types::{LoginRequest, LoginResponse, Notification, User, Tenant};
 use sqlx::{Pool, Postgres, Row};
-use redis::aio::ConnectionManager;
+use telemetry::{db_span, Metrics};
+use tracing::Instrument;
+
+use crate::{kv::KvStore, notifications};
 
 pub struct AuthAppService<'a> {
     pub db: &'a Pool<Postgres>,
     pub jwt: &'a JwtService,
     pub password: &'a PasswordService,
-    pub redis: &'a ConnectionManager,
+    pub kv: &'a dyn KvStore,
+    pub metrics: &'a Metrics,
 }
 
 impl<'a> AuthAppService<'a> {
@@ -17,9 +21,10 @@ impl<'a> AuthAppService<'a> {
         db: &'a Pool<Postgres>,
         jwt: &'a JwtService,
         password: &'a PasswordService,
-        redis: &'a ConnectionManager,
+        kv: &'a dyn KvStore,
+        metrics: &'a Metrics,
     ) -> Self {
-        Self { db, jwt, password, redis }
+        Self { db, jwt, password, kv, metrics }
     }
 
     pub async fn register_tenant(
@@ -37,6 +42,7 @@ impl<'a> AuthAppService<'a> {
         let exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(1) FROM tenants WHERE slug = $1")
             .bind(slug)
             .fetch_one(&mut *tx)
+            .instrument(db_span("SELECT", "tenants"))
             .await?;
         if exists > 0 { anyhow::bail!("TENANT_SLUG_TAKEN"); }
 
@@ -44,6 +50,7 @@ impl<'a> AuthAppService<'a> {
         let email_exists = sqlx::query_scalar::<_, i64>("SELECT COUNT(1) FROM users WHERE email = $1")
             .bind(admin_email)
             .fetch_one(&mut *tx)
+            .instrument(db_span("SELECT", "users"))
             .await?;
         if email_exists > 0 { anyhow::bail!("USER_ALREADY_EXISTS"); }
 
@@ -56,6 +63,7 @@ impl<'a> AuthAppService<'a> {
         .bind("basic")
         .bind(serde_json::json!({}))
         .fetch_one(&mut *tx)
+        .instrument(db_span("INSERT", "tenants"))
         .await?;
 
         // Hash password
@@ -73,6 +81,7 @@ impl<'a> AuthAppService<'a> {
         .bind(admin_first_name)
         .bind(admin_last_name)
         .fetch_one(&mut *tx)
+        .instrument(db_span("INSERT", "users"))
         .await?;
 
         // Membership as owner
@@ -82,12 +91,98 @@ impl<'a> AuthAppService<'a> {
         .bind(tenant_id)
         .bind(user_id)
         .execute(&mut *tx)
+        .instrument(db_span("INSERT", "tenant_memberships"))
         .await?;
 
         tx.commit().await?;
         Ok(())
     }
 
+    /// Join the tenant an invitation is for, creating the account unless the
+    /// email already has one (in which case `password` must match it). Returns
+    /// the tenant joined and the inviter's notification, to publish.
+    pub async fn accept_invitation(&self, token: &str, password: &str) -> Result<(uuid::Uuid, Vec<Notification>)> {
+        let mut tx = self.db.begin().await?;
+
+        // Lock the invitation so two accepts of the same token cannot both succeed
+        let invitation = sqlx::query(
+            r#"SELECT id, tenant_id, email, first_name, last_name, role, invited_by FROM invitations
+               WHERE token = $1 AND accepted_at IS NULL AND expires_at > NOW()
+               FOR UPDATE"#,
+        )
+        .bind(token)
+        .fetch_optional(&mut *tx)
+        .instrument(db_span("SELECT", "invitations"))
+        .await?;
+        let Some(invitation) = invitation else { anyhow::bail!("INVALID_INVITATION") };
+        let invitation_id: uuid::Uuid = invitation.get("id");
+        let tenant_id: uuid::Uuid = invitation.get("tenant_id");
+        let email: String = invitation.get("email");
+        let first_name: String = invitation.get("first_name");
+        let last_name: String = invitation.get("last_name");
+
+        let existing = sqlx::query("SELECT id, password_hash FROM users WHERE email = $1")
+            .bind(&email)
+            .fetch_optional(&mut *tx)
+            .instrument(db_span("SELECT", "users"))
+            .await?;
+        let user_id: uuid::Uuid = match existing {
+            Some(user) => {
+                let valid = self
+                    .password
+                    .verify_password(password, user.get("password_hash"))
+                    .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+                if !valid {
+                    anyhow::bail!("INVALID_CREDENTIALS");
+                }
+                user.get("id")
+            }
+            None => {
+                let password_hash = self
+                    .password
+                    .hash_password(password)
+                    .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+                sqlx::query_scalar(
+                    "INSERT INTO users (email, password_hash, first_name, last_name, is_active) VALUES ($1, $2, $3, $4, true) RETURNING id"
+                )
+                .bind(&email)
+                .bind(password_hash)
+                .bind(&first_name)
+                .bind(&last_name)
+                .fetch_one(&mut *tx)
+                .instrument(db_span("INSERT", "users"))
+                .await?
+            }
+        };
+
+        // A former member is reactivated with the invited role
+        sqlx::query(
+            r#"INSERT INTO tenant_memberships (tenant_id, user_id, role, is_active) VALUES ($1, $2, $3, true)
+               ON CONFLICT (tenant_id, user_id) DO UPDATE SET role = EXCLUDED.role, is_active = true"#,
+        )
+        .bind(tenant_id)
+        .bind(user_id)
+        .bind(invitation.get::<String, _>("role"))
+        .execute(&mut *tx)
+        .instrument(db_span("INSERT", "tenant_memberships"))
+        .await?;
+
+        sqlx::query("UPDATE invitations SET accepted_at = NOW() WHERE id = $1")
+            .bind(invitation_id)
+            .execute(&mut *tx)
+            .instrument(db_span("UPDATE", "invitations"))
+            .await?;
+
+        let name = format!("{} {}", first_name, last_name);
+        let notice = notifications::Notice::invitation_accepted(invitation_id, user_id, name.trim(), &email);
+        let created = notifications::create(&mut tx, tenant_id, &[invitation.get("invited_by")], &notice)
+            .instrument(db_span("INSERT", "notifications"))
+            .await?;
+
+        tx.commit().await?;
+        Ok((tenant_id, created))
+    }
+
     pub async fn login(&self, req: &LoginRequest) -> Result<LoginResponse> {
         // Lookup user by email
         let row = sqlx::query(
@@ -101,6 +196,7 @@ impl<'a> AuthAppService<'a> {
         )
         .bind(&req.email)
         .fetch_optional(self.db)
+        .instrument(db_span("SELECT", "users"))
         .await?;
 
         let row = match row {
@@ -131,23 +227,13 @@ impl<'a> AuthAppService<'a> {
         let refresh_token = self.jwt.generate_refresh_token();
         let expires_at = self.jwt.access_token_expires_at();
 
-        // Store refresh token in Redis with TTL (map token -> user_id,tenant_id)
-        {
-            
-            let mut conn = self.redis.clone();
-            let key = format!("refresh:{}", refresh_token);
-            let ttl = (self.jwt.refresh_token_expires_at() - Utc::now()).num_seconds();
-            let value = serde_json::json!({
-                "user_id": user_id,
-                "tenant_id": tenant_id
-            })
-            .to_string();
-            let _: () = redis::pipe()
-                .cmd("SET").arg(&key).arg(&value).ignore()
-                .cmd("EXPIRE").arg(&key).arg(ttl).ignore()
-                .query_async(&mut conn)
-                .await?;
-        }
+        // Store refresh token with TTL (map token -> user_id,tenant_id)
+        let value = serde_json::json!({
+            "user_id": user_id,
+            "tenant_id": tenant_id
+        })
+        .to_string();
+        self.kv.set(&format!("refresh:{}", refresh_token), &value, self.refresh_ttl()).await?;
 
         // Compose response types
         let user = User {
@@ -158,6 +244,7 @@ impl<'a> AuthAppService<'a> {
             is_active: row.try_get::<bool, _>("is_active").unwrap_or(true),
             email_verified_at: None,
             last_login_at: None,
+            locale: None,
         };
         let tenant = Tenant {
             base: shared_types::BaseEntity { id: tenant_id, created_at: Utc::now(), updated_at: Utc::now() },
@@ -172,11 +259,9 @@ impl<'a> AuthAppService<'a> {
     }
 
     pub async fn refresh(&self, refresh_token: &str) -> Result<LoginResponse> {
-        // Lookup refresh token in Redis
-        use redis::AsyncCommands;
-        let mut conn = self.redis.clone();
+        // Lookup refresh token
         let key = format!("refresh:{}", refresh_token);
-        let value: Option<String> = conn.get(&key).await?;
+        let value = self.kv.get(&key).await?;
         let value = match value {
             Some(v) => v,
             None => anyhow::bail!("TOKEN_INVALID"),
@@ -199,6 +284,7 @@ impl<'a> AuthAppService<'a> {
         .bind(user_id)
         .bind(tenant_id)
         .fetch_one(self.db)
+        .instrument(db_span("SELECT", "users"))
         .await?;
 
         // Permissions placeholder
@@ -211,15 +297,9 @@ impl<'a> AuthAppService<'a> {
         let new_refresh_token = self.jwt.generate_refresh_token();
         let expires_at = self.jwt.access_token_expires_at();
 
-        let new_key = format!("refresh:{}", new_refresh_token);
-        let ttl = (self.jwt.refresh_token_expires_at() - Utc::now()).num_seconds();
         let store_value = serde_json::json!({ "user_id": user_id, "tenant_id": tenant_id }).to_string();
-        let _: () = redis::pipe()
-            .cmd("DEL").arg(&key).ignore()
-            .cmd("SET").arg(&new_key).arg(&store_value).ignore()
-            .cmd("EXPIRE").arg(&new_key).arg(ttl).ignore()
-            .query_async(&mut conn)
-            .await?;
+        self.kv.delete(&key).await?;
+        self.kv.set(&format!("refresh:{}", new_refresh_token), &store_value, self.refresh_ttl()).await?;
 
         // Compose response
         let user = User {
@@ -230,6 +310,7 @@ impl<'a> AuthAppService<'a> {
             is_active: row.try_get::<bool, _>("is_active").unwrap_or(true),
             email_verified_at: None,
             last_login_at: None,
+            locale: None,
         };
         let tenant = Tenant {
             base: shared_types::BaseEntity { id: tenant_id, created_at: Utc::now(), updated_at: Utc::now() },
@@ -242,5 +323,10 @@ impl<'a> AuthAppService<'a> {
 
         Ok(LoginResponse { user, tenant, permissions, access_token, refresh_token: new_refresh_token, expires_at })
     }
+
+    /// Refresh tokens are kept until they expire.
+    fn refresh_ttl(&self) -> std::time::Duration {
+        (self.jwt.refresh_token_expires_at() - Utc::now()).to_std().unwrap_or_default()
+    }
 }
 
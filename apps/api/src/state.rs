@@ -2,43 +2,85 @@ use crate::config::AppConfig;
 use anyhow::Result;
 use auth::{JwtService, PasswordService};
 use redis::aio::ConnectionManager;
-use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::time::Duration;
+use sqlx::PgPool;
+use std::sync::Arc;
+use telemetry::Metrics;
+
+use crate::cache::ReadCache;
+use crate::email::Mailer;
+use crate::kv::{KvStore, RedisKv};
+use crate::exchange_rates::{self, RateFetcher};
+use crate::notifications::NotificationHub;
+use crate::storage::{self, Storage};
+use crate::workers::BackgroundWorkers;
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: AppConfig,
     pub db_pool: PgPool,
-    pub redis: ConnectionManager,
+    pub kv: Arc<dyn KvStore>,
+    pub cache: ReadCache,
     pub jwt_service: JwtService,
     pub password_service: PasswordService,
+    pub metrics: Metrics,
+    pub workers: BackgroundWorkers,
+    pub storage: Arc<dyn Storage>,
+    pub notification_hub: NotificationHub,
+    pub mailer: Arc<Mailer>,
+    pub rate_fetcher: Arc<dyn RateFetcher>,
 }
 
 impl AppState {
     pub async fn new(config: AppConfig) -> Result<Self> {
         // Initialize database connection pool
-        let db_pool = PgPoolOptions::new()
-            .max_connections(config.database.max_connections)
-            .min_connections(config.database.min_connections)
-            .acquire_timeout(Duration::from_secs(config.database.acquire_timeout))
-            .idle_timeout(Duration::from_secs(config.database.idle_timeout))
-            .connect(&config.database.url)
-            .await?;
+        let database = &config.database;
+        let db_pool = persistence::create_pool(
+            &database.url,
+            database.max_connections,
+            database.min_connections,
+            database.acquire_timeout,
+            database.idle_timeout,
+        )
+        .await?;
 
         // Initialize Redis connection
         let redis_client = redis::Client::open(config.redis.url.clone())?;
         let redis = ConnectionManager::new(redis_client).await?;
 
+        let metrics = Metrics::new(config.telemetry.metrics_tenant_labels)?;
+        let kv = Arc::new(RedisKv::new(redis, metrics.clone()));
+        Self::assemble(config, db_pool, kv, metrics)
+    }
+
+    /// State over an existing pool and key-value store, e.g. a test database
+    /// and a [`MemoryKv`](crate::kv::MemoryKv).
+    pub fn with_backends(config: AppConfig, db_pool: PgPool, kv: Arc<dyn KvStore>) -> Result<Self> {
+        let metrics = Metrics::new(config.telemetry.metrics_tenant_labels)?;
+        Self::assemble(config, db_pool, kv, metrics)
+    }
+
+    fn assemble(config: AppConfig, db_pool: PgPool, kv: Arc<dyn KvStore>, metrics: Metrics) -> Result<Self> {
         // Initialize services
         let jwt_service = JwtService::new(&config.jwt.secret);
         let password_service = PasswordService::new();
+        let storage = storage::from_config(&config.storage)?;
+        let mailer = Arc::new(Mailer::from_config(&config.email)?);
+        let rate_fetcher = exchange_rates::from_config(&config.exchange_rates);
+        let cache = ReadCache::new(kv.clone(), metrics.clone(), config.cache.clone());
 
         Ok(Self {
             config,
             db_pool,
-            redis,
+            kv,
+            cache,
             jwt_service,
             password_service,
+            metrics,
+            workers: BackgroundWorkers::new(),
+            storage,
+            notification_hub: NotificationHub::new(),
+            mailer,
+            rate_fetcher,
         })
     }
 }
@@ -0,0 +1,93 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use super::{check_key, ByteStream, Storage, StorageError};
+
+/// Files under a root directory, one per key.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        check_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, path: &Path, _size: u64, _content_type: &str, _sha256: &str) -> Result<(), StorageError> {
+        let target = self.path(key)?;
+        let dir = target.parent().expect("keys have a file name");
+        tokio::fs::create_dir_all(dir).await?;
+
+        // Copy next to the target and rename, so readers never see half a file
+        let partial = dir.join(format!(".{}.partial", Uuid::new_v4()));
+        if let Err(e) = tokio::fs::copy(path, &partial).await {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(e.into());
+        }
+        tokio::fs::rename(&partial, &target).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<ByteStream, StorageError> {
+        let file = match tokio::fs::File::open(self.path(key)?).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(StorageError::NotFound),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Box::pin(ReaderStream::new(file)))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+
+    #[tokio::test]
+    async fn stores_reads_and_deletes() {
+        let root = std::env::temp_dir().join(format!("erp-storage-test-{}", Uuid::new_v4()));
+        let storage = LocalStorage::new(&root);
+        let source = root.join("upload");
+        tokio::fs::create_dir_all(&root).await.unwrap();
+        tokio::fs::write(&source, b"hello").await.unwrap();
+
+        storage.put("tenant/abc", &source, 5, "text/plain", "").await.unwrap();
+        let bytes: Vec<u8> = storage
+            .get("tenant/abc")
+            .await
+            .unwrap()
+            .map_ok(|chunk| chunk.to_vec())
+            .try_concat()
+            .await
+            .unwrap();
+        assert_eq!(bytes, b"hello");
+
+        storage.delete("tenant/abc").await.unwrap();
+        storage.delete("tenant/abc").await.unwrap();
+        assert!(matches!(storage.get("tenant/abc").await, Err(StorageError::NotFound)));
+        assert!(matches!(storage.get("../upload").await, Err(StorageError::InvalidKey(_))));
+
+        tokio::fs::remove_dir_all(&root).await.unwrap();
+    }
+}
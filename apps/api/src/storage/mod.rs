@@ -0,0 +1,80 @@
+//! Blob storage for attachments.
+//!
+//! Files are addressed by an opaque key (`{tenant_id}/{uuid}`) and written
+//! once; the database decides what a key means. [`LocalStorage`] keeps them in
+//! a directory, [`S3Storage`] in any S3-compatible bucket.
+
+mod local;
+mod s3;
+
+use std::{io, path::Path, sync::Arc};
+
+use axum::body::Bytes;
+use futures::stream::BoxStream;
+
+use crate::config::StorageConfig;
+pub use local::LocalStorage;
+pub use s3::S3Storage;
+
+/// Contents of a stored file, read as it is sent.
+pub type ByteStream = BoxStream<'static, io::Result<Bytes>>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StorageError {
+    #[error("File not found")]
+    NotFound,
+    #[error("Invalid storage key '{0}'")]
+    InvalidKey(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Storage backend error: {0}")]
+    Backend(String),
+}
+
+#[async_trait::async_trait]
+pub trait Storage: Send + Sync {
+    /// Store the local file at `path` under `key`, replacing any previous
+    /// contents. `sha256` is the hex digest of the file, already computed
+    /// while the upload was spooled.
+    async fn put(&self, key: &str, path: &Path, size: u64, content_type: &str, sha256: &str) -> Result<(), StorageError>;
+
+    async fn get(&self, key: &str) -> Result<ByteStream, StorageError>;
+
+    /// Remove `key`; removing a key that doesn't exist is not an error.
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+}
+
+/// Backend selected by `storage.backend`.
+pub fn from_config(config: &StorageConfig) -> anyhow::Result<Arc<dyn Storage>> {
+    Ok(match config.backend.as_str() {
+        "s3" => Arc::new(S3Storage::new(&config.s3)?),
+        _ => Arc::new(LocalStorage::new(&config.local_path)),
+    })
+}
+
+/// Keys are `/`-separated segments of ASCII letters, digits, `-` and `_`, so
+/// they can't escape the local root or need escaping in URLs.
+fn check_key(key: &str) -> Result<(), StorageError> {
+    let valid = !key.is_empty()
+        && key.split('/').all(|segment| {
+            !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(StorageError::InvalidKey(key.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_plain_segments() {
+        assert!(check_key("7c9e6679-7425-40de-944b-e07fc1f90ae7/9f86d081884c7d65").is_ok());
+        for key in ["", "/a", "a/", "a//b", "../etc/passwd", "a/./b", "a b", "a\\b", "ä"] {
+            assert!(check_key(key).is_err(), "{key}");
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::{info, warn};
+
+/// Background tasks owned by the server process.
+///
+/// Every task gets a [`CancellationToken`] that fires when shutdown starts;
+/// tasks are expected to finish their current unit of work and return.
+#[derive(Clone, Default)]
+pub struct BackgroundWorkers {
+    token: CancellationToken,
+    tracker: TaskTracker,
+}
+
+impl BackgroundWorkers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawn a tracked task. `task` receives the shutdown token.
+    pub fn spawn<F, Fut>(&self, name: &'static str, task: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let fut = task(self.token.child_token());
+        self.tracker.spawn(async move {
+            fut.await;
+            info!(worker = name, "Background worker stopped");
+        });
+    }
+
+    /// Token cancelled when shutdown starts, for long-lived work outside `spawn`
+    /// (e.g. streaming responses).
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Signal all tasks to stop and wait up to `deadline` for them to finish.
+    /// Returns `false` if some were still running when the deadline passed.
+    pub async fn shutdown(&self, deadline: Duration) -> bool {
+        self.token.cancel();
+        self.tracker.close();
+
+        if tokio::time::timeout(deadline, self.tracker.wait()).await.is_err() {
+            warn!(remaining = self.tracker.len(), "Background workers did not stop before the deadline");
+            return false;
+        }
+        true
+    }
+}
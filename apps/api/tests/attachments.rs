@@ -0,0 +1,175 @@
+mod common;
+
+use std::time::Duration;
+
+use api::attachments::{self, UrlSigner};
+use axum::http::{header, Method, StatusCode};
+use chrono::Utc;
+use common::{Session, TestApp, TestResponse};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const ONE_GIB: i64 = 1024 * 1024 * 1024;
+
+async fn company(app: &TestApp, session: &Session, name: &str) -> String {
+    app.post(session, "/api/v1/crm/companies", json!({ "name": name })).await.id()
+}
+
+async fn attach(app: &TestApp, session: &Session, company_id: &str, filename: &str, contents: &[u8]) -> TestResponse {
+    let uri = format!("/api/v1/attachments?entity_type=company&entity_id={}", company_id);
+    app.upload(session, &uri, filename, contents).await
+}
+
+async fn download(app: &TestApp, url: &str) -> TestResponse {
+    app.send(Method::GET, url, None, None).await
+}
+
+/// Count `bytes` against the tenant's quota, as if other files were stored.
+async fn fill_quota(app: &TestApp, tenant_id: Uuid, bytes: i64) {
+    sqlx::query("INSERT INTO attachment_blobs (tenant_id, sha256, size, storage_key) VALUES ($1, $2, $3, $4)")
+        .bind(tenant_id)
+        .bind("f".repeat(64))
+        .bind(bytes)
+        .bind(format!("{}/filler", tenant_id))
+        .execute(&app.pool)
+        .await
+        .unwrap();
+}
+
+#[sqlx::test(migrations = false)]
+async fn uploads_are_listed_and_downloaded(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let globex = company(&app, &session, "Globex").await;
+
+    let uploaded = attach(&app, &session, &globex, "notes.txt", b"hello attachments").await;
+    assert_eq!(uploaded.status, StatusCode::CREATED, "{}", uploaded.body);
+    let attachment = uploaded.data();
+    assert_eq!(attachment["filename"], "notes.txt");
+    assert_eq!(attachment["content_type"], "text/plain");
+    assert_eq!(attachment["size"], 17);
+
+    let listed = app.get(&session, &format!("/api/v1/attachments?entity_type=company&entity_id={}", globex)).await;
+    assert_eq!(listed.data().as_array().unwrap().len(), 1);
+
+    let file = download(&app, attachment["url"].as_str().unwrap()).await;
+    assert_eq!(file.status, StatusCode::OK);
+    assert_eq!(&file.bytes[..], b"hello attachments");
+    assert_eq!(file.headers[header::CONTENT_TYPE], "text/plain");
+
+    let missing = attach(&app, &session, &Uuid::new_v4().to_string(), "notes.txt", b"x").await;
+    assert_eq!(missing.status, StatusCode::NOT_FOUND, "{}", missing.body);
+}
+
+#[sqlx::test(migrations = false)]
+async fn the_same_contents_are_stored_once(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let globex = company(&app, &session, "Globex").await;
+    let initech = company(&app, &session, "Initech").await;
+
+    let first = attach(&app, &session, &globex, "a.txt", b"same bytes").await;
+    let second = attach(&app, &session, &initech, "b.txt", b"same bytes").await;
+    assert_eq!(first.data()["sha256"], second.data()["sha256"]);
+    assert_ne!(first.data()["id"], second.data()["id"]);
+
+    let blobs: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM attachment_blobs WHERE tenant_id = $1")
+        .bind(session.tenant_id)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(blobs, 1);
+}
+
+#[sqlx::test(migrations = false)]
+async fn uploads_past_the_quota_get_413(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let globex = company(&app, &session, "Globex").await;
+
+    // The basic plan keeps 1 GiB; leave room for 10 bytes
+    fill_quota(&app, session.tenant_id, ONE_GIB - 10).await;
+
+    let over = attach(&app, &session, &globex, "big.txt", b"eleven byte").await;
+    assert_eq!(over.status, StatusCode::PAYLOAD_TOO_LARGE, "{}", over.body);
+    assert_eq!(over.body["success"], false);
+
+    // Two uploads that each fit but not together: only one may land
+    let (a, b) = tokio::join!(
+        attach(&app, &session, &globex, "a.txt", b"aaaaaaaa"),
+        attach(&app, &session, &globex, "b.txt", b"bbbbbbbb"),
+    );
+    let mut statuses = [a.status, b.status];
+    statuses.sort();
+    assert_eq!(statuses, [StatusCode::CREATED, StatusCode::PAYLOAD_TOO_LARGE], "{} / {}", a.body, b.body);
+
+    // Attaching contents already stored takes no extra space
+    let stored = if a.status == StatusCode::CREATED { b"aaaaaaaa" } else { b"bbbbbbbb" };
+    let again = attach(&app, &session, &globex, "again.txt", stored).await;
+    assert_eq!(again.status, StatusCode::CREATED, "{}", again.body);
+}
+
+#[sqlx::test(migrations = false)]
+async fn expired_and_tampered_links_are_refused(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let globex = company(&app, &session, "Globex").await;
+
+    let uploaded = attach(&app, &session, &globex, "notes.txt", b"signed contents").await;
+    let id: Uuid = uploaded.id().parse().unwrap();
+    let url = uploaded.data()["url"].as_str().unwrap().to_string();
+
+    let (expired, _) = UrlSigner::from_state(&app.state).sign(id, Utc::now() - chrono::Duration::hours(1));
+    let refused = download(&app, &expired).await;
+    assert_eq!(refused.status, StatusCode::FORBIDDEN, "{}", refused.body);
+
+    let mut tampered = url.clone();
+    let last = tampered.pop().unwrap();
+    tampered.push(if last == '0' { '1' } else { '0' });
+    assert_eq!(download(&app, &tampered).await.status, StatusCode::FORBIDDEN);
+
+    // A valid signature for one attachment doesn't open another
+    let other = url.replacen(&id.to_string(), &Uuid::new_v4().to_string(), 1);
+    assert_eq!(download(&app, &other).await.status, StatusCode::FORBIDDEN);
+
+    assert_eq!(download(&app, &url).await.status, StatusCode::OK);
+}
+
+#[sqlx::test(migrations = false)]
+async fn purging_a_record_removes_its_files(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let globex = company(&app, &session, "Globex").await;
+    attach(&app, &session, &globex, "notes.txt", b"purge me").await.data();
+
+    let key: String = sqlx::query_scalar("SELECT storage_key FROM attachment_blobs WHERE tenant_id = $1")
+        .bind(session.tenant_id)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert!(app.state.storage.get(&key).await.is_ok());
+
+    sqlx::query("DELETE FROM companies WHERE id = $1::uuid").bind(&globex).execute(&app.pool).await.unwrap();
+    let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM attachments WHERE entity_id = $1::uuid")
+        .bind(&globex)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(left, 0);
+
+    // Past the grace period the sweep drops the blob and its file
+    sqlx::query("UPDATE attachment_blobs SET created_at = NOW() - INTERVAL '2 hours' WHERE storage_key = $1")
+        .bind(&key)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    attachments::spawn_cleanup(&app.state);
+    for _ in 0..50 {
+        if app.state.storage.get(&key).await.is_err() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(app.state.storage.get(&key).await.is_err(), "file of a purged record is still stored");
+}
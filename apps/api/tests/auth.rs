@@ -0,0 +1,94 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{TestApp, PASSWORD};
+use serde_json::json;
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = false)]
+async fn registered_admins_can_sign_in(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+
+    let profile = app.get(&session, "/api/v1/users/profile").await;
+    assert_eq!(profile.data()["email"], session.email);
+    let tenant: String = sqlx::query_scalar("SELECT slug FROM tenants WHERE id = $1")
+        .bind(session.tenant_id)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(tenant, "northwind");
+}
+
+#[sqlx::test(migrations = false)]
+async fn wrong_passwords_and_unknown_users_are_refused(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+
+    for (email, password) in [(session.email.as_str(), "not the password"), ("nobody@example.test", PASSWORD)] {
+        let response = app.try_login(email, password).await;
+        assert!(response.is_failure(), "{}", response.body);
+        assert_eq!(response.body["code"], "INVALID_CREDENTIALS");
+    }
+    assert!(app.metrics.render().contains("failed_logins_total 2"));
+}
+
+#[sqlx::test(migrations = false)]
+async fn taken_slugs_and_emails_are_refused(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+
+    for (slug, email, code) in [
+        ("northwind", "other@example.test", "TENANT_SLUG_TAKEN"),
+        ("contoso", session.email.as_str(), "USER_ALREADY_EXISTS"),
+    ] {
+        let again = app
+            .send(
+                Method::POST,
+                "/api/v1/auth/register",
+                None,
+                Some(json!({
+                    "company_name": "Another Ltd",
+                    "slug": slug,
+                    "admin_email": email,
+                    "admin_password": PASSWORD,
+                    "admin_first_name": "Other",
+                    "admin_last_name": "Admin",
+                })),
+            )
+            .await;
+        assert!(again.is_failure(), "{}", again.body);
+        assert_eq!(again.body["code"], code, "{}", again.body);
+    }
+}
+
+#[sqlx::test(migrations = false)]
+async fn protected_routes_need_a_valid_token(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+
+    let anonymous = app.send(Method::GET, "/api/v1/crm/companies", None, None).await;
+    assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED);
+
+    let forged = app.send(Method::GET, "/api/v1/crm/companies", Some("not-a-jwt"), None).await;
+    assert_eq!(forged.status, StatusCode::UNAUTHORIZED);
+
+    let health = app.send(Method::GET, "/health/live", None, None).await;
+    assert_eq!(health.status, StatusCode::OK);
+}
+
+#[sqlx::test(migrations = false)]
+async fn refresh_tokens_rotate(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let refresh = |token: String| json!({ "refresh_token": token });
+
+    let rotated = app.send(Method::POST, "/api/v1/auth/refresh", None, Some(refresh(session.refresh_token.clone()))).await;
+    let new_token = rotated.data()["refresh_token"].as_str().expect("new refresh token").to_string();
+    assert_ne!(new_token, session.refresh_token);
+
+    let reused = app.send(Method::POST, "/api/v1/auth/refresh", None, Some(refresh(session.refresh_token))).await;
+    assert_eq!(reused.body["code"], "TOKEN_INVALID", "{}", reused.body);
+
+    let again = app.send(Method::POST, "/api/v1/auth/refresh", None, Some(refresh(new_token))).await;
+    again.data();
+}
@@ -0,0 +1,215 @@
+//! The API in-process: `create_app` over a `#[sqlx::test]` database and a
+//! [`MemoryKv`], driven with `oneshot` requests.
+//!
+//! Each test gets a fresh database, so the tests need a Postgres they can
+//! create databases on (CI runs one as a service):
+//!
+//! ```text
+//! DATABASE_URL=postgres://postgres@localhost/erp_platform cargo test -p api
+//! ```
+
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use api::{config::AppConfig, create_app, kv::MemoryKv, state::AppState};
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    Router,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use telemetry::Metrics;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+pub const PASSWORD: &str = "correct horse battery";
+
+pub struct TestApp {
+    router: Router,
+    pub pool: PgPool,
+    pub kv: Arc<MemoryKv>,
+    pub metrics: Metrics,
+    /// The state behind `router`, for driving background work directly
+    pub state: AppState,
+}
+
+/// A signed-in tenant admin.
+pub struct Session {
+    pub token: String,
+    pub refresh_token: String,
+    pub tenant_id: Uuid,
+    pub email: String,
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Value,
+    /// The body as sent, for responses that aren't JSON
+    pub bytes: Bytes,
+}
+
+impl TestResponse {
+    /// `data` of a successful response; panics with the body otherwise.
+    pub fn data(&self) -> &Value {
+        assert!(self.status.is_success(), "{}: {}", self.status, self.body);
+        assert_eq!(self.body["success"], true, "{}", self.body);
+        &self.body["data"]
+    }
+
+    pub fn id(&self) -> String {
+        self.data()["id"].as_str().expect("response has an id").to_string()
+    }
+
+    /// A response the API reports as failed, whatever its status.
+    pub fn is_failure(&self) -> bool {
+        !self.status.is_success() || self.body["success"] == false
+    }
+}
+
+impl TestApp {
+    pub async fn new(pool: PgPool) -> TestApp {
+        api::migrate(&pool).await.expect("migrations apply");
+
+        let scratch = std::env::temp_dir().join(format!("erp-api-test-{}", Uuid::new_v4().simple()));
+        let overrides = config::Config::builder()
+            .set_override("server.environment", "test")
+            .and_then(|b| b.set_override("database.url", "postgres://unused"))
+            .and_then(|b| b.set_override("redis.url", "redis://unused"))
+            .and_then(|b| b.set_override("jwt.secret", "test-secret-that-is-long-enough-for-hs256"))
+            .and_then(|b| b.set_override("email.transport", "file"))
+            .and_then(|b| b.set_override("email.file_path", scratch.join("mail").to_string_lossy().to_string()))
+            .and_then(|b| b.set_override("email.from_email", "erp@example.test"))
+            .and_then(|b| b.set_override("storage.local_path", scratch.join("attachments").to_string_lossy().to_string()))
+            .and_then(|b| b.build())
+            .expect("test configuration builds");
+        let config = AppConfig::from_source(overrides).expect("test configuration is valid");
+
+        let kv = Arc::new(MemoryKv::new());
+        let state = AppState::with_backends(config, pool.clone(), kv.clone()).expect("state builds");
+        let metrics = state.metrics.clone();
+        let router = create_app(state.clone()).await.expect("app builds");
+        TestApp { router, pool, kv, metrics, state }
+    }
+
+    pub async fn send(&self, method: Method, uri: &str, token: Option<&str>, body: Option<Value>) -> TestResponse {
+        self.send_with(method, uri, token, body, &[]).await
+    }
+
+    pub async fn send_with(
+        &self,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+        headers: &[(header::HeaderName, &str)],
+    ) -> TestResponse {
+        let mut request = Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .expect("request builds");
+        self.send_request(request).await
+    }
+
+    /// POST `contents` as the `file` field of a multipart form.
+    pub async fn upload(&self, session: &Session, uri: &str, filename: &str, contents: &[u8]) -> TestResponse {
+        const BOUNDARY: &str = "erp-test-boundary";
+        let mut body = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n",
+            BOUNDARY, filename
+        )
+        .into_bytes();
+        body.extend_from_slice(contents);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", session.token))
+            .header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", BOUNDARY))
+            .body(Body::from(body))
+            .expect("request builds");
+        self.send_request(request).await
+    }
+
+    async fn send_request(&self, request: Request<Body>) -> TestResponse {
+        let response = self.router.clone().oneshot(request).await.expect("router is infallible");
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.expect("body reads");
+        let body = if bytes.is_empty() { Value::Null } else { serde_json::from_slice(&bytes).unwrap_or(Value::Null) };
+        TestResponse { status, headers, body, bytes }
+    }
+
+    pub async fn get(&self, session: &Session, uri: &str) -> TestResponse {
+        self.send(Method::GET, uri, Some(&session.token), None).await
+    }
+
+    /// A GET that skips the read cache.
+    pub async fn get_fresh(&self, session: &Session, uri: &str) -> TestResponse {
+        self.send_with(Method::GET, uri, Some(&session.token), None, &[(header::CACHE_CONTROL, "no-cache")]).await
+    }
+
+    pub async fn post(&self, session: &Session, uri: &str, body: Value) -> TestResponse {
+        self.send(Method::POST, uri, Some(&session.token), Some(body)).await
+    }
+
+    pub async fn put(&self, session: &Session, uri: &str, body: Value) -> TestResponse {
+        self.send(Method::PUT, uri, Some(&session.token), Some(body)).await
+    }
+
+    pub async fn delete(&self, session: &Session, uri: &str) -> TestResponse {
+        self.send(Method::DELETE, uri, Some(&session.token), None).await
+    }
+
+    /// Register a tenant through the API and sign in as its admin.
+    pub async fn register(&self, slug: &str) -> Session {
+        let email = format!("admin@{}.example.test", slug);
+        let registered = self
+            .send(
+                Method::POST,
+                "/api/v1/auth/register",
+                None,
+                Some(json!({
+                    "company_name": format!("{} Ltd", slug),
+                    "slug": slug,
+                    "admin_email": email,
+                    "admin_password": PASSWORD,
+                    "admin_first_name": "Ada",
+                    "admin_last_name": "Admin",
+                })),
+            )
+            .await;
+        registered.data();
+        self.login(&email, PASSWORD).await
+    }
+
+    pub async fn login(&self, email: &str, password: &str) -> Session {
+        let response = self.try_login(email, password).await;
+        let data = response.data();
+        Session {
+            token: data["access_token"].as_str().expect("access token").to_string(),
+            refresh_token: data["refresh_token"].as_str().expect("refresh token").to_string(),
+            tenant_id: data["tenant"]["id"].as_str().and_then(|id| id.parse().ok()).expect("tenant id"),
+            email: email.to_string(),
+        }
+    }
+
+    pub async fn try_login(&self, email: &str, password: &str) -> TestResponse {
+        self.send(Method::POST, "/api/v1/auth/login", None, Some(json!({ "email": email, "password": password })))
+            .await
+    }
+}
@@ -0,0 +1,250 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::{Session, TestApp, TestResponse};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+/// The ids in a list response's page.
+fn ids(list: &Value) -> Vec<String> {
+    list["data"]
+        .as_array()
+        .expect("a page of results")
+        .iter()
+        .map(|item| item["id"].as_str().expect("item has an id").to_string())
+        .collect()
+}
+
+async fn product(app: &TestApp, session: &Session, sku: &str) -> String {
+    app.post(
+        session,
+        "/api/v1/inventory/products",
+        json!({
+            "sku": sku,
+            "name": format!("Product {}", sku),
+            "unit_of_measure": "pcs",
+            "cost_price": "10000",
+            "selling_price": "15000",
+            "minimum_stock": 5,
+        }),
+    )
+    .await
+    .id()
+}
+
+async fn account(app: &TestApp, session: &Session, code: &str, account_type: &str, balance_type: &str) -> String {
+    app.post(
+        session,
+        "/api/v1/accounting/accounts",
+        json!({ "code": code, "name": format!("Account {}", code), "account_type": account_type, "balance_type": balance_type }),
+    )
+    .await
+    .id()
+}
+
+#[sqlx::test(migrations = false)]
+async fn companies_are_created_read_updated_and_deleted(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+
+    let id = app
+        .post(&session, "/api/v1/crm/companies", json!({ "name": "Globex", "website": "https://globex.example" }))
+        .await
+        .id();
+    let uri = format!("/api/v1/crm/companies/{}", id);
+
+    assert_eq!(app.get(&session, &uri).await.data()["name"], "Globex");
+    assert!(ids(app.get(&session, "/api/v1/crm/companies?page=1&per_page=5").await.data()).contains(&id));
+    let mangled = app.get(&session, "/api/v1/crm/companies?search=%FF").await;
+    assert_eq!(mangled.status, StatusCode::BAD_REQUEST, "{}", mangled.body);
+    assert_eq!(mangled.body["success"], false);
+
+    app.put(&session, &uri, json!({ "name": "Globex Corporation" })).await.data();
+    assert_eq!(app.get(&session, &uri).await.data()["name"], "Globex Corporation");
+
+    app.delete(&session, &uri).await.data();
+    assert_eq!(app.get(&session, &uri).await.data()["is_active"], false);
+    assert!(!ids(app.get(&session, "/api/v1/crm/companies").await.data()).contains(&id));
+}
+
+#[sqlx::test(migrations = false)]
+async fn contacts_are_created_read_updated_and_deleted(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+
+    let company = app.post(&session, "/api/v1/crm/companies", json!({ "name": "Globex" })).await.id();
+    let id = app
+        .post(
+            &session,
+            "/api/v1/crm/contacts",
+            json!({ "first_name": "Hank", "last_name": "Scorpio", "email": "hank@globex.example", "company_id": company }),
+        )
+        .await
+        .id();
+    let uri = format!("/api/v1/crm/contacts/{}", id);
+
+    let contact = app.get(&session, &uri).await;
+    assert_eq!(contact.data()["email"], "hank@globex.example");
+    assert_eq!(contact.data()["company_id"], company.as_str());
+
+    app.put(&session, &uri, json!({ "position": "CEO" })).await.data();
+    assert_eq!(app.get(&session, &uri).await.data()["position"], "CEO");
+    assert!(ids(app.get(&session, "/api/v1/crm/contacts").await.data()).contains(&id));
+
+    app.delete(&session, &uri).await.data();
+    assert_eq!(app.get(&session, &uri).await.data()["is_active"], false);
+    assert!(!ids(app.get(&session, "/api/v1/crm/contacts").await.data()).contains(&id));
+}
+
+#[sqlx::test(migrations = false)]
+async fn products_are_created_read_updated_and_deleted(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+
+    let id = product(&app, &session, "WID-1").await;
+    let uri = format!("/api/v1/inventory/products/{}", id);
+
+    assert_eq!(app.get(&session, &uri).await.data()["sku"], "WID-1");
+    assert!(ids(app.get(&session, "/api/v1/inventory/products").await.data()).contains(&id));
+
+    app.put(&session, &uri, json!({ "name": "Widget" })).await.data();
+    assert_eq!(app.get(&session, &uri).await.data()["name"], "Widget");
+
+    let duplicate = app
+        .post(
+            &session,
+            "/api/v1/inventory/products",
+            json!({ "sku": "WID-1", "name": "Again", "unit_of_measure": "pcs", "cost_price": "1", "selling_price": "1", "minimum_stock": 0 }),
+        )
+        .await;
+    assert!(duplicate.is_failure(), "{}", duplicate.body);
+
+    app.delete(&session, &uri).await.data();
+    assert_eq!(app.get(&session, &uri).await.data()["is_active"], false);
+    assert!(!ids(app.get(&session, "/api/v1/inventory/products").await.data()).contains(&id));
+}
+
+#[sqlx::test(migrations = false)]
+async fn warehouses_are_created_and_listed(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+
+    let id = app
+        .post(&session, "/api/v1/inventory/warehouses", json!({ "code": "WH-JKT", "name": "Jakarta" }))
+        .await
+        .id();
+    let warehouses = app.get(&session, "/api/v1/inventory/warehouses").await;
+    assert!(ids(warehouses.data()).contains(&id));
+}
+
+#[sqlx::test(migrations = false)]
+async fn cached_warehouse_lists_are_refreshed_by_writes(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let name = |list: &TestResponse| list.data()["data"][0]["name"].as_str().map(str::to_string);
+
+    app.post(&session, "/api/v1/inventory/warehouses", json!({ "code": "WH-JKT", "name": "Jakarta" }))
+        .await
+        .id();
+    assert_eq!(name(&app.get(&session, "/api/v1/inventory/warehouses").await).as_deref(), Some("Jakarta"));
+
+    // Behind the API's back: the cached page is served until a write through it
+    sqlx::query("UPDATE warehouses SET name = 'Jakarta Utara' WHERE code = 'WH-JKT'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(name(&app.get(&session, "/api/v1/inventory/warehouses").await).as_deref(), Some("Jakarta"));
+    assert_eq!(name(&app.get_fresh(&session, "/api/v1/inventory/warehouses").await).as_deref(), Some("Jakarta Utara"));
+
+    app.post(&session, "/api/v1/inventory/warehouses", json!({ "code": "WH-SBY", "name": "Surabaya" }))
+        .await
+        .id();
+    let warehouses = app.get(&session, "/api/v1/inventory/warehouses").await;
+    assert_eq!(name(&warehouses).as_deref(), Some("Jakarta Utara"));
+    assert_eq!(ids(warehouses.data()).len(), 2);
+}
+
+#[sqlx::test(migrations = false)]
+async fn purchase_orders_are_totalled_and_submitted_once(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+
+    let vendor = app
+        .post(&session, "/api/v1/procurement/vendors", json!({ "code": "V-001", "name": "Initech", "currency": "IDR" }))
+        .await
+        .id();
+    assert!(ids(app.get(&session, "/api/v1/procurement/vendors").await.data()).contains(&vendor));
+
+    let item = product(&app, &session, "WID-1").await;
+    let order = app
+        .post(
+            &session,
+            "/api/v1/procurement/purchase-orders",
+            json!({
+                "vendor_id": vendor,
+                "order_date": "2026-01-15",
+                "currency": "IDR",
+                "items": [{ "product_id": item, "quantity_ordered": 4, "unit_price": "25000", "tax_percent": "11" }],
+            }),
+        )
+        .await;
+    let id = order.id();
+    assert_eq!(order.data()["status"], "draft");
+    assert_eq!(order.data()["subtotal"]["amount"], "100000");
+    assert_eq!(order.data()["total_amount"]["amount"], "111000");
+    assert!(ids(app.get(&session, "/api/v1/procurement/purchase-orders").await.data()).contains(&id));
+
+    let submit = format!("/api/v1/procurement/purchase-orders/{}/submit", id);
+    assert_eq!(app.post(&session, &submit, json!({})).await.data()["status"], "pending");
+
+    let again = app.post(&session, &submit, json!({})).await;
+    assert_eq!(again.status, StatusCode::UNPROCESSABLE_ENTITY, "{}", again.body);
+    assert_eq!(again.body["code"], "INVALID_STATUS_TRANSITION");
+}
+
+#[sqlx::test(migrations = false)]
+async fn accounts_and_balanced_journal_entries_are_recorded(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+
+    let cash = account(&app, &session, "1-1100", "asset", "debit").await;
+    let revenue = account(&app, &session, "4-1100", "revenue", "credit").await;
+    let fetched = app.get(&session, &format!("/api/v1/accounting/accounts/{}", cash)).await;
+    assert_eq!(fetched.data()["code"], "1-1100");
+    assert!(ids(app.get(&session, "/api/v1/accounting/accounts").await.data()).contains(&revenue));
+
+    let entry = |credit: &str| {
+        json!({
+            "entry_date": "2026-01-15",
+            "description": "Cash sale",
+            "lines": [
+                { "account_id": cash, "debit_amount": "50000", "credit_amount": "0" },
+                { "account_id": revenue, "debit_amount": "0", "credit_amount": credit },
+            ],
+        })
+    };
+
+    let id = app.post(&session, "/api/v1/accounting/journal-entries", entry("50000")).await.id();
+    assert!(ids(app.get(&session, "/api/v1/accounting/journal-entries").await.data()).contains(&id));
+
+    let unbalanced = app.post(&session, "/api/v1/accounting/journal-entries", entry("40000")).await;
+    assert_eq!(unbalanced.status, StatusCode::UNPROCESSABLE_ENTITY, "{}", unbalanced.body);
+    assert_eq!(unbalanced.body["code"], "UNBALANCED_JOURNAL_ENTRY");
+}
+
+#[sqlx::test(migrations = false)]
+async fn leave_requests_are_created_and_listed(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+
+    let id = app
+        .post(
+            &session,
+            "/api/v1/hrm/leaves",
+            json!({ "leave_type": "annual", "start_date": "2026-03-02", "end_date": "2026-03-04", "reason": "Holiday" }),
+        )
+        .await
+        .id();
+    let leaves = app.get(&session, "/api/v1/hrm/leaves").await;
+    assert!(ids(leaves.data()).contains(&id));
+}
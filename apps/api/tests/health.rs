@@ -0,0 +1,92 @@
+mod common;
+
+use std::time::Duration;
+
+use api::handlers::health::mark_process_start;
+use axum::http::{Method, StatusCode};
+use common::{TestApp, TestResponse};
+use serde_json::Value;
+use sqlx::PgPool;
+
+async fn ready(app: &TestApp) -> TestResponse {
+    app.send(Method::GET, "/health/ready", None, None).await
+}
+
+fn check<'a>(response: &'a TestResponse, name: &str) -> &'a Value {
+    response.body["data"]["checks"]
+        .as_array()
+        .expect("readiness lists its checks")
+        .iter()
+        .find(|c| c["name"] == name)
+        .unwrap_or_else(|| panic!("no {} check in {}", name, response.body))
+}
+
+#[sqlx::test(migrations = false)]
+async fn ready_when_every_dependency_is(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+
+    let response = ready(&app).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.data()["status"], "ready");
+    for name in ["database", "redis", "migrations"] {
+        let check = check(&response, name);
+        assert_eq!(check["status"], "healthy");
+        assert!(check["latency_ms"].is_u64(), "{}", check);
+        assert_eq!(check["error"], Value::Null);
+    }
+    assert_eq!(response.body["data"]["checks"].as_array().unwrap().len(), 3);
+}
+
+#[sqlx::test(migrations = false)]
+async fn a_missing_migration_makes_it_unready(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    sqlx::query("DELETE FROM _sqlx_migrations WHERE version = (SELECT MAX(version) FROM _sqlx_migrations)")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+
+    let response = ready(&app).await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.body["success"], false);
+    assert_eq!(response.body["data"]["status"], "not_ready");
+    assert_eq!(check(&response, "migrations")["error"], "1 pending migration(s)");
+    assert_eq!(check(&response, "database")["status"], "healthy");
+}
+
+#[sqlx::test(migrations = false)]
+async fn an_unreachable_kv_store_makes_it_unready(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    app.kv.set_unreachable(true);
+
+    let response = ready(&app).await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.body["success"], false);
+    assert_eq!(check(&response, "redis")["status"], "unhealthy");
+    assert_eq!(check(&response, "redis")["error"], "Key-value store error: connection refused");
+
+    let health = app.send(Method::GET, "/health", None, None).await;
+    assert_eq!(health.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(health.body["success"], false);
+    assert_eq!(health.body["data"]["redis"], "unhealthy");
+
+    // Liveness never looks at dependencies
+    let live = app.send(Method::GET, "/health/live", None, None).await;
+    assert_eq!(live.status, StatusCode::OK);
+
+    app.kv.set_unreachable(false);
+    assert_eq!(ready(&app).await.status, StatusCode::OK);
+}
+
+#[sqlx::test(migrations = false)]
+async fn liveness_reports_the_build_and_uptime(pool: PgPool) {
+    mark_process_start();
+    let app = TestApp::new(pool).await;
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+
+    let live = app.send(Method::GET, "/health/live", None, None).await;
+    let data = live.data();
+    assert_eq!(data["status"], "alive");
+    assert_eq!(data["git_sha"], env!("GIT_SHA"));
+    assert_eq!(data["version"], env!("CARGO_PKG_VERSION"));
+    assert!(data["uptime"].as_u64().unwrap() >= 1, "{}", data);
+}
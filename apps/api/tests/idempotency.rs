@@ -0,0 +1,98 @@
+mod common;
+
+use std::time::Duration;
+
+use api::{
+    kv::KvStore,
+    middleware::idempotency::{IDEMPOTENCY_KEY, IDEMPOTENT_REPLAYED},
+};
+use axum::http::{Method, StatusCode};
+use common::{Session, TestApp, TestResponse};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+async fn post_once(app: &TestApp, session: &Session, key: &str, body: Value) -> TestResponse {
+    app.send_with(Method::POST, "/api/v1/accounting/accounts", Some(&session.token), Some(body), &[(IDEMPOTENCY_KEY, key)])
+        .await
+}
+
+#[sqlx::test(migrations = false)]
+async fn successes_are_replayed_and_failures_retried(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let cash = json!({ "code": "1100", "name": "Cash", "account_type": "asset", "balance_type": "debit" });
+
+    let created = post_once(&app, &session, "create-cash", cash.clone()).await;
+    let replayed = post_once(&app, &session, "create-cash", cash.clone()).await;
+    assert_eq!(replayed.id(), created.id());
+    assert_eq!(replayed.headers[IDEMPOTENT_REPLAYED], "true");
+
+    // A failure envelope releases the key, so the retry reaches the handler again
+    let duplicate = post_once(&app, &session, "create-cash-again", cash.clone()).await;
+    assert!(duplicate.is_failure());
+    let retried = post_once(&app, &session, "create-cash-again", cash).await;
+    assert!(retried.is_failure());
+    assert!(!retried.headers.contains_key(IDEMPOTENT_REPLAYED));
+}
+
+#[sqlx::test(migrations = false)]
+async fn a_key_reused_with_another_body_conflicts(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let cash = json!({ "code": "1100", "name": "Cash", "account_type": "asset", "balance_type": "debit" });
+    let bank = json!({ "code": "1200", "name": "Bank", "account_type": "asset", "balance_type": "debit" });
+
+    post_once(&app, &session, "create-account", cash).await.data();
+    let reused = post_once(&app, &session, "create-account", bank).await;
+    assert_eq!(reused.status, StatusCode::CONFLICT, "{}", reused.body);
+    assert_eq!(reused.body["error_type"], "CONFLICT");
+    assert_eq!(reused.body["message"], "Idempotency-Key was already used with a different request");
+}
+
+#[sqlx::test(migrations = false)]
+async fn a_retry_while_the_first_request_runs_conflicts(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let cash = json!({ "code": "1100", "name": "Cash", "account_type": "asset", "balance_type": "debit" });
+
+    // Borrow the hash of this exact request from a completed one, then claim
+    // another key with it as the first request would while still running
+    post_once(&app, &session, "completed", cash.clone()).await.data();
+    let stored: Value = serde_json::from_str(
+        &app.kv.get(&format!("idempotency:{}:completed", session.tenant_id)).await.unwrap().expect("response stored"),
+    )
+    .unwrap();
+    let claim = json!({ "state": "in_flight", "request_hash": stored["request_hash"] });
+    let key = format!("idempotency:{}:running", session.tenant_id);
+    assert!(app.kv.set_nx(&key, &claim.to_string(), Duration::from_secs(60)).await.unwrap());
+
+    let retry = post_once(&app, &session, "running", cash).await;
+    assert_eq!(retry.status, StatusCode::CONFLICT, "{}", retry.body);
+    assert_eq!(retry.body["message"], "A request with this Idempotency-Key is still being processed");
+}
+
+#[sqlx::test(migrations = false)]
+async fn keys_are_scoped_to_the_tenant(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let northwind = app.register("northwind").await;
+    let contoso = app.register("contoso").await;
+    let cash = json!({ "code": "1100", "name": "Cash", "account_type": "asset", "balance_type": "debit" });
+
+    let first = post_once(&app, &northwind, "create-cash", cash.clone()).await;
+    let second = post_once(&app, &contoso, "create-cash", cash).await;
+    assert_ne!(first.id(), second.id());
+    assert!(!second.headers.contains_key(IDEMPOTENT_REPLAYED));
+}
+
+#[sqlx::test(migrations = false)]
+async fn bodies_too_large_to_buffer_get_413(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    // Past every limit the layer buffers up to, the largest being attachments
+    let name = "x".repeat(app.state.config.storage.max_file_size + 1);
+    let huge = json!({ "code": "1100", "name": name, "account_type": "asset", "balance_type": "debit" });
+
+    let rejected = post_once(&app, &session, "too-large", huge).await;
+    assert_eq!(rejected.status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(rejected.body["error_type"], "PAYLOAD_TOO_LARGE");
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use std::time::Duration;
+
+use common::TestApp;
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[sqlx::test(migrations = false)]
+async fn stale_running_imports_are_resumed_and_unknown_entities_fail(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let user_id: Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+        .bind(&session.email)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+
+    // Left `running` by a worker that crashed long ago, and one claimed just now
+    let mut jobs = Vec::new();
+    for started in ["2 hours", "1 second"] {
+        let id: Uuid = sqlx::query_scalar(
+            r#"INSERT INTO import_jobs (tenant_id, entity, file_name, file_format, status, created_by, started_at)
+               VALUES ($1, 'bogus', 'bogus.csv', 'csv', 'running', $2, NOW() - $3::interval)
+               RETURNING id"#,
+        )
+        .bind(session.tenant_id)
+        .bind(user_id)
+        .bind(started)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+        jobs.push(id);
+    }
+
+    api::imports::resume_queued(&app.state).await.unwrap();
+
+    let status = |id: Uuid| {
+        sqlx::query_as::<_, (String, Value)>("SELECT status, errors FROM import_jobs WHERE id = $1")
+            .bind(id)
+            .fetch_one(&app.pool)
+    };
+    let mut stale = status(jobs[0]).await.unwrap();
+    for _ in 0..50 {
+        if stale.0 == "failed" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        stale = status(jobs[0]).await.unwrap();
+    }
+    assert_eq!(stale.0, "failed");
+    assert_eq!(stale.1[0]["message"], "Unknown import entity 'bogus'");
+
+    assert_eq!(status(jobs[1]).await.unwrap().0, "running");
+}
@@ -0,0 +1,69 @@
+use api::MIGRATOR;
+use sqlx::{
+    migrate::{Migrate, MigrateError},
+    PgPool,
+};
+
+async fn applied_versions(pool: &PgPool) -> Vec<i64> {
+    sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success ORDER BY version")
+        .fetch_all(pool)
+        .await
+        .unwrap()
+}
+
+async fn acme_purchase_orders(pool: &PgPool) -> i64 {
+    sqlx::query_scalar(
+        "SELECT COUNT(*) FROM purchase_orders po JOIN tenants t ON t.id = po.tenant_id WHERE t.slug = 'acme'",
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+#[sqlx::test(migrations = false)]
+async fn a_fresh_database_migrates_with_its_seeds(pool: PgPool) {
+    api::migrate(&pool).await.unwrap();
+
+    let embedded: Vec<i64> = MIGRATOR.iter().map(|m| m.version).collect();
+    assert_eq!(applied_versions(&pool).await, embedded);
+    assert_eq!(acme_purchase_orders(&pool).await, 4);
+
+    // The seed tenant doesn't leak into connections the app gets
+    for _ in 0..3 {
+        let tenant: Option<String> = sqlx::query_scalar("SELECT NULLIF(current_setting('app.current_tenant_id', true), '')")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(tenant, None);
+    }
+
+    // Nothing left to do the second time
+    api::migrate(&pool).await.unwrap();
+    assert_eq!(acme_purchase_orders(&pool).await, 4);
+}
+
+#[sqlx::test(migrations = false)]
+async fn a_run_stopped_after_the_tenant_seed_resumes(pool: PgPool) {
+    let mut conn = pool.acquire().await.unwrap();
+    conn.ensure_migrations_table().await.unwrap();
+    for migration in MIGRATOR.iter().take_while(|m| m.version <= 3) {
+        conn.apply(migration).await.unwrap();
+    }
+    drop(conn);
+
+    api::migrate(&pool).await.unwrap();
+    assert_eq!(applied_versions(&pool).await.len(), MIGRATOR.iter().count());
+    assert_eq!(acme_purchase_orders(&pool).await, 4);
+}
+
+#[sqlx::test(migrations = false)]
+async fn a_changed_migration_stops_the_run(pool: PgPool) {
+    api::migrate(&pool).await.unwrap();
+    sqlx::query("UPDATE _sqlx_migrations SET checksum = '\\x00' WHERE version = 9")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let error = api::migrate(&pool).await.unwrap_err();
+    assert!(matches!(error, MigrateError::VersionMismatch(9)), "{}", error);
+}
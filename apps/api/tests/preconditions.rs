@@ -0,0 +1,128 @@
+mod common;
+
+use axum::http::{header, Method, StatusCode};
+use common::{Session, TestApp, TestResponse};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+fn etag(response: &TestResponse) -> String {
+    response.headers[header::ETAG].to_str().expect("etag is ascii").to_string()
+}
+
+async fn put_if_match(app: &TestApp, session: &Session, uri: &str, tag: &str, body: Value) -> TestResponse {
+    app.send_with(Method::PUT, uri, Some(&session.token), Some(body), &[(header::IF_MATCH, tag)]).await
+}
+
+async fn get_if_none_match(app: &TestApp, session: &Session, uri: &str, tag: &str) -> TestResponse {
+    app.send_with(Method::GET, uri, Some(&session.token), None, &[(header::IF_NONE_MATCH, tag)]).await
+}
+
+async fn product(app: &TestApp, session: &Session) -> String {
+    let created = app
+        .post(
+            session,
+            "/api/v1/inventory/products",
+            json!({ "sku": "WID-1", "name": "Widget", "unit_of_measure": "pcs", "cost_price": "1", "selling_price": "2", "minimum_stock": 0 }),
+        )
+        .await;
+    format!("/api/v1/inventory/products/{}", created.id())
+}
+
+#[sqlx::test(migrations = false)]
+async fn stale_writers_get_412_and_the_row_is_kept(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let uri = product(&app, &session).await;
+
+    let read = app.get_fresh(&session, &uri).await;
+    read.data();
+    let first = etag(&read);
+
+    // Both writers read the same version; only the first one lands
+    let updated = put_if_match(&app, &session, &uri, &first, json!({ "name": "Widget A" })).await;
+    assert_eq!(updated.data()["name"], "Widget A");
+    let second = etag(&updated);
+    assert_ne!(second, first);
+
+    let stale = put_if_match(&app, &session, &uri, &first, json!({ "name": "Widget B" })).await;
+    assert_eq!(stale.status, StatusCode::PRECONDITION_FAILED, "{}", stale.body);
+    assert_eq!(stale.body["success"], false);
+
+    let current = app.get_fresh(&session, &uri).await;
+    assert_eq!(current.data()["name"], "Widget A");
+    assert_eq!(etag(&current), second);
+}
+
+#[sqlx::test(migrations = false)]
+async fn wildcard_and_missing_if_match_update_unconditionally(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let uri = product(&app, &session).await;
+
+    let starred = put_if_match(&app, &session, &uri, "*", json!({ "name": "Starred" })).await;
+    assert_eq!(starred.data()["name"], "Starred");
+
+    let plain = app.put(&session, &uri, json!({ "name": "Plain" })).await;
+    assert_eq!(plain.data()["name"], "Plain");
+    assert_ne!(etag(&plain), etag(&starred));
+
+    // A tag the API never issued can't match anything
+    let foreign = put_if_match(&app, &session, &uri, "\"not-ours\"", json!({ "name": "Foreign" })).await;
+    assert_eq!(foreign.status, StatusCode::PRECONDITION_FAILED, "{}", foreign.body);
+}
+
+#[sqlx::test(migrations = false)]
+async fn matching_if_none_match_gets_304(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let uri = product(&app, &session).await;
+
+    let tag = etag(&app.get_fresh(&session, &uri).await);
+    let cached = get_if_none_match(&app, &session, &uri, &tag).await;
+    assert_eq!(cached.status, StatusCode::NOT_MODIFIED);
+    assert_eq!(cached.body, Value::Null);
+    assert_eq!(etag(&cached), tag);
+
+    let weak = get_if_none_match(&app, &session, &uri, &format!("W/{}", tag)).await;
+    assert_eq!(weak.status, StatusCode::NOT_MODIFIED);
+
+    app.put(&session, &uri, json!({ "name": "Changed" })).await.data();
+    let changed = get_if_none_match(&app, &session, &uri, &tag).await;
+    assert_eq!(changed.data()["name"], "Changed");
+    assert_ne!(etag(&changed), tag);
+}
+
+#[sqlx::test(migrations = false)]
+async fn companies_contacts_and_accounts_carry_etags(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+
+    let company = app.post(&session, "/api/v1/crm/companies", json!({ "name": "Globex" })).await;
+    let company_uri = format!("/api/v1/crm/companies/{}", company.id());
+    let contact = app
+        .post(
+            &session,
+            "/api/v1/crm/contacts",
+            json!({ "company_id": company.id(), "first_name": "Hank", "last_name": "Scorpio" }),
+        )
+        .await;
+    let contact_uri = format!("/api/v1/crm/contacts/{}", contact.id());
+
+    for (uri, body) in [(&company_uri, json!({ "name": "Globex Corp" })), (&contact_uri, json!({ "first_name": "Henry" }))] {
+        let tag = etag(&app.get_fresh(&session, uri).await);
+        put_if_match(&app, &session, uri, &tag, body.clone()).await.data();
+        let stale = put_if_match(&app, &session, uri, &tag, body).await;
+        assert_eq!(stale.status, StatusCode::PRECONDITION_FAILED, "{}: {}", uri, stale.body);
+    }
+
+    let account = app
+        .post(
+            &session,
+            "/api/v1/accounting/accounts",
+            json!({ "code": "1100", "name": "Cash", "account_type": "asset", "balance_type": "debit" }),
+        )
+        .await;
+    let account_uri = format!("/api/v1/accounting/accounts/{}", account.id());
+    let tag = etag(&app.get_fresh(&session, &account_uri).await);
+    assert_eq!(get_if_none_match(&app, &session, &account_uri, &tag).await.status, StatusCode::NOT_MODIFIED);
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::time::Duration;
+
+use api::sequences;
+use chrono::NaiveDate;
+use common::{Session, TestApp};
+use serde_json::{json, Value};
+use shared_types::DocumentType;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+async fn account(app: &TestApp, session: &Session, code: &str, account_type: &str, balance_type: &str) -> String {
+    app.post(
+        session,
+        "/api/v1/accounting/accounts",
+        json!({ "code": code, "name": format!("Account {}", code), "account_type": account_type, "balance_type": balance_type }),
+    )
+    .await
+    .id()
+}
+
+async fn tenant_tx(pool: &PgPool, tenant_id: Uuid) -> Transaction<'static, Postgres> {
+    let mut tx = pool.begin().await.unwrap();
+    sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(tenant_id.to_string())
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    tx
+}
+
+#[sqlx::test(migrations = false)]
+async fn failed_journal_entries_give_their_number_back(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let cash = account(&app, &session, "1-1100", "asset", "debit").await;
+    let revenue = account(&app, &session, "4-1100", "revenue", "credit").await;
+
+    let entry = |reference: String| -> Value {
+        json!({
+            "entry_date": "2026-01-15",
+            "reference": reference,
+            "description": "Cash sale",
+            "lines": [
+                { "account_id": cash, "debit_amount": "50000", "credit_amount": "0" },
+                { "account_id": revenue, "debit_amount": "0", "credit_amount": "50000" },
+            ],
+        })
+    };
+    let post = |body: Value| app.post(&session, "/api/v1/accounting/journal-entries", body);
+
+    let first = post(entry("R-1".into())).await;
+    assert_eq!(first.data()["entry_number"], "JE-202601-000001");
+
+    // Numbered, then the insert fails on the over-long reference and rolls back
+    let failed = post(entry("R".repeat(101))).await;
+    assert!(failed.is_failure(), "{}", failed.body);
+
+    let next = post(entry("R-2".into())).await;
+    assert_eq!(next.data()["entry_number"], "JE-202601-000002");
+}
+
+#[sqlx::test(migrations = false)]
+async fn concurrent_documents_wait_for_the_counter(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let session = app.register("northwind").await;
+    let tenant_id = session.tenant_id;
+    let date = NaiveDate::from_ymd_opt(2026, 1, 15).unwrap();
+
+    let mut first = tenant_tx(&app.pool, tenant_id).await;
+    let taken = sequences::next_number(&mut first, tenant_id, DocumentType::JournalEntry, date).await.unwrap();
+    assert_eq!(taken, "JE-202601-000001");
+
+    let pool = app.pool.clone();
+    let second = tokio::spawn(async move {
+        let mut tx = tenant_tx(&pool, tenant_id).await;
+        let number = sequences::next_number(&mut tx, tenant_id, DocumentType::JournalEntry, date).await.unwrap();
+        tx.commit().await.unwrap();
+        number
+    });
+
+    // The counter row stays locked until the first document's transaction ends
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(!second.is_finished());
+
+    first.rollback().await.unwrap();
+    assert_eq!(second.await.unwrap(), "JE-202601-000001");
+
+    let mut third = tenant_tx(&app.pool, tenant_id).await;
+    let next = sequences::next_number(&mut third, tenant_id, DocumentType::JournalEntry, date).await.unwrap();
+    assert_eq!(next, "JE-202601-000002");
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+fn ids(list: &Value) -> Vec<String> {
+    list["data"]
+        .as_array()
+        .expect("a page of results")
+        .iter()
+        .map(|item| item["id"].as_str().expect("item has an id").to_string())
+        .collect()
+}
+
+#[sqlx::test(migrations = false)]
+async fn lists_only_show_the_callers_tenant(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let northwind = app.register("northwind").await;
+    let contoso = app.register("contoso").await;
+
+    let created = [
+        ("/api/v1/crm/companies", json!({ "name": "Globex" })),
+        ("/api/v1/crm/contacts", json!({ "first_name": "Hank", "last_name": "Scorpio" })),
+        (
+            "/api/v1/inventory/products",
+            json!({ "sku": "WID-1", "name": "Widget", "unit_of_measure": "pcs", "cost_price": "1", "selling_price": "2", "minimum_stock": 0 }),
+        ),
+        ("/api/v1/inventory/warehouses", json!({ "code": "WH-1", "name": "Main" })),
+        ("/api/v1/procurement/vendors", json!({ "code": "V-1", "name": "Initech", "currency": "IDR" })),
+        ("/api/v1/accounting/accounts", json!({ "code": "1-1100", "name": "Cash", "account_type": "asset", "balance_type": "debit" })),
+        ("/api/v1/hrm/leaves", json!({ "leave_type": "sick", "start_date": "2026-02-02", "end_date": "2026-02-02" })),
+    ];
+
+    for (uri, body) in created {
+        let id = app.post(&northwind, uri, body).await.id();
+        assert!(ids(app.get(&northwind, uri).await.data()).contains(&id), "{} lists its own", uri);
+        assert!(!ids(app.get(&contoso, uri).await.data()).contains(&id), "{} leaks across tenants", uri);
+    }
+}
+
+#[sqlx::test(migrations = false)]
+async fn other_tenants_records_cannot_be_read_or_changed(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let northwind = app.register("northwind").await;
+    let contoso = app.register("contoso").await;
+
+    let company = app.post(&northwind, "/api/v1/crm/companies", json!({ "name": "Globex" })).await.id();
+    let contact = app
+        .post(&northwind, "/api/v1/crm/contacts", json!({ "first_name": "Hank", "last_name": "Scorpio" }))
+        .await
+        .id();
+    let product = app
+        .post(
+            &northwind,
+            "/api/v1/inventory/products",
+            json!({ "sku": "WID-1", "name": "Widget", "unit_of_measure": "pcs", "cost_price": "1", "selling_price": "2", "minimum_stock": 0 }),
+        )
+        .await
+        .id();
+    let account = app
+        .post(
+            &northwind,
+            "/api/v1/accounting/accounts",
+            json!({ "code": "1-1100", "name": "Cash", "account_type": "asset", "balance_type": "debit" }),
+        )
+        .await
+        .id();
+
+    let records = [
+        (format!("/api/v1/crm/companies/{}", company), Some(json!({ "name": "Hijacked" }))),
+        (format!("/api/v1/crm/contacts/{}", contact), Some(json!({ "first_name": "Hijacked" }))),
+        (format!("/api/v1/inventory/products/{}", product), Some(json!({ "name": "Hijacked" }))),
+        (format!("/api/v1/accounting/accounts/{}", account), None),
+    ];
+
+    for (uri, update) in records {
+        let read = app.get(&contoso, &uri).await;
+        assert!(read.is_failure(), "{} was readable: {}", uri, read.body);
+
+        if let Some(update) = update {
+            let updated = app.put(&contoso, &uri, update).await;
+            assert!(updated.is_failure(), "{} was updatable: {}", uri, updated.body);
+            let deleted = app.delete(&contoso, &uri).await;
+            assert!(deleted.is_failure(), "{} was deletable: {}", uri, deleted.body);
+        }
+
+        let own = app.get(&northwind, &uri).await;
+        assert_ne!(own.data()["name"], "Hijacked");
+        assert_ne!(own.data()["first_name"], "Hijacked");
+        assert_ne!(own.data()["is_active"], false, "{} was deactivated", uri);
+    }
+}
+
+#[sqlx::test(migrations = false)]
+async fn journal_entries_cannot_use_another_tenants_accounts(pool: PgPool) {
+    let app = TestApp::new(pool).await;
+    let northwind = app.register("northwind").await;
+    let contoso = app.register("contoso").await;
+
+    let mut accounts = Vec::new();
+    for (code, account_type, balance_type) in [("1-1100", "asset", "debit"), ("4-1100", "revenue", "credit")] {
+        let body = json!({ "code": code, "name": code, "account_type": account_type, "balance_type": balance_type });
+        accounts.push(app.post(&northwind, "/api/v1/accounting/accounts", body).await.id());
+    }
+
+    let entry = app
+        .post(
+            &contoso,
+            "/api/v1/accounting/journal-entries",
+            json!({
+                "entry_date": "2026-01-15",
+                "description": "Borrowed accounts",
+                "lines": [
+                    { "account_id": accounts[0], "debit_amount": "100", "credit_amount": "0" },
+                    { "account_id": accounts[1], "debit_amount": "0", "credit_amount": "100" },
+                ],
+            }),
+        )
+        .await;
+    assert_eq!(entry.status, StatusCode::UNPROCESSABLE_ENTITY, "{}", entry.body);
+}
@@ -0,0 +1,109 @@
+//! `erpctl`: administrative commands for operators.
+//!
+//! Reads the same environment as the API server (`AppConfig`) and prints one
+//! JSON document per run: the result on stdout, or `{"error": ...}` on stderr
+//! with exit status 1.
+
+mod maintenance;
+mod migrate;
+mod seed;
+mod tenant;
+mod user;
+
+use std::{io::Write, process::ExitCode};
+
+use anyhow::Result;
+use api::{config::AppConfig, openapi::ApiDoc};
+use clap::{Parser, Subcommand};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use utoipa::OpenApi;
+
+#[derive(Parser)]
+#[command(name = "erpctl", version, about = "ERP platform administration")]
+struct Cli {
+    /// Indent the JSON output
+    #[arg(long, global = true)]
+    pretty: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Apply or inspect database migrations
+    Migrate {
+        #[command(subcommand)]
+        command: migrate::MigrateCommand,
+    },
+    /// Create, list, suspend and export tenants
+    Tenant {
+        #[command(subcommand)]
+        command: tenant::TenantCommand,
+    },
+    /// Reset passwords and unlock accounts
+    User {
+        #[command(subcommand)]
+        command: user::UserCommand,
+    },
+    /// Load a template of starter data into a tenant
+    Seed(seed::SeedArgs),
+    /// Rebuild the full-text and trigram indexes behind global search
+    ReindexSearch(maintenance::ReindexArgs),
+    /// Rebuild `account_balances` from posted journal entries
+    RecomputeBalances(maintenance::RecomputeArgs),
+    /// Print the API's OpenAPI document, as `erp-client` is generated from
+    Openapi,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = run(cli.command).await;
+    let (document, code) = match result {
+        Ok(value) => (value, ExitCode::SUCCESS),
+        Err(e) => (json!({ "error": format!("{:#}", e) }), ExitCode::FAILURE),
+    };
+    let output = if cli.pretty {
+        serde_json::to_string_pretty(&document)
+    } else {
+        serde_json::to_string(&document)
+    }
+    .expect("JSON values always serialize");
+
+    // A closed pipe (`erpctl ... | head`) is not worth a panic
+    let _ = if code == ExitCode::SUCCESS {
+        writeln!(std::io::stdout(), "{}", output)
+    } else {
+        writeln!(std::io::stderr(), "{}", output)
+    };
+    code
+}
+
+async fn run(command: Command) -> Result<Value> {
+    // The document is static: no configuration or database needed
+    if let Command::Openapi = command {
+        return Ok(serde_json::to_value(ApiDoc::openapi())?);
+    }
+
+    let config = AppConfig::load()?;
+    let pool = connect(&config).await?;
+
+    match command {
+        Command::Migrate { command } => migrate::run(&pool, command).await,
+        Command::Tenant { command } => tenant::run(&pool, &config, command).await,
+        Command::User { command } => user::run(&pool, command).await,
+        Command::Seed(args) => seed::run(&pool, args).await,
+        Command::ReindexSearch(args) => maintenance::reindex_search(&pool, args).await,
+        Command::RecomputeBalances(args) => maintenance::recompute_balances(&pool, args).await,
+        Command::Openapi => unreachable!("handled above"),
+    }
+}
+
+/// A small pool: commands run one statement at a time.
+async fn connect(config: &AppConfig) -> Result<PgPool> {
+    let database = &config.database;
+    persistence::create_pool(&database.url, 2, 0, database.acquire_timeout, database.idle_timeout).await
+}
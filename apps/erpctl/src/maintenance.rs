@@ -0,0 +1,132 @@
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use serde_json::{json, Value};
+use sqlx::{Executor, PgPool, Row};
+use uuid::Uuid;
+
+use crate::tenant::{find, set_tenant};
+
+#[derive(Args)]
+pub struct ReindexArgs {
+    /// Only this table, e.g. `products`
+    #[arg(long)]
+    table: Option<String>,
+}
+
+#[derive(Args)]
+pub struct RecomputeArgs {
+    /// Tenant id or slug; every tenant when omitted
+    #[arg(long)]
+    tenant: Option<String>,
+}
+
+/// The search vectors are generated columns and never go stale, but their GIN
+/// and trigram indexes bloat under heavy updates. Rebuilds them without
+/// blocking writes and refreshes the planner statistics of each table.
+pub async fn reindex_search(pool: &PgPool, args: ReindexArgs) -> Result<Value> {
+    let rows = sqlx::query(
+        r#"SELECT i.tablename::text AS table_name, i.indexname::text AS index_name
+           FROM pg_indexes i
+           WHERE i.schemaname = current_schema()
+             AND i.indexdef ILIKE '% USING gin %'
+             AND EXISTS (
+                 SELECT 1 FROM information_schema.columns c
+                 WHERE c.table_schema = i.schemaname AND c.table_name = i.tablename AND c.column_name = 'search_vector'
+             )
+             AND ($1::text IS NULL OR i.tablename = $1)
+           ORDER BY i.tablename, i.indexname"#,
+    )
+    .bind(&args.table)
+    .fetch_all(pool)
+    .await?;
+    if rows.is_empty() {
+        match &args.table {
+            Some(table) => anyhow::bail!("'{}' is not a searchable table", table),
+            None => anyhow::bail!("no search indexes found; are the migrations applied?"),
+        }
+    }
+
+    // REINDEX CONCURRENTLY cannot run inside a transaction, so each statement
+    // goes straight to the pool
+    let mut indexes = Vec::new();
+    let mut tables: Vec<String> = Vec::new();
+    for row in &rows {
+        let table: String = row.get("table_name");
+        let index: String = row.get("index_name");
+        let started = Instant::now();
+        pool.execute(format!("REINDEX INDEX CONCURRENTLY {}", quote(&index)).as_str())
+            .await
+            .with_context(|| format!("failed to rebuild {}", index))?;
+        indexes.push(json!({ "table": table, "index": index, "ms": started.elapsed().as_millis() as u64 }));
+        if !tables.contains(&table) {
+            tables.push(table);
+        }
+    }
+    for table in &tables {
+        pool.execute(format!("ANALYZE {}", quote(table)).as_str()).await?;
+    }
+
+    Ok(json!({ "indexes": indexes, "analyzed": tables }))
+}
+
+/// Identifiers come from the catalog; quoting keeps them literal anyway.
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Replace a tenant's `account_balances` with monthly totals of its posted
+/// journal lines. Reversed entries count too: they were posted, and their
+/// reversal is a posted entry of its own. Months without activity get no row;
+/// the closing balance of the latest earlier month carries over.
+const RECOMPUTE_BALANCES: &str = r#"
+INSERT INTO account_balances
+    (tenant_id, account_id, period_year, period_month, opening_balance, debit_total, credit_total, closing_balance)
+SELECT tenant_id, account_id, period_year, period_month, closing - net, debit_total, credit_total, closing
+FROM (
+    SELECT p.*, SUM(p.net) OVER (PARTITION BY p.account_id ORDER BY p.period_year, p.period_month) AS closing
+    FROM (
+        SELECT l.tenant_id,
+               l.account_id,
+               EXTRACT(YEAR FROM e.entry_date)::INTEGER AS period_year,
+               EXTRACT(MONTH FROM e.entry_date)::INTEGER AS period_month,
+               SUM(l.debit_amount) AS debit_total,
+               SUM(l.credit_amount) AS credit_total,
+               SUM(CASE WHEN a.balance_type = 'credit' THEN l.credit_amount - l.debit_amount
+                        ELSE l.debit_amount - l.credit_amount END) AS net
+        FROM journal_entry_lines l
+        JOIN journal_entries e ON e.id = l.journal_entry_id
+        JOIN accounts a ON a.id = l.account_id
+        WHERE l.tenant_id = $1 AND e.status IN ('posted', 'reversed')
+        GROUP BY l.tenant_id, l.account_id, 3, 4
+    ) p
+) balances"#;
+
+pub async fn recompute_balances(pool: &PgPool, args: RecomputeArgs) -> Result<Value> {
+    let tenant_ids: Vec<Uuid> = match &args.tenant {
+        Some(key) => vec![find(&mut *pool.acquire().await?, key).await?.id],
+        None => sqlx::query_scalar("SELECT id FROM tenants ORDER BY created_at").fetch_all(pool).await?,
+    };
+
+    let mut results = Vec::with_capacity(tenant_ids.len());
+    for tenant_id in tenant_ids {
+        let mut tx = pool.begin().await?;
+        set_tenant(&mut tx, tenant_id).await?;
+        let removed = sqlx::query("DELETE FROM account_balances WHERE tenant_id = $1")
+            .bind(tenant_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        let written = sqlx::query(RECOMPUTE_BALANCES)
+            .bind(tenant_id)
+            .execute(&mut *tx)
+            .await
+            .with_context(|| format!("failed to recompute balances of tenant {}", tenant_id))?
+            .rows_affected();
+        tx.commit().await?;
+        results.push(json!({ "tenant_id": tenant_id, "removed": removed, "written": written }));
+    }
+
+    Ok(json!({ "tenants": results }))
+}
@@ -0,0 +1,316 @@
+use anyhow::Result;
+use api::MIGRATOR;
+use chrono::{DateTime, Utc};
+use clap::Subcommand;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::{migrate::Migration, PgPool, Row};
+
+#[derive(Subcommand)]
+pub enum MigrateCommand {
+    /// Apply the migrations embedded in this build that the database lacks
+    Up {
+        /// List what would be applied without changing the database
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Compare the embedded migrations with the database
+    Status,
+    /// Record the current checksums of 003 and 009 on databases that applied
+    /// them as first shipped, before they were corrected in place
+    Repair {
+        /// List what would be repaired without changing the database
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// SHA-384 checksums of migrations as first shipped, before they were
+/// corrected in place. A database that recorded one of these is `modified`
+/// only because of that correction, which `repair` accepts.
+const SUPERSEDED: [(i64, &str); 2] = [
+    (
+        3,
+        "1b50a8f7945c91147db99ca06f659e9a274b48f2a2d679903cc93b8e4e2ada4e363b44bf40e0f223074faec00e894998",
+    ),
+    (
+        9,
+        "edda9bc95dcfd765a66ed91353b0a96eac6a5615bd30cab73fb15a6b4b3be81f84fb9125479bbd9e77c6b91dc50dedcc",
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum State {
+    Applied,
+    Pending,
+    /// Started but did not finish; needs a manual fix before anything else runs
+    Failed,
+    /// Applied, but the file changed since; sqlx refuses to continue
+    Modified,
+    /// Applied by a newer build; this binary does not know it
+    Unknown,
+}
+
+impl State {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            State::Applied => "applied",
+            State::Pending => "pending",
+            State::Failed => "failed",
+            State::Modified => "modified",
+            State::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: State,
+    pub installed_on: Option<DateTime<Utc>>,
+}
+
+/// A row of `_sqlx_migrations`.
+#[derive(Debug, Clone)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub description: String,
+    pub success: bool,
+    pub checksum: Vec<u8>,
+    pub installed_on: DateTime<Utc>,
+}
+
+pub async fn run(pool: &PgPool, command: MigrateCommand) -> Result<Value> {
+    match command {
+        MigrateCommand::Status => {
+            let statuses = status(pool).await?;
+            let pending = statuses.iter().filter(|s| s.state == State::Pending).count();
+            let up_to_date = statuses.iter().all(|s| s.state == State::Applied);
+            Ok(json!({ "up_to_date": up_to_date, "pending": pending, "migrations": statuses }))
+        }
+        MigrateCommand::Up { dry_run } => {
+            let statuses = status(pool).await?;
+            if let Some(blocked) = statuses.iter().find(|s| !matches!(s.state, State::Applied | State::Pending)) {
+                let (embedded, applied) = (embedded(), applied_migrations(pool).await?);
+                if repairs(&embedded, &applied, &SUPERSEDED).iter().any(|m| m.version == blocked.version) {
+                    anyhow::bail!(
+                        "migration {} ({}) was corrected after this database applied it; run `erpctl migrate repair`",
+                        blocked.version,
+                        blocked.description
+                    );
+                }
+                anyhow::bail!(
+                    "migration {} ({}) is {}; resolve it before migrating",
+                    blocked.version,
+                    blocked.description,
+                    blocked.state.as_str()
+                );
+            }
+            let pending: Vec<_> = statuses.into_iter().filter(|s| s.state == State::Pending).collect();
+            if dry_run {
+                return Ok(json!({ "dry_run": true, "pending": pending }));
+            }
+
+            api::migrate(pool).await?;
+            let applied: Vec<_> = status(pool)
+                .await?
+                .into_iter()
+                .filter(|s| s.state == State::Applied && pending.iter().any(|p| p.version == s.version))
+                .collect();
+            Ok(json!({ "dry_run": false, "applied": applied }))
+        }
+        MigrateCommand::Repair { dry_run } => {
+            let embedded = embedded();
+            let repairs = repairs(&embedded, &applied_migrations(pool).await?, &SUPERSEDED);
+            let repaired: Vec<_> =
+                repairs.iter().map(|m| json!({ "version": m.version, "description": m.description })).collect();
+            if dry_run {
+                return Ok(json!({ "dry_run": true, "repaired": repaired }));
+            }
+
+            let mut tx = pool.begin().await?;
+            for migration in &repairs {
+                sqlx::query("UPDATE _sqlx_migrations SET checksum = $1 WHERE version = $2")
+                    .bind(&*migration.checksum)
+                    .bind(migration.version)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            tx.commit().await?;
+            Ok(json!({ "dry_run": false, "repaired": repaired }))
+        }
+    }
+}
+
+fn embedded() -> Vec<&'static Migration> {
+    MIGRATOR.iter().filter(|m| !m.migration_type.is_down_migration()).collect()
+}
+
+async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>> {
+    let applied = applied_migrations(pool).await?;
+    Ok(compare(&embedded(), &applied))
+}
+
+/// Read without creating `_sqlx_migrations`, so `status` and dry runs never write.
+async fn applied_migrations(pool: &PgPool) -> Result<Vec<AppliedMigration>> {
+    let exists: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(pool)
+        .await?;
+    if !exists {
+        return Ok(Vec::new());
+    }
+
+    let rows = sqlx::query(
+        "SELECT version, description, success, checksum, installed_on FROM _sqlx_migrations ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| AppliedMigration {
+            version: row.get("version"),
+            description: row.get("description"),
+            success: row.get("success"),
+            checksum: row.get("checksum"),
+            installed_on: row.get("installed_on"),
+        })
+        .collect())
+}
+
+/// State of every migration known to either side, by version.
+pub fn compare(embedded: &[&Migration], applied: &[AppliedMigration]) -> Vec<MigrationStatus> {
+    let mut statuses: Vec<MigrationStatus> = embedded
+        .iter()
+        .map(|m| {
+            let row = applied.iter().find(|a| a.version == m.version);
+            let state = match row {
+                None => State::Pending,
+                Some(a) if !a.success => State::Failed,
+                Some(a) if a.checksum != *m.checksum => State::Modified,
+                Some(_) => State::Applied,
+            };
+            MigrationStatus {
+                version: m.version,
+                description: m.description.to_string(),
+                state,
+                installed_on: row.map(|a| a.installed_on),
+            }
+        })
+        .collect();
+
+    statuses.extend(applied.iter().filter(|a| !embedded.iter().any(|m| m.version == a.version)).map(|a| {
+        MigrationStatus {
+            version: a.version,
+            description: a.description.clone(),
+            state: if a.success { State::Unknown } else { State::Failed },
+            installed_on: Some(a.installed_on),
+        }
+    }));
+    statuses.sort_by_key(|s| s.version);
+    statuses
+}
+
+/// Applied migrations whose recorded checksum is one `superseded` lists for
+/// their version, rather than the embedded one.
+pub fn repairs<'a>(
+    embedded: &[&'a Migration],
+    applied: &[AppliedMigration],
+    superseded: &[(i64, &str)],
+) -> Vec<&'a Migration> {
+    embedded
+        .iter()
+        .filter(|m| {
+            applied.iter().any(|a| {
+                a.version == m.version
+                    && a.success
+                    && a.checksum != *m.checksum
+                    && superseded.iter().any(|(version, checksum)| *version == a.version && hex(&a.checksum) == *checksum)
+            })
+        })
+        .copied()
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::migrate::MigrationType;
+
+    fn migration(version: i64, sql: &'static str) -> Migration {
+        Migration::new(version, format!("step {}", version).into(), MigrationType::Simple, sql.into())
+    }
+
+    fn applied(m: &Migration, success: bool) -> AppliedMigration {
+        AppliedMigration {
+            version: m.version,
+            description: m.description.to_string(),
+            success,
+            checksum: m.checksum.to_vec(),
+            installed_on: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn classifies_each_migration() {
+        let first = migration(1, "CREATE TABLE a ()");
+        let second = migration(2, "CREATE TABLE b ()");
+        let third = migration(3, "CREATE TABLE c ()");
+        let fourth = migration(4, "CREATE TABLE d ()");
+        let mut edited = applied(&second, true);
+        edited.checksum = migration(2, "CREATE TABLE b2 ()").checksum.to_vec();
+        let newer = applied(&migration(5, "CREATE TABLE e ()"), true);
+
+        let statuses = compare(
+            &[&first, &second, &third, &fourth],
+            &[applied(&first, true), edited, applied(&third, false), newer],
+        );
+        let states: Vec<_> = statuses.iter().map(|s| (s.version, s.state)).collect();
+        assert_eq!(
+            states,
+            [(1, State::Applied), (2, State::Modified), (3, State::Failed), (4, State::Pending), (5, State::Unknown)]
+        );
+        assert!(statuses[3].installed_on.is_none());
+    }
+
+    #[test]
+    fn everything_is_pending_on_an_empty_database() {
+        let first = migration(1, "SELECT 1");
+        let statuses = compare(&[&first], &[]);
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].state, State::Pending);
+    }
+
+    #[test]
+    fn repairs_only_checksums_known_to_be_superseded() {
+        let original = migration(3, "INSERT INTO t VALUES ('{}')");
+        let corrected = migration(3, "INSERT INTO t VALUES ('{}'::jsonb)");
+        let other = migration(4, "SELECT 4");
+        let superseded = [(3, hex(&original.checksum))];
+        let superseded: Vec<_> = superseded.iter().map(|(v, c)| (*v, c.as_str())).collect();
+
+        let mut edited = applied(&other, true);
+        edited.checksum = migration(4, "SELECT 5").checksum.to_vec();
+        let stale = [applied(&original, true), edited];
+        let repaired = repairs(&[&corrected, &other], &stale, &superseded);
+        assert_eq!(repaired.iter().map(|m| m.version).collect::<Vec<_>>(), [3]);
+
+        // Already current, or never finished: nothing to repair
+        assert!(repairs(&[&corrected], &[applied(&corrected, true)], &superseded).is_empty());
+        assert!(repairs(&[&corrected], &[applied(&original, false)], &superseded).is_empty());
+    }
+
+    #[test]
+    fn superseded_checksums_are_for_embedded_migrations() {
+        for (version, checksum) in SUPERSEDED {
+            let migration = MIGRATOR.iter().find(|m| m.version == version).unwrap();
+            assert_eq!(checksum.len(), migration.checksum.len() * 2);
+            assert_ne!(hex(&migration.checksum), checksum);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use serde_json::{json, Value};
+use sqlx::{Executor, PgPool};
+
+use crate::tenant::{find, set_tenant};
+
+/// Starter data. The scripts read the target tenant from
+/// `app.current_tenant_id`, like the per-tenant seed migrations they come from.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Template {
+    /// Indonesian chart of accounts and the current fiscal year
+    Accounting,
+    /// Sample categories, warehouses, products and stock levels
+    Inventory,
+}
+
+impl Template {
+    fn name(self) -> &'static str {
+        match self {
+            Template::Accounting => "accounting",
+            Template::Inventory => "inventory",
+        }
+    }
+
+    fn sql(self) -> &'static str {
+        match self {
+            Template::Accounting => include_str!("../../api/migrations/005_seed_accounting.sql"),
+            Template::Inventory => include_str!("../../api/migrations/007_seed_inventory.sql"),
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct SeedArgs {
+    /// Tenant id or slug
+    #[arg(long)]
+    tenant: String,
+    #[arg(long, value_enum)]
+    template: Template,
+    /// Roll back instead of committing, to check the template applies cleanly
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Runs in one transaction: a template either loads completely or not at all,
+/// e.g. when the tenant already has accounts with the same codes.
+pub async fn run(pool: &PgPool, args: SeedArgs) -> Result<Value> {
+    let mut tx = pool.begin().await?;
+    let tenant = find(&mut tx, &args.tenant).await?;
+    set_tenant(&mut tx, tenant.id).await?;
+
+    let inserted = tx
+        .execute(args.template.sql())
+        .await
+        .with_context(|| format!("template {} failed", args.template.name()))?
+        .rows_affected();
+
+    if args.dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+    }
+    Ok(json!({
+        "tenant": tenant.summary(),
+        "template": args.template.name(),
+        "dry_run": args.dry_run,
+        "rows": inserted,
+    }))
+}
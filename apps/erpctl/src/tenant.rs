@@ -0,0 +1,303 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use api::config::AppConfig;
+use auth::PasswordService;
+use chrono::Utc;
+use clap::Subcommand;
+use serde_json::{json, Map, Value};
+use shared_types::RegisterTenantRequest;
+use sqlx::{PgConnection, PgPool, Row};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::user::generate_password;
+
+/// Columns left out of exports because they grant access on their own
+const SECRET_COLUMNS: &[&str] = &["token"];
+
+#[derive(Subcommand)]
+pub enum TenantCommand {
+    /// Create a tenant with its owner account, like self-service registration
+    Create {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        slug: String,
+        /// One of the plans with a storage quota (`STORAGE__QUOTAS__*`)
+        #[arg(long, default_value = "basic")]
+        plan: String,
+        #[arg(long)]
+        owner_email: String,
+        /// Generated and printed when omitted
+        #[arg(long)]
+        owner_password: Option<String>,
+        #[arg(long)]
+        owner_first_name: String,
+        #[arg(long)]
+        owner_last_name: String,
+    },
+    /// List tenants with their member counts
+    List,
+    /// Block sign-in and token refresh for every member of a tenant
+    Suspend {
+        /// Tenant id or slug
+        tenant: String,
+    },
+    /// Lift a suspension
+    Resume {
+        /// Tenant id or slug
+        tenant: String,
+    },
+    /// Dump every row the tenant owns as JSON
+    Export {
+        /// Tenant id or slug
+        tenant: String,
+        /// Write the export to this file and print a summary instead
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+pub async fn run(pool: &PgPool, config: &AppConfig, command: TenantCommand) -> Result<Value> {
+    match command {
+        TenantCommand::Create { name, slug, plan, owner_email, owner_password, owner_first_name, owner_last_name } => {
+            if !config.storage.quotas.contains_key(&plan) {
+                let mut plans: Vec<_> = config.storage.quotas.keys().map(String::as_str).collect();
+                plans.sort_unstable();
+                anyhow::bail!("unknown plan '{}'; expected one of: {}", plan, plans.join(", "));
+            }
+            let generated = owner_password.is_none();
+            let request = RegisterTenantRequest {
+                company_name: name,
+                slug,
+                admin_email: owner_email,
+                admin_password: owner_password.unwrap_or_else(generate_password),
+                admin_first_name: owner_first_name,
+                admin_last_name: owner_last_name,
+            };
+            create(pool, &plan, &request).await.map(|mut created| {
+                if generated {
+                    created["owner"]["password"] = json!(request.admin_password);
+                }
+                created
+            })
+        }
+        TenantCommand::List => {
+            let rows = sqlx::query(
+                r#"SELECT t.id, t.name, t.slug, t.plan, t.is_active, t.created_at,
+                          COUNT(tm.id) FILTER (WHERE tm.is_active) AS members
+                   FROM tenants t
+                   LEFT JOIN tenant_memberships tm ON tm.tenant_id = t.id
+                   GROUP BY t.id
+                   ORDER BY t.created_at"#,
+            )
+            .fetch_all(pool)
+            .await?;
+            let tenants: Vec<Value> = rows
+                .iter()
+                .map(|row| {
+                    json!({
+                        "id": row.get::<Uuid, _>("id"),
+                        "name": row.get::<String, _>("name"),
+                        "slug": row.get::<String, _>("slug"),
+                        "plan": row.get::<String, _>("plan"),
+                        "is_active": row.get::<bool, _>("is_active"),
+                        "members": row.get::<i64, _>("members"),
+                        "created_at": row.get::<chrono::DateTime<Utc>, _>("created_at"),
+                    })
+                })
+                .collect();
+            Ok(json!({ "tenants": tenants }))
+        }
+        TenantCommand::Suspend { tenant } => set_active(pool, &tenant, false).await,
+        TenantCommand::Resume { tenant } => set_active(pool, &tenant, true).await,
+        TenantCommand::Export { tenant, output } => {
+            let mut conn = pool.acquire().await?;
+            let tenant = find(&mut conn, &tenant).await?;
+            let export = export(pool, &tenant).await?;
+            match output {
+                None => Ok(export),
+                Some(path) => {
+                    std::fs::write(&path, serde_json::to_vec(&export)?)
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    let counts: Map<String, Value> = export["tables"]
+                        .as_object()
+                        .into_iter()
+                        .flatten()
+                        .map(|(table, rows)| (table.clone(), json!(rows.as_array().map_or(0, Vec::len))))
+                        .collect();
+                    Ok(json!({ "tenant": tenant.summary(), "output": path, "rows": counts }))
+                }
+            }
+        }
+    }
+}
+
+pub struct TenantRef {
+    pub id: Uuid,
+    pub slug: String,
+    pub name: String,
+    pub is_active: bool,
+}
+
+impl TenantRef {
+    pub fn summary(&self) -> Value {
+        json!({ "id": self.id, "slug": self.slug, "name": self.name, "is_active": self.is_active })
+    }
+}
+
+/// Look a tenant up by id or slug.
+pub async fn find(conn: &mut PgConnection, key: &str) -> Result<TenantRef> {
+    let row = match Uuid::parse_str(key) {
+        Ok(id) => sqlx::query("SELECT id, slug, name, is_active FROM tenants WHERE id = $1").bind(id),
+        Err(_) => sqlx::query("SELECT id, slug, name, is_active FROM tenants WHERE slug = $1").bind(key),
+    }
+    .fetch_optional(&mut *conn)
+    .await?
+    .with_context(|| format!("tenant '{}' not found", key))?;
+
+    Ok(TenantRef {
+        id: row.get("id"),
+        slug: row.get("slug"),
+        name: row.get("name"),
+        is_active: row.get("is_active"),
+    })
+}
+
+/// Point row-level security at `tenant_id` for the rest of the transaction.
+pub async fn set_tenant(conn: &mut PgConnection, tenant_id: Uuid) -> Result<()> {
+    sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(tenant_id.to_string())
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+async fn create(pool: &PgPool, plan: &str, request: &RegisterTenantRequest) -> Result<Value> {
+    request.validate().context("invalid tenant")?;
+    let password = PasswordService::new();
+    if let Err(problems) = password.validate_password_strength(&request.admin_password) {
+        anyhow::bail!("weak owner password: {}", problems.join("; "));
+    }
+
+    let mut tx = pool.begin().await?;
+    let slug_taken: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM tenants WHERE slug = $1)")
+        .bind(&request.slug)
+        .fetch_one(&mut *tx)
+        .await?;
+    if slug_taken {
+        anyhow::bail!("slug '{}' is already taken", request.slug);
+    }
+    let email_taken: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE email = $1)")
+        .bind(&request.admin_email)
+        .fetch_one(&mut *tx)
+        .await?;
+    if email_taken {
+        anyhow::bail!("a user with email '{}' already exists", request.admin_email);
+    }
+
+    let tenant_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO tenants (name, slug, plan, settings, is_active) VALUES ($1, $2, $3, '{}', true) RETURNING id",
+    )
+    .bind(&request.company_name)
+    .bind(&request.slug)
+    .bind(plan)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let password_hash = password.hash_password(&request.admin_password).map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    let user_id: Uuid = sqlx::query_scalar(
+        "INSERT INTO users (email, password_hash, first_name, last_name, is_active) VALUES ($1, $2, $3, $4, true) RETURNING id",
+    )
+    .bind(&request.admin_email)
+    .bind(password_hash)
+    .bind(&request.admin_first_name)
+    .bind(&request.admin_last_name)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query("INSERT INTO tenant_memberships (tenant_id, user_id, role, is_active) VALUES ($1, $2, 'owner', true)")
+        .bind(tenant_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(json!({
+        "tenant": { "id": tenant_id, "name": request.company_name, "slug": request.slug, "plan": plan },
+        "owner": { "id": user_id, "email": request.admin_email },
+    }))
+}
+
+async fn set_active(pool: &PgPool, key: &str, active: bool) -> Result<Value> {
+    let mut conn = pool.acquire().await?;
+    let tenant = find(&mut conn, key).await?;
+    let changed = tenant.is_active != active;
+    if changed {
+        sqlx::query("UPDATE tenants SET is_active = $2, updated_at = NOW() WHERE id = $1")
+            .bind(tenant.id)
+            .bind(active)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(json!({ "tenant": TenantRef { is_active: active, ..tenant }.summary(), "changed": changed }))
+}
+
+/// Every table with a `tenant_id` column, read in one snapshot, plus the
+/// member accounts (without password hashes).
+async fn export(pool: &PgPool, tenant: &TenantRef) -> Result<Value> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    set_tenant(&mut tx, tenant.id).await?;
+
+    let tenant_row: Value = sqlx::query_scalar("SELECT to_jsonb(t) FROM tenants t WHERE id = $1")
+        .bind(tenant.id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let users: Value = sqlx::query_scalar(
+        r#"SELECT COALESCE(jsonb_agg(to_jsonb(u) - 'password_hash' ORDER BY u.email), '[]')
+           FROM users u
+           WHERE u.id IN (SELECT user_id FROM tenant_memberships WHERE tenant_id = $1)"#,
+    )
+    .bind(tenant.id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let tables: Vec<String> = sqlx::query_scalar(
+        r#"SELECT c.table_name::text
+           FROM information_schema.columns c
+           JOIN information_schema.tables t ON t.table_schema = c.table_schema AND t.table_name = c.table_name
+           WHERE c.table_schema = current_schema() AND c.column_name = 'tenant_id' AND t.table_type = 'BASE TABLE'
+           ORDER BY c.table_name"#,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let secret_columns: Vec<String> = SECRET_COLUMNS.iter().map(|c| c.to_string()).collect();
+    let mut rows = Map::new();
+    for table in tables {
+        // Names come from the catalog; quoting keeps them literal anyway
+        let sql = format!(
+            r#"SELECT COALESCE(jsonb_agg(to_jsonb(r) - $2::text[]), '[]') FROM "{}" r WHERE tenant_id = $1"#,
+            table.replace('"', "\"\"")
+        );
+        let data: Value = sqlx::query_scalar(&sql)
+            .bind(tenant.id)
+            .bind(&secret_columns)
+            .fetch_one(&mut *tx)
+            .await
+            .with_context(|| format!("failed to export {}", table))?;
+        rows.insert(table, data);
+    }
+    tx.commit().await?;
+
+    Ok(json!({
+        "exported_at": Utc::now(),
+        "tenant": tenant_row,
+        "users": users,
+        "tables": rows,
+    }))
+}
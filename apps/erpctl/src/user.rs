@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use auth::PasswordService;
+use clap::Subcommand;
+use rand::{seq::SliceRandom, Rng};
+use serde_json::{json, Value};
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+/// Characters of generated passwords; no look-alikes such as `0`/`O` or `1`/`l`
+const LOWER: &[u8] = b"abcdefghijkmnopqrstuvwxyz";
+const UPPER: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+const DIGITS: &[u8] = b"23456789";
+const SPECIAL: &[u8] = b"!@#$%^&*-_=+?";
+const GENERATED_LENGTH: usize = 20;
+
+#[derive(Subcommand)]
+pub enum UserCommand {
+    /// Set a new password; one is generated and printed when omitted
+    ResetPassword {
+        email: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Reactivate a deactivated account
+    Unlock {
+        email: String,
+    },
+}
+
+pub async fn run(pool: &PgPool, command: UserCommand) -> Result<Value> {
+    match command {
+        UserCommand::ResetPassword { email, password } => {
+            let service = PasswordService::new();
+            let generated = password.is_none();
+            let password = password.unwrap_or_else(generate_password);
+            if let Err(problems) = service.validate_password_strength(&password) {
+                anyhow::bail!("weak password: {}", problems.join("; "));
+            }
+            let hash = service.hash_password(&password).map_err(|e| anyhow::anyhow!(e.to_string()))?;
+
+            let id: Uuid = sqlx::query_scalar(
+                "UPDATE users SET password_hash = $2, updated_at = NOW() WHERE email = $1 RETURNING id",
+            )
+            .bind(&email)
+            .bind(hash)
+            .fetch_optional(pool)
+            .await?
+            .with_context(|| format!("user '{}' not found", email))?;
+
+            let mut result = json!({ "user": { "id": id, "email": email } });
+            if generated {
+                result["password"] = json!(password);
+            }
+            Ok(result)
+        }
+        UserCommand::Unlock { email } => {
+            let row = sqlx::query(
+                r#"UPDATE users u SET is_active = true, updated_at = NOW()
+                   FROM (SELECT id, is_active FROM users WHERE email = $1 FOR UPDATE) old
+                   WHERE u.id = old.id
+                   RETURNING u.id, old.is_active AS was_active"#,
+            )
+            .bind(&email)
+            .fetch_optional(pool)
+            .await?
+            .with_context(|| format!("user '{}' not found", email))?;
+
+            Ok(json!({
+                "user": { "id": row.get::<Uuid, _>("id"), "email": email },
+                "changed": !row.get::<bool, _>("was_active"),
+            }))
+        }
+    }
+}
+
+/// A random password that passes `PasswordService::validate_password_strength`.
+pub fn generate_password() -> String {
+    let mut rng = rand::thread_rng();
+    let classes = [LOWER, UPPER, DIGITS, SPECIAL];
+    let all: Vec<u8> = classes.concat();
+
+    // One of each class, the rest from all of them, then shuffled
+    let mut bytes: Vec<u8> = classes.iter().map(|class| class[rng.gen_range(0..class.len())]).collect();
+    bytes.extend((classes.len()..GENERATED_LENGTH).map(|_| all[rng.gen_range(0..all.len())]));
+    bytes.shuffle(&mut rng);
+    String::from_utf8(bytes).expect("password alphabet is ASCII")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_passwords_are_strong_and_distinct() {
+        let service = PasswordService::new();
+        let first = generate_password();
+        assert_eq!(first.len(), GENERATED_LENGTH);
+        assert_ne!(first, generate_password());
+        for _ in 0..100 {
+            assert_eq!(service.validate_password_strength(&generate_password()), Ok(()));
+        }
+    }
+}
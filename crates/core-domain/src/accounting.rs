@@ -0,0 +1,248 @@
+//! Journal entries: one-sided lines that must balance, and posting.
+
+use shared_types::{Currency, JournalEntryStatus, Money};
+use uuid::Uuid;
+
+use crate::{DomainError, DomainResult};
+
+/// A debit or a credit to one account.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JournalLine {
+    account_id: Uuid,
+    debit: Money,
+    credit: Money,
+}
+
+impl JournalLine {
+    /// Exactly one of `debit` and `credit` is positive, the other zero.
+    pub fn new(account_id: Uuid, debit: Money, credit: Money) -> DomainResult<JournalLine> {
+        if debit.currency() != credit.currency() {
+            return Err(invalid("debit and credit must be in the same currency"));
+        }
+        if debit.is_negative() || credit.is_negative() {
+            return Err(invalid("amounts must not be negative"));
+        }
+        if debit.is_zero() == credit.is_zero() {
+            return Err(invalid("each line must have either a debit or a credit amount, not both or neither"));
+        }
+        Ok(JournalLine { account_id, debit, credit })
+    }
+
+    pub fn debit(account_id: Uuid, amount: Money) -> DomainResult<JournalLine> {
+        JournalLine::new(account_id, amount, Money::zero(amount.currency()))
+    }
+
+    pub fn credit(account_id: Uuid, amount: Money) -> DomainResult<JournalLine> {
+        JournalLine::new(account_id, Money::zero(amount.currency()), amount)
+    }
+
+    pub fn account_id(&self) -> Uuid {
+        self.account_id
+    }
+
+    pub fn debit_amount(&self) -> Money {
+        self.debit
+    }
+
+    pub fn credit_amount(&self) -> Money {
+        self.credit
+    }
+}
+
+/// A balanced set of lines in one currency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    status: JournalEntryStatus,
+    currency: Currency,
+    lines: Vec<JournalLine>,
+    total: Money,
+}
+
+impl JournalEntry {
+    /// A new draft. Debits must equal credits; since every line is one-sided
+    /// and positive, that takes at least one of each.
+    pub fn draft(currency: Currency, lines: Vec<JournalLine>) -> DomainResult<JournalEntry> {
+        JournalEntry::restore(JournalEntryStatus::Draft, currency, lines)
+    }
+
+    /// An entry as stored.
+    pub fn restore(status: JournalEntryStatus, currency: Currency, lines: Vec<JournalLine>) -> DomainResult<JournalEntry> {
+        if lines.is_empty() {
+            return Err(invalid("a journal entry needs lines"));
+        }
+        let debit = Money::sum(currency, lines.iter().map(|l| l.debit))?;
+        let credit = Money::sum(currency, lines.iter().map(|l| l.credit))?;
+        if debit != credit {
+            return Err(DomainError::UnbalancedJournalEntry { debit, credit });
+        }
+        Ok(JournalEntry { status, currency, lines, total: debit })
+    }
+
+    pub fn status(&self) -> JournalEntryStatus {
+        self.status
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn lines(&self) -> &[JournalLine] {
+        &self.lines
+    }
+
+    /// Total debits, which are also the total credits.
+    pub fn total(&self) -> Money {
+        self.total
+    }
+
+    pub fn post(&mut self) -> DomainResult<()> {
+        self.transition_to(JournalEntryStatus::Posted)
+    }
+
+    /// Only posted entries are reversed; drafts are simply deleted.
+    pub fn reverse(&mut self) -> DomainResult<()> {
+        self.transition_to(JournalEntryStatus::Reversed)
+    }
+
+    fn transition_to(&mut self, to: JournalEntryStatus) -> DomainResult<()> {
+        use JournalEntryStatus::*;
+        match (self.status, to) {
+            (Draft, Posted) | (Posted, Reversed) => {
+                self.status = to;
+                Ok(())
+            }
+            (from, to) => Err(DomainError::InvalidStatusTransition {
+                from: status_name(from).to_string(),
+                to: status_name(to).to_string(),
+            }),
+        }
+    }
+}
+
+/// As stored and serialized.
+pub fn status_name(status: JournalEntryStatus) -> &'static str {
+    match status {
+        JournalEntryStatus::Draft => "draft",
+        JournalEntryStatus::Posted => "posted",
+        JournalEntryStatus::Reversed => "reversed",
+    }
+}
+
+fn invalid(message: &str) -> DomainError {
+    DomainError::ValidationFailed { message: message.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use JournalEntryStatus::*;
+
+    fn idr(amount: i64) -> Money {
+        Money::new(Decimal::from(amount), Currency::IDR)
+    }
+
+    fn cash() -> Uuid {
+        Uuid::from_u128(1)
+    }
+
+    fn revenue() -> Uuid {
+        Uuid::from_u128(2)
+    }
+
+    fn sale(amount: i64) -> JournalEntry {
+        JournalEntry::draft(
+            Currency::IDR,
+            vec![JournalLine::debit(cash(), idr(amount)).unwrap(), JournalLine::credit(revenue(), idr(amount)).unwrap()],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn lines_are_one_sided_and_positive() {
+        assert!(JournalLine::debit(cash(), idr(100)).is_ok());
+        assert!(JournalLine::credit(cash(), idr(100)).is_ok());
+        for bad in [
+            JournalLine::new(cash(), idr(100), idr(100)),
+            JournalLine::new(cash(), idr(0), idr(0)),
+            JournalLine::debit(cash(), idr(-100)),
+            JournalLine::credit(cash(), idr(-100)),
+            JournalLine::new(cash(), idr(100), Money::zero(Currency::USD)),
+        ] {
+            assert!(matches!(bad, Err(DomainError::ValidationFailed { .. })), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn a_balanced_entry_totals_its_debits() {
+        let entry = JournalEntry::draft(
+            Currency::IDR,
+            vec![
+                JournalLine::debit(cash(), idr(700)).unwrap(),
+                JournalLine::debit(cash(), idr(300)).unwrap(),
+                JournalLine::credit(revenue(), idr(1_000)).unwrap(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(entry.total(), idr(1_000));
+        assert_eq!(entry.lines().len(), 3);
+        assert_eq!(entry.status(), Draft);
+    }
+
+    #[test]
+    fn unbalanced_entries_are_refused_with_both_totals() {
+        let entry = JournalEntry::draft(
+            Currency::IDR,
+            vec![JournalLine::debit(cash(), idr(1_000)).unwrap(), JournalLine::credit(revenue(), idr(900)).unwrap()],
+        );
+        match entry {
+            Err(DomainError::UnbalancedJournalEntry { debit, credit }) => {
+                assert_eq!((debit, credit), (idr(1_000), idr(900)));
+            }
+            other => panic!("expected an unbalanced entry, got {:?}", other),
+        }
+
+        let one_sided = JournalEntry::draft(Currency::IDR, vec![JournalLine::debit(cash(), idr(1_000)).unwrap()]);
+        assert!(matches!(one_sided, Err(DomainError::UnbalancedJournalEntry { .. })));
+    }
+
+    #[test]
+    fn entries_need_lines_in_their_currency() {
+        assert!(matches!(JournalEntry::draft(Currency::IDR, vec![]), Err(DomainError::ValidationFailed { .. })));
+        let usd = Money::new(Decimal::from(10), Currency::USD);
+        let mixed = JournalEntry::draft(
+            Currency::IDR,
+            vec![JournalLine::debit(cash(), usd).unwrap(), JournalLine::credit(revenue(), usd).unwrap()],
+        );
+        assert!(matches!(mixed, Err(DomainError::ValidationFailed { .. })));
+    }
+
+    #[test]
+    fn drafts_are_posted_and_posted_entries_reversed() {
+        let mut entry = sale(1_000);
+        entry.post().unwrap();
+        assert_eq!(entry.status(), Posted);
+        entry.reverse().unwrap();
+        assert_eq!(entry.status(), Reversed);
+    }
+
+    #[test]
+    fn other_moves_are_invalid_transitions() {
+        let mut draft = sale(1_000);
+        assert!(matches!(draft.reverse(), Err(DomainError::InvalidStatusTransition { .. })));
+
+        let mut posted = sale(1_000);
+        posted.post().unwrap();
+        match posted.post() {
+            Err(DomainError::InvalidStatusTransition { from, to }) => assert_eq!((from.as_str(), to.as_str()), ("posted", "posted")),
+            other => panic!("expected an invalid transition, got {:?}", other),
+        }
+
+        let mut reversed = sale(1_000);
+        reversed.post().unwrap();
+        reversed.reverse().unwrap();
+        assert!(reversed.post().is_err());
+        assert!(reversed.reverse().is_err());
+        assert_eq!(reversed.status(), Reversed);
+    }
+}
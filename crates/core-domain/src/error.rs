@@ -1,3 +1,4 @@
+use shared_types::{i18n, Locale, Money, MoneyError};
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -53,6 +54,9 @@ pub enum DomainError {
     #[error("Invalid status transition from {from} to {to}")]
     InvalidStatusTransition { from: String, to: String },
     
+    #[error("Unbalanced journal entry: debits {debit}, credits {credit}")]
+    UnbalancedJournalEntry { debit: Money, credit: Money },
+    
     #[error("Duplicate entry: {field}")]
     DuplicateEntry { field: String },
     
@@ -99,6 +103,7 @@ impl DomainError {
             Self::InvalidInvitation => "INVALID_INVITATION",
             Self::InsufficientStock { .. } => "INSUFFICIENT_STOCK",
             Self::InvalidStatusTransition { .. } => "INVALID_STATUS_TRANSITION",
+            Self::UnbalancedJournalEntry { .. } => "UNBALANCED_JOURNAL_ENTRY",
             Self::DuplicateEntry { .. } => "DUPLICATE_ENTRY",
             Self::ReferencedByOtherEntity => "REFERENCED_BY_OTHER_ENTITY",
             Self::ValidationFailed { .. } => "VALIDATION_FAILED",
@@ -110,6 +115,22 @@ impl DomainError {
         }
     }
 
+    /// Message for end users in `locale`. Identifiers are left out; details
+    /// a user can act on, such as the field or the statuses, are appended.
+    pub fn message(&self, locale: Locale) -> String {
+        let message = i18n::translate(self.error_code(), locale, &[]);
+        let detail = match self {
+            Self::InsufficientPermissions { permission } => permission.clone(),
+            Self::InvalidStatusTransition { from, to } => format!("{} → {}", from, to),
+            Self::UnbalancedJournalEntry { debit, credit } => format!("{} ≠ {}", debit, credit),
+            Self::DuplicateEntry { field } => field.clone(),
+            Self::ValidationFailed { message } | Self::Conflict { message } => message.clone(),
+            Self::NotFound { resource } => resource.clone(),
+            _ => return message,
+        };
+        format!("{}: {}", message, detail)
+    }
+
     pub fn http_status_code(&self) -> u16 {
         match self {
             Self::InvalidCredentials 
@@ -131,7 +152,9 @@ impl DomainError {
             
             Self::ValidationFailed { .. } 
             | Self::InvalidInvitation 
-            | Self::InvalidStatusTransition { .. } => 422,
+            | Self::InvalidStatusTransition { .. }
+            | Self::UnbalancedJournalEntry { .. }
+            | Self::InsufficientStock { .. } => 422,
             
             Self::EmailServiceUnavailable 
             | Self::PaymentServiceUnavailable => 502,
@@ -141,4 +164,11 @@ impl DomainError {
     }
 }
 
+/// Amounts that don't fit their currency or each other are invalid input.
+impl From<MoneyError> for DomainError {
+    fn from(e: MoneyError) -> Self {
+        Self::ValidationFailed { message: e.to_string() }
+    }
+}
+
 pub type DomainResult<T> = Result<T, DomainError>;
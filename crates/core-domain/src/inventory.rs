@@ -0,0 +1,150 @@
+//! Stock on hand and the movements that change it.
+
+use shared_types::StockMovementType;
+use uuid::Uuid;
+
+use crate::{DomainError, DomainResult};
+
+/// On-hand quantity of one product in one warehouse. Stock never goes below
+/// zero: issuing more than is on hand is refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StockLedger {
+    product_id: Uuid,
+    on_hand: i32,
+}
+
+impl StockLedger {
+    pub fn new(product_id: Uuid, on_hand: i32) -> StockLedger {
+        StockLedger { product_id, on_hand }
+    }
+
+    pub fn product_id(&self) -> Uuid {
+        self.product_id
+    }
+
+    pub fn on_hand(&self) -> i32 {
+        self.on_hand
+    }
+
+    /// At or below `minimum`, as the `low_stock` filters count it.
+    pub fn is_low(&self, minimum: i32) -> bool {
+        self.on_hand <= minimum
+    }
+
+    pub fn receive(&mut self, quantity: i32) -> DomainResult<i32> {
+        positive(quantity)?;
+        self.on_hand = self.on_hand.checked_add(quantity).ok_or_else(|| invalid("quantity is too large"))?;
+        Ok(self.on_hand)
+    }
+
+    pub fn issue(&mut self, quantity: i32) -> DomainResult<i32> {
+        positive(quantity)?;
+        if quantity > self.on_hand {
+            return Err(DomainError::InsufficientStock { product_id: self.product_id });
+        }
+        self.on_hand -= quantity;
+        Ok(self.on_hand)
+    }
+
+    /// Set the quantity to a count, e.g. after a stocktake.
+    pub fn adjust_to(&mut self, counted: i32) -> DomainResult<i32> {
+        if counted < 0 {
+            return Err(invalid("counted quantity must not be negative"));
+        }
+        self.on_hand = counted;
+        Ok(self.on_hand)
+    }
+
+    /// A stored movement's effect, with `adjustment` quantities being the new
+    /// total as in `stock_movements`. Transfers are an `out` here and an `in`
+    /// at the other warehouse, so they can't be applied to one ledger.
+    pub fn apply(&mut self, movement_type: StockMovementType, quantity: i32) -> DomainResult<i32> {
+        match movement_type {
+            StockMovementType::In => self.receive(quantity),
+            StockMovementType::Out => self.issue(quantity),
+            StockMovementType::Adjustment => self.adjust_to(quantity),
+            StockMovementType::Transfer => Err(invalid("a transfer moves stock between two warehouses")),
+        }
+    }
+}
+
+fn positive(quantity: i32) -> DomainResult<()> {
+    if quantity > 0 {
+        Ok(())
+    } else {
+        Err(invalid("quantity must be greater than 0"))
+    }
+}
+
+fn invalid(message: &str) -> DomainError {
+    DomainError::ValidationFailed { message: message.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ledger(on_hand: i32) -> StockLedger {
+        StockLedger::new(Uuid::from_u128(7), on_hand)
+    }
+
+    #[test]
+    fn receipts_and_issues_move_the_quantity() {
+        let mut stock = ledger(0);
+        assert_eq!(stock.receive(10).unwrap(), 10);
+        assert_eq!(stock.issue(4).unwrap(), 6);
+        assert_eq!(stock.issue(6).unwrap(), 0);
+        assert_eq!(stock.on_hand(), 0);
+    }
+
+    #[test]
+    fn issuing_more_than_on_hand_is_insufficient_stock() {
+        let mut stock = ledger(3);
+        match stock.issue(4) {
+            Err(DomainError::InsufficientStock { product_id }) => assert_eq!(product_id, Uuid::from_u128(7)),
+            other => panic!("expected insufficient stock, got {:?}", other),
+        }
+        assert_eq!(stock.on_hand(), 3);
+    }
+
+    #[test]
+    fn quantities_must_be_positive() {
+        let mut stock = ledger(5);
+        for bad in [stock.receive(0), stock.receive(-1), stock.issue(0), stock.issue(-1), stock.adjust_to(-1)] {
+            assert!(matches!(bad, Err(DomainError::ValidationFailed { .. })), "{:?}", bad);
+        }
+        assert_eq!(stock.on_hand(), 5);
+    }
+
+    #[test]
+    fn receipts_that_overflow_are_refused() {
+        let mut stock = ledger(i32::MAX);
+        assert!(stock.receive(1).is_err());
+        assert_eq!(stock.on_hand(), i32::MAX);
+    }
+
+    #[test]
+    fn adjustments_set_the_count() {
+        let mut stock = ledger(5);
+        assert_eq!(stock.adjust_to(2).unwrap(), 2);
+        assert_eq!(stock.adjust_to(0).unwrap(), 0);
+    }
+
+    #[test]
+    fn movements_apply_by_type() {
+        let mut stock = ledger(0);
+        assert_eq!(stock.apply(StockMovementType::In, 8).unwrap(), 8);
+        assert_eq!(stock.apply(StockMovementType::Out, 3).unwrap(), 5);
+        assert_eq!(stock.apply(StockMovementType::Adjustment, 4).unwrap(), 4);
+        assert!(matches!(stock.apply(StockMovementType::Out, 5), Err(DomainError::InsufficientStock { .. })));
+        assert!(matches!(stock.apply(StockMovementType::Transfer, 1), Err(DomainError::ValidationFailed { .. })));
+        assert_eq!(stock.on_hand(), 4);
+    }
+
+    #[test]
+    fn low_is_at_or_below_the_minimum() {
+        assert!(ledger(5).is_low(5));
+        assert!(ledger(4).is_low(5));
+        assert!(!ledger(6).is_low(5));
+    }
+}
@@ -1,8 +1,14 @@
+pub mod accounting;
 pub mod auth;
+pub mod inventory;
+pub mod procurement;
 pub mod tenant;
 pub mod user;
 pub mod error;
 pub mod events;
 
+pub use accounting::{JournalEntry, JournalLine};
 pub use error::*;
 pub use events::*;
+pub use inventory::StockLedger;
+pub use procurement::{OrderLine, OrderTotals, PurchaseOrder};
@@ -0,0 +1,382 @@
+//! Purchase orders: line amounts, header totals and the status lifecycle.
+
+use rust_decimal::Decimal;
+use shared_types::{Currency, Money, MoneyError, PurchaseOrderStatus};
+use uuid::Uuid;
+
+use crate::{DomainError, DomainResult};
+
+/// One product on an order, priced in the order's currency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderLine {
+    product_id: Uuid,
+    quantity: i32,
+    unit_price: Money,
+    discount_percent: Decimal,
+    discount_amount: Money,
+    tax_percent: Decimal,
+    tax_amount: Money,
+    line_total: Money,
+}
+
+impl OrderLine {
+    /// Discount applies to the gross amount, tax to the amount after discount.
+    pub fn new(
+        product_id: Uuid,
+        quantity: i32,
+        unit_price: Money,
+        discount_percent: Decimal,
+        tax_percent: Decimal,
+    ) -> DomainResult<OrderLine> {
+        if quantity <= 0 {
+            return Err(invalid("quantity must be greater than 0"));
+        }
+        if unit_price.is_negative() {
+            return Err(invalid("unit price must not be negative"));
+        }
+        if discount_percent < Decimal::ZERO || discount_percent > Decimal::ONE_HUNDRED {
+            return Err(invalid("discount must be between 0 and 100 percent"));
+        }
+        if tax_percent < Decimal::ZERO {
+            return Err(invalid("tax must not be negative"));
+        }
+
+        let gross = unit_price.times(Decimal::from(quantity))?;
+        let discount_amount = gross.percent(discount_percent)?;
+        let net = gross.checked_sub(discount_amount)?;
+        let tax_amount = net.percent(tax_percent)?;
+        let line_total = net.checked_add(tax_amount)?;
+        Ok(OrderLine {
+            product_id,
+            quantity,
+            unit_price,
+            discount_percent,
+            discount_amount,
+            tax_percent,
+            tax_amount,
+            line_total,
+        })
+    }
+
+    pub fn product_id(&self) -> Uuid {
+        self.product_id
+    }
+
+    pub fn quantity(&self) -> i32 {
+        self.quantity
+    }
+
+    pub fn unit_price(&self) -> Money {
+        self.unit_price
+    }
+
+    pub fn discount_percent(&self) -> Decimal {
+        self.discount_percent
+    }
+
+    pub fn discount_amount(&self) -> Money {
+        self.discount_amount
+    }
+
+    pub fn tax_percent(&self) -> Decimal {
+        self.tax_percent
+    }
+
+    pub fn tax_amount(&self) -> Money {
+        self.tax_amount
+    }
+
+    /// After discount, including tax.
+    pub fn line_total(&self) -> Money {
+        self.line_total
+    }
+}
+
+/// Header totals as `calculate_purchase_order_totals` computes them: the
+/// subtotal is after discounts and before tax.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrderTotals {
+    pub subtotal: Money,
+    pub discount_amount: Money,
+    pub tax_amount: Money,
+    pub total_amount: Money,
+}
+
+impl OrderTotals {
+    fn of(currency: Currency, lines: &[OrderLine]) -> Result<OrderTotals, MoneyError> {
+        let discount_amount = Money::sum(currency, lines.iter().map(|l| l.discount_amount))?;
+        let tax_amount = Money::sum(currency, lines.iter().map(|l| l.tax_amount))?;
+        let total_amount = Money::sum(currency, lines.iter().map(|l| l.line_total))?;
+        let subtotal = total_amount.checked_sub(tax_amount)?;
+        Ok(OrderTotals { subtotal, discount_amount, tax_amount, total_amount })
+    }
+}
+
+/// A purchase order's lines and status. Lines only change on drafts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PurchaseOrder {
+    status: PurchaseOrderStatus,
+    currency: Currency,
+    lines: Vec<OrderLine>,
+    totals: OrderTotals,
+}
+
+impl PurchaseOrder {
+    /// A new draft with at least one line, all priced in `currency`.
+    pub fn draft(currency: Currency, lines: Vec<OrderLine>) -> DomainResult<PurchaseOrder> {
+        PurchaseOrder::restore(PurchaseOrderStatus::Draft, currency, lines)
+    }
+
+    /// An order as stored.
+    pub fn restore(status: PurchaseOrderStatus, currency: Currency, lines: Vec<OrderLine>) -> DomainResult<PurchaseOrder> {
+        if lines.is_empty() {
+            return Err(invalid("a purchase order needs at least one line"));
+        }
+        let totals = OrderTotals::of(currency, &lines)?;
+        Ok(PurchaseOrder { status, currency, lines, totals })
+    }
+
+    pub fn status(&self) -> PurchaseOrderStatus {
+        self.status
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn lines(&self) -> &[OrderLine] {
+        &self.lines
+    }
+
+    pub fn totals(&self) -> OrderTotals {
+        self.totals
+    }
+
+    /// Replace the lines of a draft.
+    pub fn set_lines(&mut self, lines: Vec<OrderLine>) -> DomainResult<()> {
+        if self.status != PurchaseOrderStatus::Draft {
+            return Err(DomainError::Conflict {
+                message: format!("lines of a {} purchase order cannot change", status_name(self.status)),
+            });
+        }
+        *self = PurchaseOrder::draft(self.currency, lines)?;
+        Ok(())
+    }
+
+    /// Send a draft for approval.
+    pub fn submit(&mut self) -> DomainResult<()> {
+        self.transition_to(PurchaseOrderStatus::Pending)
+    }
+
+    pub fn approve(&mut self) -> DomainResult<()> {
+        self.transition_to(PurchaseOrderStatus::Approved)
+    }
+
+    /// Return a pending order to its author as a draft.
+    pub fn reject(&mut self) -> DomainResult<()> {
+        self.transition_to(PurchaseOrderStatus::Draft)
+    }
+
+    pub fn cancel(&mut self) -> DomainResult<()> {
+        self.transition_to(PurchaseOrderStatus::Cancelled)
+    }
+
+    pub fn transition_to(&mut self, to: PurchaseOrderStatus) -> DomainResult<()> {
+        self.status = transition(self.status, to)?;
+        Ok(())
+    }
+}
+
+/// `to`, if an order in `from` may move there. Orders go draft → pending →
+/// approved → sent → (partially_)received → closed; a pending order can go
+/// back to draft, a partially received one can be closed short, and anything
+/// not yet received can be cancelled.
+pub fn transition(from: PurchaseOrderStatus, to: PurchaseOrderStatus) -> DomainResult<PurchaseOrderStatus> {
+    if can_transition(from, to) {
+        Ok(to)
+    } else {
+        Err(DomainError::InvalidStatusTransition {
+            from: status_name(from).to_string(),
+            to: status_name(to).to_string(),
+        })
+    }
+}
+
+pub fn can_transition(from: PurchaseOrderStatus, to: PurchaseOrderStatus) -> bool {
+    use PurchaseOrderStatus::*;
+    matches!(
+        (from, to),
+        (Draft, Pending)
+            | (Pending, Approved)
+            | (Pending, Draft)
+            | (Approved, Sent)
+            | (Sent, PartiallyReceived)
+            | (Sent, Received)
+            | (PartiallyReceived, PartiallyReceived)
+            | (PartiallyReceived, Received)
+            | (PartiallyReceived, Closed)
+            | (Received, Closed)
+            | (Draft | Pending | Approved | Sent, Cancelled)
+    )
+}
+
+/// As stored and serialized.
+pub fn status_name(status: PurchaseOrderStatus) -> &'static str {
+    match status {
+        PurchaseOrderStatus::Draft => "draft",
+        PurchaseOrderStatus::Pending => "pending",
+        PurchaseOrderStatus::Approved => "approved",
+        PurchaseOrderStatus::Sent => "sent",
+        PurchaseOrderStatus::PartiallyReceived => "partially_received",
+        PurchaseOrderStatus::Received => "received",
+        PurchaseOrderStatus::Cancelled => "cancelled",
+        PurchaseOrderStatus::Closed => "closed",
+    }
+}
+
+fn invalid(message: &str) -> DomainError {
+    DomainError::ValidationFailed { message: message.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PurchaseOrderStatus::*;
+
+    const ALL: [PurchaseOrderStatus; 8] = [Draft, Pending, Approved, Sent, PartiallyReceived, Received, Cancelled, Closed];
+
+    fn idr(amount: i64) -> Money {
+        Money::new(Decimal::from(amount), Currency::IDR)
+    }
+
+    fn usd(cents: i64) -> Money {
+        Money::new(Decimal::new(cents, 2), Currency::USD)
+    }
+
+    fn line(quantity: i32, unit_price: Money, discount: i64, tax: i64) -> DomainResult<OrderLine> {
+        OrderLine::new(Uuid::nil(), quantity, unit_price, Decimal::from(discount), Decimal::from(tax))
+    }
+
+    #[test]
+    fn discount_comes_off_the_gross_and_tax_goes_on_the_net() {
+        let l = line(3, idr(10_000), 10, 11).unwrap();
+        assert_eq!(l.discount_amount(), idr(3_000));
+        assert_eq!(l.tax_amount(), idr(2_970));
+        assert_eq!(l.line_total(), idr(29_970));
+    }
+
+    #[test]
+    fn line_amounts_round_to_the_currency() {
+        // 2 × 3.33 = 6.66; 6.66 × 12.5% = 0.8325 → 0.83; 5.83 × 11% = 0.6413 → 0.64
+        let l = OrderLine::new(Uuid::nil(), 2, usd(333), Decimal::new(125, 1), Decimal::from(11)).unwrap();
+        assert_eq!(l.discount_amount(), usd(83));
+        assert_eq!(l.tax_amount(), usd(64));
+        assert_eq!(l.line_total(), usd(647));
+    }
+
+    #[test]
+    fn free_lines_are_allowed() {
+        assert_eq!(line(1, idr(0), 0, 11).unwrap().line_total(), idr(0));
+    }
+
+    #[test]
+    fn invalid_lines_are_rejected() {
+        for bad in [
+            line(0, idr(1_000), 0, 0),
+            line(-1, idr(1_000), 0, 0),
+            line(1, idr(-1), 0, 0),
+            line(1, idr(1_000), -1, 0),
+            line(1, idr(1_000), 101, 0),
+            line(1, idr(1_000), 0, -1),
+        ] {
+            assert!(matches!(bad, Err(DomainError::ValidationFailed { .. })), "{:?}", bad);
+        }
+        assert!(line(1, idr(1_000), 100, 0).is_ok());
+    }
+
+    #[test]
+    fn totals_add_up_the_lines() {
+        let order = PurchaseOrder::draft(
+            Currency::IDR,
+            vec![line(3, idr(10_000), 10, 11).unwrap(), line(1, idr(5_000), 0, 0).unwrap()],
+        )
+        .unwrap();
+        let totals = order.totals();
+        assert_eq!(totals.discount_amount, idr(3_000));
+        assert_eq!(totals.tax_amount, idr(2_970));
+        assert_eq!(totals.total_amount, idr(34_970));
+        assert_eq!(totals.subtotal, idr(32_000));
+        assert_eq!(order.status(), Draft);
+    }
+
+    #[test]
+    fn an_order_needs_lines_in_its_currency() {
+        assert!(matches!(PurchaseOrder::draft(Currency::IDR, vec![]), Err(DomainError::ValidationFailed { .. })));
+        let mixed = PurchaseOrder::draft(Currency::IDR, vec![line(1, usd(100), 0, 0).unwrap()]);
+        assert!(matches!(mixed, Err(DomainError::ValidationFailed { .. })));
+    }
+
+    #[test]
+    fn only_drafts_change_lines() {
+        let mut order = PurchaseOrder::draft(Currency::IDR, vec![line(1, idr(1_000), 0, 0).unwrap()]).unwrap();
+        order.set_lines(vec![line(2, idr(1_000), 0, 0).unwrap()]).unwrap();
+        assert_eq!(order.totals().total_amount, idr(2_000));
+
+        order.submit().unwrap();
+        let refused = order.set_lines(vec![line(5, idr(1_000), 0, 0).unwrap()]);
+        assert!(matches!(refused, Err(DomainError::Conflict { .. })));
+        assert_eq!(order.totals().total_amount, idr(2_000));
+    }
+
+    #[test]
+    fn the_lifecycle_runs_from_draft_to_closed() {
+        let mut order = PurchaseOrder::draft(Currency::IDR, vec![line(1, idr(1_000), 0, 0).unwrap()]).unwrap();
+        order.submit().unwrap();
+        order.reject().unwrap();
+        order.submit().unwrap();
+        order.approve().unwrap();
+        for next in [Sent, PartiallyReceived, PartiallyReceived, Received, Closed] {
+            order.transition_to(next).unwrap();
+        }
+        assert_eq!(order.status(), Closed);
+    }
+
+    #[test]
+    fn illegal_moves_name_both_statuses_and_change_nothing() {
+        let mut order = PurchaseOrder::restore(Received, Currency::IDR, vec![line(1, idr(1_000), 0, 0).unwrap()]).unwrap();
+        match order.cancel() {
+            Err(DomainError::InvalidStatusTransition { from, to }) => {
+                assert_eq!((from.as_str(), to.as_str()), ("received", "cancelled"));
+            }
+            other => panic!("expected an invalid transition, got {:?}", other),
+        }
+        assert_eq!(order.status(), Received);
+    }
+
+    #[test]
+    fn transition_table() {
+        let legal = [
+            (Draft, Pending),
+            (Draft, Cancelled),
+            (Pending, Approved),
+            (Pending, Draft),
+            (Pending, Cancelled),
+            (Approved, Sent),
+            (Approved, Cancelled),
+            (Sent, PartiallyReceived),
+            (Sent, Received),
+            (Sent, Cancelled),
+            (PartiallyReceived, PartiallyReceived),
+            (PartiallyReceived, Received),
+            (PartiallyReceived, Closed),
+            (Received, Closed),
+        ];
+        for from in ALL {
+            for to in ALL {
+                let expected = legal.contains(&(from, to));
+                assert_eq!(can_transition(from, to), expected, "{:?} -> {:?}", from, to);
+                assert_eq!(transition(from, to).is_ok(), expected, "{:?} -> {:?}", from, to);
+            }
+        }
+    }
+}
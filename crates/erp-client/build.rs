@@ -0,0 +1,373 @@
+//! Generates `models.rs` and `operations.rs` in `OUT_DIR` from `openapi.json`.
+//!
+//! Only the subset of OpenAPI that the API's utoipa derives emit is handled:
+//! objects, string enums, `allOf` composition, `$ref`s and the standard
+//! `ApiResponse` envelope around success bodies.
+
+use std::{collections::BTreeSet, env, fmt::Write as _, fs, path::Path};
+
+use serde_json::Value;
+
+const SPEC: &str = "openapi.json";
+
+/// The envelope is `erp_client::Envelope`; its bare component carries no data.
+const ENVELOPE: &str = "ApiResponse";
+
+const KEYWORDS: &[&str] = &["type", "ref", "match", "mod", "use", "move", "self", "crate", "fn", "impl", "loop", "where"];
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", SPEC);
+    let spec: Value = serde_json::from_str(&fs::read_to_string(SPEC).expect("read openapi.json")).expect("parse openapi.json");
+    let out = env::var("OUT_DIR").unwrap();
+
+    fs::write(Path::new(&out).join("models.rs"), models(&spec)).unwrap();
+    fs::write(Path::new(&out).join("operations.rs"), operations(&spec)).unwrap();
+}
+
+fn models(spec: &Value) -> String {
+    let mut out = String::new();
+    for (name, schema) in spec["components"]["schemas"].as_object().unwrap() {
+        if name == ENVELOPE {
+            continue;
+        }
+        docs(&mut out, "", schema["description"].as_str());
+        if let Some(values) = schema["enum"].as_array() {
+            string_enum(&mut out, name, values);
+        } else if let Some(parts) = schema["allOf"].as_array() {
+            let mut fields = String::new();
+            let mut all_optional = true;
+            for part in parts {
+                match part["$ref"].as_str() {
+                    Some(target) => {
+                        let base = ref_name(target);
+                        writeln!(fields, "    #[serde(flatten)]\n    pub {}: {},", snake(base), model(base)).unwrap();
+                        all_optional = false;
+                    }
+                    None => all_optional &= properties(&mut fields, part),
+                }
+            }
+            structure(&mut out, name, &fields, all_optional);
+        } else if schema["type"] == "object" {
+            let mut fields = String::new();
+            let all_optional = properties(&mut fields, schema);
+            structure(&mut out, name, &fields, all_optional);
+        } else {
+            writeln!(out, "pub type {} = {};\n", name, rust_type(schema)).unwrap();
+        }
+    }
+    out
+}
+
+fn string_enum(out: &mut String, name: &str, values: &[Value]) {
+    let values: Vec<&str> = values.iter().map(|v| v.as_str().expect("string enum")).collect();
+    writeln!(out, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]").unwrap();
+    writeln!(out, "pub enum {} {{", name).unwrap();
+    for value in &values {
+        writeln!(out, "    #[serde(rename = \"{}\")]\n    {},", value, pascal(value)).unwrap();
+    }
+    writeln!(out, "}}\n\nimpl {} {{\n    pub fn as_str(&self) -> &'static str {{\n        match self {{", name).unwrap();
+    for value in &values {
+        writeln!(out, "            Self::{} => \"{}\",", pascal(value), value).unwrap();
+    }
+    writeln!(out, "        }}\n    }}\n}}\n").unwrap();
+    writeln!(
+        out,
+        "impl std::fmt::Display for {} {{\n    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {{\n        f.write_str(self.as_str())\n    }}\n}}\n",
+        name
+    )
+    .unwrap();
+}
+
+fn structure(out: &mut String, name: &str, fields: &str, derive_default: bool) {
+    let default = if derive_default { ", Default" } else { "" };
+    writeln!(out, "#[derive(Debug, Clone, PartialEq{}, serde::Serialize, serde::Deserialize)]", default).unwrap();
+    writeln!(out, "pub struct {} {{\n{}}}\n", name, fields).unwrap();
+}
+
+/// Writes one field per property; returns whether every field is optional.
+fn properties(out: &mut String, schema: &Value) -> bool {
+    let required = names(&schema["required"]);
+    let mut all_optional = true;
+    for (property, prop) in schema["properties"].as_object().into_iter().flatten() {
+        let optional = !required.contains(property.as_str()) || nullable(prop);
+        all_optional &= optional;
+        field(out, property, prop, optional);
+    }
+    all_optional
+}
+
+fn field(out: &mut String, name: &str, schema: &Value, optional: bool) {
+    docs(out, "    ", schema["description"].as_str());
+    let ident = ident(name);
+    if ident != name {
+        writeln!(out, "    #[serde(rename = \"{}\")]", name).unwrap();
+    }
+    if optional {
+        writeln!(out, "    #[serde(default, skip_serializing_if = \"Option::is_none\")]").unwrap();
+        writeln!(out, "    pub {}: Option<{}>,", ident, rust_type(schema)).unwrap();
+    } else {
+        writeln!(out, "    pub {}: {},", ident, rust_type(schema)).unwrap();
+    }
+}
+
+/// What an operation's success response decodes to.
+enum Response {
+    /// `ApiResponse` with `data` of this type.
+    Data(String),
+    /// A page of this item type.
+    Page(String),
+    /// `ApiResponse` without `data`.
+    Empty,
+    /// Anything that isn't JSON, handed back undecoded.
+    Raw,
+}
+
+fn operations(spec: &Value) -> String {
+    let mut queries = String::new();
+    let mut methods = String::new();
+
+    for (path, item) in spec["paths"].as_object().unwrap() {
+        for (method, op) in item.as_object().unwrap() {
+            let id = op["operationId"].as_str().expect("operationId");
+            let params = op["parameters"].as_array().cloned().unwrap_or_default();
+            let in_path: Vec<&Value> = params.iter().filter(|p| p["in"] == "path").collect();
+            let in_query: Vec<&Value> = params.iter().filter(|p| p["in"] == "query").collect();
+            let response = response(op);
+
+            let mut args = String::new();
+            for param in &in_path {
+                let name = param["name"].as_str().unwrap();
+                let ty = match rust_type(&param["schema"]).as_str() {
+                    "String" => "&str".to_string(),
+                    other => other.to_string(),
+                };
+                write!(args, ", {}: {}", ident(name), ty).unwrap();
+            }
+
+            let query = if in_query.is_empty() {
+                None
+            } else {
+                let name = format!("{}Query", pascal(id));
+                query_struct(&mut queries, &name, &in_query, matches!(response, Response::Page(_)));
+                write!(args, ", query: &{}", name).unwrap();
+                Some(name)
+            };
+
+            let body = op["requestBody"]["content"].as_object();
+            let send_body = match body.and_then(|content| content.iter().next()) {
+                Some((content_type, media)) if content_type == "application/json" => {
+                    let ty = rust_type(&media["schema"]);
+                    write!(args, ", body: &{}", ty).unwrap();
+                    ".json(body)"
+                }
+                Some(_) => {
+                    args.push_str(", form: reqwest::multipart::Form");
+                    ".multipart(form)"
+                }
+                None => "",
+            };
+
+            let (output, send) = match &response {
+                Response::Data(ty) => (ty.clone(), "self.data(request).await"),
+                Response::Page(ty) => (format!("crate::Page<{}>", ty), "self.data(request).await"),
+                Response::Empty => ("()".to_string(), "self.empty(request).await"),
+                Response::Raw => ("reqwest::Response".to_string(), "self.raw(request).await"),
+            };
+
+            docs(&mut methods, "    ", op["summary"].as_str());
+            writeln!(methods, "    #[doc = \"`{} {}`\"]", method.to_uppercase(), path).unwrap();
+            writeln!(methods, "    pub async fn {}(&self{}) -> crate::Result<{}> {{", id, args, output).unwrap();
+            writeln!(
+                methods,
+                "        let request = self.request(reqwest::Method::{}, {}){}{};",
+                method.to_uppercase(),
+                path_expr(path),
+                if query.is_some() { ".query(query)" } else { "" },
+                send_body,
+            )
+            .unwrap();
+            writeln!(methods, "        {}\n    }}\n", send).unwrap();
+
+            if let (Response::Page(ty), Some(query), true) = (&response, &query, in_path.is_empty()) {
+                writeln!(methods, "    /// Every page of [`Self::{}`], following `pagination` from `query` on.", id).unwrap();
+                writeln!(
+                    methods,
+                    "    pub fn {id}_pages(&self, query: {query}) -> impl futures::Stream<Item = crate::Result<crate::Page<{ty}>>> + '_ {{\n        \
+                     crate::pages(query, move |query| async move {{ self.{id}(&query).await }})\n    }}\n",
+                )
+                .unwrap();
+                writeln!(methods, "    /// Every item of [`Self::{}`], fetching pages as the stream is polled.", id).unwrap();
+                writeln!(
+                    methods,
+                    "    pub fn {id}_items(&self, query: {query}) -> impl futures::Stream<Item = crate::Result<{ty}>> + '_ {{\n        \
+                     crate::items(self.{id}_pages(query))\n    }}\n",
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    format!("{}impl crate::Client {{\n{}}}\n", queries, methods)
+}
+
+fn query_struct(out: &mut String, name: &str, params: &[&Value], paginated: bool) {
+    let mut fields = String::new();
+    let mut all_optional = true;
+    let mut names = BTreeSet::new();
+    for param in params {
+        let property = param["name"].as_str().unwrap();
+        let optional = param["required"] != true || nullable(&param["schema"]);
+        all_optional &= optional;
+        let mut schema = param["schema"].clone();
+        if let Some(description) = param["description"].as_str() {
+            schema["description"] = Value::String(description.to_string());
+        }
+        field(&mut fields, property, &schema, optional);
+        names.insert(property);
+    }
+    writeln!(out, "/// Query string of [`Client::{}`](crate::Client).", snake(name.trim_end_matches("Query"))).unwrap();
+    structure(out, name, &fields, all_optional);
+
+    if paginated {
+        writeln!(out, "impl crate::PageQuery for {} {{", name).unwrap();
+        if names.contains("page") {
+            writeln!(out, "    fn set_page(&mut self, page: u32) {{\n        self.page = Some(page);\n    }}").unwrap();
+        }
+        if names.contains("cursor") {
+            writeln!(out, "    fn set_cursor(&mut self, cursor: String) {{\n        self.cursor = Some(cursor);\n    }}").unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+}
+
+fn response(op: &Value) -> Response {
+    let success = op["responses"]
+        .as_object()
+        .unwrap()
+        .iter()
+        .find(|(code, _)| code.starts_with('2'))
+        .map(|(_, response)| response);
+    let Some((content_type, media)) = success.and_then(|r| r["content"].as_object()).and_then(|c| c.iter().next()) else {
+        return Response::Empty;
+    };
+    if content_type != "application/json" {
+        return Response::Raw;
+    }
+
+    let schema = &media["schema"];
+    if schema["$ref"].as_str().map(ref_name) == Some(ENVELOPE) {
+        return Response::Empty;
+    }
+    let data = match schema["properties"]["data"]["allOf"].get(0) {
+        Some(data) => data,
+        None => return Response::Empty,
+    };
+    if data["properties"]["pagination"].is_object() {
+        return Response::Page(rust_type(&data["properties"]["data"]["items"]));
+    }
+    Response::Data(rust_type(data))
+}
+
+fn rust_type(schema: &Value) -> String {
+    if let Some(target) = schema["$ref"].as_str() {
+        return model(ref_name(target));
+    }
+    if let Some([single]) = schema["allOf"].as_array().map(Vec::as_slice) {
+        return rust_type(single);
+    }
+    let unsigned = schema["minimum"].as_f64().is_some_and(|min| min >= 0.0);
+    match (schema["type"].as_str(), schema["format"].as_str()) {
+        (Some("string"), Some("uuid")) => "uuid::Uuid".into(),
+        (Some("string"), Some("date-time")) => "chrono::DateTime<chrono::Utc>".into(),
+        (Some("string"), Some("date")) => "chrono::NaiveDate".into(),
+        (Some("string"), _) => "String".into(),
+        (Some("integer"), Some("int32")) if unsigned => "u32".into(),
+        (Some("integer"), Some("int32")) => "i32".into(),
+        (Some("integer"), _) if unsigned => "u64".into(),
+        (Some("integer"), _) => "i64".into(),
+        (Some("number"), _) => "f64".into(),
+        (Some("boolean"), _) => "bool".into(),
+        (Some("array"), _) => format!("Vec<{}>", rust_type(&schema["items"])),
+        (Some("object"), _) if schema["additionalProperties"].is_object() => {
+            format!("std::collections::HashMap<String, {}>", rust_type(&schema["additionalProperties"]))
+        }
+        _ => "serde_json::Value".into(),
+    }
+}
+
+fn model(name: &str) -> String {
+    format!("crate::models::{}", name)
+}
+
+fn ref_name(target: &str) -> &str {
+    target.rsplit('/').next().unwrap()
+}
+
+fn nullable(schema: &Value) -> bool {
+    schema["nullable"] == true
+}
+
+fn names(list: &Value) -> BTreeSet<&str> {
+    list.as_array().into_iter().flatten().filter_map(Value::as_str).collect()
+}
+
+/// The request path as a `&str` expression, with path parameters percent-encoded.
+fn path_expr(path: &str) -> String {
+    if !path.contains('{') {
+        return format!("\"{}\"", path);
+    }
+    let mut template = String::new();
+    let mut args = String::new();
+    for (i, part) in path.split(['{', '}']).enumerate() {
+        if i % 2 == 0 {
+            template.push_str(part);
+        } else {
+            template.push_str("{}");
+            write!(args, ", crate::encode_segment({})", ident(part)).unwrap();
+        }
+    }
+    format!("&format!(\"{}\"{})", template, args)
+}
+
+fn docs(out: &mut String, indent: &str, text: Option<&str>) {
+    for line in text.into_iter().flat_map(str::lines) {
+        writeln!(out, "{}/// {}", indent, line).unwrap();
+    }
+}
+
+fn ident(name: &str) -> String {
+    let name: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    let name = name.trim_end_matches('_').to_string();
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("r#{}", name)
+    } else {
+        name
+    }
+}
+
+fn pascal(value: &str) -> String {
+    value
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            first.to_string() + chars.as_str()
+        })
+        .collect()
+}
+
+fn snake(value: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in value.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
@@ -0,0 +1,385 @@
+//! Typed client for the ERP platform API.
+//!
+//! Models and one method per operation are generated at build time from
+//! `openapi.json`, the document `erpctl openapi` prints. Regenerate it with
+//! `erpctl --pretty openapi > crates/erp-client/openapi.json` after changing a
+//! route; the API's tests fail while it is stale.
+//!
+//! ```no_run
+//! # async fn run() -> erp_client::Result<()> {
+//! use erp_client::{models::LoginRequest, Client, ListCompaniesQuery};
+//! use futures::TryStreamExt;
+//!
+//! let client = Client::new("http://localhost:8080");
+//! let login = client
+//!     .login(&LoginRequest { email: "admin@northwind.example".into(), password: "secret".into(), remember_me: None })
+//!     .await?;
+//! let client = client.with_token(login.access_token);
+//!
+//! let companies: Vec<_> = client.list_companies_items(ListCompaniesQuery::default()).try_collect().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+
+use futures::{stream, Stream, TryStreamExt};
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+
+pub mod models {
+    //! Schemas from the OpenAPI document's components.
+    include!(concat!(env!("OUT_DIR"), "/models.rs"));
+}
+
+mod operations {
+    include!(concat!(env!("OUT_DIR"), "/operations.rs"));
+}
+
+pub use operations::*;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The request never got a response, or its body couldn't be read.
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    /// The API answered with an error status or `success: false`.
+    #[error("{status}: {}", message.as_deref().unwrap_or("request failed"))]
+    Api {
+        status: StatusCode,
+        /// Business error code such as `INVALID_CREDENTIALS`.
+        code: Option<String>,
+        message: Option<String>,
+        errors: Vec<models::ValidationError>,
+    },
+    /// The body didn't match the documented schema.
+    #[error("unexpected response body: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error("response has no data")]
+    MissingData,
+}
+
+impl Error {
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Api { status, .. } => Some(*status),
+            Self::Http(error) => error.status(),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Self::Api { code, .. } => code.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// The `ApiResponse` wrapper every JSON body comes in.
+#[derive(Debug, Deserialize)]
+pub struct Envelope<T> {
+    pub success: bool,
+    pub data: Option<T>,
+    pub message: Option<String>,
+    #[serde(default)]
+    pub errors: Option<Vec<models::ValidationError>>,
+    pub code: Option<String>,
+}
+
+/// One page of a list endpoint.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub pagination: Pagination,
+}
+
+/// Offset pages answer `page`/`per_page`; keyset pages answer `cursor`/`limit`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Pagination {
+    Offset(models::PaginationMeta),
+    Cursor(models::CursorMeta),
+}
+
+/// Query strings of list operations, moved on to the next page by
+/// [`pages`]. A query without `page` or `cursor` stops after one page.
+pub trait PageQuery: Clone {
+    fn set_page(&mut self, _page: u32) {}
+    fn set_cursor(&mut self, _cursor: String) {}
+
+    /// The query for the page after `pagination`, if there is one.
+    fn next(&self, pagination: &Pagination) -> Option<Self> {
+        let mut next = self.clone();
+        match pagination {
+            Pagination::Offset(meta) if meta.has_next => next.set_page(meta.current_page + 1),
+            Pagination::Cursor(meta) if meta.has_next => next.set_cursor(meta.next_cursor.clone()?),
+            _ => return None,
+        }
+        Some(next)
+    }
+}
+
+/// Fetches pages one at a time, starting from `query`, until the last.
+pub fn pages<'a, Q, T, F, Fut>(query: Q, fetch: F) -> impl Stream<Item = Result<Page<T>>> + 'a
+where
+    Q: PageQuery + 'a,
+    T: 'a,
+    F: FnMut(Q) -> Fut + 'a,
+    Fut: Future<Output = Result<Page<T>>> + 'a,
+{
+    stream::try_unfold((fetch, Some(query)), |(mut fetch, query)| async move {
+        let Some(query) = query else {
+            return Ok(None);
+        };
+        let page = fetch(query.clone()).await?;
+        let next = query.next(&page.pagination);
+        Ok(Some((page, (fetch, next))))
+    })
+}
+
+/// Flattens a stream of pages into their items.
+pub fn items<'a, T: 'a>(pages: impl Stream<Item = Result<Page<T>>> + 'a) -> impl Stream<Item = Result<T>> + 'a {
+    pages.map_ok(|page| stream::iter(page.data.into_iter().map(Ok))).try_flatten()
+}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl Client {
+    /// A client for the API at `base_url`, such as `http://api:8080`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    /// Like [`Client::new`], sending through a configured `reqwest::Client`.
+    pub fn with_http_client(http: reqwest::Client, base_url: impl Into<String>) -> Self {
+        let base_url = base_url.into().trim_end_matches('/').to_string();
+        Self { http, base_url, token: None }
+    }
+
+    /// Sends `token` as the bearer token on every request.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn data<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let (status, body) = self.send(request).await?;
+        let envelope: Envelope<T> = serde_json::from_slice(&body)?;
+        if !envelope.success {
+            return Err(api_error(status, envelope));
+        }
+        envelope.data.ok_or(Error::MissingData)
+    }
+
+    async fn empty(&self, request: RequestBuilder) -> Result<()> {
+        let (status, body) = self.send(request).await?;
+        let envelope: Envelope<serde_json::Value> = serde_json::from_slice(&body)?;
+        if !envelope.success {
+            return Err(api_error(status, envelope));
+        }
+        Ok(())
+    }
+
+    async fn raw(&self, request: RequestBuilder) -> Result<reqwest::Response> {
+        let response = request.send().await?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        Err(error_body(status, &response.bytes().await?))
+    }
+
+    /// The body of a successful response; errors are turned into [`Error::Api`].
+    async fn send(&self, request: RequestBuilder) -> Result<(StatusCode, Vec<u8>)> {
+        let response = request.send().await?;
+        let status = response.status();
+        let body = response.bytes().await?;
+        if !status.is_success() {
+            return Err(error_body(status, &body));
+        }
+        Ok((status, body.to_vec()))
+    }
+}
+
+fn error_body(status: StatusCode, body: &[u8]) -> Error {
+    match serde_json::from_slice::<Envelope<serde_json::Value>>(body) {
+        Ok(envelope) => api_error(status, envelope),
+        Err(_) => Error::Api {
+            status,
+            code: None,
+            message: Some(String::from_utf8_lossy(body).into_owned()).filter(|m| !m.is_empty()),
+            errors: Vec::new(),
+        },
+    }
+}
+
+fn api_error<T>(status: StatusCode, envelope: Envelope<T>) -> Error {
+    Error::Api { status, code: envelope.code, message: envelope.message, errors: envelope.errors.unwrap_or_default() }
+}
+
+/// Percent-encodes a path parameter.
+fn encode_segment(value: impl std::fmt::Display) -> String {
+    let value = value.to_string();
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+    use serde_json::{json, Value};
+    use wiremock::{
+        matchers::{header, method, path, query_param, query_param_is_missing},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::*;
+
+    fn warehouse(code: &str) -> Value {
+        json!({
+            "id": uuid::Uuid::new_v4(),
+            "tenant_id": uuid::Uuid::new_v4(),
+            "code": code,
+            "name": code,
+            "is_active": true,
+            "created_at": "2026-01-15T00:00:00Z",
+            "updated_at": "2026-01-15T00:00:00Z",
+        })
+    }
+
+    fn offset_page(codes: &[&str], page: u32, has_next: bool) -> Value {
+        json!({
+            "success": true,
+            "data": {
+                "data": codes.iter().map(|code| warehouse(code)).collect::<Vec<_>>(),
+                "pagination": {
+                    "current_page": page, "per_page": 2, "total_pages": 2, "total_count": 3,
+                    "has_next": has_next, "has_prev": page > 1,
+                },
+            },
+        })
+    }
+
+    fn cursor_page(codes: &[&str], next_cursor: Option<&str>) -> Value {
+        json!({
+            "success": true,
+            "data": {
+                "data": codes.iter().map(|code| warehouse(code)).collect::<Vec<_>>(),
+                "pagination": { "limit": 2, "has_next": next_cursor.is_some(), "next_cursor": next_cursor, "total_count_estimated": false },
+            },
+        })
+    }
+
+    fn codes(warehouses: &[models::Warehouse]) -> Vec<&str> {
+        warehouses.iter().map(|w| w.code.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn data_is_unwrapped_and_the_token_sent() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v1/notifications/unread-count"))
+            .and(header("authorization", "Bearer t0ken"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "success": true, "data": { "unread": 3 } })))
+            .mount(&server)
+            .await;
+
+        let client = Client::new(format!("{}/", server.uri())).with_token("t0ken");
+        assert_eq!(client.unread_count().await.unwrap().unread, 3);
+    }
+
+    #[tokio::test]
+    async fn error_envelopes_become_api_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v1/auth/login"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+                "success": false,
+                "message": "Invalid email or password",
+                "code": "INVALID_CREDENTIALS",
+            })))
+            .mount(&server)
+            .await;
+
+        let request = models::LoginRequest { email: "a@b.example".into(), password: "wrong".into(), remember_me: None };
+        let error = Client::new(server.uri()).login(&request).await.unwrap_err();
+        assert_eq!(error.status(), Some(StatusCode::UNAUTHORIZED));
+        assert_eq!(error.code(), Some("INVALID_CREDENTIALS"));
+        assert_eq!(error.to_string(), "401 Unauthorized: Invalid email or password");
+    }
+
+    #[tokio::test]
+    async fn offset_pages_are_followed_to_the_last() {
+        let server = MockServer::start().await;
+        Mock::given(path("/api/v1/inventory/warehouses"))
+            .and(query_param_is_missing("page"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(offset_page(&["WH-1", "WH-2"], 1, true)))
+            .mount(&server)
+            .await;
+        Mock::given(path("/api/v1/inventory/warehouses"))
+            .and(query_param("page", "2"))
+            .and(query_param("per_page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(offset_page(&["WH-3"], 2, false)))
+            .mount(&server)
+            .await;
+
+        let client = Client::new(server.uri());
+        let query = ListWarehousesQuery { per_page: Some(2), ..Default::default() };
+        let warehouses: Vec<_> = client.list_warehouses_items(query).try_collect().await.unwrap();
+        assert_eq!(codes(&warehouses), ["WH-1", "WH-2", "WH-3"]);
+    }
+
+    #[tokio::test]
+    async fn cursor_pages_are_followed_to_the_last() {
+        let server = MockServer::start().await;
+        Mock::given(path("/api/v1/inventory/warehouses"))
+            .and(query_param_is_missing("cursor"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(cursor_page(&["WH-1", "WH-2"], Some("abc"))))
+            .mount(&server)
+            .await;
+        Mock::given(path("/api/v1/inventory/warehouses"))
+            .and(query_param("cursor", "abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(cursor_page(&["WH-3"], None)))
+            .mount(&server)
+            .await;
+
+        let client = Client::new(server.uri());
+        let query = ListWarehousesQuery { limit: Some(2), ..Default::default() };
+        let pages: Vec<_> = client.list_warehouses_pages(query).try_collect().await.unwrap();
+        assert_eq!(pages.len(), 2);
+        assert!(matches!(pages[0].pagination, Pagination::Cursor(_)));
+        assert_eq!(codes(&pages[1].data), ["WH-3"]);
+    }
+
+    #[test]
+    fn path_parameters_are_percent_encoded() {
+        assert_eq!(encode_segment("welcome"), "welcome");
+        assert_eq!(encode_segment("a/b c"), "a%2Fb%20c");
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use shared_types::{
+    Account, AccountType, BalanceType, CreateAccountRequest, Currency, JournalEntry, JournalEntryLine,
+    JournalEntryStatus, Money,
+};
+use sqlx::{postgres::PgRow, FromRow};
+use telemetry::db_span;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::Tx;
+
+#[derive(FromRow)]
+struct AccountRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    code: String,
+    name: String,
+    account_type: AccountType,
+    account_subtype: Option<String>,
+    parent_id: Option<Uuid>,
+    is_active: bool,
+    description: Option<String>,
+    balance_type: BalanceType,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<AccountRow> for Account {
+    fn from(row: AccountRow) -> Self {
+        Account {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            code: row.code,
+            name: row.name,
+            account_type: row.account_type,
+            account_subtype: row.account_subtype,
+            parent_id: row.parent_id,
+            is_active: row.is_active,
+            description: row.description,
+            balance_type: row.balance_type,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+/// The chart of accounts.
+pub struct AccountRepo;
+
+impl AccountRepo {
+    /// `SELECT` list [`AccountRepo::from_row`] reads
+    pub const COLUMNS: &'static str = r#"id, tenant_id, code, name, account_type, account_subtype, parent_id,
+       is_active, description, balance_type, created_at, updated_at"#;
+
+    pub fn from_row(row: &PgRow) -> Result<Account, sqlx::Error> {
+        AccountRow::from_row(row).map(Account::from)
+    }
+
+    pub async fn find(tx: &mut Tx<'_>, tenant_id: Uuid, id: Uuid) -> Result<Option<Account>, sqlx::Error> {
+        let row = sqlx::query_as::<_, AccountRow>(&format!(
+            "SELECT {} FROM accounts WHERE tenant_id = $1 AND id = $2",
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .instrument(db_span("SELECT", "accounts"))
+        .await?;
+        Ok(row.map(Account::from))
+    }
+
+    /// Accounts by id; ids of other tenants or unknown ones are left out.
+    pub async fn find_many(tx: &mut Tx<'_>, tenant_id: Uuid, ids: &[Uuid]) -> Result<HashMap<Uuid, Account>, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let rows = sqlx::query_as::<_, AccountRow>(&format!(
+            "SELECT {} FROM accounts WHERE tenant_id = $1 AND id = ANY($2)",
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(ids)
+        .fetch_all(&mut **tx)
+        .instrument(db_span("SELECT", "accounts"))
+        .await?;
+        Ok(rows.into_iter().map(|row| (row.id, Account::from(row))).collect())
+    }
+
+    pub async fn code_exists(tx: &mut Tx<'_>, tenant_id: Uuid, code: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM accounts WHERE tenant_id = $1 AND code = $2)")
+            .bind(tenant_id)
+            .bind(code)
+            .fetch_one(&mut **tx)
+            .instrument(db_span("SELECT", "accounts"))
+            .await
+    }
+
+    pub async fn insert(tx: &mut Tx<'_>, tenant_id: Uuid, req: &CreateAccountRequest) -> Result<Account, sqlx::Error> {
+        let row = sqlx::query_as::<_, AccountRow>(&format!(
+            r#"INSERT INTO accounts (tenant_id, code, name, account_type, account_subtype, parent_id, description, balance_type)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+               RETURNING {}"#,
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(&req.code)
+        .bind(&req.name)
+        .bind(&req.account_type)
+        .bind(&req.account_subtype)
+        .bind(req.parent_id)
+        .bind(&req.description)
+        .bind(&req.balance_type)
+        .fetch_one(&mut **tx)
+        .instrument(db_span("INSERT", "accounts"))
+        .await?;
+        Ok(row.into())
+    }
+}
+
+#[derive(FromRow)]
+struct JournalEntryRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    entry_number: String,
+    entry_date: NaiveDate,
+    reference: Option<String>,
+    description: String,
+    currency: Currency,
+    total_debit: Decimal,
+    total_credit: Decimal,
+    status: JournalEntryStatus,
+    created_by: Uuid,
+    posted_by: Option<Uuid>,
+    posted_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<JournalEntryRow> for JournalEntry {
+    fn from(row: JournalEntryRow) -> Self {
+        JournalEntry {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            entry_number: row.entry_number,
+            entry_date: row.entry_date,
+            reference: row.reference,
+            description: row.description,
+            currency: row.currency,
+            total_debit: Money::new(row.total_debit, row.currency),
+            total_credit: Money::new(row.total_credit, row.currency),
+            status: row.status,
+            created_by: row.created_by,
+            posted_by: row.posted_by,
+            posted_at: row.posted_at,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            lines: None,
+        }
+    }
+}
+
+/// A line with its entry's currency, which the amounts are in
+#[derive(FromRow)]
+struct JournalLineRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    journal_entry_id: Uuid,
+    account_id: Uuid,
+    description: Option<String>,
+    debit_amount: Decimal,
+    credit_amount: Decimal,
+    line_number: i32,
+    created_at: DateTime<Utc>,
+    currency: Currency,
+}
+
+impl From<JournalLineRow> for JournalEntryLine {
+    fn from(row: JournalLineRow) -> Self {
+        JournalEntryLine {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            journal_entry_id: row.journal_entry_id,
+            account_id: row.account_id,
+            account: None,
+            description: row.description,
+            debit_amount: Money::new(row.debit_amount, row.currency),
+            credit_amount: Money::new(row.credit_amount, row.currency),
+            line_number: row.line_number,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// Header of an entry to book; the currency is that of the totals.
+pub struct NewJournalEntry<'a> {
+    pub entry_number: &'a str,
+    pub entry_date: NaiveDate,
+    pub reference: Option<&'a str>,
+    pub description: &'a str,
+    pub total_debit: Money,
+    pub total_credit: Money,
+    pub created_by: Uuid,
+}
+
+pub struct NewJournalLine<'a> {
+    pub account_id: Uuid,
+    pub description: Option<&'a str>,
+    pub debit: Money,
+    pub credit: Money,
+}
+
+/// Journal entries and their lines.
+pub struct JournalRepo;
+
+impl JournalRepo {
+    /// `SELECT` list [`JournalRepo::from_row`] reads
+    pub const COLUMNS: &'static str = r#"id, tenant_id, entry_number, entry_date, reference, description,
+       currency, total_debit, total_credit, status, created_by, posted_by, posted_at,
+       created_at, updated_at"#;
+
+    pub fn from_row(row: &PgRow) -> Result<JournalEntry, sqlx::Error> {
+        JournalEntryRow::from_row(row).map(JournalEntry::from)
+    }
+
+    /// Book an entry with its lines, numbered from 1 in the order given.
+    /// The entry comes back without its lines.
+    pub async fn insert(
+        tx: &mut Tx<'_>,
+        tenant_id: Uuid,
+        entry: &NewJournalEntry<'_>,
+        lines: &[NewJournalLine<'_>],
+    ) -> Result<JournalEntry, sqlx::Error> {
+        let row = sqlx::query_as::<_, JournalEntryRow>(&format!(
+            r#"INSERT INTO journal_entries (tenant_id, entry_number, entry_date, reference, description,
+                                           currency, total_debit, total_credit, created_by)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+               RETURNING {}"#,
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(entry.entry_number)
+        .bind(entry.entry_date)
+        .bind(entry.reference)
+        .bind(entry.description)
+        .bind(entry.total_debit.currency())
+        .bind(entry.total_debit.amount())
+        .bind(entry.total_credit.amount())
+        .bind(entry.created_by)
+        .fetch_one(&mut **tx)
+        .instrument(db_span("INSERT", "journal_entries"))
+        .await?;
+
+        for (index, line) in lines.iter().enumerate() {
+            sqlx::query(
+                r#"INSERT INTO journal_entry_lines (tenant_id, journal_entry_id, account_id,
+                                                   description, debit_amount, credit_amount, line_number)
+                   VALUES ($1, $2, $3, $4, $5, $6, $7)"#,
+            )
+            .bind(tenant_id)
+            .bind(row.id)
+            .bind(line.account_id)
+            .bind(line.description)
+            .bind(line.debit.amount())
+            .bind(line.credit.amount())
+            .bind((index + 1) as i32)
+            .execute(&mut **tx)
+            .instrument(db_span("INSERT", "journal_entry_lines"))
+            .await?;
+        }
+        Ok(row.into())
+    }
+
+    /// Lines of the given entries, grouped by entry in line order.
+    pub async fn lines(tx: &mut Tx<'_>, tenant_id: Uuid, entry_ids: &[Uuid]) -> Result<Vec<JournalEntryLine>, sqlx::Error> {
+        if entry_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let rows = sqlx::query_as::<_, JournalLineRow>(
+            r#"SELECT l.id, l.tenant_id, l.journal_entry_id, l.account_id, l.description, l.debit_amount,
+                      l.credit_amount, l.line_number, l.created_at, e.currency
+               FROM journal_entry_lines l
+               JOIN journal_entries e ON e.id = l.journal_entry_id
+               WHERE l.tenant_id = $1 AND l.journal_entry_id = ANY($2)
+               ORDER BY l.journal_entry_id, l.line_number"#,
+        )
+        .bind(tenant_id)
+        .bind(entry_ids)
+        .fetch_all(&mut **tx)
+        .instrument(db_span("SELECT", "journal_entry_lines"))
+        .await?;
+        Ok(rows.into_iter().map(JournalEntryLine::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{begin, testing};
+
+    fn account(code: &str, account_type: AccountType, balance_type: BalanceType) -> CreateAccountRequest {
+        CreateAccountRequest {
+            code: code.to_string(),
+            name: format!("Account {}", code),
+            account_type,
+            account_subtype: None,
+            parent_id: None,
+            description: None,
+            balance_type,
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn accounts_round_trip_their_types(pool: PgPool) -> sqlx::Result<()> {
+        testing::migrate(&pool).await?;
+        let mut conn = pool.acquire().await?;
+        let tenant_id = testing::tenant(&mut conn).await?;
+        let mut tx = begin(&mut conn, tenant_id).await?;
+
+        let created = AccountRepo::insert(&mut tx, tenant_id, &account("2100", AccountType::Liability, BalanceType::Credit)).await?;
+        assert!(AccountRepo::code_exists(&mut tx, tenant_id, "2100").await?);
+        assert!(!AccountRepo::code_exists(&mut tx, testing::OTHER_TENANT, "2100").await?);
+
+        let found = AccountRepo::find(&mut tx, tenant_id, created.id).await?.expect("account");
+        assert!(matches!(found.account_type, AccountType::Liability));
+        assert!(matches!(found.balance_type, BalanceType::Credit));
+        assert!(AccountRepo::find(&mut tx, testing::OTHER_TENANT, created.id).await?.is_none());
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn unknown_account_type_is_a_decode_error(pool: PgPool) -> sqlx::Result<()> {
+        testing::migrate(&pool).await?;
+        let mut conn = pool.acquire().await?;
+        let tenant_id = testing::tenant(&mut conn).await?;
+        let mut tx = begin(&mut conn, tenant_id).await?;
+
+        let id: Uuid = sqlx::query_scalar(
+            "INSERT INTO accounts (tenant_id, code, name, account_type) VALUES ($1, '9999', 'Odd', 'contra') RETURNING id",
+        )
+        .bind(tenant_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        let err = AccountRepo::find(&mut tx, tenant_id, id).await.expect_err("contra is not an account type");
+        assert!(matches!(err, sqlx::Error::ColumnDecode { .. }), "{:?}", err);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn entries_are_booked_with_numbered_lines(pool: PgPool) -> sqlx::Result<()> {
+        testing::migrate(&pool).await?;
+        let mut conn = pool.acquire().await?;
+        let tenant_id = testing::tenant(&mut conn).await?;
+        let user_id = testing::user(&mut conn, tenant_id).await?;
+        let mut tx = begin(&mut conn, tenant_id).await?;
+
+        let cash = AccountRepo::insert(&mut tx, tenant_id, &account("1100", AccountType::Asset, BalanceType::Debit)).await?;
+        let sales = AccountRepo::insert(&mut tx, tenant_id, &account("4100", AccountType::Revenue, BalanceType::Credit)).await?;
+        let total = Money::new(Decimal::new(2550, 2), Currency::USD);
+        let zero = Money::zero(Currency::USD);
+        let entry = NewJournalEntry {
+            entry_number: "JE-1",
+            entry_date: NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            reference: None,
+            description: "Cash sale",
+            total_debit: total,
+            total_credit: total,
+            created_by: user_id,
+        };
+        let lines = [
+            NewJournalLine { account_id: cash.id, description: None, debit: total, credit: zero },
+            NewJournalLine { account_id: sales.id, description: Some("Sale"), debit: zero, credit: total },
+        ];
+        let booked = JournalRepo::insert(&mut tx, tenant_id, &entry, &lines).await?;
+        assert_eq!(booked.currency, Currency::USD);
+        assert_eq!(booked.total_debit, total);
+        assert!(matches!(booked.status, JournalEntryStatus::Draft));
+
+        let lines = JournalRepo::lines(&mut tx, tenant_id, &[booked.id]).await?;
+        let numbers: Vec<_> = lines.iter().map(|l| (l.line_number, l.account_id)).collect();
+        assert_eq!(numbers, vec![(1, cash.id), (2, sales.id)]);
+        assert_eq!(lines[1].credit_amount, total);
+        Ok(())
+    }
+}
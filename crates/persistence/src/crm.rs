@@ -0,0 +1,376 @@
+use chrono::{DateTime, Utc};
+use shared_types::{Company, Contact};
+use sqlx::{postgres::PgRow, FromRow};
+use telemetry::db_span;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::Tx;
+
+#[derive(FromRow)]
+struct CompanyRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    name: String,
+    website: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+    address: serde_json::Value,
+    tags: Option<Vec<String>>,
+    is_active: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<CompanyRow> for Company {
+    fn from(row: CompanyRow) -> Self {
+        Company {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            name: row.name,
+            website: row.website,
+            email: row.email,
+            phone: row.phone,
+            address: row.address,
+            tags: row.tags.unwrap_or_default(),
+            is_active: row.is_active,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+pub struct NewCompany {
+    pub name: String,
+    pub website: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: serde_json::Value,
+    pub tags: Vec<String>,
+}
+
+/// Fields to change; `None` keeps the current value.
+#[derive(Default)]
+pub struct CompanyChanges {
+    pub name: Option<String>,
+    pub website: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<serde_json::Value>,
+    pub tags: Option<Vec<String>>,
+    pub is_active: Option<bool>,
+}
+
+/// CRM companies.
+pub struct CompanyRepo;
+
+impl CompanyRepo {
+    /// `SELECT` list [`CompanyRepo::from_row`] reads
+    pub const COLUMNS: &'static str = "id, tenant_id, name, website, email, phone, address, tags, is_active, created_at, updated_at";
+
+    pub fn from_row(row: &PgRow) -> Result<Company, sqlx::Error> {
+        CompanyRow::from_row(row).map(Company::from)
+    }
+
+    pub async fn find(tx: &mut Tx<'_>, tenant_id: Uuid, id: Uuid) -> Result<Option<Company>, sqlx::Error> {
+        let row = sqlx::query_as::<_, CompanyRow>(&format!(
+            "SELECT {} FROM companies WHERE tenant_id = $1 AND id = $2",
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .instrument(db_span("SELECT", "companies"))
+        .await?;
+        Ok(row.map(Company::from))
+    }
+
+    pub async fn insert(tx: &mut Tx<'_>, tenant_id: Uuid, company: &NewCompany) -> Result<Company, sqlx::Error> {
+        let row = sqlx::query_as::<_, CompanyRow>(&format!(
+            r#"INSERT INTO companies (tenant_id, name, website, email, phone, address, tags)
+               VALUES ($1, $2, $3, $4, $5, $6, $7)
+               RETURNING {}"#,
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(&company.name)
+        .bind(&company.website)
+        .bind(&company.email)
+        .bind(&company.phone)
+        .bind(&company.address)
+        .bind(&company.tags)
+        .fetch_one(&mut **tx)
+        .instrument(db_span("INSERT", "companies"))
+        .await?;
+        Ok(row.into())
+    }
+
+    /// Apply `changes`. With `expected`, only a company last updated at one of
+    /// those times is changed; `None` means the company is missing or was
+    /// updated since.
+    pub async fn update(
+        tx: &mut Tx<'_>,
+        tenant_id: Uuid,
+        id: Uuid,
+        changes: &CompanyChanges,
+        expected: Option<&[DateTime<Utc>]>,
+    ) -> Result<Option<Company>, sqlx::Error> {
+        let row = sqlx::query_as::<_, CompanyRow>(&format!(
+            r#"UPDATE companies SET
+                   name = COALESCE($3, name),
+                   website = COALESCE($4, website),
+                   email = COALESCE($5, email),
+                   phone = COALESCE($6, phone),
+                   address = COALESCE($7, address),
+                   tags = COALESCE($8, tags),
+                   is_active = COALESCE($9, is_active),
+                   updated_at = NOW()
+               WHERE tenant_id = $1 AND id = $2 AND ($10::timestamptz[] IS NULL OR updated_at = ANY($10))
+               RETURNING {}"#,
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(id)
+        .bind(&changes.name)
+        .bind(&changes.website)
+        .bind(&changes.email)
+        .bind(&changes.phone)
+        .bind(&changes.address)
+        .bind(&changes.tags)
+        .bind(changes.is_active)
+        .bind(expected)
+        .fetch_optional(&mut **tx)
+        .instrument(db_span("UPDATE", "companies"))
+        .await?;
+        Ok(row.map(Company::from))
+    }
+
+    /// Soft-delete; `false` if there was no such company.
+    pub async fn deactivate(tx: &mut Tx<'_>, tenant_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE companies SET is_active = false, updated_at = NOW() WHERE tenant_id = $1 AND id = $2")
+            .bind(tenant_id)
+            .bind(id)
+            .execute(&mut **tx)
+            .instrument(db_span("UPDATE", "companies"))
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[derive(FromRow)]
+struct ContactRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    company_id: Option<Uuid>,
+    first_name: String,
+    last_name: String,
+    email: Option<String>,
+    phone: Option<String>,
+    position: Option<String>,
+    notes: Option<String>,
+    is_active: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<ContactRow> for Contact {
+    fn from(row: ContactRow) -> Self {
+        Contact {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            company_id: row.company_id,
+            first_name: row.first_name,
+            last_name: row.last_name,
+            email: row.email,
+            phone: row.phone,
+            position: row.position,
+            notes: row.notes,
+            is_active: row.is_active,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+pub struct NewContact {
+    pub company_id: Option<Uuid>,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub position: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// Fields to change; `None` keeps the current value.
+#[derive(Default)]
+pub struct ContactChanges {
+    pub company_id: Option<Uuid>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub position: Option<String>,
+    pub notes: Option<String>,
+    pub is_active: Option<bool>,
+}
+
+/// CRM contacts.
+pub struct ContactRepo;
+
+impl ContactRepo {
+    /// `SELECT` list [`ContactRepo::from_row`] reads
+    pub const COLUMNS: &'static str =
+        "id, tenant_id, company_id, first_name, last_name, email, phone, position, notes, is_active, created_at, updated_at";
+
+    pub fn from_row(row: &PgRow) -> Result<Contact, sqlx::Error> {
+        ContactRow::from_row(row).map(Contact::from)
+    }
+
+    pub async fn find(tx: &mut Tx<'_>, tenant_id: Uuid, id: Uuid) -> Result<Option<Contact>, sqlx::Error> {
+        let row = sqlx::query_as::<_, ContactRow>(&format!(
+            "SELECT {} FROM contacts WHERE tenant_id = $1 AND id = $2",
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .instrument(db_span("SELECT", "contacts"))
+        .await?;
+        Ok(row.map(Contact::from))
+    }
+
+    pub async fn insert(tx: &mut Tx<'_>, tenant_id: Uuid, contact: &NewContact) -> Result<Contact, sqlx::Error> {
+        let row = sqlx::query_as::<_, ContactRow>(&format!(
+            r#"INSERT INTO contacts (tenant_id, company_id, first_name, last_name, email, phone, position, notes)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+               RETURNING {}"#,
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(contact.company_id)
+        .bind(&contact.first_name)
+        .bind(&contact.last_name)
+        .bind(&contact.email)
+        .bind(&contact.phone)
+        .bind(&contact.position)
+        .bind(&contact.notes)
+        .fetch_one(&mut **tx)
+        .instrument(db_span("INSERT", "contacts"))
+        .await?;
+        Ok(row.into())
+    }
+
+    /// Apply `changes`, like [`CompanyRepo::update`].
+    pub async fn update(
+        tx: &mut Tx<'_>,
+        tenant_id: Uuid,
+        id: Uuid,
+        changes: &ContactChanges,
+        expected: Option<&[DateTime<Utc>]>,
+    ) -> Result<Option<Contact>, sqlx::Error> {
+        let row = sqlx::query_as::<_, ContactRow>(&format!(
+            r#"UPDATE contacts SET
+                   company_id = COALESCE($3, company_id),
+                   first_name = COALESCE($4, first_name),
+                   last_name = COALESCE($5, last_name),
+                   email = COALESCE($6, email),
+                   phone = COALESCE($7, phone),
+                   position = COALESCE($8, position),
+                   notes = COALESCE($9, notes),
+                   is_active = COALESCE($10, is_active),
+                   updated_at = NOW()
+               WHERE tenant_id = $1 AND id = $2 AND ($11::timestamptz[] IS NULL OR updated_at = ANY($11))
+               RETURNING {}"#,
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(id)
+        .bind(changes.company_id)
+        .bind(&changes.first_name)
+        .bind(&changes.last_name)
+        .bind(&changes.email)
+        .bind(&changes.phone)
+        .bind(&changes.position)
+        .bind(&changes.notes)
+        .bind(changes.is_active)
+        .bind(expected)
+        .fetch_optional(&mut **tx)
+        .instrument(db_span("UPDATE", "contacts"))
+        .await?;
+        Ok(row.map(Contact::from))
+    }
+
+    /// Soft-delete; `false` if there was no such contact.
+    pub async fn deactivate(tx: &mut Tx<'_>, tenant_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE contacts SET is_active = false, updated_at = NOW() WHERE tenant_id = $1 AND id = $2")
+            .bind(tenant_id)
+            .bind(id)
+            .execute(&mut **tx)
+            .instrument(db_span("UPDATE", "contacts"))
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{begin, testing};
+
+    #[sqlx::test(migrations = false)]
+    async fn companies_keep_unchanged_fields(pool: PgPool) -> sqlx::Result<()> {
+        testing::migrate(&pool).await?;
+        let mut conn = pool.acquire().await?;
+        let tenant_id = testing::tenant(&mut conn).await?;
+        let mut tx = begin(&mut conn, tenant_id).await?;
+
+        let company = NewCompany {
+            name: "Acme".to_string(),
+            website: None,
+            email: Some("sales@acme.test".to_string()),
+            phone: None,
+            address: serde_json::json!({}),
+            tags: vec!["key".to_string()],
+        };
+        let created = CompanyRepo::insert(&mut tx, tenant_id, &company).await?;
+        let renamed = CompanyChanges { name: Some("Acme Corp".to_string()), ..Default::default() };
+        let updated = CompanyRepo::update(&mut tx, tenant_id, created.id, &renamed, None).await?.expect("company");
+        assert_eq!(updated.name, "Acme Corp");
+        assert_eq!(updated.email.as_deref(), Some("sales@acme.test"));
+        assert_eq!(updated.tags, vec!["key".to_string()]);
+
+        assert!(!CompanyRepo::deactivate(&mut tx, testing::OTHER_TENANT, created.id).await?);
+        assert!(CompanyRepo::deactivate(&mut tx, tenant_id, created.id).await?);
+        assert!(!CompanyRepo::find(&mut tx, tenant_id, created.id).await?.expect("company").is_active);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn contacts_are_scoped_to_their_tenant(pool: PgPool) -> sqlx::Result<()> {
+        testing::migrate(&pool).await?;
+        let mut conn = pool.acquire().await?;
+        let tenant_id = testing::tenant(&mut conn).await?;
+        let mut tx = begin(&mut conn, tenant_id).await?;
+
+        let contact = NewContact {
+            company_id: None,
+            first_name: "Siti".to_string(),
+            last_name: "Rahma".to_string(),
+            email: None,
+            phone: None,
+            position: Some("Buyer".to_string()),
+            notes: None,
+        };
+        let created = ContactRepo::insert(&mut tx, tenant_id, &contact).await?;
+        assert_eq!(ContactRepo::find(&mut tx, tenant_id, created.id).await?.map(|c| c.position), Some(Some("Buyer".to_string())));
+        assert!(ContactRepo::find(&mut tx, testing::OTHER_TENANT, created.id).await?.is_none());
+
+        let stale = [created.updated_at - chrono::Duration::seconds(1)];
+        let moved = ContactChanges { notes: Some("Moved".to_string()), ..Default::default() };
+        assert!(ContactRepo::update(&mut tx, tenant_id, created.id, &moved, Some(&stale)).await?.is_none());
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use sqlx::{postgres::PgPoolOptions, Connection, PgConnection, PgPool, Postgres, Transaction};
 use anyhow::Result;
 use std::time::Duration;
+use uuid::Uuid;
 
 pub async fn create_pool(database_url: &str, max: u32, min: u32, acquire_sec: u64, idle_sec: u64) -> Result<PgPool> {
     let pool = PgPoolOptions::new()
@@ -12,3 +13,19 @@ pub async fn create_pool(database_url: &str, max: u32, min: u32, acquire_sec: u6
         .await?;
     Ok(pool)
 }
+
+/// The transaction every repository call runs in.
+pub type Tx<'c> = Transaction<'c, Postgres>;
+
+/// Begin a transaction for `tenant_id`.
+///
+/// `app.current_tenant_id`, which the RLS policies read, is set local to the
+/// transaction, so it can't leak to the next user of a pooled connection.
+pub async fn begin(conn: &mut PgConnection, tenant_id: Uuid) -> Result<Tx<'_>, sqlx::Error> {
+    let mut tx = conn.begin().await?;
+    sqlx::query("SELECT set_config('app.current_tenant_id', $1, true)")
+        .bind(tenant_id.to_string())
+        .execute(&mut *tx)
+        .await?;
+    Ok(tx)
+}
@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use shared_types::{CreateProductRequest, CreateWarehouseRequest, Product, ProductStatus, UpdateProductRequest, Warehouse};
+use sqlx::{postgres::PgRow, FromRow};
+use telemetry::db_span;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::Tx;
+
+#[derive(FromRow)]
+struct ProductRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    sku: String,
+    name: String,
+    description: Option<String>,
+    category_id: Option<Uuid>,
+    unit_of_measure: String,
+    cost_price: Decimal,
+    selling_price: Decimal,
+    minimum_stock: i32,
+    current_stock: i32,
+    status: ProductStatus,
+    barcode: Option<String>,
+    weight: Option<Decimal>,
+    dimensions: Option<serde_json::Value>,
+    supplier_id: Option<Uuid>,
+    is_active: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<ProductRow> for Product {
+    fn from(row: ProductRow) -> Self {
+        Product {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            sku: row.sku,
+            name: row.name,
+            description: row.description,
+            category_id: row.category_id,
+            category: None,
+            unit_of_measure: row.unit_of_measure,
+            cost_price: row.cost_price,
+            selling_price: row.selling_price,
+            minimum_stock: row.minimum_stock,
+            current_stock: row.current_stock,
+            status: row.status,
+            barcode: row.barcode,
+            weight: row.weight,
+            dimensions: row.dimensions,
+            supplier_id: row.supplier_id,
+            supplier: None,
+            is_active: row.is_active,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+/// Products. Queries alias the table as `p`, as the product list does.
+pub struct ProductRepo;
+
+impl ProductRepo {
+    /// `SELECT` list [`ProductRepo::from_row`] reads
+    pub const COLUMNS: &'static str = r#"p.id, p.tenant_id, p.sku, p.name, p.description, p.category_id,
+       p.unit_of_measure, p.cost_price, p.selling_price,
+       p.minimum_stock, p.current_stock, p.status, p.barcode, p.weight,
+       p.dimensions, p.supplier_id, p.is_active, p.created_at, p.updated_at"#;
+
+    pub fn from_row(row: &PgRow) -> Result<Product, sqlx::Error> {
+        ProductRow::from_row(row).map(Product::from)
+    }
+
+    pub async fn find(tx: &mut Tx<'_>, tenant_id: Uuid, id: Uuid) -> Result<Option<Product>, sqlx::Error> {
+        let row = sqlx::query_as::<_, ProductRow>(&format!(
+            "SELECT {} FROM products p WHERE p.tenant_id = $1 AND p.id = $2",
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .instrument(db_span("SELECT", "products"))
+        .await?;
+        Ok(row.map(Product::from))
+    }
+
+    /// Products by id, inactive ones included.
+    pub async fn find_many(tx: &mut Tx<'_>, tenant_id: Uuid, ids: &[Uuid]) -> Result<HashMap<Uuid, Product>, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let rows = sqlx::query_as::<_, ProductRow>(&format!(
+            "SELECT {} FROM products p WHERE p.tenant_id = $1 AND p.id = ANY($2)",
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(ids)
+        .fetch_all(&mut **tx)
+        .instrument(db_span("SELECT", "products"))
+        .await?;
+        Ok(rows.into_iter().map(|row| (row.id, Product::from(row))).collect())
+    }
+
+    /// Whether another product of the tenant than `except` has `sku`.
+    pub async fn sku_exists(tx: &mut Tx<'_>, tenant_id: Uuid, sku: &str, except: Option<Uuid>) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM products WHERE tenant_id = $1 AND sku = $2 AND id IS DISTINCT FROM $3)",
+        )
+        .bind(tenant_id)
+        .bind(sku)
+        .bind(except)
+        .fetch_one(&mut **tx)
+        .instrument(db_span("SELECT", "products"))
+        .await
+    }
+
+    pub async fn insert(tx: &mut Tx<'_>, tenant_id: Uuid, req: &CreateProductRequest) -> Result<Product, sqlx::Error> {
+        let row = sqlx::query_as::<_, ProductRow>(&format!(
+            r#"INSERT INTO products AS p (tenant_id, sku, name, description, category_id, unit_of_measure,
+                                         cost_price, selling_price, minimum_stock, barcode, weight, dimensions, supplier_id)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+               RETURNING {}"#,
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(&req.sku)
+        .bind(&req.name)
+        .bind(&req.description)
+        .bind(req.category_id)
+        .bind(&req.unit_of_measure)
+        .bind(req.cost_price)
+        .bind(req.selling_price)
+        .bind(req.minimum_stock)
+        .bind(&req.barcode)
+        .bind(req.weight)
+        .bind(&req.dimensions)
+        .bind(req.supplier_id)
+        .fetch_one(&mut **tx)
+        .instrument(db_span("INSERT", "products"))
+        .await?;
+        Ok(row.into())
+    }
+
+    /// Apply the fields `req` sets. With `expected`, only a product last
+    /// updated at one of those times is changed; `None` means the product is
+    /// missing or was updated since.
+    pub async fn update(
+        tx: &mut Tx<'_>,
+        tenant_id: Uuid,
+        id: Uuid,
+        req: &UpdateProductRequest,
+        expected: Option<&[DateTime<Utc>]>,
+    ) -> Result<Option<Product>, sqlx::Error> {
+        let row = sqlx::query_as::<_, ProductRow>(&format!(
+            r#"UPDATE products p SET
+                   sku = COALESCE($3, sku),
+                   name = COALESCE($4, name),
+                   description = COALESCE($5, description),
+                   category_id = COALESCE($6, category_id),
+                   unit_of_measure = COALESCE($7, unit_of_measure),
+                   cost_price = COALESCE($8, cost_price),
+                   selling_price = COALESCE($9, selling_price),
+                   minimum_stock = COALESCE($10, minimum_stock),
+                   status = COALESCE($11, status),
+                   barcode = COALESCE($12, barcode),
+                   weight = COALESCE($13, weight),
+                   dimensions = COALESCE($14, dimensions),
+                   supplier_id = COALESCE($15, supplier_id),
+                   is_active = COALESCE($16, is_active),
+                   updated_at = NOW()
+               WHERE p.tenant_id = $1 AND p.id = $2 AND ($17::timestamptz[] IS NULL OR p.updated_at = ANY($17))
+               RETURNING {}"#,
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(id)
+        .bind(&req.sku)
+        .bind(&req.name)
+        .bind(&req.description)
+        .bind(req.category_id)
+        .bind(&req.unit_of_measure)
+        .bind(req.cost_price)
+        .bind(req.selling_price)
+        .bind(req.minimum_stock)
+        .bind(&req.status)
+        .bind(&req.barcode)
+        .bind(req.weight)
+        .bind(&req.dimensions)
+        .bind(req.supplier_id)
+        .bind(req.is_active)
+        .bind(expected)
+        .fetch_optional(&mut **tx)
+        .instrument(db_span("UPDATE", "products"))
+        .await?;
+        Ok(row.map(Product::from))
+    }
+
+    /// Soft-delete; `false` if there was no such product.
+    pub async fn deactivate(tx: &mut Tx<'_>, tenant_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("UPDATE products SET is_active = false, updated_at = NOW() WHERE tenant_id = $1 AND id = $2")
+            .bind(tenant_id)
+            .bind(id)
+            .execute(&mut **tx)
+            .instrument(db_span("UPDATE", "products"))
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[derive(FromRow)]
+struct WarehouseRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    code: String,
+    name: String,
+    description: Option<String>,
+    address: Option<serde_json::Value>,
+    manager_id: Option<Uuid>,
+    is_active: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<WarehouseRow> for Warehouse {
+    fn from(row: WarehouseRow) -> Self {
+        Warehouse {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            code: row.code,
+            name: row.name,
+            description: row.description,
+            address: row.address,
+            manager_id: row.manager_id,
+            is_active: row.is_active,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+/// Warehouses.
+pub struct WarehouseRepo;
+
+impl WarehouseRepo {
+    /// `SELECT` list [`WarehouseRepo::from_row`] reads
+    pub const COLUMNS: &'static str = "id, tenant_id, code, name, description, address, manager_id, is_active, created_at, updated_at";
+
+    pub fn from_row(row: &PgRow) -> Result<Warehouse, sqlx::Error> {
+        WarehouseRow::from_row(row).map(Warehouse::from)
+    }
+
+    pub async fn find_many(tx: &mut Tx<'_>, tenant_id: Uuid, ids: &[Uuid]) -> Result<HashMap<Uuid, Warehouse>, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let rows = sqlx::query_as::<_, WarehouseRow>(&format!(
+            "SELECT {} FROM warehouses WHERE tenant_id = $1 AND id = ANY($2)",
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(ids)
+        .fetch_all(&mut **tx)
+        .instrument(db_span("SELECT", "warehouses"))
+        .await?;
+        Ok(rows.into_iter().map(|row| (row.id, Warehouse::from(row))).collect())
+    }
+
+    pub async fn code_exists(tx: &mut Tx<'_>, tenant_id: Uuid, code: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM warehouses WHERE tenant_id = $1 AND code = $2)")
+            .bind(tenant_id)
+            .bind(code)
+            .fetch_one(&mut **tx)
+            .instrument(db_span("SELECT", "warehouses"))
+            .await
+    }
+
+    pub async fn insert(tx: &mut Tx<'_>, tenant_id: Uuid, req: &CreateWarehouseRequest) -> Result<Warehouse, sqlx::Error> {
+        let row = sqlx::query_as::<_, WarehouseRow>(&format!(
+            r#"INSERT INTO warehouses (tenant_id, code, name, description, address, manager_id)
+               VALUES ($1, $2, $3, $4, $5, $6)
+               RETURNING {}"#,
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(&req.code)
+        .bind(&req.name)
+        .bind(&req.description)
+        .bind(&req.address)
+        .bind(req.manager_id)
+        .fetch_one(&mut **tx)
+        .instrument(db_span("INSERT", "warehouses"))
+        .await?;
+        Ok(row.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{begin, testing};
+
+    fn product(sku: &str) -> CreateProductRequest {
+        CreateProductRequest {
+            sku: sku.to_string(),
+            name: format!("Product {}", sku),
+            description: None,
+            category_id: None,
+            unit_of_measure: "pcs".to_string(),
+            cost_price: Decimal::new(1000, 2),
+            selling_price: Decimal::new(1500, 2),
+            minimum_stock: 5,
+            barcode: None,
+            weight: None,
+            dimensions: None,
+            supplier_id: None,
+        }
+    }
+
+    fn no_changes() -> UpdateProductRequest {
+        UpdateProductRequest {
+            sku: None,
+            name: None,
+            description: None,
+            category_id: None,
+            unit_of_measure: None,
+            cost_price: None,
+            selling_price: None,
+            minimum_stock: None,
+            status: None,
+            barcode: None,
+            weight: None,
+            dimensions: None,
+            supplier_id: None,
+            is_active: None,
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn products_update_only_at_the_expected_version(pool: PgPool) -> sqlx::Result<()> {
+        testing::migrate(&pool).await?;
+        let mut conn = pool.acquire().await?;
+        let tenant_id = testing::tenant(&mut conn).await?;
+        let mut tx = begin(&mut conn, tenant_id).await?;
+
+        let created = ProductRepo::insert(&mut tx, tenant_id, &product("SKU-1")).await?;
+        assert!(matches!(created.status, ProductStatus::Active));
+        assert!(ProductRepo::sku_exists(&mut tx, tenant_id, "SKU-1", None).await?);
+        assert!(!ProductRepo::sku_exists(&mut tx, tenant_id, "SKU-1", Some(created.id)).await?);
+
+        let discontinue = UpdateProductRequest { status: Some(ProductStatus::Discontinued), ..no_changes() };
+        let stale = [created.updated_at - chrono::Duration::seconds(1)];
+        assert!(ProductRepo::update(&mut tx, tenant_id, created.id, &discontinue, Some(&stale)).await?.is_none());
+        assert!(ProductRepo::update(&mut tx, testing::OTHER_TENANT, created.id, &discontinue, None).await?.is_none());
+
+        let updated = ProductRepo::update(&mut tx, tenant_id, created.id, &discontinue, Some(&[created.updated_at]))
+            .await?
+            .expect("product at the expected version");
+        assert!(matches!(updated.status, ProductStatus::Discontinued));
+        assert_eq!(updated.sku, "SKU-1");
+
+        assert!(ProductRepo::deactivate(&mut tx, tenant_id, created.id).await?);
+        let found = ProductRepo::find_many(&mut tx, tenant_id, &[created.id]).await?;
+        assert!(!found[&created.id].is_active);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn warehouses_are_scoped_to_their_tenant(pool: PgPool) -> sqlx::Result<()> {
+        testing::migrate(&pool).await?;
+        let mut conn = pool.acquire().await?;
+        let tenant_id = testing::tenant(&mut conn).await?;
+        let mut tx = begin(&mut conn, tenant_id).await?;
+
+        let req = CreateWarehouseRequest {
+            code: "WH-1".to_string(),
+            name: "Main".to_string(),
+            description: None,
+            address: Some(serde_json::json!({ "city": "Bandung" })),
+            manager_id: None,
+        };
+        let created = WarehouseRepo::insert(&mut tx, tenant_id, &req).await?;
+        assert!(WarehouseRepo::code_exists(&mut tx, tenant_id, "WH-1").await?);
+        assert_eq!(WarehouseRepo::find_many(&mut tx, tenant_id, &[created.id]).await?[&created.id].address, req.address);
+        assert!(WarehouseRepo::find_many(&mut tx, testing::OTHER_TENANT, &[created.id]).await?.is_empty());
+        Ok(())
+    }
+}
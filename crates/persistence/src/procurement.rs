@@ -0,0 +1,520 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use shared_types::{
+    CreateVendorRequest, Currency, Money, PurchaseOrder, PurchaseOrderItem, PurchaseOrderStatus, Vendor, VendorStatus,
+};
+use sqlx::{postgres::PgRow, FromRow};
+use telemetry::db_span;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::Tx;
+
+#[derive(FromRow)]
+struct VendorRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    code: String,
+    name: String,
+    contact_person: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+    address: Option<serde_json::Value>,
+    tax_number: Option<String>,
+    payment_terms: Option<String>,
+    currency: String,
+    status: VendorStatus,
+    credit_limit: Option<Decimal>,
+    is_active: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<VendorRow> for Vendor {
+    fn from(row: VendorRow) -> Self {
+        Vendor {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            code: row.code,
+            name: row.name,
+            contact_person: row.contact_person,
+            email: row.email,
+            phone: row.phone,
+            address: row.address,
+            tax_number: row.tax_number,
+            payment_terms: row.payment_terms,
+            currency: row.currency,
+            status: row.status,
+            credit_limit: row.credit_limit,
+            is_active: row.is_active,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+/// Vendors.
+pub struct VendorRepo;
+
+impl VendorRepo {
+    /// `SELECT` list [`VendorRepo::from_row`] reads
+    pub const COLUMNS: &'static str = r#"id, tenant_id, code, name, contact_person, email, phone, address,
+       tax_number, payment_terms, currency, status, credit_limit, is_active,
+       created_at, updated_at"#;
+
+    pub fn from_row(row: &PgRow) -> Result<Vendor, sqlx::Error> {
+        VendorRow::from_row(row).map(Vendor::from)
+    }
+
+    pub async fn find(tx: &mut Tx<'_>, tenant_id: Uuid, id: Uuid) -> Result<Option<Vendor>, sqlx::Error> {
+        let row = sqlx::query_as::<_, VendorRow>(&format!(
+            "SELECT {} FROM vendors WHERE tenant_id = $1 AND id = $2",
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .instrument(db_span("SELECT", "vendors"))
+        .await?;
+        Ok(row.map(Vendor::from))
+    }
+
+    pub async fn find_many(tx: &mut Tx<'_>, tenant_id: Uuid, ids: &[Uuid]) -> Result<HashMap<Uuid, Vendor>, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let rows = sqlx::query_as::<_, VendorRow>(&format!(
+            "SELECT {} FROM vendors WHERE tenant_id = $1 AND id = ANY($2)",
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(ids)
+        .fetch_all(&mut **tx)
+        .instrument(db_span("SELECT", "vendors"))
+        .await?;
+        Ok(rows.into_iter().map(|row| (row.id, Vendor::from(row))).collect())
+    }
+
+    pub async fn code_exists(tx: &mut Tx<'_>, tenant_id: Uuid, code: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM vendors WHERE tenant_id = $1 AND code = $2)")
+            .bind(tenant_id)
+            .bind(code)
+            .fetch_one(&mut **tx)
+            .instrument(db_span("SELECT", "vendors"))
+            .await
+    }
+
+    /// Whether the vendor exists and hasn't been deleted.
+    pub async fn is_active(tx: &mut Tx<'_>, tenant_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM vendors WHERE tenant_id = $1 AND id = $2 AND is_active = true)")
+            .bind(tenant_id)
+            .bind(id)
+            .fetch_one(&mut **tx)
+            .instrument(db_span("SELECT", "vendors"))
+            .await
+    }
+
+    pub async fn insert(tx: &mut Tx<'_>, tenant_id: Uuid, req: &CreateVendorRequest) -> Result<Vendor, sqlx::Error> {
+        let row = sqlx::query_as::<_, VendorRow>(&format!(
+            r#"INSERT INTO vendors (tenant_id, code, name, contact_person, email, phone, address,
+                                  tax_number, payment_terms, currency, credit_limit)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+               RETURNING {}"#,
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(&req.code)
+        .bind(&req.name)
+        .bind(&req.contact_person)
+        .bind(&req.email)
+        .bind(&req.phone)
+        .bind(&req.address)
+        .bind(&req.tax_number)
+        .bind(&req.payment_terms)
+        .bind(&req.currency)
+        .bind(req.credit_limit)
+        .fetch_one(&mut **tx)
+        .instrument(db_span("INSERT", "vendors"))
+        .await?;
+        Ok(row.into())
+    }
+}
+
+#[derive(FromRow)]
+struct PurchaseOrderRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    po_number: String,
+    vendor_id: Uuid,
+    order_date: NaiveDate,
+    expected_delivery_date: Option<NaiveDate>,
+    delivery_address: Option<serde_json::Value>,
+    status: PurchaseOrderStatus,
+    currency: Currency,
+    exchange_rate: Decimal,
+    subtotal: Decimal,
+    tax_amount: Decimal,
+    discount_amount: Decimal,
+    total_amount: Decimal,
+    notes: Option<String>,
+    terms_conditions: Option<String>,
+    created_by: Uuid,
+    approved_by: Option<Uuid>,
+    approved_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<PurchaseOrderRow> for PurchaseOrder {
+    fn from(row: PurchaseOrderRow) -> Self {
+        let currency = row.currency;
+        PurchaseOrder {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            po_number: row.po_number,
+            vendor_id: row.vendor_id,
+            vendor: None,
+            order_date: row.order_date,
+            expected_delivery_date: row.expected_delivery_date,
+            delivery_address: row.delivery_address,
+            status: row.status,
+            currency,
+            exchange_rate: row.exchange_rate.normalize(),
+            subtotal: Money::new(row.subtotal, currency),
+            tax_amount: Money::new(row.tax_amount, currency),
+            discount_amount: Money::new(row.discount_amount, currency),
+            total_amount: Money::new(row.total_amount, currency),
+            notes: row.notes,
+            terms_conditions: row.terms_conditions,
+            created_by: row.created_by,
+            approved_by: row.approved_by,
+            approved_at: row.approved_at,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            items: None,
+        }
+    }
+}
+
+/// An item with its product and its order's currency, which the amounts are in
+#[derive(FromRow)]
+struct PurchaseOrderItemRow {
+    id: Uuid,
+    tenant_id: Uuid,
+    purchase_order_id: Uuid,
+    product_id: Uuid,
+    product_sku: Option<String>,
+    product_name: Option<String>,
+    description: Option<String>,
+    quantity_ordered: i32,
+    quantity_received: i32,
+    unit_price: Decimal,
+    discount_percent: Decimal,
+    discount_amount: Decimal,
+    tax_percent: Decimal,
+    tax_amount: Decimal,
+    line_total: Decimal,
+    line_number: i32,
+    created_at: DateTime<Utc>,
+    currency: Currency,
+}
+
+impl From<PurchaseOrderItemRow> for PurchaseOrderItem {
+    fn from(row: PurchaseOrderItemRow) -> Self {
+        let currency = row.currency;
+        PurchaseOrderItem {
+            id: row.id,
+            tenant_id: row.tenant_id,
+            purchase_order_id: row.purchase_order_id,
+            product_id: row.product_id,
+            product_sku: row.product_sku,
+            product_name: row.product_name,
+            description: row.description,
+            quantity_ordered: row.quantity_ordered,
+            quantity_received: row.quantity_received,
+            unit_price: Money::new(row.unit_price, currency),
+            discount_percent: row.discount_percent,
+            discount_amount: Money::new(row.discount_amount, currency),
+            tax_percent: row.tax_percent,
+            tax_amount: Money::new(row.tax_amount, currency),
+            line_total: Money::new(row.line_total, currency),
+            line_number: row.line_number,
+            created_at: row.created_at,
+        }
+    }
+}
+
+/// Header of an order to place, as a draft.
+pub struct NewPurchaseOrder<'a> {
+    pub po_number: &'a str,
+    pub vendor_id: Uuid,
+    pub order_date: NaiveDate,
+    pub expected_delivery_date: Option<NaiveDate>,
+    pub delivery_address: Option<&'a serde_json::Value>,
+    pub currency: Currency,
+    pub exchange_rate: Decimal,
+    pub notes: Option<&'a str>,
+    pub terms_conditions: Option<&'a str>,
+    pub created_by: Uuid,
+}
+
+/// An order line with its amounts already worked out.
+pub struct NewPurchaseOrderItem<'a> {
+    pub product_id: Uuid,
+    pub description: Option<&'a str>,
+    pub quantity_ordered: i32,
+    pub unit_price: Money,
+    pub discount_percent: Decimal,
+    pub discount_amount: Money,
+    pub tax_percent: Decimal,
+    pub tax_amount: Money,
+    pub line_total: Money,
+}
+
+/// Purchase orders and their items. Queries alias the table as `po`, as the
+/// order list does.
+pub struct PurchaseOrderRepo;
+
+impl PurchaseOrderRepo {
+    /// `SELECT` list [`PurchaseOrderRepo::from_row`] reads
+    pub const COLUMNS: &'static str = r#"po.id, po.tenant_id, po.po_number, po.vendor_id, po.order_date,
+       po.expected_delivery_date, po.delivery_address, po.status, po.currency,
+       po.exchange_rate, po.subtotal, po.tax_amount, po.discount_amount,
+       po.total_amount, po.notes, po.terms_conditions, po.created_by,
+       po.approved_by, po.approved_at, po.created_at, po.updated_at"#;
+
+    pub fn from_row(row: &PgRow) -> Result<PurchaseOrder, sqlx::Error> {
+        PurchaseOrderRow::from_row(row).map(PurchaseOrder::from)
+    }
+
+    pub async fn find(tx: &mut Tx<'_>, tenant_id: Uuid, id: Uuid) -> Result<Option<PurchaseOrder>, sqlx::Error> {
+        let row = sqlx::query_as::<_, PurchaseOrderRow>(&format!(
+            "SELECT {} FROM purchase_orders po WHERE po.tenant_id = $1 AND po.id = $2",
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .instrument(db_span("SELECT", "purchase_orders"))
+        .await?;
+        Ok(row.map(PurchaseOrder::from))
+    }
+
+    /// Place an order with its items, numbered from 1 in the order given.
+    ///
+    /// The header comes back as inserted, before the items' trigger summed
+    /// them into its totals, and without its items.
+    pub async fn insert(
+        tx: &mut Tx<'_>,
+        tenant_id: Uuid,
+        order: &NewPurchaseOrder<'_>,
+        items: &[NewPurchaseOrderItem<'_>],
+    ) -> Result<PurchaseOrder, sqlx::Error> {
+        let row = sqlx::query_as::<_, PurchaseOrderRow>(&format!(
+            r#"INSERT INTO purchase_orders AS po (tenant_id, po_number, vendor_id, order_date,
+                                                 expected_delivery_date, delivery_address, currency,
+                                                 exchange_rate, notes, terms_conditions, created_by)
+               VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+               RETURNING {}"#,
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(order.po_number)
+        .bind(order.vendor_id)
+        .bind(order.order_date)
+        .bind(order.expected_delivery_date)
+        .bind(order.delivery_address)
+        .bind(order.currency)
+        .bind(order.exchange_rate)
+        .bind(order.notes)
+        .bind(order.terms_conditions)
+        .bind(order.created_by)
+        .fetch_one(&mut **tx)
+        .instrument(db_span("INSERT", "purchase_orders"))
+        .await?;
+
+        for (index, item) in items.iter().enumerate() {
+            sqlx::query(
+                r#"INSERT INTO purchase_order_items (tenant_id, purchase_order_id, product_id,
+                                                    description, quantity_ordered, unit_price,
+                                                    discount_percent, discount_amount, tax_percent,
+                                                    tax_amount, line_total, line_number)
+                   VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#,
+            )
+            .bind(tenant_id)
+            .bind(row.id)
+            .bind(item.product_id)
+            .bind(item.description)
+            .bind(item.quantity_ordered)
+            .bind(item.unit_price.amount())
+            .bind(item.discount_percent)
+            .bind(item.discount_amount.amount())
+            .bind(item.tax_percent)
+            .bind(item.tax_amount.amount())
+            .bind(item.line_total.amount())
+            .bind((index + 1) as i32)
+            .execute(&mut **tx)
+            .instrument(db_span("INSERT", "purchase_order_items"))
+            .await?;
+        }
+        Ok(row.into())
+    }
+
+    /// Items of the given orders, grouped by order in line order.
+    pub async fn items(tx: &mut Tx<'_>, tenant_id: Uuid, order_ids: &[Uuid]) -> Result<Vec<PurchaseOrderItem>, sqlx::Error> {
+        if order_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let rows = sqlx::query_as::<_, PurchaseOrderItemRow>(
+            r#"SELECT i.id, i.tenant_id, i.purchase_order_id, i.product_id, p.sku AS product_sku,
+                      p.name AS product_name, i.description, i.quantity_ordered, i.quantity_received,
+                      i.unit_price, i.discount_percent, i.discount_amount, i.tax_percent, i.tax_amount,
+                      i.line_total, i.line_number, i.created_at, po.currency
+               FROM purchase_order_items i
+               JOIN purchase_orders po ON po.id = i.purchase_order_id
+               LEFT JOIN products p ON i.product_id = p.id
+               WHERE i.tenant_id = $1 AND i.purchase_order_id = ANY($2)
+               ORDER BY i.purchase_order_id, i.line_number"#,
+        )
+        .bind(tenant_id)
+        .bind(order_ids)
+        .fetch_all(&mut **tx)
+        .instrument(db_span("SELECT", "purchase_order_items"))
+        .await?;
+        Ok(rows.into_iter().map(PurchaseOrderItem::from).collect())
+    }
+
+    /// Move an order from `from` to `to`; `None` if there's no such order or
+    /// it isn't in `from` (any more).
+    pub async fn transition(
+        tx: &mut Tx<'_>,
+        tenant_id: Uuid,
+        id: Uuid,
+        from: PurchaseOrderStatus,
+        to: PurchaseOrderStatus,
+    ) -> Result<Option<PurchaseOrder>, sqlx::Error> {
+        let row = sqlx::query_as::<_, PurchaseOrderRow>(&format!(
+            r#"UPDATE purchase_orders po SET status = $4, updated_at = NOW()
+               WHERE po.tenant_id = $1 AND po.id = $2 AND po.status = $3
+               RETURNING {}"#,
+            Self::COLUMNS
+        ))
+        .bind(tenant_id)
+        .bind(id)
+        .bind(from)
+        .bind(to)
+        .fetch_optional(&mut **tx)
+        .instrument(db_span("UPDATE", "purchase_orders"))
+        .await?;
+        Ok(row.map(PurchaseOrder::from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::{begin, testing};
+
+    fn vendor(code: &str) -> CreateVendorRequest {
+        CreateVendorRequest {
+            code: code.to_string(),
+            name: format!("Vendor {}", code),
+            contact_person: None,
+            email: None,
+            phone: None,
+            address: None,
+            tax_number: None,
+            payment_terms: Some("NET30".to_string()),
+            currency: "IDR".to_string(),
+            credit_limit: None,
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn orders_are_placed_with_their_items(pool: PgPool) -> sqlx::Result<()> {
+        testing::migrate(&pool).await?;
+        let mut conn = pool.acquire().await?;
+        let tenant_id = testing::tenant(&mut conn).await?;
+        let user_id = testing::user(&mut conn, tenant_id).await?;
+        let mut tx = begin(&mut conn, tenant_id).await?;
+
+        let supplier = VendorRepo::insert(&mut tx, tenant_id, &vendor("V-1")).await?;
+        assert!(matches!(supplier.status, VendorStatus::Active));
+        assert!(VendorRepo::is_active(&mut tx, tenant_id, supplier.id).await?);
+        assert!(!VendorRepo::is_active(&mut tx, testing::OTHER_TENANT, supplier.id).await?);
+        let product_id: Uuid = sqlx::query_scalar("INSERT INTO products (tenant_id, sku, name) VALUES ($1, 'P-1', 'Bolt') RETURNING id")
+            .bind(tenant_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let price = Money::new(Decimal::new(1250, 2), Currency::USD);
+        let order = NewPurchaseOrder {
+            po_number: "PO-1",
+            vendor_id: supplier.id,
+            order_date: NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(),
+            expected_delivery_date: None,
+            delivery_address: None,
+            currency: Currency::USD,
+            exchange_rate: Decimal::new(15500, 0),
+            notes: None,
+            terms_conditions: None,
+            created_by: user_id,
+        };
+        let item = NewPurchaseOrderItem {
+            product_id,
+            description: None,
+            quantity_ordered: 2,
+            unit_price: price,
+            discount_percent: Decimal::ZERO,
+            discount_amount: Money::zero(Currency::USD),
+            tax_percent: Decimal::ZERO,
+            tax_amount: Money::zero(Currency::USD),
+            line_total: Money::new(Decimal::new(2500, 2), Currency::USD),
+        };
+        let placed = PurchaseOrderRepo::insert(&mut tx, tenant_id, &order, &[item]).await?;
+        assert!(matches!(placed.status, PurchaseOrderStatus::Draft));
+        assert_eq!(placed.exchange_rate, Decimal::new(15500, 0));
+
+        let items = PurchaseOrderRepo::items(&mut tx, tenant_id, &[placed.id]).await?;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].product_sku.as_deref(), Some("P-1"));
+        assert_eq!(items[0].unit_price, price);
+        Ok(())
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn orders_only_move_from_the_expected_status(pool: PgPool) -> sqlx::Result<()> {
+        testing::migrate(&pool).await?;
+        let mut conn = pool.acquire().await?;
+        let tenant_id = testing::tenant(&mut conn).await?;
+        let user_id = testing::user(&mut conn, tenant_id).await?;
+        let mut tx = begin(&mut conn, tenant_id).await?;
+
+        let supplier = VendorRepo::insert(&mut tx, tenant_id, &vendor("V-2")).await?;
+        let order = NewPurchaseOrder {
+            po_number: "PO-2",
+            vendor_id: supplier.id,
+            order_date: NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(),
+            expected_delivery_date: None,
+            delivery_address: None,
+            currency: Currency::IDR,
+            exchange_rate: Decimal::ONE,
+            notes: None,
+            terms_conditions: None,
+            created_by: user_id,
+        };
+        let placed = PurchaseOrderRepo::insert(&mut tx, tenant_id, &order, &[]).await?;
+
+        let submitted = PurchaseOrderRepo::transition(&mut tx, tenant_id, placed.id, PurchaseOrderStatus::Draft, PurchaseOrderStatus::Pending).await?;
+        assert!(matches!(submitted.map(|o| o.status), Some(PurchaseOrderStatus::Pending)));
+        let again = PurchaseOrderRepo::transition(&mut tx, tenant_id, placed.id, PurchaseOrderStatus::Draft, PurchaseOrderStatus::Pending).await?;
+        assert!(again.is_none());
+        let found = PurchaseOrderRepo::find(&mut tx, tenant_id, placed.id).await?.expect("order");
+        assert!(matches!(found.status, PurchaseOrderStatus::Pending));
+        Ok(())
+    }
+}
@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::money::{Currency, Money};
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Account {
     pub id: Uuid,
@@ -22,6 +24,7 @@ pub struct Account {
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type), sqlx(type_name = "varchar", rename_all = "snake_case"))]
 pub enum AccountType {
     Asset,
     Liability,
@@ -32,6 +35,7 @@ pub enum AccountType {
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type), sqlx(type_name = "varchar", rename_all = "snake_case"))]
 pub enum BalanceType {
     Debit,
     Credit,
@@ -45,8 +49,9 @@ pub struct JournalEntry {
     pub entry_date: NaiveDate,
     pub reference: Option<String>,
     pub description: String,
-    pub total_debit: Decimal,
-    pub total_credit: Decimal,
+    pub currency: Currency,
+    pub total_debit: Money,
+    pub total_credit: Money,
     pub status: JournalEntryStatus,
     pub created_by: Uuid,
     pub posted_by: Option<Uuid>,
@@ -56,8 +61,9 @@ pub struct JournalEntry {
     pub lines: Option<Vec<JournalEntryLine>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type), sqlx(type_name = "varchar", rename_all = "snake_case"))]
 pub enum JournalEntryStatus {
     Draft,
     Posted,
@@ -72,8 +78,8 @@ pub struct JournalEntryLine {
     pub account_id: Uuid,
     pub account: Option<Account>,
     pub description: Option<String>,
-    pub debit_amount: Decimal,
-    pub credit_amount: Decimal,
+    pub debit_amount: Money,
+    pub credit_amount: Money,
     pub line_number: i32,
     pub created_at: DateTime<Utc>,
 }
@@ -86,9 +92,13 @@ pub struct AccountBalance {
     pub account: Option<Account>,
     pub period_year: i32,
     pub period_month: i32,
+    #[schema(value_type = String)]
     pub opening_balance: Decimal,
+    #[schema(value_type = String)]
     pub debit_total: Decimal,
+    #[schema(value_type = String)]
     pub credit_total: Decimal,
+    #[schema(value_type = String)]
     pub closing_balance: Decimal,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -143,6 +153,8 @@ pub struct CreateJournalEntryRequest {
     pub entry_date: NaiveDate,
     pub reference: Option<String>,
     pub description: String,
+    /// Currency of every line; IDR when omitted
+    pub currency: Option<Currency>,
     pub lines: Vec<CreateJournalEntryLineRequest>,
 }
 
@@ -150,7 +162,9 @@ pub struct CreateJournalEntryRequest {
 pub struct CreateJournalEntryLineRequest {
     pub account_id: Uuid,
     pub description: Option<String>,
+    #[schema(value_type = String)]
     pub debit_amount: Decimal,
+    #[schema(value_type = String)]
     pub credit_amount: Decimal,
 }
 
@@ -168,14 +182,18 @@ pub struct TrialBalance {
     pub period_start: NaiveDate,
     pub period_end: NaiveDate,
     pub accounts: Vec<TrialBalanceAccount>,
+    #[schema(value_type = String)]
     pub total_debits: Decimal,
+    #[schema(value_type = String)]
     pub total_credits: Decimal,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TrialBalanceAccount {
     pub account: Account,
+    #[schema(value_type = String)]
     pub debit_balance: Decimal,
+    #[schema(value_type = String)]
     pub credit_balance: Decimal,
 }
 
@@ -185,8 +203,11 @@ pub struct BalanceSheet {
     pub assets: Vec<BalanceSheetSection>,
     pub liabilities: Vec<BalanceSheetSection>,
     pub equity: Vec<BalanceSheetSection>,
+    #[schema(value_type = String)]
     pub total_assets: Decimal,
+    #[schema(value_type = String)]
     pub total_liabilities: Decimal,
+    #[schema(value_type = String)]
     pub total_equity: Decimal,
 }
 
@@ -194,12 +215,14 @@ pub struct BalanceSheet {
 pub struct BalanceSheetSection {
     pub section_name: String,
     pub accounts: Vec<BalanceSheetAccount>,
+    #[schema(value_type = String)]
     pub section_total: Decimal,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct BalanceSheetAccount {
     pub account: Account,
+    #[schema(value_type = String)]
     pub balance: Decimal,
 }
 
@@ -209,8 +232,11 @@ pub struct IncomeStatement {
     pub period_end: NaiveDate,
     pub revenue: Vec<IncomeStatementSection>,
     pub expenses: Vec<IncomeStatementSection>,
+    #[schema(value_type = String)]
     pub total_revenue: Decimal,
+    #[schema(value_type = String)]
     pub total_expenses: Decimal,
+    #[schema(value_type = String)]
     pub net_income: Decimal,
 }
 
@@ -218,11 +244,106 @@ pub struct IncomeStatement {
 pub struct IncomeStatementSection {
     pub section_name: String,
     pub accounts: Vec<IncomeStatementAccount>,
+    #[schema(value_type = String)]
     pub section_total: Decimal,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct IncomeStatementAccount {
     pub account: Account,
+    #[schema(value_type = String)]
     pub amount: Decimal,
 }
+
+/// Which published rate a figure uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RateType {
+    /// Market rate of the day; what documents are booked at
+    #[default]
+    Spot,
+    /// Kurs pajak, fixed weekly by decree of the Minister of Finance (KMK), for VAT and withholding tax
+    Tax,
+    /// Period average, for translating income statements
+    Average,
+}
+
+impl RateType {
+    pub const ALL: [RateType; 3] = [RateType::Spot, RateType::Tax, RateType::Average];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateType::Spot => "spot",
+            RateType::Tax => "tax",
+            RateType::Average => "average",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == s)
+    }
+}
+
+/// One unit of `from_currency` is worth `rate` units of `to_currency`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ExchangeRate {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub from_currency: Currency,
+    pub to_currency: Currency,
+    pub rate_type: RateType,
+    pub rate_date: NaiveDate,
+    #[schema(value_type = String, example = "16250.5")]
+    pub rate: Decimal,
+    /// `manual`, `import` or `feed`
+    pub source: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Creates the rate, or replaces the one with the same pair, type and date.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreateExchangeRateRequest {
+    pub from_currency: Currency,
+    pub to_currency: Currency,
+    /// `spot` when omitted
+    pub rate_type: Option<RateType>,
+    pub rate_date: NaiveDate,
+    #[schema(value_type = String, example = "16250.5")]
+    pub rate: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FetchExchangeRatesRequest {
+    /// Day to fetch; today when omitted
+    pub date: Option<NaiveDate>,
+}
+
+/// How an effective rate was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConversionMethod {
+    /// Same currency, rate 1
+    Identity,
+    /// A stored rate for the pair
+    Direct,
+    /// The reciprocal of a stored rate for the opposite pair
+    Inverse,
+    /// Two rates chained through the tenant's base currency
+    Triangulated,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Conversion {
+    pub amount: Money,
+    pub converted: Money,
+    #[schema(value_type = String, example = "16250.5")]
+    pub rate: Decimal,
+    pub rate_type: RateType,
+    /// Date of the rate used; the oldest leg when triangulated
+    pub rate_date: NaiveDate,
+    pub method: ConversionMethod,
+    /// The currency both legs go through when triangulated
+    pub via: Option<Currency>,
+}
@@ -5,6 +5,7 @@ use uuid::Uuid;
 use validator::Validate;
 
 use crate::common::BaseEntity;
+use crate::i18n::Locale;
 
 /// User entity
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -17,6 +18,8 @@ pub struct User {
     pub is_active: bool,
     pub email_verified_at: Option<DateTime<Utc>>,
     pub last_login_at: Option<DateTime<Utc>>,
+    /// Language of API messages when the request has no `Accept-Language`; unset follows the tenant
+    pub locale: Option<Locale>,
 }
 
 /// Tenant entity
@@ -115,6 +118,23 @@ pub struct RefreshTokenRequest {
     pub refresh_token: String,
 }
 
+/// Invite user request
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct InviteUserRequest {
+    #[validate(email)]
+    pub email: String,
+
+    #[validate(length(min = 1, max = 100))]
+    pub first_name: String,
+
+    #[validate(length(min = 1, max = 100))]
+    pub last_name: String,
+
+    /// Membership role given on acceptance, e.g. `admin` or `member`
+    #[validate(length(min = 1, max = 50))]
+    pub role: String,
+}
+
 /// Accept invitation request
 #[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct AcceptInvitationRequest {
@@ -157,6 +177,9 @@ pub struct UpdateProfileRequest {
 
     #[validate(length(min = 1, max = 50))]
     pub last_name: Option<String>,
+
+    /// Language of API messages, `en` or `id`
+    pub locale: Option<Locale>,
 }
 
 /// JWT claims
@@ -191,7 +214,7 @@ pub struct UserSession {
 }
 
 /// Invitation entity
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct Invitation {
     pub id: Uuid,
     pub tenant_id: Uuid,
@@ -4,6 +4,8 @@ use utoipa::ToSchema;
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::error::ValidationError;
+
 /// Standard pagination parameters
 #[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
 pub struct PaginationParams {
@@ -24,13 +26,13 @@ impl Default for PaginationParams {
 }
 
 /// Standard pagination response
-#[derive(Debug, Clone, Serialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PaginatedResponse<T> {
     pub data: Vec<T>,
     pub pagination: PaginationMeta,
 }
 
-#[derive(Debug, Clone, Serialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct PaginationMeta {
     pub current_page: u32,
     pub per_page: u32,
@@ -40,36 +42,108 @@ pub struct PaginationMeta {
     pub has_prev: bool,
 }
 
-/// Standard filter parameters
-#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
-pub struct FilterParams {
-    pub search: Option<String>,
-    pub sort_by: Option<String>,
-    pub sort_order: Option<SortOrder>,
-    pub created_after: Option<DateTime<Utc>>,
-    pub created_before: Option<DateTime<Utc>>,
+/// Keyset (cursor) pagination response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CursorPaginatedResponse<T> {
+    pub data: Vec<T>,
+    pub pagination: CursorMeta,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CursorMeta {
+    pub limit: u32,
+    /// Opaque cursor for the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+    pub has_next: bool,
+    /// Only present when requested with `count=exact` or `count=estimated`
+    pub total_count: Option<u64>,
+    pub total_count_estimated: bool,
+}
+
+/// How a cursor-paginated list reports its total
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CountMode {
+    /// No total (cheapest)
+    #[default]
+    None,
+    /// Planner row estimate, no table scan
+    Estimated,
+    /// Exact `COUNT(*)`
+    Exact,
+}
+
+/// Operator of a list filter, written as `?field[op]=value` (`?field=value` means `eq`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterOperator {
+    Eq,
+    Ne,
+    /// Comma-separated list of values
+    In,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    /// Case-insensitive substring match
+    Ilike,
+    /// `true` for `IS NULL`, `false` for `IS NOT NULL`
+    Null,
+}
+
+impl FilterOperator {
+    pub const ALL: [FilterOperator; 9] = [
+        Self::Eq,
+        Self::Ne,
+        Self::In,
+        Self::Gt,
+        Self::Gte,
+        Self::Lt,
+        Self::Lte,
+        Self::Ilike,
+        Self::Null,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Eq => "eq",
+            Self::Ne => "ne",
+            Self::In => "in",
+            Self::Gt => "gt",
+            Self::Gte => "gte",
+            Self::Lt => "lt",
+            Self::Lte => "lte",
+            Self::Ilike => "ilike",
+            Self::Null => "null",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|op| op.as_str() == s)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
 #[serde(rename_all = "lowercase")]
+#[derive(Default)]
 pub enum SortOrder {
     Asc,
+    #[default]
     Desc,
 }
 
-impl Default for SortOrder {
-    fn default() -> Self {
-        Self::Desc
-    }
-}
 
 /// Standard API response wrapper
-#[derive(Debug, Clone, Serialize, ToSchema)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ApiResponse<T> {
     pub success: bool,
     pub data: Option<T>,
     pub message: Option<String>,
-    pub errors: Option<Vec<String>>,
+    /// Per-field validation errors
+    pub errors: Option<Vec<ValidationError>>,
+    /// Business error code such as `INVALID_CREDENTIALS`, when there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
 }
 
 impl<T> ApiResponse<T> {
@@ -79,6 +153,7 @@ impl<T> ApiResponse<T> {
             data: Some(data),
             message: None,
             errors: None,
+            code: None,
         }
     }
 
@@ -88,6 +163,7 @@ impl<T> ApiResponse<T> {
             data: Some(data),
             message: Some(message),
             errors: None,
+            code: None,
         }
     }
 
@@ -97,6 +173,7 @@ impl<T> ApiResponse<T> {
             data: None,
             message: Some(message),
             errors: None,
+            code: None,
         }
     }
 
@@ -106,17 +183,28 @@ impl<T> ApiResponse<T> {
             data: None,
             message: Some(message),
             errors: None,
+            code: None,
         }
     }
 
-    pub fn validation_error(errors: Vec<String>) -> ApiResponse<()> {
+    pub fn validation_error(message: String, errors: Vec<ValidationError>) -> ApiResponse<()> {
+        ApiResponse::<()>::validation_error_typed(message, errors)
+    }
+
+    pub fn validation_error_typed(message: String, errors: Vec<ValidationError>) -> ApiResponse<T> {
         ApiResponse {
             success: false,
             data: None,
-            message: Some("Validation failed".to_string()),
+            message: Some(message),
             errors: Some(errors),
+            code: Some("VALIDATION_FAILED".to_string()),
         }
     }
+
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
 }
 
 /// Base entity fields
@@ -174,21 +262,60 @@ pub struct ContactInfo {
     pub website: Option<String>,
 }
 
-/// Money amount with currency
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-pub struct Money {
-    pub amount: f64,
-    pub currency: String, // ISO 4217 currency code
+/// Records files can be attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentEntity {
+    Company,
+    Contact,
+    Product,
+    Vendor,
+    PurchaseOrder,
+    JournalEntry,
+}
+
+impl AttachmentEntity {
+    pub const ALL: [AttachmentEntity; 6] = [
+        AttachmentEntity::Company,
+        AttachmentEntity::Contact,
+        AttachmentEntity::Product,
+        AttachmentEntity::Vendor,
+        AttachmentEntity::PurchaseOrder,
+        AttachmentEntity::JournalEntry,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttachmentEntity::Company => "company",
+            AttachmentEntity::Contact => "contact",
+            AttachmentEntity::Product => "product",
+            AttachmentEntity::Vendor => "vendor",
+            AttachmentEntity::PurchaseOrder => "purchase_order",
+            AttachmentEntity::JournalEntry => "journal_entry",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.as_str() == s)
+    }
 }
 
 /// File attachment
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Attachment {
     pub id: Uuid,
+    pub entity_type: AttachmentEntity,
+    pub entity_id: Uuid,
     pub filename: String,
+    /// Detected from the file contents, not taken from the upload
     pub content_type: String,
     pub size: u64,
+    /// Hex SHA-256 of the contents
+    pub sha256: String,
+    /// Signed download link, valid without a bearer token until `url_expires_at`
     pub url: String,
+    pub url_expires_at: DateTime<Utc>,
+    pub uploaded_by: Uuid,
     pub uploaded_at: DateTime<Utc>,
 }
 
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+/// Tenant overrides for the look of outgoing emails; unset fields use the defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Validate, ToSchema)]
+pub struct EmailBranding {
+    /// Absolute URL of a logo shown in the email header
+    #[validate(url, length(max = 2048))]
+    pub logo_url: Option<String>,
+
+    /// Header and button colour, `#RRGGBB`
+    #[validate(custom(function = "hex_color"))]
+    pub primary_color: Option<String>,
+
+    /// Page background colour, `#RRGGBB`
+    #[validate(custom(function = "hex_color"))]
+    pub background_color: Option<String>,
+
+    /// Plain text shown at the bottom of every email, e.g. the company address
+    #[validate(length(max = 500))]
+    pub footer: Option<String>,
+}
+
+fn hex_color(value: &str) -> Result<(), ValidationError> {
+    let valid = value.len() == 7
+        && value.starts_with('#')
+        && value[1..].bytes().all(|b| b.is_ascii_hexdigit());
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("hex_color"))
+    }
+}
+
+/// An email template and the variables it takes
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EmailTemplateInfo {
+    pub name: String,
+    pub locales: Vec<String>,
+    /// Variables every render must provide
+    pub required: Vec<String>,
+    /// Variables the template uses when given
+    pub optional: Vec<String>,
+}
+
+/// Render a template with the current tenant's branding without sending it
+#[derive(Debug, Clone, Default, Deserialize, Validate, ToSchema)]
+pub struct PreviewEmailRequest {
+    /// `en` or `id`; defaults to the tenant's language
+    pub locale: Option<String>,
+    /// Template variables; sample values are used when omitted
+    #[schema(value_type = Option<Object>)]
+    pub variables: Option<Map<String, Value>>,
+    /// Unsaved branding to try out instead of the tenant's
+    #[validate(nested)]
+    pub branding: Option<EmailBranding>,
+}
+
+/// A rendered email
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
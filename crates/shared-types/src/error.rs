@@ -62,6 +62,10 @@ impl ApiError {
     pub fn service_unavailable(message: &str) -> Self {
         Self::new("SERVICE_UNAVAILABLE", message)
     }
+
+    pub fn payload_too_large(message: &str) -> Self {
+        Self::new("PAYLOAD_TOO_LARGE", message)
+    }
 }
 
 /// Validation error details
@@ -0,0 +1,60 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LeaveRequest {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    /// The user taking the leave
+    pub user_id: Uuid,
+    pub leave_type: LeaveType,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub reason: Option<String>,
+    pub status: LeaveStatus,
+    pub reviewed_by: Option<Uuid>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaveType {
+    Annual,
+    Sick,
+    Unpaid,
+    Other,
+}
+
+impl LeaveType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LeaveType::Annual => "annual",
+            LeaveType::Sick => "sick",
+            LeaveType::Unpaid => "unpaid",
+            LeaveType::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaveStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Deserialize, Validate, ToSchema)]
+pub struct CreateLeaveRequest {
+    pub leave_type: LeaveType,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    #[validate(length(max = 1000))]
+    pub reason: Option<String>,
+}
@@ -0,0 +1,311 @@
+//! Translated API messages.
+//!
+//! Messages are looked up by code in one catalog with a column per
+//! [`Locale`]: business error codes such as `INVALID_CREDENTIALS` (the codes
+//! of `BusinessErrorType` and `DomainError`), and `validation.*` codes for
+//! `validator` failures. Placeholders such as `{min}` are filled from the
+//! error's parameters. Unknown codes fall back to the code itself.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+use validator::{ValidationErrors, ValidationErrorsKind};
+
+use crate::common::ApiResponse;
+use crate::error::{BusinessErrorType, ValidationError};
+
+/// Language of messages and emails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    En,
+    Id,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::Id];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Id => "id",
+        }
+    }
+
+    /// Accepts language tags such as `id`, `id-ID` or `en_US`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let language = s.split(['-', '_']).next().unwrap_or_default().trim();
+        Self::ALL.into_iter().find(|l| l.as_str().eq_ignore_ascii_case(language))
+    }
+
+    /// The supported language a client prefers most in an `Accept-Language`
+    /// header such as `id-ID,id;q=0.9,en;q=0.8`.
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut ranges: Vec<(f32, Locale)> = header
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let locale = Locale::parse(parts.next()?)?;
+                let quality = parts
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                (quality > 0.0).then_some((quality, locale))
+            })
+            .collect();
+        // Stable, so equal weights keep the client's order
+        ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+        ranges.first().map(|(_, locale)| *locale)
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Code, English, Indonesian
+static CATALOG: &[(&str, &str, &str)] = &[
+    // Auth
+    ("INVALID_CREDENTIALS", "Invalid email or password", "Email atau kata sandi salah"),
+    ("ACCOUNT_LOCKED", "This account is locked", "Akun ini terkunci"),
+    ("EMAIL_NOT_VERIFIED", "This email address has not been verified", "Alamat email ini belum diverifikasi"),
+    ("TOKEN_EXPIRED", "Your session has expired; please log in again", "Sesi Anda telah berakhir; silakan masuk kembali"),
+    ("TOKEN_INVALID", "Invalid or expired token", "Token tidak valid atau sudah kedaluwarsa"),
+    ("MALFORMED_TOKEN", "Invalid or expired token", "Token tidak valid atau sudah kedaluwarsa"),
+    ("INSUFFICIENT_PERMISSIONS", "You do not have permission to do this", "Anda tidak memiliki izin untuk melakukan ini"),
+    // Tenants and users
+    ("TENANT_NOT_FOUND", "Company not found", "Perusahaan tidak ditemukan"),
+    ("TENANT_INACTIVE", "This company account is inactive", "Akun perusahaan ini tidak aktif"),
+    ("TENANT_SLUG_TAKEN", "This company address is already taken", "Alamat perusahaan ini sudah digunakan"),
+    ("USER_NOT_FOUND", "User not found", "Pengguna tidak ditemukan"),
+    ("USER_ALREADY_EXISTS", "An account with this email already exists", "Akun dengan email ini sudah terdaftar"),
+    ("USER_INACTIVE", "This user is inactive", "Pengguna ini tidak aktif"),
+    ("INVALID_INVITATION", "This invitation is invalid, expired or already used", "Undangan ini tidak valid, kedaluwarsa, atau sudah digunakan"),
+    // Business rules
+    ("INSUFFICIENT_STOCK", "Not enough stock", "Stok tidak mencukupi"),
+    ("INVALID_STATUS_TRANSITION", "This action is not allowed in the document's current status", "Tindakan ini tidak diizinkan pada status dokumen saat ini"),
+    ("UNBALANCED_JOURNAL_ENTRY", "Total debits must equal total credits", "Total debit harus sama dengan total kredit"),
+    ("DUPLICATE_ENTRY", "This data already exists", "Data ini sudah ada"),
+    ("REFERENCED_BY_OTHER_ENTITY", "This data is still used elsewhere and cannot be deleted", "Data ini masih digunakan di tempat lain dan tidak dapat dihapus"),
+    ("VALIDATION_FAILED", "Some fields are invalid", "Beberapa isian tidak valid"),
+    ("NOT_FOUND", "Not found", "Data tidak ditemukan"),
+    ("CONFLICT", "This conflicts with existing data", "Bertentangan dengan data yang sudah ada"),
+    // Services
+    ("EMAIL_SERVICE_UNAVAILABLE", "Email cannot be sent right now; please try again later", "Email tidak dapat dikirim saat ini; silakan coba lagi nanti"),
+    ("PAYMENT_SERVICE_UNAVAILABLE", "Payments are unavailable right now; please try again later", "Layanan pembayaran tidak tersedia saat ini; silakan coba lagi nanti"),
+    ("RATE_LIMIT_EXCEEDED", "Too many requests; please wait a moment", "Terlalu banyak permintaan; silakan tunggu sebentar"),
+    ("INTERNAL_ERROR", "Something went wrong; please try again", "Terjadi kesalahan; silakan coba lagi"),
+    ("UNKNOWN_ERROR", "Something went wrong; please try again", "Terjadi kesalahan; silakan coba lagi"),
+    // Fields; shown next to the field, so they leave out its name
+    ("validation.required", "is required", "wajib diisi"),
+    ("validation.email", "must be a valid email address", "harus berupa alamat email yang valid"),
+    ("validation.url", "must be a valid URL", "harus berupa URL yang valid"),
+    ("validation.length.min", "must be at least {min} characters", "minimal {min} karakter"),
+    ("validation.length.max", "must be at most {max} characters", "maksimal {max} karakter"),
+    ("validation.length.between", "must be {min} to {max} characters", "harus {min} sampai {max} karakter"),
+    ("validation.length.equal", "must be exactly {equal} characters", "harus tepat {equal} karakter"),
+    ("validation.count.min", "must have at least {min} items", "minimal {min} item"),
+    ("validation.count.max", "must have at most {max} items", "maksimal {max} item"),
+    ("validation.count.between", "must have {min} to {max} items", "harus {min} sampai {max} item"),
+    ("validation.count.equal", "must have exactly {equal} items", "harus tepat {equal} item"),
+    ("validation.range.min", "must be at least {min}", "minimal {min}"),
+    ("validation.range.max", "must be at most {max}", "maksimal {max}"),
+    ("validation.range.between", "must be between {min} and {max}", "harus antara {min} dan {max}"),
+    ("validation.must_match", "must match {other}", "harus sama dengan {other}"),
+    ("validation.regex", "has an invalid format", "formatnya tidak valid"),
+    ("validation.hex_color", "must be a colour such as #1A2B3C", "harus berupa warna seperti #1A2B3C"),
+    ("validation.invalid", "is invalid", "tidak valid"),
+];
+
+/// The catalog message for `code`, if there is one.
+pub fn lookup(code: &str, locale: Locale) -> Option<&'static str> {
+    CATALOG.iter().find(|(c, _, _)| *c == code).map(|(_, en, id)| match locale {
+        Locale::En => *en,
+        Locale::Id => *id,
+    })
+}
+
+/// The message for `code` with `{name}` placeholders filled from `args`; the
+/// code itself when the catalog has no entry.
+pub fn translate(code: &str, locale: Locale, args: &[(&str, String)]) -> String {
+    let Some(message) = lookup(code, locale) else {
+        return code.to_string();
+    };
+    args.iter().fold(message.to_string(), |message, (name, value)| message.replace(&format!("{{{}}}", name), value))
+}
+
+impl BusinessErrorType {
+    pub fn message(&self, locale: Locale) -> String {
+        translate(self.as_str(), locale, &[])
+    }
+}
+
+/// Per-field errors of a failed `validate()`, translated. Nested fields are
+/// named by path, e.g. `lines[0].account_id` or `branding.logo_url`.
+pub fn validation_errors(errors: &ValidationErrors, locale: Locale) -> Vec<ValidationError> {
+    let mut out = Vec::new();
+    collect(errors, "", locale, &mut out);
+    out.sort_by(|a, b| a.field.cmp(&b.field));
+    out
+}
+
+fn collect(errors: &ValidationErrors, prefix: &str, locale: Locale, out: &mut Vec<ValidationError>) {
+    let path = |field: &str| if prefix.is_empty() { field.to_string() } else { format!("{}.{}", prefix, field) };
+    for (field, kind) in errors.errors() {
+        match kind {
+            ValidationErrorsKind::Field(errors) => out.extend(errors.iter().map(|error| ValidationError {
+                field: path(field),
+                message: field_message(error, locale),
+                code: error.code.to_string(),
+            })),
+            ValidationErrorsKind::Struct(errors) => collect(errors, &path(field), locale, out),
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    collect(errors, &format!("{}[{}]", path(field), index), locale, out);
+                }
+            }
+        }
+    }
+}
+
+fn field_message(error: &validator::ValidationError, locale: Locale) -> String {
+    let param = |name: &str| error.params.get(name).map(param_text);
+    let bounds = |family: &str| {
+        let key = match (param("equal"), param("min"), param("max")) {
+            (Some(_), _, _) => "equal",
+            (None, Some(_), Some(_)) => "between",
+            (None, Some(_), None) => "min",
+            _ => "max",
+        };
+        let args: Vec<(&str, String)> =
+            ["equal", "min", "max"].into_iter().filter_map(|name| Some((name, param(name)?))).collect();
+        translate(&format!("validation.{}.{}", family, key), locale, &args)
+    };
+    match error.code.as_ref() {
+        // `length` also checks lists, whose value is an array
+        "length" if matches!(error.params.get("value"), Some(Value::Array(_))) => bounds("count"),
+        "length" => bounds("length"),
+        "range" => bounds("range"),
+        "must_match" => translate("validation.must_match", locale, &[("other", param("other").unwrap_or_default())]),
+        code => match lookup(&format!("validation.{}", code), locale) {
+            Some(message) => message.to_string(),
+            None => match (&error.message, locale) {
+                // Custom messages are written in English
+                (Some(message), Locale::En) => message.to_string(),
+                _ => translate("validation.invalid", locale, &[]),
+            },
+        },
+    }
+}
+
+fn param_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+impl<T> ApiResponse<T> {
+    /// `VALIDATION_FAILED` with the translated errors of each field.
+    pub fn invalid(errors: &ValidationErrors, locale: Locale) -> Self {
+        Self::validation_error_typed(translate("VALIDATION_FAILED", locale, &[]), validation_errors(errors, locale))
+    }
+
+    /// An error for a business code such as `INVALID_CREDENTIALS`, translated
+    /// and with the code attached; other messages pass through unchanged.
+    pub fn business_error(message: &str, locale: Locale) -> Self {
+        let response = Self::error_typed(translate(message, locale, &[]));
+        match lookup(message, locale) {
+            Some(_) => response.with_code(message),
+            None => response,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use validator::Validate;
+
+    #[derive(Serialize, Validate)]
+    struct Line {
+        #[validate(range(min = 1, max = 10))]
+        quantity: u32,
+    }
+
+    #[derive(Validate)]
+    struct Form {
+        #[validate(length(min = 2, max = 5))]
+        code: String,
+        #[validate(email)]
+        email: String,
+        #[validate(length(min = 1), nested)]
+        lines: Vec<Line>,
+    }
+
+    #[test]
+    fn accept_language_takes_the_most_preferred_supported_language() {
+        assert_eq!(Locale::from_accept_language("id-ID,id;q=0.9,en;q=0.8"), Some(Locale::Id));
+        assert_eq!(Locale::from_accept_language("fr-FR, en;q=0.5, id;q=0.7"), Some(Locale::Id));
+        assert_eq!(Locale::from_accept_language("en, id"), Some(Locale::En));
+        assert_eq!(Locale::from_accept_language("id;q=0, fr"), None);
+        assert_eq!(Locale::from_accept_language(""), None);
+    }
+
+    #[test]
+    fn every_entry_is_translated_with_the_same_placeholders() {
+        let placeholders = |s: &str| {
+            let mut names: Vec<String> = s.split('{').skip(1).filter_map(|p| p.split_once('}')).map(|(n, _)| n.to_string()).collect();
+            names.sort();
+            names
+        };
+        for (code, en, id) in CATALOG {
+            assert!(!en.is_empty() && !id.is_empty(), "{}", code);
+            assert_eq!(placeholders(en), placeholders(id), "{}", code);
+        }
+    }
+
+    #[test]
+    fn business_codes_have_messages() {
+        let codes = [
+            BusinessErrorType::InvalidCredentials,
+            BusinessErrorType::InsufficientStock,
+            BusinessErrorType::InvalidStatusTransition,
+            BusinessErrorType::RateLimitExceeded,
+            BusinessErrorType::UnknownError,
+        ];
+        for code in codes {
+            assert!(lookup(code.as_str(), Locale::Id).is_some(), "{}", code.as_str());
+        }
+        assert_eq!(BusinessErrorType::InsufficientStock.message(Locale::Id), "Stok tidak mencukupi");
+
+        let response = ApiResponse::<()>::business_error("INVALID_CREDENTIALS", Locale::Id);
+        assert_eq!(response.message.as_deref(), Some("Email atau kata sandi salah"));
+        assert_eq!(response.code.as_deref(), Some("INVALID_CREDENTIALS"));
+        let response = ApiResponse::<()>::business_error("connection refused", Locale::Id);
+        assert_eq!((response.message.as_deref(), response.code), (Some("connection refused"), None));
+    }
+
+    #[test]
+    fn validation_errors_are_per_field_and_translated() {
+        let form = Form { code: "X".into(), email: "nope".into(), lines: vec![Line { quantity: 1 }, Line { quantity: 0 }] };
+        let errors = validation_errors(&form.validate().unwrap_err(), Locale::Id);
+        let fields: Vec<(&str, &str, &str)> =
+            errors.iter().map(|e| (e.field.as_str(), e.code.as_str(), e.message.as_str())).collect();
+        assert_eq!(
+            fields,
+            [
+                ("code", "length", "harus 2 sampai 5 karakter"),
+                ("email", "email", "harus berupa alamat email yang valid"),
+                ("lines[1].quantity", "range", "harus antara 1 dan 10"),
+            ]
+        );
+
+        let empty = Form { code: "AB".into(), email: "a@b.co".into(), lines: vec![] };
+        let errors = validation_errors(&empty.validate().unwrap_err(), Locale::En);
+        assert_eq!(errors[0].message, "must have at least 1 items");
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Master data that can be bulk imported from CSV/XLSX
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportEntity {
+    Products,
+    Companies,
+    Contacts,
+    Vendors,
+    Accounts,
+    OpeningStock,
+    ExchangeRates,
+}
+
+impl ImportEntity {
+    pub const ALL: [ImportEntity; 7] = [
+        ImportEntity::Products,
+        ImportEntity::Companies,
+        ImportEntity::Contacts,
+        ImportEntity::Vendors,
+        ImportEntity::Accounts,
+        ImportEntity::OpeningStock,
+        ImportEntity::ExchangeRates,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportEntity::Products => "products",
+            ImportEntity::Companies => "companies",
+            ImportEntity::Contacts => "contacts",
+            ImportEntity::Vendors => "vendors",
+            ImportEntity::Accounts => "accounts",
+            ImportEntity::OpeningStock => "opening_stock",
+            ImportEntity::ExchangeRates => "exchange_rates",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.as_str() == s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Validated, // Dry run finished, waiting for commit
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl ImportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportStatus::Validated => "validated",
+            ImportStatus::Queued => "queued",
+            ImportStatus::Running => "running",
+            ImportStatus::Completed => "completed",
+            ImportStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "queued" => ImportStatus::Queued,
+            "running" => ImportStatus::Running,
+            "completed" => ImportStatus::Completed,
+            "failed" => ImportStatus::Failed,
+            _ => ImportStatus::Validated,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImportJob {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub entity: ImportEntity,
+    pub file_name: String,
+    pub file_format: String, // csv, xlsx
+    /// Target field -> source column header
+    pub column_mapping: BTreeMap<String, String>,
+    pub status: ImportStatus,
+    pub total_rows: i32,
+    pub error_rows: i32,
+    pub imported_rows: i32,
+    /// First row errors; the full list is served as a CSV report
+    pub errors: Option<Vec<ImportRowError>>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ImportRowError {
+    /// Row number in the uploaded sheet, counting the header as row 1
+    pub row: u32,
+    pub column: Option<String>,
+    pub value: Option<String>,
+    pub message: String,
+}
+
+/// Result of uploading a file: the import job plus what a mapping UI needs
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImportPreview {
+    pub job: ImportJob,
+    /// First valid rows as they will be imported
+    #[schema(value_type = Vec<Object>)]
+    pub sample: Vec<JsonValue>,
+    /// Columns in the file that no field was mapped to
+    pub unmapped_columns: Vec<String>,
+    pub fields: Vec<ImportField>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImportField {
+    pub name: String,
+    pub required: bool,
+    pub description: String,
+}
@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::procurement::Vendor;
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Product {
     pub id: Uuid,
@@ -14,15 +16,19 @@ pub struct Product {
     pub category_id: Option<Uuid>,
     pub category: Option<Category>,
     pub unit_of_measure: String,
+    #[schema(value_type = String)]
     pub cost_price: Decimal,
+    #[schema(value_type = String)]
     pub selling_price: Decimal,
     pub minimum_stock: i32,
     pub current_stock: i32,
     pub status: ProductStatus,
     pub barcode: Option<String>,
+    #[schema(value_type = Option<String>)]
     pub weight: Option<Decimal>,
     pub dimensions: Option<serde_json::Value>,
     pub supplier_id: Option<Uuid>,
+    pub supplier: Option<Vendor>,
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -30,6 +36,7 @@ pub struct Product {
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type), sqlx(type_name = "varchar", rename_all = "snake_case"))]
 pub enum ProductStatus {
     Active,
     Inactive,
@@ -73,6 +80,7 @@ pub struct StockMovement {
     pub warehouse: Option<Warehouse>,
     pub movement_type: StockMovementType,
     pub quantity: i32,
+    #[schema(value_type = Option<String>)]
     pub unit_cost: Option<Decimal>,
     pub reference_type: Option<String>, // purchase_order, sales_order, adjustment, etc.
     pub reference_id: Option<Uuid>,
@@ -81,7 +89,7 @@ pub struct StockMovement {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum StockMovementType {
     In,      // Stock masuk
@@ -116,10 +124,13 @@ pub struct CreateProductRequest {
     pub description: Option<String>,
     pub category_id: Option<Uuid>,
     pub unit_of_measure: String,
+    #[schema(value_type = String)]
     pub cost_price: Decimal,
+    #[schema(value_type = String)]
     pub selling_price: Decimal,
     pub minimum_stock: i32,
     pub barcode: Option<String>,
+    #[schema(value_type = Option<String>)]
     pub weight: Option<Decimal>,
     pub dimensions: Option<serde_json::Value>,
     pub supplier_id: Option<Uuid>,
@@ -132,11 +143,14 @@ pub struct UpdateProductRequest {
     pub description: Option<String>,
     pub category_id: Option<Uuid>,
     pub unit_of_measure: Option<String>,
+    #[schema(value_type = Option<String>)]
     pub cost_price: Option<Decimal>,
+    #[schema(value_type = Option<String>)]
     pub selling_price: Option<Decimal>,
     pub minimum_stock: Option<i32>,
     pub status: Option<ProductStatus>,
     pub barcode: Option<String>,
+    #[schema(value_type = Option<String>)]
     pub weight: Option<Decimal>,
     pub dimensions: Option<serde_json::Value>,
     pub supplier_id: Option<Uuid>,
@@ -183,6 +197,7 @@ pub struct CreateStockMovementRequest {
     pub warehouse_id: Uuid,
     pub movement_type: StockMovementType,
     pub quantity: i32,
+    #[schema(value_type = Option<String>)]
     pub unit_cost: Option<Decimal>,
     pub reference_type: Option<String>,
     pub reference_id: Option<Uuid>,
@@ -206,7 +221,9 @@ pub struct InventoryValuation {
     pub warehouse_id: Uuid,
     pub warehouse: Warehouse,
     pub quantity_on_hand: i32,
+    #[schema(value_type = String)]
     pub unit_cost: Decimal,
+    #[schema(value_type = String)]
     pub total_value: Decimal,
 }
 
@@ -1,17 +1,34 @@
 pub mod auth;
 pub mod common;
 pub mod error;
+pub mod i18n;
+pub mod money;
+pub mod schema;
 
 pub mod crm;
 pub mod accounting;
 pub mod inventory;
 pub mod procurement;
-// pub mod hrm;
+pub mod imports;
+pub mod search;
+pub mod notification;
+pub mod hrm;
+pub mod email;
+pub mod sequence;
 
 pub use auth::*;
 pub use common::*;
 pub use error::*;
+pub use i18n::Locale;
+pub use money::*;
+pub use schema::DataSchema;
 pub use crm::*;
 pub use accounting::*;
 pub use inventory::*;
 pub use procurement::*;
+pub use imports::*;
+pub use search::*;
+pub use notification::*;
+pub use hrm::*;
+pub use email::*;
+pub use sequence::*;
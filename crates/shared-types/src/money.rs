@@ -0,0 +1,479 @@
+//! Currency-aware money amounts.
+//!
+//! A [`Money`] is a [`Decimal`] amount tagged with its ISO 4217 [`Currency`] and
+//! always held at that currency's precision: `IDR 1500000`, `USD 12.50`,
+//! `JPY 980`. Results of multiplication are rounded with banker's rounding
+//! unless a [`RoundingMode`] says otherwise, and arithmetic across currencies is
+//! an error rather than a silent conversion.
+//!
+//! In JSON a money value is `{"amount": "12.50", "currency": "USD"}`; the amount
+//! is a string so no client parses it into a float. In the database an amount
+//! is a `DECIMAL(15,2)` column next to the row's `currency` column: read with
+//! `Money::new(row.get("total_amount"), row.get("currency"))` and bind
+//! [`Money::amount`].
+
+use std::{cmp::Ordering, fmt, ops::Neg, str::FromStr};
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use utoipa::{
+    openapi::{ObjectBuilder, RefOr, Schema, SchemaType},
+    ToSchema,
+};
+
+/// ISO 4217 codes whose amounts have no minor unit. IDR is listed here
+/// although ISO 4217 gives it two decimals: rupiah cents are not used in
+/// practice and Indonesian invoices and tax documents carry whole rupiah.
+const ZERO_DECIMAL: &[&str] = &[
+    "BIF", "CLP", "DJF", "GNF", "IDR", "ISK", "JPY", "KMF", "KRW", "PYG", "RWF", "UGX", "VND", "VUV", "XAF", "XOF",
+    "XPF",
+];
+
+/// ISO 4217 codes with two decimals. Currencies with three (BHD, KWD, ...) are
+/// left out because amount columns are `DECIMAL(15,2)`.
+const TWO_DECIMAL: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT", "BGN", "BMD", "BND",
+    "BOB", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD", "CAD", "CDF", "CHF", "CNY", "COP", "CRC", "CUP", "CVE", "CZK",
+    "DKK", "DOP", "DZD", "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS", "GIP", "GMD", "GTQ", "GYD",
+    "HKD", "HNL", "HTG", "HUF", "ILS", "INR", "IRR", "JMD", "KES", "KGS", "KHR", "KPW", "KYD", "KZT", "LAK", "LBP",
+    "LKR", "LRD", "LSL", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MYR",
+    "MZN", "NAD", "NGN", "NIO", "NOK", "NPR", "NZD", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "QAR", "RON", "RSD",
+    "RUB", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD", "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL",
+    "THB", "TJS", "TMT", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "USD", "UYU", "UZS", "VES", "WST", "XCD", "YER",
+    "ZAR", "ZMW", "ZWL",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoneyError {
+    UnknownCurrency(String),
+    CurrencyMismatch { expected: Currency, found: Currency },
+    /// More decimals than the currency has, e.g. `IDR 10.50`
+    TooPrecise { amount: Decimal, currency: Currency },
+    Overflow,
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoneyError::UnknownCurrency(code) => write!(f, "'{}' is not a supported ISO 4217 currency code", code),
+            MoneyError::CurrencyMismatch { expected, found } => {
+                write!(f, "cannot combine {} with {} amounts", expected, found)
+            }
+            MoneyError::TooPrecise { amount, currency } => write!(
+                f,
+                "{} has more than {} decimal place(s), the precision of {}",
+                amount,
+                currency.minor_units(),
+                currency
+            ),
+            MoneyError::Overflow => write!(f, "amount out of range"),
+        }
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+/// An ISO 4217 currency and its number of decimals.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Currency {
+    code: &'static str,
+    minor_units: u32,
+}
+
+impl Currency {
+    pub const IDR: Currency = Currency { code: "IDR", minor_units: 0 };
+    pub const USD: Currency = Currency { code: "USD", minor_units: 2 };
+    pub const EUR: Currency = Currency { code: "EUR", minor_units: 2 };
+    pub const SGD: Currency = Currency { code: "SGD", minor_units: 2 };
+    pub const JPY: Currency = Currency { code: "JPY", minor_units: 0 };
+
+    /// The currency for a code such as `usd` or `IDR`.
+    pub fn parse(code: &str) -> Option<Currency> {
+        let code = code.trim().to_ascii_uppercase();
+        let find = |codes: &'static [&'static str]| codes.iter().find(|c| **c == code).copied();
+        find(ZERO_DECIMAL)
+            .map(|code| Currency { code, minor_units: 0 })
+            .or_else(|| find(TWO_DECIMAL).map(|code| Currency { code, minor_units: 2 }))
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// Decimals of an amount: 0 for IDR and JPY, 2 for USD
+    pub fn minor_units(&self) -> u32 {
+        self.minor_units
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code)
+    }
+}
+
+impl fmt::Debug for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code)
+    }
+}
+
+impl FromStr for Currency {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Currency::parse(s).ok_or_else(|| MoneyError::UnknownCurrency(s.to_string()))
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code)
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        code.parse().map_err(de::Error::custom)
+    }
+}
+
+impl<'s> ToSchema<'s> for Currency {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        let schema = ObjectBuilder::new()
+            .schema_type(SchemaType::String)
+            .description(Some("ISO 4217 currency code"))
+            .example(Some(serde_json::json!("IDR")))
+            .min_length(Some(3))
+            .max_length(Some(3));
+        ("Currency", schema.into())
+    }
+}
+
+/// How to round an amount to its currency's precision.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// Halves go to the even neighbour (banker's rounding): 2.5 → 2, 3.5 → 4
+    #[default]
+    HalfEven,
+    /// Halves go away from zero: 2.5 → 3, -2.5 → -3
+    HalfUp,
+    /// Halves go towards zero: 2.5 → 2
+    HalfDown,
+    /// Towards zero (truncate)
+    Down,
+    /// Away from zero
+    Up,
+    /// Towards negative infinity
+    Floor,
+    /// Towards positive infinity
+    Ceiling,
+}
+
+impl RoundingMode {
+    fn strategy(self) -> RoundingStrategy {
+        match self {
+            RoundingMode::HalfEven => RoundingStrategy::MidpointNearestEven,
+            RoundingMode::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+            RoundingMode::HalfDown => RoundingStrategy::MidpointTowardZero,
+            RoundingMode::Down => RoundingStrategy::ToZero,
+            RoundingMode::Up => RoundingStrategy::AwayFromZero,
+            RoundingMode::Floor => RoundingStrategy::ToNegativeInfinity,
+            RoundingMode::Ceiling => RoundingStrategy::ToPositiveInfinity,
+        }
+    }
+}
+
+/// An amount in a currency, at the currency's precision.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+pub struct Money {
+    /// Decimal string with the currency's number of decimals, e.g. `"1500000"` or `"12.50"`
+    #[schema(value_type = String, example = "1500000")]
+    amount: Decimal,
+    currency: Currency,
+}
+
+impl Money {
+    /// `amount` rounded to `currency` with banker's rounding.
+    pub fn new(amount: Decimal, currency: Currency) -> Money {
+        Money::rounded(amount, currency, RoundingMode::HalfEven)
+    }
+
+    pub fn rounded(amount: Decimal, currency: Currency, mode: RoundingMode) -> Money {
+        let mut amount = amount.round_dp_with_strategy(currency.minor_units, mode.strategy());
+        amount.rescale(currency.minor_units);
+        Money { amount, currency }
+    }
+
+    /// `amount` as given, or an error if rounding would change it. For amounts
+    /// entered by users, where `IDR 10.50` is a mistake rather than a rounding case.
+    pub fn exact(amount: Decimal, currency: Currency) -> Result<Money, MoneyError> {
+        let money = Money::new(amount, currency);
+        if money.amount == amount {
+            Ok(money)
+        } else {
+            Err(MoneyError::TooPrecise { amount, currency })
+        }
+    }
+
+    pub fn zero(currency: Currency) -> Money {
+        Money::new(Decimal::ZERO, currency)
+    }
+
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amount.is_zero()
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.amount.is_sign_positive() && !self.amount.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.amount.is_sign_negative() && !self.amount.is_zero()
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(&other)?;
+        let amount = self.amount.checked_add(other.amount).ok_or(MoneyError::Overflow)?;
+        Ok(Money { amount, currency: self.currency })
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(&other)?;
+        let amount = self.amount.checked_sub(other.amount).ok_or(MoneyError::Overflow)?;
+        Ok(Money { amount, currency: self.currency })
+    }
+
+    /// `self × factor`, e.g. a unit price times a quantity, with banker's rounding.
+    pub fn times(self, factor: Decimal) -> Result<Money, MoneyError> {
+        self.times_rounded(factor, RoundingMode::HalfEven)
+    }
+
+    pub fn times_rounded(self, factor: Decimal, mode: RoundingMode) -> Result<Money, MoneyError> {
+        let amount = self.amount.checked_mul(factor).ok_or(MoneyError::Overflow)?;
+        Ok(Money::rounded(amount, self.currency, mode))
+    }
+
+    /// `percent`% of `self`, e.g. a discount or tax, with banker's rounding.
+    pub fn percent(self, percent: Decimal) -> Result<Money, MoneyError> {
+        let amount = self.amount.checked_mul(percent).ok_or(MoneyError::Overflow)? / Decimal::ONE_HUNDRED;
+        Ok(Money::new(amount, self.currency))
+    }
+
+    /// Total of `amounts`, all of which must be in `currency`.
+    pub fn sum(currency: Currency, amounts: impl IntoIterator<Item = Money>) -> Result<Money, MoneyError> {
+        amounts.into_iter().try_fold(Money::zero(currency), Money::checked_add)
+    }
+
+    /// `self` in `to`, at `rate` units of `to` per unit of `self`, with banker's rounding.
+    pub fn convert(self, to: Currency, rate: Decimal) -> Result<Money, MoneyError> {
+        let amount = self.amount.checked_mul(rate).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount, to))
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<(), MoneyError> {
+        if self.currency == other.currency {
+            Ok(())
+        } else {
+            Err(MoneyError::CurrencyMismatch { expected: self.currency, found: other.currency })
+        }
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money { amount: -self.amount, currency: self.currency }
+    }
+}
+
+/// Amounts in different currencies are unordered.
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Money) -> Option<Ordering> {
+        (self.currency == other.currency).then(|| self.amount.cmp(&other.amount))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.currency, self.amount)
+    }
+}
+
+impl fmt::Debug for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Money({} {})", self.currency, self.amount)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct MoneyRepr {
+    amount: String,
+    currency: Currency,
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MoneyRepr { amount: self.amount.to_string(), currency: self.currency }.serialize(serializer)
+    }
+}
+
+/// Accepts string amounts only, at no more than the currency's precision.
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = MoneyRepr::deserialize(deserializer)?;
+        let amount = Decimal::from_str_exact(repr.amount.trim())
+            .map_err(|_| de::Error::custom(format!("'{}' is not a decimal amount", repr.amount)))?;
+        Money::exact(amount, repr.currency).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(feature = "sqlx")]
+mod db {
+    use sqlx::{
+        encode::IsNull,
+        error::BoxDynError,
+        postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+        Decode, Encode, Postgres, Type,
+    };
+
+    use super::Currency;
+
+    /// Stored as its code in `VARCHAR(3)` columns.
+    impl Type<Postgres> for Currency {
+        fn type_info() -> PgTypeInfo {
+            <&str as Type<Postgres>>::type_info()
+        }
+
+        fn compatible(ty: &PgTypeInfo) -> bool {
+            <&str as Type<Postgres>>::compatible(ty)
+        }
+    }
+
+    impl Encode<'_, Postgres> for Currency {
+        fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+            <&str as Encode<Postgres>>::encode_by_ref(&self.code, buf)
+        }
+    }
+
+    impl<'r> Decode<'r, Postgres> for Currency {
+        fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+            let code = <&str as Decode<Postgres>>::decode(value)?;
+            Ok(code.parse::<Currency>()?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::prelude::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn currencies_know_their_precision() {
+        assert_eq!(Currency::parse("idr"), Some(Currency::IDR));
+        assert_eq!(Currency::parse("USD").map(|c| c.minor_units()), Some(2));
+        assert_eq!(Currency::parse("JPY").map(|c| c.minor_units()), Some(0));
+        assert_eq!(Currency::parse("KWD"), None);
+        assert_eq!(Currency::parse("XYZ"), None);
+        assert_eq!("usd".parse::<Currency>(), Ok(Currency::USD));
+    }
+
+    #[test]
+    fn new_rounds_half_to_even_at_the_currency_precision() {
+        assert_eq!(Money::new(dec("2.5"), Currency::IDR).amount(), dec("2"));
+        assert_eq!(Money::new(dec("3.5"), Currency::IDR).amount(), dec("4"));
+        assert_eq!(Money::new(dec("0.125"), Currency::USD).amount(), dec("0.12"));
+        assert_eq!(Money::new(dec("0.135"), Currency::USD).amount(), dec("0.14"));
+        assert_eq!(Money::new(dec("12.5"), Currency::USD).to_string(), "USD 12.50");
+    }
+
+    #[test]
+    fn explicit_rounding_modes() {
+        let usd = |s, mode| Money::rounded(dec(s), Currency::USD, mode).amount();
+        assert_eq!(usd("0.125", RoundingMode::HalfUp), dec("0.13"));
+        assert_eq!(usd("0.125", RoundingMode::HalfDown), dec("0.12"));
+        assert_eq!(usd("0.129", RoundingMode::Down), dec("0.12"));
+        assert_eq!(usd("0.121", RoundingMode::Up), dec("0.13"));
+        assert_eq!(usd("-0.121", RoundingMode::Floor), dec("-0.13"));
+        assert_eq!(usd("-0.129", RoundingMode::Ceiling), dec("-0.12"));
+    }
+
+    #[test]
+    fn exact_rejects_amounts_finer_than_the_currency() {
+        assert!(Money::exact(dec("1500000"), Currency::IDR).is_ok());
+        assert!(Money::exact(dec("12.50"), Currency::USD).is_ok());
+        assert_eq!(
+            Money::exact(dec("10.50"), Currency::IDR),
+            Err(MoneyError::TooPrecise { amount: dec("10.50"), currency: Currency::IDR })
+        );
+    }
+
+    #[test]
+    fn arithmetic_refuses_to_mix_currencies() {
+        let idr = Money::new(dec("1000"), Currency::IDR);
+        let usd = Money::new(dec("1"), Currency::USD);
+        assert_eq!(
+            idr.checked_add(usd),
+            Err(MoneyError::CurrencyMismatch { expected: Currency::IDR, found: Currency::USD })
+        );
+        assert!(idr.checked_sub(usd).is_err());
+        assert!(Money::sum(Currency::IDR, [idr, usd]).is_err());
+        assert_eq!(idr.partial_cmp(&usd), None);
+        assert!(idr > Money::zero(Currency::IDR));
+    }
+
+    #[test]
+    fn line_math_rounds_each_step() {
+        let price = Money::exact(dec("19.99"), Currency::USD).unwrap();
+        let gross = price.times(Decimal::from(3)).unwrap();
+        assert_eq!(gross.amount(), dec("59.97"));
+        let tax = gross.percent(dec("11")).unwrap();
+        assert_eq!(tax.amount(), dec("6.60"));
+        assert_eq!(gross.checked_add(tax).unwrap().amount(), dec("66.57"));
+        assert_eq!(Money::sum(Currency::USD, [gross, tax]).unwrap(), gross.checked_add(tax).unwrap());
+        assert!((-tax).is_negative());
+    }
+
+    #[test]
+    fn convert_rounds_into_the_target_currency() {
+        let usd = Money::exact(dec("12.50"), Currency::USD).unwrap();
+        assert_eq!(usd.convert(Currency::IDR, dec("16250.5")).unwrap().to_string(), "IDR 203131");
+        let idr = Money::exact(dec("1000000"), Currency::IDR).unwrap();
+        assert_eq!(idr.convert(Currency::USD, dec("0.0000615")).unwrap().to_string(), "USD 61.50");
+    }
+
+    #[test]
+    fn serializes_amounts_as_strings() {
+        let money = Money::new(dec("12.5"), Currency::USD);
+        assert_eq!(serde_json::to_value(money).unwrap(), serde_json::json!({"amount": "12.50", "currency": "USD"}));
+        let idr = Money::new(dec("1500000.00"), Currency::IDR);
+        assert_eq!(serde_json::to_value(idr).unwrap()["amount"], "1500000");
+
+        let parsed: Money = serde_json::from_str(r#"{"amount": "12.5", "currency": "usd"}"#).unwrap();
+        assert_eq!(parsed, money);
+    }
+
+    #[test]
+    fn rejects_float_and_overly_precise_amounts() {
+        assert!(serde_json::from_str::<Money>(r#"{"amount": 12.5, "currency": "USD"}"#).is_err());
+        assert!(serde_json::from_str::<Money>(r#"{"amount": "10.50", "currency": "IDR"}"#).is_err());
+        assert!(serde_json::from_str::<Money>(r#"{"amount": "1", "currency": "XYZ"}"#).is_err());
+        assert!(serde_json::from_str::<Money>(r#"{"amount": "abc", "currency": "USD"}"#).is_err());
+    }
+}
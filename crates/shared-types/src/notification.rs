@@ -0,0 +1,73 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// What a notification is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// A product dropped to or below its minimum stock
+    LowStock,
+    /// A purchase order was submitted and waits for approval
+    PurchaseOrderApproval,
+    /// A leave request waits for approval
+    LeaveRequest,
+    /// Someone accepted an invitation to the tenant
+    InvitationAccepted,
+}
+
+impl NotificationKind {
+    pub const ALL: [NotificationKind; 4] = [
+        NotificationKind::LowStock,
+        NotificationKind::PurchaseOrderApproval,
+        NotificationKind::LeaveRequest,
+        NotificationKind::InvitationAccepted,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::LowStock => "low_stock",
+            NotificationKind::PurchaseOrderApproval => "purchase_order_approval",
+            NotificationKind::LeaveRequest => "leave_request",
+            NotificationKind::InvitationAccepted => "invitation_accepted",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == s)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Notification {
+    pub id: Uuid,
+    pub tenant_id: Uuid,
+    pub user_id: Uuid,
+    pub kind: NotificationKind,
+    pub title: String,
+    pub body: String,
+    /// Ids of the records involved, e.g. `{"product_id": "..."}`
+    pub data: serde_json::Value,
+    pub read_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Channels a user receives one kind of notification on
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NotificationPreference {
+    pub kind: NotificationKind,
+    pub in_app: bool,
+    pub email: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UpdateNotificationPreferencesRequest {
+    /// Kinds not listed keep their current setting
+    pub preferences: Vec<NotificationPreference>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UnreadCount {
+    pub unread: i64,
+}
@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+use crate::money::{Currency, Money};
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Vendor {
     pub id: Uuid,
@@ -18,6 +20,7 @@ pub struct Vendor {
     pub payment_terms: Option<String>, // NET30, NET60, etc.
     pub currency: String,
     pub status: VendorStatus,
+    #[schema(value_type = Option<String>)]
     pub credit_limit: Option<Decimal>,
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
@@ -26,6 +29,7 @@ pub struct Vendor {
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type), sqlx(type_name = "varchar", rename_all = "snake_case"))]
 pub enum VendorStatus {
     Active,
     Inactive,
@@ -44,12 +48,13 @@ pub struct PurchaseOrder {
     pub expected_delivery_date: Option<NaiveDate>,
     pub delivery_address: Option<serde_json::Value>,
     pub status: PurchaseOrderStatus,
-    pub currency: String,
+    pub currency: Currency,
+    #[schema(value_type = String)]
     pub exchange_rate: Decimal,
-    pub subtotal: Decimal,
-    pub tax_amount: Decimal,
-    pub discount_amount: Decimal,
-    pub total_amount: Decimal,
+    pub subtotal: Money,
+    pub tax_amount: Money,
+    pub discount_amount: Money,
+    pub total_amount: Money,
     pub notes: Option<String>,
     pub terms_conditions: Option<String>,
     pub created_by: Uuid,
@@ -60,8 +65,9 @@ pub struct PurchaseOrder {
     pub items: Option<Vec<PurchaseOrderItem>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type), sqlx(type_name = "varchar", rename_all = "snake_case"))]
 pub enum PurchaseOrderStatus {
     Draft,
     Pending,
@@ -84,12 +90,14 @@ pub struct PurchaseOrderItem {
     pub description: Option<String>,
     pub quantity_ordered: i32,
     pub quantity_received: i32,
-    pub unit_price: Decimal,
+    pub unit_price: Money,
+    #[schema(value_type = String)]
     pub discount_percent: Decimal,
-    pub discount_amount: Decimal,
+    pub discount_amount: Money,
+    #[schema(value_type = String)]
     pub tax_percent: Decimal,
-    pub tax_amount: Decimal,
-    pub line_total: Decimal,
+    pub tax_amount: Money,
+    pub line_total: Money,
     pub line_number: i32,
     pub created_at: DateTime<Utc>,
 }
@@ -129,7 +137,9 @@ pub struct PurchaseReceiptItem {
     pub purchase_order_item_id: Uuid,
     pub product_id: Uuid,
     pub quantity_received: i32,
+    #[schema(value_type = String)]
     pub unit_cost: Decimal,
+    #[schema(value_type = String)]
     pub line_total: Decimal,
     pub quality_status: QualityStatus,
     pub notes: Option<String>,
@@ -155,14 +165,15 @@ pub struct VendorInvoice {
     pub purchase_order_id: Option<Uuid>,
     pub invoice_date: NaiveDate,
     pub due_date: NaiveDate,
-    pub currency: String,
+    pub currency: Currency,
+    #[schema(value_type = String)]
     pub exchange_rate: Decimal,
-    pub subtotal: Decimal,
-    pub tax_amount: Decimal,
-    pub discount_amount: Decimal,
-    pub total_amount: Decimal,
-    pub paid_amount: Decimal,
-    pub outstanding_amount: Decimal,
+    pub subtotal: Money,
+    pub tax_amount: Money,
+    pub discount_amount: Money,
+    pub total_amount: Money,
+    pub paid_amount: Money,
+    pub outstanding_amount: Money,
     pub status: InvoiceStatus,
     pub payment_terms: Option<String>,
     pub notes: Option<String>,
@@ -194,6 +205,7 @@ pub struct CreateVendorRequest {
     pub tax_number: Option<String>,
     pub payment_terms: Option<String>,
     pub currency: String,
+    #[schema(value_type = Option<String>)]
     pub credit_limit: Option<Decimal>,
 }
 
@@ -209,6 +221,7 @@ pub struct UpdateVendorRequest {
     pub payment_terms: Option<String>,
     pub currency: Option<String>,
     pub status: Option<VendorStatus>,
+    #[schema(value_type = Option<String>)]
     pub credit_limit: Option<Decimal>,
     pub is_active: Option<bool>,
 }
@@ -219,7 +232,9 @@ pub struct CreatePurchaseOrderRequest {
     pub order_date: NaiveDate,
     pub expected_delivery_date: Option<NaiveDate>,
     pub delivery_address: Option<serde_json::Value>,
-    pub currency: String,
+    pub currency: Currency,
+    /// Rate into the base currency; the stored spot rate for `order_date` when omitted
+    #[schema(value_type = Option<String>)]
     pub exchange_rate: Option<Decimal>,
     pub notes: Option<String>,
     pub terms_conditions: Option<String>,
@@ -231,8 +246,12 @@ pub struct CreatePurchaseOrderItemRequest {
     pub product_id: Uuid,
     pub description: Option<String>,
     pub quantity_ordered: i32,
+    /// In the order's currency, at no more than its precision
+    #[schema(value_type = String)]
     pub unit_price: Decimal,
+    #[schema(value_type = Option<String>)]
     pub discount_percent: Option<Decimal>,
+    #[schema(value_type = Option<String>)]
     pub tax_percent: Option<Decimal>,
 }
 
@@ -261,6 +280,7 @@ pub struct CreatePurchaseReceiptRequest {
 pub struct CreatePurchaseReceiptItemRequest {
     pub purchase_order_item_id: Uuid,
     pub quantity_received: i32,
+    #[schema(value_type = String)]
     pub unit_cost: Decimal,
     pub quality_status: QualityStatus,
     pub notes: Option<String>,
@@ -271,8 +291,11 @@ pub struct CreatePurchaseReceiptItemRequest {
 pub struct VendorPerformanceReport {
     pub vendor: Vendor,
     pub total_orders: i32,
+    #[schema(value_type = String)]
     pub total_amount: Decimal,
+    #[schema(value_type = String)]
     pub on_time_delivery_rate: Decimal,
+    #[schema(value_type = String)]
     pub quality_rating: Decimal,
     pub average_lead_time: i32, // days
 }
@@ -282,9 +305,12 @@ pub struct PurchaseAnalytics {
     pub period_start: NaiveDate,
     pub period_end: NaiveDate,
     pub total_purchase_orders: i32,
+    #[schema(value_type = String)]
     pub total_purchase_amount: Decimal,
+    #[schema(value_type = String)]
     pub average_order_value: Decimal,
     pub top_vendors: Vec<VendorPerformanceReport>,
     pub pending_orders_count: i32,
+    #[schema(value_type = String)]
     pub pending_orders_value: Decimal,
 }
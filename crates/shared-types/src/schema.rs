@@ -0,0 +1,167 @@
+//! OpenAPI schemas for the generic response wrappers.
+//!
+//! utoipa can't name `ApiResponse<T>` per `T`, so its schema is written out
+//! here with `data` taken from [`DataSchema`]; handlers document success
+//! bodies as `inline(ApiResponse<T>)` to get a typed `data`.
+
+use utoipa::{
+    openapi::{
+        schema::{AllOfBuilder, ArrayBuilder, ObjectBuilder, Schema, SchemaType},
+        Ref, RefOr,
+    },
+    ToSchema,
+};
+
+use crate::{ApiResponse, CursorPaginatedResponse, PaginatedResponse};
+
+/// The schema of `T` as `ApiResponse<T>::data`.
+pub trait DataSchema {
+    /// `None` when there is no data, as for `ApiResponse<()>`.
+    fn data_schema() -> Option<RefOr<Schema>>;
+}
+
+/// [`DataSchema`] for types with their own component schema, referenced by
+/// name. The component still has to be listed in the `OpenApi` derive.
+#[macro_export]
+macro_rules! data_schema {
+    ($($ty:ty),* $(,)?) => {$(
+        impl $crate::schema::DataSchema for $ty {
+            fn data_schema() -> Option<utoipa::openapi::RefOr<utoipa::openapi::schema::Schema>> {
+                Some(utoipa::openapi::Ref::from_schema_name(<$ty as utoipa::ToSchema>::schema().0).into())
+            }
+        }
+    )*};
+}
+
+impl DataSchema for () {
+    fn data_schema() -> Option<RefOr<Schema>> {
+        None
+    }
+}
+
+impl DataSchema for serde_json::Value {
+    fn data_schema() -> Option<RefOr<Schema>> {
+        Some(ObjectBuilder::new().schema_type(SchemaType::Value).into())
+    }
+}
+
+impl<T: DataSchema> DataSchema for Vec<T> {
+    fn data_schema() -> Option<RefOr<Schema>> {
+        Some(ArrayBuilder::new().items(T::data_schema()?).into())
+    }
+}
+
+impl<T: DataSchema> DataSchema for PaginatedResponse<T> {
+    fn data_schema() -> Option<RefOr<Schema>> {
+        Some(page_schema::<T>(Ref::from_schema_name("PaginationMeta").into()))
+    }
+}
+
+impl<T: DataSchema> DataSchema for CursorPaginatedResponse<T> {
+    fn data_schema() -> Option<RefOr<Schema>> {
+        Some(page_schema::<T>(Ref::from_schema_name("CursorMeta").into()))
+    }
+}
+
+/// `{ data: [T], pagination }`, the shape of every list page.
+pub fn page_schema<T: DataSchema>(pagination: RefOr<Schema>) -> RefOr<Schema> {
+    ObjectBuilder::new()
+        .property("data", ArrayBuilder::new().items(T::data_schema().unwrap_or_else(|| ObjectBuilder::new().into())))
+        .required("data")
+        .property("pagination", pagination)
+        .required("pagination")
+        .into()
+}
+
+impl<'s, T: DataSchema> ToSchema<'s> for ApiResponse<T> {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        let mut envelope = ObjectBuilder::new()
+            .description(Some("Standard API response wrapper"))
+            .property("success", ObjectBuilder::new().schema_type(SchemaType::Boolean))
+            .required("success");
+        if let Some(data) = T::data_schema() {
+            envelope = envelope.property("data", AllOfBuilder::new().item(data).nullable(true));
+        }
+        let envelope = envelope
+            .property("message", ObjectBuilder::new().schema_type(SchemaType::String).nullable(true))
+            .property(
+                "errors",
+                ArrayBuilder::new()
+                    .items(Ref::from_schema_name("ValidationError"))
+                    .description(Some("Per-field validation errors"))
+                    .nullable(true),
+            )
+            .property(
+                "code",
+                ObjectBuilder::new()
+                    .schema_type(SchemaType::String)
+                    .description(Some("Business error code such as `INVALID_CREDENTIALS`, when there is one"))
+                    .nullable(true),
+            );
+        ("ApiResponse", envelope.into())
+    }
+}
+
+data_schema!(
+    crate::Account,
+    crate::Attachment,
+    crate::Company,
+    crate::Contact,
+    crate::Conversion,
+    crate::DocumentSequence,
+    crate::EmailBranding,
+    crate::EmailTemplateInfo,
+    crate::ExchangeRate,
+    crate::ImportField,
+    crate::ImportJob,
+    crate::ImportPreview,
+    crate::Invitation,
+    crate::JournalEntry,
+    crate::LeaveRequest,
+    crate::LoginResponse,
+    crate::Notification,
+    crate::NotificationPreference,
+    crate::Product,
+    crate::PurchaseOrder,
+    crate::RenderedEmail,
+    crate::SearchHit,
+    crate::StockLevel,
+    crate::StockMovement,
+    crate::UnreadCount,
+    crate::User,
+    crate::Vendor,
+    crate::Warehouse,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Company;
+
+    fn json<'s, T: ToSchema<'s>>() -> serde_json::Value {
+        serde_json::to_value(T::schema().1).unwrap()
+    }
+
+    #[test]
+    fn data_refers_to_the_payload_schema() {
+        let envelope = json::<ApiResponse<Company>>();
+        assert_eq!(envelope["properties"]["data"]["allOf"][0]["$ref"], "#/components/schemas/Company");
+        assert_eq!(envelope["required"], serde_json::json!(["success"]));
+    }
+
+    #[test]
+    fn pages_and_lists_wrap_the_item_schema() {
+        let page = json::<ApiResponse<PaginatedResponse<Company>>>();
+        let data = &page["properties"]["data"]["allOf"][0];
+        assert_eq!(data["properties"]["data"]["items"]["$ref"], "#/components/schemas/Company");
+        assert_eq!(data["properties"]["pagination"]["$ref"], "#/components/schemas/PaginationMeta");
+
+        let list = json::<ApiResponse<Vec<Company>>>();
+        assert_eq!(list["properties"]["data"]["allOf"][0]["items"]["$ref"], "#/components/schemas/Company");
+    }
+
+    #[test]
+    fn empty_responses_have_no_data() {
+        assert!(json::<ApiResponse<()>>()["properties"].get("data").is_none());
+    }
+}
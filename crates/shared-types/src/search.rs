@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Record types the global search covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchEntity {
+    Company,
+    Contact,
+    Product,
+    Vendor,
+    PurchaseOrder,
+    JournalEntry,
+}
+
+impl SearchEntity {
+    pub const ALL: [SearchEntity; 6] = [
+        SearchEntity::Company,
+        SearchEntity::Contact,
+        SearchEntity::Product,
+        SearchEntity::Vendor,
+        SearchEntity::PurchaseOrder,
+        SearchEntity::JournalEntry,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchEntity::Company => "company",
+            SearchEntity::Contact => "contact",
+            SearchEntity::Product => "product",
+            SearchEntity::Vendor => "vendor",
+            SearchEntity::PurchaseOrder => "purchase_order",
+            SearchEntity::JournalEntry => "journal_entry",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.as_str() == s)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SearchHit {
+    pub entity: SearchEntity,
+    pub id: Uuid,
+    /// Main label, e.g. the product name or PO number
+    pub title: String,
+    /// Secondary label, e.g. the SKU or vendor name
+    pub subtitle: Option<String>,
+    /// Higher is better; only comparable within one response
+    pub rank: f32,
+}
@@ -0,0 +1,77 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A kind of document numbered from a tenant sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentType {
+    JournalEntry,
+    PurchaseOrder,
+}
+
+impl DocumentType {
+    pub const ALL: [DocumentType; 2] = [DocumentType::JournalEntry, DocumentType::PurchaseOrder];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentType::JournalEntry => "journal_entry",
+            DocumentType::PurchaseOrder => "purchase_order",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == s)
+    }
+}
+
+/// When a sequence starts again from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResetRule {
+    /// Every calendar year of the document date; the pattern must contain `{YYYY}`
+    #[default]
+    Yearly,
+    /// Every calendar month; the pattern must contain `{YYYY}` and `{MM}`
+    Monthly,
+    Never,
+}
+
+impl ResetRule {
+    pub const ALL: [ResetRule; 3] = [ResetRule::Yearly, ResetRule::Monthly, ResetRule::Never];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResetRule::Yearly => "yearly",
+            ResetRule::Monthly => "monthly",
+            ResetRule::Never => "never",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.as_str() == s)
+    }
+}
+
+/// How a tenant numbers one document type.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DocumentSequence {
+    pub document_type: DocumentType,
+    /// Literal text with `{YYYY}`, `{MM}` and one `{SEQ:n}` (counter, zero-padded to n digits)
+    #[schema(example = "PO/{YYYY}/{MM}/{SEQ:6}")]
+    pub pattern: String,
+    pub reset_rule: ResetRule,
+    /// The number the next document would get today
+    pub next_number: String,
+    /// Whether the tenant changed the built-in pattern
+    pub customized: bool,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Replaces the pattern and reset rule; issued numbers are kept.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UpdateDocumentSequenceRequest {
+    #[schema(example = "PO/{YYYY}/{MM}/{SEQ:6}")]
+    pub pattern: String,
+    pub reset_rule: ResetRule,
+}
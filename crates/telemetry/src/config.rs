@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+/// Log output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One JSON object per line, carrying `trace_id`/`span_id`
+    Json,
+    /// Human readable output for local development
+    Pretty,
+}
+
+/// Telemetry settings, usually embedded in the service configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    pub service_name: String,
+    pub environment: String,
+    /// OTLP/gRPC collector endpoint, e.g. `http://localhost:4317`. Export is disabled when unset.
+    pub otlp_endpoint: Option<String>,
+    pub log_format: LogFormat,
+    /// Default filter directives, overridden by `RUST_LOG`
+    pub log_filter: String,
+    /// Fraction of root traces to sample, between 0.0 and 1.0
+    pub sample_ratio: f64,
+    /// Add a `tenant_id` label to business counters. Off by default to keep cardinality bounded.
+    #[serde(default)]
+    pub metrics_tenant_labels: bool,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            service_name: "erp-api".to_string(),
+            environment: "development".to_string(),
+            otlp_endpoint: None,
+            log_format: LogFormat::Pretty,
+            log_filter: "info".to_string(),
+            sample_ratio: 1.0,
+            metrics_tenant_labels: false,
+        }
+    }
+}
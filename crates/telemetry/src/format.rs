@@ -0,0 +1,206 @@
+use std::fmt;
+
+use opentelemetry::trace::{SpanId, TraceContextExt, TraceId};
+use serde_json::{Map, Value};
+use tracing::{field::Field, Event, Subscriber};
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::{
+    fmt::{format::Writer, FmtContext, FormatEvent, FormatFields, FormattedFields},
+    registry::{LookupSpan, SpanRef},
+};
+
+/// JSON event formatter that adds the OpenTelemetry `trace_id` and `span_id`
+/// of the current span, so log lines can be joined with exported traces.
+///
+/// Span fields are read from [`FormattedFields`], so the layer must be built
+/// with `.fmt_fields(JsonFields::new())`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceJsonFormat;
+
+impl<S, N> FormatEvent<S, N> for TraceJsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let meta = event.metadata();
+        let mut fields = Map::new();
+        event.record(&mut JsonVisitor(&mut fields));
+
+        let mut line = Map::new();
+        line.insert(
+            "timestamp".into(),
+            chrono::Utc::now()
+                .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+                .into(),
+        );
+        line.insert("level".into(), meta.level().as_str().into());
+        line.insert("target".into(), meta.target().into());
+        if let Some(message) = fields.remove("message") {
+            line.insert("message".into(), message);
+        }
+        if !fields.is_empty() {
+            line.insert("fields".into(), Value::Object(fields));
+        }
+
+        if let Some(scope) = ctx.event_scope() {
+            let mut span_fields = Map::new();
+            for span in scope.from_root() {
+                if let Some(formatted) = span.extensions().get::<FormattedFields<N>>() {
+                    if let Ok(Value::Object(map)) = serde_json::from_str::<Value>(formatted) {
+                        span_fields.extend(map);
+                    }
+                }
+            }
+            if !span_fields.is_empty() {
+                line.insert("span_fields".into(), Value::Object(span_fields));
+            }
+        }
+
+        if let Some(span) = ctx.lookup_current() {
+            line.insert("span".into(), span.name().into());
+            if let Some((trace_id, span_id)) = otel_ids(&span) {
+                line.insert("trace_id".into(), trace_id.to_string().into());
+                line.insert("span_id".into(), span_id.to_string().into());
+            }
+        }
+
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
+
+/// Trace and span id that the OpenTelemetry layer assigned to `span`.
+fn otel_ids<S>(span: &SpanRef<'_, S>) -> Option<(TraceId, SpanId)>
+where
+    S: for<'a> LookupSpan<'a>,
+{
+    let extensions = span.extensions();
+    let data = extensions.get::<OtelData>()?;
+    let parent = data.parent_cx.span();
+    let trace_id = if data.parent_cx.has_active_span() {
+        parent.span_context().trace_id()
+    } else {
+        data.builder.trace_id?
+    };
+    Some((trace_id, data.builder.span_id?))
+}
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl tracing::field::Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().into(), format!("{:?}", value).into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::{fmt::format::JsonFields, layer::SubscriberExt};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn lines(&self) -> Vec<Value> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|l| serde_json::from_str(l).unwrap())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_json_log_carries_trace_and_span_id() {
+        let provider = TracerProvider::builder().build();
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")))
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .fmt_fields(JsonFields::new())
+                    .event_format(TraceJsonFormat)
+                    .with_writer(move || writer.clone()),
+            );
+
+        let expected = tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("http.request", request_id = "abc");
+            let _entered = span.enter();
+            tracing::info!(user = 7, "hello");
+            let cx = span.context();
+            let span_ref = cx.span();
+            let sc = span_ref.span_context();
+            (sc.trace_id().to_string(), sc.span_id().to_string())
+        });
+
+        let lines = buffer.lines();
+        assert_eq!(lines.len(), 1);
+        let line = &lines[0];
+        assert_eq!(line["message"], "hello");
+        assert_eq!(line["fields"]["user"], 7);
+        assert_eq!(line["span"], "http.request");
+        assert_eq!(line["span_fields"]["request_id"], "abc");
+        assert_eq!(line["trace_id"], expected.0);
+        assert_eq!(line["span_id"], expected.1);
+    }
+
+    #[test]
+    fn test_json_log_outside_span_has_no_trace_id() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .fmt_fields(JsonFields::new())
+                .event_format(TraceJsonFormat)
+                .with_writer(move || writer.clone()),
+        );
+
+        tracing::subscriber::with_default(subscriber, || tracing::warn!("plain"));
+
+        let line = &buffer.lines()[0];
+        assert_eq!(line["level"], "WARN");
+        assert!(line.get("trace_id").is_none());
+    }
+}
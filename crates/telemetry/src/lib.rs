@@ -1,17 +1,102 @@
+pub mod config;
+pub mod format;
+pub mod metrics;
+pub mod propagation;
+pub mod spans;
+
+pub use config::*;
+pub use format::*;
+pub use metrics::*;
+pub use propagation::*;
+pub use spans::*;
+
 use anyhow::Result;
-use opentelemetry::global;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    trace::{self as sdktrace, Sampler},
+    Resource,
+};
+use tracing::Subscriber;
+use tracing_subscriber::{
+    fmt::format::JsonFields, layer::SubscriberExt, registry::LookupSpan, util::SubscriberInitExt,
+    EnvFilter, Layer,
+};
+
+/// Keeps the OpenTelemetry pipeline alive; flushes pending spans when dropped.
+#[must_use = "dropping the guard shuts down trace export"]
+pub struct TelemetryGuard {
+    exporting: bool,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if self.exporting {
+            shutdown_tracing();
+        }
+    }
+}
+
+/// Install the global tracing subscriber: env filter, log output and, when
+/// an OTLP endpoint is configured, span export.
+///
+/// Must be called from within a Tokio runtime when export is enabled.
+pub fn init(config: &TelemetryConfig) -> Result<TelemetryGuard> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let tracer = match &config.otlp_endpoint {
+        Some(endpoint) => Some(otlp_tracer(config, endpoint)?),
+        None => None,
+    };
+    let exporting = tracer.is_some();
 
-pub fn init_tracing(service_name: &str) -> Result<()> {
-    // Minimal tracing init for now; Jaeger/OTel can be added later when needed
     tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| config.log_filter.as_str().into()))
+        .with(fmt_layer(config.log_format))
+        .with(tracer.map(|t| tracing_opentelemetry::layer().with_tracer(t)))
+        .try_init()?;
 
-    Ok(())
+    Ok(TelemetryGuard { exporting })
 }
 
+/// Flush and shut down the global tracer provider.
 pub fn shutdown_tracing() {
     global::shutdown_tracer_provider();
 }
+
+fn otlp_tracer(config: &TelemetryConfig, endpoint: &str) -> Result<sdktrace::Tracer> {
+    let trace_config = sdktrace::config()
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio,
+        ))))
+        .with_resource(Resource::new(vec![
+            KeyValue::new("service.name", config.service_name.clone()),
+            KeyValue::new("deployment.environment", config.environment.clone()),
+        ]));
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(trace_config)
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+
+    Ok(tracer)
+}
+
+fn fmt_layer<S>(format: LogFormat) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    match format {
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .fmt_fields(JsonFields::new())
+            .event_format(TraceJsonFormat)
+            .boxed(),
+        LogFormat::Pretty => tracing_subscriber::fmt::layer().boxed(),
+    }
+}
@@ -0,0 +1,278 @@
+//! Prometheus metrics for the API: HTTP traffic, connection pools, Redis
+//! latency, read cache effectiveness and a handful of business counters.
+//!
+//! Label values are kept to bounded sets (route templates, methods, status
+//! codes, command names). Tenant ids are only attached to business counters
+//! when explicitly enabled, since every tenant adds a new series.
+
+use std::{fmt::Display, future::Future, time::Duration, time::Instant};
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use tracing::Instrument;
+
+use crate::spans::redis_span;
+
+const HTTP_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+const REDIS_BUCKETS: &[f64] = &[0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0];
+
+/// Handle to the metrics registry. Cheap to clone; all clones share the same series.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    tenant_labels: bool,
+    http_requests_total: IntCounterVec,
+    http_request_duration_seconds: HistogramVec,
+    db_pool_connections: IntGaugeVec,
+    db_pool_waiting: IntGauge,
+    redis_command_duration_seconds: HistogramVec,
+    cache_requests_total: IntCounterVec,
+    cache_invalidations_total: IntCounterVec,
+    logins_total: IntCounterVec,
+    failed_logins_total: IntCounter,
+    journal_entries_posted_total: IntCounterVec,
+    stock_movements_total: IntCounterVec,
+    job_queue_depth: IntGaugeVec,
+}
+
+impl Metrics {
+    /// Create and register all metrics. With `tenant_labels` the business
+    /// counters carry an extra `tenant_id` label.
+    pub fn new(tenant_labels: bool) -> prometheus::Result<Self> {
+        let registry = Registry::new();
+        let tenant: &[&str] = if tenant_labels { &["tenant_id"] } else { &[] };
+
+        let http_requests_total = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["route", "method", "status"],
+        )?;
+        let http_request_duration_seconds = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency")
+                .buckets(HTTP_BUCKETS.to_vec()),
+            &["route", "method", "status"],
+        )?;
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections by state"),
+            &["state"],
+        )?;
+        let db_pool_waiting = IntGauge::new(
+            "db_pool_waiting",
+            "Requests waiting to acquire a database connection",
+        )?;
+        let redis_command_duration_seconds = HistogramVec::new(
+            HistogramOpts::new("redis_command_duration_seconds", "Redis command latency")
+                .buckets(REDIS_BUCKETS.to_vec()),
+            &["command"],
+        )?;
+        let cache_requests_total = IntCounterVec::new(
+            Opts::new("cache_requests_total", "Read cache lookups by outcome: hit, miss, bypass or error"),
+            &["scope", "result"],
+        )?;
+        let cache_invalidations_total = IntCounterVec::new(
+            Opts::new("cache_invalidations_total", "Read cache scopes dropped after a write"),
+            &["scope"],
+        )?;
+        let logins_total = IntCounterVec::new(
+            Opts::new("logins_total", "Successful logins"),
+            tenant,
+        )?;
+        let failed_logins_total = IntCounter::new("failed_logins_total", "Rejected login attempts")?;
+        let journal_entries_posted_total = IntCounterVec::new(
+            Opts::new("journal_entries_posted_total", "Journal entries written to the ledger"),
+            tenant,
+        )?;
+        let stock_movements_total = IntCounterVec::new(
+            Opts::new("stock_movements_total", "Stock movements recorded"),
+            &[&["movement_type"], tenant].concat(),
+        )?;
+        let job_queue_depth = IntGaugeVec::new(
+            Opts::new("job_queue_depth", "Jobs waiting in background queues"),
+            &["queue"],
+        )?;
+
+        registry.register(Box::new(http_requests_total.clone()))?;
+        registry.register(Box::new(http_request_duration_seconds.clone()))?;
+        registry.register(Box::new(db_pool_connections.clone()))?;
+        registry.register(Box::new(db_pool_waiting.clone()))?;
+        registry.register(Box::new(redis_command_duration_seconds.clone()))?;
+        registry.register(Box::new(cache_requests_total.clone()))?;
+        registry.register(Box::new(cache_invalidations_total.clone()))?;
+        registry.register(Box::new(logins_total.clone()))?;
+        registry.register(Box::new(failed_logins_total.clone()))?;
+        registry.register(Box::new(journal_entries_posted_total.clone()))?;
+        registry.register(Box::new(stock_movements_total.clone()))?;
+        registry.register(Box::new(job_queue_depth.clone()))?;
+
+        Ok(Self {
+            registry,
+            tenant_labels,
+            http_requests_total,
+            http_request_duration_seconds,
+            db_pool_connections,
+            db_pool_waiting,
+            redis_command_duration_seconds,
+            cache_requests_total,
+            cache_invalidations_total,
+            logins_total,
+            failed_logins_total,
+            journal_entries_posted_total,
+            stock_movements_total,
+            job_queue_depth,
+        })
+    }
+
+    /// Record a finished HTTP request. `route` must be the route template
+    /// (e.g. `/api/v1/crm/companies/:id`), never the raw path.
+    pub fn observe_http(&self, route: &str, method: &str, status: u16, elapsed: Duration) {
+        let status = status.to_string();
+        let labels = [route, method, status.as_str()];
+        self.http_requests_total.with_label_values(&labels).inc();
+        self.http_request_duration_seconds
+            .with_label_values(&labels)
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// Update the pool gauges, typically right before a scrape.
+    pub fn set_db_pool(&self, size: u32, idle: usize) {
+        self.db_pool_connections.with_label_values(&["size"]).set(size as i64);
+        self.db_pool_connections.with_label_values(&["idle"]).set(idle as i64);
+    }
+
+    /// Count the caller as waiting for a database connection until the guard drops.
+    pub fn db_wait(&self) -> PoolWaitGuard {
+        self.db_pool_waiting.inc();
+        PoolWaitGuard(self.db_pool_waiting.clone())
+    }
+
+    /// Run a Redis command inside a client span and record its latency.
+    pub async fn time_redis<F: Future>(&self, command: &str, fut: F) -> F::Output {
+        let start = Instant::now();
+        let output = fut.instrument(redis_span(command)).await;
+        self.redis_command_duration_seconds
+            .with_label_values(&[command])
+            .observe(start.elapsed().as_secs_f64());
+        output
+    }
+
+    /// Count a read cache lookup; `result` is `hit`, `miss`, `bypass` or `error`.
+    pub fn record_cache(&self, scope: &str, result: &str) {
+        self.cache_requests_total.with_label_values(&[scope, result]).inc();
+    }
+
+    pub fn record_cache_invalidation(&self, scope: &str) {
+        self.cache_invalidations_total.with_label_values(&[scope]).inc();
+    }
+
+    pub fn record_login(&self, tenant_id: impl Display) {
+        let tenant = tenant_id.to_string();
+        self.logins_total.with_label_values(&self.labels(&[], &tenant)).inc();
+    }
+
+    pub fn record_failed_login(&self) {
+        self.failed_logins_total.inc();
+    }
+
+    pub fn record_journal_entry_posted(&self, tenant_id: impl Display) {
+        let tenant = tenant_id.to_string();
+        self.journal_entries_posted_total
+            .with_label_values(&self.labels(&[], &tenant))
+            .inc();
+    }
+
+    pub fn record_stock_movements(&self, movement_type: &str, tenant_id: impl Display, count: u64) {
+        let tenant = tenant_id.to_string();
+        self.stock_movements_total
+            .with_label_values(&self.labels(&[movement_type], &tenant))
+            .inc_by(count);
+    }
+
+    pub fn set_job_queue_depth(&self, queue: &str, depth: usize) {
+        self.job_queue_depth.with_label_values(&[queue]).set(depth as i64);
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        // Encoding into a Vec only fails on malformed metric families, which
+        // `new` rules out by construction.
+        let _ = TextEncoder::new().encode(&self.registry.gather(), &mut buffer);
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+
+    /// Label values for a business counter, appending the tenant when enabled.
+    fn labels<'a>(&self, values: &[&'a str], tenant_id: &'a str) -> Vec<&'a str> {
+        let mut labels = values.to_vec();
+        if self.tenant_labels {
+            labels.push(tenant_id);
+        }
+        labels
+    }
+}
+
+/// Decrements `db_pool_waiting` when dropped, including on cancellation.
+pub struct PoolWaitGuard(IntGauge);
+
+impl Drop for PoolWaitGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_metrics_use_route_template() {
+        let metrics = Metrics::new(false).unwrap();
+        metrics.observe_http("/api/v1/crm/companies/:id", "GET", 200, Duration::from_millis(12));
+
+        let text = metrics.render();
+        assert!(text.contains(
+            r#"http_requests_total{method="GET",route="/api/v1/crm/companies/:id",status="200"} 1"#
+        ));
+        assert!(text.contains("http_request_duration_seconds_bucket"));
+    }
+
+    #[test]
+    fn test_tenant_labels_are_opt_in() {
+        let metrics = Metrics::new(false).unwrap();
+        metrics.record_login("7f1c");
+        metrics.record_stock_movements("in", "7f1c", 1);
+        let text = metrics.render();
+        assert!(text.contains("logins_total 1"));
+        assert!(text.contains(r#"stock_movements_total{movement_type="in"} 1"#));
+        assert!(!text.contains("tenant_id"));
+
+        let metrics = Metrics::new(true).unwrap();
+        metrics.record_login("7f1c");
+        metrics.record_stock_movements("in", "7f1c", 1);
+        let text = metrics.render();
+        assert!(text.contains(r#"logins_total{tenant_id="7f1c"} 1"#));
+        assert!(text.contains(r#"stock_movements_total{movement_type="in",tenant_id="7f1c"} 1"#));
+    }
+
+    #[test]
+    fn test_cache_counters_by_scope_and_result() {
+        let metrics = Metrics::new(true).unwrap();
+        metrics.record_cache("products", "hit");
+        metrics.record_cache("products", "hit");
+        metrics.record_cache("products", "miss");
+        metrics.record_cache_invalidation("products");
+        let text = metrics.render();
+        assert!(text.contains(r#"cache_requests_total{result="hit",scope="products"} 2"#));
+        assert!(text.contains(r#"cache_requests_total{result="miss",scope="products"} 1"#));
+        assert!(text.contains(r#"cache_invalidations_total{scope="products"} 1"#));
+    }
+
+    #[test]
+    fn test_pool_wait_guard_decrements_on_drop() {
+        let metrics = Metrics::new(false).unwrap();
+        let guard = metrics.db_wait();
+        assert!(metrics.render().contains("db_pool_waiting 1"));
+        drop(guard);
+        assert!(metrics.render().contains("db_pool_waiting 0"));
+    }
+}
@@ -0,0 +1,27 @@
+//! Client spans for outgoing database and cache calls, named after the
+//! OpenTelemetry semantic conventions so backends group them correctly.
+
+use tracing::Span;
+
+/// Span for a single SQL statement, e.g. `db_span("SELECT", "products")`.
+pub fn db_span(operation: &str, table: &str) -> Span {
+    tracing::info_span!(
+        "db.query",
+        otel.name = %format_args!("{} {}", operation, table),
+        otel.kind = "client",
+        db.system = "postgresql",
+        db.operation = operation,
+        db.sql.table = table,
+    )
+}
+
+/// Span for a Redis command or pipeline, e.g. `redis_span("GET")`.
+pub fn redis_span(command: &str) -> Span {
+    tracing::info_span!(
+        "redis.command",
+        otel.name = %format_args!("redis {}", command),
+        otel.kind = "client",
+        db.system = "redis",
+        db.operation = command,
+    )
+}